@@ -24,13 +24,7 @@ pub fn remote_loopback_deploy(
         let test_id = NONCE.fetch_add(1, Ordering::SeqCst);
         let [hi, lo] = test_id.to_be_bytes();
         let address = format!("127.{hi}.{lo}.{host_id}");
-        hosts.push(HostConfig {
-            address,
-            base_port: PORT_BASE,
-            num_cores: cores_per_host,
-            ssh: Default::default(),
-            perf_path: None,
-        });
+        hosts.push(HostConfig::new(address, PORT_BASE, cores_per_host));
     }
 
     let mut join_handles = vec![];
@@ -21,7 +21,7 @@ fn kafka_prod(ctx: &StreamContext, size: u64, topic: &str) {
     ctx.stream_par_iter(0u64..size)
         .batch_mode(BatchMode::timed(1024, Duration::from_millis(100)))
         .map(|x| x.to_ne_bytes())
-        .write_kafka(producer, topic);
+        .write_kafka(producer, topic, |_| "");
 }
 
 fn kafka_consume(ctx: &StreamContext, size: u64, topic: &str) {
@@ -47,6 +47,8 @@ impl Fields {
         self.main.push_str("\n\n");
         self.main
             .push_str(&Self::generate_struct_declarations(self.structs.clone()));
+        self.main
+            .push_str(&Self::generate_key_declarations(&self.streams));
         self.main.push_str("\n\n");
 
         self.main.push_str(
@@ -160,6 +162,26 @@ impl Fields {
         result
     }
 
+    /// Generates the composite key structs of the streams grouped by multiple columns.
+    pub(crate) fn generate_key_declarations(streams: &IndexMap<String, StreamInfo>) -> String {
+        streams
+            .values()
+            .filter_map(|stream| stream.key_struct.as_ref())
+            .fold(String::new(), |mut output, (key_struct, key_types)| {
+                let fields = key_types
+                    .iter()
+                    .map(|key_type| format!("Option<{}>", key_type))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let _ = writeln!(
+                    output,
+                    "renoir::composite_key! {{\nstruct {}({});\n}}\n",
+                    key_struct, fields
+                );
+                output
+            })
+    }
+
     /// Generates the necessary code to handle the result of a subquery.
     pub(crate) fn collect_subquery_result(&mut self, is_single_result: bool) -> (String, String) {
         let stream_name = self.streams.first().unwrap().0.clone();
@@ -486,12 +486,14 @@ impl GroupParser {
             IrParseError::InvalidInput("Missing aggregate function type".to_string())
         })?;
 
-        let function = match func_type.as_str() {
+        let mut function = match func_type.as_str() {
             "max" => AggregateType::Max,
             "min" => AggregateType::Min,
             "avg" => AggregateType::Avg,
             "sum" => AggregateType::Sum,
             "count" => AggregateType::Count,
+            "approx_count_distinct" => AggregateType::ApproxCountDistinct,
+            "approx_percentile" => AggregateType::ApproxPercentile(0.0.into()),
             _ => {
                 return Err(Box::new(IrParseError::InvalidInput(format!(
                     "Invalid aggregate function: {}",
@@ -503,6 +505,7 @@ impl GroupParser {
         let column_ref = inner.next().ok_or_else(|| {
             IrParseError::InvalidInput("Missing column in aggregate function".to_string())
         })?;
+        IrParser::parse_aggregate_param(&mut function, inner.next())?;
 
         let column = if column_ref.as_str() == "*" {
             ColumnRef {
@@ -1,4 +1,5 @@
 // New IrAST structure following Polars approach
+use ordered_float::OrderedFloat;
 use std::sync::Arc;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -131,6 +132,9 @@ pub enum AggregateType {
     Avg,
     Count,
     Sum,
+    ApproxCountDistinct,
+    /// Approximate percentile, between 0 and 1.
+    ApproxPercentile(OrderedFloat<f64>),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -293,6 +297,8 @@ impl std::fmt::Display for AggregateType {
             AggregateType::Avg => write!(f, "avg"),
             AggregateType::Sum => write!(f, "sum"),
             AggregateType::Count => write!(f, "count"),
+            AggregateType::ApproxCountDistinct => write!(f, "approx_count_distinct"),
+            AggregateType::ApproxPercentile(_) => write!(f, "approx_percentile"),
         }
     }
 }
@@ -313,6 +319,9 @@ impl std::fmt::Display for ComplexField {
                 IrLiteral::Boolean(b) => write!(f, "{}", b),
             }
         } else if let Some(ref agg) = self.aggregate {
+            if let AggregateType::ApproxPercentile(q) = agg.function {
+                return write!(f, "approx_percentile({}, {})", agg.column, q);
+            }
             write!(
                 f,
                 "{}({})",
@@ -322,6 +331,8 @@ impl std::fmt::Display for ComplexField {
                     AggregateType::Avg => "avg",
                     AggregateType::Sum => "sum",
                     AggregateType::Count => "count",
+                    AggregateType::ApproxCountDistinct => "approx_count_distinct",
+                    AggregateType::ApproxPercentile(_) => unreachable!(),
                 },
                 agg.column
             )
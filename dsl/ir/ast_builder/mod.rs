@@ -55,6 +55,35 @@ impl IrParser {
 
         IrParser::parse_query(inner_ir)
     }
+
+    /// Parses the optional second argument of an aggregate function: approx_percentile requires
+    /// the percentile, between 0 and 1, while the other functions take no argument.
+    pub(crate) fn parse_aggregate_param(
+        function: &mut AggregateType,
+        param: Option<Pair<Rule>>,
+    ) -> Result<(), Box<IrParseError>> {
+        match (function, param) {
+            (AggregateType::ApproxPercentile(q), Some(param)) => {
+                let value = param.as_str().parse::<f64>().map_err(|_| {
+                    IrParseError::InvalidInput(format!("Invalid percentile: {}", param.as_str()))
+                })?;
+                if !(0.0..=1.0).contains(&value) {
+                    return Err(Box::new(IrParseError::InvalidInput(
+                        "approx_percentile percentile must be between 0 and 1".to_string(),
+                    )));
+                }
+                *q = value.into();
+                Ok(())
+            }
+            (AggregateType::ApproxPercentile(_), None) => Err(Box::new(
+                IrParseError::InvalidInput("approx_percentile requires a percentile".to_string()),
+            )),
+            (_, Some(_)) => Err(Box::new(IrParseError::InvalidInput(
+                "Too many arguments for aggregate function".to_string(),
+            ))),
+            (_, None) => Ok(()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -85,6 +114,24 @@ mod tests {
         assert!(IrParser::parse_query(input).is_ok());
     }
 
+    #[test]
+    fn test_query_with_approx_count_distinct() {
+        let input = "from stream1 in input1 select approx_count_distinct(value)";
+        assert!(IrParser::parse_query(input).is_ok());
+    }
+
+    #[test]
+    fn test_query_with_approx_percentile() {
+        let input = "from stream1 in input1 select approx_percentile(value, 0.95)";
+        assert!(IrParser::parse_query(input).is_ok());
+
+        let input = "from stream1 in input1 select approx_percentile(value)";
+        assert!(IrParser::parse_query(input).is_err());
+
+        let input = "from stream1 in input1 select approx_percentile(value, 2)";
+        assert!(IrParser::parse_query(input).is_err());
+    }
+
     #[test]
     fn test_invalid_query() {
         let input = "invalid query syntax";
@@ -138,7 +138,7 @@ impl ProjectionParser {
     fn parse_aggregate_function(pair: Pair<Rule>) -> Result<AggregateFunction, Box<IrParseError>> {
         let mut agg = pair.into_inner();
 
-        let func = match agg
+        let mut func = match agg
             .next()
             .ok_or_else(|| IrParseError::InvalidInput("Missing aggregate function".to_string()))?
             .as_str()
@@ -150,6 +150,8 @@ impl ProjectionParser {
             "avg" => AggregateType::Avg,
             "sum" => AggregateType::Sum,
             "count" => AggregateType::Count,
+            "approx_count_distinct" => AggregateType::ApproxCountDistinct,
+            "approx_percentile" => AggregateType::ApproxPercentile(0.0.into()),
             unknown => {
                 return Err(Box::new(IrParseError::InvalidInput(format!(
                     "Unknown aggregate function: {}",
@@ -162,6 +164,7 @@ impl ProjectionParser {
             .next()
             .ok_or_else(|| IrParseError::InvalidInput("Missing aggregate field".to_string()))?;
         let col_ref = Self::parse_column_ref(var_pair)?;
+        IrParser::parse_aggregate_param(&mut func, agg.next())?;
 
         Ok(AggregateFunction {
             function: func,
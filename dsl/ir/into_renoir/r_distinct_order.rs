@@ -97,7 +97,7 @@ pub(crate) fn process_distinct_order(stream_name: &String, query_object: &mut Qu
     let order_op = if !order_by.is_empty() {
         if limit.is_none() {
             format!(
-                ".sorted_by({})",
+                ".sorted_global_by({})",
                 generate_sort_code(order_by, final_struct_of)
             )
         } else {
@@ -105,12 +105,18 @@ fn collect_field_aggregates(
                 AggregateType::Avg => {
                     acc_info.add_avg(agg.column.clone(), col_type);
                 }
-                AggregateType::Count => {
+                AggregateType::Count | AggregateType::ApproxCountDistinct => {
                     acc_info.add_aggregate(
                         GroupAccumulatorValue::Aggregate(agg.function.clone(), agg.column.clone()),
                         "usize".to_string(),
                     );
                 }
+                AggregateType::ApproxPercentile(_) => {
+                    acc_info.add_aggregate(
+                        GroupAccumulatorValue::Aggregate(agg.function.clone(), agg.column.clone()),
+                        "f64".to_string(),
+                    );
+                }
                 _ => {
                     acc_info.add_aggregate(
                         GroupAccumulatorValue::Aggregate(agg.function.clone(), agg.column.clone()),
@@ -2,6 +2,7 @@ use crate::dsl::ir::ir_ast_structure::{
     AggregateType, ComplexField, GroupBaseCondition, GroupClause, NullOp,
 };
 use crate::dsl::ir::r_group::r_group_keys::GroupAccumulatorInfo;
+use crate::dsl::ir::r_sink::r_sink_utils::create_approx_percentile_value;
 use crate::dsl::ir::r_utils::convert_literal;
 use crate::dsl::ir::{
     AggregateFunction, BinaryOp, ComparisonOp, ExistsCondition, InCondition, IrLiteral,
@@ -479,13 +480,29 @@ fn process_filter_condition(
                                 };
 
                                 // Generate safety checks if needed
-                                if agg.function != AggregateType::Count {
+                                if !matches!(
+                                    agg.function,
+                                    AggregateType::Count
+                                        | AggregateType::ApproxCountDistinct
+                                        | AggregateType::ApproxPercentile(_)
+                                ) {
                                     check_list.push(format!("{}.is_some()", col_access));
                                 }
 
                                 // Generate the appropriate access based on aggregate type
                                 let agg_value = match agg.function {
                                     AggregateType::Count => col_access.to_string(),
+                                    AggregateType::ApproxCountDistinct => {
+                                        format!("{}.estimate()", col_access)
+                                    }
+                                    AggregateType::ApproxPercentile(q) => {
+                                        let estimate = create_approx_percentile_value(
+                                            &col_access,
+                                            q.into_inner(),
+                                        );
+                                        check_list.push(format!("{}.is_some()", estimate));
+                                        format!("{}.unwrap()", estimate)
+                                    }
                                     AggregateType::Max
                                     | AggregateType::Min
                                     | AggregateType::Sum => {
@@ -513,8 +530,16 @@ fn process_filter_condition(
                                     );
                                 }
                                 // Retireve aggregate type
-                                let agg_type = if agg.function == AggregateType::Count {
+                                let agg_type = if matches!(
+                                    agg.function,
+                                    AggregateType::Count | AggregateType::ApproxCountDistinct
+                                ) {
                                     "usize".to_string()
+                                } else if matches!(
+                                    agg.function,
+                                    AggregateType::ApproxPercentile(_)
+                                ) {
+                                    "f64".to_string()
                                 } else {
                                     query_object.get_type(&agg.column)
                                 };
@@ -865,7 +890,12 @@ fn process_filter_field(
             format!("x.1.{}", agg_pos)
         };
 
-        if agg.function != AggregateType::Count {
+        if !matches!(
+            agg.function,
+            AggregateType::Count
+                | AggregateType::ApproxCountDistinct
+                | AggregateType::ApproxPercentile(_)
+        ) {
             check_list.push(format!("{}.is_some()", col_access));
         }
 
@@ -877,6 +907,22 @@ fn process_filter_field(
                     col_access.to_string()
                 }
             }
+            AggregateType::ApproxCountDistinct => {
+                if !cast.is_empty() {
+                    format!("({}.estimate() as {})", col_access, cast)
+                } else {
+                    format!("{}.estimate()", col_access)
+                }
+            }
+            AggregateType::ApproxPercentile(q) => {
+                let estimate = create_approx_percentile_value(&col_access, q.into_inner());
+                check_list.push(format!("{}.is_some()", estimate));
+                if !cast.is_empty() && *cast != "f64" {
+                    format!("({}.unwrap() as {})", estimate, cast)
+                } else {
+                    format!("{}.unwrap()", estimate)
+                }
+            }
             AggregateType::Max | AggregateType::Min | AggregateType::Sum => {
                 if !cast.is_empty() {
                     format!("({}.unwrap() as {})", col_access, cast)
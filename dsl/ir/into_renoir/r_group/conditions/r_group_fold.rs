@@ -1,5 +1,8 @@
 use crate::dsl::ir::ir_ast_structure::AggregateType;
 use crate::dsl::ir::r_group::r_group_keys::{GroupAccumulatorInfo, GroupAccumulatorValue};
+use crate::dsl::ir::r_sink::r_sink_utils::{
+    create_approx_count_distinct_update, create_approx_percentile_update,
+};
 use crate::dsl::ir::{AggregateFunction, QueryObject};
 use indexmap::IndexMap;
 
@@ -49,6 +52,16 @@ pub(crate) fn create_fold_operation(
                         tuple_types.push(val_type.clone());
                         tuple_inits.push("0.0".to_string());
                     }
+                    AggregateType::ApproxCountDistinct => {
+                        // The sketches of the replicas are merged in the global fold
+                        tuple_types.push("renoir::operator::HyperLogLog".to_string());
+                        tuple_inits.push("renoir::operator::HyperLogLog::default()".to_string());
+                    }
+                    AggregateType::ApproxPercentile(_) => {
+                        // The digests of the replicas are merged in the global fold
+                        tuple_types.push("renoir::operator::TDigest".to_string());
+                        tuple_inits.push("renoir::operator::TDigest::default()".to_string());
+                    }
                 }
 
                 // Generate update code
@@ -342,6 +355,66 @@ pub(crate) fn create_fold_operation(
                                     ),
                                 );
                             }
+                            AggregateType::ApproxCountDistinct => {
+                                update_code.push_str(&create_approx_count_distinct_update(
+                                    &col_access,
+                                    &col_type,
+                                    &acc_access,
+                                ));
+                                global_update_code.push_str(&format!(
+                                    "    {}.merge(&local_acc{});\n",
+                                    acc_access,
+                                    if single_agg {
+                                        "".to_string()
+                                    } else {
+                                        format!(".{}", pos)
+                                    }
+                                ));
+                                agg_map.insert(
+                                    AggregateFunction {
+                                        column: col.clone(),
+                                        function: AggregateType::ApproxCountDistinct,
+                                    },
+                                    format!(
+                                        "x.1{}",
+                                        if single_agg {
+                                            String::from("")
+                                        } else {
+                                            format!(".{}", pos)
+                                        }
+                                    ),
+                                );
+                            }
+                            AggregateType::ApproxPercentile(_) => {
+                                update_code.push_str(&create_approx_percentile_update(
+                                    &col_access,
+                                    &col_type,
+                                    &acc_access,
+                                ));
+                                global_update_code.push_str(&format!(
+                                    "    {}.merge(&local_acc{});\n",
+                                    acc_access,
+                                    if single_agg {
+                                        "".to_string()
+                                    } else {
+                                        format!(".{}", pos)
+                                    }
+                                ));
+                                agg_map.insert(
+                                    AggregateFunction {
+                                        column: col.clone(),
+                                        function: agg_type.clone(),
+                                    },
+                                    format!(
+                                        "x.1{}",
+                                        if single_agg {
+                                            String::from("")
+                                        } else {
+                                            format!(".{}", pos)
+                                        }
+                                    ),
+                                );
+                            }
                             AggregateType::Avg => {} // Handled through Sum and Count
                         }
                     }
@@ -106,7 +106,16 @@ pub(crate) fn process_group_by(
     }
 
     // Generate GROUP BY operation
-    let group_by_keys = process_group_by_keys(keys, query_object);
+    let (group_by_fields, key_types) = process_group_by_keys(keys, query_object);
+    // Multiple columns are grouped in a composite key struct, accessed by position like a tuple
+    let group_by_keys = if key_types.len() > 1 {
+        let key_struct = key_struct_name(stream_name);
+        let stream = query_object.get_mut_stream(stream_name);
+        stream.key_struct = Some((key_struct.clone(), key_types));
+        format!("{}({})", key_struct, group_by_fields)
+    } else {
+        group_by_fields
+    };
 
     // Process having conditions if present
     let mut acc_info = GroupAccumulatorInfo::new();
@@ -126,8 +135,13 @@ pub(crate) fn process_group_by(
                         GroupAccumulatorValue::Aggregate(agg.function.clone(), agg.column.clone());
                     if agg.function == AggregateType::Avg {
                         acc_info.add_avg(agg.column.clone(), "f64".to_string());
-                    } else if agg.function == AggregateType::Count {
+                    } else if matches!(
+                        agg.function,
+                        AggregateType::Count | AggregateType::ApproxCountDistinct
+                    ) {
                         acc_info.add_aggregate(agg_value, "usize".to_string());
+                    } else if matches!(agg.function, AggregateType::ApproxPercentile(_)) {
+                        acc_info.add_aggregate(agg_value, "f64".to_string());
                     } else {
                         acc_info.add_aggregate(agg_value, col_type);
                     }
@@ -152,7 +166,14 @@ pub(crate) fn process_group_by(
             query_object,
         ));
     } else {
-        group_string_keys.push_str(&format!(".group_by(|x| ({}))", group_by_keys));
+        // if the stream comes from a keyed join, change its key instead of dropping it
+        let stream = query_object.get_mut_stream(stream_name);
+        if stream.join_key_dropped {
+            stream.op_chain.pop();
+            group_string_keys.push_str(&format!(".rekey(|_, x| ({}))", group_by_keys));
+        } else {
+            group_string_keys.push_str(&format!(".group_by(|x| ({}))", group_by_keys));
+        }
     }
 
     // Store the operation in the correct stream
@@ -179,9 +200,14 @@ pub(crate) fn process_group_by(
 ///
 /// # Returns
 ///
-/// A String containing the tuple of column references for group by
-fn process_group_by_keys(columns: &[ColumnRef], query_object: &mut QueryObject) -> String {
+/// A String containing the column references for group by, separated by commas, and the types
+/// of the key columns
+fn process_group_by_keys(
+    columns: &[ColumnRef],
+    query_object: &mut QueryObject,
+) -> (String, Vec<String>) {
     let mut index:usize = 0;
+    let mut key_types = Vec::new();
     if !query_object.has_join {
         let stream_name = query_object
             .streams
@@ -204,6 +230,7 @@ fn process_group_by_keys(columns: &[ColumnRef], query_object: &mut QueryObject)
                 };
                 check_column_validity(col, &col_stream, query_object);
                 let needs_casting = stream.get_field_type(&col.column) == "f64";
+                key_types.push(key_type(&stream.get_field_type(&col.column)));
                 format!(
                     "x.{}.clone(){}",
                     col.column,
@@ -225,7 +252,7 @@ fn process_group_by_keys(columns: &[ColumnRef], query_object: &mut QueryObject)
             .zip(0..columns.len())
             .collect::<Vec<_>>();
 
-        final_string
+        (final_string, key_types)
     } else {
         // With joins - need to handle tuple access
         let final_string = columns
@@ -257,6 +284,7 @@ fn process_group_by_keys(columns: &[ColumnRef], query_object: &mut QueryObject)
                 stream.check_if_column_exists(&col.column);
 
                 let needs_casting = stream.get_field_type(&col.column) == "f64";
+                key_types.push(key_type(&stream.get_field_type(&col.column)));
 
                 let stream_access = stream.get_access().get_base_path();
 
@@ -279,6 +307,42 @@ fn process_group_by_keys(columns: &[ColumnRef], query_object: &mut QueryObject)
             .collect::<Vec<_>>()
             .join(", ");
 
-        final_string
+        (final_string, key_types)
+    }
+}
+
+/// Type of a column in the group by key: floats are wrapped in `OrderedFloat` to be hashable.
+fn key_type(col_type: &str) -> String {
+    if col_type == "f64" {
+        "OrderedFloat<f64>".to_string()
+    } else {
+        col_type.to_string()
+    }
+}
+
+/// Name of the composite key struct of a stream, in CamelCase like the other Rust types:
+/// `stream_1` becomes `KeyStream1`.
+fn key_struct_name(stream_name: &str) -> String {
+    let camel: String = stream_name
+        .split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    format!("Key{}", camel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::key_struct_name;
+
+    #[test]
+    fn key_struct_name_is_camel_case() {
+        assert_eq!(key_struct_name("stream0"), "KeyStream0");
+        assert_eq!(key_struct_name("stream_1"), "KeyStream1");
     }
 }
@@ -85,7 +85,7 @@ pub(crate) fn process_join(
     }
     // Construct the join operation string
     let join_op = format!(
-        ".{}({}, |x| ({}), |y| ({})).drop_key()",
+        ".{}({}, |x| ({}), |y| ({}))",
         join_method,
         right_stream,
         left_tuple.join(", "),
@@ -95,6 +95,9 @@ pub(crate) fn process_join(
     // Store the join operation in the left stream
     let stream = query_object.get_mut_stream(left_stream);
     stream.insert_op(join_op);
+    // the key is dropped by its own operator, so that a group by can change it instead
+    stream.insert_op(".drop_key()".to_string());
+    stream.join_key_dropped = true;
 
     let mut final_join_op = String::new();
 
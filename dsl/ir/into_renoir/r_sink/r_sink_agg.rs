@@ -16,8 +16,8 @@ fn create_accumulator_info_from_stream(
     for (agg_func, position) in &stream.agg_position {
         let value = AccumulatorValue::Aggregate(agg_func.function.clone(), agg_func.column.clone());
         let val_type = match agg_func.function {
-            AggregateType::Count => "usize".to_string(),
-            AggregateType::Avg => "f64".to_string(),
+            AggregateType::Count | AggregateType::ApproxCountDistinct => "usize".to_string(),
+            AggregateType::Avg | AggregateType::ApproxPercentile(_) => "f64".to_string(),
             _ => query_object.get_type(&agg_func.column),
         };
 
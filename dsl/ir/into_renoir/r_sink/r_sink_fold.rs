@@ -1,5 +1,8 @@
 use crate::dsl::ir::ir_ast_structure::{ComplexField, ProjectionColumn};
-use crate::dsl::ir::r_sink::r_sink_utils::{AccumulatorInfo, AccumulatorValue};
+use crate::dsl::ir::r_sink::r_sink_utils::{
+    create_approx_count_distinct_update, create_approx_percentile_update,
+    create_approx_percentile_value, AccumulatorInfo, AccumulatorValue,
+};
 use crate::dsl::ir::{AggregateType, ColumnRef, IrLiteral};
 use crate::dsl::struct_object::object::QueryObject;
 ///
@@ -49,7 +52,7 @@ pub(crate) fn create_aggregate_map(
 
                     acc_info.add_avg(agg.column.clone(), col_type);
                 }
-                AggregateType::Count => {
+                AggregateType::Count | AggregateType::ApproxCountDistinct => {
                     acc_info.add_value(
                         AccumulatorValue::Aggregate(agg.function.clone(), agg.column.clone()),
                         "usize".to_string(),
@@ -106,12 +109,18 @@ fn collect_aggregates_from_complex_field(
             AggregateType::Avg => {
                 acc_info.add_avg(agg.column.clone(), query_object.get_type(&agg.column));
             }
-            AggregateType::Count => {
+            AggregateType::Count | AggregateType::ApproxCountDistinct => {
                 acc_info.add_value(
                     AccumulatorValue::Aggregate(agg.function.clone(), agg.column.clone()),
                     "usize".to_string(),
                 );
             }
+            AggregateType::ApproxPercentile(_) => {
+                acc_info.add_value(
+                    AccumulatorValue::Aggregate(agg.function.clone(), agg.column.clone()),
+                    "f64".to_string(),
+                );
+            }
             _ => {
                 acc_info.add_value(
                     AccumulatorValue::Aggregate(agg.function.clone(), agg.column.clone()),
@@ -173,6 +182,16 @@ fn create_fold(
                         tuple_inits.push("None".to_string());
                         tuple_types.push("Option<f64>".to_string());
                     }
+                    AggregateType::ApproxCountDistinct => {
+                        // The sketch is turned into the estimate in the .map()
+                        tuple_inits.push("renoir::operator::HyperLogLog::default()".to_string());
+                        tuple_types.push("renoir::operator::HyperLogLog".to_string());
+                    }
+                    AggregateType::ApproxPercentile(_) => {
+                        // The digest is turned into the estimate in the .map()
+                        tuple_inits.push("renoir::operator::TDigest::default()".to_string());
+                        tuple_types.push("renoir::operator::TDigest".to_string());
+                    }
                 }
             }
         }
@@ -305,6 +324,20 @@ fn create_fold(
                             }
                         ));
                     }
+                    AggregateType::ApproxCountDistinct => {
+                        update_code.push_str(&create_approx_count_distinct_update(
+                            &col_access,
+                            &col_type,
+                            &format!("acc{}", index_acc),
+                        ));
+                    }
+                    AggregateType::ApproxPercentile(_) => {
+                        update_code.push_str(&create_approx_percentile_update(
+                            &col_access,
+                            &col_type,
+                            &format!("acc{}", index_acc),
+                        ));
+                    }
                     AggregateType::Avg => {} // Handled through Sum and Count
                 }
             }
@@ -404,6 +437,47 @@ pub(crate) fn create_map(
                             }
                         )
                     }
+                    AggregateType::ApproxCountDistinct => {
+                        let pos = acc_info
+                            .value_positions
+                            .get(&AccumulatorValue::Aggregate(
+                                agg.function.clone(),
+                                agg.column.clone(),
+                            ))
+                            .unwrap()
+                            .0;
+                        format!(
+                            "Some(x{}{}.estimate())",
+                            if is_grouped { ".1" } else { "" },
+                            if is_single_acc {
+                                String::new()
+                            } else {
+                                format!(".{}", pos)
+                            }
+                        )
+                    }
+                    AggregateType::ApproxPercentile(q) => {
+                        let pos = acc_info
+                            .value_positions
+                            .get(&AccumulatorValue::Aggregate(
+                                agg.function.clone(),
+                                agg.column.clone(),
+                            ))
+                            .unwrap()
+                            .0;
+                        create_approx_percentile_value(
+                            &format!(
+                                "x{}{}",
+                                if is_grouped { ".1" } else { "" },
+                                if is_single_acc {
+                                    String::new()
+                                } else {
+                                    format!(".{}", pos)
+                                }
+                            ),
+                            q.into_inner(),
+                        )
+                    }
                     _ => {
                         let pos = acc_info
                             .value_positions
@@ -751,7 +825,7 @@ fn process_complex_field_for_map(
                     }
                 )
             }
-            AggregateType::Count => {
+            AggregateType::Count | AggregateType::ApproxCountDistinct => {
                 let pos = acc_info
                     .value_positions
                     .get(&AccumulatorValue::Aggregate(
@@ -760,22 +834,51 @@ fn process_complex_field_for_map(
                     ))
                     .unwrap()
                     .0;
+                // the approximate count is the estimate of the sketch
+                let estimate = if agg.function == AggregateType::ApproxCountDistinct {
+                    ".estimate()"
+                } else {
+                    ""
+                };
 
                 if !cast.is_empty() {
                     format!(
-                        "(x{}{} as {})",
+                        "(x{}{}{} as {})",
                         if is_keyed { ".1" } else { "" },
                         if is_single_acc {
                             "".to_string()
                         } else {
                             format!(".{}", pos)
                         },
+                        estimate,
                         cast
                     )
                 }
                 // Count doesn't need a safety check as it's always available
                 else {
                     format!(
+                        "x{}{}{}",
+                        if is_keyed { ".1" } else { "" },
+                        if is_single_acc {
+                            "".to_string()
+                        } else {
+                            format!(".{}", pos)
+                        },
+                        estimate
+                    )
+                }
+            }
+            AggregateType::ApproxPercentile(q) => {
+                let pos = acc_info
+                    .value_positions
+                    .get(&AccumulatorValue::Aggregate(
+                        agg.function.clone(),
+                        agg.column.clone(),
+                    ))
+                    .unwrap()
+                    .0;
+                let estimate = create_approx_percentile_value(
+                    &format!(
                         "x{}{}",
                         if is_keyed { ".1" } else { "" },
                         if is_single_acc {
@@ -783,7 +886,15 @@ fn process_complex_field_for_map(
                         } else {
                             format!(".{}", pos)
                         }
-                    )
+                    ),
+                    q.into_inner(),
+                );
+                check_list.push(format!("{}.is_some()", estimate));
+
+                if !cast.is_empty() && *cast != "f64" {
+                    format!("({}.unwrap() as {})", estimate, cast)
+                } else {
+                    format!("{}.unwrap()", estimate)
                 }
             }
             _ => {
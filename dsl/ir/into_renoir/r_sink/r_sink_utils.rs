@@ -49,6 +49,49 @@ impl AccumulatorInfo {
     }
 }
 
+// Generate the code inserting the value of a column into the HyperLogLog sketch of an
+// APPROX_COUNT_DISTINCT accumulator. Floats are not hashable, so their bits are inserted.
+pub(crate) fn create_approx_count_distinct_update(
+    col_access: &str,
+    col_type: &str,
+    acc_access: &str,
+) -> String {
+    format!(
+        "    if let Some(val) = &{} {{ {}.insert({}); }}\n",
+        col_access,
+        acc_access,
+        if col_type == "f64" {
+            "&val.to_bits()"
+        } else {
+            "val"
+        }
+    )
+}
+
+// Generate the code inserting the value of a numeric column into the t-digest of an
+// APPROX_PERCENTILE accumulator.
+pub(crate) fn create_approx_percentile_update(
+    col_access: &str,
+    col_type: &str,
+    acc_access: &str,
+) -> String {
+    let value = match col_type {
+        "f64" => "val",
+        "i64" | "usize" => "val as f64",
+        _ => panic!("APPROX_PERCENTILE requires a numeric column, got {}", col_type),
+    };
+    format!(
+        "    if let Some(val) = {} {{ {}.insert({}); }}\n",
+        col_access, acc_access, value
+    )
+}
+
+// Generate the code estimating the percentile q (an Option<f64>) from the t-digest of an
+// APPROX_PERCENTILE accumulator. The debug format keeps the decimal point of round values.
+pub(crate) fn create_approx_percentile_value(acc_access: &str, q: f64) -> String {
+    format!("{}.quantile({:?})", acc_access, q)
+}
+
 // Recursive function to check for aggregates in ComplexField
 pub(crate) fn has_aggregate_in_complex_field(field: &ComplexField) -> bool {
     // Check if this field has an aggregate
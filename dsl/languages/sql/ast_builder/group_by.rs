@@ -486,10 +486,12 @@ impl GroupByParser {
             }
             Rule::aggregate_expr => {
                 let mut agg = pair.into_inner();
-                let aggregate = match agg.next().unwrap().as_str() {
+                let mut aggregate = match agg.next().unwrap().as_str() {
                     "SUM" => AggregateFunction::Sum,
                     "AVG" => AggregateFunction::Avg,
                     "COUNT" => AggregateFunction::Count,
+                    "APPROX_COUNT_DISTINCT" => AggregateFunction::ApproxCountDistinct,
+                    "APPROX_PERCENTILE" => AggregateFunction::ApproxPercentile(0.0),
                     "MIN" => AggregateFunction::Min,
                     "MAX" => AggregateFunction::Max,
                     _ => {
@@ -500,6 +502,7 @@ impl GroupByParser {
                 };
 
                 let column = Self::parse_column_ref(agg.next().unwrap())?;
+                SqlParser::parse_aggregate_param(&mut aggregate, agg.next())?;
 
                 if column.column == '*'.to_string() && aggregate != AggregateFunction::Count {
                     return Err(Box::new(SqlParseError::InvalidInput(
@@ -703,7 +706,7 @@ impl GroupByParser {
             })),
             Rule::aggregate_expr => {
                 let mut agg = factor.into_inner();
-                let func = match agg
+                let mut func = match agg
                     .next()
                     .ok_or_else(|| {
                         SqlParseError::InvalidInput("Missing aggregate function".to_string())
@@ -716,6 +719,8 @@ impl GroupByParser {
                     "MIN" => AggregateFunction::Min,
                     "SUM" => AggregateFunction::Sum,
                     "COUNT" => AggregateFunction::Count,
+                    "APPROX_COUNT_DISTINCT" => AggregateFunction::ApproxCountDistinct,
+                    "APPROX_PERCENTILE" => AggregateFunction::ApproxPercentile(0.0),
                     "AVG" => AggregateFunction::Avg,
                     _ => {
                         return Err(Box::new(SqlParseError::InvalidInput(
@@ -727,6 +732,7 @@ impl GroupByParser {
                 let col_ref = Self::parse_column_ref(agg.next().ok_or_else(|| {
                     SqlParseError::InvalidInput("Missing aggregate column".to_string())
                 })?)?;
+                SqlParser::parse_aggregate_param(&mut func, agg.next())?;
 
                 Ok(ArithmeticExpr::Aggregate(func, col_ref))
             }
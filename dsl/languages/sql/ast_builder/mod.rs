@@ -16,6 +16,7 @@ pub use sql_ast_structure::SqlAST;
 
 use crate::dsl::languages::sql::ast_builder::builder::SqlASTBuilder;
 use crate::dsl::languages::sql::ast_builder::error::SqlParseError;
+use crate::dsl::languages::sql::ast_builder::sql_ast_structure::AggregateFunction;
 use pest::Parser;
 use pest_derive::Parser;
 
@@ -56,4 +57,32 @@ impl SqlParser {
         // Parse the inner SQL directly using the main parser
         SqlParser::parse_query(inner_sql)
     }
+
+    /// Parses the optional second argument of an aggregate function: APPROX_PERCENTILE requires
+    /// the percentile, between 0 and 1, while the other functions take no argument.
+    pub(crate) fn parse_aggregate_param(
+        func: &mut AggregateFunction,
+        param: Option<Pair<Rule>>,
+    ) -> Result<(), Box<SqlParseError>> {
+        match (func, param) {
+            (AggregateFunction::ApproxPercentile(q), Some(param)) => {
+                *q = param.as_str().parse::<f64>().map_err(|_| {
+                    SqlParseError::InvalidInput(format!("Invalid percentile: {}", param.as_str()))
+                })?;
+                if !(0.0..=1.0).contains(q) {
+                    return Err(Box::new(SqlParseError::InvalidInput(
+                        "APPROX_PERCENTILE percentile must be between 0 and 1".to_string(),
+                    )));
+                }
+                Ok(())
+            }
+            (AggregateFunction::ApproxPercentile(_), None) => Err(Box::new(
+                SqlParseError::InvalidInput("APPROX_PERCENTILE requires a percentile".to_string()),
+            )),
+            (_, Some(_)) => Err(Box::new(SqlParseError::InvalidInput(
+                "Too many arguments for aggregate function".to_string(),
+            ))),
+            (_, None) => Ok(()),
+        }
+    }
 }
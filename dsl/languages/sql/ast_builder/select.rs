@@ -116,7 +116,7 @@ impl SelectParser {
         pair: Pair<Rule>,
     ) -> Result<(AggregateFunction, ColumnRef), Box<SqlParseError>> {
         let mut agg = pair.into_inner();
-        let func = match agg
+        let mut func = match agg
             .next()
             .ok_or_else(|| SqlParseError::InvalidInput("Missing aggregate function".to_string()))?
             .as_str()
@@ -127,6 +127,8 @@ impl SelectParser {
             "MIN" => AggregateFunction::Min,
             "SUM" => AggregateFunction::Sum,
             "COUNT" => AggregateFunction::Count,
+            "APPROX_COUNT_DISTINCT" => AggregateFunction::ApproxCountDistinct,
+            "APPROX_PERCENTILE" => AggregateFunction::ApproxPercentile(0.0),
             "AVG" => AggregateFunction::Avg,
             _ => {
                 return Err(Box::new(SqlParseError::InvalidInput(
@@ -139,6 +141,7 @@ impl SelectParser {
             .next()
             .ok_or_else(|| SqlParseError::InvalidInput("Missing aggregate column".to_string()))?;
         let col_ref = Self::parse_column_ref(var_pair)?;
+        SqlParser::parse_aggregate_param(&mut func, agg.next())?;
 
         //if aggregation is different than COUNT and column is *, return error
         if func != AggregateFunction::Count && col_ref.column == "*" {
@@ -38,6 +38,9 @@ pub enum AggregateFunction {
     Avg,
     Count,
     Sum,
+    ApproxCountDistinct,
+    /// Approximate percentile, between 0 and 1.
+    ApproxPercentile(f64),
 }
 
 #[derive(Debug, PartialEq, Clone)]
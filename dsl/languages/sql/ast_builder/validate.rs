@@ -145,6 +145,8 @@ fn check_arithmetic_for_aggregates(expr: &ArithmeticExpr) -> Result<(), Box<SqlP
                     AggregateFunction::Avg => "AVG",
                     AggregateFunction::Sum => "SUM",
                     AggregateFunction::Count => "COUNT",
+                    AggregateFunction::ApproxCountDistinct => "APPROX_COUNT_DISTINCT",
+                    AggregateFunction::ApproxPercentile(_) => "APPROX_PERCENTILE",
                 },
                 col_ref
             ))));
@@ -416,7 +416,7 @@ impl ConditionParser {
             })),
             Rule::aggregate_expr => {
                 let mut agg = factor.into_inner();
-                let func = match agg
+                let mut func = match agg
                     .next()
                     .ok_or_else(|| {
                         SqlParseError::InvalidInput("Missing aggregate function".to_string())
@@ -429,6 +429,8 @@ impl ConditionParser {
                     "MIN" => AggregateFunction::Min,
                     "SUM" => AggregateFunction::Sum,
                     "COUNT" => AggregateFunction::Count,
+                    "APPROX_COUNT_DISTINCT" => AggregateFunction::ApproxCountDistinct,
+                    "APPROX_PERCENTILE" => AggregateFunction::ApproxPercentile(0.0),
                     "AVG" => AggregateFunction::Avg,
                     _ => {
                         return Err(Box::new(SqlParseError::InvalidInput(
@@ -440,6 +442,7 @@ impl ConditionParser {
                 let col_ref = Self::parse_column_ref(agg.next().ok_or_else(|| {
                     SqlParseError::InvalidInput("Missing aggregate column".to_string())
                 })?)?;
+                SqlParser::parse_aggregate_param(&mut func, agg.next())?;
 
                 Ok(ArithmeticExpr::Aggregate(func, col_ref))
             }
@@ -42,16 +42,7 @@ impl SqlToIr {
             .map(|select_clause| {
                 let selection_str = match &select_clause.selection {
                     SelectType::Simple(col_ref) => col_ref.to_string(),
-                    SelectType::Aggregate(func, col_ref) => {
-                        let agg = match func {
-                            AggregateFunction::Max => "max",
-                            AggregateFunction::Min => "min",
-                            AggregateFunction::Sum => "sum",
-                            AggregateFunction::Avg => "avg",
-                            AggregateFunction::Count => "count",
-                        };
-                        format!("{}({})", agg, col_ref)
-                    }
+                    SelectType::Aggregate(func, col_ref) => Self::aggregate_to_string(func, col_ref),
                     SelectType::ArithmeticExpr(expr) => {
                         Self::arithmetic_expr_to_string(expr, index, nested_index)
                     }
@@ -321,6 +312,22 @@ impl SqlToIr {
         }
     }
 
+    /// Converts an aggregate function call to its string representation in IR format.
+    fn aggregate_to_string(func: &AggregateFunction, col_ref: &ColumnRef) -> String {
+        let agg = match func {
+            AggregateFunction::Max => "max",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Avg => "avg",
+            AggregateFunction::Count => "count",
+            AggregateFunction::ApproxCountDistinct => "approx_count_distinct",
+            AggregateFunction::ApproxPercentile(q) => {
+                return format!("approx_percentile({}, {})", col_ref, q)
+            }
+        };
+        format!("{}({})", agg, col_ref)
+    }
+
     /// Converts an arithmetic expression to its string representation in IR format.
     fn arithmetic_expr_to_string(
         expr: &ArithmeticExpr,
@@ -335,16 +342,7 @@ impl SqlToIr {
                 SqlLiteral::String(val) => format!("'{}'", val),
                 SqlLiteral::Boolean(val) => val.to_string(),
             },
-            ArithmeticExpr::Aggregate(func, col_ref) => {
-                let agg = match func {
-                    AggregateFunction::Max => "max",
-                    AggregateFunction::Min => "min",
-                    AggregateFunction::Sum => "sum",
-                    AggregateFunction::Avg => "avg",
-                    AggregateFunction::Count => "count",
-                };
-                format!("{}({})", agg, col_ref)
-            }
+            ArithmeticExpr::Aggregate(func, col_ref) => Self::aggregate_to_string(func, col_ref),
             ArithmeticExpr::NestedExpr(left, op, right, is_parenthesized) => {
                 let left_str = Self::arithmetic_expr_to_string(left, index, nested_index);
                 let right_str = Self::arithmetic_expr_to_string(right, index, nested_index);
@@ -403,18 +401,8 @@ impl SqlToIr {
                     } else if cond.left_field.column.is_some() {
                         cond.left_field.column.as_ref().unwrap().to_string()
                     } else if cond.left_field.aggregate.is_some() {
-                        let aggregate = match cond.left_field.aggregate.as_ref().unwrap().0 {
-                            AggregateFunction::Max => "max",
-                            AggregateFunction::Min => "min",
-                            AggregateFunction::Sum => "sum",
-                            AggregateFunction::Avg => "avg",
-                            AggregateFunction::Count => "count",
-                        };
-                        format!(
-                            "{}({})",
-                            aggregate,
-                            cond.left_field.aggregate.as_ref().unwrap().1
-                        )
+                        let (func, col_ref) = cond.left_field.aggregate.as_ref().unwrap();
+                        Self::aggregate_to_string(func, col_ref)
                     } else if let Some(ref subquery) = cond.left_field.subquery {
                         format!("({})", Self::convert(subquery, index, nested_index + 1))
                     } else {
@@ -441,18 +429,8 @@ impl SqlToIr {
                     } else if cond.right_field.column.is_some() {
                         cond.right_field.column.as_ref().unwrap().to_string()
                     } else if cond.right_field.aggregate.is_some() {
-                        let aggregate = match cond.right_field.aggregate.as_ref().unwrap().0 {
-                            AggregateFunction::Max => "max",
-                            AggregateFunction::Min => "min",
-                            AggregateFunction::Sum => "sum",
-                            AggregateFunction::Avg => "avg",
-                            AggregateFunction::Count => "count",
-                        };
-                        format!(
-                            "{}({})",
-                            aggregate,
-                            cond.right_field.aggregate.as_ref().unwrap().1
-                        )
+                        let (func, col_ref) = cond.right_field.aggregate.as_ref().unwrap();
+                        Self::aggregate_to_string(func, col_ref)
                     } else if let Some(ref subquery) = cond.right_field.subquery {
                         format!("({})", Self::convert(subquery, index, nested_index + 1))
                     } else {
@@ -507,14 +485,7 @@ impl SqlToIr {
                         } else if let Some(ref column) = field.column {
                             column.to_string()
                         } else if let Some(ref aggregate) = field.aggregate {
-                            let agg_func = match aggregate.0 {
-                                AggregateFunction::Max => "max",
-                                AggregateFunction::Min => "min",
-                                AggregateFunction::Sum => "sum",
-                                AggregateFunction::Avg => "avg",
-                                AggregateFunction::Count => "count",
-                            };
-                            format!("{}({})", agg_func, aggregate.1)
+                            Self::aggregate_to_string(&aggregate.0, &aggregate.1)
                         } else {
                             match &field.value {
                                 Some(SqlLiteral::Float(val)) => format!("{:.2}", val),
@@ -161,13 +161,27 @@ pub(crate) fn process_ir_ast(
     renoir_path: &Option<String>,
     input_tables: &IndexMap<String, (String, String)>,
 ) -> io::Result<String> {
+    //creates a new Rust project if it doesn't exist
+    let rust_project = creation::RustProject::create_empty_project(output_path, renoir_path)?;
+
+    //generates main.rs and updates it in the Rust project
+    let main = generate_main(ir_ast, output_path, input_tables);
+    rust_project.update_main_rs(&main)?;
+
+    //finally compiles the generated binary
+    binary_execution(output_path, rust_project)
+}
+
+/// Generates the content of the main.rs file with the Renoir code corresponding to the IR AST.
+pub(crate) fn generate_main(
+    ir_ast: Arc<IrPlan>,
+    output_path: &str,
+    input_tables: &IndexMap<String, (String, String)>,
+) -> String {
     //creates a new QueryObject and sets the output path
     let mut query_object = QueryObject::new();
     query_object.set_output_path(output_path);
 
-    //creates a new Rust project if it doesn't exist
-    let rust_project = creation::RustProject::create_empty_project(output_path, renoir_path)?;
-
     //opens csvs input, reads column names and data types and creates the struct for each csv file
     let mut tables_info: IndexMap<String, IndexMap<String, String>> = IndexMap::new();
     let mut tables_csv: IndexMap<String, String> = IndexMap::new();
@@ -204,13 +218,59 @@ pub(crate) fn process_ir_ast(
     let structs = query_object.structs.clone();
     let streams = query_object.streams.clone();
     let fields = query_object.get_mut_fields();
-    fields.output_path = output_path.clone();
+    fields.output_path = output_path.to_string();
     fields.fill(structs, streams);
 
-    //generates main.rs and updates it in the Rust project
     fields.fill_main();
-    rust_project.update_main_rs(&fields.main.clone())?;
+    fields.main.clone()
+}
 
-    //finally compiles the generated binary
-    binary_execution(output_path, rust_project)
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use indexmap::IndexMap;
+
+    use super::generate_main;
+    use crate::dsl::ir::query_ir_to_ast;
+    use crate::dsl::languages::sql::sql_parser::sql_to_ir;
+
+    /// Generate the main.rs of a SQL query on the tables `a(id, x)` and `b(id, y)`.
+    fn generate_sql(sql_query: &str) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let mut input_tables = IndexMap::new();
+        for (table, column) in [("a", "x"), ("b", "y")] {
+            let path = dir.path().join(format!("{}.csv", table));
+            fs::write(&path, format!("id,{}\n1,2\n", column)).unwrap();
+            let path = path.to_str().unwrap().to_string();
+            input_tables.insert(table.to_string(), (path, "i64,i64".to_string()));
+        }
+        let output_path = dir.path().to_str().unwrap();
+        generate_main(
+            query_ir_to_ast(&sql_to_ir(sql_query)),
+            output_path,
+            &input_tables,
+        )
+    }
+
+    #[test]
+    fn group_by_after_join_rekeys() {
+        let main = generate_sql("SELECT a.x FROM a JOIN b ON a.id = b.id GROUP BY a.x");
+        assert!(main.contains(
+            ".join(stream1, |x| (x.id.clone()), |y| (y.id.clone())).rekey(|_, x| (x.0.x.clone()))"
+        ));
+        // the key of the group by is dropped before writing the output
+        assert!(main.contains("}).drop_key() .write_csv("));
+        assert_eq!(main.matches(".drop_key()").count(), 1);
+    }
+
+    #[test]
+    fn join_drops_key_before_other_operators() {
+        let main =
+            generate_sql("SELECT a.x FROM a JOIN b ON a.id = b.id WHERE b.y > 1 GROUP BY a.x");
+        assert!(main
+            .contains(".join(stream1, |x| (x.id.clone()), |y| (y.id.clone())).drop_key().filter("));
+        assert!(main.contains(".group_by(|x| (x.0.x.clone()))"));
+        assert!(!main.contains(".rekey("));
+    }
 }
@@ -661,8 +661,12 @@ impl QueryObject {
                     };
 
                     let col_type = match agg_func.function {
-                        AggregateType::Count => "usize".to_string(),
-                        AggregateType::Avg => "f64".to_string(),
+                        AggregateType::Count | AggregateType::ApproxCountDistinct => {
+                            "usize".to_string()
+                        }
+                        AggregateType::Avg | AggregateType::ApproxPercentile(_) => {
+                            "f64".to_string()
+                        }
                         _ => self.get_type(&agg_func.column),
                     };
 
@@ -779,8 +783,8 @@ impl QueryObject {
                 check_column_validity(&agg.column, &stream_name, self);
             }
             match agg.function {
-                AggregateType::Count => "usize".to_string(),
-                AggregateType::Avg => "f64".to_string(),
+                AggregateType::Count | AggregateType::ApproxCountDistinct => "usize".to_string(),
+                AggregateType::Avg | AggregateType::ApproxPercentile(_) => "f64".to_string(),
                 _ => self.get_type(&agg.column),
             }
         } else if let Some((_, ref result_type)) = field.subquery_vec {
@@ -15,6 +15,8 @@ pub struct StreamInfo {
     pub access: AccessPath, // Access path for tuple
     pub is_keyed: bool, // Whether the stream is keyed
     pub key_columns: Vec<(ColumnRef, usize)>, // Key columns and position
+    pub key_struct: Option<(String, Vec<String>)>, // Composite key struct name and field types
+    pub join_key_dropped: bool, // Whether the last operator drops the key of a join
     pub op_chain: Vec<String>, // Operator chain
     pub final_struct: IndexMap<String, IndexMap<String, String>>, // key: final_struct name, value: struct
     pub join_tree: Option<JoinTree>, // Join tree
@@ -56,6 +58,8 @@ impl StreamInfo {
             },
             is_keyed: false,
             key_columns: Vec::new(),
+            key_struct: None,
+            join_key_dropped: false,
             op_chain: Vec::new(),
             final_struct: IndexMap::new(),
             join_tree: None,
@@ -71,6 +75,7 @@ impl StreamInfo {
     }
 
     pub(crate) fn insert_op(&mut self, op: String) {
+        self.join_key_dropped = false;
         self.op_chain.push(op);
     }
 
@@ -46,7 +46,7 @@ async fn main() {
 
     ctx.stream_par_iter(0..200)
         .map(|x| format!("{x:08X}"))
-        .write_kafka(producer, "test1");
+        .write_kafka(producer, "test1", |x| x.clone());
 
     ctx.execute().await;
 }
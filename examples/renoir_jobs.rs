@@ -0,0 +1,76 @@
+//! Manage the jobs running on the worker daemons of the standalone cluster mode.
+//!
+//! ```text
+//! renoir_jobs list <daemon>...
+//! renoir_jobs status <job id> <daemon>...
+//! renoir_jobs cancel <job id> <daemon>...
+//! ```
+//!
+//! The daemons are given as `address:port`. If they have a secret, it's read from the
+//! `RENOIR_DAEMON_SECRET` environment variable.
+use renoir::cluster::{cancel_job, list_jobs, JobInfo};
+
+fn usage() -> ! {
+    eprintln!("usage: renoir_jobs list <daemon>...");
+    eprintln!("       renoir_jobs status <job id> <daemon>...");
+    eprintln!("       renoir_jobs cancel <job id> <daemon>...");
+    std::process::exit(2);
+}
+
+fn print_job(daemon: &str, job: &JobInfo, with_progress: bool) {
+    println!(
+        "{}\thost {}\t{daemon}\trunning for {:.0?}",
+        job.job_id, job.host_id, job.elapsed
+    );
+    if !with_progress {
+        return;
+    }
+    for block in &job.progress {
+        println!(
+            "    block {} [{}]: {} in, {} out, {}/{} replicas running",
+            block.block_id,
+            block.operators.join(" -> "),
+            block.items_in,
+            block.items_out,
+            block.running,
+            block.replicas
+        );
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, job_id, daemons) = match args.first().map(String::as_str) {
+        Some("list") if args.len() > 1 => ("list", None, &args[1..]),
+        Some(command @ ("status" | "cancel")) if args.len() > 2 => {
+            (command, Some(args[1].as_str()), &args[2..])
+        }
+        _ => usage(),
+    };
+
+    let secret = std::env::var("RENOIR_DAEMON_SECRET").ok();
+    let secret = secret.as_deref();
+    let mut failed = false;
+    for daemon in daemons {
+        let result = match job_id {
+            Some(job_id) if command == "cancel" => cancel_job(daemon.as_str(), secret, job_id)
+                .map(|killed| println!("{daemon}: cancelled {killed} workers of {job_id}")),
+            job_id => list_jobs(daemon.as_str(), secret).map(|jobs| {
+                for job in jobs {
+                    if job_id.map_or(true, |id| id == job.job_id) {
+                        print_job(daemon, &job, job_id.is_some());
+                    }
+                }
+            }),
+        };
+        if let Err(e) = result {
+            eprintln!("{daemon}: {e}");
+            failed = true;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,34 @@
+//! A worker daemon of the standalone cluster mode.
+//!
+//! Run it on each host, then set the `daemon_port` of the hosts in the configuration file of the
+//! jobs:
+//!
+//! ```text
+//! worker_daemon [port] [work_dir] [address]
+//! ```
+//!
+//! By default it listens only on the loopback interface. To listen on another address it needs a
+//! secret, read from the `RENOIR_DAEMON_SECRET` environment variable, which goes in the
+//! `daemon_secret` of the hosts.
+use renoir::cluster::WorkerDaemon;
+
+fn main() {
+    env_logger::init();
+
+    let mut args = std::env::args().skip(1);
+    let port = args
+        .next()
+        .map(|port| port.parse().expect("invalid port"))
+        .unwrap_or(9400);
+    let mut daemon = WorkerDaemon::new(port);
+    if let Some(work_dir) = args.next() {
+        daemon = daemon.with_work_dir(work_dir);
+    }
+    if let Some(address) = args.next() {
+        daemon = daemon.with_address(address);
+    }
+    if let Ok(secret) = std::env::var("RENOIR_DAEMON_SECRET") {
+        daemon = daemon.with_secret(secret);
+    }
+    daemon.run().expect("the worker daemon failed");
+}
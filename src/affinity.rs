@@ -0,0 +1,206 @@
+//! Placement of the threads of the replicas on the cores of the host.
+
+use std::path::Path;
+
+use crate::config::PinningConfig;
+use crate::network::Coord;
+
+/// Where the NUMA nodes of the host are listed.
+const NUMA_NODES_PATH: &str = "/sys/devices/system/node";
+
+/// The core a replica is pinned to, and the NUMA node its memory is allocated from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Placement {
+    pub core: usize,
+    /// `None` if the replicas are not placed on the NUMA nodes.
+    pub node: Option<usize>,
+}
+
+/// Assign a core to each replica of the host.
+///
+/// The replicas with the same index are placed on the same NUMA node, and the cores of a node are
+/// assigned in turn.
+#[derive(Debug, Clone)]
+pub(crate) struct CorePlacement {
+    /// The id and the cores of each NUMA node.
+    nodes: Vec<(Option<usize>, Vec<usize>)>,
+    /// The number of replicas placed on each node so far.
+    placed: Vec<usize>,
+}
+
+impl CorePlacement {
+    pub fn new(config: &PinningConfig) -> Self {
+        let cores = match &config.cores {
+            Some(cores) => cores.clone(),
+            None => core_affinity::get_core_ids()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|core| core.id)
+                .collect(),
+        };
+        let nodes = if config.numa {
+            numa_nodes(Path::new(NUMA_NODES_PATH))
+                .into_iter()
+                .map(|(id, node)| (id, node.into_iter().filter(|c| cores.contains(c)).collect()))
+                .collect()
+        } else {
+            vec![]
+        };
+        Self::from_nodes(nodes, cores)
+    }
+
+    /// Place the replicas on `nodes`, or on `cores` if none of the nodes has a core.
+    fn from_nodes(nodes: Vec<(usize, Vec<usize>)>, cores: Vec<usize>) -> Self {
+        let mut nodes: Vec<_> = nodes
+            .into_iter()
+            .filter(|(_, node)| !node.is_empty())
+            .map(|(id, node)| (Some(id), node))
+            .collect();
+        if nodes.is_empty() && !cores.is_empty() {
+            nodes.push((None, cores));
+        }
+        if nodes.is_empty() {
+            log::warn!("cannot find the cores of the host, the threads won't be pinned");
+        }
+        Self {
+            placed: vec![0; nodes.len()],
+            nodes,
+        }
+    }
+
+    /// The placement of the replica `coord`, if there is a core for it.
+    pub fn place(&mut self, coord: Coord) -> Option<Placement> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let index = coord.replica_id as usize % self.nodes.len();
+        let (node, cores) = &self.nodes[index];
+        let core = cores[self.placed[index] % cores.len()];
+        self.placed[index] += 1;
+        Some(Placement { core, node: *node })
+    }
+}
+
+/// Pin the current thread to the core of `placement`, and allocate its memory from the node.
+///
+/// The batches sent by a replica are allocated by its thread, and so are the queues of the
+/// channels towards the next block, which grow when the first batches are sent: the replicas with
+/// the same index of two consecutive blocks exchange the items in the memory of their node.
+pub(crate) fn pin_current_thread(placement: Placement) {
+    let core = placement.core;
+    if !core_affinity::set_for_current(core_affinity::CoreId { id: core }) {
+        log::warn!(
+            "cannot pin thread {:?} to core {core}",
+            std::thread::current().name()
+        );
+    }
+    if let Some(node) = placement.node {
+        prefer_node_memory(node);
+    }
+}
+
+/// Allocate the memory of the current thread from `node`, falling back to the other nodes when
+/// it's full.
+#[cfg(target_os = "linux")]
+fn prefer_node_memory(node: usize) {
+    let bits = libc::c_ulong::BITS as usize;
+    let mut mask = vec![0 as libc::c_ulong; node / bits + 1];
+    mask[node / bits] |= 1 << (node % bits);
+    // SAFETY: the kernel reads `maxnode - 1` bits of the mask, which are all allocated
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_set_mempolicy,
+            libc::MPOL_PREFERRED,
+            mask.as_ptr(),
+            mask.len() * bits + 1,
+        )
+    };
+    if res != 0 {
+        log::warn!(
+            "cannot allocate the memory of thread {:?} from NUMA node {node}: {}",
+            std::thread::current().name(),
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn prefer_node_memory(_node: usize) {}
+
+/// The id and the cores of each NUMA node listed in `path`, empty if they cannot be read.
+fn numa_nodes(path: &Path) -> Vec<(usize, Vec<usize>)> {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return vec![];
+    };
+    let mut nodes: Vec<(usize, Vec<usize>)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let id = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("node")?
+                .parse()
+                .ok()?;
+            let cpus = std::fs::read_to_string(entry.path().join("cpulist")).ok()?;
+            Some((id, parse_cpu_list(&cpus)?))
+        })
+        .collect();
+    nodes.sort();
+    nodes
+}
+
+/// Parse a list of cpus in the kernel format, e.g. `0-3,8,10-11`.
+fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cores = vec![];
+    for range in list.trim().split(',').filter(|r| !r.is_empty()) {
+        match range.split_once('-') {
+            Some((start, end)) => cores.extend(start.parse::<usize>().ok()?..=end.parse().ok()?),
+            None => cores.push(range.parse().ok()?),
+        }
+    }
+    Some(cores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_list() {
+        assert_eq!(
+            parse_cpu_list("0-3,8,10-11\n"),
+            Some(vec![0, 1, 2, 3, 8, 10, 11])
+        );
+        assert_eq!(parse_cpu_list(""), Some(vec![]));
+        assert_eq!(parse_cpu_list("0-a"), None);
+    }
+
+    #[test]
+    fn numa_placement() {
+        let nodes = vec![(0, vec![0, 1]), (1, vec![]), (3, vec![2])];
+        let mut placement = CorePlacement::from_nodes(nodes, vec![]);
+        let placed: Vec<_> = (0..3)
+            .flat_map(|block| (0..4).map(move |replica| Coord::new(block, 0, replica)))
+            .map(|coord| placement.place(coord).unwrap())
+            .collect();
+        let cores: Vec<_> = placed.iter().map(|p| p.core).collect();
+        assert_eq!(cores, [0, 2, 1, 2, 0, 2, 1, 2, 0, 2, 1, 2]);
+        let nodes: Vec<_> = placed.iter().map(|p| p.node.unwrap()).collect();
+        assert_eq!(nodes, [0, 3, 0, 3, 0, 3, 0, 3, 0, 3, 0, 3]);
+
+        let mut placement = CorePlacement::from_nodes(vec![], vec![4, 5]);
+        let core = Placement {
+            core: 4,
+            node: None,
+        };
+        assert_eq!(placement.place(Coord::new(0, 0, 7)), Some(core));
+        assert_eq!(
+            placement.place(Coord::new(1, 0, 7)).map(|p| p.core),
+            Some(5)
+        );
+        assert_eq!(
+            CorePlacement::from_nodes(vec![], vec![]).place(Coord::new(0, 0, 0)),
+            None
+        );
+    }
+}
@@ -6,7 +6,8 @@ use std::time::Duration;
 use coarsetime::Instant;
 use parking_lot::Mutex;
 
-use crate::network::{Coord, NetworkMessage, NetworkSender, NetworkTrySendError};
+use crate::checkpoint::CheckpointId;
+use crate::network::{Coord, NetworkMessage, NetworkSender, NetworkTrySendError, CHANNEL_CAPACITY};
 use crate::operator::StreamElement;
 
 /// Which policy to use for batching the messages before sending them.
@@ -31,6 +32,16 @@ pub enum BatchMode {
         max_size: NonZeroUsize,
         interval: Duration,
     },
+    /// The size of the batches is chosen by a feedback controller, separately for each channel,
+    /// between 1 and `max_size`. The size grows while the receiver has a backlog of batches or
+    /// the batches fill well within `latency_target`, and it shrinks when a batch waits longer
+    /// than `latency_target`. A batch is also flushed when its oldest message waited
+    /// `latency_target`. NOTE: As with `Adaptive`, the timer is checked only when a new message
+    /// arrives.
+    Controlled {
+        max_size: NonZeroUsize,
+        latency_target: Duration,
+    },
 
     /// Send each message infdividually
     Single,
@@ -42,6 +53,7 @@ impl BatchMode {
             BatchMode::Fixed(s) => s.get(),
             BatchMode::Adaptive(s, _) => s.get(),
             BatchMode::Timed { max_size, .. } => max_size.get(),
+            BatchMode::Controlled { max_size, .. } => max_size.get(),
             BatchMode::Single => 1,
         }
     }
@@ -50,11 +62,73 @@ impl BatchMode {
         match self {
             BatchMode::Adaptive(_, ts) => Some(*ts),
             BatchMode::Timed { interval, .. } => Some(*interval),
+            BatchMode::Controlled { latency_target, .. } => Some(*latency_target),
             _ => None,
         }
     }
 }
 
+/// The initial size of the batches of `BatchMode::Controlled`, if `max_size` is larger.
+const CONTROLLED_INITIAL_SIZE: usize = 64;
+
+/// Feedback controller of the size of the batches of a channel with `BatchMode::Controlled`.
+///
+/// After each flush the size is halved if the batch waited more than the latency target, and it
+/// is doubled if the receiver has at least half of its queue full (bigger batches reduce its
+/// per-message overhead) or if the batch waited less than half of the latency target.
+#[derive(Debug, Clone)]
+pub(crate) struct BatchController {
+    max_size: usize,
+    latency_target: Duration,
+    /// The current size of the batches.
+    size: usize,
+    /// When the oldest message in the buffer was enqueued.
+    oldest: Option<Instant>,
+}
+
+impl BatchController {
+    pub(crate) fn new(max_size: NonZeroUsize, latency_target: Duration) -> Self {
+        Self {
+            max_size: max_size.get(),
+            latency_target,
+            size: max_size.get().min(CONTROLLED_INITIAL_SIZE),
+            oldest: None,
+        }
+    }
+
+    /// The current size of the batches.
+    #[cfg(test)]
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Record that a message has been enqueued in a buffer of `len` messages, returning whether
+    /// the buffer should be flushed.
+    pub(crate) fn enqueued(&mut self, len: usize) -> bool {
+        let oldest = self.oldest.get_or_insert_with(Instant::now);
+        len >= self.size || oldest.elapsed() > self.latency_target.into()
+    }
+
+    /// Update the size after the buffer has been flushed, with `queue_depth` batches waiting at
+    /// the receiver, if known.
+    pub(crate) fn flushed(&mut self, queue_depth: Option<usize>) {
+        let Some(oldest) = self.oldest.take() else {
+            return;
+        };
+        let waited: Duration = oldest.elapsed().into();
+        self.adjust(waited, queue_depth);
+    }
+
+    fn adjust(&mut self, waited: Duration, queue_depth: Option<usize>) {
+        let backlog = queue_depth.is_some_and(|depth| depth >= CHANNEL_CAPACITY / 2);
+        if waited > self.latency_target {
+            self.size = (self.size / 2).max(1);
+        } else if backlog || waited < self.latency_target / 2 {
+            self.size = (self.size * 2).min(self.max_size);
+        }
+    }
+}
+
 /// A `Batcher` wraps a sender and sends the messages in batches to reduce the network overhead.
 ///
 /// Internally it spawns a new task to handle the timeouts and join it at the end.
@@ -69,6 +143,8 @@ pub(crate) struct BatcherInner<Out: Send + 'static> {
     last_send: Instant,
     /// The coordinate of this block, used for marking the sender of the batch.
     coord: Coord,
+    /// The controller of the batch size, only with `BatchMode::Controlled`.
+    controller: Option<BatchController>,
 
     finished: bool,
 }
@@ -96,6 +172,13 @@ impl<Out: Send + 'static> BatcherInner<Out> {
                     self.flush()
                 }
             }
+            BatchMode::Controlled { .. } => {
+                self.buffer.push(message);
+                let controller = self.controller.as_mut().unwrap();
+                if controller.enqueued(self.buffer.len()) {
+                    self.flush()
+                }
+            }
             BatchMode::Single => {
                 let message = NetworkMessage::new_single(message, self.coord);
                 self.remote_sender.send(message).unwrap();
@@ -117,6 +200,14 @@ impl<Out: Send + 'static> BatcherInner<Out> {
             let message = NetworkMessage::new_batch(batch, self.coord);
             self.remote_sender.send(message).unwrap();
             self.last_send = Instant::now();
+            self.flushed();
+        }
+    }
+
+    /// Give the feedback of a flush to the controller of the batch size, if any.
+    fn flushed(&mut self) {
+        if let Some(controller) = &mut self.controller {
+            controller.flushed(self.remote_sender.queue_depth());
         }
     }
 
@@ -135,6 +226,7 @@ impl<Out: Send + 'static> BatcherInner<Out> {
             match self.remote_sender.try_send(message) {
                 Ok(_) => {
                     self.last_send = Instant::now();
+                    self.flushed();
                     true
                 }
                 Err(NetworkTrySendError::Full(m)) => {
@@ -150,6 +242,13 @@ impl<Out: Send + 'static> BatcherInner<Out> {
         }
     }
 
+    /// Send the barrier of the checkpoint `id`, after the messages in the buffer.
+    pub(crate) fn barrier(&mut self, id: CheckpointId) {
+        self.flush();
+        let message = NetworkMessage::new_barrier(id, self.coord);
+        self.remote_sender.send(message).unwrap();
+    }
+
     /// Tell the batcher that the stream is ended, flush all the remaining messages.
     pub(crate) fn end(&mut self) {
         // Send the remaining messages
@@ -171,6 +270,13 @@ pub(crate) enum Batcher<T: Send + 'static> {
 
 impl<T: Clone + Send + 'static> Batcher<T> {
     pub(crate) fn new(remote_sender: NetworkSender<T>, mode: BatchMode, coord: Coord) -> Self {
+        let controller = match mode {
+            BatchMode::Controlled {
+                max_size,
+                latency_target,
+            } => Some(BatchController::new(max_size, latency_target)),
+            _ => None,
+        };
         match mode {
             BatchMode::Timed { interval, .. } => {
                 let inner = BatcherInner {
@@ -179,6 +285,7 @@ impl<T: Clone + Send + 'static> Batcher<T> {
                     buffer: Default::default(),
                     last_send: Instant::now(),
                     coord,
+                    controller,
                     finished: false,
                 };
                 let inner = Arc::new(Mutex::new(inner));
@@ -220,6 +327,7 @@ impl<T: Clone + Send + 'static> Batcher<T> {
                 buffer: Default::default(),
                 last_send: Instant::now(),
                 coord,
+                controller,
                 finished: false,
             }),
         }
@@ -236,6 +344,12 @@ impl<T: Clone + Send + 'static> Batcher<T> {
             Batcher::Sync { inner, .. } => inner.lock().flush(),
         }
     }
+    pub(crate) fn barrier(&mut self, id: CheckpointId) {
+        match self {
+            Batcher::Unsync(inner) => inner.barrier(id),
+            Batcher::Sync { inner, .. } => inner.lock().barrier(id),
+        }
+    }
     pub(crate) fn end(self) {
         match self {
             Batcher::Unsync(mut inner) => inner.end(),
@@ -274,6 +388,15 @@ impl BatchMode {
         }
     }
 
+    /// Construct a new `BatchMode::Controlled` with the given positive maximum batch size and
+    /// latency target.
+    pub fn controlled(max_size: usize, latency_target: Duration) -> BatchMode {
+        BatchMode::Controlled {
+            max_size: NonZeroUsize::new(max_size).expect("The batch size must be positive"),
+            latency_target,
+        }
+    }
+
     /// Construct a new `BatchMode::Single`.
     pub fn single() -> BatchMode {
         BatchMode::Single
@@ -283,6 +406,7 @@ impl BatchMode {
         match &self {
             BatchMode::Adaptive(_, max_delay) => Some(*max_delay),
             BatchMode::Timed { interval, .. } => Some(*interval),
+            BatchMode::Controlled { latency_target, .. } => Some(*latency_target),
             BatchMode::Fixed(_) | BatchMode::Single => None,
         }
     }
@@ -293,3 +417,39 @@ impl Default for BatchMode {
         BatchMode::adaptive(1024, Duration::from_millis(50))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+    use std::time::Duration;
+
+    use super::BatchController;
+    use crate::network::CHANNEL_CAPACITY;
+
+    fn controller() -> BatchController {
+        BatchController::new(NonZeroUsize::new(256).unwrap(), Duration::from_millis(100))
+    }
+
+    #[test]
+    fn controller_grows_with_slack_or_backlog() {
+        let mut controller = controller();
+        assert_eq!(controller.size(), 64);
+        controller.adjust(Duration::from_millis(10), Some(0));
+        assert_eq!(controller.size(), 128);
+        controller.adjust(Duration::from_millis(80), Some(CHANNEL_CAPACITY));
+        assert_eq!(controller.size(), 256);
+        controller.adjust(Duration::from_millis(10), None);
+        assert_eq!(controller.size(), 256);
+    }
+
+    #[test]
+    fn controller_shrinks_over_latency_target() {
+        let mut controller = controller();
+        controller.adjust(Duration::from_millis(80), Some(0));
+        assert_eq!(controller.size(), 64);
+        for _ in 0..10 {
+            controller.adjust(Duration::from_millis(200), Some(CHANNEL_CAPACITY));
+        }
+        assert_eq!(controller.size(), 1);
+    }
+}
@@ -140,13 +140,14 @@ impl JobGraphGenerator {
                     };
                     let style = match connection.strategy {
                         ConnectionStrategy::OnlyOne => "dotted",
-                        ConnectionStrategy::Random => "solid",
+                        ConnectionStrategy::Random | ConnectionStrategy::RoundRobin => "solid",
                         ConnectionStrategy::GroupBy => "dashed",
                         ConnectionStrategy::All => "bold",
                     };
                     let sublabel = match connection.strategy {
                         ConnectionStrategy::OnlyOne => "only-one",
                         ConnectionStrategy::Random => "shuffle",
+                        ConnectionStrategy::RoundRobin => "rebalance",
                         ConnectionStrategy::GroupBy => "group-by",
                         ConnectionStrategy::All => "broadcast",
                     };
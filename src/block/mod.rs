@@ -1,6 +1,7 @@
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
+use std::time::Duration;
 
 pub use batcher::BatchMode;
 pub(crate) use batcher::*;
@@ -43,6 +44,13 @@ where
     pub(crate) is_only_one_strategy: bool,
     /// The set of requirements that the block imposes on the scheduler.
     pub(crate) scheduling: Scheduling,
+    /// After how long a previous replica that sends nothing is ignored by the watermarks.
+    pub(crate) watermark_idle_timeout: Option<Duration>,
+    /// Whether the following operators are forced in this block, see `Stream::chained`.
+    pub(crate) chained: bool,
+    /// Whether the replicas of this block may steal the batches received by each other, see
+    /// `RuntimeConfig::with_work_stealing`.
+    pub(crate) work_stealing: bool,
 }
 
 impl<OperatorChain> Clone for Block<OperatorChain>
@@ -57,6 +65,9 @@ where
             iteration_ctx: self.iteration_ctx.clone(),
             is_only_one_strategy: self.is_only_one_strategy,
             scheduling: self.scheduling.clone(),
+            watermark_idle_timeout: self.watermark_idle_timeout,
+            chained: self.chained,
+            work_stealing: self.work_stealing,
         }
     }
 }
@@ -78,6 +89,9 @@ where
             iteration_ctx: self.iteration_ctx,
             is_only_one_strategy: false,
             scheduling: self.scheduling,
+            watermark_idle_timeout: self.watermark_idle_timeout,
+            chained: self.chained,
+            work_stealing: self.work_stealing,
         }
     }
 }
@@ -165,6 +179,9 @@ where
             iteration_ctx,
             is_only_one_strategy: false,
             scheduling,
+            watermark_idle_timeout: None,
+            chained: false,
+            work_stealing: false,
         }
     }
 
@@ -203,6 +220,20 @@ pub fn group_by_hash<T: Hash>(item: &T) -> u64 {
     hasher.finish()
 }
 
+/// The number of key groups.
+///
+/// The hash of a key selects its key group, and each replica of a keyed block processes a
+/// contiguous range of key groups. When the number of replicas changes the ranges change, so the
+/// keyed state in a checkpoint can be redistributed to the new replicas. A block with more replicas
+/// than key groups leaves some of them idle.
+pub(crate) const KEY_GROUPS: u64 = 1 << 15;
+
+/// The index of the replica, out of `replicas`, that processes the keys with hash `hash`.
+pub(crate) fn key_group_replica(hash: u64, replicas: usize) -> usize {
+    let key_group = hash % KEY_GROUPS;
+    (key_group * replicas as u64 / KEY_GROUPS) as usize
+}
+
 /// Hasher used for internal hashmaps that have coordinates as keys
 /// (optimized for small keys)
 pub type CoordHasherBuilder = fxhash::FxBuildHasher;
@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::hash::Hash;
 use std::marker::PhantomData;
 
@@ -5,7 +6,7 @@ use nanorand::{tls_rng, Rng};
 
 use crate::operator::{ExchangeData, KeyerFn};
 
-use super::group_by_hash;
+use super::{group_by_hash, key_group_replica};
 
 /// The next strategy used at the end of a block.
 ///
@@ -24,8 +25,16 @@ where
     OnlyOne,
     /// A random replica will receive the message.
     Random,
+    /// The replicas will receive the messages in turn, the value is the index of the next one.
+    RoundRobin(Cell<usize>),
     /// Among the next replica, the one is selected based on the hash of the key of the message.
+    ///
+    /// The hash selects a key group, see [`KEY_GROUPS`](super::KEY_GROUPS).
     GroupBy(IndexFn, PhantomData<Out>),
+    /// The replica is selected by an index computed from the message, modulo the number of
+    /// replicas. Unlike with `GroupBy` the index is not a hash, so small indexes are spread
+    /// among the replicas.
+    Partition(IndexFn, PhantomData<Out>),
     /// Every following replica will receive every message.
     All,
 }
@@ -38,7 +47,9 @@ where
         match self {
             Self::OnlyOne => write!(f, "OnlyOne"),
             Self::Random => write!(f, "Random"),
+            Self::RoundRobin(_) => write!(f, "RoundRobin"),
             Self::GroupBy(_, _) => write!(f, "GroupBy"),
+            Self::Partition(_, _) => write!(f, "Partition"),
             Self::All => write!(f, "All"),
         }
     }
@@ -52,7 +63,9 @@ where
         match self {
             Self::OnlyOne => Self::OnlyOne,
             Self::Random => Self::Random,
+            Self::RoundRobin(next) => Self::RoundRobin(next.clone()),
             Self::GroupBy(idx, _) => Self::GroupBy(idx.clone(), PhantomData),
+            Self::Partition(idx, _) => Self::Partition(idx.clone(), PhantomData),
             Self::All => Self::All,
         }
     }
@@ -86,6 +99,11 @@ impl<Out: ExchangeData> NextStrategy<Out> {
     pub(crate) fn random() -> NextStrategy<Out> {
         NextStrategy::Random
     }
+
+    /// Returns `NextStrategy::RoundRobin` with default `IndexFn`.
+    pub(crate) fn round_robin() -> NextStrategy<Out> {
+        NextStrategy::RoundRobin(Cell::new(0))
+    }
 }
 
 impl<Out: ExchangeData, IndexFn> NextStrategy<Out, IndexFn>
@@ -93,11 +111,66 @@ where
     IndexFn: KeyerFn<u64, Out>,
 {
     /// Compute the index of the replica which this message should be forwarded to.
+    ///
+    /// With `RoundRobin` each call advances the turn, so it must be called once per message.
     pub fn index(&self, message: &Out) -> usize {
         match self {
             NextStrategy::OnlyOne | NextStrategy::All => 0,
             NextStrategy::Random => tls_rng().generate(),
-            NextStrategy::GroupBy(keyer, _) => keyer(message) as usize,
+            NextStrategy::RoundRobin(next) => next.replace(next.get().wrapping_add(1)),
+            NextStrategy::GroupBy(keyer, _) | NextStrategy::Partition(keyer, _) => {
+                keyer(message) as usize
+            }
+        }
+    }
+
+    /// The replica, out of `replicas`, that receives the message whose index is `index`.
+    pub fn replica(&self, index: usize, replicas: usize) -> usize {
+        match self {
+            // the keys are split among the replicas by key group
+            NextStrategy::GroupBy(_, _) => key_group_replica(index as u64, replicas),
+            _ => index % replicas,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replicas<IndexFn: KeyerFn<u64, u64>>(
+        strategy: NextStrategy<u64, IndexFn>,
+        items: std::ops::Range<u64>,
+        replicas: usize,
+    ) -> Vec<usize> {
+        items
+            .map(|item| {
+                let index = strategy.index(&item);
+                strategy.replica(index, replicas)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn partition_small_indexes() {
+        let strategy = NextStrategy::Partition(|item: &u64| *item, PhantomData);
+        assert_eq!(replicas(strategy, 0..8, 4), vec![0, 1, 2, 3, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn group_by_key_groups() {
+        let strategy = NextStrategy::group_by(|item: &u64| *item);
+        let assigned = replicas(strategy, 0..1000, 4);
+        for (key, &replica) in assigned.iter().enumerate() {
+            assert_eq!(replica, key_group_replica(group_by_hash(&(key as u64)), 4));
+        }
+        // the hashes spread the keys among all the replicas
+        for replica in 0..4 {
+            let count = assigned.iter().filter(|&&r| r == replica).count();
+            assert!(
+                count > 150,
+                "replica {replica} got {count} keys out of 1000"
+            );
         }
     }
 }
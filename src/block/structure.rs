@@ -47,6 +47,11 @@ pub struct OperatorStructure {
     pub connections: Vec<Connection>,
     /// The type of the data that comes out of this operator.
     pub out_type: DataType,
+    /// Whether the operator keeps some state that it doesn't save in the checkpoints.
+    ///
+    /// Such an operator would start empty when the job is restored, so a job that contains it
+    /// cannot be checkpointed.
+    pub unsaved_state: bool,
 }
 
 /// The kind of operator: either `Operator`, `Source` or `Sink`.
@@ -97,6 +102,8 @@ pub enum ConnectionStrategy {
     OnlyOne,
     /// A random replica is chosen for sending the data.
     Random,
+    /// The replicas are chosen in turn for sending the data.
+    RoundRobin,
     /// A key-based approach is used for choosing the next replica.
     GroupBy,
     /// All the replicas receive all the elements of the stream.
@@ -164,6 +171,7 @@ impl OperatorStructure {
             receivers: Default::default(),
             connections: Default::default(),
             out_type: DataType::of::<Out>(),
+            unsaved_state: false,
         }
     }
 }
@@ -202,7 +210,10 @@ where
         match strategy {
             NextStrategy::OnlyOne => ConnectionStrategy::OnlyOne,
             NextStrategy::Random => ConnectionStrategy::Random,
-            NextStrategy::GroupBy(_, _) => ConnectionStrategy::GroupBy,
+            NextStrategy::RoundRobin(_) => ConnectionStrategy::RoundRobin,
+            NextStrategy::GroupBy(_, _) | NextStrategy::Partition(_, _) => {
+                ConnectionStrategy::GroupBy
+            }
             NextStrategy::All => ConnectionStrategy::All,
         }
     }
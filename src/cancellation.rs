@@ -0,0 +1,213 @@
+//! Cancellation and pausing of a running job.
+//!
+//! A [`CancellationToken`] taken from the [`StreamContext`](crate::StreamContext) stops the job
+//! cleanly: the sources stop emitting and end their streams as if their input was over, so the
+//! batches in flight are delivered, the operators emit what they have accumulated and the sinks
+//! flush their output. The execution then returns [`ExecutionStatus::Cancelled`].
+//!
+//! A [`PauseHandle`] suspends the job instead: the sources flush their output and stop reading
+//! their input until the job is resumed, while the rest of the pipeline keeps its state.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often a waiting source checks whether the job was cancelled or resumed.
+const CANCELLATION_POLL: Duration = Duration::from_millis(100);
+
+/// Stop a running job, see [`StreamContext::cancellation_token`](crate::StreamContext::cancellation_token).
+///
+/// ```
+/// # use renoir::{ExecutionStatus, StreamContext, RuntimeConfig};
+/// # use renoir::operator::source::GeneratorSource;
+/// let env = StreamContext::new(RuntimeConfig::local(1).unwrap());
+/// let token = env.cancellation_token();
+/// let source = GeneratorSource::new(|i| i, 1000.0);
+/// let result = env.stream(source).collect_count();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(std::time::Duration::from_millis(100));
+///     token.cancel();
+/// });
+/// assert_eq!(env.execute_blocking(), ExecutionStatus::Cancelled);
+/// assert!(result.get().is_some());
+/// ```
+///
+/// **Note**: in a remote environment every host should cancel the job, since each host stops only
+/// its own sources.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    control: Arc<Control>,
+    /// Whether the source holding this copy has flushed its output since the job was paused.
+    pause_flushed: bool,
+}
+
+/// The state of the job shared by the tokens and the pause handles.
+#[derive(Debug, Default)]
+struct Control {
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+}
+
+impl CancellationToken {
+    /// Stop the job: the sources end their streams the next time they are polled.
+    pub fn cancel(&self) {
+        self.control.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Whether the job has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.control.cancelled.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn pause_handle(&self) -> PauseHandle {
+        PauseHandle {
+            control: self.control.clone(),
+        }
+    }
+
+    /// Called by a source before reading its input: `true` if the job has just been paused and the
+    /// source has to flush its output, otherwise wait until the job is not paused.
+    pub(crate) fn hold(&mut self) -> bool {
+        if !self.control.paused.load(Ordering::Acquire) {
+            self.pause_flushed = false;
+            return false;
+        }
+        if !self.pause_flushed {
+            self.pause_flushed = true;
+            return true;
+        }
+        self.wait_resumed();
+        self.pause_flushed = false;
+        false
+    }
+
+    fn wait_resumed(&self) {
+        while self.control.paused.load(Ordering::Acquire) && !self.is_cancelled() {
+            std::thread::sleep(CANCELLATION_POLL);
+        }
+    }
+
+    /// Wait for a message on `rx`, giving up with `None` if the job is cancelled meanwhile.
+    ///
+    /// No message is received while the job is paused.
+    pub(crate) fn recv<T>(&self, rx: &flume::Receiver<T>) -> Option<Result<T, flume::RecvError>> {
+        loop {
+            self.wait_resumed();
+            match rx.recv_timeout(CANCELLATION_POLL) {
+                Ok(t) => return Some(Ok(t)),
+                Err(flume::RecvTimeoutError::Disconnected) => {
+                    return Some(Err(flume::RecvError::Disconnected))
+                }
+                Err(flume::RecvTimeoutError::Timeout) if self.is_cancelled() => return None,
+                Err(flume::RecvTimeoutError::Timeout) => {}
+            }
+        }
+    }
+
+    /// How the execution ended, once all the workers are done.
+    pub(crate) fn status(&self) -> ExecutionStatus {
+        if self.is_cancelled() {
+            ExecutionStatus::Cancelled
+        } else {
+            ExecutionStatus::Completed
+        }
+    }
+}
+
+/// Suspend a running job, see [`StreamContext::pause_handle`](crate::StreamContext::pause_handle).
+///
+/// ```
+/// # use renoir::{StreamContext, RuntimeConfig};
+/// # use renoir::operator::source::IteratorSource;
+/// let env = StreamContext::new(RuntimeConfig::local(1).unwrap());
+/// let handle = env.pause_handle();
+/// let result = env.stream(IteratorSource::new(0..100)).collect_count();
+/// // the job starts paused and waits for a thread to resume it
+/// handle.pause();
+/// let resumer = handle.clone();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(std::time::Duration::from_millis(100));
+///     resumer.resume();
+/// });
+/// env.execute_blocking();
+/// assert!(!handle.is_paused());
+/// assert_eq!(result.get(), Some(100));
+/// ```
+///
+/// **Note**: in a remote environment every host should pause the job, since each host suspends
+/// only its own sources.
+#[derive(Clone, Debug)]
+pub struct PauseHandle {
+    control: Arc<Control>,
+}
+
+impl PauseHandle {
+    /// Suspend the job: the sources flush their output and stop reading their input.
+    pub fn pause(&self) {
+        self.control.paused.store(true, Ordering::Release);
+    }
+
+    /// Resume a paused job from where it was suspended.
+    pub fn resume(&self) {
+        self.control.paused.store(false, Ordering::Release);
+    }
+
+    /// Whether the job is paused.
+    pub fn is_paused(&self) -> bool {
+        self.control.paused.load(Ordering::Acquire)
+    }
+}
+
+/// How the execution of a job ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExecutionStatus {
+    /// All the sources reached the end of their input.
+    Completed,
+    /// The job was stopped with a [`CancellationToken`].
+    Cancelled,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recv_until_cancelled() {
+        let token = CancellationToken::default();
+        let (tx, rx) = flume::unbounded();
+        tx.send(1).unwrap();
+        assert_eq!(token.recv(&rx), Some(Ok(1)));
+        assert_eq!(token.status(), ExecutionStatus::Completed);
+
+        token.clone().cancel();
+        assert_eq!(token.recv(&rx), None);
+        drop(tx);
+        assert_eq!(token.recv(&rx), Some(Err(flume::RecvError::Disconnected)));
+        assert_eq!(token.status(), ExecutionStatus::Cancelled);
+    }
+
+    #[test]
+    fn hold_while_paused() {
+        let mut token = CancellationToken::default();
+        let handle = token.pause_handle();
+        assert!(!token.hold());
+
+        handle.pause();
+        // flush once, then wait
+        assert!(token.hold());
+        let resumer = handle.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            resumer.resume();
+        });
+        assert!(!token.hold());
+        assert!(!handle.is_paused());
+        thread.join().unwrap();
+
+        // a cancelled job is never held
+        handle.pause();
+        assert!(token.hold());
+        token.cancel();
+        assert!(!token.hold());
+    }
+}
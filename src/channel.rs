@@ -111,6 +111,12 @@ impl<T: Send + 'static> Clone for Sender<T> {
 #[derive(Debug)]
 pub struct Receiver<T: Send + 'static>(ReceiverExt<T>);
 
+impl<T: Send + 'static> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
 impl<T: Send + 'static> Sender<T> {
     /// Send a message in the channel, blocking if it's full.
     #[inline]
@@ -122,6 +128,18 @@ impl<T: Send + 'static> Sender<T> {
     pub fn try_send(&self, item: T) -> Result<(), TrySendErrorExt<T>> {
         self.0.try_send(item)
     }
+
+    /// The number of messages waiting in the channel.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no messages waiting in the channel.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 impl<T: Send + 'static> Receiver<T> {
@@ -142,6 +160,18 @@ impl<T: Send + 'static> Receiver<T> {
         self.0.recv_async().await.map_err(RecvError::from)
     }
 
+    /// The number of messages waiting in the channel.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no messages waiting in the channel.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     /// Block until a message is present in the channel and return it when ready.
     ///
     /// If the timeout expires an error is returned.
@@ -172,8 +202,14 @@ impl<T: Send + 'static> Receiver<T> {
 }
 
 /// A wrapper on an unbounded channel sender.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct UnboundedSender<T: Send + 'static>(SenderExt<T>);
+
+impl<T: Send + 'static> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
 /// A wrapper on an unbounded channel receiver.
 #[derive(Debug)]
 pub struct UnboundedReceiver<T: Send + 'static>(pub(crate) ReceiverExt<T>);
@@ -193,6 +229,17 @@ impl<T: Send + 'static> UnboundedReceiver<T> {
         self.0.recv().map_err(RecvError::from)
     }
 
+    /// Like `recv`, but without blocking.
+    #[inline]
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.0.try_recv().map_err(TryRecvError::from)
+    }
+
+    #[inline]
+    pub async fn recv_async(&self) -> Result<T, RecvError> {
+        self.0.recv_async().await.map_err(RecvError::from)
+    }
+
     /// Block until a message is present in the channel and return it when ready.
     ///
     /// If the timeout expires an error is returned.
@@ -0,0 +1,1082 @@
+//! Periodic checkpoints of the state of the operators.
+//!
+//! When the checkpoints are enabled in the [`RuntimeConfig`](crate::RuntimeConfig), a coordinator
+//! on each host periodically asks the sources to inject a _barrier_ in the stream. The barriers
+//! flow with the data: when a block receives the barrier of a checkpoint from one of the previous
+//! replicas, it holds back the messages from that replica until all the other previous replicas
+//! sent the same barrier. At that point the state of the block reflects exactly the messages
+//! before the barriers: the operators save their state, the barrier is forwarded to the next
+//! blocks and the replica writes its snapshot.
+//!
+//! Inside a replica the barrier is a [`StreamElement::FlushBatch`](crate::operator::StreamElement)
+//! emitted while the checkpoint is in progress, so the operators that keep some state save it when
+//! they receive it.
+//!
+//! The snapshot of a replica is stored in `<path>/chk-<id>/<block>-<host>-<replica>.state`. A
+//! checkpoint is completed on a host when all its replicas have written their snapshot, and the
+//! coordinator marks it by creating the file `_COMPLETED-<host>` in the directory of the
+//! checkpoint: when all the hosts created their file the checkpoint is completed, and it can be
+//! restored. The replicas that have ended don't take part in the following checkpoints.
+//!
+//! **Note**: the sources that do not support the barriers, and the blocks inside an iteration, take
+//! part in a checkpoint only when they end.
+//!
+//! **Note**: only the aggregations of [`Stream::fold_assoc`](crate::Stream::fold_assoc) and
+//! [`Stream::group_by_fold`](crate::Stream::group_by_fold) save their state. The other operators
+//! that keep some state, like the windows, the joins, the iterations, the `rich_map` family and
+//! the other folds, cannot save it: they would start empty when the job is restored, so the
+//! execution of a job that contains them panics before starting if the checkpoints are enabled.
+//!
+//! The [`DeliveryGuarantee`] set in the config selects whether the barriers are aligned
+//! (exactly-once) or not (at-least-once), and when the transactional sinks commit their output.
+//!
+//! ## Savepoints
+//!
+//! A savepoint is a checkpoint taken on demand with a [`SavepointTrigger`], for example before
+//! stopping a long-running job to upgrade it. A new execution of the same job graph can then start
+//! from the state saved in any completed checkpoint or savepoint with
+//! [`CheckpointConfig::restore_from`]: the operators restore their state, and the sources that
+//! support it resume from where they were when the checkpoint was taken.
+//!
+//! ## Rescaling
+//!
+//! The new execution may use a different parallelism, changing the number of replicas of some
+//! blocks. The keyed state of the aggregations is partitioned in key groups (see
+//! [`group_by_hash`](crate::group_by_hash)): each replica of a keyed block processes a contiguous
+//! range of them, so after a rescaling each new replica restores the entries of the keys in its
+//! range from the snapshots of all the previous replicas. The other kinds of state cannot be
+//! redistributed: restoring them with a different number of replicas fails, so the parallelism of
+//! the sources and of the non-keyed aggregations must stay the same.
+//!
+//! The local pre-aggregations that `fold_assoc` and `group_by_fold` run before the shuffle do not
+//! save any state: before forwarding a barrier they send their partial results downstream, where
+//! they are saved by the global aggregation. For this reason their blocks, like the blocks without
+//! any state, can be restored with any number of replicas.
+//!
+//! **Note**: the replication can change only between executions, also for the blocks without any
+//! state, since the job graph is deployed once when the execution starts: to rescale a running
+//! job, take a savepoint, stop it and restore it with the new config.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::block::BlockStructure;
+use crate::channel::{
+    self, RecvTimeoutError, SelectResult, Sender, UnboundedReceiver, UnboundedSender,
+};
+use crate::config::{CheckpointConfig, DeliveryGuarantee};
+use crate::network::Coord;
+use crate::operator::DataKey;
+use crate::scheduler::{BlockId, ExecutionMetadata, HostId};
+use crate::state::StateMap;
+
+/// The identifier of a checkpoint. The first checkpoint has id 1.
+pub type CheckpointId = u64;
+
+/// How often the coordinator checks whether the other hosts completed the checkpoints completed
+/// on its host.
+const CONFIRM_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The name of the directory with the snapshots of a checkpoint.
+fn checkpoint_dir(path: &Path, id: CheckpointId) -> PathBuf {
+    path.join(format!("chk-{id}"))
+}
+
+/// The name of the file with the snapshot of a replica.
+fn replica_file(coord: Coord) -> String {
+    format!(
+        "{}-{}-{}.state",
+        coord.block_id, coord.host_id, coord.replica_id
+    )
+}
+
+/// The coordinates of the replica whose snapshot is stored in the file `name`, if any.
+fn parse_replica_file(name: &str) -> Option<Coord> {
+    let mut ids = name
+        .strip_suffix(".state")?
+        .split('-')
+        .map(|id| id.parse().ok());
+    let coord = Coord::new(ids.next()??, ids.next()??, ids.next()??);
+    ids.next().is_none().then_some(coord)
+}
+
+/// The name of the file that records the last checkpoint of a replica that has ended.
+fn ended_marker(coord: Coord) -> String {
+    format!("ended-{}", replica_file(coord))
+}
+
+/// The name of the file that marks a checkpoint completed on a host.
+fn completed_marker(host_id: HostId) -> String {
+    format!("_COMPLETED-{host_id}")
+}
+
+/// Whether the checkpoint `id` stored in `path` has been completed by all the `num_hosts` hosts.
+fn completed_by_all(path: &Path, id: CheckpointId, num_hosts: usize) -> bool {
+    let dir = checkpoint_dir(path, id);
+    (0..num_hosts as HostId).all(|host| dir.join(completed_marker(host)).exists())
+}
+
+/// The checkpoints stored in `path` that have been completed by all the `num_hosts` hosts, sorted
+/// by id.
+pub(crate) fn completed_checkpoints(
+    path: &Path,
+    num_hosts: usize,
+) -> io::Result<Vec<CheckpointId>> {
+    let mut completed = Vec::new();
+    if !path.exists() {
+        return Ok(completed);
+    }
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(id) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("chk-"))
+            .and_then(|id| id.parse().ok())
+        else {
+            continue;
+        };
+        if completed_by_all(path, id, num_hosts) {
+            completed.push(id);
+        }
+    }
+    completed.sort_unstable();
+    Ok(completed)
+}
+
+/// The last checkpoint stored in `path`, even if not completed.
+fn last_checkpoint(path: &Path) -> CheckpointId {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            name.to_str()?.strip_prefix("chk-")?.parse().ok()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Whether the replica had already ended when the checkpoint `id` was taken.
+fn ended_before(path: &Path, coord: Coord, id: CheckpointId) -> bool {
+    fs::read_to_string(path.join(ended_marker(coord)))
+        .ok()
+        .and_then(|last| last.trim().parse::<CheckpointId>().ok())
+        .is_some_and(|last| last < id)
+}
+
+/// The replicas of the block `block_id` that took part in the checkpoint `id`, sorted: the ones
+/// with a snapshot, and the ones that had already ended.
+fn checkpoint_replicas(path: &Path, id: CheckpointId, block_id: BlockId) -> io::Result<Vec<Coord>> {
+    let mut replicas = Vec::new();
+    for entry in fs::read_dir(checkpoint_dir(path, id))? {
+        if let Some(coord) = entry?.file_name().to_str().and_then(parse_replica_file) {
+            replicas.push(coord);
+        }
+    }
+    for entry in fs::read_dir(path)? {
+        let name = entry?.file_name();
+        let coord = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("ended-"))
+            .and_then(parse_replica_file);
+        if let Some(coord) = coord.filter(|&coord| ended_before(path, coord, id)) {
+            replicas.push(coord);
+        }
+    }
+    replicas.retain(|coord| coord.block_id == block_id);
+    replicas.sort_unstable();
+    replicas.dedup();
+    Ok(replicas)
+}
+
+/// The snapshot of a replica in the checkpoint `id`, used to restore its state.
+///
+/// Returns `None` if the replica had already ended when the checkpoint was taken.
+fn read_snapshot(
+    path: &Path,
+    id: CheckpointId,
+    coord: Coord,
+) -> io::Result<Option<HashMap<usize, Vec<u8>>>> {
+    let file = checkpoint_dir(path, id).join(replica_file(coord));
+    match fs::read(&file) {
+        Ok(bytes) => {
+            let (snapshot, _): (Vec<(usize, Vec<u8>)>, _) =
+                bincode::serde::decode_from_slice(&bytes, bincode::config::standard())
+                    .map_err(io::Error::other)?;
+            Ok(Some(snapshot.into_iter().collect()))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            // the replica ended before the checkpoint, so it didn't write its snapshot
+            if ended_before(path, coord, id) {
+                Ok(None)
+            } else {
+                Err(e)
+            }
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Panics if an operator of the job keeps some state that it doesn't save in the checkpoints.
+///
+/// That operator would start empty when the job is restored, silently losing its state, so the
+/// job is refused when the checkpoints are enabled instead.
+pub(crate) fn assert_saved_state(structures: &[(Coord, BlockStructure)]) {
+    for (coord, structure) in structures {
+        if let Some(operator) = structure.operators.iter().find(|op| op.unsaved_state) {
+            panic!(
+                "The checkpoints cannot save the state of the operator {} in block {}: disable the \
+                checkpoints or remove it from the job",
+                operator.title, coord.block_id
+            );
+        }
+    }
+}
+
+/// A request of a savepoint, answered with its id once completed.
+pub(crate) type SavepointRequest = Sender<Option<CheckpointId>>;
+
+/// Trigger a savepoint, i.e. a checkpoint taken on demand, while the job is running.
+///
+/// It's obtained with [`StreamContext::savepoint_trigger`](crate::StreamContext::savepoint_trigger)
+/// before starting the execution, and it can be sent to another thread.
+///
+/// ## Example
+///
+/// ```no_run
+/// # use renoir::{StreamContext, RuntimeConfig};
+/// # use renoir::config::CheckpointConfig;
+/// # use std::time::Duration;
+/// let checkpoint = CheckpointConfig::new("/tmp/renoir-savepoints").interval(Duration::from_secs(3600));
+/// let env = StreamContext::new(RuntimeConfig::local(4).unwrap().with_checkpoints(checkpoint));
+/// env.stream_generator(|i| i, 1000.0).for_each(std::mem::drop);
+///
+/// let trigger = env.savepoint_trigger();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(Duration::from_secs(10));
+///     let id = trigger.savepoint().expect("savepoint failed");
+///     println!("restart from the savepoint {id}");
+/// });
+/// env.execute_blocking();
+/// ```
+#[derive(Clone, Debug)]
+pub struct SavepointTrigger {
+    requests: UnboundedSender<SavepointRequest>,
+}
+
+impl SavepointTrigger {
+    pub(crate) fn new() -> (Self, UnboundedReceiver<SavepointRequest>) {
+        let (requests, receiver) = channel::unbounded();
+        (Self { requests }, receiver)
+    }
+
+    /// Take a savepoint, blocking until it's completed on this host.
+    ///
+    /// Returns the id of the savepoint, which can be used with
+    /// [`CheckpointConfig::restore_from`], or `None` if the savepoint failed, the checkpoints are
+    /// not enabled or the job has ended. If a checkpoint is already in progress, its id is
+    /// returned when it completes.
+    ///
+    /// **Note**: in a remote environment every host should trigger the savepoint, since the
+    /// replicas of a block wait for the barriers of all the previous replicas.
+    pub fn savepoint(&self) -> Option<CheckpointId> {
+        let (sender, receiver) = channel::bounded(1);
+        self.requests.send(sender).ok()?;
+        receiver.recv().ok().flatten()
+    }
+}
+
+/// What a replica tells to the coordinator of its host.
+#[derive(Debug, Clone)]
+enum ReplicaEvent {
+    /// The replica has written its snapshot of the checkpoint.
+    Completed(Coord, CheckpointId),
+    /// The replica failed to write its snapshot of the checkpoint.
+    Failed(Coord, CheckpointId),
+    /// The replica has ended, it won't take part in the next checkpoints.
+    Finished(Coord),
+}
+
+#[derive(Debug, Default)]
+struct SnapshotState {
+    /// Whether the snapshot of the last started checkpoint is being taken.
+    in_progress: bool,
+    /// The number of operators that registered their state.
+    operators: usize,
+    /// The serialized state of the operators, with the index of the operator.
+    snapshot: Vec<(usize, Vec<u8>)>,
+}
+
+/// The checkpoints of a replica, shared by its operators.
+///
+/// The snapshot starts when the barriers are aligned (or when a source injects a barrier), then
+/// the operators save their state when the barrier passes through them, and finally the worker
+/// writes the snapshot when the barrier has gone through the whole block.
+#[derive(Debug)]
+pub(crate) struct ReplicaCheckpoint {
+    coord: Coord,
+    path: PathBuf,
+    /// The last checkpoint requested by the coordinator.
+    triggered: AtomicU64,
+    /// The last checkpoint started by the replica.
+    started: AtomicU64,
+    /// The last checkpoint completed by all the hosts.
+    completed: AtomicU64,
+    guarantee: DeliveryGuarantee,
+    state: Mutex<SnapshotState>,
+    events: UnboundedSender<ReplicaEvent>,
+    /// The state of the operators to restore.
+    restored: Mutex<Restored>,
+}
+
+/// The state of a replica restored from a checkpoint.
+#[derive(Debug)]
+enum Restored {
+    /// The state of the operators, by index.
+    Snapshot(HashMap<usize, Vec<u8>>),
+    /// The block has a different number of replicas: the state of the operators in the snapshots
+    /// of all the previous replicas, to redistribute to the replica `index` out of `replicas`.
+    Rescaled {
+        snapshots: Vec<HashMap<usize, Vec<u8>>>,
+        index: usize,
+        replicas: usize,
+    },
+    /// The replica had already ended.
+    Ended,
+}
+
+/// The state of an operator restored from a checkpoint.
+#[derive(Debug, PartialEq)]
+enum RestoredState {
+    /// The state saved by the same replica.
+    Snapshot(Vec<u8>),
+    /// The states saved by the previous replicas, to redistribute to the replica `index` out of
+    /// `replicas`.
+    Rescaled {
+        snapshots: Vec<Vec<u8>>,
+        index: usize,
+        replicas: usize,
+    },
+}
+
+impl ReplicaCheckpoint {
+    /// Start the snapshot of the checkpoint `id`, unless it was already started.
+    ///
+    /// Returns `true` if the snapshot has started: the operators will save their state at the next
+    /// `FlushBatch`.
+    pub(crate) fn begin(&self, id: CheckpointId) -> bool {
+        let mut state = self.state.lock();
+        if id <= self.started.load(Ordering::Acquire) {
+            return false;
+        }
+        log::debug!("{} starting checkpoint {id}", self.coord);
+        self.started.store(id, Ordering::Release);
+        state.in_progress = true;
+        state.snapshot.clear();
+        true
+    }
+
+    /// Start the snapshot of the last checkpoint requested by the coordinator, if not already
+    /// started.
+    ///
+    /// This is used by the sources: when it returns `true` they should emit a `FlushBatch`, which is
+    /// the barrier of the checkpoint.
+    pub(crate) fn inject_barrier(&self) -> bool {
+        let triggered = self.triggered.load(Ordering::Acquire);
+        triggered > self.started.load(Ordering::Acquire) && self.begin(triggered)
+    }
+
+    /// The checkpoint whose snapshot is being taken, if any.
+    pub(crate) fn in_progress(&self) -> Option<CheckpointId> {
+        let state = self.state.lock();
+        state
+            .in_progress
+            .then(|| self.started.load(Ordering::Acquire))
+    }
+
+    /// The delivery guarantee of the job.
+    pub(crate) fn guarantee(&self) -> DeliveryGuarantee {
+        self.guarantee
+    }
+
+    /// The last checkpoint completed by all the hosts: the output that precedes its barrier can be
+    /// committed.
+    pub(crate) fn last_completed(&self) -> CheckpointId {
+        self.completed.load(Ordering::Acquire)
+    }
+
+    /// Whether the replica had already ended in the restored checkpoint: its sources should not
+    /// emit anything.
+    pub(crate) fn ended(&self) -> bool {
+        matches!(*self.restored.lock(), Restored::Ended)
+    }
+
+    /// Take the restored state of the operator with index `index`, if any.
+    fn restored(&self, index: usize) -> Option<RestoredState> {
+        match &mut *self.restored.lock() {
+            Restored::Snapshot(snapshot) => snapshot.remove(&index).map(RestoredState::Snapshot),
+            Restored::Rescaled {
+                snapshots,
+                index: replica,
+                replicas,
+            } => {
+                let snapshots = snapshots
+                    .iter_mut()
+                    .filter_map(|snapshot| snapshot.remove(&index))
+                    .collect::<Vec<_>>();
+                (!snapshots.is_empty()).then_some(RestoredState::Rescaled {
+                    snapshots,
+                    index: *replica,
+                    replicas: *replicas,
+                })
+            }
+            Restored::Ended => None,
+        }
+    }
+
+    /// Register an operator with some state, returning its index.
+    fn register(&self) -> usize {
+        let mut state = self.state.lock();
+        state.operators += 1;
+        state.operators - 1
+    }
+
+    /// Save the state of an operator in the snapshot in progress.
+    fn save(&self, index: usize, bytes: Vec<u8>) {
+        let mut state = self.state.lock();
+        if state.in_progress {
+            state.snapshot.push((index, bytes));
+        }
+    }
+
+    /// Write the snapshot in progress, if any, and tell the coordinator.
+    pub(crate) fn complete(&self) {
+        let (id, snapshot) = {
+            let mut state = self.state.lock();
+            if !state.in_progress {
+                return;
+            }
+            state.in_progress = false;
+            let id = self.started.load(Ordering::Acquire);
+            (id, std::mem::take(&mut state.snapshot))
+        };
+        let event = match self.write(id, &snapshot) {
+            Ok(()) => {
+                log::debug!("{} completed checkpoint {id}", self.coord);
+                ReplicaEvent::Completed(self.coord, id)
+            }
+            Err(e) => {
+                log::error!("{} failed to write checkpoint {id}: {e}", self.coord);
+                ReplicaEvent::Failed(self.coord, id)
+            }
+        };
+        // the coordinator may have already ended
+        let _ = self.events.send(event);
+    }
+
+    /// The replica has ended: write the snapshot in progress, if any, and record the last
+    /// checkpoint it took part in.
+    pub(crate) fn finish(&self) {
+        self.complete();
+        let last = self.started.load(Ordering::Acquire);
+        if let Err(e) = fs::create_dir_all(&self.path)
+            .and_then(|_| fs::write(self.path.join(ended_marker(self.coord)), last.to_string()))
+        {
+            log::error!("{} failed to record its end: {e}", self.coord);
+        }
+    }
+
+    fn write(&self, id: CheckpointId, snapshot: &[(usize, Vec<u8>)]) -> io::Result<()> {
+        let dir = checkpoint_dir(&self.path, id);
+        fs::create_dir_all(&dir)?;
+        let bytes = bincode::serde::encode_to_vec(snapshot, bincode::config::standard())
+            .map_err(io::Error::other)?;
+        // write the whole file before making it visible
+        let file = dir.join(replica_file(self.coord));
+        let tmp = file.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(tmp, file)
+    }
+
+    /// Request the checkpoint `id`, it will be started by the sources.
+    fn trigger(&self, id: CheckpointId) {
+        self.triggered.fetch_max(id, Ordering::AcqRel);
+    }
+}
+
+impl Drop for ReplicaCheckpoint {
+    fn drop(&mut self) {
+        let _ = self.events.send(ReplicaEvent::Finished(self.coord));
+    }
+}
+
+/// Merges the states saved by the old replicas into the state of a new replica, given the index
+/// of the new replica and the new number of replicas.
+type RescaleFn<S> = fn(Vec<Vec<u8>>, usize, usize) -> S;
+
+/// The handle used by an operator to save its state `S` in the checkpoints.
+///
+/// The handle is registered in `setup`, then the operator calls `snapshot` when it receives a
+/// `FlushBatch`: this saves the state only if a checkpoint is in progress.
+pub(crate) struct OperatorState<S> {
+    encode: fn(&S) -> Vec<u8>,
+    decode: fn(&[u8]) -> S,
+    /// How to redistribute the state when the number of replicas changes, if it's possible.
+    rescale: Option<RescaleFn<S>>,
+    slot: Option<(Arc<ReplicaCheckpoint>, usize)>,
+}
+
+fn encode<S: Serialize>(state: &S) -> Vec<u8> {
+    bincode::serde::encode_to_vec(state, bincode::config::standard())
+        .expect("failed to serialize the state of the operator")
+}
+
+fn decode<S: DeserializeOwned>(bytes: &[u8]) -> S {
+    bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+        .expect("failed to deserialize the state of the operator")
+        .0
+}
+
+impl<S: Serialize + DeserializeOwned> OperatorState<S> {
+    pub(crate) fn new() -> Self {
+        Self {
+            encode: encode::<S>,
+            decode: decode::<S>,
+            rescale: None,
+            slot: None,
+        }
+    }
+}
+
+impl<K, V> OperatorState<StateMap<K, V>>
+where
+    K: DataKey + Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    /// The handle of a keyed state: when the number of replicas changes, each new replica
+    /// restores the entries of the keys in its key groups.
+    pub(crate) fn keyed() -> Self {
+        Self {
+            rescale: Some(|snapshots, index, replicas| {
+                let snapshots = snapshots.iter().map(|bytes| decode(bytes));
+                StateMap::rescale(snapshots, index, replicas)
+            }),
+            ..Self::new()
+        }
+    }
+}
+
+impl<S> OperatorState<S> {
+    pub(crate) fn setup(&mut self, metadata: &ExecutionMetadata) {
+        self.slot = metadata
+            .checkpoint
+            .as_ref()
+            .map(|checkpoint| (checkpoint.clone(), checkpoint.register()));
+    }
+
+    /// The checkpoints of the replica, if they are enabled.
+    pub(crate) fn replica(&self) -> Option<&ReplicaCheckpoint> {
+        self.slot
+            .as_ref()
+            .map(|(checkpoint, _)| checkpoint.as_ref())
+    }
+
+    /// The state saved in the restored checkpoint, if any. It can be taken only once.
+    pub(crate) fn restore(&self) -> Option<S> {
+        let (checkpoint, index) = self.slot.as_ref()?;
+        match checkpoint.restored(*index)? {
+            RestoredState::Snapshot(bytes) => Some((self.decode)(&bytes)),
+            RestoredState::Rescaled {
+                snapshots,
+                index,
+                replicas,
+            } => {
+                let rescale = self.rescale.unwrap_or_else(|| {
+                    panic!(
+                        "{} cannot redistribute its state {} to {replicas} replicas",
+                        checkpoint.coord,
+                        std::any::type_name::<S>()
+                    )
+                });
+                Some(rescale(snapshots, index, replicas))
+            }
+        }
+    }
+
+    /// Whether the replica had already ended in the restored checkpoint.
+    pub(crate) fn ended(&self) -> bool {
+        self.slot
+            .as_ref()
+            .is_some_and(|(checkpoint, _)| checkpoint.ended())
+    }
+
+    /// Used by the sources, see [`ReplicaCheckpoint::inject_barrier`].
+    pub(crate) fn inject_barrier(&self) -> bool {
+        self.slot
+            .as_ref()
+            .is_some_and(|(checkpoint, _)| checkpoint.inject_barrier())
+    }
+
+    /// Save `state` in the checkpoint in progress, if any.
+    pub(crate) fn snapshot(&self, state: &S) {
+        if let Some((checkpoint, index)) = &self.slot {
+            if checkpoint.in_progress().is_some() {
+                checkpoint.save(*index, (self.encode)(state));
+            }
+        }
+    }
+}
+
+impl<S> Clone for OperatorState<S> {
+    fn clone(&self) -> Self {
+        Self {
+            encode: self.encode,
+            decode: self.decode,
+            rescale: self.rescale,
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+impl<S> std::fmt::Debug for OperatorState<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OperatorState")
+            .field("index", &self.slot.as_ref().map(|(_, index)| index))
+            .finish()
+    }
+}
+
+/// Periodically requests a checkpoint to the replicas of a host, and keeps track of the completed
+/// ones. It also takes the savepoints requested by the [`SavepointTrigger`].
+pub(crate) struct CheckpointCoordinator {
+    config: CheckpointConfig,
+    host_id: HostId,
+    num_hosts: usize,
+    /// The replicas of this host, the coordinator doesn't keep them alive.
+    replicas: Vec<Weak<ReplicaCheckpoint>>,
+    events: UnboundedReceiver<ReplicaEvent>,
+    /// Cloned into each replica, it's dropped when the coordinator starts.
+    events_sender: Option<UnboundedSender<ReplicaEvent>>,
+    /// The replicas that have not ended, with the last checkpoint they completed.
+    running: HashMap<Coord, CheckpointId>,
+    /// The last requested checkpoint.
+    last: CheckpointId,
+    /// The checkpoint that has been requested but not yet completed.
+    pending: Option<CheckpointId>,
+    /// The checkpoints completed on this host.
+    completed: Vec<CheckpointId>,
+    /// The checkpoints completed on this host, but not yet by all the other hosts.
+    unconfirmed: Vec<CheckpointId>,
+    /// The requests of a savepoint, if a trigger was created.
+    requests: Option<UnboundedReceiver<SavepointRequest>>,
+    /// The savepoint requests waiting for the pending checkpoint.
+    waiting: Vec<SavepointRequest>,
+}
+
+impl CheckpointCoordinator {
+    pub(crate) fn new(
+        config: CheckpointConfig,
+        host_id: HostId,
+        num_hosts: usize,
+        requests: Option<UnboundedReceiver<SavepointRequest>>,
+    ) -> Self {
+        let (events_sender, events) = channel::unbounded();
+        // continue the numbering of the previous executions
+        let last = last_checkpoint(&config.path);
+        if let Some(id) = config.restore {
+            // the transactions prepared in the checkpoint are committed when it's restored
+            assert!(
+                completed_by_all(&config.path, id, num_hosts),
+                "cannot restore checkpoint {id}: it was not completed by all the hosts"
+            );
+            log::info!("host {host_id} restoring checkpoint {id}");
+        }
+        Self {
+            config,
+            host_id,
+            num_hosts,
+            replicas: Default::default(),
+            events,
+            events_sender: Some(events_sender),
+            running: Default::default(),
+            last,
+            pending: None,
+            completed: Default::default(),
+            unconfirmed: Default::default(),
+            requests,
+            waiting: Default::default(),
+        }
+    }
+
+    /// Build the checkpoints of a replica of this host, whose block has the replicas `replicas`.
+    pub(crate) fn replica(&mut self, coord: Coord, replicas: &[Coord]) -> Arc<ReplicaCheckpoint> {
+        let restored = match self.config.restore {
+            Some(id) => self.restore(id, coord, replicas).unwrap_or_else(|e| {
+                panic!("{coord} cannot read its snapshot of checkpoint {id}: {e}")
+            }),
+            None => Restored::Snapshot(Default::default()),
+        };
+        let replica = Arc::new(ReplicaCheckpoint {
+            coord,
+            path: self.config.path.clone(),
+            triggered: AtomicU64::new(self.last),
+            started: AtomicU64::new(self.last),
+            completed: AtomicU64::new(0),
+            guarantee: self.config.guarantee,
+            state: Default::default(),
+            events: self.events_sender.clone().unwrap(),
+            restored: Mutex::new(restored),
+        });
+        self.replicas.push(Arc::downgrade(&replica));
+        self.running.insert(coord, self.last);
+        replica
+    }
+
+    /// Read the state of a replica from the checkpoint `id`.
+    ///
+    /// If the block had a different number of replicas, the snapshots of all the previous
+    /// replicas are read, so that the state can be redistributed.
+    fn restore(&self, id: CheckpointId, coord: Coord, replicas: &[Coord]) -> io::Result<Restored> {
+        let path = &self.config.path;
+        let previous = checkpoint_replicas(path, id, coord.block_id)?;
+        let mut current = replicas.to_vec();
+        current.sort_unstable();
+        if previous.is_empty() || previous == current {
+            let snapshot = read_snapshot(path, id, coord)?;
+            return Ok(snapshot.map_or(Restored::Ended, Restored::Snapshot));
+        }
+
+        let snapshots = previous
+            .iter()
+            .map(|&replica| read_snapshot(path, id, replica))
+            .collect::<io::Result<Vec<_>>>()?;
+        if snapshots.iter().all(Option::is_none) {
+            return Ok(Restored::Ended);
+        }
+        log::info!(
+            "{coord} redistributing the state of {} replicas to {}",
+            previous.len(),
+            current.len()
+        );
+        Ok(Restored::Rescaled {
+            snapshots: snapshots.into_iter().flatten().collect(),
+            index: current.binary_search(&coord).unwrap(),
+            replicas: current.len(),
+        })
+    }
+
+    /// Start the coordinator in a new thread, it ends when all the replicas have ended.
+    pub(crate) fn spawn(mut self) -> JoinHandle<()> {
+        self.events_sender = None;
+        std::thread::Builder::new()
+            .name("checkpoint".into())
+            .spawn(move || self.run())
+            .unwrap()
+    }
+
+    fn run(mut self) {
+        let interval = self.config.interval;
+        let mut next_trigger = Instant::now() + interval;
+        loop {
+            let mut timeout = next_trigger.saturating_duration_since(Instant::now());
+            if !self.unconfirmed.is_empty() {
+                timeout = timeout.min(CONFIRM_POLL_INTERVAL);
+            }
+            let event = match self.requests.as_ref() {
+                Some(requests) => match self.events.select_timeout(requests, timeout) {
+                    Ok(SelectResult::A(event)) => event.map_err(|_| RecvTimeoutError::Disconnected),
+                    Ok(SelectResult::B(Ok(request))) => {
+                        self.savepoint(request);
+                        continue;
+                    }
+                    // all the triggers have been dropped
+                    Ok(SelectResult::B(Err(_))) => {
+                        self.requests = None;
+                        continue;
+                    }
+                    Err(e) => Err(e),
+                },
+                None => self.events.recv_timeout(timeout),
+            };
+            match event {
+                Ok(event) => self.handle(event),
+                Err(RecvTimeoutError::Timeout) => {
+                    self.confirm();
+                    if Instant::now() < next_trigger {
+                        continue;
+                    }
+                    // wait for the checkpoint in progress before requesting a new one
+                    if self.pending.is_none() {
+                        self.trigger();
+                    }
+                    next_trigger = Instant::now() + interval;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        log::info!(
+            "host {} completed the checkpoints {:?}",
+            self.host_id,
+            self.completed
+        );
+    }
+
+    /// Take a savepoint, or wait for the checkpoint in progress.
+    fn savepoint(&mut self, request: SavepointRequest) {
+        if self.pending.is_none() {
+            self.trigger();
+        }
+        if self.pending.is_some() {
+            self.waiting.push(request);
+        } else {
+            // all the replicas have ended
+            let _ = request.send(None);
+        }
+    }
+
+    fn trigger(&mut self) {
+        if self.running.is_empty() {
+            return;
+        }
+        // some replicas may have already completed a checkpoint started by another host
+        let last = self.running.values().copied().max().unwrap_or(0);
+        self.last = self.last.max(last) + 1;
+        let id = self.last;
+        log::debug!("host {} requesting checkpoint {id}", self.host_id);
+        self.pending = Some(id);
+        for replica in self.replicas.iter().filter_map(Weak::upgrade) {
+            replica.trigger(id);
+        }
+    }
+
+    fn handle(&mut self, event: ReplicaEvent) {
+        match event {
+            ReplicaEvent::Completed(coord, id) => {
+                if let Some(last) = self.running.get_mut(&coord) {
+                    *last = (*last).max(id);
+                }
+            }
+            ReplicaEvent::Failed(coord, id) => {
+                if self.pending == Some(id) {
+                    log::warn!("checkpoint {id} failed at {coord}");
+                    self.pending = None;
+                    for request in self.waiting.drain(..) {
+                        let _ = request.send(None);
+                    }
+                }
+            }
+            ReplicaEvent::Finished(coord) => {
+                self.running.remove(&coord);
+            }
+        }
+
+        if let Some(id) = self.pending {
+            if self.running.values().all(|&last| last >= id) {
+                self.pending = None;
+                self.complete(id);
+            }
+        }
+    }
+
+    fn complete(&mut self, id: CheckpointId) {
+        let dir = checkpoint_dir(&self.config.path, id);
+        let res = fs::create_dir_all(&dir)
+            .and_then(|_| fs::write(dir.join(completed_marker(self.host_id)), b""));
+        match res {
+            Ok(()) => {
+                log::info!("host {} completed checkpoint {id}", self.host_id);
+                self.completed.push(id);
+                self.unconfirmed.push(id);
+                self.confirm();
+            }
+            Err(e) => log::error!("failed to mark checkpoint {id} as completed: {e}"),
+        }
+        let completed = self.completed.last() == Some(&id);
+        for request in self.waiting.drain(..) {
+            let _ = request.send(completed.then_some(id));
+        }
+    }
+
+    /// Tell the replicas the last checkpoint completed on this host that has been completed by
+    /// all the other hosts too, if any.
+    fn confirm(&mut self) {
+        let Some(pos) = self
+            .unconfirmed
+            .iter()
+            .rposition(|&id| completed_by_all(&self.config.path, id, self.num_hosts))
+        else {
+            return;
+        };
+        let id = self.unconfirmed[pos];
+        self.unconfirmed.drain(..=pos);
+        log::debug!("checkpoint {id} completed by all the hosts");
+        for replica in self.replicas.iter().filter_map(Weak::upgrade) {
+            replica.completed.fetch_max(id, Ordering::AcqRel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::block::{group_by_hash, key_group_replica};
+
+    #[test]
+    fn replica_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CheckpointConfig::new(dir.path()).interval(Duration::from_secs(3600));
+        let mut coordinator = CheckpointCoordinator::new(config, 0, 1, None);
+        let coord = Coord::new(0, 0, 0);
+        let replica = coordinator.replica(coord, &[coord]);
+
+        let index = replica.register();
+        // no checkpoint requested
+        assert!(!replica.inject_barrier());
+        assert_eq!(replica.in_progress(), None);
+
+        replica.trigger(1);
+        assert!(replica.inject_barrier());
+        assert!(!replica.inject_barrier());
+        assert_eq!(replica.in_progress(), Some(1));
+        replica.save(index, vec![42]);
+        replica.complete();
+        assert_eq!(replica.in_progress(), None);
+
+        let file = checkpoint_dir(dir.path(), 1).join(replica_file(coord));
+        let (snapshot, _): (Vec<(usize, Vec<u8>)>, _) = bincode::serde::decode_from_slice(
+            &fs::read(file).unwrap(),
+            bincode::config::standard(),
+        )
+        .unwrap();
+        assert_eq!(snapshot, vec![(index, vec![42])]);
+
+        let event = coordinator.events.recv().unwrap();
+        coordinator.handle(event);
+        coordinator.pending = Some(1);
+        coordinator.handle(ReplicaEvent::Finished(Coord::new(1, 0, 0)));
+        assert_eq!(coordinator.completed, vec![1]);
+        assert_eq!(completed_checkpoints(dir.path(), 1).unwrap(), vec![1]);
+        assert!(completed_checkpoints(dir.path(), 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn confirm_completed_by_all_hosts() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CheckpointConfig::new(dir.path()).interval(Duration::from_secs(3600));
+        let mut coordinator = CheckpointCoordinator::new(config, 0, 2, None);
+        let coord = Coord::new(0, 0, 0);
+        let replica = coordinator.replica(coord, &[coord]);
+
+        replica.trigger(1);
+        assert!(replica.inject_barrier());
+        replica.complete();
+        let event = coordinator.events.recv().unwrap();
+        coordinator.pending = Some(1);
+        coordinator.handle(event);
+        // the other host has not completed the checkpoint yet
+        assert_eq!(coordinator.completed, vec![1]);
+        assert_eq!(replica.last_completed(), 0);
+
+        fs::write(checkpoint_dir(dir.path(), 1).join(completed_marker(1)), b"").unwrap();
+        coordinator.confirm();
+        assert_eq!(replica.last_completed(), 1);
+        assert!(coordinator.unconfirmed.is_empty());
+    }
+
+    #[test]
+    fn restore_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CheckpointConfig::new(dir.path()).interval(Duration::from_secs(3600));
+        let mut coordinator = CheckpointCoordinator::new(config.clone(), 0, 1, None);
+        let running = Coord::new(0, 0, 0);
+        let ended = Coord::new(0, 0, 1);
+        let replica = coordinator.replica(running, &[running, ended]);
+        let index = replica.register();
+        coordinator.replica(ended, &[running, ended]).finish();
+
+        replica.trigger(1);
+        assert!(replica.inject_barrier());
+        replica.save(index, encode(&42u64));
+        replica.complete();
+        for _ in 0..2 {
+            let event = coordinator.events.recv().unwrap();
+            coordinator.handle(event);
+        }
+        coordinator.pending = Some(1);
+        coordinator.handle(ReplicaEvent::Finished(running));
+        assert_eq!(coordinator.completed, vec![1]);
+
+        let mut coordinator = CheckpointCoordinator::new(config.restore_from(1), 0, 1, None);
+        // the numbering continues from the restored execution
+        assert_eq!(coordinator.last, 1);
+        let replica = coordinator.replica(running, &[running, ended]);
+        assert!(!replica.ended());
+        let index = replica.register();
+        assert_eq!(
+            replica.restored(index),
+            Some(RestoredState::Snapshot(encode(&42u64)))
+        );
+        assert_eq!(replica.restored(index), None);
+        assert!(coordinator.replica(ended, &[running, ended]).ended());
+    }
+
+    #[test]
+    fn rescale_keyed_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = CheckpointConfig::new(dir.path()).interval(Duration::from_secs(3600));
+        let mut coordinator = CheckpointCoordinator::new(config.clone(), 0, 1, None);
+        let previous = [Coord::new(0, 0, 0), Coord::new(0, 0, 1)];
+        let replicas = previous.map(|coord| coordinator.replica(coord, &previous));
+        for (i, replica) in replicas.iter().enumerate() {
+            let index = replica.register();
+            replica.trigger(1);
+            assert!(replica.inject_barrier());
+            let mut state = StateMap::default();
+            state.extend(
+                (0..100u64)
+                    .filter(|key| key_group_replica(group_by_hash(key), 2) == i)
+                    .map(|key| (key, key * 10)),
+            );
+            replica.save(index, encode(&state));
+            replica.complete();
+        }
+        coordinator.pending = Some(1);
+        for _ in 0..2 {
+            let event = coordinator.events.recv().unwrap();
+            coordinator.handle(event);
+        }
+        assert_eq!(coordinator.completed, vec![1]);
+
+        // restore the state on 3 replicas
+        let mut coordinator = CheckpointCoordinator::new(config.restore_from(1), 0, 1, None);
+        let current = [0, 1, 2].map(|replica_id| Coord::new(0, 0, replica_id));
+        let mut restored = Vec::new();
+        for (i, &coord) in current.iter().enumerate() {
+            let replica = coordinator.replica(coord, &current);
+            let mut state = OperatorState::<StateMap<u64, u64>>::keyed();
+            state.slot = Some((replica.clone(), replica.register()));
+            let mut state = state.restore().unwrap();
+            for (key, value) in state.drain() {
+                assert_eq!(key_group_replica(group_by_hash(&key), 3), i);
+                assert_eq!(value, key * 10);
+                restored.push(key);
+            }
+        }
+        restored.sort_unstable();
+        assert_eq!(restored, (0..100).collect::<Vec<_>>());
+    }
+}
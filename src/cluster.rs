@@ -0,0 +1,967 @@
+//! Standalone cluster mode, with worker daemons running persistently on the hosts.
+//!
+//! By default the spawner deploys each run of a job via SSH: it uploads the executable to every
+//! host and starts the workers with a remote command. In the standalone mode a [`WorkerDaemon`]
+//! runs on each host instead, and the hosts of the configuration set their `daemon_port`:
+//!
+//! ```toml
+//! [[host]]
+//! address = "host1.lan"
+//! base_port = 9500
+//! num_cores = 16
+//! daemon_port = 9400
+//! ```
+//!
+//! The spawner submits to each daemon a job description with the identifier of the executable,
+//! the id of the host, the configuration and the arguments of the job. The daemons keep the
+//! executables they receive, so each build is uploaded only once, then they run the worker and
+//! stream its output and its exit code back to the spawner. There are no SSH sessions and no
+//! uploads between the runs of the same executable.
+//!
+//! The daemon is started with:
+//!
+//! ```no_run
+//! # use renoir::cluster::WorkerDaemon;
+//! WorkerDaemon::new(9400)
+//!     .with_address("0.0.0.0")
+//!     .with_secret("a long random string")
+//!     .run()
+//!     .unwrap();
+//! ```
+//!
+//! Since the daemons run the executables they are sent, by default they listen only on the
+//! loopback interface. To listen on the other interfaces a daemon must have a secret, which is
+//! shared with the spawners in the `daemon_secret` of the hosts of the configuration: each
+//! connection starts with a challenge of the daemon, and the requests of a spawner that doesn't
+//! prove to know the secret are refused. The secret is never sent, but the connections are not
+//! encrypted, so the daemons should still listen only on a trusted network.
+//!
+//! Each run of a job gets a job id, which the spawner logs when it deploys the job. The daemons
+//! keep track of the workers of the jobs they are running, and of their progress: the workers
+//! report the number of elements processed by each block (the chain of operators running in the
+//! same replica) of the host. The running jobs are listed with [`list_jobs`] and cancelled with
+//! [`cancel_job`]; a cancelled job is not restarted by the restart policy. The `renoir_jobs`
+//! example is a small command line interface for them:
+//!
+//! ```text
+//! renoir_jobs list host1.lan:9400 host2.lan:9400
+//! renoir_jobs cancel <job id> host1.lan:9400 host2.lan:9400
+//! ```
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Once};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use nanorand::{ChaCha20, Rng};
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::{CONFIG_ENV_VAR, HOST_ID_ENV_VAR, JOB_ID_ENV_VAR};
+use crate::profiler::metrics::{self, ReplicaStatus};
+use crate::scheduler::{BlockId, HostId};
+
+/// Default directory where the daemons keep the executables.
+const DEFAULT_WORK_DIR: &str = "/tmp/renoir/daemon";
+/// Size of the buffer used to send and receive the executables.
+const EXECUTABLE_BUFFER_SIZE: usize = 512 * 1024;
+/// Maximum size of a message of the protocol, the executables are sent outside of the messages.
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+/// The prefix of the lines with the progress of a worker, kept by the daemon.
+const PROGRESS_PREFIX: &str = "__renoir_PROGRESS__";
+/// How often the workers report their progress.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+/// Size of the challenge sent by a daemon with a secret.
+const CHALLENGE_SIZE: usize = 32;
+/// How long a daemon waits for the answer to its challenge.
+const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The description of the worker of a job on a host, submitted by the spawner to the daemon.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct JobDescription {
+    /// The id of this run of the job, the same on all the hosts.
+    pub job_id: String,
+    /// The identifier of the executable, derived from its hash.
+    pub executable_uid: String,
+    pub host_id: HostId,
+    /// The configuration of the job, in TOML.
+    pub config: String,
+    /// The arguments of the executable, without the name of the program.
+    pub args: Vec<String>,
+    /// The environment variables to set, in addition to the host id and the configuration.
+    pub env: Vec<(String, String)>,
+}
+
+/// A message from the spawner to a daemon.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum Request {
+    /// The answer to the challenge of the daemon, see [`challenge_response`].
+    Authenticate(Vec<u8>),
+    /// Run the worker of a job.
+    Submit(JobDescription),
+    /// The executable the daemon asked for: its size, followed by its content.
+    Executable(u64),
+    /// Kill the workers of the job, e.g. after the failure of another host.
+    Kill { job_id: String },
+    /// Kill the workers of the job, which must not be restarted.
+    Cancel { job_id: String },
+    /// List the jobs running on the daemon.
+    ListJobs,
+}
+
+/// A message from a daemon to the spawner.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Response {
+    /// The first message of a connection: the challenge the spawner has to answer with the
+    /// secret, if the daemon has one.
+    Challenge(Option<Vec<u8>>),
+    /// The executable of the job is missing, the spawner has to send it.
+    NeedExecutable,
+    /// A line of the standard output of the worker.
+    Stdout(String),
+    /// A line of the standard error of the worker.
+    Stderr(String),
+    /// The worker exited with this code, `1` if it was killed by a signal.
+    Exited(i32),
+    /// The worker was killed because its job was cancelled.
+    Cancelled,
+    /// The number of workers killed.
+    Killed(usize),
+    /// The workers running on the daemon.
+    Jobs(Vec<JobInfo>),
+    /// The request completed.
+    Done,
+    /// The request failed.
+    Error(String),
+}
+
+/// The worker of a job running on a daemon, as returned by [`list_jobs`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub job_id: String,
+    pub host_id: HostId,
+    /// The identifier of the executable, derived from its hash.
+    pub executable_uid: String,
+    /// How long the worker has been running.
+    pub elapsed: Duration,
+    /// The last progress reported by the worker, by block.
+    pub progress: Vec<BlockProgress>,
+}
+
+/// The elements processed by the replicas of a block on a host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockProgress {
+    pub block_id: BlockId,
+    /// The titles of the operators of the block, from the first one.
+    pub operators: Vec<String>,
+    /// The number of replicas of the block on the host.
+    pub replicas: usize,
+    /// The number of replicas still running.
+    pub running: usize,
+    /// The elements received by the replicas, i.e. processed by the first operator.
+    pub items_in: u64,
+    /// The elements sent by the replicas to the next blocks.
+    pub items_out: u64,
+}
+
+/// A new id for a run of a job, from the current time and the process id of the spawner.
+pub(crate) fn new_job_id() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{:x}-{:x}", now.as_millis(), std::process::id())
+}
+
+/// Write a message, prefixed by its length.
+pub(crate) fn write_message<T: Serialize>(
+    stream: &mut impl Write,
+    message: &T,
+) -> std::io::Result<()> {
+    let buf = bincode::serde::encode_to_vec(message, bincode::config::standard())
+        .map_err(std::io::Error::other)?;
+    stream.write_all(&(buf.len() as u32).to_le_bytes())?;
+    stream.write_all(&buf)
+}
+
+/// Read a message written by `write_message`.
+pub(crate) fn read_message<T: DeserializeOwned>(stream: &mut impl Read) -> std::io::Result<T> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("message of {len} bytes is too large"),
+        ));
+    }
+    let mut buf = vec![0; len];
+    stream.read_exact(&mut buf)?;
+    bincode::serde::decode_from_slice(&buf, bincode::config::standard())
+        .map(|(message, _)| message)
+        .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))
+}
+
+/// The answer to the challenge `nonce` of a daemon, which proves that the spawner knows its
+/// secret without sending it.
+fn challenge_response(secret: &str, nonce: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(nonce);
+    hasher.update(secret.as_bytes());
+    hasher.finalize().to_vec()
+}
+
+/// The hash of the content of an executable, in hex. The identifier of the executable on a host
+/// starts with it, so a daemon can check the executables it receives.
+pub(crate) fn executable_hash(mut executable: impl Read) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut executable, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Compare two answers to a challenge in constant time.
+fn same_response(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Connect to the daemon at `address`, answering its challenge with `secret`.
+fn connect(
+    address: impl ToSocketAddrs,
+    secret: Option<&str>,
+    timeout: Option<Duration>,
+) -> std::io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(timeout)?;
+    match read_message(&mut stream)? {
+        Response::Challenge(None) => {}
+        Response::Challenge(Some(nonce)) => {
+            let Some(secret) = secret else {
+                return Err(std::io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    "the daemon requires a secret",
+                ));
+            };
+            let response = challenge_response(secret, &nonce);
+            write_message(&mut stream, &Request::Authenticate(response))?;
+        }
+        response => return Err(unexpected(response)),
+    }
+    Ok(stream)
+}
+
+/// Submit `job` to the daemon at `address`, sending `executable` if the daemon doesn't have it.
+///
+/// Each line of the output of the worker is passed to `output`, and the exit code of the worker is
+/// returned, or `None` if the job was cancelled. If `timeout` is set and the worker prints nothing
+/// for that long, e.g. because the host is dead and its heartbeats stopped, this fails with
+/// `ErrorKind::TimedOut`.
+pub(crate) fn submit(
+    address: impl ToSocketAddrs,
+    secret: Option<&str>,
+    job: JobDescription,
+    executable: &Path,
+    timeout: Option<Duration>,
+    mut output: impl FnMut(Response),
+) -> std::io::Result<Option<i32>> {
+    // the daemon answers right away, then the worker prints at least its heartbeats
+    let mut stream = connect(address, secret, timeout)?;
+    write_message(&mut stream, &Request::Submit(job))?;
+    loop {
+        let response = read_message(&mut stream).map_err(|e| match e.kind() {
+            // the read timeout is reported as `WouldBlock` on some platforms
+            ErrorKind::WouldBlock => ErrorKind::TimedOut.into(),
+            _ => e,
+        })?;
+        match response {
+            Response::NeedExecutable => {
+                let mut file = File::open(executable)?;
+                let len = file.metadata()?.len();
+                write_message(&mut stream, &Request::Executable(len))?;
+                std::io::copy(&mut file, &mut stream)?;
+            }
+            Response::Exited(code) => return Ok(Some(code)),
+            Response::Cancelled => return Ok(None),
+            Response::Error(e) => return Err(std::io::Error::other(e)),
+            line @ (Response::Stdout(_) | Response::Stderr(_)) => output(line),
+            _ => {}
+        }
+    }
+}
+
+/// Send `request` to the daemon at `address` and wait for its response.
+fn request(
+    address: impl ToSocketAddrs,
+    secret: Option<&str>,
+    request: &Request,
+) -> std::io::Result<Response> {
+    let mut stream = connect(address, secret, None)?;
+    write_message(&mut stream, request)?;
+    match read_message(&mut stream)? {
+        Response::Error(e) => Err(std::io::Error::other(e)),
+        response => Ok(response),
+    }
+}
+
+fn unexpected(response: Response) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::InvalidData,
+        format!("unexpected response {response:?}"),
+    )
+}
+
+/// Tell the daemon at `address` to kill the workers of `job_id`.
+pub(crate) fn kill(
+    address: impl ToSocketAddrs,
+    secret: Option<&str>,
+    job_id: &str,
+) -> std::io::Result<()> {
+    let job_id = job_id.to_string();
+    request(address, secret, &Request::Kill { job_id }).map(|_| ())
+}
+
+/// List the workers of the jobs running on the daemon at `address`, sorted by job.
+///
+/// The `secret` is required if the daemon has one.
+pub fn list_jobs(
+    address: impl ToSocketAddrs,
+    secret: Option<&str>,
+) -> std::io::Result<Vec<JobInfo>> {
+    match request(address, secret, &Request::ListJobs)? {
+        Response::Jobs(jobs) => Ok(jobs),
+        response => Err(unexpected(response)),
+    }
+}
+
+/// Cancel the job `job_id` on the daemon at `address`, killing its workers, and return how many
+/// they were.
+///
+/// The spawner of the job stops the workers on the other hosts too, and doesn't restart the job.
+/// The `secret` is required if the daemon has one.
+pub fn cancel_job(
+    address: impl ToSocketAddrs,
+    secret: Option<&str>,
+    job_id: &str,
+) -> std::io::Result<usize> {
+    let job_id = job_id.to_string();
+    match request(address, secret, &Request::Cancel { job_id })? {
+        Response::Killed(killed) => Ok(killed),
+        response => Err(unexpected(response)),
+    }
+}
+
+/// The progress of the blocks of this host, from the metrics of their replicas.
+fn progress() -> Vec<BlockProgress> {
+    let mut progress: Vec<_> = metrics::blocks()
+        .into_iter()
+        .map(|(block_id, structure)| BlockProgress {
+            block_id,
+            operators: structure.operators.into_iter().map(|op| op.title).collect(),
+            replicas: 0,
+            running: 0,
+            items_in: 0,
+            items_out: 0,
+        })
+        .collect();
+    for replica in metrics::replicas() {
+        let Some(block) = progress
+            .iter_mut()
+            .find(|block| block.block_id == replica.coord.block_id)
+        else {
+            continue;
+        };
+        block.replicas += 1;
+        block.running += (replica.status == ReplicaStatus::Running) as usize;
+        block.items_in += replica.items_in;
+        block.items_out += replica.items_out;
+    }
+    progress
+}
+
+/// If this is a worker started by a daemon, collect the metrics and print the progress of the
+/// blocks on the standard output, which is read by the daemon, until the process exits.
+pub(crate) fn start_progress_reports() {
+    static STARTED: Once = Once::new();
+    if std::env::var_os(JOB_ID_ENV_VAR).is_none() {
+        return;
+    }
+    metrics::enable();
+    STARTED.call_once(|| {
+        std::thread::Builder::new()
+            .name("progress".into())
+            .spawn(|| loop {
+                std::thread::sleep(PROGRESS_INTERVAL);
+                let progress = serde_json::to_string(&progress()).unwrap();
+                // the standard output is locked, so the report is not mixed with other lines
+                if writeln!(std::io::stdout().lock(), "{PROGRESS_PREFIX}{progress}").is_err() {
+                    break;
+                }
+            })
+            .unwrap();
+    });
+}
+
+/// A daemon running the workers of the jobs submitted to this host, see the [module
+/// documentation](self).
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct WorkerDaemon {
+    address: (String, u16),
+    work_dir: PathBuf,
+    #[derivative(Debug = "ignore")]
+    secret: Option<String>,
+}
+
+/// A worker running on a daemon.
+#[derive(Debug)]
+struct Worker {
+    host_id: HostId,
+    executable_uid: String,
+    started: Instant,
+    child: Mutex<Child>,
+    /// The last progress reported by the worker.
+    progress: Mutex<Vec<BlockProgress>>,
+    /// Whether the worker was killed by the cancellation of its job.
+    cancelled: AtomicBool,
+}
+
+/// The workers running on a daemon, by job.
+type Workers = Arc<Mutex<HashMap<String, Vec<Arc<Worker>>>>>;
+
+impl WorkerDaemon {
+    /// A daemon listening on `port` of the loopback interface.
+    pub fn new(port: u16) -> Self {
+        Self {
+            address: ("127.0.0.1".to_string(), port),
+            work_dir: DEFAULT_WORK_DIR.into(),
+            secret: None,
+        }
+    }
+
+    /// Listen on the interface with this address, `0.0.0.0` for all the interfaces.
+    ///
+    /// A daemon listening on an interface other than the loopback one must have a secret, see
+    /// [`WorkerDaemon::with_secret`].
+    pub fn with_address(mut self, address: impl Into<String>) -> Self {
+        self.address.0 = address.into();
+        self
+    }
+
+    /// Serve only the spawners that know this secret, set in the `daemon_secret` of the host in
+    /// their configuration.
+    pub fn with_secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Keep the executables in this directory, `/tmp/renoir/daemon` by default.
+    pub fn with_work_dir(mut self, work_dir: impl Into<PathBuf>) -> Self {
+        self.work_dir = work_dir.into();
+        self
+    }
+
+    /// Serve the spawners forever, each one in a separate thread.
+    pub fn run(self) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.work_dir)?;
+        let listener = TcpListener::bind((self.address.0.as_str(), self.address.1))?;
+        let local_addr = listener.local_addr()?;
+        if self.secret.is_none() && !local_addr.ip().is_loopback() {
+            return Err(std::io::Error::new(
+                ErrorKind::PermissionDenied,
+                format!("the worker daemon needs a secret to listen at {local_addr}"),
+            ));
+        }
+        log::info!("worker daemon listening at {local_addr:?}");
+        let daemon = Arc::new(self);
+        let workers = Workers::default();
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("worker daemon failed to accept a connection: {e:?}");
+                    continue;
+                }
+            };
+            let daemon = daemon.clone();
+            let workers = workers.clone();
+            std::thread::Builder::new()
+                .name("daemon-conn".into())
+                .spawn(move || {
+                    let peer = stream.peer_addr();
+                    if let Err(e) = daemon.serve(stream, &workers) {
+                        log::warn!("worker daemon failed to serve {peer:?}: {e:?}");
+                    }
+                })
+                .unwrap();
+        }
+        Ok(())
+    }
+
+    /// Send the challenge to the spawner and check its answer, if the daemon has a secret.
+    fn authenticate(&self, stream: &mut TcpStream) -> std::io::Result<()> {
+        let Some(secret) = &self.secret else {
+            return write_message(stream, &Response::Challenge(None));
+        };
+        let nonce = ChaCha20::new().rand()[..CHALLENGE_SIZE].to_vec();
+        write_message(stream, &Response::Challenge(Some(nonce.clone())))?;
+        stream.set_read_timeout(Some(CHALLENGE_TIMEOUT))?;
+        match read_message(stream)? {
+            Request::Authenticate(response)
+                if same_response(&response, &challenge_response(secret, &nonce)) =>
+            {
+                stream.set_read_timeout(None)
+            }
+            _ => {
+                write_message(
+                    stream,
+                    &Response::Error("authentication failed".to_string()),
+                )?;
+                Err(std::io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    "authentication failed",
+                ))
+            }
+        }
+    }
+
+    fn serve(&self, mut stream: TcpStream, workers: &Workers) -> std::io::Result<()> {
+        self.authenticate(&mut stream)?;
+        match read_message(&mut stream)? {
+            Request::Submit(job) => match self.run_job(&job, &mut stream, workers) {
+                Err(e) if e.kind() != ErrorKind::BrokenPipe => {
+                    let message = format!("failed to run the job on host {}: {e}", job.host_id);
+                    write_message(&mut stream, &Response::Error(message))
+                }
+                res => res,
+            },
+            Request::Kill { job_id } => {
+                let killed = kill_workers(workers, &job_id, false);
+                write_message(&mut stream, &Response::Killed(killed))
+            }
+            Request::Cancel { job_id } => {
+                let killed = kill_workers(workers, &job_id, true);
+                write_message(&mut stream, &Response::Killed(killed))
+            }
+            Request::ListJobs => {
+                let mut jobs: Vec<_> = workers
+                    .lock()
+                    .iter()
+                    .flat_map(|(job_id, workers)| {
+                        workers.iter().map(|worker| JobInfo {
+                            job_id: job_id.clone(),
+                            host_id: worker.host_id,
+                            executable_uid: worker.executable_uid.clone(),
+                            elapsed: worker.started.elapsed(),
+                            progress: worker.progress.lock().clone(),
+                        })
+                    })
+                    .collect();
+                jobs.sort_by(|a, b| (&a.job_id, a.host_id).cmp(&(&b.job_id, b.host_id)));
+                write_message(&mut stream, &Response::Jobs(jobs))
+            }
+            Request::Executable(_) | Request::Authenticate(_) => write_message(
+                &mut stream,
+                &Response::Error("unexpected request".to_string()),
+            ),
+        }
+    }
+
+    /// Run the worker of `job`, streaming its output to the spawner.
+    fn run_job(
+        &self,
+        job: &JobDescription,
+        stream: &mut TcpStream,
+        workers: &Workers,
+    ) -> std::io::Result<()> {
+        let path = self.executable(&job.executable_uid, stream)?;
+        log::info!(
+            "starting worker of host {} of job {} with {}",
+            job.host_id,
+            job.job_id,
+            path.display()
+        );
+        let mut child = Command::new(&path)
+            .args(&job.args)
+            .envs(job.env.iter().cloned())
+            .env(HOST_ID_ENV_VAR, job.host_id.to_string())
+            .env(CONFIG_ENV_VAR, &job.config)
+            .env(JOB_ID_ENV_VAR, &job.job_id)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // the two pipes are read by separate threads, so neither of them fills up
+        let (tx, rx) = std::sync::mpsc::channel();
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+        let readers = [
+            forward(stdout, tx.clone(), Response::Stdout),
+            forward(stderr, tx, Response::Stderr),
+        ];
+        let worker = Arc::new(Worker {
+            host_id: job.host_id,
+            executable_uid: job.executable_uid.clone(),
+            started: Instant::now(),
+            child: Mutex::new(child),
+            progress: Default::default(),
+            cancelled: AtomicBool::new(false),
+        });
+        workers
+            .lock()
+            .entry(job.job_id.clone())
+            .or_default()
+            .push(worker.clone());
+
+        let mut sent = Ok(());
+        for line in rx {
+            if let Response::Stdout(line) = &line {
+                if let Some(progress) = line.strip_prefix(PROGRESS_PREFIX) {
+                    match serde_json::from_str(progress) {
+                        Ok(progress) => *worker.progress.lock() = progress,
+                        Err(e) => log::warn!("invalid progress of host {}: {e}", job.host_id),
+                    }
+                    continue;
+                }
+            }
+            sent = write_message(stream, &line);
+            if sent.is_err() {
+                // the spawner is gone, nobody would wait for this worker
+                log::warn!(
+                    "spawner of host {} disconnected, killing the worker",
+                    job.host_id
+                );
+                let _ = worker.child.lock().kill();
+                break;
+            }
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        let status = worker.child.lock().wait()?;
+        let mut jobs = workers.lock();
+        if let Some(workers) = jobs.get_mut(&job.job_id) {
+            workers.retain(|w| !Arc::ptr_eq(w, &worker));
+            if workers.is_empty() {
+                jobs.remove(&job.job_id);
+            }
+        }
+        drop(jobs);
+        log::info!("worker of host {} exited: {status}", job.host_id);
+        sent?;
+        if worker.cancelled.load(Ordering::Acquire) {
+            write_message(stream, &Response::Cancelled)
+        } else {
+            write_message(stream, &Response::Exited(status.code().unwrap_or(1)))
+        }
+    }
+
+    /// The path of the executable `uid`, received from the spawner if it's missing.
+    fn executable(&self, uid: &str, stream: &mut TcpStream) -> std::io::Result<PathBuf> {
+        // the uid becomes a file name, it must not escape the work directory
+        if uid.is_empty()
+            || !uid
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid executable uid {uid:?}"),
+            ));
+        }
+        let path = self.work_dir.join(uid);
+        if path.exists() {
+            return Ok(path);
+        }
+
+        write_message(stream, &Response::NeedExecutable)?;
+        let Request::Executable(len) = read_message(stream)? else {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "expected the executable",
+            ));
+        };
+        // each upload has its own file, renamed when complete and checked, so neither a failed
+        // upload nor a concurrent one of the same executable is ever run
+        let suffix: u64 = ChaCha20::new().generate();
+        let partial = self.work_dir.join(format!("{uid}.{suffix:016x}.part"));
+        let received = receive_executable(uid, len, stream, &partial)
+            .and_then(|_| std::fs::rename(&partial, &path));
+        if received.is_err() {
+            let _ = std::fs::remove_file(&partial);
+        }
+        received?;
+        log::info!("received executable {uid} ({len} bytes)");
+        Ok(path)
+    }
+}
+
+/// Write to `path` the executable `uid` of `len` bytes sent on `stream`, checking that its content
+/// has the hash in the uid.
+fn receive_executable(
+    uid: &str,
+    len: u64,
+    stream: &mut TcpStream,
+    path: &Path,
+) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    let mut reader = BufReader::with_capacity(EXECUTABLE_BUFFER_SIZE, stream.take(len));
+    let copied = std::io::copy(&mut reader, &mut file)?;
+    if copied != len {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    file.sync_all()?;
+    drop(file);
+    // the hosts sharing a daemon add a suffix to the hash
+    let hash = executable_hash(BufReader::new(File::open(path)?))?;
+    if !uid
+        .strip_prefix(&hash)
+        .is_some_and(|suffix| suffix.is_empty() || suffix.starts_with('-'))
+    {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("the executable does not match its uid {uid:?}"),
+        ));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o500))?;
+    }
+    Ok(())
+}
+
+/// Kill the workers of `job_id` and return how many they were.
+fn kill_workers(workers: &Workers, job_id: &str, cancel: bool) -> usize {
+    let killed = workers.lock().remove(job_id).unwrap_or_default();
+    let action = if cancel { "cancelling" } else { "killing" };
+    log::info!("{action} {} workers of job {job_id}", killed.len());
+    for worker in &killed {
+        worker.cancelled.store(cancel, Ordering::Release);
+        let _ = worker.child.lock().kill();
+    }
+    killed.len()
+}
+
+/// Send each line of `pipe` to `tx`, until the pipe is closed.
+fn forward(
+    pipe: impl Read + Send + 'static,
+    tx: std::sync::mpsc::Sender<Response>,
+    line: fn(String) -> Response,
+) -> std::thread::JoinHandle<()> {
+    std::thread::Builder::new()
+        .name("daemon-pipe".into())
+        .spawn(move || {
+            for l in BufReader::new(pipe).lines().map_while(Result::ok) {
+                if tx.send(line(l)).is_err() {
+                    break;
+                }
+            }
+        })
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start a daemon on a free port of localhost.
+    fn start_daemon() -> (u16, tempfile::TempDir) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let work_dir = tempfile::tempdir().unwrap();
+        let daemon = WorkerDaemon::new(port)
+            .with_address("127.0.0.1")
+            .with_work_dir(work_dir.path());
+        std::thread::spawn(move || daemon.run().unwrap());
+        (port, work_dir)
+    }
+
+    /// Submit `job`, retrying while the daemon is starting.
+    fn submit_job(
+        port: u16,
+        job: &JobDescription,
+        executable: &Path,
+    ) -> (Option<i32>, Vec<Response>) {
+        for _ in 0..100 {
+            let mut output = Vec::new();
+            match submit(
+                ("127.0.0.1", port),
+                None,
+                job.clone(),
+                executable,
+                None,
+                |line| output.push(line),
+            ) {
+                Ok(code) => return (code, output),
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                    std::thread::sleep(Duration::from_millis(10))
+                }
+                Err(e) => panic!("{e:?}"),
+            }
+        }
+        panic!("the daemon is not listening");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn submit_job_to_daemon() {
+        let (port, work_dir) = start_daemon();
+        let mut script = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            script,
+            "#!/bin/sh\necho \"$1 ${HOST_ID_ENV_VAR} ${JOB_ID_ENV_VAR}\"\necho \"${CONFIG_ENV_VAR}\" >&2\nexit 3\n"
+        )
+        .unwrap();
+        let hash = executable_hash(File::open(script.path()).unwrap()).unwrap();
+        let job = JobDescription {
+            job_id: "job-01".into(),
+            executable_uid: format!("{hash}-01"),
+            host_id: 2,
+            config: "config".into(),
+            args: vec!["hello".into()],
+            env: vec![],
+        };
+
+        let (code, mut output) = submit_job(port, &job, script.path());
+        assert_eq!(code, Some(3));
+        output.sort_by_key(|line| matches!(line, Response::Stderr(_)));
+        assert_eq!(
+            output,
+            vec![
+                Response::Stdout("hello 2 job-01".into()),
+                Response::Stderr("config".into())
+            ]
+        );
+
+        // the second run uses the executable already received
+        let missing = work_dir.path().join("missing");
+        let (code, _) = submit_job(port, &job, &missing);
+        assert_eq!(code, Some(3));
+
+        let invalid = JobDescription {
+            executable_uid: "../script".into(),
+            ..job.clone()
+        };
+        assert!(submit(
+            ("127.0.0.1", port),
+            None,
+            invalid,
+            script.path(),
+            None,
+            |_| {}
+        )
+        .is_err());
+
+        // an executable is refused if its content doesn't have the hash in the uid
+        let mismatched = JobDescription {
+            executable_uid: "script-01".into(),
+            ..job
+        };
+        assert!(submit(
+            ("127.0.0.1", port),
+            None,
+            mismatched,
+            script.path(),
+            None,
+            |_| {}
+        )
+        .is_err());
+        let files: Vec<_> = std::fs::read_dir(work_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(files, vec![format!("{hash}-01")]);
+        kill(("127.0.0.1", port), None, "job-01").unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn list_and_cancel_jobs() {
+        let (port, _work_dir) = start_daemon();
+        let progress = BlockProgress {
+            block_id: 1,
+            operators: vec!["Source".into(), "Map".into()],
+            replicas: 2,
+            running: 1,
+            items_in: 10,
+            items_out: 7,
+        };
+        let mut script = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            script,
+            "#!/bin/sh\necho '{PROGRESS_PREFIX}{}'\nexec sleep 60\n",
+            serde_json::to_string(&[&progress]).unwrap()
+        )
+        .unwrap();
+        let uid = executable_hash(File::open(script.path()).unwrap()).unwrap();
+        let job = JobDescription {
+            job_id: "job-02".into(),
+            executable_uid: uid.clone(),
+            host_id: 0,
+            config: String::new(),
+            args: vec![],
+            env: vec![],
+        };
+        let path = script.path().to_owned();
+        let spawner = std::thread::spawn(move || submit_job(port, &job, &path));
+
+        let jobs = loop {
+            match list_jobs(("127.0.0.1", port), None) {
+                Ok(jobs) if jobs.iter().any(|job| !job.progress.is_empty()) => break jobs,
+                _ => std::thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].job_id, "job-02");
+        assert_eq!(jobs[0].executable_uid, uid);
+        assert_eq!(jobs[0].progress, vec![progress]);
+
+        assert_eq!(cancel_job(("127.0.0.1", port), None, "job-02").unwrap(), 1);
+        let (code, output) = spawner.join().unwrap();
+        assert_eq!(code, None);
+        // the progress is kept by the daemon, not forwarded
+        assert!(output.is_empty());
+        assert!(list_jobs(("127.0.0.1", port), None).unwrap().is_empty());
+        assert_eq!(cancel_job(("127.0.0.1", port), None, "job-02").unwrap(), 0);
+    }
+
+    #[test]
+    fn authenticate_spawners() {
+        let daemon = WorkerDaemon::new(0).with_address("0.0.0.0");
+        let err = daemon.run().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let work_dir = tempfile::tempdir().unwrap();
+        let daemon = WorkerDaemon::new(port)
+            .with_work_dir(work_dir.path())
+            .with_secret("secret");
+        std::thread::spawn(move || daemon.run().unwrap());
+
+        let jobs = loop {
+            match list_jobs(("127.0.0.1", port), Some("secret")) {
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                    std::thread::sleep(Duration::from_millis(10))
+                }
+                jobs => break jobs,
+            }
+        };
+        assert!(jobs.unwrap().is_empty());
+        let err = list_jobs(("127.0.0.1", port), None).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(list_jobs(("127.0.0.1", port), Some("wrong")).is_err());
+        assert!(cancel_job(("127.0.0.1", port), Some("wrong"), "job").is_err());
+    }
+}
@@ -4,14 +4,17 @@
 
 use std::env;
 use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 #[cfg(feature = "clap")]
 use clap::Parser;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::checkpoint::{completed_checkpoints, CheckpointId};
 use crate::scheduler::HostId;
 use crate::CoordUInt;
 
@@ -21,6 +24,9 @@ pub const HOST_ID_ENV_VAR: &str = "NOIR_HOST_ID";
 /// Environment variable set by the runner with the content of the config file so that it's not
 /// required to have it on all the hosts.
 pub const CONFIG_ENV_VAR: &str = "NOIR_CONFIG";
+/// Environment variable set by the worker daemons with the id of the job, see
+/// [`cluster`](crate::cluster).
+pub const JOB_ID_ENV_VAR: &str = "NOIR_JOB_ID";
 
 /// The runtime configuration of the environment,
 ///
@@ -107,6 +113,26 @@ pub struct LocalConfig {
     ///
     /// A thread will be spawned for each core, for each block in the job graph.
     pub parallelism: CoordUInt,
+    /// If specified the state of the operators is periodically checkpointed.
+    pub checkpoint: Option<CheckpointConfig>,
+    /// Where the keyed state of the operators is stored.
+    pub state_backend: StateBackendConfig,
+    /// If specified the state of the operators of each replica is spilled to disk when it exceeds
+    /// this budget.
+    pub memory_budget: Option<MemoryBudgetConfig>,
+    /// How the operators are fused into blocks.
+    pub chaining: ChainingStrategy,
+    /// If specified the threads of the replicas are pinned to the cores of this host.
+    pub pinning: Option<PinningConfig>,
+    /// If specified the metrics are served at `/metrics` on this port.
+    pub metrics_port: Option<u16>,
+    /// The address the metrics endpoint is bound to, localhost if not specified.
+    pub metrics_address: Option<IpAddr>,
+    /// If specified the spans of the execution are exported to this OTLP endpoint.
+    pub otlp_endpoint: Option<String>,
+    /// Whether the idle replicas steal the batches queued for their siblings, see
+    /// [`RuntimeConfig::with_work_stealing`].
+    pub work_stealing: bool,
 }
 
 /// This environment uses local threads and remote hosts.
@@ -123,10 +149,53 @@ pub struct RemoteConfig {
     /// Remove remote binaries after execution
     #[serde(default)]
     pub cleanup_executable: bool,
+    /// If specified the state of the operators is periodically checkpointed.
+    #[serde(default)]
+    pub checkpoint: Option<CheckpointConfig>,
+    /// If specified the job is deployed again when a remote worker fails.
+    #[serde(default)]
+    pub restart: Option<RestartPolicy>,
+    /// If specified the remote workers send heartbeats to the spawner, which detects the dead
+    /// hosts, see [`RuntimeConfig::with_heartbeat`].
+    #[serde(default)]
+    pub heartbeat: Option<HeartbeatConfig>,
+    /// Where the keyed state of the operators is stored.
+    #[serde(default)]
+    pub state_backend: StateBackendConfig,
+    /// If specified the state of the operators of each replica is spilled to disk when it exceeds
+    /// this budget, see [`RuntimeConfig::with_memory_budget`].
+    #[serde(default)]
+    pub memory_budget: Option<MemoryBudgetConfig>,
+    /// How the operators are fused into blocks.
+    #[serde(default)]
+    pub chaining: ChainingStrategy,
+    /// If specified the spans of the execution of all the hosts are exported to this OTLP
+    /// endpoint, see [`RuntimeConfig::with_otlp_endpoint`].
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+    /// How the messages sent to the other hosts are compressed.
+    #[serde(default)]
+    pub compression: NetworkCompression,
+    /// How the connections with the other hosts are retried when they fail.
+    #[serde(default)]
+    pub retry: NetworkRetryConfig,
+    /// Whether the idle replicas steal the batches queued for their siblings on the same host, see
+    /// [`RuntimeConfig::with_work_stealing`].
+    #[serde(default)]
+    pub work_stealing: bool,
+    /// Whether the remote channels between two hosts share a single connection, see
+    /// [`RuntimeConfig::with_connection_multiplexing`].
+    #[serde(default)]
+    pub multiplex_connections: bool,
 }
 
 /// The configuration of a single remote host.
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+///
+/// Outside of the configuration file it's built with [`HostConfig::new`] and the methods that set
+/// the optional parameters.
+#[derive(Clone, Serialize, Deserialize, Eq, PartialEq, Derivative)]
+#[derivative(Debug)]
+#[non_exhaustive]
 pub struct HostConfig {
     /// The IP address or domain name to use for connecting to this remote host.
     ///
@@ -135,7 +204,7 @@ pub struct HostConfig {
     /// The first port to use for inter-host communication.
     ///
     /// This port and the following ones will be bound by the host, one for each connection between
-    /// blocks of the job graph..
+    /// blocks of the job graph. When the connections are multiplexed only this port is bound.
     pub base_port: u16,
     /// The number of cores of the remote host.
     ///
@@ -147,6 +216,30 @@ pub struct HostConfig {
     /// If specified the remote worker will be spawned under `perf`, and its output will be stored
     /// at this location.
     pub perf_path: Option<PathBuf>,
+    /// If specified the threads of the replicas are pinned to the cores of this host.
+    #[serde(default)]
+    pub pinning: Option<PinningConfig>,
+    /// If specified the metrics of the replicas of this host are served by an HTTP endpoint at
+    /// `/metrics` on this port, in the Prometheus text format.
+    #[serde(default)]
+    pub metrics_port: Option<u16>,
+    /// The address the metrics endpoint is bound to, localhost if not specified. Use `0.0.0.0` to
+    /// let the metrics be scraped from the other machines.
+    #[serde(default)]
+    pub metrics_address: Option<IpAddr>,
+    /// If specified the connections with the other hosts are encrypted with TLS.
+    ///
+    /// It must be specified for all the hosts or for none of them. Requires the `tls` feature.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// If specified the jobs are submitted to the worker daemon listening on this port, instead of
+    /// being deployed via SSH, see the [standalone cluster mode](crate::cluster).
+    #[serde(default)]
+    pub daemon_port: Option<u16>,
+    /// The secret shared with the worker daemon of this host, if it requires one.
+    #[serde(default)]
+    #[derivative(Debug = "ignore")]
+    pub daemon_secret: Option<String>,
 }
 
 /// The information used to connect to a remote host via SSH.
@@ -168,6 +261,477 @@ pub struct SSHConfig {
     pub key_passphrase: Option<String>,
 }
 
+/// How the threads of the replicas are pinned to the cores of a host.
+///
+/// Each replica runs in its own thread: pinning it to a core avoids the migrations between the
+/// cores, for more predictable performance. In the configuration file of a remote environment it
+/// is configured for each host with:
+///
+/// ```toml
+/// [[host]]
+/// address = "host1"
+/// base_port = 9500
+/// num_cores = 16
+/// pinning = { cores = [0, 1, 2, 3, 4, 5, 6, 7], numa = true }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PinningConfig {
+    /// The cores the threads are pinned to, in turn. Defaults to all the cores of the host.
+    #[serde(default)]
+    pub cores: Option<Vec<usize>>,
+    /// Place the replicas with the same index on the same NUMA node.
+    ///
+    /// The replicas with the same index of two consecutive blocks are the ones connected when the
+    /// items are not moved between the replicas: this way they exchange the items through the
+    /// memory of their node. On Linux the memory of each replica is allocated from its node, and
+    /// so are the batches it sends and the queues of its channels towards the next block. The
+    /// nodes are read from `/sys/devices/system/node`, if it's missing all the cores are in the
+    /// same node.
+    #[serde(default)]
+    pub numa: bool,
+}
+
+/// The certificates used to encrypt the connections of a host with TLS.
+///
+/// The host presents its certificate to the hosts that connect to it, and verifies the
+/// certificates of the hosts it connects to against the certificate authority: the certificate
+/// must be valid for the `address` of the host. With `mutual` the hosts that connect also present
+/// their certificate, and the connections from hosts without a valid certificate are refused. In
+/// the configuration file of a remote environment it is configured for each host with:
+///
+/// ```toml
+/// [[host]]
+/// address = "host1"
+/// base_port = 9500
+/// num_cores = 16
+/// tls = { cert = "certs/host1.pem", key = "certs/host1.key", ca = "certs/ca.pem", mutual = true }
+/// ```
+///
+/// All the files are in the PEM format.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct TlsConfig {
+    /// The certificate chain of the host.
+    pub cert: PathBuf,
+    /// The private key of the certificate of the host.
+    pub key: PathBuf,
+    /// The certificate of the authority that signed the certificates of the hosts.
+    pub ca: PathBuf,
+    /// Require the hosts that connect to present a certificate signed by the authority.
+    #[serde(default)]
+    pub mutual: bool,
+}
+
+/// The configuration of the periodic checkpoints of the state of the operators.
+///
+/// See the [`checkpoint`](crate::checkpoint) module for how the checkpoints are taken. In the
+/// configuration file of a remote environment they are configured with:
+///
+/// ```toml
+/// [checkpoint]
+/// path = "/mnt/shared/checkpoints"
+/// interval_ms = 10000
+/// guarantee = "exactly_once"
+/// # resume the job from the checkpoint (or savepoint) 42
+/// restore = 42
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CheckpointConfig {
+    /// The directory where the checkpoints are stored.
+    ///
+    /// In a remote environment each host writes here the snapshots of its replicas, so this
+    /// should be on a filesystem shared by all the hosts.
+    pub path: PathBuf,
+    /// How often a new checkpoint is started. Defaults to 10 seconds.
+    #[serde(
+        rename = "interval_ms",
+        default = "checkpoint_default_interval",
+        with = "duration_millis"
+    )]
+    pub interval: Duration,
+    /// Restore the state of the operators from this checkpoint when the execution starts.
+    ///
+    /// The job graph must be the same of the execution that took the checkpoint. The parallelism
+    /// can change only if the state of the blocks with a different number of replicas can be
+    /// redistributed, see [rescaling](crate::checkpoint#rescaling).
+    #[serde(default)]
+    pub restore: Option<CheckpointId>,
+    /// The delivery guarantee of the job. Defaults to exactly-once.
+    #[serde(default)]
+    pub guarantee: DeliveryGuarantee,
+}
+
+/// The guarantee on the delivery of the items to the sinks after a job is restored from a
+/// checkpoint.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryGuarantee {
+    /// No checkpoint is taken: after a failure the job cannot be restored, so some items may never
+    /// be delivered.
+    AtMostOnce,
+    /// The barriers of the checkpoints are not aligned: a block doesn't wait for the barriers of
+    /// all the previous replicas before processing the messages that follow them. The checkpoints
+    /// are faster, but after a restore some items may be processed and delivered again.
+    AtLeastOnce,
+    /// The barriers are aligned, and the transactional sinks commit their output only when the
+    /// checkpoint that includes it is completed by all the hosts.
+    ///
+    /// After a restore no item is lost or delivered twice to a
+    /// [`TransactionalSink`](crate::operator::sink::TransactionalSink). This covers the state
+    /// saved in the checkpoints, that is the position of the sources
+    /// [`stream_iter`](crate::StreamContext::stream_iter),
+    /// [`stream_par_iter`](crate::StreamContext::stream_par_iter) and
+    /// [`stream_generator`](crate::StreamContext::stream_generator), and the aggregations of
+    /// [`Stream::fold_assoc`](crate::Stream::fold_assoc) and
+    /// [`Stream::group_by_fold`](crate::Stream::group_by_fold). The jobs with operators that keep
+    /// some other state, like the windows, the joins and the iterations, cannot be checkpointed
+    /// and panic when they start. The other sinks may receive again the items that followed the
+    /// last completed checkpoint.
+    #[default]
+    ExactlyOnce,
+}
+
+/// Where the keyed state of the operators is stored.
+///
+/// See the [`state`](crate::state) module for which operators use the backend. In the
+/// configuration file of a remote environment it is configured with:
+///
+/// ```toml
+/// [state_backend]
+/// type = "rocksdb"
+/// path = "/var/lib/renoir/state"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StateBackendConfig {
+    /// Keep the state in memory, inside the data structures of the operators.
+    #[default]
+    Memory,
+    /// Store the state in RocksDB databases inside `path`, for state larger than the memory.
+    ///
+    /// Only the operators listed in the [`state`](crate::state) module use it: the windows are
+    /// always kept in memory.
+    ///
+    /// This requires the `rocksdb` feature. The databases are temporary: they are created empty
+    /// when the execution starts, and removed when it ends.
+    #[serde(rename = "rocksdb")]
+    RocksDb {
+        /// The directory of the databases, on the local disk of each host.
+        path: PathBuf,
+    },
+}
+
+/// The memory budget of the state of the operators.
+///
+/// Each replica of each block estimates the memory used by the state of its operators: the
+/// accumulators of the keyed aggregations, the items buffered by the joins and the open windows.
+/// When the total exceeds `limit` the largest structure that can be moved to disk is spilled inside
+/// `spill_path`, instead of growing until the worker runs out of memory. In the configuration file
+/// of a remote environment it is configured with:
+///
+/// ```toml
+/// [memory_budget]
+/// limit = 536870912
+/// spill_path = "/tmp/renoir-spill"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct MemoryBudgetConfig {
+    /// The maximum memory used by the state of the operators of a replica, in bytes.
+    pub limit: u64,
+    /// The directory of the spilled state, on the local disk of each host.
+    ///
+    /// The spill files are temporary: they are removed when the state is read back, or when the
+    /// execution ends.
+    pub spill_path: PathBuf,
+}
+
+impl MemoryBudgetConfig {
+    /// Spill the state of a replica inside `spill_path` when it uses more than `limit` bytes.
+    pub fn new(limit: u64, spill_path: impl Into<PathBuf>) -> Self {
+        Self {
+            limit,
+            spill_path: spill_path.into(),
+        }
+    }
+}
+
+/// How the operators of the job graph are fused into blocks.
+///
+/// The operators of a block are chained: they run in the same thread, passing the items with a
+/// function call. A block is split at every operator that needs to exchange the items between the
+/// replicas (e.g. [`Stream::group_by`](crate::Stream::group_by)), and where the stream is split
+/// with [`Stream::start_new_block`](crate::Stream::start_new_block). The strategy selects which of
+/// these hints are followed. In the configuration file of a remote environment it is configured
+/// with:
+///
+/// ```toml
+/// chaining = "never"
+/// ```
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainingStrategy {
+    /// Follow the hints: a new block is started by
+    /// [`Stream::start_new_block`](crate::Stream::start_new_block), unless the stream is
+    /// [`Stream::chained`](crate::Stream::chained).
+    #[default]
+    Hints,
+    /// Fuse as many operators as possible, ignoring
+    /// [`Stream::start_new_block`](crate::Stream::start_new_block).
+    Always,
+    /// Start a new block at every [`Stream::start_new_block`](crate::Stream::start_new_block),
+    /// even if the stream is [`Stream::chained`](crate::Stream::chained).
+    Never,
+}
+
+/// The compression of the messages sent between the hosts.
+///
+/// Only the remote channels are compressed, the items exchanged between the replicas of the same
+/// host are never serialized. The compression is negotiated when a connection is opened: the
+/// sender proposes its codec, and falls back to sending uncompressed messages if the receiver
+/// cannot decompress it. The small messages, and the ones that do not shrink, are always sent
+/// uncompressed. In the configuration file of a remote environment it is configured with:
+///
+/// ```toml
+/// compression = "lz4"
+/// ```
+///
+/// The codecs require the `network_compression` feature.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkCompression {
+    /// Send the messages uncompressed.
+    #[default]
+    None,
+    /// LZ4, fast with a moderate ratio.
+    Lz4,
+    /// Zstandard, slower but with a better ratio, for the slowest links.
+    Zstd,
+}
+
+/// How the connections between the hosts are retried when they fail.
+///
+/// A connection is retried with an exponential backoff when it cannot be opened, and it's opened
+/// again when it drops during the job: the batches not yet delivered are sent again, so the
+/// receivers see each batch exactly once and in order. After too many failures the job fails. In
+/// the configuration file of a remote environment it is configured with:
+///
+/// ```toml
+/// [retry]
+/// connect_attempts = 32
+/// initial_delay_ms = 8
+/// max_delay_ms = 1000
+/// max_reconnections = 8
+/// reconnect_timeout_ms = 60000
+/// ```
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct NetworkRetryConfig {
+    /// The attempts to open a connection before failing. Defaults to 32.
+    pub connect_attempts: u32,
+    /// The delay before the second attempt, doubled at each of the following ones. Defaults to
+    /// 8 milliseconds.
+    #[serde(rename = "initial_delay_ms", with = "duration_millis")]
+    pub initial_delay: Duration,
+    /// The maximum delay between two attempts. Defaults to 1 second.
+    #[serde(rename = "max_delay_ms", with = "duration_millis")]
+    pub max_delay: Duration,
+    /// The times a connection that dropped is opened again before the job fails, `0` to fail at
+    /// the first drop. Defaults to 8.
+    pub max_reconnections: u32,
+    /// How long the receiving side of a dropped connection waits for the sender to open it
+    /// again. Defaults to 1 minute.
+    #[serde(rename = "reconnect_timeout_ms", with = "duration_millis")]
+    pub reconnect_timeout: Duration,
+}
+
+impl Default for NetworkRetryConfig {
+    fn default() -> Self {
+        Self {
+            connect_attempts: 32,
+            initial_delay: Duration::from_millis(8),
+            max_delay: Duration::from_secs(1),
+            max_reconnections: 8,
+            reconnect_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl NetworkRetryConfig {
+    /// The delay after the failed `attempt` (starting from 1).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        2u32.checked_pow(attempt.saturating_sub(1))
+            .and_then(|factor| self.initial_delay.checked_mul(factor))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+/// How the spawner detects the remote workers that stopped responding.
+///
+/// Each remote worker sends a heartbeat to the spawner every `interval`. When no heartbeat
+/// arrives from a host for `timeout`, e.g. because the host crashed or it's unreachable, the host
+/// is considered dead: the spawner stops the workers of the other hosts, which would wait forever
+/// for its messages, and the job fails or is restarted following the [`RestartPolicy`]. In the
+/// configuration file of a remote environment it is configured with:
+///
+/// ```toml
+/// [heartbeat]
+/// interval_ms = 1000
+/// timeout_ms = 10000
+/// ```
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    /// How often the workers send a heartbeat. Defaults to 1 second.
+    #[serde(rename = "interval_ms", with = "duration_millis")]
+    pub interval: Duration,
+    /// How long without heartbeats before a host is considered dead, it must be longer than
+    /// `interval`. Defaults to 10 seconds.
+    #[serde(rename = "timeout_ms", with = "duration_millis")]
+    pub timeout: Duration,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// How the job is restarted after the failure of a remote worker.
+///
+/// When a worker crashes its process exits, the spawner stops the workers of the other hosts and,
+/// after a delay, deploys the whole job again. If the checkpoints are enabled the job is restored
+/// from the last one completed, otherwise the sources replay their data from the start. In the
+/// configuration file of a remote environment the policy is configured with:
+///
+/// ```toml
+/// [restart]
+/// strategy = "exponential_backoff"
+/// initial_delay_ms = 1000
+/// max_delay_ms = 60000
+/// multiplier = 2
+/// max_attempts = 5
+/// ```
+///
+/// or, for a fixed delay between the attempts:
+///
+/// ```toml
+/// [restart]
+/// strategy = "fixed_delay"
+/// delay_ms = 5000
+/// max_attempts = 3
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RestartPolicy {
+    /// How long to wait before each attempt.
+    #[serde(flatten)]
+    pub strategy: RestartStrategy,
+    /// The number of restarts after which the job is considered failed.
+    pub max_attempts: u32,
+}
+
+/// The delay before restarting a failed job.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum RestartStrategy {
+    /// Wait the same time before each attempt.
+    FixedDelay {
+        #[serde(rename = "delay_ms", with = "duration_millis")]
+        delay: Duration,
+    },
+    /// Multiply the delay by `multiplier` after each attempt, up to `max_delay`.
+    ExponentialBackoff {
+        #[serde(rename = "initial_delay_ms", with = "duration_millis")]
+        initial_delay: Duration,
+        #[serde(rename = "max_delay_ms", with = "duration_millis")]
+        max_delay: Duration,
+        #[serde(default = "restart_default_multiplier")]
+        multiplier: u32,
+    },
+}
+
+impl RestartPolicy {
+    /// Restart the job at most `max_attempts` times, waiting `delay` before each attempt.
+    pub fn fixed_delay(delay: Duration, max_attempts: u32) -> Self {
+        Self {
+            strategy: RestartStrategy::FixedDelay { delay },
+            max_attempts,
+        }
+    }
+
+    /// Restart the job at most `max_attempts` times, doubling the delay after each attempt,
+    /// starting from `initial_delay` up to `max_delay`.
+    pub fn exponential_backoff(
+        initial_delay: Duration,
+        max_delay: Duration,
+        max_attempts: u32,
+    ) -> Self {
+        Self {
+            strategy: RestartStrategy::ExponentialBackoff {
+                initial_delay,
+                max_delay,
+                multiplier: restart_default_multiplier(),
+            },
+            max_attempts,
+        }
+    }
+
+    /// The delay before the restart `attempt` (starting from 1), or `None` if there are no more
+    /// attempts left.
+    pub fn delay(&self, attempt: u32) -> Option<Duration> {
+        if attempt == 0 || attempt > self.max_attempts {
+            return None;
+        }
+        let delay = match &self.strategy {
+            RestartStrategy::FixedDelay { delay } => *delay,
+            RestartStrategy::ExponentialBackoff {
+                initial_delay,
+                max_delay,
+                multiplier,
+            } => multiplier
+                .checked_pow(attempt - 1)
+                .and_then(|factor| initial_delay.checked_mul(factor))
+                .map_or(*max_delay, |delay| delay.min(*max_delay)),
+        };
+        Some(delay)
+    }
+}
+
+impl CheckpointConfig {
+    /// Store the checkpoints inside `path`, starting one every 10 seconds.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            interval: checkpoint_default_interval(),
+            restore: None,
+            guarantee: Default::default(),
+        }
+    }
+
+    /// Start a new checkpoint every `interval`.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Start the execution from the state saved in the checkpoint (or savepoint) `id`, which
+    /// must be completed.
+    ///
+    /// The sources that support it resume from where they were when the checkpoint was taken,
+    /// instead of reprocessing the data from scratch.
+    pub fn restore_from(mut self, id: CheckpointId) -> Self {
+        self.restore = Some(id);
+        self
+    }
+
+    /// Set the delivery guarantee of the job.
+    pub fn guarantee(mut self, guarantee: DeliveryGuarantee) -> Self {
+        self.guarantee = guarantee;
+        self
+    }
+}
+
 impl std::fmt::Debug for SSHConfig {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut d = f.debug_struct("Ssh");
@@ -281,6 +845,528 @@ impl RuntimeConfig {
             RuntimeConfig::Remote(remote) => remote.host_id,
         }
     }
+
+    /// Periodically checkpoint the state of the operators.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// # use renoir::config::CheckpointConfig;
+    /// # use std::time::Duration;
+    /// let checkpoint = CheckpointConfig::new("/tmp/renoir-checkpoints").interval(Duration::from_secs(5));
+    /// let config = RuntimeConfig::local(4).unwrap().with_checkpoints(checkpoint);
+    /// ```
+    pub fn with_checkpoints(mut self, checkpoint: CheckpointConfig) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(local) => local.checkpoint = Some(checkpoint),
+            RuntimeConfig::Remote(remote) => remote.checkpoint = Some(checkpoint),
+        }
+        self
+    }
+
+    /// Deploy the job again when a remote worker fails, following `policy`.
+    ///
+    /// The restarts are supported only by the remote environments: a local one ignores the policy.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// # use renoir::config::RestartPolicy;
+    /// # use std::time::Duration;
+    /// let policy = RestartPolicy::fixed_delay(Duration::from_secs(5), 3);
+    /// let config = RuntimeConfig::local(4).unwrap().with_restart_policy(policy);
+    /// ```
+    pub fn with_restart_policy(mut self, policy: RestartPolicy) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(_) => {
+                log::warn!("the restart policies are supported only by the remote environments")
+            }
+            RuntimeConfig::Remote(remote) => remote.restart = Some(policy),
+        }
+        self
+    }
+
+    /// Detect the dead hosts with the heartbeats of their workers, see [`HeartbeatConfig`].
+    ///
+    /// Without the heartbeats a host that crashes or becomes unreachable is noticed only when its
+    /// SSH connection drops, which may take very long, and meanwhile the other hosts wait for its
+    /// messages. The heartbeats are supported only by the remote environments: a local one ignores
+    /// them.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// # use renoir::config::HeartbeatConfig;
+    /// # use std::time::Duration;
+    /// let heartbeat = HeartbeatConfig {
+    ///     interval: Duration::from_millis(500),
+    ///     timeout: Duration::from_secs(5),
+    /// };
+    /// let config = RuntimeConfig::local(4).unwrap().with_heartbeat(heartbeat);
+    /// ```
+    pub fn with_heartbeat(mut self, heartbeat: HeartbeatConfig) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(_) => {
+                log::warn!("the heartbeats are supported only by the remote environments")
+            }
+            RuntimeConfig::Remote(remote) => remote.heartbeat = Some(heartbeat),
+        }
+        self
+    }
+
+    /// Compress the messages sent to the other hosts with `compression`.
+    ///
+    /// The compression is applied only to the remote channels: a local environment ignores it.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// # use renoir::config::NetworkCompression;
+    /// let config = RuntimeConfig::local(4)
+    ///     .unwrap()
+    ///     .with_network_compression(NetworkCompression::Lz4);
+    /// ```
+    pub fn with_network_compression(mut self, compression: NetworkCompression) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(_) => {
+                log::warn!("the network compression is used only by the remote environments")
+            }
+            RuntimeConfig::Remote(remote) => remote.compression = compression,
+        }
+        self
+    }
+
+    /// How the messages sent to the other hosts are compressed.
+    pub fn network_compression(&self) -> NetworkCompression {
+        match self {
+            RuntimeConfig::Local(_) => NetworkCompression::None,
+            RuntimeConfig::Remote(remote) => remote.compression,
+        }
+    }
+
+    /// Retry the connections with the other hosts following `retry`.
+    ///
+    /// A local environment has no connections, it ignores the configuration.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// # use renoir::config::NetworkRetryConfig;
+    /// let retry = NetworkRetryConfig {
+    ///     max_reconnections: 0,
+    ///     ..Default::default()
+    /// };
+    /// let config = RuntimeConfig::local(4).unwrap().with_network_retry(retry);
+    /// ```
+    pub fn with_network_retry(mut self, retry: NetworkRetryConfig) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(_) => {
+                log::warn!("the network retries are used only by the remote environments")
+            }
+            RuntimeConfig::Remote(remote) => remote.retry = retry,
+        }
+        self
+    }
+
+    /// How the connections with the other hosts are retried.
+    pub fn network_retry(&self) -> NetworkRetryConfig {
+        match self {
+            RuntimeConfig::Local(_) => Default::default(),
+            RuntimeConfig::Remote(remote) => remote.retry,
+        }
+    }
+
+    /// Multiplex the remote channels between two hosts over a single connection.
+    ///
+    /// By default each channel between the blocks of two hosts has its own connection, and each
+    /// host binds a port for each channel it receives from. With the multiplexing each host binds
+    /// only its `base_port` and shares one connection, used in both directions, with each host it
+    /// exchanges messages with, reducing the sockets and the handshakes of large jobs. The channels keep their flow control and resume
+    /// independently, but when the shared connection drops all of them reconnect.
+    ///
+    /// The multiplexing is not available with the `tokio` feature: enabling it panics, and the
+    /// configuration files that enable it are rejected.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// let config = RuntimeConfig::local(4)
+    ///     .unwrap()
+    ///     .with_connection_multiplexing(true);
+    /// ```
+    pub fn with_connection_multiplexing(mut self, enabled: bool) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(_) => {
+                log::warn!("the connection multiplexing is used only by the remote environments")
+            }
+            RuntimeConfig::Remote(remote) => {
+                assert!(
+                    !(enabled && cfg!(feature = "tokio")),
+                    "the connection multiplexing is not available with the tokio feature"
+                );
+                remote.multiplex_connections = enabled
+            }
+        }
+        self
+    }
+
+    /// Whether the remote channels between two hosts share a single connection.
+    pub fn connection_multiplexing(&self) -> bool {
+        match self {
+            RuntimeConfig::Local(_) => false,
+            RuntimeConfig::Remote(remote) => remote.multiplex_connections,
+        }
+    }
+
+    /// Store the keyed state of the operators in `backend`.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// # use renoir::config::StateBackendConfig;
+    /// let backend = StateBackendConfig::RocksDb {
+    ///     path: "/tmp/renoir-state".into(),
+    /// };
+    /// let config = RuntimeConfig::local(4).unwrap().with_state_backend(backend);
+    /// ```
+    pub fn with_state_backend(mut self, backend: StateBackendConfig) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(local) => local.state_backend = backend,
+            RuntimeConfig::Remote(remote) => remote.state_backend = backend,
+        }
+        self
+    }
+
+    /// Where the keyed state of the operators is stored.
+    pub fn state_backend(&self) -> &StateBackendConfig {
+        match self {
+            RuntimeConfig::Local(local) => &local.state_backend,
+            RuntimeConfig::Remote(remote) => &remote.state_backend,
+        }
+    }
+
+    /// Limit the memory used by the state of the operators of each replica, spilling it to disk
+    /// when the budget is exceeded.
+    ///
+    /// See [`MemoryBudgetConfig`] for which state is tracked. The memory is estimated from the size
+    /// of the structures and of the serialization of a sample of their items, so the budget is not
+    /// a hard limit. Only the state that is serializable can be spilled: the keyed state of
+    /// [`Stream::group_by_fold`](crate::Stream::group_by_fold) and of the joins. The windows
+    /// contain the user functions of the window managers, so they count toward the budget but they
+    /// are always kept in memory.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// # use renoir::config::MemoryBudgetConfig;
+    /// let budget = MemoryBudgetConfig::new(512 << 20, "/tmp/renoir-spill");
+    /// let config = RuntimeConfig::local(4).unwrap().with_memory_budget(budget);
+    /// ```
+    pub fn with_memory_budget(mut self, budget: MemoryBudgetConfig) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(local) => local.memory_budget = Some(budget),
+            RuntimeConfig::Remote(remote) => remote.memory_budget = Some(budget),
+        }
+        self
+    }
+
+    /// The memory budget of the state of the operators of each replica, if any.
+    pub fn memory_budget(&self) -> Option<&MemoryBudgetConfig> {
+        match self {
+            RuntimeConfig::Local(local) => local.memory_budget.as_ref(),
+            RuntimeConfig::Remote(remote) => remote.memory_budget.as_ref(),
+        }
+    }
+
+    /// Fuse the operators into blocks following `strategy`.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// # use renoir::config::ChainingStrategy;
+    /// let config = RuntimeConfig::local(4).unwrap().with_chaining(ChainingStrategy::Never);
+    /// ```
+    pub fn with_chaining(mut self, strategy: ChainingStrategy) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(local) => local.chaining = strategy,
+            RuntimeConfig::Remote(remote) => remote.chaining = strategy,
+        }
+        self
+    }
+
+    /// Let the idle replicas of a block steal the batches queued for their overloaded siblings on
+    /// the same host.
+    ///
+    /// This improves the utilization when the data is skewed, and it applies only where any replica
+    /// may process any item: to the blocks after a [`Stream::shuffle`](crate::Stream::shuffle) or
+    /// a [`Stream::rebalance`](crate::Stream::rebalance). Only the batches of plain items (without
+    /// timestamps) are stolen, and the stealing is disabled when the checkpoints are enabled.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// let config = RuntimeConfig::local(4).unwrap().with_work_stealing(true);
+    /// ```
+    pub fn with_work_stealing(mut self, enabled: bool) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(local) => local.work_stealing = enabled,
+            RuntimeConfig::Remote(remote) => remote.work_stealing = enabled,
+        }
+        self
+    }
+
+    /// Whether the idle replicas steal the batches queued for their siblings.
+    pub fn work_stealing(&self) -> bool {
+        match self {
+            RuntimeConfig::Local(local) => local.work_stealing,
+            RuntimeConfig::Remote(remote) => remote.work_stealing,
+        }
+    }
+
+    /// Pin the threads of the replicas to the cores following `pinning`.
+    ///
+    /// In a remote environment this applies to all the hosts, use the configuration file to pin
+    /// each host differently.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// # use renoir::config::PinningConfig;
+    /// let pinning = PinningConfig {
+    ///     cores: Some(vec![0, 2, 4, 6]),
+    ///     numa: false,
+    /// };
+    /// let config = RuntimeConfig::local(4).unwrap().with_pinning(pinning);
+    /// ```
+    pub fn with_pinning(mut self, pinning: PinningConfig) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(local) => local.pinning = Some(pinning),
+            RuntimeConfig::Remote(remote) => {
+                for host in &mut remote.hosts {
+                    host.pinning = Some(pinning.clone());
+                }
+            }
+        }
+        self
+    }
+
+    /// How the threads of the replicas are pinned to the cores of this host, if they are.
+    pub fn pinning(&self) -> Option<&PinningConfig> {
+        match self {
+            RuntimeConfig::Local(local) => local.pinning.as_ref(),
+            RuntimeConfig::Remote(remote) => {
+                let host_id = remote.host_id?;
+                remote.hosts[host_id as usize].pinning.as_ref()
+            }
+        }
+    }
+
+    /// Serve the metrics of the replicas at `/metrics` on `port`, in the Prometheus text format.
+    ///
+    /// In a remote environment each host serves its own metrics on the same port, use the
+    /// configuration file to use a different port for each host. See the `renoir_*` metrics for
+    /// what is exported.
+    ///
+    /// The endpoint is bound to localhost, see [`RuntimeConfig::with_metrics_address`] to expose it
+    /// to the other machines.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// let config = RuntimeConfig::local(4).unwrap().with_metrics_port(9091);
+    /// ```
+    pub fn with_metrics_port(mut self, port: u16) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(local) => local.metrics_port = Some(port),
+            RuntimeConfig::Remote(remote) => {
+                for host in &mut remote.hosts {
+                    host.metrics_port = Some(port);
+                }
+            }
+        }
+        self
+    }
+
+    /// Bind the metrics endpoint to `address` instead of localhost, e.g. `0.0.0.0` for all the
+    /// interfaces. It has no effect unless the endpoint is enabled with
+    /// [`RuntimeConfig::with_metrics_port`].
+    ///
+    /// ```
+    /// # use std::net::Ipv4Addr;
+    /// # use renoir::RuntimeConfig;
+    /// let config = RuntimeConfig::local(4)
+    ///     .unwrap()
+    ///     .with_metrics_port(9091)
+    ///     .with_metrics_address(Ipv4Addr::UNSPECIFIED.into());
+    /// ```
+    pub fn with_metrics_address(mut self, address: IpAddr) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(local) => local.metrics_address = Some(address),
+            RuntimeConfig::Remote(remote) => {
+                for host in &mut remote.hosts {
+                    host.metrics_address = Some(address);
+                }
+            }
+        }
+        self
+    }
+
+    /// The port of the metrics endpoint of this host, if it's enabled.
+    pub fn metrics_port(&self) -> Option<u16> {
+        match self {
+            RuntimeConfig::Local(local) => local.metrics_port,
+            RuntimeConfig::Remote(remote) => {
+                let host_id = remote.host_id?;
+                remote.hosts[host_id as usize].metrics_port
+            }
+        }
+    }
+
+    /// The address of the metrics endpoint of this host, if it's enabled.
+    pub fn metrics_address(&self) -> Option<SocketAddr> {
+        let (port, address) = match self {
+            RuntimeConfig::Local(local) => (local.metrics_port?, local.metrics_address),
+            RuntimeConfig::Remote(remote) => {
+                let host = &remote.hosts[remote.host_id? as usize];
+                (host.metrics_port?, host.metrics_address)
+            }
+        };
+        let address = address.unwrap_or(Ipv4Addr::LOCALHOST.into());
+        Some(SocketAddr::new(address, port))
+    }
+
+    /// The certificates used to encrypt the connections of this host, if they are encrypted.
+    pub fn tls(&self) -> Option<&TlsConfig> {
+        match self {
+            RuntimeConfig::Local(_) => None,
+            RuntimeConfig::Remote(remote) => {
+                let host_id = remote.host_id?;
+                remote.hosts[host_id as usize].tls.as_ref()
+            }
+        }
+    }
+
+    /// Export the spans of the execution to the OpenTelemetry collector at `endpoint`, using OTLP
+    /// over HTTP (e.g. `http://localhost:4318/v1/traces`).
+    ///
+    /// Each replica has a span, with the spans of the batches it produces and of the messages it
+    /// sends through the network as children, all with the coordinates of the replica as
+    /// attributes. This requires the `opentelemetry` feature, otherwise the endpoint is ignored.
+    ///
+    /// ```
+    /// # use renoir::RuntimeConfig;
+    /// let config = RuntimeConfig::local(4)
+    ///     .unwrap()
+    ///     .with_otlp_endpoint("http://localhost:4318/v1/traces");
+    /// ```
+    pub fn with_otlp_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        match &mut self {
+            RuntimeConfig::Local(local) => local.otlp_endpoint = Some(endpoint.into()),
+            RuntimeConfig::Remote(remote) => remote.otlp_endpoint = Some(endpoint.into()),
+        }
+        self
+    }
+
+    /// The OTLP endpoint the spans are exported to, if any.
+    pub fn otlp_endpoint(&self) -> Option<&str> {
+        match self {
+            RuntimeConfig::Local(local) => local.otlp_endpoint.as_deref(),
+            RuntimeConfig::Remote(remote) => remote.otlp_endpoint.as_deref(),
+        }
+    }
+
+    /// How the operators are fused into blocks.
+    pub fn chaining(&self) -> ChainingStrategy {
+        match self {
+            RuntimeConfig::Local(local) => local.chaining,
+            RuntimeConfig::Remote(remote) => remote.chaining,
+        }
+    }
+
+    /// The configuration of the checkpoints, if they are enabled.
+    pub fn checkpoint(&self) -> Option<&CheckpointConfig> {
+        match self {
+            RuntimeConfig::Local(local) => local.checkpoint.as_ref(),
+            RuntimeConfig::Remote(remote) => remote.checkpoint.as_ref(),
+        }
+    }
+
+    /// The delivery guarantee of the job, at-most-once if the checkpoints are not enabled.
+    pub fn delivery_guarantee(&self) -> DeliveryGuarantee {
+        self.checkpoint()
+            .map(|checkpoint| checkpoint.guarantee)
+            .unwrap_or(DeliveryGuarantee::AtMostOnce)
+    }
+
+    /// The checkpoints completed by all the hosts, sorted by id.
+    ///
+    /// This is empty if the checkpoints are not enabled.
+    pub fn completed_checkpoints(&self) -> std::io::Result<Vec<CheckpointId>> {
+        match self.checkpoint() {
+            Some(checkpoint) => completed_checkpoints(&checkpoint.path, self.num_hosts()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The number of hosts of the environment.
+    pub(crate) fn num_hosts(&self) -> usize {
+        match self {
+            RuntimeConfig::Local(_) => 1,
+            RuntimeConfig::Remote(remote) => remote.hosts.len(),
+        }
+    }
+}
+
+impl HostConfig {
+    /// A host reachable at `address`, with `num_cores` cores, binding the ports from `base_port`.
+    ///
+    /// The workers are deployed via SSH with the default configuration.
+    pub fn new(address: impl Into<String>, base_port: u16, num_cores: CoordUInt) -> Self {
+        Self {
+            address: address.into(),
+            base_port,
+            num_cores,
+            ssh: Default::default(),
+            perf_path: None,
+            pinning: None,
+            metrics_port: None,
+            metrics_address: None,
+            tls: None,
+            daemon_port: None,
+            daemon_secret: None,
+        }
+    }
+
+    /// Connect to the host via SSH with `ssh`.
+    pub fn ssh(mut self, ssh: SSHConfig) -> Self {
+        self.ssh = ssh;
+        self
+    }
+
+    /// Spawn the worker under `perf`, storing its output at `path`.
+    pub fn perf_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.perf_path = Some(path.into());
+        self
+    }
+
+    /// Pin the threads of the replicas to the cores of the host.
+    pub fn pinning(mut self, pinning: PinningConfig) -> Self {
+        self.pinning = Some(pinning);
+        self
+    }
+
+    /// Serve the metrics of the replicas of the host on `port`.
+    pub fn metrics_port(mut self, port: u16) -> Self {
+        self.metrics_port = Some(port);
+        self
+    }
+
+    /// Bind the metrics endpoint of the host to `address` instead of localhost.
+    pub fn metrics_address(mut self, address: IpAddr) -> Self {
+        self.metrics_address = Some(address);
+        self
+    }
+
+    /// Encrypt the connections with the other hosts with `tls`.
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Submit the jobs to the worker daemon listening on `port`, authenticating with `secret`.
+    pub fn daemon(mut self, port: u16, secret: Option<String>) -> Self {
+        self.daemon_port = Some(port);
+        self.daemon_secret = secret;
+        self
+    }
 }
 
 impl Display for HostConfig {
@@ -304,6 +1390,17 @@ pub struct ConfigBuilder {
     hosts: Vec<HostConfig>,
     tracing_dir: Option<PathBuf>,
     cleanup_executable: bool,
+    checkpoint: Option<CheckpointConfig>,
+    restart: Option<RestartPolicy>,
+    heartbeat: Option<HeartbeatConfig>,
+    state_backend: Option<StateBackendConfig>,
+    memory_budget: Option<MemoryBudgetConfig>,
+    chaining: Option<ChainingStrategy>,
+    otlp_endpoint: Option<String>,
+    compression: Option<NetworkCompression>,
+    retry: Option<NetworkRetryConfig>,
+    work_stealing: bool,
+    multiplex_connections: bool,
 }
 
 impl ConfigBuilder {
@@ -313,7 +1410,18 @@ impl ConfigBuilder {
                 "The number of cores should be positive".into(),
             ))
         } else {
-            Ok(RuntimeConfig::Local(LocalConfig { parallelism }))
+            Ok(RuntimeConfig::Local(LocalConfig {
+                parallelism,
+                checkpoint: None,
+                state_backend: Default::default(),
+                memory_budget: None,
+                chaining: Default::default(),
+                pinning: None,
+                metrics_port: None,
+                metrics_address: None,
+                otlp_endpoint: None,
+                work_stealing: false,
+            }))
         }
     }
 
@@ -323,6 +1431,17 @@ impl ConfigBuilder {
             hosts: Vec::new(),
             tracing_dir: None,
             cleanup_executable: false,
+            checkpoint: None,
+            restart: None,
+            heartbeat: None,
+            state_backend: None,
+            memory_budget: None,
+            chaining: None,
+            otlp_endpoint: None,
+            compression: None,
+            retry: None,
+            work_stealing: false,
+            multiplex_connections: false,
         }
     }
     /// Parse toml and integrate it in the builder.
@@ -334,6 +1453,17 @@ impl ConfigBuilder {
             hosts,
             tracing_dir,
             cleanup_executable,
+            checkpoint,
+            restart,
+            heartbeat,
+            state_backend,
+            memory_budget,
+            chaining,
+            otlp_endpoint,
+            compression,
+            retry,
+            work_stealing,
+            multiplex_connections,
         } = toml::from_str(config_str)?;
 
         // validate the configuration
@@ -348,6 +1478,29 @@ impl ConfigBuilder {
         }
         self.tracing_dir = self.tracing_dir.take().or(tracing_dir);
         self.cleanup_executable |= cleanup_executable;
+        self.checkpoint = self.checkpoint.take().or(checkpoint);
+        self.restart = self.restart.take().or(restart);
+        self.heartbeat = self.heartbeat.take().or(heartbeat);
+        self.state_backend = self
+            .state_backend
+            .take()
+            .or((state_backend != StateBackendConfig::Memory).then_some(state_backend));
+        self.memory_budget = self.memory_budget.take().or(memory_budget);
+        self.chaining = self
+            .chaining
+            .take()
+            .or((chaining != ChainingStrategy::Hints).then_some(chaining));
+        self.otlp_endpoint = self.otlp_endpoint.take().or(otlp_endpoint);
+        self.compression = self
+            .compression
+            .take()
+            .or((compression != NetworkCompression::None).then_some(compression));
+        self.retry = self
+            .retry
+            .take()
+            .or((retry != NetworkRetryConfig::default()).then_some(retry));
+        self.work_stealing |= work_stealing;
+        self.multiplex_connections |= multiplex_connections;
 
         Ok(self)
     }
@@ -396,12 +1549,53 @@ impl ConfigBuilder {
                 )));
             }
         };
+        if self
+            .memory_budget
+            .as_ref()
+            .is_some_and(|budget| budget.limit == 0)
+        {
+            return Err(ConfigError::Invalid(
+                "the memory budget should be positive".into(),
+            ));
+        }
+        if self.multiplex_connections && cfg!(feature = "tokio") {
+            return Err(ConfigError::Invalid(
+                "the connection multiplexing is not available with the tokio feature".into(),
+            ));
+        }
+        let encrypted = self.hosts.iter().filter(|host| host.tls.is_some()).count();
+        if encrypted != 0 && encrypted != self.hosts.len() {
+            let plain = self.hosts.iter().find(|host| host.tls.is_none()).unwrap();
+            return Err(ConfigError::Invalid(format!(
+                "the connections must be encrypted for all the hosts or for none: {} hosts have a tls configuration, but {} doesn't",
+                encrypted, plain.address
+            )));
+        }
+        if let Some(heartbeat) = self.heartbeat {
+            if heartbeat.timeout <= heartbeat.interval {
+                return Err(ConfigError::Invalid(format!(
+                    "the heartbeat timeout ({:?}) must be longer than the interval ({:?})",
+                    heartbeat.timeout, heartbeat.interval
+                )));
+            }
+        }
 
         let conf = RuntimeConfig::Remote(RemoteConfig {
             host_id: self.host_id,
             hosts: self.hosts.clone(),
             tracing_dir: self.tracing_dir.clone(),
             cleanup_executable: self.cleanup_executable,
+            checkpoint: self.checkpoint.clone(),
+            restart: self.restart.clone(),
+            heartbeat: self.heartbeat,
+            state_backend: self.state_backend.clone().unwrap_or_default(),
+            memory_budget: self.memory_budget.clone(),
+            chaining: self.chaining.unwrap_or_default(),
+            otlp_endpoint: self.otlp_endpoint.clone(),
+            compression: self.compression.unwrap_or_default(),
+            retry: self.retry.unwrap_or_default(),
+            work_stealing: self.work_stealing,
+            multiplex_connections: self.multiplex_connections,
         });
         Ok(conf)
     }
@@ -412,6 +1606,28 @@ fn ssh_default_port() -> u16 {
     22
 }
 
+/// Default interval between the checkpoints, used by the serde default value.
+fn checkpoint_default_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn restart_default_multiplier() -> u32 {
+    2
+}
+
+/// (De)serialize a `Duration` as a number of milliseconds.
+mod duration_millis {
+    use super::*;
+
+    pub(super) fn serialize<S: Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(duration.as_millis() as u64)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        u64::deserialize(d).map(Duration::from_millis)
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
     #[error("Serialization error: {0}")]
@@ -426,3 +1642,141 @@ pub enum ConfigError {
     #[error("Missing environment variable {0}: {1}")]
     Environment(String, env::VarError),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_policy_delay() {
+        let fixed = RestartPolicy::fixed_delay(Duration::from_secs(5), 2);
+        assert_eq!(fixed.delay(1), Some(Duration::from_secs(5)));
+        assert_eq!(fixed.delay(2), Some(Duration::from_secs(5)));
+        assert_eq!(fixed.delay(3), None);
+
+        let backoff =
+            RestartPolicy::exponential_backoff(Duration::from_secs(1), Duration::from_secs(5), 100);
+        assert_eq!(backoff.delay(1), Some(Duration::from_secs(1)));
+        assert_eq!(backoff.delay(2), Some(Duration::from_secs(2)));
+        assert_eq!(backoff.delay(3), Some(Duration::from_secs(4)));
+        assert_eq!(backoff.delay(4), Some(Duration::from_secs(5)));
+        // the delay saturates instead of overflowing
+        assert_eq!(backoff.delay(100), Some(Duration::from_secs(5)));
+        assert_eq!(backoff.delay(101), None);
+    }
+
+    #[test]
+    fn restart_policy_toml() {
+        let mut builder = ConfigBuilder::new_remote();
+        builder
+            .parse_toml_str(
+                r#"
+                host = []
+
+                [restart]
+                strategy = "exponential_backoff"
+                initial_delay_ms = 1000
+                max_delay_ms = 60000
+                max_attempts = 5
+                "#,
+            )
+            .unwrap();
+        let expected =
+            RestartPolicy::exponential_backoff(Duration::from_secs(1), Duration::from_secs(60), 5);
+        assert_eq!(builder.restart, Some(expected));
+    }
+
+    #[test]
+    fn heartbeat_toml() {
+        let mut builder = ConfigBuilder::new_remote();
+        builder
+            .parse_toml_str("host = []\n[heartbeat]\ntimeout_ms = 3000")
+            .unwrap();
+        let expected = HeartbeatConfig {
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(3),
+        };
+        assert_eq!(builder.heartbeat, Some(expected));
+
+        let mut builder = ConfigBuilder::new_remote();
+        builder
+            .parse_toml_str("host = []\n[heartbeat]\ninterval_ms = 3000\ntimeout_ms = 3000")
+            .unwrap();
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn memory_budget_toml() {
+        let mut builder = ConfigBuilder::new_remote();
+        builder
+            .parse_toml_str("host = []\n[memory_budget]\nlimit = 1024\nspill_path = \"/tmp/spill\"")
+            .unwrap();
+        let config = builder.build().unwrap();
+        assert_eq!(
+            config.memory_budget(),
+            Some(&MemoryBudgetConfig::new(1024, "/tmp/spill"))
+        );
+        assert_eq!(RuntimeConfig::local(2).unwrap().memory_budget(), None);
+
+        let mut builder = ConfigBuilder::new_remote();
+        builder
+            .parse_toml_str("host = []\n[memory_budget]\nlimit = 0\nspill_path = \"/tmp/spill\"")
+            .unwrap();
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn tls_all_hosts_or_none() {
+        let tls = TlsConfig {
+            cert: "host.pem".into(),
+            key: "host.key".into(),
+            ca: "ca.pem".into(),
+            mutual: false,
+        };
+        let plain = HostConfig::new("host1", 9500, 4);
+        let encrypted = HostConfig::new("host2", 9500, 4).tls(tls);
+
+        let mut builder = ConfigBuilder::new_remote();
+        builder.add_hosts(&[encrypted.clone(), plain.clone()]);
+        assert!(builder.build().is_err());
+
+        for hosts in [[plain.clone(), plain], [encrypted.clone(), encrypted]] {
+            let mut builder = ConfigBuilder::new_remote();
+            builder.add_hosts(&hosts).host_id(1);
+            let config = builder.build().unwrap();
+            assert_eq!(config.tls(), hosts[1].tls.as_ref());
+        }
+    }
+
+    #[test]
+    fn network_retry() {
+        let mut builder = ConfigBuilder::new_remote();
+        builder
+            .parse_toml_str("host = []\n[retry]\nmax_reconnections = 2\nmax_delay_ms = 100")
+            .unwrap();
+        let retry = builder.build().unwrap().network_retry();
+        assert_eq!(retry.max_reconnections, 2);
+        assert_eq!(retry.connect_attempts, 32);
+        assert_eq!(retry.delay(1), Duration::from_millis(8));
+        assert_eq!(retry.delay(4), Duration::from_millis(64));
+        assert_eq!(retry.delay(5), Duration::from_millis(100));
+        assert_eq!(retry.delay(100), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn network_compression_toml() {
+        let mut builder = ConfigBuilder::new_remote();
+        builder
+            .parse_toml_str(
+                "host = []
+compression = \"zstd\"",
+            )
+            .unwrap();
+        let config = builder.build().unwrap();
+        assert_eq!(config.network_compression(), NetworkCompression::Zstd);
+        assert_eq!(
+            RuntimeConfig::local(2).unwrap().network_compression(),
+            NetworkCompression::None
+        );
+    }
+}
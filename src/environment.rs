@@ -3,8 +3,11 @@ use std::any::TypeId;
 use std::sync::Arc;
 
 use crate::block::{Block, Scheduling};
+use crate::cancellation::{CancellationToken, ExecutionStatus, PauseHandle};
+use crate::checkpoint::SavepointTrigger;
 use crate::config::RuntimeConfig;
 use crate::operator::iteration::IterationStateLock;
+use crate::operator::sink::{MetricsRegistry, StreamMetrics};
 use crate::operator::source::Source;
 use crate::operator::{Data, Operator};
 use crate::scheduler::{BlockId, Scheduler};
@@ -23,6 +26,8 @@ pub(crate) struct StreamContextInner {
     /// The scheduler that will start the computation. It's an option because it will be moved out
     /// of this struct when the computation starts.
     scheduler: Option<Scheduler>,
+    /// The metrics registered by `inspect_metrics`.
+    pub(crate) metrics: Arc<MetricsRegistry>,
 }
 
 /// Streaming environment from which it's possible to register new streams and start the
@@ -47,8 +52,15 @@ impl StreamContext {
     /// Construct a new environment from the config.
     pub fn new(config: impl Into<Arc<RuntimeConfig>>) -> Self {
         debug!("new environment");
+        let config = config.into();
+        if let Some(endpoint) = config.otlp_endpoint() {
+            #[cfg(feature = "opentelemetry")]
+            crate::telemetry::init_from_config(endpoint, config.host_id().unwrap_or(0));
+            #[cfg(not(feature = "opentelemetry"))]
+            log::warn!("exporting the spans to {endpoint} requires the `opentelemetry` feature");
+        }
         StreamContext {
-            inner: Arc::new(Mutex::new(StreamContextInner::new(config.into()))),
+            inner: Arc::new(Mutex::new(StreamContextInner::new(config))),
         }
     }
 
@@ -77,27 +89,103 @@ impl StreamContext {
     }
 
     /// Start the computation. Await on the returned future to actually start the computation.
+    ///
+    /// Returns whether the job completed or was cancelled.
     #[cfg(feature = "tokio")]
-    pub async fn execute(self) {
+    pub async fn execute(self) -> ExecutionStatus {
         let mut ctx = self.inner.lock();
         info!("starting execution ({} blocks)", ctx.block_count);
         let scheduler = ctx.scheduler.take().unwrap();
         let block_count = ctx.block_count;
         drop(ctx);
-        scheduler.start(block_count).await;
-        info!("finished execution");
+        let status = scheduler.start(block_count).await;
+        #[cfg(feature = "opentelemetry")]
+        crate::telemetry::flush();
+        info!("finished execution ({status:?})");
+        status
+    }
+
+    /// Start the computation on a tokio runtime shared with the rest of the application, instead
+    /// of creating a new one. Blocks until the computation is complete.
+    ///
+    /// The network tasks and the async operators run on `runtime`. This must be called outside
+    /// of an async context, from an async context use [`execute`](StreamContext::execute).
+    ///
+    /// Returns whether the job completed or was cancelled.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::StreamContext;
+    /// let runtime = tokio::runtime::Runtime::new().unwrap();
+    ///
+    /// let env = StreamContext::new_local();
+    /// let res = env.stream_iter(0..5u32).collect_vec();
+    /// env.execute_on(runtime.handle());
+    /// assert_eq!(res.get().unwrap(), vec![0, 1, 2, 3, 4]);
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn execute_on(self, runtime: &tokio::runtime::Handle) -> ExecutionStatus {
+        let mut env = self.inner.lock();
+        info!("starting execution ({} blocks)", env.block_count);
+        let scheduler = env.scheduler.take().unwrap();
+        let block_count = env.block_count;
+        drop(env);
+        let status = scheduler.start_on(runtime, block_count);
+        #[cfg(feature = "opentelemetry")]
+        crate::telemetry::flush();
+        info!("finished execution ({status:?})");
+        status
     }
 
     /// Start the computation. Blocks until the computation is complete.
     ///
     /// Execute on a thread or use the async version [`execute`]
     /// for non-blocking alternatives
-    pub fn execute_blocking(self) {
+    ///
+    /// Returns whether the job completed or was cancelled.
+    pub fn execute_blocking(self) -> ExecutionStatus {
         let mut env = self.inner.lock();
         info!("starting execution ({} blocks)", env.block_count);
         let scheduler = env.scheduler.take().unwrap();
-        scheduler.start_blocking(env.block_count);
-        info!("finished execution");
+        let status = scheduler.start_blocking(env.block_count);
+        #[cfg(feature = "opentelemetry")]
+        crate::telemetry::flush();
+        info!("finished execution ({status:?})");
+        status
+    }
+
+    /// Get a [`SavepointTrigger`] to take a savepoint while the job is running.
+    ///
+    /// The checkpoints must be enabled in the config, see
+    /// [`RuntimeConfig::with_checkpoints`].
+    pub fn savepoint_trigger(&self) -> SavepointTrigger {
+        self.inner.lock().scheduler_mut().savepoint_trigger()
+    }
+
+    /// Get a [`CancellationToken`] to stop the job while it's running.
+    ///
+    /// The sources stop emitting, the batches in flight are delivered and the sinks flush their
+    /// output before the execution returns [`ExecutionStatus::Cancelled`].
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.inner.lock().scheduler_mut().cancellation_token()
+    }
+
+    /// Get a [`PauseHandle`] to suspend the job while it's running and resume it later.
+    ///
+    /// While the job is paused the sources flush their output and stop reading their input, so the
+    /// data in flight drains through the pipeline while the operators keep their state.
+    pub fn pause_handle(&self) -> PauseHandle {
+        self.inner.lock().scheduler_mut().pause_handle()
+    }
+
+    /// Get the [`StreamMetrics`] registered with `name` by
+    /// [`Stream::inspect_metrics`](crate::Stream::inspect_metrics), if any.
+    ///
+    /// The metrics belong to this environment: they start from zero in each job, even with the
+    /// same name.
+    pub fn metrics(&self, name: &str) -> Option<Arc<StreamMetrics>> {
+        self.inner.lock().metrics.get(name)
     }
 
     /// Get the total number of processing cores in the cluster.
@@ -111,10 +199,13 @@ impl StreamContext {
 
 impl StreamContextInner {
     fn new(config: Arc<RuntimeConfig>) -> Self {
+        let metrics = Arc::new(MetricsRegistry::default());
+        crate::profiler::metrics::register_environment(&metrics);
         Self {
             config: config.clone(),
             block_count: 0,
             scheduler: Some(Scheduler::new(config)),
+            metrics,
         }
     }
 
@@ -121,14 +121,19 @@ pub use block::structure;
 pub use block::BatchMode;
 pub use block::Replication;
 pub use block::{group_by_hash, GroupHasherBuilder};
+pub use cancellation::{CancellationToken, ExecutionStatus, PauseHandle};
 pub use config::RuntimeConfig;
 pub use environment::StreamContext;
 pub use operator::iteration::IterationStateHandle;
 pub use scheduler::ExecutionMetadata;
 pub use stream::{KeyedStream, Stream, WindowedStream};
 
+pub(crate) mod affinity;
 pub(crate) mod block;
+pub(crate) mod cancellation;
 pub(crate) mod channel;
+pub mod checkpoint;
+pub mod cluster;
 pub mod config;
 #[path = "../dsl/mod.rs"]
 pub mod dsl;
@@ -139,7 +144,11 @@ mod profiler;
 #[cfg(feature = "ssh")]
 pub(crate) mod runner;
 pub(crate) mod scheduler;
+pub(crate) mod spill;
+pub mod state;
 pub(crate) mod stream;
+#[cfg(feature = "opentelemetry")]
+pub mod telemetry;
 #[cfg(test)]
 pub(crate) mod test;
 pub(crate) mod worker;
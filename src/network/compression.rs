@@ -0,0 +1,182 @@
+//! Compression of the messages of the remote channels.
+//!
+//! When a multiplexer connects to a demultiplexer it sends the tag of the codec it wants to use,
+//! and the demultiplexer answers with the codec of the connection: the proposed one if it can
+//! decompress it, `None` otherwise. With a codec each message starts with a tag byte, telling
+//! whether the rest of the message is compressed.
+
+use std::borrow::Cow;
+
+use crate::config::NetworkCompression;
+
+/// The messages smaller than this are sent uncompressed, the codecs wouldn't shrink them.
+const MIN_COMPRESSED_SIZE: usize = 256;
+/// The compression level of zstd, the default of the library.
+#[cfg(feature = "network_compression")]
+const ZSTD_LEVEL: i32 = 3;
+
+impl NetworkCompression {
+    /// The byte identifying the codec in the negotiation and in the messages.
+    pub(crate) fn tag(self) -> u8 {
+        match self {
+            NetworkCompression::None => 0,
+            NetworkCompression::Lz4 => 1,
+            NetworkCompression::Zstd => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(NetworkCompression::None),
+            1 => Some(NetworkCompression::Lz4),
+            2 => Some(NetworkCompression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Whether this build can compress and decompress with this codec.
+    pub(crate) fn is_supported(self) -> bool {
+        match self {
+            NetworkCompression::None => true,
+            NetworkCompression::Lz4 | NetworkCompression::Zstd => {
+                cfg!(feature = "network_compression")
+            }
+        }
+    }
+
+    /// The codec of a connection where the multiplexer proposed the codec `tag`.
+    pub(crate) fn negotiate(tag: u8) -> Self {
+        Self::from_tag(tag)
+            .filter(|codec| codec.is_supported())
+            .unwrap_or(NetworkCompression::None)
+    }
+
+    /// The codec the multiplexer proposes, `None` if this build cannot use the configured one.
+    pub(crate) fn proposal(self) -> Self {
+        if self.is_supported() {
+            self
+        } else {
+            log::warn!(
+                "the {self:?} network compression requires the `network_compression` feature"
+            );
+            NetworkCompression::None
+        }
+    }
+
+    /// The codec of a connection, from the answer of the demultiplexer.
+    pub(crate) fn accepted(tag: u8) -> Self {
+        Self::from_tag(tag).expect("malformed compression negotiation")
+    }
+}
+
+/// Compress the message in `buf[start..]` in place, prefixing it with the tag of its codec.
+///
+/// Nothing is done without a codec.
+pub(crate) fn compress(codec: NetworkCompression, buf: &mut Vec<u8>, start: usize) {
+    if codec == NetworkCompression::None {
+        return;
+    }
+    let len = buf.len() - start;
+    let compressed = (len >= MIN_COMPRESSED_SIZE)
+        .then(|| compress_with(codec, &buf[start..]))
+        .filter(|compressed| compressed.len() < len);
+    match compressed {
+        Some(compressed) => {
+            buf.truncate(start);
+            buf.push(codec.tag());
+            buf.extend_from_slice(&compressed);
+        }
+        None => buf.insert(start, NetworkCompression::None.tag()),
+    }
+}
+
+/// Decompress a message produced by `compress` with `codec`.
+pub(crate) fn decompress(codec: NetworkCompression, message: &[u8]) -> Cow<'_, [u8]> {
+    if codec == NetworkCompression::None {
+        return Cow::Borrowed(message);
+    }
+    let (&tag, data) = message.split_first().expect("Malformed message");
+    match NetworkCompression::from_tag(tag) {
+        Some(NetworkCompression::None) => Cow::Borrowed(data),
+        Some(codec) if codec.is_supported() => Cow::Owned(decompress_with(codec, data)),
+        _ => panic!("Malformed message: unknown compression {tag}"),
+    }
+}
+
+#[cfg(feature = "network_compression")]
+fn compress_with(codec: NetworkCompression, data: &[u8]) -> Vec<u8> {
+    match codec {
+        NetworkCompression::None => data.to_vec(),
+        NetworkCompression::Lz4 => lz4_flex::compress_prepend_size(data),
+        NetworkCompression::Zstd => {
+            zstd::bulk::compress(data, ZSTD_LEVEL).expect("Failed to compress message")
+        }
+    }
+}
+
+#[cfg(feature = "network_compression")]
+fn decompress_with(codec: NetworkCompression, data: &[u8]) -> Vec<u8> {
+    match codec {
+        NetworkCompression::None => data.to_vec(),
+        NetworkCompression::Lz4 => {
+            lz4_flex::decompress_size_prepended(data).expect("Malformed compressed message")
+        }
+        NetworkCompression::Zstd => zstd::decode_all(data).expect("Malformed compressed message"),
+    }
+}
+
+// without the feature the connections never negotiate a codec
+#[cfg(not(feature = "network_compression"))]
+fn compress_with(codec: NetworkCompression, _data: &[u8]) -> Vec<u8> {
+    unreachable!("{codec:?} requires the `network_compression` feature")
+}
+
+#[cfg(not(feature = "network_compression"))]
+fn decompress_with(codec: NetworkCompression, _data: &[u8]) -> Vec<u8> {
+    unreachable!("{codec:?} requires the `network_compression` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiation() {
+        assert_eq!(NetworkCompression::negotiate(0), NetworkCompression::None);
+        assert_eq!(NetworkCompression::negotiate(42), NetworkCompression::None);
+        let expected = if cfg!(feature = "network_compression") {
+            NetworkCompression::Zstd
+        } else {
+            NetworkCompression::None
+        };
+        assert_eq!(NetworkCompression::negotiate(2), expected);
+        assert_eq!(NetworkCompression::Zstd.proposal(), expected);
+    }
+
+    #[test]
+    fn uncompressed_messages() {
+        let mut buf = vec![9, 9, 1, 2, 3];
+        compress(NetworkCompression::None, &mut buf, 2);
+        assert_eq!(buf, [9, 9, 1, 2, 3]);
+        assert_eq!(&*decompress(NetworkCompression::None, &buf[2..]), [1, 2, 3]);
+
+        // too small to be compressed
+        compress(NetworkCompression::Lz4, &mut buf, 2);
+        assert_eq!(buf, [9, 9, 0, 1, 2, 3]);
+        assert_eq!(&*decompress(NetworkCompression::Lz4, &buf[2..]), [1, 2, 3]);
+    }
+
+    #[cfg(feature = "network_compression")]
+    #[test]
+    fn compressed_messages() {
+        let message: Vec<u8> = (0..10_000).map(|i| (i % 7) as u8).collect();
+        for codec in [NetworkCompression::Lz4, NetworkCompression::Zstd] {
+            let mut buf = vec![0; 4];
+            buf.extend_from_slice(&message);
+            compress(codec, &mut buf, 4);
+            assert!(buf.len() < message.len() / 10);
+            assert_eq!(buf[4], codec.tag());
+            assert_eq!(&*decompress(codec, &buf[4..]), &message[..]);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::network::ReceiverEndpoint;
+
+/// The number of messages a multiplexer can send to a remote receiver before receiving new
+/// credits: the same capacity of the local channels.
+pub(crate) const INITIAL_CREDITS: usize = super::network_channel::CHANNEL_CAPACITY;
+/// The number of messages waiting for credits for a single receiver after which the multiplexer
+/// stops accepting new messages, blocking the senders.
+pub(crate) const MAX_PENDING: usize = 16;
+
+/// Credit-based flow control of a multiplexed remote channel.
+///
+/// The multiplexer can send a message to a remote receiver only if it has a credit for it, and
+/// each message consumes a credit. The demultiplexer gives back a credit to the multiplexer each
+/// time it delivers a message to the local receiver, so each receiver has at most
+/// `INITIAL_CREDITS` messages in flight. The messages without credits are kept here, until the
+/// queue of a receiver has `MAX_PENDING` messages: then the multiplexer stops accepting messages,
+/// so a slow receiver throttles its senders without blocking the other receivers of the same
+/// connection and without unbounded buffering.
+#[derive(Debug)]
+pub(crate) struct CreditGate<T> {
+    initial_credits: usize,
+    credits: HashMap<ReceiverEndpoint, usize>,
+    pending: HashMap<ReceiverEndpoint, VecDeque<T>>,
+    /// The number of receivers with `MAX_PENDING` pending messages.
+    saturated: usize,
+}
+
+impl<T> CreditGate<T> {
+    pub(crate) fn new(initial_credits: usize) -> Self {
+        Self {
+            initial_credits,
+            credits: Default::default(),
+            pending: Default::default(),
+            saturated: 0,
+        }
+    }
+
+    /// Add a message for `dest`, returning it if it can be sent now.
+    pub(crate) fn push(&mut self, dest: ReceiverEndpoint, message: T) -> Option<T> {
+        let credits = self.credits.entry(dest).or_insert(self.initial_credits);
+        let pending = self.pending.entry(dest).or_default();
+        if *credits > 0 && pending.is_empty() {
+            *credits -= 1;
+            return Some(message);
+        }
+        pending.push_back(message);
+        if pending.len() == MAX_PENDING {
+            self.saturated += 1;
+        }
+        None
+    }
+
+    /// Add `credits` credits for `dest`, returning the pending messages that can be sent now.
+    pub(crate) fn grant(&mut self, dest: ReceiverEndpoint, credits: usize) -> Vec<T> {
+        let available = self.credits.entry(dest).or_insert(self.initial_credits);
+        *available += credits;
+        let Some(pending) = self.pending.get_mut(&dest) else {
+            return Vec::new();
+        };
+        let was_saturated = pending.len() >= MAX_PENDING;
+        let n = (*available).min(pending.len());
+        *available -= n;
+        let ready: Vec<_> = pending.drain(..n).collect();
+        if was_saturated && pending.len() < MAX_PENDING {
+            self.saturated -= 1;
+        }
+        ready
+    }
+
+    /// Whether the queue of some receiver is full: no message should be accepted until it
+    /// receives new credits.
+    pub(crate) fn is_saturated(&self) -> bool {
+        self.saturated > 0
+    }
+
+    /// Whether some messages are waiting for credits.
+    pub(crate) fn has_pending(&self) -> bool {
+        self.pending.values().any(|p| !p.is_empty())
+    }
+
+    /// Whether all the messages have been sent and delivered: none is pending and all the
+    /// credits are back.
+    pub(crate) fn is_idle(&self) -> bool {
+        !self.has_pending() && self.credits.values().all(|&c| c >= self.initial_credits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Coord;
+
+    fn endpoint(replica_id: u64) -> ReceiverEndpoint {
+        ReceiverEndpoint::new(Coord::new(1, 0, replica_id), 0)
+    }
+
+    #[test]
+    fn credit_gate() {
+        let (a, b) = (endpoint(0), endpoint(1));
+        let mut gate = CreditGate::new(2);
+
+        assert_eq!(gate.push(a, 1), Some(1));
+        assert_eq!(gate.push(a, 2), Some(2));
+        // no more credits for a, but b is not blocked
+        assert_eq!(gate.push(a, 3), None);
+        assert_eq!(gate.push(b, 4), Some(4));
+        assert!(gate.has_pending());
+
+        // the pending messages are sent in order
+        assert_eq!(gate.push(a, 5), None);
+        assert_eq!(gate.grant(a, 1), vec![3]);
+        assert_eq!(gate.grant(a, 2), vec![5]);
+        assert!(!gate.has_pending());
+        assert!(!gate.is_idle());
+        assert_eq!(gate.push(a, 6), Some(6));
+        assert_eq!(gate.push(a, 7), None);
+
+        for i in 1..=MAX_PENDING {
+            assert!(!gate.is_saturated());
+            assert_eq!(gate.push(b, i), if i == 1 { Some(1) } else { None });
+        }
+        assert_eq!(gate.push(b, 0), None);
+        assert!(gate.is_saturated());
+        assert_eq!(gate.grant(b, 1).len(), 1);
+        assert!(!gate.is_saturated());
+    }
+}
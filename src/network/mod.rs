@@ -1,10 +1,14 @@
 use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
 pub(crate) use network_channel::*;
+pub(crate) use stealing::Stealer;
 pub(crate) use topology::*;
 
+use crate::checkpoint::CheckpointId;
+use crate::config::{NetworkCompression, NetworkRetryConfig};
 use crate::operator::StreamElement;
 use crate::scheduler::{BlockId, HostId, ReplicaId};
 
@@ -18,7 +22,12 @@ mod sync;
 #[cfg(not(feature = "tokio"))]
 use sync::*;
 
+mod compression;
+mod credit;
 mod network_channel;
+mod resume;
+mod stealing;
+mod tls;
 mod topology;
 
 #[derive(Debug, Clone)]
@@ -39,6 +48,8 @@ impl<T> Iterator for NetworkDataIterator<T> {
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize)]
 pub enum NetworkData<T> {
     Batch(Vec<T>),
+    /// The barrier of a checkpoint, it separates the messages before and after the checkpoint.
+    Barrier(CheckpointId),
 }
 
 /// What is sent from a replica to the next.
@@ -112,9 +123,26 @@ impl<T> NetworkMessage<T> {
         }
     }
 
+    /// The barrier of the checkpoint `id`.
+    pub fn new_barrier(id: CheckpointId, sender: Coord) -> Self {
+        Self {
+            data: NetworkData::Barrier(id),
+            sender,
+        }
+    }
+
     pub fn into_vec(self) -> Vec<StreamElement<T>> {
         match self.data {
             NetworkData::Batch(vec) => vec,
+            NetworkData::Barrier(_) => Vec::new(),
+        }
+    }
+
+    /// The checkpoint of the barrier, if this message is a barrier.
+    pub fn barrier(&self) -> Option<CheckpointId> {
+        match self.data {
+            NetworkData::Barrier(id) => Some(id),
+            NetworkData::Batch(_) => None,
         }
     }
 
@@ -127,6 +155,32 @@ impl<T> NetworkMessage<T> {
     pub fn num_items(&self) -> usize {
         match &self.data {
             NetworkData::Batch(v) => v.len(),
+            NetworkData::Barrier(_) => 0,
+        }
+    }
+
+    /// Whether the message is a batch of items without timestamps, that any replica of the next
+    /// block could process.
+    pub(crate) fn is_plain_batch(&self) -> bool {
+        match &self.data {
+            NetworkData::Batch(v) => v.iter().all(|el| matches!(el, StreamElement::Item(_))),
+            NetworkData::Barrier(_) => false,
+        }
+    }
+
+    /// Whether the message ends the stream of the receiver.
+    pub(crate) fn ends_stream(&self) -> bool {
+        let ends = |v: &[StreamElement<T>]| {
+            v.iter().any(|el| {
+                matches!(
+                    el,
+                    StreamElement::FlushAndRestart | StreamElement::Terminate
+                )
+            })
+        };
+        match &self.data {
+            NetworkData::Batch(v) => ends(v),
+            NetworkData::Barrier(_) => false,
         }
     }
 }
@@ -139,6 +193,7 @@ impl<T> IntoIterator for NetworkMessage<T> {
     fn into_iter(self) -> Self::IntoIter {
         match self.data {
             NetworkData::Batch(v) => NetworkDataIterator::Batch(v.into_iter()),
+            NetworkData::Barrier(_) => NetworkDataIterator::Batch(Vec::new().into_iter()),
         }
     }
 }
@@ -162,6 +217,21 @@ impl ReceiverEndpoint {
     }
 }
 
+/// How this host connects with the other hosts, shared by all its remote channels.
+#[derive(Debug, Clone)]
+pub(crate) struct RemoteOptions {
+    /// The id of this host, multiplexers use it to introduce themselves.
+    pub(crate) host_id: HostId,
+    /// The TLS context of the connections, if they are encrypted.
+    pub(crate) tls: Option<Arc<tls::TlsContext>>,
+    /// The codec the multiplexers propose.
+    pub(crate) compression: NetworkCompression,
+    pub(crate) retry: NetworkRetryConfig,
+    /// The links with the other hosts, when the channels are multiplexed.
+    #[cfg(not(feature = "tokio"))]
+    pub(crate) links: Option<Arc<link::Links>>,
+}
+
 impl DemuxCoord {
     pub fn new(from: Coord, to: Coord) -> Self {
         Self {
@@ -229,6 +299,7 @@ impl From<ReceiverEndpoint> for DemuxCoord {
 #[cfg(test)]
 mod tests {
     use super::remote::{MessageHeader, BINCODE_HEADER, HEADER_SIZE};
+    use super::*;
     use bincode::enc::write::SizeWriter;
 
     #[test]
@@ -263,4 +334,18 @@ mod tests {
             assert_eq!(HEADER_SIZE, size_w.bytes_written);
         }
     }
+
+    #[test]
+    fn local_channel_moves_batches() {
+        let sender = Coord::new(0, 0, 0);
+        let (tx, rx) = local_channel::<String>(ReceiverEndpoint::new(Coord::new(1, 0, 0), 0));
+        let items = vec![StreamElement::Item("a".to_string()); 3];
+        let ptr = items.as_ptr();
+        tx.send(NetworkMessage::new_batch(items, sender)).unwrap();
+
+        // the receiver gets the same allocation: the batch is neither serialized nor copied
+        let received = rx.recv().unwrap().into_vec();
+        assert_eq!(received.as_ptr(), ptr);
+        assert_eq!(received.len(), 3);
+    }
 }
@@ -8,10 +8,10 @@ use crate::channel::{
 
 use crate::network::{NetworkMessage, ReceiverEndpoint};
 use crate::operator::ExchangeData;
-use crate::profiler::{get_profiler, Profiler};
+use crate::profiler::{get_profiler, metrics, Profiler};
 
 /// The capacity of the in-buffer.
-const CHANNEL_CAPACITY: usize = 16;
+pub(crate) const CHANNEL_CAPACITY: usize = 16;
 
 pub(crate) fn local_channel<T: ExchangeData>(
     receiver_endpoint: ReceiverEndpoint,
@@ -29,6 +29,29 @@ pub(crate) fn local_channel<T: ExchangeData>(
     )
 }
 
+/// Like `local_channel`, but the batches of plain items are sent to a separate steal queue, whose
+/// receiver is returned as the third element. See `Stealer`.
+pub(crate) fn stealing_channel<T: ExchangeData>(
+    receiver_endpoint: ReceiverEndpoint,
+) -> (NetworkSender<T>, NetworkReceiver<T>, NetworkReceiver<T>) {
+    let (sender, receiver) = channel::bounded(CHANNEL_CAPACITY);
+    let (steal_sender, steal_receiver) = channel::bounded(CHANNEL_CAPACITY);
+    (
+        NetworkSender {
+            receiver_endpoint,
+            sender: SenderInner::Stealable(sender, steal_sender),
+        },
+        NetworkReceiver {
+            receiver_endpoint,
+            receiver,
+        },
+        NetworkReceiver {
+            receiver_endpoint,
+            receiver: steal_receiver,
+        },
+    )
+}
+
 pub(crate) fn mux_sender<T: ExchangeData>(
     receiver_endpoint: ReceiverEndpoint,
     tx: Sender<(ReceiverEndpoint, NetworkMessage<T>)>,
@@ -48,7 +71,7 @@ pub(crate) fn mux_sender<T: ExchangeData>(
 /// Internally it contains a in-memory sender-receiver pair, to get the local sender call
 /// `.sender()`. When the socket will be bound an task will be spawned, it will bind the
 /// socket and send to the same in-memory channel the received messages.
-#[derive(Derivative)]
+#[derive(Clone, Derivative)]
 #[derivative(Debug)]
 pub(crate) struct NetworkReceiver<In: Send + 'static> {
     /// The ReceiverEndpoint of the current receiver.
@@ -70,6 +93,8 @@ impl<In: Send + 'static> NetworkReceiver<In> {
                 self.receiver_endpoint.coord,
                 message.num_items(),
             );
+            metrics::items_in(self.receiver_endpoint.coord, message.num_items());
+            metrics::queue_depth(self.receiver_endpoint.coord, self.receiver.len());
         })
     }
 
@@ -88,6 +113,16 @@ impl<In: Send + 'static> NetworkReceiver<In> {
         self.profile_message(self.receiver.recv_timeout(timeout))
     }
 
+    /// The number of messages waiting in the channel.
+    pub fn len(&self) -> usize {
+        self.receiver.len()
+    }
+
+    /// Whether there are no messages waiting in the channel.
+    pub fn is_empty(&self) -> bool {
+        self.receiver.is_empty()
+    }
+
     /// Receive a message from any sender of this receiver of the other provided receiver.
     ///
     /// The first message of the two is returned. If both receivers are ready one of them is chosen
@@ -128,6 +163,8 @@ pub(crate) struct NetworkSender<Out: Send + 'static> {
 enum SenderInner<Out: Send + 'static> {
     Mux(Sender<(ReceiverEndpoint, NetworkMessage<Out>)>),
     Local(Sender<NetworkMessage<Out>>),
+    /// A local receiver with a steal queue for the batches of plain items, see `stealing_channel`.
+    Stealable(Sender<NetworkMessage<Out>>, Sender<NetworkMessage<Out>>),
 }
 
 impl<Out: Send + 'static> Clone for SenderInner<Out> {
@@ -135,17 +172,28 @@ impl<Out: Send + 'static> Clone for SenderInner<Out> {
         match self {
             Self::Mux(arg0) => Self::Mux(arg0.clone()),
             Self::Local(arg0) => Self::Local(arg0.clone()),
+            Self::Stealable(arg0, arg1) => Self::Stealable(arg0.clone(), arg1.clone()),
         }
     }
 }
 
 impl<Out: Send + 'static> NetworkSender<Out> {
+    /// The number of batches waiting to be received, for a local receiver.
+    pub fn queue_depth(&self) -> Option<usize> {
+        match &self.sender {
+            SenderInner::Mux(_) => None,
+            SenderInner::Local(tx) => Some(tx.len()),
+            SenderInner::Stealable(tx, steal) => Some(tx.len() + steal.len()),
+        }
+    }
+
     pub fn send(&self, message: NetworkMessage<Out>) -> Result<(), NetworkSendError> {
         get_profiler().items_out(
             message.sender,
             self.receiver_endpoint.coord,
             message.num_items(),
         );
+        metrics::items_out(message.sender, message.num_items());
 
         match &self.sender {
             SenderInner::Mux(tx) => tx
@@ -154,6 +202,11 @@ impl<Out: Send + 'static> NetworkSender<Out> {
             SenderInner::Local(tx) => tx
                 .send(message)
                 .map_err(|_| NetworkSendError::Disconnected(self.receiver_endpoint)),
+            SenderInner::Stealable(tx, steal) => {
+                let tx = if message.is_plain_batch() { steal } else { tx };
+                tx.send(message)
+                    .map_err(|_| NetworkSendError::Disconnected(self.receiver_endpoint))
+            }
         }
     }
 
@@ -176,9 +229,19 @@ impl<Out: Send + 'static> NetworkSender<Out> {
                     NetworkTrySendError::Disconnected(self.receiver_endpoint)
                 }
             }),
+            SenderInner::Stealable(tx, steal) => {
+                let tx = if message.is_plain_batch() { steal } else { tx };
+                tx.try_send(message).map_err(|e| match e {
+                    TrySendError::Full(item) => NetworkTrySendError::Full(item),
+                    TrySendError::Disconnected(_) => {
+                        NetworkTrySendError::Disconnected(self.receiver_endpoint)
+                    }
+                })
+            }
         };
         if res.is_ok() {
             get_profiler().items_out(sender, self.receiver_endpoint.coord, size);
+            metrics::items_out(sender, size);
         }
         res
     }
@@ -186,7 +249,8 @@ impl<Out: Send + 'static> NetworkSender<Out> {
     pub fn clone_inner(&self) -> Sender<NetworkMessage<Out>> {
         match &self.sender {
             SenderInner::Mux(_) => panic!("Trying to clone mux channel. Not supported"),
-            SenderInner::Local(tx) => tx.clone(),
+            // the messages from the other hosts are never stolen
+            SenderInner::Local(tx) | SenderInner::Stealable(tx, _) => tx.clone(),
         }
     }
 }
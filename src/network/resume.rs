@@ -0,0 +1,190 @@
+//! Recovery of the remote channels whose connection drops.
+//!
+//! When a multiplexer connects to a demultiplexer it introduces itself with the id of its host, so
+//! a new connection from the same host resumes the channel of the previous one. The multiplexer
+//! keeps each message it sent until the demultiplexer gives back its credit (see `CreditGate`):
+//! on every connection the demultiplexer tells how many messages it has delivered to each
+//! receiver, the multiplexer forgets those and sends the others again. Each message is therefore
+//! delivered exactly once and in order. At the end the multiplexer sends a close marker, so the
+//! demultiplexer can tell a closed channel from a dropped connection.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::config::NetworkCompression;
+use crate::network::{Coord, DemuxCoord, ReceiverEndpoint};
+use crate::scheduler::{HostId, ReplicaId};
+
+/// The size of the message a multiplexer sends when it connects: the id of its host and the tag
+/// of the codec it proposes.
+pub(crate) const HELLO_SIZE: usize = 9;
+/// The size of an entry of the resume table: the id of the replica and the number of messages
+/// delivered to it.
+pub(crate) const RESUME_ENTRY_SIZE: usize = 16;
+/// The `replica_id` of the header of the close marker.
+pub(crate) const CLOSE_MARKER: ReplicaId = ReplicaId::MAX;
+
+/// The number of messages the demultiplexer has delivered to each receiver of a channel.
+pub(crate) type Delivered = HashMap<ReceiverEndpoint, u64>;
+
+/// A message serialized by the multiplexer, header included.
+#[derive(Debug, Clone)]
+pub(crate) struct Frame {
+    /// The replica that sent the message.
+    pub(crate) from: Coord,
+    pub(crate) bytes: Vec<u8>,
+}
+
+/// The messages sent by a multiplexer whose credit has not come back yet.
+#[derive(Debug, Default)]
+pub(crate) struct ReplayBuffer {
+    receivers: HashMap<ReceiverEndpoint, Unacked>,
+}
+
+#[derive(Debug, Default)]
+struct Unacked {
+    frames: VecDeque<Frame>,
+    /// The number of messages acknowledged since the start of the channel.
+    acked: u64,
+}
+
+impl ReplayBuffer {
+    /// Keep a message sent to `dest`.
+    pub(crate) fn push(&mut self, dest: ReceiverEndpoint, frame: Frame) {
+        self.receivers
+            .entry(dest)
+            .or_default()
+            .frames
+            .push_back(frame);
+    }
+
+    /// Forget the oldest `n` messages sent to `dest`: their credits came back.
+    pub(crate) fn ack(&mut self, dest: ReceiverEndpoint, n: u64) {
+        let unacked = self.receivers.entry(dest).or_default();
+        let n = n.min(unacked.frames.len() as u64);
+        unacked.frames.drain(..n as usize);
+        unacked.acked += n;
+    }
+
+    /// Forget the messages delivered before the connection dropped, returning the number of them
+    /// whose credit has been lost for each receiver.
+    pub(crate) fn resume(&mut self, delivered: &Delivered) -> Vec<(ReceiverEndpoint, usize)> {
+        let mut lost = Vec::new();
+        for (&dest, &delivered) in delivered {
+            let acked = self.receivers.get(&dest).map_or(0, |u| u.acked);
+            let n = delivered.saturating_sub(acked);
+            if n > 0 {
+                self.ack(dest, n);
+                lost.push((dest, n as usize));
+            }
+        }
+        lost
+    }
+
+    /// The messages to send again, in the order they were first sent to each receiver.
+    pub(crate) fn frames(&self) -> impl Iterator<Item = (ReceiverEndpoint, &Frame)> {
+        self.receivers
+            .iter()
+            .flat_map(|(&dest, unacked)| unacked.frames.iter().map(move |frame| (dest, frame)))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.receivers.values().all(|u| u.frames.is_empty())
+    }
+}
+
+pub(crate) fn encode_hello(host_id: HostId, codec: NetworkCompression) -> [u8; HELLO_SIZE] {
+    let mut buf = [0; HELLO_SIZE];
+    buf[..8].copy_from_slice(&host_id.to_le_bytes());
+    buf[8] = codec.tag();
+    buf
+}
+
+/// The host of the multiplexer and the tag of the codec it proposes.
+pub(crate) fn decode_hello(buf: &[u8; HELLO_SIZE]) -> (HostId, u8) {
+    let host_id = HostId::from_le_bytes(buf[..8].try_into().unwrap());
+    (host_id, buf[8])
+}
+
+/// The resume table: the number of entries followed by the entries.
+pub(crate) fn encode_resume(delivered: &Delivered) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(4 + delivered.len() * RESUME_ENTRY_SIZE);
+    buf.extend_from_slice(&(delivered.len() as u32).to_le_bytes());
+    for (dest, count) in delivered {
+        buf.extend_from_slice(&dest.coord.replica_id.to_le_bytes());
+        buf.extend_from_slice(&count.to_le_bytes());
+    }
+    buf
+}
+
+/// Decode the entries of a resume table of the channel `coord`.
+pub(crate) fn decode_resume(coord: DemuxCoord, entries: &[u8]) -> Delivered {
+    entries
+        .chunks_exact(RESUME_ENTRY_SIZE)
+        .map(|entry| {
+            let replica_id = ReplicaId::from_le_bytes(entry[..8].try_into().unwrap());
+            let count = u64::from_le_bytes(entry[8..].try_into().unwrap());
+            let dest = ReceiverEndpoint::new(
+                Coord::new(coord.coord.block_id, coord.coord.host_id, replica_id),
+                coord.prev_block_id,
+            );
+            (dest, count)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(replica_id: u64) -> ReceiverEndpoint {
+        ReceiverEndpoint::new(Coord::new(1, 0, replica_id), 0)
+    }
+
+    fn frame(byte: u8) -> Frame {
+        Frame {
+            from: Coord::new(0, 1, 0),
+            bytes: vec![byte],
+        }
+    }
+
+    #[test]
+    fn replay_unacked() {
+        let (a, b) = (endpoint(0), endpoint(1));
+        let mut buffer = ReplayBuffer::default();
+        for i in 0..5 {
+            buffer.push(a, frame(i));
+        }
+        buffer.push(b, frame(10));
+        buffer.ack(a, 2);
+
+        // the credits of 2 more messages to a were lost with the connection
+        let delivered = [(a, 4), (b, 0)].into_iter().collect();
+        assert_eq!(buffer.resume(&delivered), vec![(a, 2)]);
+        let mut replay: Vec<_> = buffer.frames().map(|(d, f)| (d, f.bytes[0])).collect();
+        replay.sort();
+        assert_eq!(replay, vec![(a, 4), (b, 10)]);
+
+        buffer.ack(a, 1);
+        buffer.ack(b, 1);
+        assert!(buffer.is_empty());
+        // a connection never dropped has nothing to resume
+        assert!(buffer
+            .resume(&[(a, 5), (b, 1)].into_iter().collect())
+            .is_empty());
+    }
+
+    #[test]
+    fn handshake_encoding() {
+        let (host_id, tag) = decode_hello(&encode_hello(42, NetworkCompression::Zstd));
+        assert_eq!(host_id, 42);
+        assert_eq!(tag, NetworkCompression::Zstd.tag());
+
+        let coord = DemuxCoord::from(endpoint(0));
+        let delivered: Delivered = [(endpoint(0), 3), (endpoint(7), 1 << 40)]
+            .into_iter()
+            .collect();
+        let buf = encode_resume(&delivered);
+        assert_eq!(u32::from_le_bytes(buf[..4].try_into().unwrap()), 2);
+        assert_eq!(decode_resume(coord, &buf[4..]), delivered);
+    }
+}
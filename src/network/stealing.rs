@@ -0,0 +1,199 @@
+//! Work stealing between the local replicas of a block.
+//!
+//! The channels towards the replicas of a block that may steal (see
+//! [`RuntimeConfig::with_work_stealing`](crate::RuntimeConfig::with_work_stealing)) have two
+//! queues: the batches of plain items go to the steal queue, all the other messages (watermarks,
+//! flushes, the end of the stream...) to the main one. A replica with nothing to do takes the
+//! batches from the steal queue of its most loaded sibling on the same host.
+//!
+//! The receiver empties its steal queue before handling a message of the main queue, so a message
+//! is never overtaken by the batches sent before it. A replica stops stealing when one of its
+//! previous replicas ends the stream: a batch queued for a sibling may have been sent after the end
+//! of the stream of this replica.
+
+use std::time::{Duration, Instant};
+
+use crate::channel::{RecvTimeoutError, SelectResult, TryRecvError};
+use crate::network::{NetworkMessage, NetworkReceiver};
+use crate::operator::ExchangeData;
+
+/// How often an idle replica looks for batches to steal.
+const STEAL_POLL: Duration = Duration::from_millis(5);
+
+/// The receiving side of a replica that steals the batches of its siblings.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub(crate) struct Stealer<T: ExchangeData> {
+    /// The steal queue of this replica.
+    own: NetworkReceiver<T>,
+    /// The steal queues of the other local replicas of the block.
+    siblings: Vec<NetworkReceiver<T>>,
+    /// A message of the main queue, handled once the steal queue is empty.
+    #[derivative(Debug = "ignore")]
+    pending: Option<NetworkMessage<T>>,
+    /// Whether a previous replica has ended the stream of this one.
+    ended: bool,
+    /// Whether the senders of the steal queue are gone.
+    closed: bool,
+}
+
+impl<T: ExchangeData> Stealer<T> {
+    pub(crate) fn new(own: NetworkReceiver<T>, siblings: Vec<NetworkReceiver<T>>) -> Self {
+        Self {
+            own,
+            siblings,
+            pending: None,
+            ended: false,
+            closed: false,
+        }
+    }
+
+    /// Receive the next message from `main` or from the steal queues, waiting at most `timeout`
+    /// (forever if `None`).
+    pub(crate) fn recv_timeout(
+        &mut self,
+        main: &NetworkReceiver<T>,
+        timeout: Option<Duration>,
+    ) -> Result<NetworkMessage<T>, RecvTimeoutError> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            match self.own.try_recv() {
+                Ok(message) => return Ok(message),
+                Err(TryRecvError::Disconnected) => self.closed = true,
+                Err(TryRecvError::Empty) => {}
+            }
+            if let Some(message) = self.pending.take() {
+                return Ok(message);
+            }
+            match main.try_recv() {
+                Ok(message) => {
+                    self.hold(message);
+                    continue;
+                }
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) => {}
+            }
+            if let Some(message) = self.steal() {
+                return Ok(message);
+            }
+
+            // nothing to do: wait for a message, looking for batches to steal every `STEAL_POLL`
+            let remaining = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Some(remaining),
+                    _ => return Err(RecvTimeoutError::Timeout),
+                },
+                None => None,
+            };
+            let wait = match (remaining, self.ended) {
+                (Some(remaining), false) => remaining.min(STEAL_POLL),
+                (Some(remaining), true) => remaining,
+                (None, false) => STEAL_POLL,
+                (None, true) => Duration::from_secs(1),
+            };
+            if self.closed {
+                match main.recv_timeout(wait) {
+                    Ok(message) => self.hold(message),
+                    Err(RecvTimeoutError::Disconnected) => {
+                        return Err(RecvTimeoutError::Disconnected)
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                }
+                continue;
+            }
+            match main.select_timeout(&self.own, wait) {
+                Ok(SelectResult::A(Ok(message))) => self.hold(message),
+                Ok(SelectResult::A(Err(_))) => return Err(RecvTimeoutError::Disconnected),
+                Ok(SelectResult::B(Ok(message))) => return Ok(message),
+                Ok(SelectResult::B(Err(_))) => self.closed = true,
+                Err(_) => {}
+            }
+        }
+    }
+
+    /// Keep a message of the main queue until the steal queue is empty.
+    fn hold(&mut self, message: NetworkMessage<T>) {
+        debug_assert!(self.pending.is_none());
+        self.ended |= message.ends_stream();
+        self.pending = Some(message);
+    }
+
+    /// Take a batch from the most loaded sibling, if this replica can steal.
+    fn steal(&self) -> Option<NetworkMessage<T>> {
+        if self.ended {
+            return None;
+        }
+        let sibling = self
+            .siblings
+            .iter()
+            .filter(|sibling| !sibling.is_empty())
+            .max_by_key(|sibling| sibling.len())?;
+        let message = sibling.try_recv().ok()?;
+        log::trace!(
+            "{} stole a batch of {} items from {}",
+            self.own.receiver_endpoint,
+            message.num_items(),
+            sibling.receiver_endpoint
+        );
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{stealing_channel, Coord, ReceiverEndpoint};
+    use crate::operator::StreamElement;
+
+    fn endpoint(replica_id: u64) -> ReceiverEndpoint {
+        ReceiverEndpoint::new(Coord::new(1, 0, replica_id), 0)
+    }
+
+    fn items(sender: Coord, items: &[u32]) -> NetworkMessage<u32> {
+        let items = items.iter().map(|&i| StreamElement::Item(i)).collect();
+        NetworkMessage::new_batch(items, sender)
+    }
+
+    #[test]
+    fn steal_from_siblings() {
+        let sender = Coord::new(0, 0, 0);
+        let (tx0, main0, steal0) = stealing_channel::<u32>(endpoint(0));
+        let (tx1, main1, steal1) = stealing_channel::<u32>(endpoint(1));
+        let mut stealer0 = Stealer::new(steal0.clone(), vec![steal1.clone()]);
+        let mut stealer1 = Stealer::new(steal1, vec![steal0]);
+
+        tx0.send(items(sender, &[1, 2])).unwrap();
+        tx0.send(NetworkMessage::new_single(
+            StreamElement::FlushBatch,
+            sender,
+        ))
+        .unwrap();
+        tx0.send(items(sender, &[3])).unwrap();
+        // the idle replica takes a batch of the other
+        let stolen = stealer1.recv_timeout(&main1, Some(Duration::ZERO)).unwrap();
+        assert_eq!(
+            stolen.into_vec(),
+            vec![StreamElement::Item(1), StreamElement::Item(2)]
+        );
+        // the flush waits for the batch sent before it
+        let next = stealer0.recv_timeout(&main0, None).unwrap();
+        assert_eq!(next.into_vec(), vec![StreamElement::Item(3)]);
+        let next = stealer0.recv_timeout(&main0, None).unwrap();
+        assert_eq!(next.into_vec(), vec![StreamElement::FlushBatch]);
+
+        // after the end of its stream a replica does not steal anymore
+        tx1.send(NetworkMessage::new_single(StreamElement::Terminate, sender))
+            .unwrap();
+        let next = stealer1.recv_timeout(&main1, None).unwrap();
+        assert!(next.ends_stream());
+        tx0.send(items(sender, &[4])).unwrap();
+        assert_eq!(
+            stealer1
+                .recv_timeout(&main1, Some(Duration::from_millis(20)))
+                .unwrap_err(),
+            RecvTimeoutError::Timeout
+        );
+        let next = stealer0.recv_timeout(&main0, None).unwrap();
+        assert_eq!(next.into_vec(), vec![StreamElement::Item(4)]);
+    }
+}
@@ -1,13 +1,26 @@
-use std::net::{Shutdown, TcpListener, TcpStream};
+use std::collections::hash_map::Entry;
+use std::io::ErrorKind;
+use std::net::TcpListener;
 use std::thread::JoinHandle;
+use std::time::Duration;
 
 use std::collections::HashMap;
 use std::net::ToSocketAddrs;
 
-use crate::channel::{self, Sender, UnboundedReceiver, UnboundedSender};
-use crate::network::remote::remote_recv;
-use crate::network::{DemuxCoord, NetworkMessage, ReceiverEndpoint};
+use crate::channel::{self, RecvTimeoutError, Sender, UnboundedReceiver, UnboundedSender};
+use crate::config::NetworkCompression;
+use crate::network::remote::{accept_hello, remote_recv, remote_send_credits, remote_send_resume};
+use crate::network::resume::Delivered;
+use crate::network::stream::NetStream;
+use crate::network::{DemuxCoord, NetworkMessage, ReceiverEndpoint, RemoteOptions};
 use crate::operator::ExchangeData;
+use crate::scheduler::HostId;
+
+/// How long the demultiplexer waits for new connections before checking whether its channels are
+/// closed.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+type RegisterMessage<In> = (ReceiverEndpoint, Sender<NetworkMessage<In>>);
 
 /// Like `NetworkReceiver`, but this should be used in a multiplexed channel (i.e. a remote one).
 ///
@@ -19,7 +32,7 @@ use crate::operator::ExchangeData;
 pub(crate) struct DemuxHandle<In: ExchangeData> {
     coord: DemuxCoord,
     /// Tell the dem&ultiplexer that a new receiver is present,
-    tx_senders: UnboundedSender<(ReceiverEndpoint, Sender<NetworkMessage<In>>)>,
+    tx_senders: UnboundedSender<RegisterMessage<In>>,
 }
 
 impl<In: ExchangeData> DemuxHandle<In> {
@@ -33,6 +46,7 @@ impl<In: ExchangeData> DemuxHandle<In> {
         coord: DemuxCoord,
         address: (String, u16),
         num_clients: usize,
+        options: RemoteOptions,
     ) -> (Self, JoinHandle<()>) {
         let (tx_senders, rx_senders) = channel::unbounded();
         let join_handle = std::thread::Builder::new()
@@ -40,7 +54,7 @@ impl<In: ExchangeData> DemuxHandle<In> {
                 "reg-{}:{}-{}",
                 coord.coord.host_id, coord.prev_block_id, coord.coord.block_id
             ))
-            .spawn(move || bind_remotes(coord, address, num_clients, rx_senders))
+            .spawn(move || bind_remotes(coord, address, num_clients, options, rx_senders))
             .unwrap();
         (Self { coord, tx_senders }, join_handle)
     }
@@ -58,13 +72,59 @@ impl<In: ExchangeData> DemuxHandle<In> {
     }
 }
 
-/// Bind the socket of this demultiplexer.
+/// Bind the socket of this demultiplexer, or listen for the streams of the links when the
+/// connections are multiplexed.
+///
+/// The socket keeps accepting connections until all the channels are closed, so the multiplexers
+/// whose connection dropped can resume their channel.
 fn bind_remotes<In: ExchangeData>(
     coord: DemuxCoord,
     address: (String, u16),
     num_clients: usize,
-    rx_senders: UnboundedReceiver<(ReceiverEndpoint, Sender<NetworkMessage<In>>)>,
+    options: RemoteOptions,
+    rx_senders: UnboundedReceiver<RegisterMessage<In>>,
 ) {
+    let listener = match &options.links {
+        Some(links) => {
+            debug!("{coord} ready on the links, waiting for {num_clients} clients");
+            Listener::Link(links.listen(coord))
+        }
+        None => Listener::Socket(bind(coord, address, num_clients)),
+    };
+
+    let mut acceptor = Acceptor::new(coord, listener, num_clients, options);
+    while acceptor.routes.len() < num_clients {
+        acceptor.poll();
+    }
+    log::debug!("{} all clients connected", coord);
+
+    // Broadcast senders
+    loop {
+        match rx_senders.recv_timeout(ACCEPT_POLL_INTERVAL) {
+            Ok(t) => {
+                for route in acceptor.routes.values() {
+                    route.senders.as_ref().unwrap().send(t.clone()).unwrap();
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => acceptor.poll(),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    // Start all demuxes
+    for route in acceptor.routes.values_mut() {
+        route.senders = None;
+    }
+    while !acceptor.is_finished() {
+        acceptor.poll();
+    }
+    for handle in acceptor.join_handles {
+        handle.join().unwrap();
+    }
+    log::debug!("{} finished", coord);
+}
+
+/// Bind the socket of the demultiplexer at `address`, which is polled by the `Acceptor`.
+fn bind(coord: DemuxCoord, address: (String, u16), num_clients: usize) -> TcpListener {
     let address = (address.0.as_ref(), address.1);
     let address: Vec<_> = address
         .to_socket_addrs()
@@ -89,99 +149,306 @@ fn bind_remotes<In: ExchangeData>(
         "{} ready at {}, waiting for {} clients",
         coord, address, num_clients
     );
+    // the socket is polled, to notice when the channels are closed
+    listener
+        .set_nonblocking(true)
+        .unwrap_or_else(|e| panic!("Failed to configure the socket of {coord}: {e:?}"));
+    listener
+}
 
-    // the list of JoinHandle of all the spawned threads, including the demultiplexer one
-    let mut join_handles = vec![];
-    let mut tx_broadcast = vec![];
+/// Where the connections of the multiplexers come from.
+enum Listener {
+    /// The socket of this demultiplexer.
+    Socket(TcpListener),
+    /// The streams opened on the links with the other hosts.
+    Link(flume::Receiver<NetStream>),
+}
 
-    let mut incoming = listener.incoming();
-    let mut connected_clients = 0;
-    while connected_clients < num_clients {
-        let stream = incoming.next().unwrap();
-        let stream = match stream {
-            Ok(stream) => stream,
-            Err(e) => {
-                warn!("{} to accept incoming connection: {:?}", coord, e);
-                continue;
+/// The demultiplexer thread of the channel of a remote host.
+struct Route<In: ExchangeData> {
+    /// The registered receivers, dropped when all of them are known.
+    senders: Option<UnboundedSender<RegisterMessage<In>>>,
+    /// The new connections of the host.
+    streams: UnboundedSender<(NetStream, NetworkCompression)>,
+    /// The last connection of the host.
+    current: Option<NetStream>,
+}
+
+/// Accepts the connections of a demultiplexer, routing them to the thread of their host.
+struct Acceptor<In: ExchangeData> {
+    coord: DemuxCoord,
+    listener: Listener,
+    num_clients: usize,
+    options: RemoteOptions,
+    handshakes_tx: UnboundedSender<(HostId, NetStream, NetworkCompression)>,
+    handshakes_rx: UnboundedReceiver<(HostId, NetStream, NetworkCompression)>,
+    routes: HashMap<HostId, Route<In>>,
+    /// the list of JoinHandle of all the spawned threads
+    join_handles: Vec<JoinHandle<()>>,
+}
+
+impl<In: ExchangeData> Acceptor<In> {
+    fn new(
+        coord: DemuxCoord,
+        listener: Listener,
+        num_clients: usize,
+        options: RemoteOptions,
+    ) -> Self {
+        let (handshakes_tx, handshakes_rx) = channel::unbounded();
+        Self {
+            coord,
+            listener,
+            num_clients,
+            options,
+            handshakes_tx,
+            handshakes_rx,
+            routes: Default::default(),
+            join_handles: Default::default(),
+        }
+    }
+
+    /// Accept the pending connections and route one that completed the handshake, waiting at most
+    /// `ACCEPT_POLL_INTERVAL`.
+    fn poll(&mut self) {
+        match &self.listener {
+            Listener::Socket(listener) => loop {
+                match listener.accept() {
+                    Ok((stream, peer_addr)) => {
+                        let tls = self.options.tls.clone();
+                        self.handshake(peer_addr.to_string(), move || {
+                            stream.set_nonblocking(false)?;
+                            NetStream::accept(stream, tls.as_deref())
+                        })
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        warn!("{} to accept incoming connection: {:?}", self.coord, e);
+                        break;
+                    }
+                }
+            },
+            // the streams of the links are already encrypted
+            Listener::Link(streams) => {
+                for stream in streams.try_iter() {
+                    self.handshake(peer_address(&stream), move || Ok(stream));
+                }
             }
-        };
-        connected_clients += 1;
-        let peer_addr = stream.peer_addr().unwrap();
-        debug!(
-            "{} new connection from {} ({} / {})",
-            coord, peer_addr, connected_clients, num_clients
-        );
-
-        let (demux_tx, demux_rx) = channel::unbounded();
-        let join_handle = std::thread::Builder::new()
+        }
+        if let Ok((host_id, stream, compression)) =
+            self.handshakes_rx.recv_timeout(ACCEPT_POLL_INTERVAL)
+        {
+            self.route(host_id, stream, compression);
+        }
+    }
+
+    fn handshake(
+        &self,
+        peer_addr: String,
+        open: impl FnOnce() -> std::io::Result<NetStream> + Send + 'static,
+    ) {
+        let coord = self.coord;
+        let handshakes_tx = self.handshakes_tx.clone();
+        // the handshake happens in a separate thread, to keep accepting the other connections
+        std::thread::Builder::new()
             .name(format!(
-                "demux-{}:{}-{}",
+                "hello-{}:{}-{}",
                 coord.coord.host_id, coord.prev_block_id, coord.coord.block_id
             ))
             .spawn(move || {
-                let mut senders = HashMap::new();
-                while let Ok((endpoint, sender)) = demux_rx.recv() {
-                    senders.insert(endpoint, sender);
+                let handshake = open().and_then(|mut stream| {
+                    let (host_id, compression) = accept_hello(&mut stream)?;
+                    Ok((host_id, stream, compression))
+                });
+                match handshake {
+                    Ok(incoming) => {
+                        let _ = handshakes_tx.send(incoming);
+                    }
+                    Err(e) => warn!("{coord} failed the handshake with {peer_addr}: {e:?}"),
                 }
-                log::debug!("{coord} got senders");
-                demux_thread::<In>(coord, senders, stream);
             })
             .unwrap();
-        join_handles.push(join_handle);
-        tx_broadcast.push(demux_tx);
     }
-    log::debug!("{} all clients connected", coord);
-    drop(listener);
 
-    // Broadcast senders
-    while let Ok(t) = rx_senders.recv() {
-        for tx in tx_broadcast.iter() {
-            tx.send(t.clone()).unwrap();
+    /// Give the connection of `host_id` to its thread, starting it at the first connection.
+    fn route(&mut self, host_id: HostId, stream: NetStream, compression: NetworkCompression) {
+        let coord = self.coord;
+        let num_routes = self.routes.len();
+        match self.routes.entry(host_id) {
+            Entry::Occupied(mut route) => {
+                let route = route.get_mut();
+                debug!("{coord} new connection from host {host_id}, resuming its channel");
+                // unblock the thread if it's still reading from the previous connection
+                if let Some(prev) = route.current.replace(stream.try_clone().unwrap()) {
+                    let _ = prev.shutdown();
+                }
+                let _ = route.streams.send((stream, compression));
+            }
+            Entry::Vacant(_) if num_routes == self.num_clients => {
+                warn!("{coord} unexpected connection from host {host_id}");
+            }
+            Entry::Vacant(entry) => {
+                debug!(
+                    "{} new connection from host {} ({} / {})",
+                    coord,
+                    host_id,
+                    num_routes + 1,
+                    self.num_clients
+                );
+                let (senders_tx, senders_rx) = channel::unbounded();
+                let (streams_tx, streams_rx) = channel::unbounded();
+                let current = stream.try_clone().ok();
+                streams_tx.send((stream, compression)).unwrap();
+                let reconnect_timeout = self.options.retry.reconnect_timeout;
+                let join_handle = std::thread::Builder::new()
+                    .name(format!(
+                        "demux-{}:{}-{}",
+                        coord.coord.host_id, coord.prev_block_id, coord.coord.block_id
+                    ))
+                    .spawn(move || {
+                        demux_thread::<In>(coord, senders_rx, streams_rx, reconnect_timeout)
+                    })
+                    .unwrap();
+                self.join_handles.push(join_handle);
+                entry.insert(Route {
+                    senders: Some(senders_tx),
+                    streams: streams_tx,
+                    current,
+                });
+            }
         }
     }
-    drop(tx_broadcast); // Start all demuxes
-    for handle in join_handles {
-        handle.join().unwrap();
+
+    fn is_finished(&self) -> bool {
+        self.join_handles.iter().all(|h| h.is_finished())
     }
-    log::debug!("{} finished", coord);
 }
 
-/// Handle the connection with a remote sender.
-///
-/// Will deserialize the message upon arrival and send to the corresponding recipient the
-/// deserialized data. If the recipient is not yet known, it is waited until it registers.
-///
-/// # Upgrade path
+fn peer_address(stream: &NetStream) -> String {
+    stream
+        .peer_addr()
+        .map(|a| a.to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// What the thread of a channel tells the thread that gives back its credits.
+enum CreditEvent {
+    /// The channel resumed on a new connection: the multiplexer recomputes the credits not given
+    /// back on the previous one from the resume table.
+    Connection(NetStream),
+    /// A message has been delivered to its recipient.
+    Delivered(ReceiverEndpoint),
+}
+
+/// Give back the credits of the delivered messages to the multiplexer.
 ///
-/// Replace send with queue.
+/// The credits of all the messages delivered while the thread was writing are sent together, a
+/// single frame for each recipient, so under load a frame gives back many credits.
+fn credit_thread(coord: DemuxCoord, events: UnboundedReceiver<CreditEvent>) {
+    let mut stream = None;
+    let mut credits: HashMap<ReceiverEndpoint, u32> = HashMap::new();
+    while let Ok(event) = events.recv() {
+        let ready = std::iter::from_fn(|| events.try_recv().ok());
+        for event in std::iter::once(event).chain(ready) {
+            match event {
+                CreditEvent::Connection(next) => {
+                    credits.clear();
+                    stream = Some(next);
+                }
+                CreditEvent::Delivered(dest) => *credits.entry(dest).or_default() += 1,
+            }
+        }
+        let Some(stream) = stream.as_mut() else {
+            continue;
+        };
+        for (dest, n) in credits.drain() {
+            if let Err(e) = remote_send_credits(dest, n, stream) {
+                log::trace!(
+                    "{coord} failed to send credits to {}: {e:?}",
+                    peer_address(stream)
+                );
+            }
+        }
+    }
+}
+
+/// Handle the channel with a remote sender.
 ///
-/// The queue uses a hierarchical queue:
-/// + First try to reserve and put the value in the fast queue
-/// + If the fast queue is full, put in the slow (unbounded?) queue
-/// + Return an enum, either Queued or Overflowed
+/// Will deserialize the message upon arrival and send to the corresponding recipient the
+/// deserialized data. The messages are received only after all the recipients have registered.
 ///
-/// if overflowed send a yield request through a second channel
+/// Each message delivered to its recipient gives back a credit to the multiplexer, which sends
+/// at most `INITIAL_CREDITS` messages to each recipient before receiving new credits (see
+/// `CreditGate`). The credits are batched by a separate thread, see `credit_thread`. When the connection drops the thread waits for the multiplexer to open a new
+/// one, and tells it how many messages have been delivered so far.
 fn demux_thread<In: ExchangeData>(
     coord: DemuxCoord,
-    senders: HashMap<ReceiverEndpoint, Sender<NetworkMessage<In>>>,
-    mut stream: TcpStream,
+    rx_senders: UnboundedReceiver<RegisterMessage<In>>,
+    streams: UnboundedReceiver<(NetStream, NetworkCompression)>,
+    reconnect_timeout: Duration,
 ) {
-    let address = stream
-        .peer_addr()
-        .map(|a| a.to_string())
-        .unwrap_or_else(|_| "unknown".to_string());
+    let mut delivered = Delivered::new();
+    let (mut stream, mut compression) = streams.recv().unwrap();
+    let mut address = peer_address(&stream);
+    // the multiplexer waits for the resume table before sending, even if it's empty
+    if let Err(e) = remote_send_resume(&mut stream, &delivered) {
+        log::trace!("{coord} failed to send the resume table to {address}: {e:?}");
+    }
+    let clone = |stream: &NetStream| {
+        stream
+            .try_clone()
+            .unwrap_or_else(|e| panic!("Failed to clone the connection of {coord}: {e:?}"))
+    };
+    let (credits, credit_events) = channel::unbounded();
+    credits
+        .send(CreditEvent::Connection(clone(&stream)))
+        .unwrap();
+    let credit_handle = std::thread::Builder::new()
+        .name(format!(
+            "credit-{}:{}-{}",
+            coord.coord.host_id, coord.prev_block_id, coord.coord.block_id
+        ))
+        .spawn(move || credit_thread(coord, credit_events))
+        .unwrap();
+
+    let mut senders = HashMap::new();
+    while let Ok((endpoint, sender)) = rx_senders.recv() {
+        senders.insert(endpoint, sender);
+    }
     log::debug!("{} started", coord);
 
-    // let mut r = std::io::BufReader::new(&mut stream);
-    let mut r = &mut stream;
     let mut scratch = Vec::new();
-
-    while let Some((dest, message)) = remote_recv(coord, &mut r, &mut scratch, &address) {
-        if let Err(e) = senders[&dest].send(message) {
-            warn!("demux failed to send message to {}: {:?}", dest, e);
+    loop {
+        match remote_recv(coord, &mut stream, &mut scratch, compression) {
+            Ok(Some((dest, message))) => {
+                if let Err(e) = senders[&dest].send(message) {
+                    warn!("demux failed to send message to {}: {:?}", dest, e);
+                }
+                *delivered.entry(dest).or_default() += 1;
+                // the message left the connection: give back its credit
+                let _ = credits.send(CreditEvent::Delivered(dest));
+            }
+            Ok(None) => break,
+            Err(e) => {
+                log::warn!("{coord} lost the connection with {address}: {e:?}");
+                let _ = stream.shutdown();
+                (stream, compression) = streams
+                    .recv_timeout(reconnect_timeout)
+                    .unwrap_or_else(|_| {
+                        panic!(
+                            "{coord} lost the connection with {address}, not opened again in {reconnect_timeout:?}"
+                        )
+                    });
+                address = peer_address(&stream);
+                if let Err(e) = remote_send_resume(&mut stream, &delivered) {
+                    log::trace!("{coord} failed to send the resume table to {address}: {e:?}");
+                }
+                let _ = credits.send(CreditEvent::Connection(clone(&stream)));
+            }
         }
     }
 
-    let _ = stream.shutdown(Shutdown::Both);
+    drop(credits);
+    credit_handle.join().unwrap();
+    let _ = stream.shutdown();
     log::debug!("{} finished", coord);
 }
@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::network::stream::NetStream;
+use crate::network::tls::TlsContext;
+use crate::network::{BlockCoord, DemuxCoord};
+use crate::scheduler::HostId;
+
+/// The identifier of a stream inside a link, chosen by the host that opened the stream: the host
+/// that connected the link uses the even ids, the other host the odd ones.
+type StreamId = u32;
+
+/// Size of the header of a frame: the kind, the stream and the length of the payload.
+const FRAME_HEADER_SIZE: usize = 9;
+/// A new stream, the payload is the `DemuxCoord` of its channel.
+const FRAME_OPEN: u8 = 0;
+/// Data of a stream.
+const FRAME_DATA: u8 = 1;
+/// The stream is closed, no more data will be sent.
+const FRAME_CLOSE: u8 = 2;
+/// The first frame of a link, the payload is the id of the host that connected it.
+const FRAME_HELLO: u8 = 3;
+/// Maximum size of the payload of a single frame.
+const MAX_FRAME_SIZE: usize = 16 << 20;
+
+/// Timeout for connecting to a remote host.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the listener waits for new links before checking whether it has been stopped.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The links between this host and the other hosts, used when the remote channels are
+/// multiplexed.
+///
+/// Each host listens on its `base_port`, and two hosts share a single connection, a link, opened
+/// by the first of them that sends messages to the other. The channels between the blocks of the
+/// two hosts are streams of that link: the multiplexer of a channel opens a stream telling its
+/// `DemuxCoord`, and the demultiplexer of the channel receives it from `listen`. The streams behave
+/// like dedicated connections, so the channels keep their protocol (hello, credits and resume
+/// table). When a link drops all its streams drop, and the next stream opened to the host connects
+/// a new link.
+///
+/// If the two hosts connect at the same time both links are kept, and the new streams are opened
+/// on the one connected by the host with the lower id.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub(crate) struct Links {
+    #[derivative(Debug = "ignore")]
+    inner: Arc<LinksInner>,
+    #[derivative(Debug = "ignore")]
+    listener: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct LinksInner {
+    host_id: HostId,
+    /// The address of the listener of each host.
+    addresses: Vec<(String, u16)>,
+    tls: Option<Arc<TlsContext>>,
+    /// The link the streams are opened on, for each host.
+    peers: Mutex<HashMap<HostId, Arc<Link>>>,
+    /// Held while connecting to each host, so that a single link is connected at a time without
+    /// blocking the streams opened to the other hosts.
+    connecting: Vec<Mutex<()>>,
+    /// All the links of this host, with the thread reading them.
+    links: Mutex<Vec<(Arc<Link>, JoinHandle<()>)>>,
+    /// The streams opened by the other hosts, by channel.
+    incoming: Mutex<HashMap<DemuxCoord, Incoming>>,
+    stopped: AtomicBool,
+}
+
+/// The streams opened for a channel.
+enum Incoming {
+    /// The demultiplexer of the channel is not listening yet.
+    Pending(Vec<NetStream>),
+    Listening(flume::Sender<NetStream>),
+}
+
+impl Links {
+    /// Start listening for the links of the other hosts at the address of `host_id`.
+    pub(crate) fn start(
+        host_id: HostId,
+        addresses: Vec<(String, u16)>,
+        tls: Option<Arc<TlsContext>>,
+    ) -> Self {
+        let address = &addresses[host_id as usize];
+        let listener = TcpListener::bind((address.0.as_str(), address.1)).unwrap_or_else(|e| {
+            panic!("Failed to bind the links of host {host_id} at {address:?}: {e:?}")
+        });
+        // the socket is polled, to notice when the links are stopped
+        listener
+            .set_nonblocking(true)
+            .unwrap_or_else(|e| panic!("Failed to configure the links of host {host_id}: {e:?}"));
+        log::debug!("host {host_id} accepting links at {address:?}");
+
+        let inner = Arc::new(LinksInner {
+            host_id,
+            connecting: addresses.iter().map(|_| Mutex::new(())).collect(),
+            addresses,
+            tls,
+            peers: Default::default(),
+            links: Default::default(),
+            incoming: Default::default(),
+            stopped: AtomicBool::new(false),
+        });
+        let accept = inner.clone();
+        let listener = std::thread::Builder::new()
+            .name(format!("links-{host_id}"))
+            .spawn(move || accept.accept(listener))
+            .unwrap();
+        Self {
+            inner,
+            listener: Mutex::new(Some(listener)),
+        }
+    }
+
+    /// Open a stream for the channel `coord`, connecting the link with its host if needed.
+    pub(crate) fn open(&self, coord: DemuxCoord) -> std::io::Result<NetStream> {
+        self.inner.link(coord.coord.host_id)?.open(coord)
+    }
+
+    /// Receive the streams the other hosts open for the channel `coord`, including the ones
+    /// already opened.
+    pub(crate) fn listen(&self, coord: DemuxCoord) -> flume::Receiver<NetStream> {
+        let (tx, rx) = flume::unbounded();
+        let mut incoming = self.inner.incoming.lock();
+        if let Some(Incoming::Pending(streams)) = incoming.remove(&coord) {
+            for stream in streams {
+                tx.send(stream).unwrap();
+            }
+        }
+        incoming.insert(coord, Incoming::Listening(tx));
+        rx
+    }
+
+    /// Close all the links, when all the channels of this host are closed.
+    pub(crate) fn stop(&self) {
+        self.inner.stopped.store(true, Ordering::Release);
+        if let Some(listener) = self.listener.lock().take() {
+            listener.join().unwrap();
+        }
+        self.inner.peers.lock().clear();
+        let links = std::mem::take(&mut *self.inner.links.lock());
+        for (link, reader) in links {
+            link.close();
+            reader.join().unwrap();
+        }
+    }
+}
+
+impl LinksInner {
+    /// Accept the links of the other hosts until the links are stopped.
+    fn accept(self: Arc<Self>, listener: TcpListener) {
+        while !self.stopped.load(Ordering::Acquire) {
+            match listener.accept() {
+                Ok((socket, peer_addr)) => {
+                    // the handshake happens in a separate thread, to keep accepting the other hosts
+                    let inner = self.clone();
+                    std::thread::Builder::new()
+                        .name(format!("link-hello-{}", self.host_id))
+                        .spawn(move || {
+                            let link = socket
+                                .set_nonblocking(false)
+                                .and_then(|_| NetStream::accept(socket, inner.tls.as_deref()))
+                                .and_then(|mut stream| {
+                                    let peer = read_hello(&mut stream)?;
+                                    inner.add(stream, peer, false)
+                                });
+                            if let Err(e) = link {
+                                log::warn!(
+                                    "host {} failed to accept the link of {peer_addr}: {e:?}",
+                                    inner.host_id
+                                );
+                            }
+                        })
+                        .unwrap();
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    std::thread::sleep(ACCEPT_POLL_INTERVAL)
+                }
+                Err(e) => {
+                    log::warn!("host {} failed to accept a link: {e:?}", self.host_id);
+                    std::thread::sleep(ACCEPT_POLL_INTERVAL);
+                }
+            }
+        }
+    }
+
+    /// The link with `host_id`, connected if there is none or the previous one dropped.
+    fn link(self: &Arc<Self>, host_id: HostId) -> std::io::Result<Arc<Link>> {
+        if let Some(link) = self.alive_link(host_id) {
+            return Ok(link);
+        }
+        let _connecting = self.connecting[host_id as usize].lock();
+        // the link may have been connected meanwhile, by this host or by the other one
+        if let Some(link) = self.alive_link(host_id) {
+            return Ok(link);
+        }
+        let (host, port) = &self.addresses[host_id as usize];
+        let mut error = None;
+        for socket_addr in (host.as_str(), *port).to_socket_addrs()? {
+            let stream = TcpStream::connect_timeout(&socket_addr, CONNECT_TIMEOUT)
+                .and_then(|socket| NetStream::connect(socket, self.tls.as_deref(), host))
+                .and_then(|mut stream| {
+                    write_frame(&mut stream, FRAME_HELLO, 0, &self.host_id.to_le_bytes())?;
+                    Ok(stream)
+                });
+            match stream.and_then(|stream| self.add(stream, host_id, true)) {
+                Ok(link) => {
+                    log::debug!("host {} linked with host {host_id}", self.host_id);
+                    return Ok(link);
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        Err(error.unwrap_or_else(|| ErrorKind::AddrNotAvailable.into()))
+    }
+
+    /// The link with `host_id`, if it's still open.
+    fn alive_link(&self, host_id: HostId) -> Option<Arc<Link>> {
+        self.peers
+            .lock()
+            .get(&host_id)
+            .filter(|link| link.alive.load(Ordering::Acquire))
+            .cloned()
+    }
+
+    /// Start reading a new link with `peer`, connected by this host if `connected`.
+    ///
+    /// Returns the link the streams to `peer` are opened on, which is a different one if both
+    /// hosts connected at the same time and the other has the lower id.
+    fn add(
+        self: &Arc<Self>,
+        stream: NetStream,
+        peer: HostId,
+        connected: bool,
+    ) -> std::io::Result<Arc<Link>> {
+        let reader = stream.try_clone()?;
+        let link = Arc::new(Link {
+            peer_addr: stream.peer_addr().ok(),
+            connected_by: if connected { self.host_id } else { peer },
+            control: stream.try_clone()?,
+            writer: Mutex::new(stream),
+            routes: Default::default(),
+            next_id: AtomicU32::new(if connected { 0 } else { 1 }),
+            alive: AtomicBool::new(true),
+        });
+        let inner = self.clone();
+        let read = link.clone();
+        let handle = std::thread::Builder::new()
+            .name(format!("link-{}", self.host_id))
+            .spawn(move || inner.read(&read, reader))
+            .unwrap();
+        self.links.lock().push((link.clone(), handle));
+
+        let mut peers = self.peers.lock();
+        let current = peers
+            .get(&peer)
+            .filter(|current| current.alive.load(Ordering::Acquire));
+        match current {
+            Some(current) if current.connected_by < link.connected_by => Ok(current.clone()),
+            _ => {
+                peers.insert(peer, link.clone());
+                Ok(link)
+            }
+        }
+    }
+
+    /// Route the frames of a link to its streams, until the link drops.
+    fn read(&self, link: &Arc<Link>, mut reader: NetStream) {
+        loop {
+            let (kind, id, payload) = match read_frame(&mut reader) {
+                Ok(frame) => frame,
+                Err(e) => {
+                    if !self.stopped.load(Ordering::Acquire) {
+                        log::warn!(
+                            "host {} lost the link with {:?}: {e:?}",
+                            self.host_id,
+                            link.peer_addr
+                        );
+                    }
+                    break;
+                }
+            };
+            match kind {
+                FRAME_OPEN => {
+                    let coord = decode_coord(&payload);
+                    let stream = link.stream(id);
+                    self.deliver(coord, stream);
+                }
+                FRAME_DATA => link.route(id, payload),
+                FRAME_CLOSE => link.unroute(id),
+                kind => panic!("malformed frame of kind {kind} in a link"),
+            }
+        }
+        link.close();
+    }
+
+    /// Give a stream opened by another host to the demultiplexer of its channel.
+    fn deliver(&self, coord: DemuxCoord, stream: NetStream) {
+        let mut incoming = self.incoming.lock();
+        match incoming
+            .entry(coord)
+            .or_insert_with(|| Incoming::Pending(Vec::new()))
+        {
+            Incoming::Pending(streams) => streams.push(stream),
+            Incoming::Listening(tx) => {
+                // if the demultiplexer already finished nobody reads the stream: close it, so that
+                // the multiplexer does not wait for an answer
+                if let Err(flume::SendError(stream)) = tx.send(stream) {
+                    let _ = stream.shutdown();
+                }
+            }
+        }
+    }
+}
+
+/// A connection with another host, carrying the streams of many channels.
+struct Link {
+    peer_addr: Option<SocketAddr>,
+    /// The host that connected the link.
+    connected_by: HostId,
+    /// A clone of the connection used to shut it down, without waiting for the writers.
+    control: NetStream,
+    /// The writing side of the connection, shared by the streams.
+    writer: Mutex<NetStream>,
+    /// Where the data received for each stream is sent.
+    routes: Mutex<HashMap<StreamId, flume::Sender<Vec<u8>>>>,
+    next_id: AtomicU32,
+    /// Whether the connection is still open.
+    alive: AtomicBool,
+}
+
+impl Link {
+    /// Open a new stream for the channel `coord`.
+    fn open(self: &Arc<Self>, coord: DemuxCoord) -> std::io::Result<NetStream> {
+        let id = self.next_id.fetch_add(2, Ordering::Relaxed);
+        let stream = self.stream(id);
+        self.send(FRAME_OPEN, id, &encode_coord(coord))?;
+        Ok(stream)
+    }
+
+    /// The stream `id` of this link.
+    fn stream(self: &Arc<Self>, id: StreamId) -> NetStream {
+        let (tx, rx) = flume::unbounded();
+        self.routes.lock().insert(id, tx);
+        NetStream::link(LinkStream {
+            id,
+            link: self.clone(),
+            rx,
+            chunk: Vec::new(),
+            pos: 0,
+        })
+    }
+
+    fn send(&self, kind: u8, id: StreamId, payload: &[u8]) -> std::io::Result<()> {
+        if !self.alive.load(Ordering::Acquire) {
+            return Err(ErrorKind::NotConnected.into());
+        }
+        write_frame(&mut *self.writer.lock(), kind, id, payload)
+    }
+
+    fn route(&self, id: StreamId, data: Vec<u8>) {
+        if let Some(tx) = self.routes.lock().get(&id) {
+            let _ = tx.send(data);
+        }
+    }
+
+    /// Stop routing the data of a stream: it reads the end of the data received so far.
+    fn unroute(&self, id: StreamId) {
+        self.routes.lock().remove(&id);
+    }
+
+    /// Close the connection: all the streams read the end of the data received so far.
+    fn close(&self) {
+        self.alive.store(false, Ordering::Release);
+        self.routes.lock().clear();
+        let _ = self.control.shutdown();
+    }
+}
+
+/// A stream of a link, it is used by the channels as a dedicated connection.
+///
+/// The clones of the stream share the data received: only one of them should read it.
+pub(crate) struct LinkStream {
+    id: StreamId,
+    link: Arc<Link>,
+    rx: flume::Receiver<Vec<u8>>,
+    /// The data received and not read yet.
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl std::fmt::Debug for LinkStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LinkStream")
+            .field("id", &self.id)
+            .field("peer_addr", &self.link.peer_addr)
+            .finish()
+    }
+}
+
+impl LinkStream {
+    pub fn try_clone(&self) -> Self {
+        Self {
+            id: self.id,
+            link: self.link.clone(),
+            rx: self.rx.clone(),
+            chunk: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    pub fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+        self.link
+            .peer_addr
+            .ok_or_else(|| ErrorKind::NotConnected.into())
+    }
+
+    /// Close the stream on both hosts, without closing the link.
+    pub fn shutdown(&self) -> std::io::Result<()> {
+        self.link.unroute(self.id);
+        self.link.send(FRAME_CLOSE, self.id, &[])
+    }
+}
+
+impl Read for LinkStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                // the stream is closed
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Write for LinkStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let n = buf.len().min(MAX_FRAME_SIZE);
+        self.link.send(FRAME_DATA, self.id, &buf[..n])?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn write_frame(
+    writer: &mut impl Write,
+    kind: u8,
+    id: StreamId,
+    payload: &[u8],
+) -> std::io::Result<()> {
+    let mut header = [0u8; FRAME_HEADER_SIZE];
+    header[0] = kind;
+    header[1..5].copy_from_slice(&id.to_le_bytes());
+    header[5..9].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+    writer.write_all(&header)?;
+    writer.write_all(payload)
+}
+
+/// Read the next frame: its kind, its stream and its payload.
+fn read_frame(reader: &mut impl Read) -> std::io::Result<(u8, StreamId, Vec<u8>)> {
+    let mut header = [0u8; FRAME_HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+    let id = StreamId::from_le_bytes(header[1..5].try_into().unwrap());
+    let len = u32::from_le_bytes(header[5..9].try_into().unwrap());
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload)?;
+    Ok((header[0], id, payload))
+}
+
+/// Read the first frame of a link, returning the host that connected it.
+fn read_hello(reader: &mut impl Read) -> std::io::Result<HostId> {
+    match read_frame(reader)? {
+        (FRAME_HELLO, _, payload) if payload.len() == 8 => {
+            Ok(HostId::from_le_bytes(payload.try_into().unwrap()))
+        }
+        (kind, _, _) => Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("the link started with a frame of kind {kind}"),
+        )),
+    }
+}
+
+fn encode_coord(coord: DemuxCoord) -> [u8; 24] {
+    let mut buf = [0u8; 24];
+    buf[0..8].copy_from_slice(&coord.coord.block_id.to_le_bytes());
+    buf[8..16].copy_from_slice(&coord.coord.host_id.to_le_bytes());
+    buf[16..24].copy_from_slice(&coord.prev_block_id.to_le_bytes());
+    buf
+}
+
+fn decode_coord(buf: &[u8]) -> DemuxCoord {
+    let int = |i: usize| u64::from_le_bytes(buf[i..i + 8].try_into().unwrap());
+    DemuxCoord {
+        coord: BlockCoord {
+            block_id: int(0),
+            host_id: int(8),
+        },
+        prev_block_id: int(16),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+
+    #[test]
+    fn link_streams() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let addresses = vec![
+            ("127.0.0.1".to_string(), 0),
+            ("127.0.0.1".to_string(), port),
+        ];
+        let host0 = Links::start(0, addresses.clone(), None);
+        let host1 = Links::start(1, addresses, None);
+
+        let coord = |prev_block_id| DemuxCoord {
+            coord: BlockCoord {
+                block_id: 2,
+                host_id: 1,
+            },
+            prev_block_id,
+        };
+        let back = DemuxCoord {
+            coord: BlockCoord {
+                block_id: 3,
+                host_id: 0,
+            },
+            prev_block_id: 2,
+        };
+        // the streams are received even if they are opened before listening
+        let mut a = host0.open(coord(0)).unwrap();
+        let mut b = host0.open(coord(1)).unwrap();
+        a.write_all(b"hello a").unwrap();
+        b.write_all(b"hello b").unwrap();
+        let mut a_remote = host1.listen(coord(0)).recv().unwrap();
+        let mut b_remote = host1.listen(coord(1)).recv().unwrap();
+
+        let mut buf = [0; 7];
+        b_remote.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello b");
+        a_remote.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello a");
+
+        // the streams are bidirectional and closed independently
+        a_remote.write_all(b"reply a").unwrap();
+        a.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"reply a");
+        a.shutdown().unwrap();
+        assert_eq!(a_remote.read(&mut buf).unwrap(), 0);
+        b.write_all(b"still b").unwrap();
+        b_remote.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"still b");
+
+        // the streams opened by the other host share the same link
+        let mut c = host1.open(back).unwrap();
+        c.write_all(b"hello c").unwrap();
+        let mut c_remote = host0.listen(back).recv().unwrap();
+        c_remote.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello c");
+        assert_eq!(host0.inner.links.lock().len(), 1);
+        assert_eq!(host1.inner.links.lock().len(), 1);
+
+        host0.stop();
+        host1.stop();
+    }
+}
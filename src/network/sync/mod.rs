@@ -1,3 +1,5 @@
 pub(super) mod demultiplexer;
+pub(super) mod link;
 pub(super) mod multiplexer;
 pub(super) mod remote;
+pub(super) mod stream;
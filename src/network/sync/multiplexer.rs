@@ -1,12 +1,19 @@
 use std::io::ErrorKind;
 use std::time::Duration;
 
-use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::net::{TcpStream, ToSocketAddrs};
 use std::thread::{sleep, JoinHandle};
 
-use crate::channel::{self, Receiver, Sender};
-use crate::network::remote::remote_send;
-use crate::network::{DemuxCoord, NetworkMessage, ReceiverEndpoint};
+use crate::channel::{self, Receiver, SelectResult, Sender};
+use crate::config::NetworkCompression;
+use crate::network::credit::{CreditGate, INITIAL_CREDITS};
+use crate::network::remote::{
+    encode_message, remote_recv_credits, remote_recv_resume, remote_send, remote_send_close,
+    send_hello,
+};
+use crate::network::resume::{Delivered, Frame, ReplayBuffer};
+use crate::network::stream::NetStream;
+use crate::network::{DemuxCoord, NetworkMessage, ReceiverEndpoint, RemoteOptions};
 use crate::operator::ExchangeData;
 
 //
@@ -14,17 +21,12 @@ use crate::operator::ExchangeData;
 
 use crate::network::NetworkSender;
 
-/// Maximum number of attempts to make for connecting to a remote host.
-const CONNECT_ATTEMPTS: usize = 32;
 /// Timeout for connecting to a remote host.
 const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
-/// To avoid spamming the connections, wait this timeout before trying again. If the connection
-/// fails again this timeout will be doubled up to `RETRY_MAX_TIMEOUT`.
-const RETRY_INITIAL_TIMEOUT: Duration = Duration::from_millis(8);
-/// Maximum timeout between connection attempts.
-const RETRY_MAX_TIMEOUT: Duration = Duration::from_secs(1);
 
 const MUX_CHANNEL_CAPACITY: usize = 10;
+/// Capacity of the channel of the credits received from the demultiplexer.
+const CREDIT_CHANNEL_CAPACITY: usize = 64;
 /// Like `NetworkSender`, but this should be used in a multiplexed channel (i.e. a remote one).
 ///
 /// The `ReceiverEndpoint` is sent alongside the actual message in order to demultiplex it.
@@ -34,7 +36,11 @@ pub struct MultiplexingSender<Out: ExchangeData> {
 }
 
 impl<Out: ExchangeData> MultiplexingSender<Out> {
-    pub fn new(coord: DemuxCoord, address: (String, u16)) -> (Self, JoinHandle<()>) {
+    pub fn new(
+        coord: DemuxCoord,
+        address: (String, u16),
+        options: RemoteOptions,
+    ) -> (Self, JoinHandle<()>) {
         let (tx, rx) = channel::bounded(MUX_CHANNEL_CAPACITY);
 
         let join_handle = std::thread::Builder::new()
@@ -47,9 +53,7 @@ impl<Out: ExchangeData> MultiplexingSender<Out> {
                     "mux {coord} connecting to {}",
                     address.to_socket_addrs().unwrap().next().unwrap()
                 );
-                let stream = connect_remote(coord, address);
-
-                mux_thread::<Out>(coord, rx, stream);
+                mux_thread::<Out>(coord, rx, address, options);
             })
             .unwrap();
         (Self { tx: Some(tx) }, join_handle)
@@ -60,20 +64,28 @@ impl<Out: ExchangeData> MultiplexingSender<Out> {
     }
 }
 
-/// Connect the sender to a remote channel located at the specified address.
+/// Connect the sender to a remote channel located at the specified address, returning the
+/// connection, its codec and the messages the demultiplexer already delivered.
 ///
 /// - At first the address is resolved to an actual address (DNS resolution)
-/// - Then at most `CONNECT_ATTEMPTS` are performed, and an exponential backoff is used in case
+/// - Then at most `connect_attempts` are performed, and an exponential backoff is used in case
 ///   of errors.
 /// - If the connection cannot be established this function will panic.
-fn connect_remote(coord: DemuxCoord, address: (String, u16)) -> TcpStream {
+///
+/// When the connections are multiplexed the connection is a new stream of the link with the host
+/// of the demultiplexer, retried in the same way.
+fn connect_remote(
+    coord: DemuxCoord,
+    address: &(String, u16),
+    options: &RemoteOptions,
+) -> (NetStream, NetworkCompression, Delivered) {
     let socket_addrs: Vec<_> = address
         .to_socket_addrs()
         .map_err(|e| format!("Failed to get the address for {coord}: {e:?}",))
         .unwrap()
         .collect();
-    let mut retry_delay = RETRY_INITIAL_TIMEOUT;
-    for attempt in 1..=CONNECT_ATTEMPTS {
+    let retry = options.retry;
+    for attempt in 1..=retry.connect_attempts {
         log::debug!(
             "{} connecting to {:?} ({} attempt)",
             coord,
@@ -81,65 +93,278 @@ fn connect_remote(coord: DemuxCoord, address: (String, u16)) -> TcpStream {
             attempt,
         );
 
-        for address in socket_addrs.iter() {
-            match TcpStream::connect_timeout(address, CONNECT_TIMEOUT) {
-                Ok(stream) => {
-                    return stream;
+        if let Some(links) = &options.links {
+            match links
+                .open(coord)
+                .and_then(|stream| hello(coord, stream, options))
+            {
+                Ok(connection) => return connection,
+                Err(err) => {
+                    log::debug!(
+                        "{coord} failed to open a stream to host {}: {err:?}",
+                        coord.coord.host_id
+                    );
+                }
+            }
+        } else {
+            for socket_addr in socket_addrs.iter() {
+                match TcpStream::connect_timeout(socket_addr, CONNECT_TIMEOUT) {
+                    Ok(stream) => match handshake(coord, stream, &address.0, options) {
+                        Ok(connection) => return connection,
+                        Err(err) => {
+                            log::warn!(
+                                "{coord} failed the handshake with {socket_addr:?}: {err:?}"
+                            );
+                        }
+                    },
+                    Err(err) => match err.kind() {
+                        ErrorKind::TimedOut => {
+                            log::debug!("{coord} timeout connecting to {socket_addr:?}");
+                        }
+                        ErrorKind::ConnectionRefused => {
+                            log::log!(
+                                if attempt > 4 {
+                                    log::Level::Warn
+                                } else {
+                                    log::Level::Debug
+                                },
+                                "{coord} connection refused connecting to {socket_addr:?} ({attempt})"
+                            );
+                        }
+                        _ => {
+                            log::warn!("{coord} failed to connect to {socket_addr:?}: {err:?}");
+                        }
+                    },
                 }
-                Err(err) => match err.kind() {
-                    ErrorKind::TimedOut => {
-                        log::debug!("{coord} timeout connecting to {address:?}");
-                    }
-                    ErrorKind::ConnectionRefused => {
-                        log::log!(
-                            if attempt > 4 {
-                                log::Level::Warn
-                            } else {
-                                log::Level::Debug
-                            },
-                            "{coord} connection refused connecting to {address:?} ({attempt})"
-                        );
-                    }
-                    _ => {
-                        log::warn!("{coord} failed to connect to {address:?}: {err:?}");
-                    }
-                },
             }
         }
 
+        let retry_delay = retry.delay(attempt);
         log::debug!(
             "{coord} retrying connection to {socket_addrs:?} in {}s",
             retry_delay.as_secs_f32(),
         );
-
         sleep(retry_delay);
-        retry_delay = (2 * retry_delay).min(RETRY_MAX_TIMEOUT);
     }
-    panic!("Failed to connect to remote {coord} at {address:?} after {CONNECT_ATTEMPTS} attempts",);
+    panic!(
+        "Failed to connect to remote {coord} at {address:?} after {} attempts",
+        retry.connect_attempts
+    );
+}
+
+/// Establish the TLS session, introduce this host and receive the resume table of the channel.
+fn handshake(
+    coord: DemuxCoord,
+    stream: TcpStream,
+    host: &str,
+    options: &RemoteOptions,
+) -> std::io::Result<(NetStream, NetworkCompression, Delivered)> {
+    let stream = NetStream::connect(stream, options.tls.as_deref(), host)?;
+    hello(coord, stream, options)
+}
+
+/// Introduce this host and receive the resume table of the channel.
+fn hello(
+    coord: DemuxCoord,
+    mut stream: NetStream,
+    options: &RemoteOptions,
+) -> std::io::Result<(NetStream, NetworkCompression, Delivered)> {
+    let compression = send_hello(&mut stream, options.host_id, options.compression)?;
+    let delivered = remote_recv_resume(coord, &mut stream)?;
+    Ok((stream, compression, delivered))
+}
+
+/// An open connection with the demultiplexer.
+struct Connection {
+    stream: NetStream,
+    address: String,
+    /// The credits read from the connection, disconnected when the connection drops.
+    credit_rx: Receiver<(ReceiverEndpoint, u32)>,
+    credit_handle: JoinHandle<()>,
+    /// Whether the writes succeeded so far: after a failure the messages are only kept for the
+    /// next connection.
+    healthy: bool,
+}
+
+impl Connection {
+    fn open(coord: DemuxCoord, stream: NetStream) -> Self {
+        let address = stream
+            .peer_addr()
+            .map(|a| a.to_string())
+            .unwrap_or_else(|_| "unknown".to_string());
+        log::debug!("{} connected to {:?}", coord, address);
+
+        // the credits come back on the same connection, read them in a separate thread
+        let mut reader = stream
+            .try_clone()
+            .unwrap_or_else(|e| panic!("Failed to clone the connection of {coord}: {e:?}"));
+        let (credit_tx, credit_rx) = channel::bounded(CREDIT_CHANNEL_CAPACITY);
+        let credit_handle = std::thread::Builder::new()
+            .name(format!(
+                "credit-{}:{}-{}",
+                coord.coord.host_id, coord.prev_block_id, coord.coord.block_id
+            ))
+            .spawn(move || {
+                while let Some(credits) = remote_recv_credits(coord, &mut reader) {
+                    if credit_tx.send(credits).is_err() {
+                        break;
+                    }
+                }
+            })
+            .unwrap();
+        Self {
+            stream,
+            address,
+            credit_rx,
+            credit_handle,
+            healthy: true,
+        }
+    }
+
+    fn write(&mut self, frame: &Frame, dest: ReceiverEndpoint) {
+        if !self.healthy {
+            return;
+        }
+        if let Err(e) = remote_send(frame, dest, &mut self.stream) {
+            log::warn!(
+                "Failed to send message to {dest} at {}: {e:?}",
+                self.address
+            );
+            self.healthy = false;
+            // stop the reader too, the credits channel tells that the connection dropped
+            let _ = self.stream.shutdown();
+        }
+    }
+
+    fn close(self) {
+        let _ = self.stream.shutdown();
+        self.credit_handle.join().unwrap();
+    }
+}
+
+/// The channel of a multiplexer, which survives the connections that drop.
+struct MuxChannel<Out> {
+    coord: DemuxCoord,
+    address: (String, u16),
+    options: RemoteOptions,
+    compression: NetworkCompression,
+    conn: Connection,
+    gate: CreditGate<NetworkMessage<Out>>,
+    replay: ReplayBuffer,
+    reconnections: u32,
+}
+
+impl<Out: ExchangeData> MuxChannel<Out> {
+    fn connect(coord: DemuxCoord, address: (String, u16), options: RemoteOptions) -> Self {
+        // the first connection has nothing to resume
+        let (stream, compression, _) = connect_remote(coord, &address, &options);
+        log::debug!("{coord} compresses the messages with {compression:?}");
+        Self {
+            coord,
+            conn: Connection::open(coord, stream),
+            address,
+            options,
+            compression,
+            gate: CreditGate::new(INITIAL_CREDITS),
+            replay: Default::default(),
+            reconnections: 0,
+        }
+    }
+
+    fn push(&mut self, dest: ReceiverEndpoint, message: NetworkMessage<Out>) {
+        if let Some(message) = self.gate.push(dest, message) {
+            self.send(dest, message);
+        }
+    }
+
+    fn send(&mut self, dest: ReceiverEndpoint, message: NetworkMessage<Out>) {
+        let frame = encode_message(message, dest, self.compression);
+        self.conn.write(&frame, dest);
+        self.replay.push(dest, frame);
+    }
+
+    fn grant(&mut self, dest: ReceiverEndpoint, credits: u32) {
+        self.replay.ack(dest, credits as u64);
+        for message in self.gate.grant(dest, credits as usize) {
+            self.send(dest, message);
+        }
+    }
+
+    /// Open a new connection, sending again the messages not delivered yet.
+    ///
+    /// Panics after `max_reconnections` reconnections: the job failed.
+    fn reconnect(&mut self) {
+        self.reconnections += 1;
+        let max = self.options.retry.max_reconnections;
+        if self.reconnections > max {
+            panic!(
+                "{} lost the connection to {} more than {max} times",
+                self.coord, self.conn.address
+            );
+        }
+        log::warn!(
+            "{} lost the connection to {}, reconnecting ({}/{max})",
+            self.coord,
+            self.conn.address,
+            self.reconnections
+        );
+        let (stream, compression, delivered) =
+            connect_remote(self.coord, &self.address, &self.options);
+        assert_eq!(
+            compression, self.compression,
+            "{} negotiated a different compression after reconnecting",
+            self.coord
+        );
+        let prev = std::mem::replace(&mut self.conn, Connection::open(self.coord, stream));
+        prev.close();
+
+        // the messages delivered while the connection dropped have lost their credits
+        let lost = self.replay.resume(&delivered);
+        for (dest, frame) in self.replay.frames() {
+            self.conn.write(frame, dest);
+        }
+        for (dest, credits) in lost {
+            for message in self.gate.grant(dest, credits) {
+                self.send(dest, message);
+            }
+        }
+    }
+
+    /// Tell the demultiplexer that the channel is closed, all the messages have been delivered.
+    fn close(mut self) {
+        debug_assert!(self.replay.is_empty());
+        while !self.conn.healthy || remote_send_close(&mut self.conn.stream).is_err() {
+            self.reconnect();
+        }
+        self.conn.close();
+    }
 }
 
 fn mux_thread<Out: ExchangeData>(
     coord: DemuxCoord,
     rx: Receiver<(ReceiverEndpoint, NetworkMessage<Out>)>,
-    mut stream: TcpStream,
+    address: (String, u16),
+    options: RemoteOptions,
 ) {
-    use std::io::Write;
-
-    let address = stream
-        .peer_addr()
-        .map(|a| a.to_string())
-        .unwrap_or_else(|_| "unknown".to_string());
-    log::debug!("{} connected to {:?}", coord, address);
+    let mut channel = MuxChannel::<Out>::connect(coord, address, options);
+    let mut open = true;
 
-    // let mut w = std::io::BufWriter::new(&mut stream);
-    let mut w = &mut stream;
-    let mut scratch = Vec::new();
-
-    while let Ok((dest, message)) = rx.recv() {
-        remote_send(message, dest, &mut w, &mut scratch, &address);
+    // wait for all the credits before closing, so all the messages have been received
+    while open || !channel.gate.is_idle() {
+        // stop accepting messages while a receiver is too slow, the senders will block
+        let event = if open && !channel.gate.is_saturated() {
+            rx.select(&channel.conn.credit_rx)
+        } else {
+            SelectResult::B(channel.conn.credit_rx.recv())
+        };
+        match event {
+            SelectResult::A(Ok((dest, message))) => channel.push(dest, message),
+            SelectResult::A(Err(_)) => open = false,
+            SelectResult::B(Ok((dest, credits))) => channel.grant(dest, credits),
+            SelectResult::B(Err(_)) => channel.reconnect(),
+        }
     }
 
-    w.flush().unwrap();
-    let _ = stream.shutdown(Shutdown::Both);
+    channel.close();
     log::debug!("{} finished", coord);
 }
@@ -9,11 +9,17 @@ use std::io::Write;
 
 use serde::{Deserialize, Serialize};
 
+use crate::config::NetworkCompression;
+use crate::network::compression::{compress, decompress};
+use crate::network::resume::{
+    decode_hello, decode_resume, encode_hello, encode_resume, Delivered, Frame, CLOSE_MARKER,
+    HELLO_SIZE, RESUME_ENTRY_SIZE,
+};
 use crate::network::{Coord, DemuxCoord, NetworkMessage, ReceiverEndpoint};
 use crate::operator::ExchangeData;
-use crate::profiler::{get_profiler, Profiler};
+use crate::profiler::{get_profiler, metrics, Profiler};
 use crate::scheduler::BlockId;
-use crate::scheduler::ReplicaId;
+use crate::scheduler::{HostId, ReplicaId};
 
 pub(crate) const HEADER_SIZE: usize = 20; // std::mem::size_of::<MessageHeader>();
 /// Configuration of the header serializer: the integers must have a fixed length encoding.
@@ -35,96 +41,187 @@ pub(crate) struct MessageHeader {
     pub(crate) sender_block_id: BlockId,
 }
 
-/// Serialize and send a message to a remote socket.
+/// Serialize a message for `dest`, compressed if the connection negotiated a codec.
 ///
-/// The network protocol works as follow:
-/// - send a `MessageHeader` serialized with bincode with `FixintEncoding`
-/// - send the message
-pub(crate) fn remote_send<T: ExchangeData, W: Write>(
+/// The frame is a `MessageHeader` serialized with bincode with `FixintEncoding`, followed by the
+/// message.
+pub(crate) fn encode_message<T: ExchangeData>(
     msg: NetworkMessage<T>,
     dest: ReceiverEndpoint,
-    writer: &mut W,
-    scratch: &mut Vec<u8>,
-    address: &str,
-) {
-    scratch.resize(HEADER_SIZE, 0);
-
-    let serialized_len = bincode::serde::encode_into_std_write(&msg, scratch, BINCODE_MESSAGE)
-        .unwrap_or_else(|e| panic!("Failed to serialize message to {dest} at {address}: {e:?}",));
+    compression: NetworkCompression,
+) -> Frame {
+    let mut bytes = vec![0; HEADER_SIZE];
+    let serialized_len = bincode::serde::encode_into_std_write(&msg, &mut bytes, BINCODE_MESSAGE)
+        .unwrap_or_else(|e| panic!("Failed to serialize message to {dest}: {e:?}"));
+    compress(compression, &mut bytes, HEADER_SIZE);
+    let size = bytes.len() - HEADER_SIZE;
 
     let header = MessageHeader {
-        size: serialized_len.try_into().unwrap(),
+        size: size.try_into().unwrap(),
         replica_id: dest.coord.replica_id,
         sender_block_id: dest.prev_block_id,
     };
-
-    bincode::serde::encode_into_slice(header, &mut scratch[0..HEADER_SIZE], BINCODE_HEADER)
-    .unwrap_or_else(|e| {
-        panic!(
-            "Failed to serialize header of message (was {serialized_len} bytes) to {dest} at {address}: {e:?}",
-        )
-    });
-
-    assert_eq!(scratch.len(), HEADER_SIZE + serialized_len as usize);
-
-    writer.write_all(scratch).unwrap_or_else(|e| {
-        panic!("Failed to send message {serialized_len} bytes to {dest} at {address}: {e:?}",)
-    });
-
-    if scratch.len() < scratch.capacity() / 3 {
-        scratch.shrink_to(scratch.capacity() / 2);
+    bincode::serde::encode_into_slice(header, &mut bytes[0..HEADER_SIZE], BINCODE_HEADER)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Failed to serialize header of message (was {serialized_len} bytes) to {dest}: {e:?}"
+            )
+        });
+    Frame {
+        from: msg.sender,
+        bytes,
     }
+}
 
-    get_profiler().net_bytes_out(msg.sender, dest.coord, scratch.len());
+/// Send a message serialized by `encode_message` to a remote socket.
+#[tracing::instrument(
+    name = "send",
+    level = "debug",
+    skip_all,
+    fields(from = %frame.from, to = %dest.coord, bytes = frame.bytes.len()),
+)]
+pub(crate) fn remote_send<W: Write>(
+    frame: &Frame,
+    dest: ReceiverEndpoint,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    writer.write_all(&frame.bytes)?;
+    get_profiler().net_bytes_out(frame.from, dest.coord, frame.bytes.len());
+    metrics::bytes_out(frame.from, frame.bytes.len());
+    Ok(())
 }
 
-/// Receive a message from the remote channel. Returns `None` if there was a failure receiving the
-/// last message.
-///
-/// The message won't be deserialized, use `deserialize()`.
+/// Tell the demultiplexer that the channel is closed: all the messages have been delivered.
+pub(crate) fn remote_send_close<W: Write>(writer: &mut W) -> std::io::Result<()> {
+    let header = MessageHeader {
+        replica_id: CLOSE_MARKER,
+        ..Default::default()
+    };
+    let mut buf = [0u8; HEADER_SIZE];
+    bincode::serde::encode_into_slice(header, &mut buf, BINCODE_HEADER)
+        .expect("Failed to serialize the close marker");
+    writer.write_all(&buf)?;
+    writer.flush()
+}
+
+/// Receive a message from the remote channel. Returns `None` if the multiplexer closed the
+/// channel, an error if the connection dropped.
 pub(crate) fn remote_recv<T: ExchangeData, R: Read>(
     coord: DemuxCoord,
     reader: &mut R,
     scratch: &mut Vec<u8>,
-    address: &str,
-) -> Option<(ReceiverEndpoint, NetworkMessage<T>)> {
+    compression: NetworkCompression,
+) -> std::io::Result<Option<(ReceiverEndpoint, NetworkMessage<T>)>> {
     let mut header = [0u8; HEADER_SIZE];
-    match reader.read_exact(&mut header) {
-        Ok(_) => {}
-        Err(e) => {
-            log::trace!(
-                "Failed to receive {} bytes of header to {} from {}: {:?}",
-                HEADER_SIZE,
-                coord,
-                address,
-                e
-            );
-            return None;
-        }
-    }
+    reader.read_exact(&mut header)?;
 
     let (header, header_len): (MessageHeader, _) =
         bincode::serde::decode_from_slice(&header, BINCODE_HEADER).expect("malformed header");
     debug_assert_eq!(HEADER_SIZE, header_len);
+    if header.replica_id == CLOSE_MARKER {
+        return Ok(None);
+    }
 
     scratch.resize(header.size as usize, 0);
-    reader.read_exact(&mut scratch[..]).unwrap_or_else(|e| {
-        panic!(
-            "Failed to receive {} bytes to {} from {}: {:?}",
-            header.size, coord, address, e
-        )
-    });
+    reader.read_exact(&mut scratch[..])?;
 
+    let message = decompress(compression, scratch);
     let (msg, msg_len): (NetworkMessage<T>, _) =
-        bincode::serde::decode_from_slice(scratch, BINCODE_MESSAGE).expect("Malformed message");
-    debug_assert_eq!(header.size as usize, msg_len);
+        bincode::serde::decode_from_slice(&message, BINCODE_MESSAGE).expect("Malformed message");
+    debug_assert_eq!(message.len(), msg_len);
 
     let dest = ReceiverEndpoint::new(
         Coord::new(coord.coord.block_id, coord.coord.host_id, header.replica_id),
         header.sender_block_id,
     );
     get_profiler().net_bytes_in(msg.sender, dest.coord, HEADER_SIZE + header.size as usize);
-    Some((dest, msg))
+    metrics::bytes_in(dest.coord, HEADER_SIZE + header.size as usize);
+    Ok(Some((dest, msg)))
+}
+
+/// Introduce the multiplexer of `host_id` to the demultiplexer of the connection, proposing
+/// `codec`. Returns the codec of the connection.
+pub(crate) fn send_hello<S: Read + Write>(
+    stream: &mut S,
+    host_id: HostId,
+    codec: NetworkCompression,
+) -> std::io::Result<NetworkCompression> {
+    stream.write_all(&encode_hello(host_id, codec.proposal()))?;
+    let mut answer = [0u8];
+    stream.read_exact(&mut answer)?;
+    Ok(NetworkCompression::accepted(answer[0]))
+}
+
+/// Answer the hello of `send_hello`, returning the host of the multiplexer and the codec of the
+/// connection.
+pub(crate) fn accept_hello<S: Read + Write>(
+    stream: &mut S,
+) -> std::io::Result<(HostId, NetworkCompression)> {
+    let mut hello = [0u8; HELLO_SIZE];
+    stream.read_exact(&mut hello)?;
+    let (host_id, proposal) = decode_hello(&hello);
+    let codec = NetworkCompression::negotiate(proposal);
+    stream.write_all(&[codec.tag()])?;
+    Ok((host_id, codec))
+}
+
+/// Tell the multiplexer how many messages have been delivered to each receiver, so it can resume
+/// the channel.
+pub(crate) fn remote_send_resume<W: Write>(
+    writer: &mut W,
+    delivered: &Delivered,
+) -> std::io::Result<()> {
+    writer.write_all(&encode_resume(delivered))
+}
+
+/// Receive the resume table sent by `remote_send_resume`.
+pub(crate) fn remote_recv_resume<R: Read>(
+    coord: DemuxCoord,
+    reader: &mut R,
+) -> std::io::Result<Delivered> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let mut entries = vec![0; u32::from_le_bytes(len) as usize * RESUME_ENTRY_SIZE];
+    reader.read_exact(&mut entries)?;
+    Ok(decode_resume(coord, &entries))
+}
+
+/// Send new credits for the messages to `dest` back to the multiplexer of a remote channel: it
+/// can send `credits` more messages to `dest`.
+///
+/// The credits travel in the opposite direction of the messages, as a `MessageHeader` whose
+/// `size` is the number of credits, without a message.
+pub(crate) fn remote_send_credits<W: Write>(
+    dest: ReceiverEndpoint,
+    credits: u32,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    let header = MessageHeader {
+        size: credits,
+        replica_id: dest.coord.replica_id,
+        sender_block_id: dest.prev_block_id,
+    };
+    let mut buf = [0u8; HEADER_SIZE];
+    bincode::serde::encode_into_slice(header, &mut buf, BINCODE_HEADER)
+        .expect("Failed to serialize credits");
+    writer.write_all(&buf)
+}
+
+/// Receive the credits sent by `remote_send_credits`. Returns `None` if the connection was
+/// closed.
+pub(crate) fn remote_recv_credits<R: Read>(
+    coord: DemuxCoord,
+    reader: &mut R,
+) -> Option<(ReceiverEndpoint, u32)> {
+    let mut buf = [0u8; HEADER_SIZE];
+    reader.read_exact(&mut buf).ok()?;
+    let (header, _): (MessageHeader, _) =
+        bincode::serde::decode_from_slice(&buf, BINCODE_HEADER).expect("malformed credits");
+    let dest = ReceiverEndpoint::new(
+        Coord::new(coord.coord.block_id, coord.coord.host_id, header.replica_id),
+        header.sender_block_id,
+    );
+    Some((dest, header.size))
 }
 
 #[cfg(test)]
@@ -0,0 +1,300 @@
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+
+#[cfg(feature = "tls")]
+use parking_lot::Mutex;
+
+use crate::network::link::LinkStream;
+use crate::network::tls::TlsContext;
+
+/// Size of the buffer used for reading the encrypted records from the socket.
+#[cfg(feature = "tls")]
+const TLS_READ_BUFFER: usize = 16 * 1024;
+
+/// A connection between a multiplexer and a demultiplexer, encrypted if the host has a
+/// `TlsContext`.
+///
+/// When the connections are multiplexed the connection is a stream of the link between the two
+/// hosts, see `Links`.
+///
+/// The connection can be cloned for reading and writing from different threads: the clones share
+/// the same TLS session.
+#[derive(Debug)]
+pub(crate) struct NetStream {
+    inner: Inner,
+}
+
+#[derive(Debug)]
+enum Inner {
+    Socket {
+        socket: TcpStream,
+        #[cfg(feature = "tls")]
+        tls: Option<Arc<Mutex<rustls::Connection>>>,
+    },
+    Link(LinkStream),
+}
+
+impl NetStream {
+    /// Start the client side of the connection with the host at `host`.
+    pub fn connect(
+        socket: TcpStream,
+        tls: Option<&TlsContext>,
+        host: &str,
+    ) -> std::io::Result<Self> {
+        match tls {
+            None => Ok(Self::plain(socket)),
+            #[cfg(feature = "tls")]
+            Some(tls) => {
+                let name = crate::network::tls::server_name(host)?;
+                let conn = rustls::ClientConnection::new(tls.client.clone(), name)
+                    .map_err(std::io::Error::other)?;
+                Self::handshake(socket, conn.into())
+            }
+            #[cfg(not(feature = "tls"))]
+            Some(_) => unreachable!("TLS requires the `tls` feature (connecting to {host})"),
+        }
+    }
+
+    /// Start the server side of the connection.
+    pub fn accept(socket: TcpStream, tls: Option<&TlsContext>) -> std::io::Result<Self> {
+        match tls {
+            None => Ok(Self::plain(socket)),
+            #[cfg(feature = "tls")]
+            Some(tls) => {
+                let conn = rustls::ServerConnection::new(tls.server.clone())
+                    .map_err(std::io::Error::other)?;
+                Self::handshake(socket, conn.into())
+            }
+            #[cfg(not(feature = "tls"))]
+            Some(_) => unreachable!("TLS requires the `tls` feature"),
+        }
+    }
+
+    /// A stream of a link, already encrypted by the link if needed.
+    pub(super) fn link(stream: LinkStream) -> Self {
+        Self {
+            inner: Inner::Link(stream),
+        }
+    }
+
+    fn plain(socket: TcpStream) -> Self {
+        Self {
+            inner: Inner::Socket {
+                socket,
+                #[cfg(feature = "tls")]
+                tls: None,
+            },
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    fn handshake(mut socket: TcpStream, mut conn: rustls::Connection) -> std::io::Result<Self> {
+        while conn.is_handshaking() {
+            conn.complete_io(&mut socket)?;
+        }
+        Ok(Self {
+            inner: Inner::Socket {
+                socket,
+                tls: Some(Arc::new(Mutex::new(conn))),
+            },
+        })
+    }
+
+    pub fn try_clone(&self) -> std::io::Result<Self> {
+        let inner = match &self.inner {
+            Inner::Socket {
+                socket,
+                #[cfg(feature = "tls")]
+                tls,
+            } => Inner::Socket {
+                socket: socket.try_clone()?,
+                #[cfg(feature = "tls")]
+                tls: tls.clone(),
+            },
+            Inner::Link(stream) => Inner::Link(stream.try_clone()),
+        };
+        Ok(Self { inner })
+    }
+
+    pub fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+        match &self.inner {
+            Inner::Socket { socket, .. } => socket.peer_addr(),
+            Inner::Link(stream) => stream.peer_addr(),
+        }
+    }
+
+    /// Close the connection, telling the peer that the TLS session has ended.
+    pub fn shutdown(&self) -> std::io::Result<()> {
+        match &self.inner {
+            Inner::Socket {
+                socket,
+                #[cfg(feature = "tls")]
+                tls,
+            } => {
+                #[cfg(feature = "tls")]
+                if let Some(tls) = tls {
+                    let mut conn = tls.lock();
+                    conn.send_close_notify();
+                    while conn.wants_write() {
+                        conn.write_tls(&mut &*socket)?;
+                    }
+                }
+                socket.shutdown(Shutdown::Both)
+            }
+            Inner::Link(stream) => stream.shutdown(),
+        }
+    }
+}
+
+impl Read for NetStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.inner {
+            Inner::Socket {
+                socket,
+                #[cfg(feature = "tls")]
+                tls,
+            } => {
+                #[cfg(feature = "tls")]
+                if let Some(tls) = tls {
+                    return read_tls(socket, tls, buf);
+                }
+                socket.read(buf)
+            }
+            Inner::Link(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for NetStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.inner {
+            Inner::Socket {
+                socket,
+                #[cfg(feature = "tls")]
+                tls,
+            } => {
+                #[cfg(feature = "tls")]
+                if let Some(tls) = tls {
+                    let mut conn = tls.lock();
+                    let n = conn.writer().write(buf)?;
+                    while conn.wants_write() {
+                        conn.write_tls(socket)?;
+                    }
+                    return Ok(n);
+                }
+                socket.write(buf)
+            }
+            Inner::Link(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.inner {
+            Inner::Socket { socket, .. } => socket.flush(),
+            Inner::Link(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Read the plaintext of the TLS session.
+///
+/// The lock on the session is not held while waiting for the socket, so the other clones of the
+/// connection can keep writing.
+#[cfg(feature = "tls")]
+fn read_tls(
+    mut socket: &TcpStream,
+    tls: &Mutex<rustls::Connection>,
+    buf: &mut [u8],
+) -> std::io::Result<usize> {
+    use std::io::ErrorKind;
+
+    let mut raw = vec![0; TLS_READ_BUFFER];
+    loop {
+        match tls.lock().reader().read(buf) {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            res => return res,
+        }
+
+        let n = socket.read(&mut raw)?;
+        let mut conn = tls.lock();
+        let mut records = &raw[..n];
+        // an empty read tells the session that the socket is closed
+        loop {
+            conn.read_tls(&mut records)?;
+            conn.process_new_packets()
+                .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+            if records.is_empty() {
+                break;
+            }
+        }
+        // answer the messages of the session, e.g. the key updates
+        while conn.wants_write() {
+            conn.write_tls(&mut socket)?;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tls"))]
+mod tests {
+    use std::net::TcpListener;
+
+    use crate::network::tls::tests::test_config;
+
+    use super::*;
+
+    #[test]
+    fn tls_stream() {
+        let context = TlsContext::load(&test_config(true)).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server_context = context.clone();
+        let server = std::thread::spawn(move || {
+            let (socket, _) = listener.accept().unwrap();
+            let mut stream = NetStream::accept(socket, Some(&server_context)).unwrap();
+            // echo everything back
+            let mut buf = [0; 1024];
+            loop {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                stream.write_all(&buf[..n]).unwrap();
+            }
+        });
+
+        let socket = TcpStream::connect(address).unwrap();
+        let mut writer = NetStream::connect(socket, Some(&context), "localhost").unwrap();
+        let mut reader = writer.try_clone().unwrap();
+        let data: Vec<u8> = (0..100_000).map(|i| i as u8).collect();
+        let expected = data.clone();
+        let reader = std::thread::spawn(move || {
+            let mut received = vec![0; expected.len()];
+            reader.read_exact(&mut received).unwrap();
+            assert_eq!(received, expected);
+        });
+        for chunk in data.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        reader.join().unwrap();
+        writer.shutdown().unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn tls_wrong_name() {
+        let context = TlsContext::load(&test_config(false)).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server_context = context.clone();
+        let server = std::thread::spawn(move || {
+            let (socket, _) = listener.accept().unwrap();
+            assert!(NetStream::accept(socket, Some(&server_context)).is_err());
+        });
+        let socket = TcpStream::connect(address).unwrap();
+        assert!(NetStream::connect(socket, Some(&context), "other-host").is_err());
+        server.join().unwrap();
+    }
+}
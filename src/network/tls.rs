@@ -0,0 +1,158 @@
+//! Encryption of the connections between the hosts with TLS.
+//!
+//! When the hosts of a remote environment have a [`TlsConfig`](crate::config::TlsConfig) every
+//! connection between a multiplexer and a demultiplexer is encrypted: the multiplexer is the
+//! client of the TLS session and verifies that the certificate of the demultiplexer is valid for
+//! the address of its host, the demultiplexer is the server and, with mutual authentication,
+//! verifies the certificate of the multiplexer.
+
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use crate::config::RuntimeConfig;
+#[cfg(feature = "tls")]
+use crate::config::TlsConfig;
+
+/// The client and server configurations of the TLS sessions of this host.
+#[derive(Clone)]
+pub(crate) struct TlsContext {
+    #[cfg(feature = "tls")]
+    pub(crate) client: Arc<rustls::ClientConfig>,
+    #[cfg(feature = "tls")]
+    pub(crate) server: Arc<rustls::ServerConfig>,
+}
+
+impl Debug for TlsContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsContext").finish_non_exhaustive()
+    }
+}
+
+impl TlsContext {
+    /// The TLS context of this host, `None` if its connections are not encrypted.
+    ///
+    /// Panics if the certificates cannot be loaded.
+    pub fn from_config(config: &RuntimeConfig) -> Option<Arc<Self>> {
+        let tls = config.tls()?;
+        #[cfg(feature = "tls")]
+        {
+            let context = Self::load(tls)
+                .unwrap_or_else(|e| panic!("Failed to load the TLS certificates: {e}"));
+            Some(Arc::new(context))
+        }
+        #[cfg(not(feature = "tls"))]
+        {
+            panic!(
+                "The connections are encrypted with the certificate {:?}, but the `tls` feature is not enabled",
+                tls.cert
+            )
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+pub(crate) use rustls_context::*;
+
+#[cfg(feature = "tls")]
+mod rustls_context {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+    use rustls::server::WebPkiClientVerifier;
+    use rustls::{ClientConfig, RootCertStore, ServerConfig};
+
+    use super::{TlsConfig, TlsContext};
+
+    #[derive(Debug, thiserror::Error)]
+    pub(crate) enum TlsError {
+        #[error("cannot read {0:?}: {1}")]
+        Pem(PathBuf, rustls::pki_types::pem::Error),
+
+        #[error(transparent)]
+        Rustls(#[from] rustls::Error),
+
+        #[error(transparent)]
+        Verifier(#[from] rustls::server::VerifierBuilderError),
+    }
+
+    impl TlsContext {
+        /// Build the TLS context from the certificates of `config`.
+        pub fn load(config: &TlsConfig) -> Result<Self, TlsError> {
+            let pem_error = |path: &Path| {
+                let path = path.to_path_buf();
+                move |e| TlsError::Pem(path, e)
+            };
+            let certs = CertificateDer::pem_file_iter(&config.cert)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .map_err(pem_error(&config.cert))?;
+            let key = PrivateKeyDer::from_pem_file(&config.key).map_err(pem_error(&config.key))?;
+            let mut roots = RootCertStore::empty();
+            for ca in CertificateDer::pem_file_iter(&config.ca).map_err(pem_error(&config.ca))? {
+                roots.add(ca.map_err(pem_error(&config.ca))?)?;
+            }
+            let roots = Arc::new(roots);
+
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let client = ClientConfig::builder_with_provider(provider.clone())
+                .with_safe_default_protocol_versions()?
+                .with_root_certificates(roots.clone());
+            let client = if config.mutual {
+                client.with_client_auth_cert(certs.clone(), key.clone_key())?
+            } else {
+                client.with_no_client_auth()
+            };
+
+            let server = ServerConfig::builder_with_provider(provider.clone())
+                .with_safe_default_protocol_versions()?;
+            let server = if config.mutual {
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(roots, provider).build()?;
+                server.with_client_cert_verifier(verifier)
+            } else {
+                server.with_no_client_auth()
+            };
+            let server = server.with_single_cert(certs, key)?;
+
+            Ok(Self {
+                client: Arc::new(client),
+                server: Arc::new(server),
+            })
+        }
+    }
+
+    /// The name the certificate of the host at `address` must be valid for.
+    pub(crate) fn server_name(address: &str) -> std::io::Result<ServerName<'static>> {
+        ServerName::try_from(address.to_string())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+    }
+}
+
+#[cfg(all(test, feature = "tls"))]
+pub(crate) mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// A certificate for `localhost`, signed by the test authority.
+    pub(crate) fn test_config(mutual: bool) -> TlsConfig {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/tls");
+        TlsConfig {
+            cert: dir.join("localhost.pem"),
+            key: dir.join("localhost.key"),
+            ca: dir.join("ca.pem"),
+            mutual,
+        }
+    }
+
+    #[test]
+    fn load_certificates() {
+        TlsContext::load(&test_config(true)).unwrap();
+
+        let mut config = test_config(false);
+        config.key = config.key.with_file_name("missing.key");
+        let err = TlsContext::load(&config).unwrap_err();
+        assert!(matches!(err, TlsError::Pem(path, _) if path == config.key));
+    }
+}
@@ -1,17 +1,32 @@
 #[cfg(feature = "tokio")]
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncWriteExt, WriteHalf};
 #[cfg(feature = "tokio")]
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::TcpListener;
 #[cfg(feature = "tokio")]
 use tokio::task::JoinHandle;
 
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use std::time::Duration;
 
 use crate::channel::{self, Sender, UnboundedReceiver, UnboundedSender};
-use crate::network::remote::remote_recv;
-use crate::network::{DemuxCoord, NetworkMessage, ReceiverEndpoint};
+use crate::config::NetworkCompression;
+use crate::network::remote::{accept_hello, remote_recv, remote_send_credits, remote_send_resume};
+use crate::network::resume::Delivered;
+use crate::network::stream::NetStream;
+use crate::network::tls::TlsContext;
+use crate::network::{DemuxCoord, NetworkMessage, ReceiverEndpoint, RemoteOptions};
 use crate::operator::ExchangeData;
+use crate::scheduler::HostId;
+
+/// How often the demultiplexer checks whether its channels are closed, once all the receivers
+/// have registered.
+const FINISHED_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+type RegisterMessage<In> = (ReceiverEndpoint, Sender<NetworkMessage<In>>);
+type Incoming = (HostId, NetStream, NetworkCompression);
 
 /// Like `NetworkReceiver`, but this should be used in a multiplexed channel (i.e. a remote one).
 ///
@@ -23,7 +38,7 @@ use crate::operator::ExchangeData;
 pub(crate) struct DemuxHandle<In: Send + 'static> {
     coord: DemuxCoord,
     /// Tell the dem&ultiplexer that a new receiver is present,
-    tx_senders: UnboundedSender<(ReceiverEndpoint, Sender<NetworkMessage<In>>)>,
+    tx_senders: UnboundedSender<RegisterMessage<In>>,
 }
 
 #[cfg(feature = "tokio")]
@@ -38,10 +53,17 @@ impl<In: ExchangeData> DemuxHandle<In> {
         coord: DemuxCoord,
         address: (String, u16),
         num_clients: usize,
+        options: RemoteOptions,
     ) -> (Self, JoinHandle<()>) {
         let (tx_senders, rx_senders) = channel::unbounded();
 
-        let join_handle = tokio::spawn(bind_remotes(coord, address, num_clients, rx_senders));
+        let join_handle = tokio::spawn(bind_remotes(
+            coord,
+            address,
+            num_clients,
+            options,
+            rx_senders,
+        ));
         (Self { coord, tx_senders }, join_handle)
     }
 
@@ -63,12 +85,16 @@ impl<In: ExchangeData> DemuxHandle<In> {
 }
 
 /// Bind the socket of this demultiplexer.
+///
+/// The socket keeps accepting connections until all the channels are closed, so the multiplexers
+/// whose connection dropped can resume their channel.
 #[cfg(feature = "tokio")]
 async fn bind_remotes<In: ExchangeData>(
     coord: DemuxCoord,
     address: (String, u16),
     num_clients: usize,
-    rx_senders: UnboundedReceiver<(ReceiverEndpoint, Sender<NetworkMessage<In>>)>,
+    options: RemoteOptions,
+    rx_senders: UnboundedReceiver<RegisterMessage<In>>,
 ) {
     let address = (address.0.as_ref(), address.1);
     let address: Vec<_> = address
@@ -96,88 +122,300 @@ async fn bind_remotes<In: ExchangeData>(
         coord, num_clients, address
     );
 
-    // the list of JoinHandle of all the spawned threads, including the demultiplexer one
-    let mut join_handles = vec![];
-    let mut tx_broadcast = vec![];
+    let (handshakes_tx, handshakes_rx) = flume::unbounded();
+    let accept_handle = tokio::spawn(accept_connections(
+        coord,
+        listener,
+        options.tls.clone(),
+        handshakes_tx,
+    ));
+    let mut routes = Routes::<In>::new(coord, num_clients, options.retry.reconnect_timeout);
+    while routes.routes.len() < num_clients {
+        let (host_id, stream, compression) = handshakes_rx
+            .recv_async()
+            .await
+            .expect("the connections are accepted until the end");
+        routes.route(host_id, stream, compression);
+    }
+    log::debug!("All connection to {} started, waiting for senders", coord);
 
-    let mut connected_clients = 0;
-    while connected_clients < num_clients {
-        let stream = listener.accept().await;
-        let (stream, peer_addr) = match stream {
+    // Broadcast senders
+    loop {
+        tokio::select! {
+            t = rx_senders.recv_async() => match t {
+                Ok(t) => {
+                    for route in routes.routes.values() {
+                        route.senders.as_ref().unwrap().send(t.clone()).unwrap();
+                    }
+                }
+                Err(_) => break,
+            },
+            Ok((host_id, stream, compression)) = handshakes_rx.recv_async() => {
+                routes.route(host_id, stream, compression);
+            }
+        }
+    }
+    // Start all demuxes
+    for route in routes.routes.values_mut() {
+        route.senders = None;
+    }
+    while !routes.join_handles.iter().all(|h| h.is_finished()) {
+        tokio::select! {
+            Ok((host_id, stream, compression)) = handshakes_rx.recv_async() => {
+                routes.route(host_id, stream, compression);
+            }
+            _ = tokio::time::sleep(FINISHED_POLL_INTERVAL) => {}
+        }
+    }
+    accept_handle.abort();
+    for handle in routes.join_handles {
+        handle.await.unwrap();
+    }
+    log::debug!("all demuxes for {} finished", coord);
+}
+
+/// Accept the connections of a demultiplexer, sending them after the handshake.
+#[cfg(feature = "tokio")]
+async fn accept_connections(
+    coord: DemuxCoord,
+    listener: TcpListener,
+    tls: Option<Arc<TlsContext>>,
+    handshakes: flume::Sender<Incoming>,
+) {
+    loop {
+        let (stream, peer_addr) = match listener.accept().await {
             Ok(stream) => stream,
             Err(e) => {
                 warn!("Failed to accept incoming connection at {}: {:?}", coord, e);
                 continue;
             }
         };
-        connected_clients += 1;
-        info!(
-            "Remote receiver at {} accepted a new connection from {} ({} / {})",
-            coord, peer_addr, connected_clients, num_clients
-        );
-
-        let (demux_tx, demux_rx) = flume::unbounded();
-        let join_handle = tokio::spawn(async move {
-            let mut senders = HashMap::new();
-            while let Ok((endpoint, sender)) = demux_rx.recv_async().await {
-                senders.insert(endpoint, sender);
+        let tls = tls.clone();
+        let handshakes = handshakes.clone();
+        // the handshake happens in a separate task, to keep accepting the other connections
+        tokio::spawn(async move {
+            let handshake = async {
+                let mut stream = NetStream::accept(stream, tls.as_deref()).await?;
+                let (host_id, compression) = accept_hello(&mut stream).await?;
+                std::io::Result::Ok((host_id, stream, compression))
+            };
+            match handshake.await {
+                Ok(incoming) => {
+                    let _ = handshakes.send(incoming);
+                }
+                Err(e) => warn!("{coord} failed the handshake with {peer_addr}: {e:?}"),
             }
-            log::debug!("demux got senders");
-            demux_thread::<In>(coord, senders, stream).await;
         });
-        join_handles.push(join_handle);
-        tx_broadcast.push(demux_tx);
     }
-    log::debug!("All connection to {} started, waiting for senders", coord);
+}
 
-    // Broadcast senders
-    while let Ok(t) = rx_senders.recv() {
-        for tx in tx_broadcast.iter() {
-            tx.send(t.clone()).unwrap();
+/// The demultiplexer task of the channel of a remote host.
+#[cfg(feature = "tokio")]
+struct Route<In: ExchangeData> {
+    /// The registered receivers, dropped when all of them are known.
+    senders: Option<flume::Sender<RegisterMessage<In>>>,
+    /// The new connections of the host.
+    streams: flume::Sender<(NetStream, NetworkCompression)>,
+}
+
+/// The channels of the remote hosts of a demultiplexer.
+#[cfg(feature = "tokio")]
+struct Routes<In: ExchangeData> {
+    coord: DemuxCoord,
+    num_clients: usize,
+    reconnect_timeout: Duration,
+    routes: HashMap<HostId, Route<In>>,
+    /// the list of JoinHandle of all the spawned tasks
+    join_handles: Vec<JoinHandle<()>>,
+}
+
+#[cfg(feature = "tokio")]
+impl<In: ExchangeData> Routes<In> {
+    fn new(coord: DemuxCoord, num_clients: usize, reconnect_timeout: Duration) -> Self {
+        Self {
+            coord,
+            num_clients,
+            reconnect_timeout,
+            routes: Default::default(),
+            join_handles: Default::default(),
         }
     }
-    drop(tx_broadcast); // Start all demuxes
-    for handle in join_handles {
-        handle.await.unwrap();
+
+    /// Give the connection of `host_id` to its task, starting it at the first connection.
+    fn route(&mut self, host_id: HostId, stream: NetStream, compression: NetworkCompression) {
+        let coord = self.coord;
+        let num_routes = self.routes.len();
+        match self.routes.entry(host_id) {
+            Entry::Occupied(route) => {
+                debug!("{coord} new connection from host {host_id}, resuming its channel");
+                let _ = route.get().streams.send((stream, compression));
+            }
+            Entry::Vacant(_) if num_routes == self.num_clients => {
+                warn!("{coord} unexpected connection from host {host_id}");
+            }
+            Entry::Vacant(entry) => {
+                info!(
+                    "Remote receiver at {} accepted a new connection from host {} ({} / {})",
+                    coord,
+                    host_id,
+                    num_routes + 1,
+                    self.num_clients
+                );
+                let (senders_tx, senders_rx) = flume::unbounded();
+                let (streams_tx, streams_rx) = flume::unbounded();
+                streams_tx.send((stream, compression)).unwrap();
+                let reconnect_timeout = self.reconnect_timeout;
+                self.join_handles.push(tokio::spawn(demux_thread::<In>(
+                    coord,
+                    senders_rx,
+                    streams_rx,
+                    reconnect_timeout,
+                )));
+                entry.insert(Route {
+                    senders: Some(senders_tx),
+                    streams: streams_tx,
+                });
+            }
+        }
     }
-    log::debug!("all demuxes for {} finished", coord);
 }
 
-/// Handle the connection with a remote sender.
-///
-/// Will deserialize the message upon arrival and send to the corresponding recipient the
-/// deserialized data. If the recipient is not yet known, it is waited until it registers.
-///
-/// # Upgrade path
+#[cfg(feature = "tokio")]
+fn peer_address(stream: &NetStream) -> String {
+    stream
+        .peer_addr()
+        .map(|a| a.to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// What the task of a channel tells the task that gives back its credits.
+#[cfg(feature = "tokio")]
+enum CreditEvent {
+    /// The channel resumed on a new connection: the multiplexer recomputes the credits not given
+    /// back on the previous one from the resume table.
+    Connection(WriteHalf<NetStream>, String),
+    /// A message has been delivered to its recipient.
+    Delivered(ReceiverEndpoint),
+}
+
+/// Give back the credits of the delivered messages to the multiplexer.
 ///
-/// Replace send with queue.
+/// The credits of all the messages delivered while the task was writing are sent together, a
+/// single frame for each recipient, so under load a frame gives back many credits.
+#[cfg(feature = "tokio")]
+async fn credit_task(coord: DemuxCoord, events: flume::Receiver<CreditEvent>) {
+    let mut conn: Option<(WriteHalf<NetStream>, String)> = None;
+    let mut credits: HashMap<ReceiverEndpoint, u32> = HashMap::new();
+    while let Ok(event) = events.recv_async().await {
+        for event in std::iter::once(event).chain(events.try_iter()) {
+            match event {
+                CreditEvent::Connection(writer, address) => {
+                    credits.clear();
+                    if let Some((mut prev, _)) = conn.replace((writer, address)) {
+                        let _ = prev.shutdown().await;
+                    }
+                }
+                CreditEvent::Delivered(dest) => *credits.entry(dest).or_default() += 1,
+            }
+        }
+        let Some((writer, address)) = conn.as_mut() else {
+            continue;
+        };
+        for (dest, n) in credits.drain() {
+            if let Err(e) = remote_send_credits(dest, n, writer).await {
+                log::trace!("{coord} failed to send credits to {address}: {e:?}");
+            }
+        }
+    }
+    if let Some((mut writer, _)) = conn {
+        let _ = writer.shutdown().await;
+    }
+}
+
+/// Handle the channel with a remote sender.
 ///
-/// The queue uses a hierarchical queue:
-/// + First try to reserve and put the value in the fast queue
-/// + If the fast queue is full, put in the slow (unbounded?) queue
-/// + Return an enum, either Queued or Overflowed
+/// Will deserialize the message upon arrival and send to the corresponding recipient the
+/// deserialized data. The messages are received only after all the recipients have registered.
 ///
-/// if overflowed send a yield request through a second channel
+/// Each message delivered to its recipient gives back a credit to the multiplexer, which sends
+/// at most `INITIAL_CREDITS` messages to each recipient before receiving new credits (see
+/// `CreditGate`). The credits are batched by a separate task, see `credit_task`. When the connection drops the task waits for the multiplexer to open a new
+/// one, and tells it how many messages have been delivered so far.
 #[cfg(feature = "tokio")]
 async fn demux_thread<In: ExchangeData>(
     coord: DemuxCoord,
-    senders: HashMap<ReceiverEndpoint, Sender<NetworkMessage<In>>>,
-    mut stream: TcpStream,
+    rx_senders: flume::Receiver<RegisterMessage<In>>,
+    streams: flume::Receiver<(NetStream, NetworkCompression)>,
+    reconnect_timeout: Duration,
 ) {
-    let address = stream
-        .peer_addr()
-        .map(|a| a.to_string())
-        .unwrap_or_else(|_| "unknown".to_string());
+    let mut delivered = Delivered::new();
+    let (mut stream, mut compression) = streams.recv_async().await.unwrap();
+    let mut address = peer_address(&stream);
+    // the multiplexer waits for the resume table before sending, even if it's empty
+    if let Err(e) = remote_send_resume(&mut stream, &delivered).await {
+        log::trace!("{coord} failed to send the resume table to {address}: {e:?}");
+    }
+    let (mut reader, writer) = tokio::io::split(stream);
+    let (credits, credit_events) = flume::unbounded();
+    credits
+        .send(CreditEvent::Connection(writer, address.clone()))
+        .unwrap();
+    let credit_handle = tokio::spawn(credit_task(coord, credit_events));
+
+    let mut senders = HashMap::new();
+    while let Ok((endpoint, sender)) = rx_senders.recv_async().await {
+        senders.insert(endpoint, sender);
+    }
     log::debug!("{} started", coord);
-    let mut scratch = Vec::new();
 
-    while let Some((dest, message)) = remote_recv(coord, &mut stream, &mut scratch, &address).await
-    {
-        if let Err(e) = senders[&dest].send(message) {
-            warn!("demux failed to send message to {}: {:?}", dest, e);
+    let mut scratch = Vec::new();
+    loop {
+        let next = tokio::select! {
+            received = remote_recv(coord, &mut reader, &mut scratch, compression) => match received {
+                Ok(Some((dest, message))) => {
+                    if let Err(e) = senders[&dest].send(message) {
+                        warn!("demux failed to send message to {}: {:?}", dest, e);
+                    }
+                    *delivered.entry(dest).or_default() += 1;
+                    // the message left the connection: give back its credit
+                    let _ = credits.send(CreditEvent::Delivered(dest));
+                    continue;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("{coord} lost the connection with {address}: {e:?}");
+                    tokio::time::timeout(reconnect_timeout, streams.recv_async())
+                        .await
+                        .ok()
+                        .and_then(Result::ok)
+                        .unwrap_or_else(|| {
+                            panic!(
+                                "{coord} lost the connection with {address}, not opened again in {reconnect_timeout:?}"
+                            )
+                        })
+                }
+            },
+            // a new connection means that the multiplexer gave up on the previous one
+            Ok(next) = streams.recv_async() => {
+                log::warn!("{coord} lost the connection with {address}, replaced by a new one");
+                next
+            }
+        };
+        let mut stream;
+        (stream, compression) = next;
+        address = peer_address(&stream);
+        if let Err(e) = remote_send_resume(&mut stream, &delivered).await {
+            log::trace!("{coord} failed to send the resume table to {address}: {e:?}");
         }
+        let writer;
+        (reader, writer) = tokio::io::split(stream);
+        let _ = credits.send(CreditEvent::Connection(writer, address.clone()));
     }
 
-    stream.shutdown().await.unwrap();
+    // the credit task shuts down the connection once it has sent all the credits
+    drop(credits);
+    if let Err(e) = credit_handle.await {
+        log::error!("{coord} credit task failed: {e:?}");
+    }
     log::debug!("{} finished", coord);
 }
@@ -1,3 +1,4 @@
 pub(super) mod demultiplexer;
 pub(super) mod multiplexer;
 pub(super) mod remote;
+pub(super) mod stream;
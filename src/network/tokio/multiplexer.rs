@@ -1,9 +1,10 @@
 use std::io::ErrorKind;
-use std::time::Duration;
 
 #[cfg(feature = "tokio")]
 use std::net::ToSocketAddrs;
 #[cfg(feature = "tokio")]
+use tokio::io::{AsyncWriteExt, WriteHalf};
+#[cfg(feature = "tokio")]
 use tokio::net::TcpStream;
 #[cfg(feature = "tokio")]
 use tokio::task::JoinHandle;
@@ -11,8 +12,15 @@ use tokio::task::JoinHandle;
 use tokio::time::sleep;
 
 use crate::channel::{self, Receiver, Sender};
-use crate::network::remote::remote_send;
-use crate::network::{DemuxCoord, NetworkMessage, ReceiverEndpoint};
+use crate::config::NetworkCompression;
+use crate::network::credit::{CreditGate, INITIAL_CREDITS};
+use crate::network::remote::{
+    encode_message, remote_recv_credits, remote_recv_resume, remote_send, remote_send_close,
+    send_hello,
+};
+use crate::network::resume::{Delivered, Frame, ReplayBuffer};
+use crate::network::stream::NetStream;
+use crate::network::{DemuxCoord, NetworkMessage, ReceiverEndpoint, RemoteOptions};
 use crate::operator::ExchangeData;
 
 // #[cfg(not(feature = "tokio"))]
@@ -20,17 +28,6 @@ use crate::operator::ExchangeData;
 
 use crate::network::NetworkSender;
 
-/// Maximum number of attempts to make for connecting to a remote host.
-const CONNECT_ATTEMPTS: usize = 32;
-/// Timeout for connecting to a remote host.
-#[cfg(not(feature = "tokio"))]
-const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
-/// To avoid spamming the connections, wait this timeout before trying again. If the connection
-/// fails again this timeout will be doubled up to `RETRY_MAX_TIMEOUT`.
-const RETRY_INITIAL_TIMEOUT: Duration = Duration::from_millis(8);
-/// Maximum timeout between connection attempts.
-const RETRY_MAX_TIMEOUT: Duration = Duration::from_secs(1);
-
 const MUX_CHANNEL_CAPACITY: usize = 10;
 /// Like `NetworkSender`, but this should be used in a multiplexed channel (i.e. a remote one).
 ///
@@ -45,15 +42,18 @@ impl<Out: ExchangeData> MultiplexingSender<Out> {
     /// Construct a new `MultiplexingSender` for a block.
     ///
     /// All the replicas of this block should point to this multiplexer (or one of its clones).
-    pub fn new(coord: DemuxCoord, address: (String, u16)) -> (Self, JoinHandle<()>) {
+    pub fn new(
+        coord: DemuxCoord,
+        address: (String, u16),
+        options: RemoteOptions,
+    ) -> (Self, JoinHandle<()>) {
         let (tx, rx) = channel::bounded(MUX_CHANNEL_CAPACITY);
         let join_handle = tokio::spawn(async move {
             log::debug!(
                 "mux connecting to {}",
                 address.to_socket_addrs().unwrap().next().unwrap()
             );
-            let stream = connect_remote(coord, address).await;
-            mux_thread::<Out>(coord, rx, stream).await;
+            mux_thread::<Out>(coord, rx, address, options).await;
         });
         (Self { tx: Some(tx) }, join_handle)
     }
@@ -75,21 +75,26 @@ impl<Out: ExchangeData> MultiplexingSender<Out> {
     }
 }
 
-/// Connect the sender to a remote channel located at the specified address.
+/// Connect the sender to a remote channel located at the specified address, returning the
+/// connection, its codec and the messages the demultiplexer already delivered.
 ///
 /// - At first the address is resolved to an actual address (DNS resolution)
-/// - Then at most `CONNECT_ATTEMPTS` are performed, and an exponential backoff is used in case
+/// - Then at most `connect_attempts` are performed, and an exponential backoff is used in case
 ///   of errors.
 /// - If the connection cannot be established this function will panic.
 #[cfg(feature = "tokio")]
-async fn connect_remote(coord: DemuxCoord, address: (String, u16)) -> TcpStream {
+async fn connect_remote(
+    coord: DemuxCoord,
+    address: &(String, u16),
+    options: &RemoteOptions,
+) -> (NetStream, NetworkCompression, Delivered) {
     let socket_addrs: Vec<_> = address
         .to_socket_addrs()
         .map_err(|e| format!("Failed to get the address for {}: {:?}", coord, e))
         .unwrap()
         .collect();
-    let mut retry_delay = RETRY_INITIAL_TIMEOUT;
-    for attempt in 1..=CONNECT_ATTEMPTS {
+    let retry = options.retry;
+    for attempt in 1..=retry.connect_attempts {
         log::debug!(
             "{} connecting to {:?} ({} attempt)",
             coord,
@@ -97,14 +102,17 @@ async fn connect_remote(coord: DemuxCoord, address: (String, u16)) -> TcpStream
             attempt,
         );
 
-        for address in socket_addrs.iter() {
-            match TcpStream::connect(address).await {
-                Ok(stream) => {
-                    return stream;
-                }
+        for socket_addr in socket_addrs.iter() {
+            match TcpStream::connect(socket_addr).await {
+                Ok(stream) => match handshake(coord, stream, &address.0, options).await {
+                    Ok(connection) => return connection,
+                    Err(err) => {
+                        log::warn!("{coord} failed the handshake with {socket_addr:?}: {err:?}");
+                    }
+                },
                 Err(err) => match err.kind() {
                     ErrorKind::TimedOut => {
-                        log::debug!("{coord} timeout connecting to {address:?}");
+                        log::debug!("{coord} timeout connecting to {socket_addr:?}");
                     }
                     ErrorKind::ConnectionRefused => {
                         log::log!(
@@ -113,49 +121,233 @@ async fn connect_remote(coord: DemuxCoord, address: (String, u16)) -> TcpStream
                             } else {
                                 log::Level::Debug
                             },
-                            "{coord} connection refused connecting to {address:?} ({attempt})"
+                            "{coord} connection refused connecting to {socket_addr:?} ({attempt})"
                         );
                     }
                     _ => {
-                        log::warn!("{coord} failed to connect to {address:?}: {err:?}");
+                        log::warn!("{coord} failed to connect to {socket_addr:?}: {err:?}");
                     }
                 },
             }
         }
 
+        let retry_delay = retry.delay(attempt);
         log::debug!(
             "{coord} retrying connection to {socket_addrs:?} in {}s",
             retry_delay.as_secs_f32(),
         );
-
         sleep(retry_delay).await;
-        retry_delay = (2 * retry_delay).min(RETRY_MAX_TIMEOUT);
     }
     panic!(
         "Failed to connect to remote {} at {:?} after {} attempts",
-        coord, address, CONNECT_ATTEMPTS
+        coord, address, retry.connect_attempts
     );
 }
 
+/// Establish the TLS session, introduce this host and receive the resume table of the channel.
+#[cfg(feature = "tokio")]
+async fn handshake(
+    coord: DemuxCoord,
+    stream: TcpStream,
+    host: &str,
+    options: &RemoteOptions,
+) -> std::io::Result<(NetStream, NetworkCompression, Delivered)> {
+    let mut stream = NetStream::connect(stream, options.tls.as_deref(), host).await?;
+    let compression = send_hello(&mut stream, options.host_id, options.compression).await?;
+    let delivered = remote_recv_resume(coord, &mut stream).await?;
+    Ok((stream, compression, delivered))
+}
+
+/// An open connection with the demultiplexer.
+#[cfg(feature = "tokio")]
+struct Connection {
+    writer: WriteHalf<NetStream>,
+    address: String,
+    /// The credits read from the connection, disconnected when the connection drops.
+    credit_rx: flume::Receiver<(ReceiverEndpoint, u32)>,
+    credit_handle: JoinHandle<()>,
+    /// Whether the writes succeeded so far: after a failure the messages are only kept for the
+    /// next connection.
+    healthy: bool,
+}
+
+#[cfg(feature = "tokio")]
+impl Connection {
+    fn open(coord: DemuxCoord, stream: NetStream) -> Self {
+        let address = stream
+            .peer_addr()
+            .map(|a| a.to_string())
+            .unwrap_or_else(|_| "unknown".to_string());
+        log::debug!("{} connected to {:?}", coord, address);
+
+        // the credits come back on the same connection, read them in a separate task
+        let (mut reader, writer) = tokio::io::split(stream);
+        let (credit_tx, credit_rx) = flume::unbounded();
+        let credit_handle = tokio::spawn(async move {
+            while let Some(credits) = remote_recv_credits(coord, &mut reader).await {
+                if credit_tx.send_async(credits).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            writer,
+            address,
+            credit_rx,
+            credit_handle,
+            healthy: true,
+        }
+    }
+
+    async fn write(&mut self, frame: &Frame, dest: ReceiverEndpoint) {
+        if !self.healthy {
+            return;
+        }
+        if let Err(e) = remote_send(frame, dest, &mut self.writer).await {
+            log::warn!(
+                "Failed to send message to {dest} at {}: {e:?}",
+                self.address
+            );
+            self.healthy = false;
+            // stop the reader too, the credits channel tells that the connection dropped
+            self.credit_handle.abort();
+        }
+    }
+
+    async fn close(mut self) {
+        let _ = self.writer.shutdown().await;
+        if let Err(e) = self.credit_handle.await {
+            assert!(e.is_cancelled(), "{e:?}");
+        }
+    }
+}
+
+/// The channel of a multiplexer, which survives the connections that drop.
+#[cfg(feature = "tokio")]
+struct MuxChannel<Out> {
+    coord: DemuxCoord,
+    address: (String, u16),
+    options: RemoteOptions,
+    compression: NetworkCompression,
+    conn: Connection,
+    gate: CreditGate<NetworkMessage<Out>>,
+    replay: ReplayBuffer,
+    reconnections: u32,
+}
+
+#[cfg(feature = "tokio")]
+impl<Out: ExchangeData> MuxChannel<Out> {
+    async fn connect(coord: DemuxCoord, address: (String, u16), options: RemoteOptions) -> Self {
+        // the first connection has nothing to resume
+        let (stream, compression, _) = connect_remote(coord, &address, &options).await;
+        log::debug!("{coord} compresses the messages with {compression:?}");
+        Self {
+            coord,
+            conn: Connection::open(coord, stream),
+            address,
+            options,
+            compression,
+            gate: CreditGate::new(INITIAL_CREDITS),
+            replay: Default::default(),
+            reconnections: 0,
+        }
+    }
+
+    async fn push(&mut self, dest: ReceiverEndpoint, message: NetworkMessage<Out>) {
+        if let Some(message) = self.gate.push(dest, message) {
+            self.send(dest, message).await;
+        }
+    }
+
+    async fn send(&mut self, dest: ReceiverEndpoint, message: NetworkMessage<Out>) {
+        let frame = encode_message(message, dest, self.compression);
+        self.conn.write(&frame, dest).await;
+        self.replay.push(dest, frame);
+    }
+
+    async fn grant(&mut self, dest: ReceiverEndpoint, credits: u32) {
+        self.replay.ack(dest, credits as u64);
+        for message in self.gate.grant(dest, credits as usize) {
+            self.send(dest, message).await;
+        }
+    }
+
+    /// Open a new connection, sending again the messages not delivered yet.
+    ///
+    /// Panics after `max_reconnections` reconnections: the job failed.
+    async fn reconnect(&mut self) {
+        self.reconnections += 1;
+        let max = self.options.retry.max_reconnections;
+        if self.reconnections > max {
+            panic!(
+                "{} lost the connection to {} more than {max} times",
+                self.coord, self.conn.address
+            );
+        }
+        log::warn!(
+            "{} lost the connection to {}, reconnecting ({}/{max})",
+            self.coord,
+            self.conn.address,
+            self.reconnections
+        );
+        let (stream, compression, delivered) =
+            connect_remote(self.coord, &self.address, &self.options).await;
+        assert_eq!(
+            compression, self.compression,
+            "{} negotiated a different compression after reconnecting",
+            self.coord
+        );
+        let prev = std::mem::replace(&mut self.conn, Connection::open(self.coord, stream));
+        prev.close().await;
+
+        // the messages delivered while the connection dropped have lost their credits
+        let lost = self.replay.resume(&delivered);
+        for (dest, frame) in self.replay.frames() {
+            self.conn.write(frame, dest).await;
+        }
+        for (dest, credits) in lost {
+            for message in self.gate.grant(dest, credits) {
+                self.send(dest, message).await;
+            }
+        }
+    }
+
+    /// Tell the demultiplexer that the channel is closed, all the messages have been delivered.
+    async fn close(mut self) {
+        debug_assert!(self.replay.is_empty());
+        while !self.conn.healthy || remote_send_close(&mut self.conn.writer).await.is_err() {
+            self.reconnect().await;
+        }
+        self.conn.close().await;
+    }
+}
+
 #[cfg(feature = "tokio")]
 async fn mux_thread<Out: ExchangeData>(
     coord: DemuxCoord,
     rx: Receiver<(ReceiverEndpoint, NetworkMessage<Out>)>,
-    mut stream: TcpStream,
+    address: (String, u16),
+    options: RemoteOptions,
 ) {
-    use tokio::io::AsyncWriteExt;
+    let mut channel = MuxChannel::<Out>::connect(coord, address, options).await;
+    let mut open = true;
 
-    let address = stream
-        .peer_addr()
-        .map(|a| a.to_string())
-        .unwrap_or_else(|_| "unknown".to_string());
-    log::debug!("{} connected to {:?}", coord, address);
-    let mut scratch = Vec::new();
-
-    while let Ok((dest, message)) = rx.recv_async().await {
-        remote_send(message, dest, &mut stream, &mut scratch, &address).await;
+    // wait for all the credits before closing, so all the messages have been received
+    while open || !channel.gate.is_idle() {
+        // stop accepting messages while a receiver is too slow, the senders will block
+        let accept = open && !channel.gate.is_saturated();
+        tokio::select! {
+            msg = rx.recv_async(), if accept => match msg {
+                Ok((dest, message)) => channel.push(dest, message).await,
+                Err(_) => open = false,
+            },
+            credits = channel.conn.credit_rx.recv_async() => match credits {
+                Ok((dest, credits)) => channel.grant(dest, credits).await,
+                Err(_) => channel.reconnect().await,
+            },
+        }
     }
 
-    stream.shutdown().await.unwrap();
+    channel.close().await;
     log::debug!("{} finished", coord);
 }
@@ -0,0 +1,106 @@
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+use crate::network::tls::TlsContext;
+
+/// A connection between a multiplexer and a demultiplexer, encrypted if the host has a
+/// `TlsContext`.
+#[derive(Debug)]
+pub(crate) enum NetStream {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<tokio_rustls::TlsStream<TcpStream>>),
+}
+
+impl NetStream {
+    /// Start the client side of the connection with the host at `host`.
+    pub async fn connect(
+        socket: TcpStream,
+        tls: Option<&TlsContext>,
+        host: &str,
+    ) -> std::io::Result<Self> {
+        match tls {
+            None => Ok(Self::Plain(socket)),
+            #[cfg(feature = "tls")]
+            Some(tls) => {
+                let name = crate::network::tls::server_name(host)?;
+                let connector = tokio_rustls::TlsConnector::from(tls.client.clone());
+                let stream = connector.connect(name, socket).await?;
+                Ok(Self::Tls(Box::new(stream.into())))
+            }
+            #[cfg(not(feature = "tls"))]
+            Some(_) => unreachable!("TLS requires the `tls` feature (connecting to {host})"),
+        }
+    }
+
+    /// Start the server side of the connection.
+    pub async fn accept(socket: TcpStream, tls: Option<&TlsContext>) -> std::io::Result<Self> {
+        match tls {
+            None => Ok(Self::Plain(socket)),
+            #[cfg(feature = "tls")]
+            Some(tls) => {
+                let acceptor = tokio_rustls::TlsAcceptor::from(tls.server.clone());
+                let stream = acceptor.accept(socket).await?;
+                Ok(Self::Tls(Box::new(stream.into())))
+            }
+            #[cfg(not(feature = "tls"))]
+            Some(_) => unreachable!("TLS requires the `tls` feature"),
+        }
+    }
+
+    pub fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+        match self {
+            Self::Plain(stream) => stream.peer_addr(),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => stream.get_ref().0.peer_addr(),
+        }
+    }
+}
+
+impl AsyncRead for NetStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for NetStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
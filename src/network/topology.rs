@@ -15,9 +15,13 @@ use typemap_rev::{TypeMap, TypeMapKey};
 use crate::channel::Sender;
 use crate::config::RuntimeConfig;
 use crate::network::demultiplexer::DemuxHandle;
+#[cfg(not(feature = "tokio"))]
+use crate::network::link::Links;
 use crate::network::multiplexer::MultiplexingSender;
+use crate::network::tls::TlsContext;
 use crate::network::{
-    local_channel, BlockCoord, Coord, DemuxCoord, NetworkReceiver, NetworkSender, ReceiverEndpoint,
+    local_channel, stealing_channel, BlockCoord, Coord, DemuxCoord, NetworkReceiver, NetworkSender,
+    ReceiverEndpoint, RemoteOptions, Stealer,
 };
 use crate::operator::ExchangeData;
 use crate::scheduler::{BlockId, HostId};
@@ -38,6 +42,13 @@ impl<In: ExchangeData> TypeMapKey for SenderKey<In> {
     type Value = HashMap<ReceiverEndpoint, NetworkSender<In>, crate::block::CoordHasherBuilder>;
 }
 
+/// This struct is used to index inside the `typemap` with the steal queues.
+struct StealQueueKey<In: ExchangeData>(PhantomData<In>);
+
+impl<In: ExchangeData> TypeMapKey for StealQueueKey<In> {
+    type Value = HashMap<ReceiverEndpoint, NetworkReceiver<In>, crate::block::CoordHasherBuilder>;
+}
+
 /// This struct is used to index inside the `typemap` with the `DemultiplexingReceiver`s.
 struct DemultiplexingReceiverKey<In: ExchangeData>(PhantomData<In>);
 
@@ -65,6 +76,8 @@ struct SenderMetadata {
 pub(crate) struct NetworkTopology {
     /// Configuration of the environment.
     config: Arc<RuntimeConfig>,
+    /// How the connections with the other hosts are made.
+    remote: RemoteOptions,
     /// All the registered receivers.
     ///
     /// Since the `NetworkReceiver` is generic over the element type we cannot simply store them
@@ -86,6 +99,10 @@ pub(crate) struct NetworkTopology {
     ///
     /// This map is indexed using `SenderKey`.
     senders: Option<TypeMap>,
+    /// The steal queues of the stealable channels, see `Stealer`.
+    ///
+    /// This map is indexed using `StealQueueKey`.
+    steal_queues: Option<TypeMap>,
 
     /// All the registered demultiplexers.
     ///
@@ -114,6 +131,8 @@ pub(crate) struct NetworkTopology {
     ///
     /// This just makes sure no endpoint is registered twice.
     registered_receivers: HashSet<ReceiverEndpoint>,
+    /// The local endpoints whose replicas steal the batches of each other.
+    stealable: HashSet<ReceiverEndpoint>,
 
     /// The mapping between the coordinate of a demultiplexer of a block to the actual address/port
     /// of that demultiplexer in the network.
@@ -142,9 +161,18 @@ impl std::fmt::Debug for NetworkTopology {
 impl NetworkTopology {
     pub(crate) fn new(config: Arc<RuntimeConfig>) -> Self {
         NetworkTopology {
+            remote: RemoteOptions {
+                host_id: config.host_id().unwrap_or_default(),
+                tls: TlsContext::from_config(&config),
+                compression: config.network_compression(),
+                retry: config.network_retry(),
+                #[cfg(not(feature = "tokio"))]
+                links: None,
+            },
             config,
             receivers: Some(TypeMap::new()),
             senders: Some(TypeMap::new()),
+            steal_queues: Some(TypeMap::new()),
             demultiplexers: Some(TypeMap::new()),
             multiplexers: Some(TypeMap::new()),
             next: Default::default(),
@@ -153,6 +181,7 @@ impl NetworkTopology {
             block_replicas: Default::default(),
             used_receivers: Default::default(),
             registered_receivers: Default::default(),
+            stealable: Default::default(),
             demultiplexer_addresses: Default::default(),
             #[cfg(not(feature = "tokio"))]
             join_handles: Default::default(),
@@ -182,6 +211,9 @@ impl NetworkTopology {
         for handle in self.join_handles.drain(..) {
             handle.join().unwrap();
         }
+        if let Some(links) = self.remote.links.take() {
+            links.stop();
+        }
     }
 
     /// Get all the outgoing senders from a replica.
@@ -279,6 +311,45 @@ impl NetworkTopology {
             .unwrap()
     }
 
+    /// Get the stealer of a replica endpoint marked with `set_stealable`, with the steal queues of
+    /// the other local replicas of the same block. This may register the channels of the replicas
+    /// if they were not registered before.
+    pub fn get_stealer<T: ExchangeData>(
+        &mut self,
+        receiver_endpoint: ReceiverEndpoint,
+    ) -> Option<Stealer<T>> {
+        if !self.stealable.contains(&receiver_endpoint) {
+            return None;
+        }
+        let group = DemuxCoord::from(receiver_endpoint);
+        let mut siblings: Vec<_> = self
+            .stealable
+            .iter()
+            .filter(|endpoint| DemuxCoord::from(**endpoint) == group)
+            .cloned()
+            .collect();
+        siblings.sort();
+        for &endpoint in &siblings {
+            if !self.registered_receivers.contains(&endpoint) {
+                self.register_channel::<T>(endpoint);
+            }
+        }
+
+        let queues = self
+            .steal_queues
+            .as_ref()
+            .unwrap()
+            .get::<StealQueueKey<T>>()
+            .unwrap();
+        let own = queues[&receiver_endpoint].clone();
+        let siblings = siblings
+            .into_iter()
+            .filter(|&endpoint| endpoint != receiver_endpoint)
+            .map(|endpoint| queues[&endpoint].clone())
+            .collect();
+        Some(Stealer::new(own, siblings))
+    }
+
     fn register_demux<T: ExchangeData>(
         &mut self,
         receiver_endpoint: ReceiverEndpoint,
@@ -314,7 +385,8 @@ impl NetworkTopology {
             }
             if !prev.is_empty() {
                 let address = self.demultiplexer_addresses[&demux_coord].clone();
-                let (demux, join_handle) = DemuxHandle::new(demux_coord, address, prev.len());
+                let (demux, join_handle) =
+                    DemuxHandle::new(demux_coord, address, prev.len(), self.remote.clone());
                 #[cfg(not(feature = "tokio"))]
                 self.join_handles.push(join_handle);
                 #[cfg(feature = "tokio")]
@@ -343,7 +415,8 @@ impl NetworkTopology {
 
         if let Entry::Vacant(e) = muxers.entry(demux_coord) {
             let address = self.demultiplexer_addresses[&demux_coord].clone();
-            let (mux, join_handle) = MultiplexingSender::new(demux_coord, address);
+            let (mux, join_handle) =
+                MultiplexingSender::new(demux_coord, address, self.remote.clone());
             #[cfg(not(feature = "tokio"))]
             self.join_handles.push(join_handle);
             #[cfg(feature = "tokio")]
@@ -356,6 +429,24 @@ impl NetworkTopology {
             .get_sender(receiver_endpoint)
     }
 
+    /// Create the channel of a local receiver, with a steal queue if the receiver is stealable.
+    fn new_local_channel<T: ExchangeData>(
+        &mut self,
+        receiver_endpoint: ReceiverEndpoint,
+    ) -> (NetworkSender<T>, NetworkReceiver<T>) {
+        if !self.stealable.contains(&receiver_endpoint) {
+            return local_channel(receiver_endpoint);
+        }
+        let (sender, receiver, steal_queue) = stealing_channel(receiver_endpoint);
+        self.steal_queues
+            .as_mut()
+            .unwrap()
+            .entry::<StealQueueKey<T>>()
+            .or_default()
+            .insert(receiver_endpoint, steal_queue);
+        (sender, receiver)
+    }
+
     /// Register the channel for the given receiver.
     ///
     /// This will initialize both the sender and the receiver to the receiver. If it's appropriate
@@ -385,7 +476,7 @@ impl NetworkTopology {
                         .or_default()
                         .insert(receiver_endpoint, sender);
                 } else {
-                    let (sender, receiver) = local_channel(receiver_endpoint);
+                    let (sender, receiver) = self.new_local_channel(receiver_endpoint);
 
                     if receiver_endpoint.coord.host_id == self.config.host_id().unwrap() {
                         self.register_demux(receiver_endpoint, sender.clone_inner());
@@ -406,7 +497,7 @@ impl NetworkTopology {
                 };
             }
             RuntimeConfig::Local(_) => {
-                let (sender, receiver) = local_channel(receiver_endpoint);
+                let (sender, receiver) = self.new_local_channel(receiver_endpoint);
 
                 self.receivers
                     .as_mut()
@@ -477,6 +568,19 @@ impl NetworkTopology {
         }
     }
 
+    /// Let the local replicas of the block of `receiver_endpoint` steal the batches that the
+    /// previous block sends to each other, see `Stealer`.
+    ///
+    /// This has to be called before any channel towards the block is registered.
+    pub fn set_stealable(&mut self, receiver_endpoint: ReceiverEndpoint) {
+        assert_eq!(
+            Some(receiver_endpoint.coord.host_id),
+            self.config.host_id(),
+            "only the local replicas can steal"
+        );
+        self.stealable.insert(receiver_endpoint);
+    }
+
     /// The list of previous replicas of a given replica.
     pub fn prev(&self, coord: Coord) -> Vec<(Coord, TypeId)> {
         if let Some(prev) = self.prev.get(&coord) {
@@ -530,6 +634,24 @@ impl NetworkTopology {
             log::debug!("demux {} socket: {:?}", coord, address);
             self.demultiplexer_addresses.insert(coord, address);
         }
+
+        if config.multiplex_connections {
+            #[cfg(not(feature = "tokio"))]
+            {
+                let addresses = config
+                    .hosts
+                    .iter()
+                    .map(|host| (host.address.clone(), host.base_port))
+                    .collect();
+                self.remote.links = Some(Arc::new(Links::start(
+                    self.remote.host_id,
+                    addresses,
+                    self.remote.tls.clone(),
+                )));
+            }
+            #[cfg(feature = "tokio")]
+            panic!("the connection multiplexing is not available with the tokio feature");
+        }
     }
 
     /// Finalize the topology and start mutliplexers and demultiplexers
@@ -538,6 +660,7 @@ impl NetworkTopology {
         // receivers.
         self.receivers.take();
         self.senders.take();
+        self.steal_queues.take();
 
         self.demultiplexers.take();
         self.multiplexers.take();
@@ -636,10 +759,8 @@ mod tests {
     #[cfg(not(feature = "tokio"))]
     #[test]
     fn test_remote_topology() {
-        use crate::config::ConfigBuilder;
-
-        let mut toml_path = tempfile::NamedTempFile::new().unwrap();
-        let config_toml = r#"[[host]]
+        remote_topology(
+            r#"[[host]]
 address = "127.0.0.1"
 base_port = 21841
 num_cores = 1
@@ -647,7 +768,32 @@ num_cores = 1
 address = "127.0.0.1"
 base_port = 31258
 num_cores = 1
-"#;
+"#,
+        );
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    #[test]
+    fn test_remote_topology_multiplexed() {
+        remote_topology(
+            r#"multiplex_connections = true
+[[host]]
+address = "127.0.0.1"
+base_port = 21941
+num_cores = 1
+[[host]]
+address = "127.0.0.1"
+base_port = 31358
+num_cores = 1
+"#,
+        );
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    fn remote_topology(config_toml: &str) {
+        use crate::config::ConfigBuilder;
+
+        let mut toml_path = tempfile::NamedTempFile::new().unwrap();
         std::io::Write::write_all(&mut toml_path, config_toml.as_bytes()).unwrap();
 
         // s1 [b0, h0, r0] -> r1 [b2, h1, r0] (endpoint 1) type=i32
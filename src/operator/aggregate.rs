@@ -0,0 +1,221 @@
+use crate::operator::{ExchangeData, ExchangeDataKey, Operator};
+use crate::stream::{KeyedStream, Stream};
+
+/// A two-phase aggregation, used by [`Stream::aggregate`] and [`Stream::group_by_aggregate`].
+///
+/// Each replica adds its items to a local accumulator of type `Acc`, then the accumulators of
+/// all the replicas are merged and the final accumulator is turned into the result. The merge
+/// must be associative, but the result can be computed in any way from the accumulator: for
+/// example an average keeps the sum and the count of the items, and divides them at the end.
+///
+/// ## Example
+///
+/// ```
+/// # use renoir::operator::AggregateFn;
+/// #[derive(Clone)]
+/// struct Average;
+///
+/// impl AggregateFn<f64> for Average {
+///     type Acc = (f64, usize);
+///     type Out = f64;
+///
+///     fn create_accumulator(&self) -> (f64, usize) {
+///         (0.0, 0)
+///     }
+///
+///     fn add(&self, acc: &mut (f64, usize), item: f64) {
+///         acc.0 += item;
+///         acc.1 += 1;
+///     }
+///
+///     fn merge(&self, acc: &mut (f64, usize), other: (f64, usize)) {
+///         acc.0 += other.0;
+///         acc.1 += other.1;
+///     }
+///
+///     fn result(&self, acc: (f64, usize)) -> f64 {
+///         acc.0 / acc.1 as f64
+///     }
+/// }
+/// ```
+pub trait AggregateFn<I>: Clone + Send + 'static {
+    /// The partial state of the aggregation, sent between the replicas.
+    type Acc: ExchangeData;
+    /// The result of the aggregation.
+    type Out: Send + 'static;
+
+    /// Create an empty accumulator.
+    fn create_accumulator(&self) -> Self::Acc;
+
+    /// Add an item to an accumulator.
+    fn add(&self, acc: &mut Self::Acc, item: I);
+
+    /// Merge into `acc` the accumulator of another replica.
+    fn merge(&self, acc: &mut Self::Acc, other: Self::Acc);
+
+    /// Compute the result from the final accumulator.
+    fn result(&self, acc: Self::Acc) -> Self::Out;
+}
+
+impl<I, Op> Stream<Op>
+where
+    I: Send + 'static,
+    Op: Operator<Out = I> + 'static,
+{
+    /// Aggregate the stream into a single value using an [`AggregateFn`].
+    ///
+    /// This is like [`Stream::fold_assoc`], but the value sent between the replicas (the
+    /// accumulator) can be different from the result, which is computed once at the end. This
+    /// allows aggregating in parallel the results that cannot be merged directly, like averages.
+    ///
+    /// **Note**: this operator will retain all the messages of the stream and emit the values only
+    /// when the stream ends. Therefore this is not properly _streaming_.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::AggregateFn;
+    /// # let mut env = StreamContext::new_local();
+    /// #[derive(Clone)]
+    /// struct Average;
+    ///
+    /// impl AggregateFn<i32> for Average {
+    ///     type Acc = (i32, usize);
+    ///     type Out = f64;
+    ///
+    ///     fn create_accumulator(&self) -> (i32, usize) {
+    ///         (0, 0)
+    ///     }
+    ///     fn add(&self, acc: &mut (i32, usize), item: i32) {
+    ///         *acc = (acc.0 + item, acc.1 + 1);
+    ///     }
+    ///     fn merge(&self, acc: &mut (i32, usize), other: (i32, usize)) {
+    ///         *acc = (acc.0 + other.0, acc.1 + other.1);
+    ///     }
+    ///     fn result(&self, acc: (i32, usize)) -> f64 {
+    ///         acc.0 as f64 / acc.1 as f64
+    ///     }
+    /// }
+    ///
+    /// let s = env.stream_iter(0..5);
+    /// let res = s.aggregate(Average).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![2.0]);
+    /// ```
+    pub fn aggregate<A>(self, aggregate: A) -> Stream<impl Operator<Out = A::Out>>
+    where
+        A: AggregateFn<I>,
+    {
+        let (local, global) = (aggregate.clone(), aggregate.clone());
+        self.fold_assoc(
+            aggregate.create_accumulator(),
+            move |acc, item| local.add(acc, item),
+            move |acc, other| global.merge(acc, other),
+        )
+        .map(move |acc| aggregate.result(acc))
+    }
+
+    /// Aggregate separately the items of each key using an [`AggregateFn`].
+    ///
+    /// This is like [`Stream::group_by_fold`]: the items are first aggregated locally on each
+    /// replica, and only the accumulators are sent through the network to be merged. See
+    /// [`Stream::aggregate`] for the differences with a fold.
+    ///
+    /// **Note**: this operator will retain all the messages of the stream and emit the values only
+    /// when the stream ends. Therefore this is not properly _streaming_.
+    ///
+    /// **Note**: this operator will split the current block.
+    pub fn group_by_aggregate<K, Fk, A>(
+        self,
+        keyer: Fk,
+        aggregate: A,
+    ) -> KeyedStream<impl Operator<Out = (K, A::Out)>>
+    where
+        I: Clone,
+        Fk: Fn(&I) -> K + Send + Clone + 'static,
+        K: ExchangeDataKey,
+        A: AggregateFn<I>,
+    {
+        let (local, global) = (aggregate.clone(), aggregate.clone());
+        self.group_by_fold(
+            keyer,
+            aggregate.create_accumulator(),
+            move |acc, item| local.add(acc, item),
+            move |acc, other| global.merge(acc, other),
+        )
+        .map(move |(_, acc)| aggregate.result(acc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AggregateFn;
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    /// Concatenate the items in the order of their sequence number, a non-commutative result.
+    #[derive(Clone)]
+    struct Concat;
+
+    impl AggregateFn<(u32, char)> for Concat {
+        type Acc = Vec<(u32, char)>;
+        type Out = String;
+
+        fn create_accumulator(&self) -> Self::Acc {
+            Vec::new()
+        }
+
+        fn add(&self, acc: &mut Self::Acc, item: (u32, char)) {
+            acc.push(item);
+        }
+
+        fn merge(&self, acc: &mut Self::Acc, other: Self::Acc) {
+            acc.extend(other);
+        }
+
+        fn result(&self, mut acc: Self::Acc) -> String {
+            acc.sort_unstable();
+            acc.into_iter().map(|(_, c)| c).collect()
+        }
+    }
+
+    #[test]
+    fn aggregate() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_par_iter(0..26u32)
+            .map(|i| (i, (b'a' + i as u8) as char))
+            .aggregate(Concat)
+            .collect_vec();
+        env.execute_blocking();
+
+        assert_eq!(res.get().unwrap(), vec![('a'..='z').collect::<String>()]);
+    }
+
+    #[test]
+    fn group_by_aggregate() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_par_iter(0..26u32)
+            .map(|i| (i, (b'a' + i as u8) as char))
+            .group_by_aggregate(|(i, _)| i % 2, Concat)
+            .collect_vec();
+        env.execute_blocking();
+
+        let mut res = res.get().unwrap();
+        res.sort_unstable();
+        assert_eq!(
+            res,
+            vec![
+                (0, "acegikmoqsuwy".to_string()),
+                (1, "bdfhjlnprtvxz".to_string())
+            ]
+        );
+    }
+}
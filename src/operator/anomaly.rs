@@ -0,0 +1,199 @@
+use crate::operator::{Data, DataKey, Operator};
+use crate::KeyedStream;
+
+/// A detector of anomalous values, used by [`KeyedStream::detect_anomalies`].
+///
+/// A detector keeps a model of the values seen so far: each new value is scored against the
+/// model (the higher the score, the more anomalous the value) and then added to it. A copy of
+/// the detector is kept for each key.
+pub trait AnomalyDetector: Clone + Send + 'static {
+    /// Score `value` against the values seen so far, then add it to the model. Returns `None`
+    /// while the detector has not seen enough values.
+    fn score(&mut self, value: f64) -> Option<f64>;
+}
+
+/// Detector scoring the values with their z-score, the distance from the mean in standard
+/// deviations, computed incrementally with Welford's algorithm.
+#[derive(Clone, Debug)]
+pub struct ZScore {
+    warmup: usize,
+    count: usize,
+    mean: f64,
+    /// Sum of the squared differences from the mean.
+    m2: f64,
+}
+
+impl Default for ZScore {
+    fn default() -> Self {
+        Self::new(10)
+    }
+}
+
+impl ZScore {
+    /// A detector that starts scoring after `warmup` values (at least 2).
+    pub fn new(warmup: usize) -> Self {
+        Self {
+            warmup: warmup.max(2),
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    /// The mean of the values seen so far.
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// The sample standard deviation of the values seen so far, or 0 with less than 2 values.
+    pub fn std_dev(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            (self.m2 / (self.count - 1) as f64).sqrt()
+        }
+    }
+
+    fn update(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+}
+
+impl AnomalyDetector for ZScore {
+    fn score(&mut self, value: f64) -> Option<f64> {
+        let score = (self.count >= self.warmup).then(|| {
+            let std_dev = self.std_dev();
+            let distance = (value - self.mean).abs();
+            if std_dev > 0.0 {
+                distance / std_dev
+            } else if distance > 0.0 {
+                f64::INFINITY
+            } else {
+                0.0
+            }
+        });
+        self.update(value);
+        score
+    }
+}
+
+/// Detector comparing each value with the values at the same position of the previous seasons,
+/// for data with a periodic pattern.
+///
+/// The values are split into `period` slots by their position modulo `period` (for example the
+/// hour of the day, with one value per hour and a period of 24), and each slot is scored with
+/// its own [`ZScore`].
+#[derive(Clone, Debug)]
+pub struct SeasonalZScore {
+    slots: Vec<ZScore>,
+    position: usize,
+}
+
+impl SeasonalZScore {
+    /// A detector with `period` slots, each one scoring after `warmup` seasons.
+    pub fn new(period: usize, warmup: usize) -> Self {
+        assert!(period > 0, "the period must not be empty");
+        Self {
+            slots: vec![ZScore::new(warmup); period],
+            position: 0,
+        }
+    }
+}
+
+impl AnomalyDetector for SeasonalZScore {
+    fn score(&mut self, value: f64) -> Option<f64> {
+        let slot = self.position;
+        self.position = (self.position + 1) % self.slots.len();
+        self.slots[slot].score(value)
+    }
+}
+
+impl<K, I, Op> KeyedStream<Op>
+where
+    K: DataKey,
+    I: Data,
+    Op: Operator<Out = (K, I)> + 'static,
+{
+    /// Forward only the anomalous elements of each key, together with their score.
+    ///
+    /// The numeric value of each element is extracted with `value` and scored by a copy of
+    /// `detector` kept for its key, and the elements whose score exceeds `threshold` are
+    /// forwarded. All the values, anomalous or not, are added to the model of the detector.
+    ///
+    /// Use [`ZScore`] for values around a stable mean, [`SeasonalZScore`] for values with a
+    /// periodic pattern, or implement [`AnomalyDetector`] for a custom model.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::ZScore;
+    /// # let mut env = StreamContext::new_local();
+    /// let latencies = vec![10, 12, 11, 9, 10, 11, 250, 10, 12];
+    /// let res = env
+    ///     .stream_iter(latencies.into_iter())
+    ///     .group_by(|_| 0)
+    ///     .detect_anomalies(ZScore::new(5), 3.0, |&l| l as f64)
+    ///     .map(|(_, (latency, _score))| latency)
+    ///     .collect_vec();
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![(0, 250)]);
+    /// ```
+    pub fn detect_anomalies<D, F>(
+        self,
+        detector: D,
+        threshold: f64,
+        value: F,
+    ) -> KeyedStream<impl Operator<Out = (K, (I, f64))>>
+    where
+        D: AnomalyDetector,
+        F: Fn(&I) -> f64 + Clone + Send + 'static,
+    {
+        let mut detector = detector;
+        self.rich_filter_map(move |(_, item)| {
+            let score = detector.score(value(&item))?;
+            (score > threshold).then_some((item, score))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnomalyDetector, SeasonalZScore, ZScore};
+
+    #[test]
+    fn z_score() {
+        let mut detector = ZScore::new(3);
+        for v in [2.0, 4.0, 4.0] {
+            assert_eq!(detector.score(v), None);
+        }
+        // mean 10/3, sample standard deviation sqrt(4/3)
+        let score = detector.score(10.0).unwrap();
+        assert!((score - (20.0 / 3.0) / (4.0f64 / 3.0).sqrt()).abs() < 1e-9);
+        assert!((detector.mean() - 5.0).abs() < 1e-9);
+        assert!((detector.std_dev() - 12f64.sqrt()).abs() < 1e-9);
+
+        let mut constant = ZScore::new(2);
+        constant.score(1.0);
+        constant.score(1.0);
+        assert_eq!(constant.score(1.0), Some(0.0));
+        assert_eq!(constant.score(2.0), Some(f64::INFINITY));
+    }
+
+    #[test]
+    fn seasonal_z_score() {
+        let mut detector = SeasonalZScore::new(2, 2);
+        // a day-night pattern: the high values are normal in their slot
+        for v in [100.0, 1.0, 101.0, 2.0, 99.0, 1.0] {
+            let score = detector.score(v);
+            assert!(score.is_none_or(|s| s < 3.0));
+        }
+        assert!(detector.score(1.0).unwrap() > 3.0);
+        assert!(detector.score(1.5).unwrap() < 3.0);
+    }
+}
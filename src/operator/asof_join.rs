@@ -0,0 +1,225 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+
+use crate::block::{BlockStructure, GroupHasherBuilder, OperatorStructure};
+use crate::operator::merge::MergeElement;
+use crate::operator::reorder::Reorder;
+use crate::operator::{ExchangeData, ExchangeDataKey, Operator, StreamElement, Timestamp};
+use crate::scheduler::ExecutionMetadata;
+use crate::KeyedStream;
+
+/// Operator that performs an as-of join.
+///
+/// Each element of the left side is matched with the last element of the right side with the
+/// same key and a timestamp lower or equal to its own.
+///
+/// This operator assumes elements are received in increasing order of timestamp. An element of
+/// the left side is joined only when an element with a greater timestamp, or a watermark not
+/// lower than its timestamp, guarantees that all the elements of the right side that could
+/// match it have been received.
+#[derive(Clone, Debug)]
+pub(crate) struct AsofJoin<Key, Out, Out2, OperatorChain>
+where
+    Key: ExchangeDataKey,
+    Out: ExchangeData,
+    Out2: ExchangeData,
+    OperatorChain: Operator<Out = (Key, MergeElement<Out, Out2>)>,
+{
+    prev: OperatorChain,
+    /// Elements of the left side waiting for the elements of the right side with the same
+    /// timestamp.
+    left: VecDeque<(Timestamp, (Key, Out))>,
+    /// Last element of the right side of each key.
+    right: HashMap<Key, Out2, GroupHasherBuilder>,
+    /// Elements ready to be sent downstream.
+    buffer: VecDeque<StreamElement<(Key, (Out, Out2))>>,
+}
+
+impl<Key, Out, Out2, OperatorChain> Display for AsofJoin<Key, Out, Out2, OperatorChain>
+where
+    Key: ExchangeDataKey,
+    Out: ExchangeData,
+    Out2: ExchangeData,
+    OperatorChain: Operator<Out = (Key, MergeElement<Out, Out2>)>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> AsofJoin<{}>",
+            self.prev,
+            std::any::type_name::<(Key, (Out, Out2))>(),
+        )
+    }
+}
+
+impl<Key, Out, Out2, OperatorChain> AsofJoin<Key, Out, Out2, OperatorChain>
+where
+    Key: ExchangeDataKey,
+    Out: ExchangeData,
+    Out2: ExchangeData,
+    OperatorChain: Operator<Out = (Key, MergeElement<Out, Out2>)>,
+{
+    pub(super) fn new(prev: OperatorChain) -> Self {
+        Self {
+            prev,
+            left: Default::default(),
+            right: Default::default(),
+            buffer: Default::default(),
+        }
+    }
+
+    /// Join the elements of the left side with a timestamp up to `ts` (excluded, if not
+    /// `inclusive`), discarding the ones without a match.
+    fn advance(&mut self, ts: Timestamp, inclusive: bool) {
+        while let Some((left_ts, _)) = self.left.front() {
+            if *left_ts > ts || (*left_ts == ts && !inclusive) {
+                break;
+            }
+            let (left_ts, (key, item)) = self.left.pop_front().unwrap();
+            if let Some(right) = self.right.get(&key) {
+                self.buffer.push_back(StreamElement::Timestamped(
+                    (key, (item, right.clone())),
+                    left_ts,
+                ));
+            }
+        }
+    }
+}
+
+impl<Key, Out, Out2, OperatorChain> Operator for AsofJoin<Key, Out, Out2, OperatorChain>
+where
+    Key: ExchangeDataKey,
+    Out: ExchangeData,
+    Out2: ExchangeData,
+    OperatorChain: Operator<Out = (Key, MergeElement<Out, Out2>)>,
+{
+    type Out = (Key, (Out, Out2));
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+    }
+
+    fn next(&mut self) -> StreamElement<(Key, (Out, Out2))> {
+        loop {
+            if let Some(el) = self.buffer.pop_front() {
+                return el;
+            }
+            match self.prev.next() {
+                StreamElement::Timestamped((key, item), ts) => {
+                    // all the elements with a lower timestamp have been received
+                    self.advance(ts, false);
+                    match item {
+                        MergeElement::Left(item) => self.left.push_back((ts, (key, item))),
+                        MergeElement::Right(item) => {
+                            self.right.insert(key, item);
+                        }
+                    }
+                }
+                StreamElement::Watermark(ts) => {
+                    self.advance(ts, true);
+                    self.buffer.push_back(StreamElement::Watermark(ts));
+                }
+                StreamElement::FlushAndRestart => {
+                    self.advance(Timestamp::MAX, true);
+                    self.right.clear();
+                    self.buffer.push_back(StreamElement::FlushAndRestart);
+                }
+                StreamElement::Item(_) => panic!("As-of Join only supports timestamped streams"),
+                StreamElement::FlushBatch => return StreamElement::FlushBatch,
+                StreamElement::Terminate => return StreamElement::Terminate,
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<(Key, (Out, Out2)), _>("AsofJoin");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+impl<K, I, Op> KeyedStream<Op>
+where
+    Op: Operator<Out = (K, I)> + 'static,
+    K: ExchangeDataKey,
+    I: ExchangeData,
+{
+    /// Given two streams **with timestamps** join each element of the left side with the last
+    /// element of the right side with the same key and a timestamp lower or equal to its own.
+    ///
+    /// This is the usual way of enriching a stream of events with the latest value of a slowly
+    /// changing stream, like joining the trades with the last quote of the same instrument. The
+    /// elements of the left side without a previous element of the right side are discarded.
+    ///
+    /// Only the last element of the right side of each key is kept in memory. The elements of
+    /// the left side are held until the watermark reaches their timestamp.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # let mut env = StreamContext::new_local();
+    /// let trades = env
+    ///     .stream_iter(vec![('A', 1), ('A', 5), ('I', 6), ('A', 9)].into_iter())
+    ///     .add_timestamps(|&(_, ts)| ts, |_, &ts| Some(ts))
+    ///     .group_by(|&(symbol, _)| symbol);
+    /// let quotes = env
+    ///     .stream_iter(vec![('A', 2, 10.0), ('I', 3, 4.0), ('A', 5, 11.0)].into_iter())
+    ///     .add_timestamps(|&(_, ts, _)| ts, |_, &ts| Some(ts))
+    ///     .group_by(|&(symbol, _, _)| symbol);
+    /// let res = trades
+    ///     .asof_join(quotes)
+    ///     .map(|(_, ((_, trade), (_, _, price)))| (trade, price))
+    ///     .collect_vec();
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_by_key(|(_, (trade, _))| *trade);
+    /// assert_eq!(res, vec![('A', (5, 11.0)), ('I', (6, 4.0)), ('A', (9, 11.0))]);
+    /// ```
+    pub fn asof_join<I2, Op2>(
+        self,
+        right: KeyedStream<Op2>,
+    ) -> KeyedStream<impl Operator<Out = (K, (I, I2))>>
+    where
+        I2: ExchangeData,
+        Op2: Operator<Out = (K, I2)> + 'static,
+    {
+        self.merge_distinct(right)
+            .add_operator(Reorder::new)
+            .add_operator(AsofJoin::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::operator::asof_join::AsofJoin;
+    use crate::operator::merge::MergeElement;
+    use crate::operator::{Operator, StreamElement};
+    use crate::test::FakeOperator;
+
+    #[test]
+    fn asof_join_same_timestamp() {
+        let mut fake = FakeOperator::empty();
+        fake.push(StreamElement::Timestamped(
+            (0u8, MergeElement::Right('a')),
+            1,
+        ));
+        // the right element with the same timestamp comes after the left one
+        fake.push(StreamElement::Timestamped((0, MergeElement::Left(10)), 5));
+        fake.push(StreamElement::Timestamped((0, MergeElement::Right('b')), 5));
+        fake.push(StreamElement::Timestamped((1, MergeElement::Left(11)), 6));
+        fake.push(StreamElement::Timestamped((0, MergeElement::Left(12)), 7));
+        fake.push(StreamElement::Watermark(6));
+        fake.push(StreamElement::Timestamped((0, MergeElement::Right('c')), 8));
+
+        let mut join = AsofJoin::<u8, i32, char, _>::new(fake);
+        assert_eq!(join.next(), StreamElement::Timestamped((0, (10, 'b')), 5));
+        // the left element with key 1 has no match
+        assert_eq!(join.next(), StreamElement::Watermark(6));
+        assert_eq!(join.next(), StreamElement::Timestamped((0, (12, 'b')), 7));
+        assert_eq!(join.next(), StreamElement::Terminate);
+    }
+}
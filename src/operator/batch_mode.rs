@@ -25,6 +25,15 @@ mod tests {
         assert_eq!(stream.block.batch_mode, batch_mode);
     }
 
+    #[test]
+    fn batch_mode_controlled() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let source = FakeOperator::<u8>::empty();
+        let batch_mode = BatchMode::controlled(42, Duration::from_millis(42));
+        let stream = env.stream(source).batch_mode(batch_mode);
+        assert_eq!(stream.block.batch_mode, batch_mode);
+    }
+
     #[test]
     fn batch_inherit_from_previous() {
         let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
@@ -96,6 +96,6 @@ where
     ///
     /// Use only when strictly necessary as it is decrimental for performance.
     pub fn into_boxed(self) -> Stream<BoxedOperator<Op::Out>> {
-        self.add_operator(|prev| BoxedOperator::new(prev))
+        self.add_uncounted_operator(|prev| BoxedOperator::new(prev))
     }
 }
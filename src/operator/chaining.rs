@@ -0,0 +1,91 @@
+use std::fmt::Display;
+
+use crate::block::BlockStructure;
+use crate::operator::{Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+
+/// The operator chain after a [`Stream::start_new_block`](crate::Stream::start_new_block): either
+/// the chain of the current block, or the start of a new one.
+#[derive(Clone, Debug)]
+pub enum Chain<Fused, Split>
+where
+    Fused: Operator,
+    Split: Operator<Out = Fused::Out>,
+{
+    Fused(Fused),
+    Split(Split),
+}
+
+impl<Fused, Split> Display for Chain<Fused, Split>
+where
+    Fused: Operator,
+    Split: Operator<Out = Fused::Out>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Chain::Fused(prev) => write!(f, "{prev}"),
+            Chain::Split(prev) => write!(f, "{prev}"),
+        }
+    }
+}
+
+impl<Fused, Split> Operator for Chain<Fused, Split>
+where
+    Fused: Operator,
+    Split: Operator<Out = Fused::Out>,
+{
+    type Out = Fused::Out;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        match self {
+            Chain::Fused(prev) => prev.setup(metadata),
+            Chain::Split(prev) => prev.setup(metadata),
+        }
+    }
+
+    #[inline]
+    fn next(&mut self) -> StreamElement<Self::Out> {
+        match self {
+            Chain::Fused(prev) => prev.next(),
+            Chain::Split(prev) => prev.next(),
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        match self {
+            Chain::Fused(prev) => prev.structure(),
+            Chain::Split(prev) => prev.structure(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{ChainingStrategy, RuntimeConfig};
+    use crate::environment::StreamContext;
+    use crate::test::FakeOperator;
+
+    #[test]
+    fn test_start_new_block() {
+        let block_ids = |strategy| {
+            let config = RuntimeConfig::local(4).unwrap().with_chaining(strategy);
+            let env = StreamContext::new(config);
+            let stream = env.stream(FakeOperator::<u8>::empty());
+            let first = stream.block.id;
+            let hinted = stream.start_new_block();
+            let second = hinted.block.id;
+            let chained = hinted.chained().start_new_block();
+            (first, second, chained.block.id)
+        };
+
+        let (first, second, third) = block_ids(ChainingStrategy::Hints);
+        assert_ne!(first, second);
+        assert_eq!(second, third);
+        let (first, second, third) = block_ids(ChainingStrategy::Always);
+        assert_eq!(first, second);
+        assert_eq!(second, third);
+        let (first, second, third) = block_ids(ChainingStrategy::Never);
+        assert_ne!(first, second);
+        assert_ne!(second, third);
+    }
+}
@@ -0,0 +1,104 @@
+/// Define a struct to be used as the key of a stream grouped by multiple fields.
+///
+/// The struct derives all the traits needed by a key (`Clone`, `Debug`, `PartialEq`, `Eq`,
+/// `Hash`, `PartialOrd`, `Ord`, `Serialize` and `Deserialize`, so the crate using the macro must
+/// depend on `serde`), together with the attributes given to it. Compared to a tuple, the fields
+/// of the key have a name, and the key is printed with it. The hash of the key depends only on
+/// the values of its fields, so the partitioning is the same in every run and on every host.
+///
+/// A struct with named fields also gets a `new` constructor taking the fields in order, and a
+/// conversion from the tuple of the fields. Tuple structs are also supported, and their fields
+/// are accessed by position like the ones of a tuple: the SQL `GROUP BY` on multiple columns
+/// generates its keys in this form.
+///
+/// The fields must implement `Eq` and `Hash`: use [`ordered_float::OrderedFloat`] for floats.
+///
+/// ## Example
+///
+/// ```
+/// # use renoir::{StreamContext, RuntimeConfig};
+/// # let mut env = StreamContext::new_local();
+/// use renoir::composite_key;
+///
+/// composite_key! {
+///     struct CityYear {
+///         city: String,
+///         year: u16,
+///     }
+/// }
+///
+/// let sales = vec![("Rome", 2023, 10), ("Milan", 2023, 5), ("Rome", 2023, 7), ("Rome", 2024, 1)];
+/// let res = env
+///     .stream_iter(sales.into_iter().map(|(city, year, amount)| (city.to_string(), year, amount)))
+///     .group_by(|(city, year, _)| CityYear::new(city.clone(), *year))
+///     .fold(0, |total, (_, _, amount)| *total += amount)
+///     .collect_vec();
+/// env.execute_blocking();
+///
+/// let mut res = res.get().unwrap();
+/// res.sort_unstable();
+/// assert_eq!(
+///     res,
+///     vec![
+///         (CityYear::new("Milan".into(), 2023), 5),
+///         (CityYear::new("Rome".into(), 2023), 17),
+///         (CityYear::new("Rome".into(), 2024), 1),
+///     ]
+/// );
+/// ```
+#[macro_export]
+macro_rules! composite_key {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident: $ty:ty),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(
+            Clone,
+            Debug,
+            PartialEq,
+            Eq,
+            Hash,
+            PartialOrd,
+            Ord,
+            ::serde::Serialize,
+            ::serde::Deserialize,
+        )]
+        $vis struct $name {
+            $($field_vis $field: $ty),+
+        }
+
+        impl $name {
+            #[allow(clippy::too_many_arguments)]
+            pub fn new($($field: $ty),+) -> Self {
+                Self { $($field),+ }
+            }
+        }
+
+        impl From<($($ty,)+)> for $name {
+            fn from(($($field,)+): ($($ty,)+)) -> Self {
+                Self { $($field),+ }
+            }
+        }
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident($($field_vis:vis $ty:ty),+ $(,)?);
+    ) => {
+        $(#[$meta])*
+        #[derive(
+            Clone,
+            Debug,
+            PartialEq,
+            Eq,
+            Hash,
+            PartialOrd,
+            Ord,
+            ::serde::Serialize,
+            ::serde::Deserialize,
+        )]
+        $vis struct $name($($field_vis $ty),+);
+    };
+}
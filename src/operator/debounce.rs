@@ -0,0 +1,221 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use crate::block::{BlockStructure, GroupHasherBuilder, OperatorStructure};
+use crate::operator::{DataKey, Operator, StreamElement, Timestamp};
+use crate::scheduler::{ExecutionMetadata, WakeUp};
+
+/// Emit only the latest element of each key after a period without updates of that key.
+#[derive(Clone)]
+pub(crate) struct Debounce<K, I, Op>
+where
+    Op: Operator<Out = (K, I)>,
+{
+    prev: Op,
+    quiet: Duration,
+    /// The latest element of each key, with the time it arrived.
+    latest: HashMap<K, (I, Option<Timestamp>, Instant), GroupHasherBuilder>,
+    /// The arrival time of the updates, from the oldest. An update is stale if a newer element
+    /// of the same key has arrived.
+    updates: VecDeque<(K, Instant)>,
+    /// Wakes up the replica when the oldest update becomes quiet.
+    wake_up: WakeUp,
+    /// The deadline of the last wake up requested.
+    requested: Option<Instant>,
+    buffer: VecDeque<StreamElement<(K, I)>>,
+}
+
+impl<K, I, Op> Debounce<K, I, Op>
+where
+    K: DataKey,
+    I: Send,
+    Op: Operator<Out = (K, I)>,
+{
+    pub(super) fn new(prev: Op, quiet: Duration) -> Self {
+        Self {
+            prev,
+            quiet,
+            latest: Default::default(),
+            updates: Default::default(),
+            wake_up: Default::default(),
+            requested: None,
+            buffer: Default::default(),
+        }
+    }
+
+    fn emit(&mut self, key: K) {
+        if let Some((item, ts, _)) = self.latest.remove(&key) {
+            self.buffer.push_back(match ts {
+                Some(ts) => StreamElement::Timestamped((key, item), ts),
+                None => StreamElement::Item((key, item)),
+            });
+        }
+    }
+
+    /// Emit the elements of the keys that have not been updated in the last `quiet` period.
+    fn emit_quiet(&mut self, now: Instant) {
+        while let Some((_, time)) = self.updates.front() {
+            if now.duration_since(*time) < self.quiet {
+                break;
+            }
+            let (key, time) = self.updates.pop_front().unwrap();
+            if matches!(self.latest.get(&key), Some((_, _, t)) if *t == time) {
+                self.emit(key);
+            }
+        }
+    }
+
+    /// Ask to be woken up when the oldest update becomes quiet, if it changed.
+    fn request_wake_up(&mut self) {
+        let first = self.updates.front().map(|(_, time)| *time + self.quiet);
+        if first != self.requested {
+            if let Some(at) = first {
+                self.wake_up.request(at);
+            }
+            self.requested = first;
+        }
+    }
+
+    /// Emit the latest element of every key.
+    fn emit_all(&mut self) {
+        while let Some((key, _)) = self.updates.pop_front() {
+            self.emit(key);
+        }
+    }
+}
+
+impl<K, I, Op> Display for Debounce<K, I, Op>
+where
+    Op: Operator<Out = (K, I)>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> Debounce<{:?}>", self.prev, self.quiet)
+    }
+}
+
+impl<K, I, Op> Operator for Debounce<K, I, Op>
+where
+    K: DataKey,
+    I: Clone + Send,
+    Op: Operator<Out = (K, I)>,
+{
+    type Out = (K, I);
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        self.wake_up = metadata.wake_up.clone();
+    }
+
+    fn next(&mut self) -> StreamElement<(K, I)> {
+        loop {
+            if let Some(el) = self.buffer.pop_front() {
+                return el;
+            }
+            let el = self.prev.next();
+            let now = Instant::now();
+            match el {
+                StreamElement::Item((key, item)) => {
+                    self.latest.insert(key.clone(), (item, None, now));
+                    self.updates.push_back((key, now));
+                    self.emit_quiet(now);
+                }
+                StreamElement::Timestamped((key, item), ts) => {
+                    self.latest.insert(key.clone(), (item, Some(ts), now));
+                    self.updates.push_back((key, now));
+                    self.emit_quiet(now);
+                }
+                StreamElement::Watermark(_) => {
+                    self.emit_quiet(now);
+                    self.buffer.push_back(el);
+                }
+                StreamElement::FlushBatch => {
+                    // the wake up may have cleared the deadline, request it again
+                    self.requested = None;
+                    self.emit_quiet(now);
+                    self.buffer.push_back(el);
+                }
+                StreamElement::FlushAndRestart | StreamElement::Terminate => {
+                    self.emit_all();
+                    self.buffer.push_back(el);
+                }
+            }
+            self.request_wake_up();
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<(K, I), _>("Debounce");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use crate::block::BatchMode;
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    #[test]
+    fn debounce() {
+        let env = StreamContext::new(RuntimeConfig::local(1).unwrap());
+        let res = env
+            .stream_iter([(0, 1), (0, 2), (0, 3), (1, 10), (0, 4), (1, 11)].into_iter())
+            .group_by(|&(key, _)| key)
+            .map(|(_, (_, value))| value)
+            .inspect(|&(key, value)| {
+                // a quiet period before the first element of key 1
+                if key == 1 && value == 10 {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+            })
+            .debounce(Duration::from_millis(20))
+            .collect_vec();
+        env.execute_blocking();
+
+        let mut res = res.get().unwrap();
+        res.sort_unstable();
+        assert_eq!(res, vec![(0, 3), (0, 4), (1, 11)]);
+    }
+
+    #[test]
+    fn debounce_while_idle() {
+        let env = StreamContext::new(RuntimeConfig::local(1).unwrap());
+        let emitted = Arc::new(AtomicBool::new(false));
+        let emitted2 = emitted.clone();
+        let items = [Some((0, 1)), Some((0, 2)), None];
+        let start = Instant::now();
+        let res = env
+            .stream_iter(items.into_iter().filter_map(move |item| {
+                // wait for the element to be emitted before ending the stream
+                let waiting = Instant::now();
+                while item.is_none()
+                    && !emitted2.load(Ordering::Acquire)
+                    && waiting.elapsed() < Duration::from_secs(5)
+                {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                item
+            }))
+            .batch_mode(BatchMode::single())
+            .group_by(|&(key, _)| key)
+            .map(|(_, (_, value))| value)
+            .debounce(Duration::from_millis(20))
+            .inspect(move |_| emitted.store(true, Ordering::Release))
+            .map(move |(_, value)| (value, start.elapsed()))
+            .collect_vec();
+        env.execute_blocking();
+
+        let res = res.get().unwrap();
+        let [(0, (2, elapsed))] = res[..] else {
+            panic!("unexpected result {res:?}");
+        };
+        // the element is emitted while the source waits, not when the stream ends
+        assert!(elapsed < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,218 @@
+use std::fmt::Display;
+use std::hash::{BuildHasher, Hash};
+
+use crate::block::{BlockStructure, GroupHasherBuilder, OperatorStructure};
+use crate::operator::{Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+
+/// Mixed into the hash of the elements, so that the bits of the filter are independent from the
+/// hash used for partitioning the stream among the replicas.
+const BLOOM_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// A partitioned Bloom filter: each of the `k` hash functions sets a bit in its own slice of the
+/// bit array, so that the false positive rate is the same for every element.
+#[derive(Clone, Debug)]
+pub(crate) struct BloomFilter {
+    slices: usize,
+    slice_bits: usize,
+    bits: Vec<u64>,
+    /// Number of elements inserted.
+    len: usize,
+}
+
+impl BloomFilter {
+    /// A filter for `expected_items` elements with a false positive rate of `fp_rate`.
+    pub(crate) fn new(expected_items: usize, fp_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let slices = (1.0 / fp_rate).log2().ceil().max(1.0) as usize;
+        let total_bits = (expected_items.max(1) as f64 * -fp_rate.ln() / (ln2 * ln2)).ceil();
+        let slice_bits = ((total_bits as usize).div_ceil(slices)).max(64);
+        Self {
+            slices,
+            slice_bits,
+            bits: vec![0; (slices * slice_bits).div_ceil(64)],
+            len: 0,
+        }
+    }
+
+    /// The position of the bit of the element with hash `hash` in the slice `i`, computed with
+    /// double hashing.
+    #[inline]
+    fn position(&self, hash: u64, i: usize) -> usize {
+        let h1 = hash & 0xffff_ffff;
+        let h2 = (hash >> 32) | 1;
+        let h = h1.wrapping_add((i as u64).wrapping_mul(h2));
+        i * self.slice_bits + (h % self.slice_bits as u64) as usize
+    }
+
+    pub(crate) fn contains(&self, hash: u64) -> bool {
+        (0..self.slices).all(|i| {
+            let p = self.position(hash, i);
+            self.bits[p / 64] & (1 << (p % 64)) != 0
+        })
+    }
+
+    /// Insert the element with hash `hash`, returning whether it was not already present.
+    pub(crate) fn insert(&mut self, hash: u64) -> bool {
+        let mut new = false;
+        for i in 0..self.slices {
+            let p = self.position(hash, i);
+            let bit = 1 << (p % 64);
+            new |= self.bits[p / 64] & bit == 0;
+            self.bits[p / 64] |= bit;
+        }
+        if new {
+            self.len += 1;
+        }
+        new
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// Forward only the elements that have not been seen before, remembering them in Bloom filters.
+///
+/// When the filter is full, with `expected_items` elements, a new one is started and the full
+/// one is kept for checking until the new one is full too, so that the memory is bounded and the
+/// false positive rate does not grow with the length of the stream.
+#[derive(Clone)]
+pub(crate) struct DedupApprox<Op>
+where
+    Op: Operator,
+{
+    prev: Op,
+    expected_items: usize,
+    fp_rate: f64,
+    current: Option<BloomFilter>,
+    previous: Option<BloomFilter>,
+}
+
+impl<Op> DedupApprox<Op>
+where
+    Op: Operator,
+    Op::Out: Hash,
+{
+    pub(super) fn new(prev: Op, expected_items: usize, fp_rate: f64) -> Self {
+        assert!(expected_items > 0, "dedup_approx expects at least one item");
+        assert!(
+            fp_rate > 0.0 && fp_rate < 1.0,
+            "the false positive rate must be between 0 and 1"
+        );
+        Self {
+            prev,
+            expected_items,
+            fp_rate,
+            current: None,
+            previous: None,
+        }
+    }
+
+    /// Whether the element is seen for the first time (up to false positives), remembering it.
+    fn first_occurrence(&mut self, item: &Op::Out) -> bool {
+        let hash = GroupHasherBuilder::default().hash_one((BLOOM_SEED, item));
+        if self.previous.as_ref().is_some_and(|f| f.contains(hash)) {
+            return false;
+        }
+        let current = self.current.as_mut().expect("DedupApprox was not set up");
+        if !current.insert(hash) {
+            return false;
+        }
+        if current.len() >= self.expected_items {
+            let full =
+                std::mem::replace(current, BloomFilter::new(self.expected_items, self.fp_rate));
+            self.previous = Some(full);
+        }
+        true
+    }
+}
+
+impl<Op> Display for DedupApprox<Op>
+where
+    Op: Operator,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> DedupApprox<{}>",
+            self.prev,
+            std::any::type_name::<Op::Out>()
+        )
+    }
+}
+
+impl<Op> Operator for DedupApprox<Op>
+where
+    Op: Operator,
+    Op::Out: Hash,
+{
+    type Out = Op::Out;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        // the elements are partitioned among the replicas, so each one sees a share of them
+        self.expected_items = self.expected_items.div_ceil(metadata.replicas.len());
+        self.current = Some(BloomFilter::new(self.expected_items, self.fp_rate));
+    }
+
+    #[inline]
+    fn next(&mut self) -> StreamElement<Op::Out> {
+        loop {
+            match self.prev.next() {
+                StreamElement::Item(item) | StreamElement::Timestamped(item, _)
+                    if !self.first_occurrence(&item) =>
+                {
+                    continue
+                }
+                el => return el,
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<Op::Out, _>("DedupApprox");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BloomFilter;
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    #[test]
+    fn bloom_filter() {
+        let mut filter = BloomFilter::new(1000, 0.01);
+        for i in 0..1000u64 {
+            let hash = i.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            filter.insert(hash);
+            assert!(filter.contains(hash));
+        }
+        let false_positives = (1000..11_000u64)
+            .filter(|i| filter.contains(i.wrapping_mul(0x9e37_79b9_7f4a_7c15)))
+            .count();
+        assert!(false_positives < 300, "{false_positives} false positives");
+    }
+
+    #[test]
+    fn dedup_approx() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_par_iter(0..10_000u64)
+            .map(|n| n % 1000)
+            .dedup_approx(1000, 0.001)
+            .collect_vec();
+        env.execute_blocking();
+
+        let mut res = res.get().unwrap();
+        res.sort_unstable();
+        let len = res.len();
+        res.dedup();
+        // no duplicates, and at most a few elements lost to false positives
+        assert_eq!(res.len(), len);
+        assert!(len > 990, "{len} distinct elements");
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use crate::block::{BlockStructure, GroupHasherBuilder, OperatorStructure};
+use crate::operator::{DataKey, Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+
+/// Options for [`Stream::distinct_by_with_options`](crate::Stream::distinct_by_with_options).
+///
+/// By default every key is remembered forever, so the memory used grows with the number of
+/// distinct keys. On unbounded streams set a TTL or a maximum size: a key that has been forgotten
+/// lets its next occurrence through again.
+#[derive(Clone, Debug, Default)]
+pub struct DistinctOptions {
+    ttl: Option<Duration>,
+    max_keys: Option<usize>,
+}
+
+impl DistinctOptions {
+    /// Forget each key after this time from its first occurrence, measured in processing time.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Maximum number of keys remembered by each replica. When the limit is reached the oldest
+    /// key is forgotten.
+    pub fn max_keys(mut self, max_keys: usize) -> Self {
+        assert!(max_keys > 0, "distinct must remember at least one key");
+        self.max_keys = Some(max_keys);
+        self
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Distinct<K, Fk, Op>
+where
+    Op: Operator,
+    Fk: Fn(&Op::Out) -> K + Clone + Send,
+{
+    prev: Op,
+    key_fn: Fk,
+    options: DistinctOptions,
+    /// The keys seen, with the time of their first occurrence.
+    seen: HashMap<K, Instant, GroupHasherBuilder>,
+    /// The keys seen, from the oldest.
+    order: VecDeque<(K, Instant)>,
+}
+
+impl<K, Fk, Op> Distinct<K, Fk, Op>
+where
+    K: DataKey,
+    Op: Operator,
+    Fk: Fn(&Op::Out) -> K + Clone + Send,
+{
+    pub(super) fn new(prev: Op, key_fn: Fk, options: DistinctOptions) -> Self {
+        Self {
+            prev,
+            key_fn,
+            options,
+            seen: Default::default(),
+            order: Default::default(),
+        }
+    }
+
+    /// Forget the keys that are expired or exceed the maximum size.
+    fn evict(&mut self, now: Instant) {
+        while let Some((key, time)) = self.order.front() {
+            let expired = matches!(self.options.ttl, Some(ttl) if now.duration_since(*time) > ttl);
+            let full = matches!(self.options.max_keys, Some(max) if self.order.len() > max);
+            if !expired && !full {
+                break;
+            }
+            // the key may have been seen again after being forgotten
+            if self.seen.get(key) == Some(time) {
+                self.seen.remove(key);
+            }
+            self.order.pop_front();
+        }
+    }
+
+    /// Whether the key is seen for the first time, remembering it.
+    fn first_occurrence(&mut self, key: K) -> bool {
+        if self.options.ttl.is_none() && self.options.max_keys.is_none() {
+            if self.seen.contains_key(&key) {
+                return false;
+            }
+            // without bounds the keys are never evicted, so their order is not needed
+            self.seen.insert(key, Instant::now());
+            return true;
+        }
+
+        let now = Instant::now();
+        self.evict(now);
+        if self.seen.contains_key(&key) {
+            return false;
+        }
+        self.seen.insert(key.clone(), now);
+        self.order.push_back((key, now));
+        self.evict(now);
+        true
+    }
+}
+
+impl<K, Fk, Op> Display for Distinct<K, Fk, Op>
+where
+    Op: Operator,
+    Fk: Fn(&Op::Out) -> K + Clone + Send,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> Distinct<{}>",
+            self.prev,
+            std::any::type_name::<K>()
+        )
+    }
+}
+
+impl<K, Fk, Op> Operator for Distinct<K, Fk, Op>
+where
+    K: DataKey,
+    Op: Operator,
+    Fk: Fn(&Op::Out) -> K + Clone + Send,
+{
+    type Out = Op::Out;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+    }
+
+    #[inline]
+    fn next(&mut self) -> StreamElement<Op::Out> {
+        loop {
+            match self.prev.next() {
+                StreamElement::Item(item) | StreamElement::Timestamped(item, _)
+                    if !self.first_occurrence((self.key_fn)(&item)) =>
+                {
+                    continue
+                }
+                StreamElement::FlushAndRestart => {
+                    self.seen.clear();
+                    self.order.clear();
+                    return StreamElement::FlushAndRestart;
+                }
+                el => return el,
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<Op::Out, _>("Distinct");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+    use crate::operator::DistinctOptions;
+    use crate::BatchMode;
+
+    #[test]
+    fn distinct() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_par_iter(0..1000u32)
+            .map(|n| n % 37)
+            .distinct()
+            .collect_vec();
+        env.execute_blocking();
+
+        let mut res = res.get().unwrap();
+        res.sort_unstable();
+        assert_eq!(res, (0..37).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn distinct_max_keys() {
+        let env = StreamContext::new(RuntimeConfig::local(1).unwrap());
+        let res = env
+            .stream_iter([1, 2, 1, 3, 1, 2].into_iter())
+            .distinct_by_with_options(|n| *n, DistinctOptions::default().max_keys(2))
+            .collect_vec();
+        env.execute_blocking();
+
+        // 1 is forgotten when 3 arrives
+        assert_eq!(res.get().unwrap(), vec![1, 2, 3, 1, 2]);
+    }
+
+    #[test]
+    fn distinct_ttl() {
+        let env = StreamContext::new(RuntimeConfig::local(1).unwrap());
+        let res = env
+            .stream_iter([1, 1, 2, 1, 2].into_iter())
+            // send each item as soon as it is produced, so the sleeps are seen by distinct
+            .batch_mode(BatchMode::Single)
+            .inspect(|n| {
+                if *n == 2 {
+                    std::thread::sleep(Duration::from_millis(30));
+                }
+            })
+            .distinct_by_with_options(
+                |n| *n,
+                DistinctOptions::default().ttl(Duration::from_millis(10)),
+            )
+            .collect_vec();
+        env.execute_blocking();
+
+        assert_eq!(res.get().unwrap(), vec![1, 2, 1, 2]);
+    }
+}
@@ -1,9 +1,11 @@
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::sync::Arc;
 
 use crate::block::{
     BatchMode, Batcher, BlockStructure, Connection, NextStrategy, OperatorStructure,
 };
+use crate::checkpoint::ReplicaCheckpoint;
 use crate::network::{Coord, ReceiverEndpoint};
 use crate::operator::{ExchangeData, KeyerFn, Operator, StreamElement};
 use crate::scheduler::{BlockId, ExecutionMetadata};
@@ -35,6 +37,7 @@ where
     senders: Vec<(ReceiverEndpoint, Batcher<OperatorChain::Out>)>,
     feedback_id: Option<BlockId>,
     ignore_block_ids: Vec<BlockId>,
+    checkpoint: Option<Arc<ReplicaCheckpoint>>,
 }
 
 impl<OperatorChain: std::fmt::Debug, IndexFn: std::fmt::Debug> std::fmt::Debug
@@ -73,6 +76,7 @@ where
             senders: Default::default(),
             feedback_id: self.feedback_id,
             ignore_block_ids: self.ignore_block_ids.clone(),
+            checkpoint: None,
         }
     }
 }
@@ -86,6 +90,7 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self.next_strategy {
             NextStrategy::Random => write!(f, "{} -> Shuffle", self.prev),
+            NextStrategy::RoundRobin(_) => write!(f, "{} -> Rebalance", self.prev),
             NextStrategy::OnlyOne => write!(f, "{} -> OnlyOne", self.prev),
             _ => self.prev.fmt(f),
         }
@@ -112,6 +117,7 @@ where
             senders: Default::default(),
             feedback_id: None,
             ignore_block_ids: Default::default(),
+            checkpoint: None,
         }
     }
 
@@ -179,6 +185,12 @@ where
 
         self.setup_senders();
 
+        // start from a different destination in each replica to spread the first messages
+        if let NextStrategy::RoundRobin(next) = &self.next_strategy {
+            next.set(metadata.coord.replica_id as usize);
+        }
+
+        self.checkpoint = metadata.checkpoint.clone();
         self.coord = Some(metadata.coord);
     }
 
@@ -209,9 +221,19 @@ where
             // Direct messages
             StreamElement::Item(item) | StreamElement::Timestamped(item, _) => {
                 let index = self.next_strategy.index(item);
-                for block in self.block_senders.iter() {
-                    let index = index % block.indexes.len();
-                    let sender_idx = block.indexes[index];
+                let next_strategy = &self.next_strategy;
+                let mut destinations = self
+                    .block_senders
+                    .iter()
+                    .map(|block| block.indexes[next_strategy.replica(index, block.indexes.len())])
+                    .peekable();
+                while let Some(sender_idx) = destinations.next() {
+                    // the last destination takes the message: a local channel moves the whole
+                    // batch to the receiver, so the items are neither copied nor serialized
+                    if destinations.peek().is_none() {
+                        self.senders[sender_idx].1.enqueue(message);
+                        break;
+                    }
                     self.senders[sender_idx].1.enqueue(message.clone());
                 }
             }
@@ -220,11 +242,18 @@ where
 
         // Flushing messages
         match to_return {
-            StreamElement::FlushAndRestart | StreamElement::FlushBatch => {
+            StreamElement::FlushAndRestart => {
                 for (_, batcher) in self.senders.iter_mut() {
                     batcher.flush();
                 }
             }
+            StreamElement::FlushBatch => {
+                // forward the barrier of the checkpoint in progress after the flushed messages
+                match self.checkpoint.as_ref().and_then(|c| c.in_progress()) {
+                    Some(id) => self.senders.iter_mut().for_each(|(_, b)| b.barrier(id)),
+                    None => self.senders.iter_mut().for_each(|(_, b)| b.flush()),
+                }
+            }
             StreamElement::Terminate => {
                 log::debug!(
                     "{} received terminate, closing {} channels",
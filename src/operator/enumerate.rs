@@ -0,0 +1,138 @@
+use crate::block::Replication;
+use crate::operator::{ExchangeData, Operator, RichMapFunction};
+use crate::scheduler::ExecutionMetadata;
+use crate::stream::Stream;
+
+/// Assign to the items of a replica the indexes `offset`, `offset + step`, `offset + 2 * step`...
+struct IndexAssigner {
+    next: u64,
+    step: u64,
+}
+
+impl IndexAssigner {
+    fn new(metadata: &ExecutionMetadata) -> Self {
+        Self {
+            next: metadata.global_id,
+            step: metadata.replicas.len() as u64,
+        }
+    }
+}
+
+impl<I: Send> RichMapFunction<I> for IndexAssigner {
+    type Out = (u64, I);
+
+    fn map(&mut self, item: I) -> (u64, I) {
+        let index = self.next;
+        self.next += self.step;
+        (index, item)
+    }
+}
+
+impl<I, Op> Stream<Op>
+where
+    I: ExchangeData,
+    Op: Operator<Out = I> + 'static,
+{
+    /// Pair each element of the stream with its position, starting from 0.
+    ///
+    /// The indexes are consecutive and follow the order in which the elements reach the single
+    /// replica that assigns them, so with a parallel source they depend on the scheduling. When
+    /// the indexes only need to be unique, [`Stream::zip_with_index`] avoids moving all the
+    /// elements to a single replica.
+    ///
+    /// **Note**: this operator is not parallelized, all the elements are sent to a single node.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(vec!['a', 'b', 'c'].into_iter());
+    /// let res = s.enumerate().collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![(0, 'a'), (1, 'b'), (2, 'c')]);
+    /// ```
+    pub fn enumerate(self) -> Stream<impl Operator<Out = (u64, I)>> {
+        self.replication(Replication::One).rich_map({
+            let mut index = 0;
+            move |item| {
+                index += 1;
+                (index - 1, item)
+            }
+        })
+    }
+
+    /// Pair each element of the stream with an index unique across all the replicas.
+    ///
+    /// Like with [`Stream::enumerate`], the index is the first element of the pair.
+    ///
+    /// Each replica assigns the indexes independently, without moving the elements: the replica
+    /// `r` of `n` gives the indexes `r`, `r + n`, `r + 2n`... to its elements in the order they
+    /// arrive. The indexes are unique and deterministic for a given partitioning of the stream,
+    /// but they are not consecutive if the replicas receive a different number of elements. Use
+    /// [`Stream::enumerate`] for consecutive indexes.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(vec!['a', 'b', 'c'].into_iter());
+    /// let res = s.zip_with_index().collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![(0, 'a'), (1, 'b'), (2, 'c')]);
+    /// ```
+    pub fn zip_with_index(self) -> Stream<impl Operator<Out = (u64, I)>> {
+        self.rich_map_init(IndexAssigner::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    #[test]
+    fn enumerate_parallel() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env.stream_par_iter(0..1000u32).enumerate().collect_vec();
+        env.execute_blocking();
+
+        let mut res = res.get().unwrap();
+        res.sort_unstable();
+        assert_eq!(
+            res.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            (0..1000).collect::<Vec<_>>()
+        );
+        let mut items = res.into_iter().map(|(_, x)| x).collect::<Vec<_>>();
+        items.sort_unstable();
+        assert_eq!(items, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn zip_with_index_parallel() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_par_iter(0..1000u32)
+            .zip_with_index()
+            .collect_vec();
+        env.execute_blocking();
+
+        let res = res.get().unwrap();
+        assert_eq!(res.len(), 1000);
+        let indexes = res.iter().map(|(i, _)| *i).collect::<HashSet<_>>();
+        assert_eq!(indexes.len(), 1000);
+        // each replica receives 250 elements
+        assert!(indexes.iter().all(|&i| i < 1000));
+    }
+}
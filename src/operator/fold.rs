@@ -1,8 +1,14 @@
 use std::fmt::Display;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 use crate::block::{BlockStructure, OperatorStructure};
+use crate::checkpoint::{OperatorState, ReplicaCheckpoint};
 use crate::operator::{Operator, StreamElement, Timestamp};
 use crate::scheduler::ExecutionMetadata;
+use crate::spill::{encoded_len, MemoryTracker};
 
 #[derive(Clone, Derivative)]
 #[derivative(Debug)]
@@ -20,6 +26,22 @@ where
     max_watermark: Option<Timestamp>,
     received_end: bool,
     received_end_iter: bool,
+    /// Save the accumulator in the checkpoints, if the operator is checkpointed.
+    checkpoint: Option<OperatorState<Option<O>>>,
+    /// The checkpoints of the replica, to forward their barriers.
+    replica: Option<Arc<ReplicaCheckpoint>>,
+    /// Whether the accumulator is a partial result, sent before each barrier.
+    partial: bool,
+    /// The barrier to send after the partial result.
+    pending_barrier: bool,
+    /// Tracks the memory of the accumulator, if there is a memory budget.
+    budget: Option<MemoryTracker>,
+    /// Measures what the accumulator stores on the heap, if it's serializable.
+    #[derivative(Debug = "ignore")]
+    heap_size: Option<fn(&O) -> usize>,
+    /// The number of items folded into the accumulator: its memory is measured again every time
+    /// it doubles.
+    folded: u64,
 }
 
 impl<O: Send + Clone, F, Op> Display for Fold<O, F, Op>
@@ -52,6 +74,57 @@ where
             max_watermark: None,
             received_end: false,
             received_end_iter: false,
+            checkpoint: None,
+            replica: None,
+            partial: false,
+            pending_barrier: false,
+            budget: None,
+            heap_size: None,
+            folded: 0,
+        }
+    }
+
+    /// Save the accumulator in the checkpoints of the job.
+    pub(super) fn checkpointed(mut self) -> Self
+    where
+        O: Serialize + DeserializeOwned,
+    {
+        self.checkpoint = Some(OperatorState::new());
+        self.heap_size = Some(encoded_len::<O>);
+        self
+    }
+
+    /// The accumulator is a partial result, merged by a following fold: instead of saving it in
+    /// the checkpoints, it's sent before each barrier, so it can be merged by any replica when the
+    /// job is restored.
+    pub(super) fn partial(mut self) -> Self
+    where
+        O: Serialize,
+    {
+        self.partial = true;
+        self.heap_size = Some(encoded_len::<O>);
+        self
+    }
+
+    /// Take the accumulator, to send it downstream.
+    fn take(&mut self) -> Option<O> {
+        self.folded = 0;
+        if let Some(budget) = &mut self.budget {
+            budget.update(0);
+        }
+        self.accumulator.take()
+    }
+
+    /// Fold an item into the accumulator, reporting its memory to the budget.
+    fn fold_item(&mut self, item: Op::Out) {
+        let accumulator = self.accumulator.get_or_insert_with(|| self.init.clone());
+        (self.fold)(accumulator, item);
+        self.folded += 1;
+        if let Some(budget) = &mut self.budget {
+            if self.folded.is_power_of_two() {
+                let heap = self.heap_size.map_or(0, |heap_size| heap_size(accumulator));
+                budget.update((std::mem::size_of::<O>() + heap) as u64);
+            }
         }
     }
 }
@@ -65,10 +138,23 @@ where
 
     fn setup(&mut self, metadata: &mut ExecutionMetadata) {
         self.prev.setup(metadata);
+        self.replica = metadata.checkpoint.clone();
+        self.budget = metadata.state_backend.memory_tracker(false);
+        if let Some(checkpoint) = self.checkpoint.as_mut() {
+            checkpoint.setup(metadata);
+            if let Some(accumulator) = checkpoint.restore() {
+                self.accumulator = accumulator;
+            }
+        }
     }
 
     #[inline]
     fn next(&mut self) -> StreamElement<O> {
+        if self.pending_barrier {
+            self.pending_barrier = false;
+            return StreamElement::FlushBatch;
+        }
+
         while !self.received_end {
             match self.prev.next() {
                 StreamElement::Terminate => self.received_end = true,
@@ -79,26 +165,39 @@ where
                 StreamElement::Watermark(ts) => {
                     self.max_watermark = Some(self.max_watermark.unwrap_or(ts).max(ts))
                 }
-                StreamElement::Item(item) => {
-                    if self.accumulator.is_none() {
-                        self.accumulator = Some(self.init.clone());
-                    }
-                    (self.fold)(self.accumulator.as_mut().unwrap(), item);
-                }
+                StreamElement::Item(item) => self.fold_item(item),
                 StreamElement::Timestamped(item, ts) => {
                     self.timestamp = Some(self.timestamp.unwrap_or(ts).max(ts));
-                    if self.accumulator.is_none() {
-                        self.accumulator = Some(self.init.clone());
+                    self.fold_item(item);
+                }
+                // this block wont sent anything until the stream ends, but the barrier of a
+                // checkpoint has to go through
+                StreamElement::FlushBatch => {
+                    if self
+                        .replica
+                        .as_ref()
+                        .is_some_and(|c| c.in_progress().is_some())
+                    {
+                        if let Some(checkpoint) = self.checkpoint.as_ref() {
+                            checkpoint.snapshot(&self.accumulator);
+                        }
+                        if self.partial {
+                            if let Some(acc) = self.take() {
+                                self.pending_barrier = true;
+                                return match self.timestamp.take() {
+                                    Some(ts) => StreamElement::Timestamped(acc, ts),
+                                    None => StreamElement::Item(acc),
+                                };
+                            }
+                        }
+                        return StreamElement::FlushBatch;
                     }
-                    (self.fold)(self.accumulator.as_mut().unwrap(), item);
                 }
-                // this block wont sent anything until the stream ends
-                StreamElement::FlushBatch => {}
             }
         }
 
         // If there is an accumulated value, return it
-        if let Some(acc) = self.accumulator.take() {
+        if let Some(acc) = self.take() {
             if let Some(ts) = self.timestamp.take() {
                 return StreamElement::Timestamped(acc, ts);
             } else {
@@ -122,17 +221,20 @@ where
     }
 
     fn structure(&self) -> BlockStructure {
-        self.prev
-            .structure()
-            .add_operator(OperatorStructure::new::<O, _>("Fold"))
+        let mut operator = OperatorStructure::new::<O, _>("Fold");
+        operator.unsaved_state = self.checkpoint.is_none() && !self.partial;
+        self.prev.structure().add_operator(operator)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::checkpoint::CheckpointCoordinator;
+    use crate::config::CheckpointConfig;
+    use crate::network::Coord;
     use crate::operator::fold::Fold;
     use crate::operator::{Operator, StreamElement};
-    use crate::test::FakeOperator;
+    use crate::test::{FakeNetworkTopology, FakeOperator};
 
     #[test]
     fn test_fold_without_timestamps() {
@@ -181,4 +283,33 @@ mod tests {
         assert_eq!(fold.next(), StreamElement::FlushAndRestart);
         assert_eq!(fold.next(), StreamElement::Terminate);
     }
+
+    #[test]
+    fn test_fold_forwards_barrier() {
+        let mut fake_operator = FakeOperator::empty();
+        fake_operator.push(StreamElement::Item(1));
+        fake_operator.push(StreamElement::FlushBatch);
+        fake_operator.push(StreamElement::Item(2));
+        fake_operator.push(StreamElement::FlushBatch);
+        fake_operator.push(StreamElement::Item(3));
+
+        // the accumulator is not saved, but the barrier has to reach the next blocks
+        let mut fold = Fold::new(fake_operator, 0, |a, b| *a += b);
+        let dir = tempfile::tempdir().unwrap();
+        let mut coordinator =
+            CheckpointCoordinator::new(CheckpointConfig::new(dir.path()), 0, 1, None);
+        let coord = Coord::new(0, 0, 0);
+        let replica = coordinator.replica(coord, &[coord]);
+        let mut topology = FakeNetworkTopology::<u8>::new(0, 0);
+        let mut metadata = topology.metadata();
+        metadata.checkpoint = Some(replica.clone());
+        fold.setup(&mut metadata);
+
+        assert!(replica.begin(1));
+        assert_eq!(fold.next(), StreamElement::FlushBatch);
+        replica.complete();
+        // without a checkpoint in progress the flushes are not forwarded
+        assert_eq!(fold.next(), StreamElement::Item(1 + 2 + 3));
+        assert_eq!(fold.next(), StreamElement::Terminate);
+    }
 }
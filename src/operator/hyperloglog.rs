@@ -0,0 +1,135 @@
+use std::hash::{BuildHasher, Hash};
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::GroupHasherBuilder;
+
+/// Default precision of the sketches, with a standard error of about 1.6%.
+pub(crate) const DEFAULT_PRECISION: u8 = 12;
+
+/// A HyperLogLog sketch, estimating the number of distinct items inserted using a fixed amount of
+/// memory.
+///
+/// The sketch has `2^precision` registers of one byte each, and the standard error of the
+/// estimate is about `1.04 / sqrt(2^precision)`. The sketches are mergeable: merging the sketches
+/// of two parts of a stream gives the sketch of the whole stream, so each replica can keep its
+/// own sketch.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_PRECISION)
+    }
+}
+
+impl HyperLogLog {
+    /// Create an empty sketch with `2^precision` registers. `precision` must be between 4 and 18.
+    pub fn new(precision: u8) -> Self {
+        assert!(
+            (4..=18).contains(&precision),
+            "HyperLogLog precision must be between 4 and 18"
+        );
+        Self {
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    /// Add an item to the sketch.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        // the hasher is not randomized, so the same item has the same hash in every replica
+        let hash = GroupHasherBuilder::default().hash_one(item);
+        let index = (hash >> (64 - self.precision)) as usize;
+        // the bit set after the remaining bits bounds the number of leading zeros
+        let rest = (hash << self.precision) | (1 << (self.precision - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    /// Merge the sketch of another part of the stream, with the same precision.
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        assert_eq!(
+            self.precision, other.precision,
+            "cannot merge HyperLogLog sketches with different precision"
+        );
+        for (r, o) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *r = (*r).max(*o);
+        }
+    }
+
+    /// Estimate the number of distinct items inserted in the sketch.
+    pub fn estimate(&self) -> usize {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // small range correction: linear counting
+            (m * (m / zeros as f64).ln()).round() as usize
+        } else {
+            estimate.round() as usize
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HyperLogLog;
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    fn assert_close(estimate: usize, exact: usize) {
+        let error = (estimate as f64 - exact as f64).abs() / exact as f64;
+        assert!(error < 0.05, "estimate {estimate}, exact {exact}");
+    }
+
+    #[test]
+    fn hyperloglog_estimate() {
+        let mut hll = HyperLogLog::default();
+        assert_eq!(hll.estimate(), 0);
+        for i in 0..100_000u64 {
+            hll.insert(&(i % 20_000));
+        }
+        assert_close(hll.estimate(), 20_000);
+
+        let mut small = HyperLogLog::default();
+        (0..10u64).for_each(|i| small.insert(&i));
+        assert!((9..=11).contains(&small.estimate()));
+    }
+
+    #[test]
+    fn hyperloglog_merge() {
+        let mut a = HyperLogLog::default();
+        let mut b = HyperLogLog::default();
+        (0..30_000u64).for_each(|i| a.insert(&i));
+        (20_000..50_000u64).for_each(|i| b.insert(&i));
+        a.merge(&b);
+        assert_close(a.estimate(), 50_000);
+    }
+
+    #[test]
+    fn count_distinct_approx() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_par_iter(0..100_000u64)
+            .map(|n| n % 10_000)
+            .count_distinct_approx()
+            .collect_vec();
+        env.execute_blocking();
+
+        let res = res.get().unwrap();
+        assert_eq!(res.len(), 1);
+        assert_close(res[0], 10_000);
+    }
+}
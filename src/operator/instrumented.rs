@@ -0,0 +1,230 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tracing::Span;
+
+use crate::block::BlockStructure;
+use crate::network::Coord;
+use crate::operator::{Operator, StreamElement};
+use crate::profiler::metrics;
+use crate::scheduler::ExecutionMetadata;
+
+/// How many items are counted locally before adding them to the counter of the operator.
+const PUBLISH_EVERY: u64 = 1024;
+
+/// Count the items emitted by an operator for the metrics endpoint, see `profiler::metrics`, and
+/// trace the batches it produces, see `telemetry`.
+///
+/// It's added by the stream after each operator of the chain, and it does nothing unless the
+/// metrics are collected or the `debug` spans are enabled.
+#[derive(Clone, Debug)]
+pub struct Instrumented<Op>
+where
+    Op: Operator,
+{
+    prev: Op,
+    counter: Option<Arc<AtomicU64>>,
+    /// The items not yet added to the counter.
+    pending: u64,
+    trace: Option<BatchTrace>,
+}
+
+/// The span of the elements produced by an operator between two flushes of its batches.
+#[derive(Clone, Debug)]
+struct BatchTrace {
+    coord: Coord,
+    /// The position of the operator in the block.
+    index: usize,
+    name: String,
+    span: Option<Span>,
+    items: u64,
+}
+
+impl BatchTrace {
+    /// Ask the next element to `prev` inside the span of the batch, starting it if needed.
+    fn next<Op: Operator>(&mut self, prev: &mut Op) -> StreamElement<Op::Out> {
+        if self.span.is_none() {
+            self.span = Some(tracing::debug_span!(
+                "operator",
+                block_id = self.coord.block_id,
+                host_id = self.coord.host_id,
+                replica_id = self.coord.replica_id,
+                operator = self.index,
+                name = %self.name,
+                items = tracing::field::Empty,
+            ));
+        }
+        let el = self.span.as_ref().unwrap().in_scope(|| prev.next());
+        match el {
+            StreamElement::Item(_) | StreamElement::Timestamped(_, _) => self.items += 1,
+            StreamElement::FlushBatch
+            | StreamElement::FlushAndRestart
+            | StreamElement::Terminate => {
+                if let Some(span) = self.span.take() {
+                    span.record("items", self.items);
+                }
+                self.items = 0;
+            }
+            StreamElement::Watermark(_) => {}
+        }
+        el
+    }
+}
+
+impl<Op> Instrumented<Op>
+where
+    Op: Operator,
+{
+    pub(crate) fn new(prev: Op) -> Self {
+        Self {
+            prev,
+            counter: None,
+            pending: 0,
+            trace: None,
+        }
+    }
+}
+
+impl<Op> Display for Instrumented<Op>
+where
+    Op: Operator,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.prev.fmt(f)
+    }
+}
+
+impl<Op> Operator for Instrumented<Op>
+where
+    Op: Operator,
+{
+    type Out = Op::Out;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        let traced = tracing::enabled!(tracing::Level::DEBUG);
+        if !metrics::enabled() && !traced {
+            return;
+        }
+        let structure = self.prev.structure();
+        let Some(operator) = structure.operators.last() else {
+            return;
+        };
+        let index = structure.operators.len() - 1;
+        if metrics::enabled() {
+            let counter = metrics::register_operator(metadata.coord, index, &operator.title);
+            self.counter = Some(counter);
+        }
+        if traced {
+            self.trace = Some(BatchTrace {
+                coord: metadata.coord,
+                index,
+                name: operator.title.clone(),
+                span: None,
+                items: 0,
+            });
+        }
+    }
+
+    #[inline]
+    fn next(&mut self) -> StreamElement<Self::Out> {
+        let el = match &mut self.trace {
+            Some(trace) => trace.next(&mut self.prev),
+            None => self.prev.next(),
+        };
+        if let Some(counter) = &self.counter {
+            let is_item = matches!(el, StreamElement::Item(_) | StreamElement::Timestamped(..));
+            self.pending += is_item as u64;
+            // publish before blocking for the next batch, or once in a while
+            if self.pending > 0 && (!is_item || self.pending >= PUBLISH_EVERY) {
+                counter.fetch_add(self.pending, Ordering::Relaxed);
+                self.pending = 0;
+            }
+        }
+        el
+    }
+
+    fn structure(&self) -> BlockStructure {
+        self.prev.structure()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Id, Record};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    use crate::network::Coord;
+    use crate::operator::instrumented::Instrumented;
+    use crate::operator::{Operator, StreamElement};
+    use crate::profiler::metrics;
+    use crate::test::{FakeNetworkTopology, FakeOperator};
+
+    #[test]
+    fn instrumented_counts_items() {
+        metrics::enable();
+        let mut fake_operator = FakeOperator::new(0..2000u32);
+        fake_operator.push(StreamElement::Watermark(100));
+
+        let mut topology = FakeNetworkTopology::<u32>::new(0, 0);
+        let mut metadata = topology.metadata();
+        let coord = Coord::new(4444, 0, 0);
+        metadata.coord = coord;
+        let mut instrumented = Instrumented::new(fake_operator);
+        instrumented.setup(&mut metadata);
+        let counter = metrics::register_operator(coord, 0, "FakeOperator");
+
+        for i in 0..2000 {
+            assert_eq!(instrumented.next(), StreamElement::Item(i));
+        }
+        // the items are published in chunks, and before any other element
+        assert_eq!(counter.load(Ordering::Relaxed), 1024);
+        assert_eq!(instrumented.next(), StreamElement::Watermark(100));
+        assert_eq!(counter.load(Ordering::Relaxed), 2000);
+    }
+
+    /// Collect the number of items recorded by the spans.
+    #[derive(Clone, Default)]
+    struct RecordedItems(Arc<Mutex<Vec<u64>>>);
+
+    impl Visit for RecordedItems {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            if field.name() == "items" {
+                self.0.lock().unwrap().push(value);
+            }
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    impl<S: Subscriber> Layer<S> for RecordedItems {
+        fn on_record(&self, _span: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            values.record(&mut self.clone());
+        }
+    }
+
+    #[test]
+    fn instrumented_traces_batches() {
+        let mut fake_operator = FakeOperator::new(0..3u32);
+        fake_operator.push(StreamElement::FlushBatch);
+        fake_operator.push(StreamElement::Item(3));
+        fake_operator.push(StreamElement::Item(4));
+
+        let recorded = RecordedItems::default();
+        let subscriber = tracing_subscriber::registry().with(recorded.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let mut topology = FakeNetworkTopology::<u32>::new(0, 0);
+            let mut instrumented = Instrumented::new(fake_operator);
+            instrumented.setup(&mut topology.metadata());
+            while instrumented.next() != StreamElement::Terminate {}
+        });
+
+        assert_eq!(*recorded.0.lock().unwrap(), vec![3, 2]);
+    }
+}
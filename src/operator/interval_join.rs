@@ -16,6 +16,10 @@ type OutputElement<Key, Out, Out2> = (Key, (Out, Out2));
 /// interval `ts - lower_bound` and `ts + upper_bound` (inclusive).
 ///
 /// This operator assumes elements are received in increasing order of timestamp.
+///
+/// The elements of the right side are kept only while they can still be matched: when the
+/// watermark passes, the ones that are too old for any future element of the left side are
+/// dropped, including those of the keys that are not seen anymore.
 #[derive(Clone, Debug)]
 pub struct IntervalJoin<Key, Out, Out2, OperatorChain>
 where
@@ -80,6 +84,32 @@ where
         }
     }
 
+    /// Drop the elements of the right side that cannot be matched by any element of the left
+    /// side, either already received or yet to come.
+    fn cleanup(&mut self) {
+        // the elements of the left side are sorted by timestamp, and the ones that will be
+        // received have a timestamp greater than the last seen
+        let oldest = self
+            .left
+            .front()
+            .map(|(ts, _)| *ts)
+            .unwrap_or(self.last_seen)
+            .min(self.last_seen);
+        let Some(threshold) = oldest.checked_sub(self.lower_bound) else {
+            return;
+        };
+        self.right.retain(|_, right| {
+            while let Some((right_ts, _)) = right.front() {
+                if *right_ts < threshold {
+                    right.pop_front();
+                } else {
+                    break;
+                }
+            }
+            !right.is_empty()
+        });
+    }
+
     /// Advance the operator, trying to generate some join tuples.
     fn advance(&mut self) {
         while let Some((left_ts, (lkey, lvalue))) = self.left.front() {
@@ -174,6 +204,9 @@ where
                 StreamElement::Watermark(ts) => {
                     assert!(ts >= self.last_seen);
                     self.last_seen = ts;
+                    self.advance();
+                    self.cleanup();
+                    continue;
                 }
                 StreamElement::FlushAndRestart => {
                     self.received_restart = true;
@@ -191,10 +224,39 @@ where
     }
 
     fn structure(&self) -> BlockStructure {
-        self.prev
-            .structure()
-            .add_operator(OperatorStructure::new::<(Key, (Out, Out2)), _>(
-                "IntervalJoin",
-            ))
+        let mut operator = OperatorStructure::new::<(Key, (Out, Out2)), _>("IntervalJoin");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::operator::interval_join::IntervalJoin;
+    use crate::operator::merge::MergeElement;
+    use crate::operator::{Operator, StreamElement};
+    use crate::test::FakeOperator;
+
+    #[test]
+    fn interval_join_state_cleanup() {
+        let mut fake = FakeOperator::empty();
+        for key in 0..10u8 {
+            fake.push(StreamElement::Timestamped(
+                (key, MergeElement::Right(key as i64)),
+                key as i64,
+            ));
+        }
+        fake.push(StreamElement::Timestamped((0, MergeElement::Left(10)), 10));
+        fake.push(StreamElement::Watermark(20));
+        fake.push(StreamElement::Timestamped((1, MergeElement::Left(21)), 21));
+        fake.push(StreamElement::Watermark(30));
+
+        let mut join = IntervalJoin::<u8, i64, i64, _>::new(fake, 15, 0);
+        assert_eq!(join.next(), StreamElement::Timestamped((0, (10, 0)), 10));
+        // the first watermark drops the right elements older than 20 - 15
+        assert_eq!(join.right.values().map(|r| r.len()).sum::<usize>(), 5);
+        // the right element with key 1 has been dropped, so nothing is left to join
+        assert_eq!(join.next(), StreamElement::Terminate);
+        assert!(join.right.is_empty());
     }
 }
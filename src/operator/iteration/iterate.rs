@@ -16,7 +16,7 @@ use crate::operator::iteration::iteration_end::IterationEnd;
 use crate::operator::iteration::leader::IterationLeader;
 use crate::operator::iteration::state_handler::IterationStateHandler;
 use crate::operator::iteration::{
-    IterationResult, IterationStateHandle, IterationStateLock, StateFeedback,
+    IterationOptions, IterationResult, IterationStateHandle, IterationStateLock, StateFeedback,
 };
 use crate::operator::source::Source;
 use crate::operator::start::Start;
@@ -277,6 +277,7 @@ impl<Out: ExchangeData, State: ExchangeData + Sync> Operator for Iterate<Out, St
 
     fn structure(&self) -> BlockStructure {
         let mut operator = OperatorStructure::new::<Out, _>("Iterate");
+        operator.unsaved_state = true;
         operator
             .receivers
             .push(OperatorReceiver::new::<StateFeedback<State>>(
@@ -372,6 +373,83 @@ where
         Stream<impl Operator<Out = State>>,
         Stream<impl Operator<Out = Out>>,
     )
+    where
+        Body: FnOnce(
+            Stream<Iterate<Out, State>>,
+            IterationStateHandle<State>,
+        ) -> Stream<OperatorChain2>,
+        OperatorChain2: Operator<Out = Out> + 'static,
+        L: Fn(&mut StateUpdate, Out) + Send + Clone + 'static,
+        G: Fn(&mut State, StateUpdate) + Send + Clone + 'static,
+        C: Fn(&mut State) -> bool + Send + Clone + 'static,
+        StateUpdate: ExchangeData + Default,
+        State: ExchangeData + Sync,
+    {
+        self.iterate_with_options(
+            initial_state,
+            body,
+            local_fold,
+            global_fold,
+            loop_condition,
+            IterationOptions::default().max_iterations(num_iterations),
+        )
+    }
+
+    /// Like [`Stream::iterate`], but the end of the iteration is set with [`IterationOptions`]:
+    /// besides the loop condition, the iteration can end when its state converges, and each
+    /// iteration can be inspected.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// Compute the square root of 2 with Newton's method, until the estimate stops changing:
+    ///
+    /// ```
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// # use std::sync::Arc;
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::iteration::IterationOptions;
+    /// # let mut env = StreamContext::new_local();
+    /// let iterations = Arc::new(AtomicUsize::new(0));
+    /// let options = IterationOptions::default()
+    ///     .max_iterations(100)
+    ///     .until_converged(|old: &f64, new: &f64| (old - new).abs() < 1e-12)
+    ///     .inspect({
+    ///         let iterations = iterations.clone();
+    ///         move |i, _| iterations.store(i, Ordering::SeqCst)
+    ///     });
+    /// let s = env.stream_iter(0..1).shuffle();
+    /// let (state, items) = s.iterate_with_options(
+    ///     1.0,
+    ///     |s, _| s,
+    ///     |_: &mut (), _| {},
+    ///     |_, _| {},
+    ///     |x| {
+    ///         *x = (*x + 2.0 / *x) / 2.0;
+    ///         true
+    ///     },
+    ///     options,
+    /// );
+    /// let state = state.collect_vec();
+    /// items.for_each(|_| {});
+    /// env.execute_blocking();
+    ///
+    /// assert!((state.get().unwrap()[0] - 2f64.sqrt()).abs() < 1e-12);
+    /// assert!(iterations.load(Ordering::SeqCst) < 10);
+    /// ```
+    pub fn iterate_with_options<Body, StateUpdate, State, L, G, C, OperatorChain2>(
+        self,
+        initial_state: State,
+        body: Body,
+        local_fold: L,
+        global_fold: G,
+        loop_condition: C,
+        options: IterationOptions<State>,
+    ) -> (
+        Stream<impl Operator<Out = State>>,
+        Stream<impl Operator<Out = Out>>,
+    )
     where
         Body: FnOnce(
             Stream<Iterate<Out, State>>,
@@ -406,7 +484,7 @@ where
         let leader_block = ctx.lock().new_block(
             IterationLeader::new(
                 initial_state,
-                num_iterations,
+                options,
                 global_fold,
                 loop_condition,
                 shared_state_update_id.clone(),
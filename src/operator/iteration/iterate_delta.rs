@@ -82,9 +82,9 @@ impl<Key: ExchangeData, I: ExchangeData, U: ExchangeData, D: ExchangeData, O: Ex
     }
 
     fn structure(&self) -> BlockStructure {
-        self.prev
-            .structure()
-            .add_operator(OperatorStructure::new::<(Key, U), _>("DeltaIterate"))
+        let mut operator = OperatorStructure::new::<(Key, U), _>("DeltaIterate");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
     }
 }
 
@@ -156,8 +156,8 @@ where
                     routes
                         .next()
                         .unwrap()
-                        .to_keyed()
-                        .add_operator(|prev| DeltaIterate { prev }),
+                        .add_uncounted_operator(|prev| DeltaIterate { prev })
+                        .to_keyed(),
                 )
                 .map(|(_, v)| Msg::Delta(v))
                 .unkey();
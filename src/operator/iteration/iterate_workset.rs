@@ -0,0 +1,344 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::block::{BlockStructure, GroupHasherBuilder, OperatorStructure};
+use crate::operator::iteration::IterationStateHandle;
+use crate::operator::{
+    ExchangeData, ExchangeDataKey, Operator, SimpleStartOperator, StreamElement,
+};
+use crate::scheduler::ExecutionMetadata;
+use crate::KeyedStream;
+
+#[derive(Clone, Serialize, Deserialize, Default, Debug)]
+struct WorksetCond {
+    /// Whether the body produced some candidate values in this iteration.
+    changed: bool,
+    /// Whether this is the iteration that outputs the solution set.
+    last_iteration: bool,
+    iter: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+enum Msg<S> {
+    /// A value to merge into the solution set.
+    Candidate(S),
+    /// An entry of the solution set that changed in this iteration.
+    Changed(S),
+    /// An entry of the final solution set.
+    Output(S),
+}
+
+impl<S> Msg<S> {
+    /// Returns `true` if the msg is [`Changed`].
+    ///
+    /// [`Changed`]: Msg::Changed
+    #[must_use]
+    fn is_changed(&self) -> bool {
+        matches!(self, Self::Changed(..))
+    }
+
+    /// Returns `true` if the msg is [`Output`].
+    ///
+    /// [`Output`]: Msg::Output
+    #[must_use]
+    fn is_output(&self) -> bool {
+        matches!(self, Self::Output(..))
+    }
+}
+
+/// Operator that keeps the part of the solution set of a workset iteration owned by a replica.
+///
+/// The candidate values are merged into the solution set, and the entries that changed are sent
+/// to the body of the loop. In the last iteration the whole solution set is sent out of the loop,
+/// before the end of the iteration.
+#[derive(Clone)]
+struct SolutionSet<K, S, F, OperatorChain>
+where
+    K: ExchangeDataKey,
+    S: ExchangeData,
+    F: Fn(&K, &mut S, S) -> bool + Clone + Send + 'static,
+    OperatorChain: Operator<Out = (K, Msg<S>)>,
+{
+    prev: OperatorChain,
+    update: F,
+    state: IterationStateHandle<WorksetCond>,
+    solution: HashMap<K, S, GroupHasherBuilder>,
+    buffer: VecDeque<StreamElement<(K, Msg<S>)>>,
+}
+
+impl<K, S, F, OperatorChain> Display for SolutionSet<K, S, F, OperatorChain>
+where
+    K: ExchangeDataKey,
+    S: ExchangeData,
+    F: Fn(&K, &mut S, S) -> bool + Clone + Send + 'static,
+    OperatorChain: Operator<Out = (K, Msg<S>)>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> SolutionSet<{}>",
+            self.prev,
+            std::any::type_name::<(K, S)>()
+        )
+    }
+}
+
+impl<K, S, F, OperatorChain> SolutionSet<K, S, F, OperatorChain>
+where
+    K: ExchangeDataKey,
+    S: ExchangeData,
+    F: Fn(&K, &mut S, S) -> bool + Clone + Send + 'static,
+    OperatorChain: Operator<Out = (K, Msg<S>)>,
+{
+    fn new(prev: OperatorChain, update: F, state: IterationStateHandle<WorksetCond>) -> Self {
+        Self {
+            prev,
+            update,
+            state,
+            solution: Default::default(),
+            buffer: Default::default(),
+        }
+    }
+
+    /// Merge a candidate value into the solution set, returning the new value of the entry if it
+    /// changed.
+    fn merge(&mut self, key: &K, candidate: S) -> Option<S> {
+        match self.solution.entry(key.clone()) {
+            Entry::Vacant(entry) => Some(entry.insert(candidate).clone()),
+            Entry::Occupied(mut entry) => {
+                if (self.update)(key, entry.get_mut(), candidate) {
+                    Some(entry.get().clone())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl<K, S, F, OperatorChain> Operator for SolutionSet<K, S, F, OperatorChain>
+where
+    K: ExchangeDataKey,
+    S: ExchangeData,
+    F: Fn(&K, &mut S, S) -> bool + Clone + Send + 'static,
+    OperatorChain: Operator<Out = (K, Msg<S>)>,
+{
+    type Out = (K, Msg<S>);
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+    }
+
+    fn next(&mut self) -> StreamElement<(K, Msg<S>)> {
+        loop {
+            if let Some(el) = self.buffer.pop_front() {
+                return el;
+            }
+            match self.prev.next() {
+                StreamElement::Item((key, Msg::Candidate(candidate))) => {
+                    let changed = self.merge(&key, candidate);
+                    // in the last iteration the late candidates are merged silently
+                    if let Some(value) = changed.filter(|_| !self.state.get().last_iteration) {
+                        return StreamElement::Item((key, Msg::Changed(value)));
+                    }
+                }
+                StreamElement::FlushAndRestart => {
+                    if self.state.get().last_iteration {
+                        self.buffer.extend(
+                            self.solution
+                                .drain()
+                                .map(|(key, value)| StreamElement::Item((key, Msg::Output(value)))),
+                        );
+                    }
+                    self.buffer.push_back(StreamElement::FlushAndRestart);
+                }
+                StreamElement::Item(_) => {
+                    unreachable!("invalid message at the solution set of WorksetIterate")
+                }
+                StreamElement::Timestamped(..) | StreamElement::Watermark(_) => {
+                    panic!("WorksetIterate does not support timestamped streams")
+                }
+                el => return el,
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<(K, Msg<S>), _>("SolutionSet");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+/// The first operator of the body of a workset iteration: the stream of the entries of the
+/// solution set that changed in the previous iteration.
+#[derive(Clone)]
+pub struct Workset<K: ExchangeData, S: ExchangeData> {
+    prev: SimpleStartOperator<(K, Msg<S>)>,
+}
+
+impl<K: ExchangeData, S: ExchangeData> Operator for Workset<K, S> {
+    type Out = (K, S);
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+    }
+
+    fn next(&mut self) -> StreamElement<(K, S)> {
+        self.prev.next().map(|(k, msg)| match msg {
+            Msg::Changed(value) => (k, value),
+            _ => unreachable!("invalid message in the workset"),
+        })
+    }
+
+    fn structure(&self) -> BlockStructure {
+        self.prev
+            .structure()
+            .add_operator(OperatorStructure::new::<(K, S), _>("Workset"))
+    }
+}
+
+impl<K: ExchangeData, S: ExchangeData> Display for Workset<K, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Workset")
+    }
+}
+
+impl<K: ExchangeDataKey, S: ExchangeData, OperatorChain> KeyedStream<OperatorChain>
+where
+    OperatorChain: Operator<Out = (K, S)> + 'static,
+{
+    /// Construct a delta iteration where only the elements that changed are fed back in the loop.
+    ///
+    /// The stream is the initial _solution set_: a value for each key, that is kept by the replica
+    /// that owns the key for the whole iteration. At each iteration, the `body` receives the
+    /// _workset_, i.e. the entries of the solution set that changed in the previous iteration (at
+    /// the first iteration, all of them), and produces some candidate values for the keys. Each
+    /// candidate is merged into the entry of its key with `update`, that returns `true` if the
+    /// value changed: only those entries go in the next workset. A candidate for a key not in the
+    /// solution set is added to it.
+    ///
+    /// The iteration ends when the workset is empty, or after `max_iterations` iterations. The
+    /// resulting stream is the final solution set.
+    ///
+    /// Since the body only processes the workset, which usually shrinks quickly, this is much
+    /// faster than a full iteration for algorithms like the connected components or the shortest
+    /// paths. See [`KeyedStream::delta_iterate`] for an iteration where all the keys are processed
+    /// at each step.
+    ///
+    /// **Note**: due to an internal limitation, it's not currently possible to add an iteration
+    /// operator when the stream has limited parallelism. This means, for example, that after a
+    /// non-parallel source you have to add a shuffle.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// Compute the shortest distance of each node from node 0:
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let edges = vec![(0, 1, 4), (0, 2, 1), (2, 1, 2), (1, 3, 1)];
+    /// let res = env
+    ///     .stream_iter(std::iter::once((0u32, 0u32)))
+    ///     .shuffle()
+    ///     .group_by(|&(node, _)| node)
+    ///     .map(|(_, (_, dist))| dist)
+    ///     .workset_iterate(
+    ///         100,
+    ///         |_node, dist, candidate| {
+    ///             let shorter = candidate < *dist;
+    ///             *dist = (*dist).min(candidate);
+    ///             shorter
+    ///         },
+    ///         move |workset| {
+    ///             workset
+    ///                 .unkey()
+    ///                 .flat_map(move |(node, dist)| {
+    ///                     edges
+    ///                         .iter()
+    ///                         .filter(move |(from, _, _)| *from == node)
+    ///                         .map(move |&(_, to, w)| (to, dist + w))
+    ///                         .collect::<Vec<_>>()
+    ///                 })
+    ///                 .group_by_reduce(|&(to, _)| to, |a, b| a.1 = a.1.min(b.1))
+    ///                 .map(|(_, (_, dist))| dist)
+    ///         },
+    ///     )
+    ///     .collect_vec();
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![(0, 0), (1, 3), (2, 1), (3, 4)]);
+    /// ```
+    pub fn workset_iterate<Body, BodyOperator>(
+        self,
+        max_iterations: usize,
+        update: impl Fn(&K, &mut S, S) -> bool + Clone + Send + 'static,
+        body: Body,
+    ) -> KeyedStream<impl Operator<Out = (K, S)>>
+    where
+        Body: FnOnce(KeyedStream<Workset<K, S>>) -> KeyedStream<BodyOperator> + 'static,
+        BodyOperator: Operator<Out = (K, S)> + 'static,
+    {
+        let (state, out) = self.map(|(_, v)| Msg::Candidate(v)).unkey().iterate(
+            // one more iteration to output the solution set
+            max_iterations.saturating_add(1),
+            WorksetCond::default(),
+            move |s, state: IterationStateHandle<WorksetCond>| {
+                let mut routes = s
+                    .group_by(|(k, _)| k.clone())
+                    .map(|(_, (_, msg))| msg)
+                    .add_operator(|prev| SolutionSet::new(prev, update, state))
+                    .unkey()
+                    .route()
+                    .add_route(|(_, msg)| msg.is_changed())
+                    .add_route(|(_, msg)| msg.is_output())
+                    .build_inner()
+                    .into_iter();
+
+                let candidates = body(
+                    routes
+                        .next()
+                        .unwrap()
+                        .add_uncounted_operator(|prev| Workset { prev })
+                        .to_keyed(),
+                )
+                .map(|(_, v)| Msg::Candidate(v))
+                .unkey();
+                let output = routes.next().unwrap();
+
+                candidates.merge(output)
+            },
+            |cond: &mut WorksetCond, (_, msg)| {
+                if let Msg::Candidate(_) = msg {
+                    cond.changed = true;
+                }
+            },
+            |global, local| global.changed |= local.changed,
+            move |s| {
+                if s.last_iteration {
+                    return false;
+                }
+                s.iter += 1;
+                if !s.changed || s.iter >= max_iterations {
+                    s.last_iteration = true;
+                }
+                s.changed = false;
+                true
+            },
+        );
+
+        state.for_each(std::mem::drop);
+        out.to_keyed().map(|(_, msg)| match msg {
+            Msg::Output(value) => value,
+            _ => unreachable!("invalid message at the end of WorksetIterate"),
+        })
+    }
+}
@@ -4,7 +4,7 @@ use std::sync::Arc;
 
 use crate::block::{BlockStructure, Connection, NextStrategy, OperatorStructure, Replication};
 use crate::network::{Coord, NetworkMessage, NetworkSender};
-use crate::operator::iteration::{IterationResult, StateFeedback};
+use crate::operator::iteration::{IterationOptions, IterationResult, StateFeedback};
 use crate::operator::source::Source;
 use crate::operator::start::{SimpleStartOperator, Start, StartReceiver};
 use crate::operator::{ExchangeData, Operator, StreamElement};
@@ -33,8 +33,8 @@ where
 
     /// The index of the current iteration (0-based).
     iteration_index: usize,
-    /// The maximum number of iterations to perform.
-    max_iterations: usize,
+    /// When the iteration ends, and the function inspecting each iteration.
+    options: IterationOptions<State>,
 
     /// The current global state of the iteration.
     ///
@@ -45,6 +45,9 @@ where
     ///
     /// Will be used for resetting the global state after all the iterations complete.
     initial_state: State,
+    /// The global state at the start of the current iteration, kept only if the convergence of
+    /// the state has to be checked.
+    previous_state: Option<State>,
 
     /// The receiver from the `IterationEnd`s at the end of the loop.
     ///
@@ -93,7 +96,7 @@ where
 {
     pub fn new(
         initial_state: State,
-        num_iterations: usize,
+        options: IterationOptions<State>,
         global_fold: Global,
         loop_condition: LoopCond,
         feedback_block_id: Arc<AtomicUsize>,
@@ -105,10 +108,11 @@ where
             coord: Coord::new(0, 0, 0),
             num_receivers: 0,
 
-            max_iterations: num_iterations,
+            options,
             iteration_index: 0,
             state: Some(initial_state.clone()),
             initial_state,
+            previous_state: None,
             feedback_block_id,
             flush_and_restart: false,
             global_fold,
@@ -117,6 +121,9 @@ where
     }
 
     fn process_updates(&mut self) -> Option<StreamElement<State>> {
+        if self.options.converged.is_some() {
+            self.previous_state.clone_from(&self.state);
+        }
         let mut missing_state_updates = self.num_receivers;
         let rx = self.state_update_receiver.as_mut().unwrap();
         while missing_state_updates > 0 {
@@ -147,15 +154,25 @@ where
     /// Returns Some if it's the last loop and the state should be returned
     fn final_result(&mut self) -> Option<State> {
         let loop_condition = (self.loop_condition)(self.state.as_mut().unwrap());
-        let more_iterations = self.iteration_index < self.max_iterations;
-        let should_continue = loop_condition && more_iterations;
+        let more_iterations = self.iteration_index < self.options.max_iterations;
+        let converged = match (&self.options.converged, &self.previous_state) {
+            (Some(converged), Some(previous)) => converged(previous, self.state.as_ref().unwrap()),
+            _ => false,
+        };
+        let should_continue = loop_condition && more_iterations && !converged;
 
+        if let Some(inspect) = &self.options.inspect {
+            inspect(self.iteration_index, self.state.as_ref().unwrap());
+        }
         if !loop_condition {
             log::trace!("iter_leader finish_condition {}", self.coord,);
         }
         if !more_iterations {
             log::trace!("iter_leader finish_max_iter {}", self.coord);
         }
+        if converged {
+            log::trace!("iter_leader finish_converged {}", self.coord);
+        }
 
         if should_continue {
             None
@@ -235,6 +252,7 @@ where
 
     fn structure(&self) -> BlockStructure {
         let mut operator = OperatorStructure::new::<State, _>("IterationLeader");
+        operator.unsaved_state = true;
         operator
             .connections
             .push(Connection::new::<StateFeedback<State>, _>(
@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 
 mod iterate;
 mod iterate_delta;
+mod iterate_workset;
 mod iteration_end;
 mod leader;
 mod replay;
@@ -30,6 +31,92 @@ impl IterationResult {
     }
 }
 
+/// How an iteration ends, and how to observe it, see [`Stream::iterate_with_options`].
+///
+/// By default the iteration ends only when its loop condition is false. It can also end after a
+/// maximum number of iterations, or when the state converges: the convergence criterion is given
+/// the state at the start and at the end of each iteration. The inspection function is called
+/// with the index (starting from 1) and the new state at the end of each iteration, which is
+/// useful for logging the progress or debugging a job.
+///
+/// The convergence criterion and the inspection function are evaluated on the single node that
+/// computes the state of the iteration.
+///
+/// ## Example
+///
+/// ```
+/// # use renoir::operator::iteration::IterationOptions;
+/// let options = IterationOptions::default()
+///     .max_iterations(100)
+///     .until_converged(|old: &f64, new: &f64| (old - new).abs() < 1e-6)
+///     .inspect(|iteration, state| println!("iteration {iteration}: {state}"));
+/// ```
+///
+/// [`Stream::iterate_with_options`]: crate::Stream::iterate_with_options
+pub struct IterationOptions<State> {
+    max_iterations: usize,
+    converged: Option<ConvergedFn<State>>,
+    inspect: Option<InspectFn<State>>,
+}
+
+type ConvergedFn<State> = Arc<dyn Fn(&State, &State) -> bool + Send + Sync>;
+type InspectFn<State> = Arc<dyn Fn(usize, &State) + Send + Sync>;
+
+impl<State> Default for IterationOptions<State> {
+    fn default() -> Self {
+        Self {
+            max_iterations: usize::MAX,
+            converged: None,
+            inspect: None,
+        }
+    }
+}
+
+impl<State> Clone for IterationOptions<State> {
+    fn clone(&self) -> Self {
+        Self {
+            max_iterations: self.max_iterations,
+            converged: self.converged.clone(),
+            inspect: self.inspect.clone(),
+        }
+    }
+}
+
+impl<State> Debug for IterationOptions<State> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IterationOptions")
+            .field("max_iterations", &self.max_iterations)
+            .finish()
+    }
+}
+
+impl<State> IterationOptions<State> {
+    /// Stop after this number of iterations.
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Stop when `converged`, given the state at the start and at the end of an iteration,
+    /// returns true.
+    pub fn until_converged<F>(mut self, converged: F) -> Self
+    where
+        F: Fn(&State, &State) -> bool + Send + Sync + 'static,
+    {
+        self.converged = Some(Arc::new(converged));
+        self
+    }
+
+    /// Call `inspect` with the index and the new state at the end of each iteration.
+    pub fn inspect<F>(mut self, inspect: F) -> Self
+    where
+        F: Fn(usize, &State) + Send + Sync + 'static,
+    {
+        self.inspect = Some(Arc::new(inspect));
+        self
+    }
+}
+
 /// The information about the new state of an iteration:
 ///
 /// - a boolean indicating if a new iteration should start
@@ -11,7 +11,7 @@ use crate::operator::iteration::iteration_end::IterationEnd;
 use crate::operator::iteration::leader::IterationLeader;
 use crate::operator::iteration::state_handler::IterationStateHandler;
 use crate::operator::iteration::{
-    IterationResult, IterationStateHandle, IterationStateLock, StateFeedback,
+    IterationOptions, IterationResult, IterationStateHandle, IterationStateLock, StateFeedback,
 };
 use crate::operator::{Data, ExchangeData, Operator, StreamElement};
 use crate::scheduler::{BlockId, ExecutionMetadata};
@@ -194,6 +194,7 @@ where
 
     fn structure(&self) -> BlockStructure {
         let mut operator = OperatorStructure::new::<Out, _>("Replay");
+        operator.unsaved_state = true;
         operator
             .receivers
             .push(OperatorReceiver::new::<StateFeedback<State>>(
@@ -292,7 +293,7 @@ where
         let output_block = env.lock().new_block(
             IterationLeader::new(
                 initial_state,
-                num_iterations,
+                IterationOptions::default().max_iterations(num_iterations),
                 global_fold,
                 loop_condition,
                 feedback_block_id.clone(),
@@ -306,8 +307,8 @@ where
         // the lock for synchronizing the access to the state of this iteration
         let state_lock = Arc::new(IterationStateLock::default());
 
-        let mut iter_start =
-            self.add_operator(|prev| Replay::new(prev, state, output_id, state_lock.clone()));
+        let mut iter_start = self
+            .add_uncounted_operator(|prev| Replay::new(prev, state, output_id, state_lock.clone()));
         let replay_block_id = iter_start.block.id;
 
         // save the stack of the iteration for checking the stream returned by the body
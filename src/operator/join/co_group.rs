@@ -0,0 +1,194 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::marker::PhantomData;
+
+use crate::block::{BlockStructure, GroupHasherBuilder, NextStrategy, OperatorStructure};
+use crate::operator::{
+    BinaryElement, BinaryStartOperator, Data, DataKey, ExchangeData, Operator, Start, StreamElement,
+};
+use crate::scheduler::ExecutionMetadata;
+use crate::KeyedStream;
+
+/// Collect the elements of both sides, grouped by key, and process each pair of groups when both
+/// sides have ended.
+#[derive(Clone)]
+struct CoGroup<K, V1, V2, O, F>
+where
+    K: DataKey + ExchangeData,
+    V1: ExchangeData,
+    V2: ExchangeData,
+    F: Fn(&K, Vec<V1>, Vec<V2>) -> O + Clone + Send,
+{
+    prev: BinaryStartOperator<(K, V1), (K, V2)>,
+    f: F,
+    groups: HashMap<K, (Vec<V1>, Vec<V2>), GroupHasherBuilder>,
+    left_ended: bool,
+    right_ended: bool,
+    buffer: VecDeque<(K, O)>,
+    _o: PhantomData<O>,
+}
+
+impl<K, V1, V2, O, F> CoGroup<K, V1, V2, O, F>
+where
+    K: DataKey + ExchangeData,
+    V1: ExchangeData,
+    V2: ExchangeData,
+    F: Fn(&K, Vec<V1>, Vec<V2>) -> O + Clone + Send,
+{
+    fn new(prev: BinaryStartOperator<(K, V1), (K, V2)>, f: F) -> Self {
+        Self {
+            prev,
+            f,
+            groups: Default::default(),
+            left_ended: false,
+            right_ended: false,
+            buffer: Default::default(),
+            _o: PhantomData,
+        }
+    }
+
+    fn process_item(&mut self, item: BinaryElement<(K, V1), (K, V2)>) {
+        match item {
+            BinaryElement::Left((key, v1)) => self.groups.entry(key).or_default().0.push(v1),
+            BinaryElement::Right((key, v2)) => self.groups.entry(key).or_default().1.push(v2),
+            BinaryElement::LeftEnd => self.left_ended = true,
+            BinaryElement::RightEnd => self.right_ended = true,
+        }
+        if self.left_ended && self.right_ended {
+            for (key, (left, right)) in self.groups.drain() {
+                let out = (self.f)(&key, left, right);
+                self.buffer.push_back((key, out));
+            }
+        }
+    }
+}
+
+impl<K, V1, V2, O, F> Display for CoGroup<K, V1, V2, O, F>
+where
+    K: DataKey + ExchangeData,
+    V1: ExchangeData,
+    V2: ExchangeData,
+    F: Fn(&K, Vec<V1>, Vec<V2>) -> O + Clone + Send,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> CoGroup<{},{},{}>",
+            self.prev,
+            std::any::type_name::<K>(),
+            std::any::type_name::<V1>(),
+            std::any::type_name::<V2>(),
+        )
+    }
+}
+
+impl<K, V1, V2, O, F> Operator for CoGroup<K, V1, V2, O, F>
+where
+    K: DataKey + ExchangeData,
+    V1: ExchangeData,
+    V2: ExchangeData,
+    O: Data,
+    F: Fn(&K, Vec<V1>, Vec<V2>) -> O + Clone + Send,
+{
+    type Out = (K, O);
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+    }
+
+    fn next(&mut self) -> StreamElement<(K, O)> {
+        loop {
+            if let Some(item) = self.buffer.pop_front() {
+                return StreamElement::Item(item);
+            }
+            match self.prev.next() {
+                StreamElement::Item(el) | StreamElement::Timestamped(el, _) => {
+                    self.process_item(el)
+                }
+                StreamElement::Watermark(_) => {}
+                StreamElement::FlushAndRestart => {
+                    debug_assert!(self.groups.is_empty());
+                    self.left_ended = false;
+                    self.right_ended = false;
+                    return StreamElement::FlushAndRestart;
+                }
+                StreamElement::Terminate => return StreamElement::Terminate,
+                StreamElement::FlushBatch => return StreamElement::FlushBatch,
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<(K, O), _>("CoGroup");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+impl<K: DataKey + ExchangeData, V1: Data + ExchangeData, O1> KeyedStream<O1>
+where
+    O1: Operator<Out = (K, V1)> + 'static,
+{
+    /// Group the elements of two keyed streams by key, and process the two groups of each key
+    /// together with the provided function.
+    ///
+    /// The function is called once for each key present in at least one of the two streams, with
+    /// all the elements of that key from the first stream and all the elements of that key from
+    /// the second stream (one of the two may be empty). This is more general than a join: for
+    /// example an inner join emits the product of the two groups, while `co_group` can merge
+    /// them in any way.
+    ///
+    /// **Note**: this operator will retain all the messages of the two streams and emit the
+    /// values only when both the streams end. Therefore this is not properly _streaming_. The
+    /// timestamps of the elements are discarded.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let orders = env
+    ///     .stream_iter(vec![('a', 10), ('b', 5), ('a', 3)].into_iter())
+    ///     .group_by(|(customer, _)| *customer)
+    ///     .map(|(_, (_, amount))| amount);
+    /// let refunds = env
+    ///     .stream_iter(vec![('a', 4), ('c', 1)].into_iter())
+    ///     .group_by(|(customer, _)| *customer)
+    ///     .map(|(_, (_, amount))| amount);
+    /// let res = orders
+    ///     .co_group(refunds, |_customer, orders, refunds| {
+    ///         orders.iter().sum::<i32>() - refunds.iter().sum::<i32>()
+    ///     })
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![('a', 9), ('b', 5), ('c', -1)]);
+    /// ```
+    pub fn co_group<V2, O2, O, F>(
+        self,
+        rhs: KeyedStream<O2>,
+        f: F,
+    ) -> KeyedStream<impl Operator<Out = (K, O)>>
+    where
+        V2: Data + ExchangeData,
+        O2: Operator<Out = (K, V2)> + 'static,
+        O: Data,
+        F: Fn(&K, Vec<V1>, Vec<V2>) -> O + Clone + Send + 'static,
+    {
+        let next_strategy1 = NextStrategy::only_one();
+        let next_strategy2 = NextStrategy::only_one();
+
+        let inner =
+            self.0
+                .binary_connection(rhs.0, Start::multiple, next_strategy1, next_strategy2);
+
+        let s = inner.add_operator(move |prev| CoGroup::new(prev, f));
+        KeyedStream(s)
+    }
+}
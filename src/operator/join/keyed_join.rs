@@ -239,9 +239,9 @@ impl<K: DataKey + ExchangeData, V1: ExchangeData, V2: ExchangeData> Operator
     }
 
     fn structure(&self) -> crate::block::BlockStructure {
-        self.prev.structure().add_operator(
-            OperatorStructure::new::<(K, InnerJoinTuple<V1, V2>), _>("JoinKeyed"),
-        )
+        let mut operator = OperatorStructure::new::<(K, InnerJoinTuple<V1, V2>), _>("JoinKeyed");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
     }
 }
 
@@ -377,9 +377,9 @@ impl<K: DataKey + ExchangeData + Debug, V1: ExchangeData + Debug, V2: ExchangeDa
     }
 
     fn structure(&self) -> crate::block::BlockStructure {
-        self.prev.structure().add_operator(
-            OperatorStructure::new::<(K, InnerJoinTuple<V1, V2>), _>("JoinKeyed"),
-        )
+        let mut operator = OperatorStructure::new::<(K, InnerJoinTuple<V1, V2>), _>("JoinKeyed");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
     }
 }
 
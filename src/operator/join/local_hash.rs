@@ -1,6 +1,6 @@
 #![allow(clippy::type_complexity)]
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{HashSet, VecDeque};
 use std::fmt::Display;
 use std::marker::PhantomData;
 
@@ -11,6 +11,7 @@ use crate::operator::join::{InnerJoinTuple, JoinVariant, LeftJoinTuple, OuterJoi
 use crate::operator::start::{BinaryElement, BinaryStartOperator};
 use crate::operator::{DataKey, ExchangeData, KeyerFn, Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
+use crate::state::StateMap;
 use crate::stream::{KeyedStream, Stream};
 
 /// This type keeps the elements of a side of the join.
@@ -19,7 +20,7 @@ struct SideHashMap<Key: DataKey, Out> {
     /// The actual items on this side, grouped by key.
     ///
     /// Note that when the other side ends this map is emptied.
-    data: HashMap<Key, Vec<Out>, crate::block::GroupHasherBuilder>,
+    data: StateMap<Key, Vec<Out>>,
     /// The set of all the keys seen.
     ///
     /// Note that when this side ends this set is emptied since it won't be used again.
@@ -30,10 +31,10 @@ struct SideHashMap<Key: DataKey, Out> {
     count: usize,
 }
 
-impl<Key: DataKey, Out> Default for SideHashMap<Key, Out> {
+impl<Key: DataKey, Out: ExchangeData> Default for SideHashMap<Key, Out> {
     fn default() -> Self {
         Self {
-            data: Default::default(),
+            data: StateMap::default().list(),
             keys: Default::default(),
             ended: false,
             count: 0,
@@ -128,7 +129,7 @@ impl<
         make_pair: impl Fn(Option<OutL>, Option<OutR>) -> OuterJoinTuple<Out1, Out2>,
     ) {
         left.count += 1;
-        if let Some(right) = right.data.get(&key) {
+        let matched = right.data.with(&key, |right| {
             // the left item has at least one right matching element
             for rhs in right {
                 buffer.push_back((
@@ -136,19 +137,18 @@ impl<
                     make_pair(Some(item.clone()), Some(rhs.clone())),
                 ));
             }
-        } else if right.ended && left_outer {
-            // if the left item has no right correspondent, but the right has already ended
-            // we might need to generate the outer tuple.
+        });
+        // if the left item has no right correspondent, but the right has already ended we might
+        // need to generate the outer tuple. Otherwise either the rhs is not ended (so we cannot
+        // generate anything for now), or it's left inner, so we cannot generate left-outer tuples.
+        if matched.is_none() && right.ended && left_outer {
             buffer.push_back((key.clone(), make_pair(Some(item.clone()), None)));
-        } else {
-            // either the rhs is not ended (so we cannot generate anything for now), or
-            // it's left inner, so we cannot generate left-outer tuples.
         }
         if right_outer {
             left.keys.insert(key.clone());
         }
         if !right.ended {
-            left.data.entry(key).or_default().push(item);
+            left.data.push(key, item);
         }
     }
 
@@ -197,6 +197,8 @@ impl<
     fn setup(&mut self, metadata: &mut ExecutionMetadata) {
         self.coord = metadata.coord;
         self.prev.setup(metadata);
+        self.left.data.setup(metadata);
+        self.right.data.setup(metadata);
     }
 
     fn next(&mut self) -> StreamElement<(Key, OuterJoinTuple<Out1, Out2>)> {
@@ -277,11 +279,10 @@ impl<
     }
 
     fn structure(&self) -> BlockStructure {
-        self.prev
-            .structure()
-            .add_operator(
-                OperatorStructure::new::<(Key, OuterJoinTuple<Out1, Out2>), _>("JoinLocalHash"),
-            )
+        let mut operator =
+            OperatorStructure::new::<(Key, OuterJoinTuple<Out1, Out2>), _>("JoinLocalHash");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
     }
 }
 
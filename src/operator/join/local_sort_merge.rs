@@ -10,6 +10,7 @@ use crate::operator::join::{InnerJoinTuple, JoinVariant, LeftJoinTuple, OuterJoi
 use crate::operator::start::{BinaryElement, BinaryStartOperator};
 use crate::operator::{Data, DataKey, ExchangeData, KeyerFn, Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
+use crate::spill::SortedRuns;
 use crate::stream::{KeyedStream, Stream};
 use crate::worker::replica_coord;
 
@@ -17,6 +18,9 @@ use crate::worker::replica_coord;
 ///
 /// This operator is able to produce the outer join tuples (the most general type of join), but it
 /// can be asked to skip generating the `None` tuples if the join was actually inner.
+///
+/// With a memory budget the elements of the two sides are written to disk in sorted runs when the
+/// budget is exceeded, and the runs are merged when both sides end.
 #[derive(Clone, Debug)]
 struct JoinLocalSortMerge<
     Key: Data + Ord,
@@ -36,17 +40,16 @@ struct JoinLocalSortMerge<
     /// Whether the right side has ended.
     right_ended: bool,
     /// Elements of the left side.
-    left: Vec<(Key, Out1)>,
+    left: SortedRuns<Key, Out1>,
     /// Elements of the right side.
-    right: Vec<(Key, Out2)>,
+    right: SortedRuns<Key, Out2>,
     /// Buffer with elements ready to be sent downstream.
     buffer: VecDeque<(Key, OuterJoinTuple<Out1, Out2>)>,
     /// Join variant.
     variant: JoinVariant,
-    /// The last key of the last element processed by `advance()` coming from the left side.
-    /// This is used to check whether an element of the right side was matched with an element
-    /// of the left side or not.
-    last_left_key: Option<Key>,
+    /// The elements of the right side with the key of the last element processed by `advance()`
+    /// coming from the left side. They are all matched with that element.
+    right_group: Option<(Key, Vec<Out2>)>,
 }
 
 impl<
@@ -87,66 +90,63 @@ impl<
             right: Default::default(),
             buffer: Default::default(),
             variant,
-            last_left_key: None,
+            right_group: None,
         }
     }
 
-    /// Discard the last element in the buffer containing elements of the right side.
-    /// If needed, generate the right-outer join tuple.
-    fn discard_right(&mut self) {
-        let (rkey, rvalue) = self.right.pop().unwrap();
-
-        // check if the element has been matched with at least one left-side element
-        let matched = matches!(&self.last_left_key, Some(lkey) if lkey == &rkey);
-
-        if !matched && self.variant.right_outer() {
+    /// Discard an element of the right side that was not matched with any element of the left
+    /// side. If needed, generate the right-outer join tuple.
+    fn discard_right(&mut self, rkey: Key, rvalue: Out2) {
+        if self.variant.right_outer() {
             self.buffer.push_back((rkey, (None, Some(rvalue))));
         }
     }
 
     /// Generate some join tuples. Since the number of join tuples can be quite high,
     /// this is used to generate the tuples incrementally, so that the memory usage is lower.
+    ///
+    /// The elements of both sides are processed in ascending order of key.
     fn advance(&mut self) {
-        while self.buffer.is_empty() && (!self.left.is_empty() || !self.right.is_empty()) {
-            // try matching one element of the left side with some elements of the right side
-            if let Some((lkey, lvalue)) = self.left.pop() {
-                // discard the elements of the right side with key bigger than the key of
-                // the element of the left side
-                let discarded = self
-                    .right
-                    .iter()
-                    .rev()
-                    .take_while(|(rkey, _)| rkey > &lkey)
-                    .count();
-                for _ in 0..discarded {
-                    self.discard_right();
+        while self.buffer.is_empty() {
+            let Some((lkey, _)) = self.left.peek() else {
+                // there are no elements left in the left side,
+                // so discard what is remaining in the right side
+                self.right_group = None;
+                match self.right.pop(&self.keyer2) {
+                    Some((rkey, rvalue)) => self.discard_right(rkey, rvalue),
+                    None => return,
                 }
+                continue;
+            };
+            let lkey = lkey.clone();
 
-                // check if there is at least one element matching in the right side
-                let has_matches = matches!(self.right.last(), Some((rkey, _)) if rkey == &lkey);
-
-                if has_matches {
-                    let matches = self
-                        .right
-                        .iter()
-                        .rev()
-                        .take_while(|(rkey, _)| &lkey == rkey)
-                        .map(|(_, rvalue)| {
-                            (lkey.clone(), (Some(lvalue.clone()), Some(rvalue.clone())))
-                        });
-                    self.buffer.extend(matches);
-                } else if self.variant.left_outer() {
-                    self.buffer.push_back((lkey.clone(), (Some(lvalue), None)));
+            if matches!(&self.right_group, Some((gkey, _)) if gkey != &lkey) {
+                self.right_group = None;
+            }
+            if self.right_group.is_none() {
+                // discard the elements of the right side with key smaller than the key of
+                // the element of the left side
+                while matches!(self.right.peek(), Some((rkey, _)) if rkey < &lkey) {
+                    let (rkey, rvalue) = self.right.pop(&self.keyer2).unwrap();
+                    self.discard_right(rkey, rvalue);
                 }
-
-                // set this key as the last key processed
-                self.last_left_key = Some(lkey);
-            } else {
-                // there are no elements left in the left side,
-                // so discard what is remaining in the right side
-                while !self.right.is_empty() {
-                    self.discard_right();
+                // collect the elements of the right side matching the left one
+                let mut group = Vec::new();
+                while matches!(self.right.peek(), Some((rkey, _)) if rkey == &lkey) {
+                    group.push(self.right.pop(&self.keyer2).unwrap().1);
                 }
+                self.right_group = Some((lkey.clone(), group));
+            }
+
+            let (lkey, lvalue) = self.left.pop(&self.keyer1).unwrap();
+            let (_, group) = self.right_group.as_ref().unwrap();
+            if !group.is_empty() {
+                let matches = group
+                    .iter()
+                    .map(|rvalue| (lkey.clone(), (Some(lvalue.clone()), Some(rvalue.clone()))));
+                self.buffer.extend(matches);
+            } else if self.variant.left_outer() {
+                self.buffer.push_back((lkey, (Some(lvalue), None)));
             }
         }
     }
@@ -165,6 +165,8 @@ impl<
 
     fn setup(&mut self, metadata: &mut ExecutionMetadata) {
         self.prev.setup(metadata);
+        self.left.setup(metadata);
+        self.right.setup(metadata);
     }
 
     fn next(&mut self) -> StreamElement<(Key, (Option<Out1>, Option<Out2>))> {
@@ -180,18 +182,18 @@ impl<
 
             match self.prev.next() {
                 StreamElement::Item(BinaryElement::Left(item)) => {
-                    self.left.push(((self.keyer1)(&item), item));
+                    self.left.push((self.keyer1)(&item), item);
                 }
                 StreamElement::Item(BinaryElement::Right(item)) => {
-                    self.right.push(((self.keyer2)(&item), item));
+                    self.right.push((self.keyer2)(&item), item);
                 }
                 StreamElement::Item(BinaryElement::LeftEnd) => {
                     self.left_ended = true;
-                    glidesort::sort_by(&mut self.left, |(k1, _), (k2, _)| k1.cmp(k2));
+                    self.left.finish(&self.keyer1);
                 }
                 StreamElement::Item(BinaryElement::RightEnd) => {
                     self.right_ended = true;
-                    glidesort::sort_by(&mut self.right, |(k1, _), (k2, _)| k1.cmp(k2));
+                    self.right.finish(&self.keyer2);
                 }
                 StreamElement::Timestamped(_, _) | StreamElement::Watermark(_) => {
                     panic!("Cannot join timestamp streams")
@@ -209,7 +211,7 @@ impl<
                     // reset the state of the operator
                     self.left_ended = false;
                     self.right_ended = false;
-                    self.last_left_key = None;
+                    self.right_group = None;
 
                     return StreamElement::FlushAndRestart;
                 }
@@ -220,10 +222,10 @@ impl<
     }
 
     fn structure(&self) -> BlockStructure {
-        self.prev.structure().add_operator(OperatorStructure::new::<
-            (Key, OuterJoinTuple<Out1, Out2>),
-            _,
-        >("JoinLocalSortMerge"))
+        let mut operator =
+            OperatorStructure::new::<(Key, OuterJoinTuple<Out1, Out2>), _>("JoinLocalSortMerge");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
     }
 }
 
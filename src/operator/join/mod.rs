@@ -13,6 +13,7 @@ pub use crate::operator::join::ship::{JoinStreamShipBroadcastRight, JoinStreamSh
 use crate::operator::{Data, DataKey, ExchangeData, KeyerFn, Operator};
 use crate::stream::{KeyedStream, Stream};
 
+mod co_group;
 mod keyed_join;
 mod local_hash;
 mod local_sort_merge;
@@ -227,6 +228,59 @@ where
             .outer()
     }
 
+    /// Given a large stream and a small one, create a stream with all the pairs (left item from
+    /// the large stream, right item from the small one) with the same key, without shuffling the
+    /// large stream.
+    ///
+    /// The small stream is sent in full to every replica of the join, where it is kept in a hash
+    /// table. The items of the large stream stay on the replica that produced them: they are
+    /// buffered only until the small stream has ended, after which they are joined and forwarded
+    /// as they arrive.
+    ///
+    /// This is an inner join, like [`Stream::join`], and a shortcut for:
+    /// `self.join_with(...).ship_broadcast_right().local_hash().inner()`.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # let mut env = StreamContext::new_local();
+    /// let orders = env.stream_par_iter(0..6u32).map(|n| (n, n % 3));
+    /// let names = env.stream_iter(vec![(0u32, 'a'), (1, 'b')].into_iter());
+    /// let res = orders
+    ///     .join_broadcast(names, |(_, c)| *c, |(c, _)| *c)
+    ///     .map(|(_, ((order, _), (_, name)))| (order, name))
+    ///     .drop_key()
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![(0, 'a'), (1, 'b'), (3, 'a'), (4, 'b')]);
+    /// ```
+    pub fn join_broadcast<Out2: ExchangeData, OperatorChain2, Key, Keyer1, Keyer2>(
+        self,
+        small: Stream<OperatorChain2>,
+        keyer1: Keyer1,
+        keyer2: Keyer2,
+    ) -> KeyedStream<impl Operator<Out = (Key, InnerJoinTuple<Out, Out2>)>>
+    where
+        Key: DataKey,
+        OperatorChain2: Operator<Out = Out2> + 'static,
+        Keyer1: Fn(&Out) -> Key + KeyerFn<Key, Out>,
+        Keyer2: Fn(&Out2) -> Key + KeyerFn<Key, Out2>,
+    {
+        KeyedStream(
+            self.join_with(small, keyer1, keyer2)
+                .ship_broadcast_right()
+                .local_hash()
+                .inner(),
+        )
+    }
+
     /// Given two streams, start building a join operator.
     ///
     /// The returned type allows you to customize the behaviour of the join. You can select which
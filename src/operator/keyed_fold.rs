@@ -1,12 +1,17 @@
 use core::iter::Iterator;
-use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 use crate::block::{BlockStructure, OperatorStructure};
+use crate::checkpoint::{OperatorState, ReplicaCheckpoint};
 
 use crate::operator::{Operator, StreamElement, Timestamp};
 use crate::scheduler::ExecutionMetadata;
+use crate::state::StateMap;
 use crate::stream::KeyedItem;
 
 pub struct KeyedFold<O: Send + Clone, F, Op>
@@ -18,12 +23,18 @@ where
     prev: Op,
     fold: F,
     init: O,
-    accumulators: HashMap<<Op::Out as KeyedItem>::Key, O, crate::block::GroupHasherBuilder>,
+    accumulators: StateMap<<Op::Out as KeyedItem>::Key, O>,
     timestamps: HashMap<<Op::Out as KeyedItem>::Key, Timestamp, crate::block::GroupHasherBuilder>,
     ready: Vec<StreamElement<(<Op::Out as KeyedItem>::Key, O)>>,
     max_watermark: Option<Timestamp>,
     received_end: bool,
     received_end_iter: bool,
+    /// Save the accumulators in the checkpoints, if the operator is checkpointed.
+    checkpoint: Option<OperatorState<StateMap<<Op::Out as KeyedItem>::Key, O>>>,
+    /// The checkpoints of the replica, to forward their barriers.
+    replica: Option<Arc<ReplicaCheckpoint>>,
+    /// Whether the accumulators are partial results, sent before each barrier.
+    partial: bool,
 }
 
 impl<O: Send + Clone, F: Clone, Op: Clone> Clone for KeyedFold<O, F, Op>
@@ -43,6 +54,9 @@ where
             max_watermark: self.max_watermark,
             received_end: self.received_end,
             received_end_iter: self.received_end_iter,
+            checkpoint: self.checkpoint.clone(),
+            replica: self.replica.clone(),
+            partial: self.partial,
         }
     }
 }
@@ -82,25 +96,63 @@ where
             max_watermark: None,
             received_end: false,
             received_end_iter: false,
+            checkpoint: None,
+            replica: None,
+            partial: false,
         }
     }
 
+    /// Save the accumulators in the checkpoints of the job, and store them in the configured
+    /// state backend.
+    pub(super) fn checkpointed(mut self) -> Self
+    where
+        <Op::Out as KeyedItem>::Key: Serialize + DeserializeOwned,
+        O: Serialize + DeserializeOwned,
+    {
+        self.checkpoint = Some(OperatorState::keyed());
+        self.accumulators = self.accumulators.persistent();
+        self
+    }
+
+    /// The accumulators are partial results, merged by a following fold: instead of saving them in
+    /// the checkpoints, they are sent before each barrier, so they can be merged by any replica
+    /// when the job is restored. They are stored in the configured state backend.
+    pub(super) fn partial(mut self) -> Self
+    where
+        <Op::Out as KeyedItem>::Key: Serialize + DeserializeOwned,
+        O: Serialize + DeserializeOwned,
+    {
+        self.partial = true;
+        self.accumulators = self.accumulators.persistent();
+        self
+    }
+
+    /// Move all the accumulators to the ready elements.
+    fn flush(&mut self) {
+        if self.accumulators.is_empty() {
+            return;
+        }
+        // take a reference to move into the closure, avoiding moving "self"
+        let timestamps = &mut self.timestamps;
+        self.ready
+            .extend(self.accumulators.drain().map(|(key, value)| {
+                if let Some(ts) = timestamps.remove(&key) {
+                    StreamElement::Timestamped((key, value), ts)
+                } else {
+                    StreamElement::Item((key, value))
+                }
+            }));
+    }
+
     /// Process a new item, folding it with the accumulator inside the hashmap.
     fn process_item(
         &mut self,
         key: <Op::Out as KeyedItem>::Key,
         value: <Op::Out as KeyedItem>::Value,
     ) {
-        match self.accumulators.entry(key) {
-            Entry::Vacant(entry) => {
-                let mut acc = self.init.clone();
-                (self.fold)(&mut acc, value);
-                entry.insert(acc);
-            }
-            Entry::Occupied(mut entry) => {
-                (self.fold)(entry.get_mut(), value);
-            }
-        }
+        let init = &self.init;
+        self.accumulators
+            .update(key, || init.clone(), |acc| (self.fold)(acc, value));
     }
 }
 
@@ -114,10 +166,23 @@ where
 
     fn setup(&mut self, metadata: &mut ExecutionMetadata) {
         self.prev.setup(metadata);
+        self.accumulators.setup(metadata);
+        self.replica = metadata.checkpoint.clone();
+        if let Some(checkpoint) = self.checkpoint.as_mut() {
+            checkpoint.setup(metadata);
+            if let Some(mut accumulators) = checkpoint.restore() {
+                self.accumulators.extend(accumulators.drain());
+            }
+        }
     }
 
     #[inline]
     fn next(&mut self) -> StreamElement<Self::Out> {
+        // the partial results sent before a barrier
+        if let Some(elem) = self.ready.pop() {
+            return elem;
+        }
+
         while !self.received_end {
             match self.prev.next() {
                 StreamElement::Terminate => self.received_end = true,
@@ -140,24 +205,31 @@ where
                         .and_modify(|entry| *entry = (*entry).max(ts))
                         .or_insert(ts);
                 }
-                // this block won't sent anything until the stream ends
-                StreamElement::FlushBatch => {}
+                // this block won't sent anything until the stream ends, but the barrier of a
+                // checkpoint has to go through
+                StreamElement::FlushBatch => {
+                    if self
+                        .replica
+                        .as_ref()
+                        .is_some_and(|c| c.in_progress().is_some())
+                    {
+                        if let Some(checkpoint) = self.checkpoint.as_ref() {
+                            checkpoint.snapshot(&self.accumulators);
+                        }
+                        if self.partial {
+                            // the ready elements are popped from the back
+                            self.ready.push(StreamElement::FlushBatch);
+                            self.flush();
+                            return self.ready.pop().unwrap();
+                        }
+                        return StreamElement::FlushBatch;
+                    }
+                }
             }
         }
 
         // move all the accumulators into a faster vec
-        if !self.accumulators.is_empty() {
-            // take a reference to move into the closure, avoiding moving "self"
-            let timestamps = &mut self.timestamps;
-            self.ready
-                .extend(self.accumulators.drain().map(|(key, value)| {
-                    if let Some(ts) = timestamps.remove(&key) {
-                        StreamElement::Timestamped((key, value), ts)
-                    } else {
-                        StreamElement::Item((key, value))
-                    }
-                }));
-        }
+        self.flush();
 
         // consume the ready items
         if let Some(elem) = self.ready.pop() {
@@ -179,9 +251,9 @@ where
     }
 
     fn structure(&self) -> BlockStructure {
-        self.prev
-            .structure()
-            .add_operator(OperatorStructure::new::<Self::Out, _>("KeyedFold"))
+        let mut operator = OperatorStructure::new::<Self::Out, _>("KeyedFold");
+        operator.unsaved_state = self.checkpoint.is_none() && !self.partial;
+        self.prev.structure().add_operator(operator)
     }
 }
 
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::block::{BlockStructure, OperatorStructure};
+use crate::operator::{DataKey, Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::state::StateMap;
+
+/// A single value kept for each key of a [`KeyedStream`](crate::KeyedStream).
+///
+/// See [`KeyedStream::rich_map_with_state`](crate::KeyedStream::rich_map_with_state).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueState<T>(Option<T>);
+
+impl<T> Default for ValueState<T> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<T> ValueState<T> {
+    /// The current value, if it has been set.
+    pub fn get(&self) -> Option<&T> {
+        self.0.as_ref()
+    }
+
+    /// Replace the current value, returning the previous one.
+    pub fn set(&mut self, value: T) -> Option<T> {
+        self.0.replace(value)
+    }
+
+    /// The current value, setting it with `init` if it has not been set yet.
+    pub fn get_or_insert_with(&mut self, init: impl FnOnce() -> T) -> &mut T {
+        self.0.get_or_insert_with(init)
+    }
+
+    /// Remove the current value, returning it.
+    pub fn take(&mut self) -> Option<T> {
+        self.0.take()
+    }
+
+    /// Remove the current value.
+    pub fn clear(&mut self) {
+        self.0 = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+}
+
+/// A list of values kept for each key of a [`KeyedStream`](crate::KeyedStream).
+///
+/// See [`KeyedStream::rich_map_with_state`](crate::KeyedStream::rich_map_with_state).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListState<T>(Vec<T>);
+
+impl<T> Default for ListState<T> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<T> ListState<T> {
+    /// Append a value to the list.
+    pub fn add(&mut self, value: T) {
+        self.0.push(value);
+    }
+
+    /// The values in the list, in insertion order.
+    pub fn get(&self) -> &[T] {
+        &self.0
+    }
+
+    /// Replace all the values in the list.
+    pub fn update(&mut self, values: Vec<T>) {
+        self.0 = values;
+    }
+
+    /// Remove the values that do not satisfy the predicate.
+    pub fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+        self.0.retain(f);
+    }
+
+    /// Remove all the values, returning them.
+    pub fn take(&mut self) -> Vec<T> {
+        std::mem::take(&mut self.0)
+    }
+
+    /// Remove all the values.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A map of values kept for each key of a [`KeyedStream`](crate::KeyedStream).
+///
+/// See [`KeyedStream::rich_map_with_state`](crate::KeyedStream::rich_map_with_state).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapState<MK: Hash + Eq, MV>(HashMap<MK, MV>);
+
+impl<MK: Hash + Eq, MV> Default for MapState<MK, MV> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+impl<MK: Hash + Eq, MV> MapState<MK, MV> {
+    /// The value associated with `key`, if any.
+    pub fn get(&self, key: &MK) -> Option<&MV> {
+        self.0.get(key)
+    }
+
+    /// The value associated with `key`, if any, for updating it.
+    pub fn get_mut(&mut self, key: &MK) -> Option<&mut MV> {
+        self.0.get_mut(key)
+    }
+
+    /// Associate `value` with `key`, returning the previous value.
+    pub fn put(&mut self, key: MK, value: MV) -> Option<MV> {
+        self.0.insert(key, value)
+    }
+
+    /// The value associated with `key`, inserting it with `init` if missing.
+    pub fn get_or_insert_with(&mut self, key: MK, init: impl FnOnce() -> MV) -> &mut MV {
+        self.0.entry(key).or_insert_with(init)
+    }
+
+    /// Remove the value associated with `key`, returning it.
+    pub fn remove(&mut self, key: &MK) -> Option<MV> {
+        self.0.remove(key)
+    }
+
+    pub fn contains(&self, key: &MK) -> bool {
+        self.0.contains_key(key)
+    }
+
+    /// Iterate over the entries of the map, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&MK, &MV)> {
+        self.0.iter()
+    }
+
+    /// Remove the entries that do not satisfy the predicate.
+    pub fn retain(&mut self, f: impl FnMut(&MK, &mut MV) -> bool) {
+        self.0.retain(f);
+    }
+
+    /// Remove all the entries.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct RichMapState<K, I, O, S, F, OperatorChain>
+where
+    F: FnMut((&K, I), &mut S) -> O + Clone + Send,
+    OperatorChain: Operator<Out = (K, I)>,
+{
+    prev: OperatorChain,
+    states: StateMap<K, S>,
+    f: F,
+    _i: PhantomData<I>,
+    _o: PhantomData<O>,
+}
+
+impl<K: DataKey, I, O, S: Clone, F: Clone, OperatorChain: Clone> Clone
+    for RichMapState<K, I, O, S, F, OperatorChain>
+where
+    F: FnMut((&K, I), &mut S) -> O + Clone + Send,
+    OperatorChain: Operator<Out = (K, I)>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            prev: self.prev.clone(),
+            states: self.states.clone(),
+            f: self.f.clone(),
+            _i: self._i,
+            _o: self._o,
+        }
+    }
+}
+
+impl<K: DataKey, I: Send, O: Send, S, F, OperatorChain> Display
+    for RichMapState<K, I, O, S, F, OperatorChain>
+where
+    F: FnMut((&K, I), &mut S) -> O + Clone + Send,
+    OperatorChain: Operator<Out = (K, I)>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> RichMapState<{} -> {}, {}>",
+            self.prev,
+            std::any::type_name::<I>(),
+            std::any::type_name::<O>(),
+            std::any::type_name::<S>()
+        )
+    }
+}
+
+impl<K: DataKey, I: Send, O: Send, S, F, OperatorChain> RichMapState<K, I, O, S, F, OperatorChain>
+where
+    F: FnMut((&K, I), &mut S) -> O + Clone + Send,
+    OperatorChain: Operator<Out = (K, I)>,
+{
+    pub(super) fn new(prev: OperatorChain, f: F) -> Self
+    where
+        S: Serialize + DeserializeOwned,
+    {
+        Self {
+            prev,
+            states: StateMap::default().persistent(),
+            f,
+            _i: Default::default(),
+            _o: Default::default(),
+        }
+    }
+}
+
+impl<K, I, O, S, F, OperatorChain> Operator for RichMapState<K, I, O, S, F, OperatorChain>
+where
+    K: DataKey,
+    I: Send,
+    O: Send,
+    S: Default + Clone + Send + Serialize + DeserializeOwned,
+    F: FnMut((&K, I), &mut S) -> O + Clone + Send,
+    OperatorChain: Operator<Out = (K, I)>,
+{
+    type Out = (K, O);
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        self.states.setup(metadata);
+    }
+
+    #[inline]
+    fn next(&mut self) -> StreamElement<(K, O)> {
+        self.prev.next().map(|(key, value)| {
+            let f = &mut self.f;
+            let new_value = self
+                .states
+                .update(key.clone(), S::default, |state| f((&key, value), state));
+            (key, new_value)
+        })
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<O, _>("RichMapState");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
+    }
+}
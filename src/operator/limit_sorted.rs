@@ -1,8 +1,10 @@
 use std::cmp::Ordering;
 use std::fmt::Display;
+use std::sync::Arc;
 use std::vec::IntoIter;
 
 use crate::block::{BlockStructure, OperatorStructure};
+use crate::checkpoint::ReplicaCheckpoint;
 use crate::operator::{Operator, StreamElement, Timestamp};
 use crate::scheduler::ExecutionMetadata;
 
@@ -34,6 +36,8 @@ where
     timestamp: Option<Timestamp>,
     max_watermark: Option<Timestamp>,
     received_end: bool,
+    /// The checkpoints of the replica, to forward their barriers.
+    replica: Option<Arc<ReplicaCheckpoint>>,
 }
 
 impl<F: Clone, Op: Clone> Clone for LimitSorted<F, Op>
@@ -51,6 +55,7 @@ where
             timestamp: Default::default(),
             max_watermark: Default::default(),
             received_end: Default::default(),
+            replica: self.replica.clone(),
         }
     }
 }
@@ -87,6 +92,7 @@ where
             timestamp: None,
             max_watermark: None,
             received_end: false,
+            replica: None,
         }
     }
 }
@@ -99,6 +105,7 @@ where
 
     fn setup(&mut self, metadata: &mut ExecutionMetadata) {
         self.prev.setup(metadata);
+        self.replica = metadata.checkpoint.clone();
     }
 
     #[inline]
@@ -124,8 +131,17 @@ where
                             self.timestamp = Some(self.timestamp.unwrap_or(ts).max(ts));
                             buf.push(item);
                         }
-                        // this block wont sent anything until the stream ends
-                        StreamElement::FlushBatch => {}
+                        // this block wont sent anything until the stream ends, but the barrier of
+                        // a checkpoint has to go through
+                        StreamElement::FlushBatch => {
+                            if self
+                                .replica
+                                .as_ref()
+                                .is_some_and(|c| c.in_progress().is_some())
+                            {
+                                return StreamElement::FlushBatch;
+                            }
+                        }
                     }
                 }
                 State::Sorting(items) => {
@@ -174,8 +190,8 @@ where
     }
 
     fn structure(&self) -> BlockStructure {
-        self.prev
-            .structure()
-            .add_operator(OperatorStructure::new::<Op::Out, _>("LimitSorted"))
+        let mut operator = OperatorStructure::new::<Op::Out, _>("LimitSorted");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
     }
 }
@@ -0,0 +1,307 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+use futures::Future;
+use quick_cache::unsync::Cache;
+
+use crate::block::{BlockStructure, OperatorStructure};
+use crate::operator::{Data, DataKey, Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+
+/// Options for [`Stream::lookup_join`](crate::Stream::lookup_join) and
+/// [`Stream::lookup_left_join`](crate::Stream::lookup_left_join).
+#[derive(Clone, Debug)]
+pub struct LookupJoinOptions {
+    capacity: usize,
+    ttl: Option<Duration>,
+    batch_size: usize,
+    cache_misses: bool,
+}
+
+impl Default for LookupJoinOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000,
+            ttl: None,
+            batch_size: 128,
+            cache_misses: false,
+        }
+    }
+}
+
+impl LookupJoinOptions {
+    /// Maximum number of keys kept in the cache of each replica, by default 10000. The least
+    /// recently used keys are evicted first. With a capacity of 0 nothing is cached.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// How long a cached value stays valid, by default forever.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Maximum number of elements whose keys are looked up together, by default 128.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(
+            batch_size > 0,
+            "the batch size of a lookup join must be positive"
+        );
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Whether to remember the keys that were not found in the store, by default `false`, so
+    /// they are looked up again every time.
+    pub fn cache_misses(mut self, cache_misses: bool) -> Self {
+        self.cache_misses = cache_misses;
+        self
+    }
+}
+
+pub struct LookupJoin<K, V, Fk, F, Fut, Op>
+where
+    Op: Operator,
+    Fk: Fn(&Op::Out) -> K + Clone + Send,
+    F: Fn(Vec<K>) -> Fut + Clone + Send,
+    Fut: Future<Output = HashMap<K, V>> + Send,
+{
+    prev: Op,
+    key_fn: Fk,
+    lookup: F,
+    options: LookupJoinOptions,
+    /// Values found in the store (or misses, if cached), with the time they were looked up.
+    cache: Option<Cache<K, (Option<V>, Instant)>>,
+    /// Elements waiting for the lookup of their keys.
+    pending: Vec<StreamElement<Op::Out>>,
+    pending_items: usize,
+    buffer: VecDeque<StreamElement<(Op::Out, Option<V>)>>,
+    rt: Option<tokio::runtime::Handle>,
+    _fut: PhantomData<fn() -> Fut>,
+}
+
+impl<K, V, Fk, F, Fut, Op> LookupJoin<K, V, Fk, F, Fut, Op>
+where
+    K: DataKey,
+    V: Data,
+    Op: Operator,
+    Fk: Fn(&Op::Out) -> K + Clone + Send,
+    F: Fn(Vec<K>) -> Fut + Clone + Send,
+    Fut: Future<Output = HashMap<K, V>> + Send,
+{
+    pub(super) fn new(prev: Op, key_fn: Fk, lookup: F, options: LookupJoinOptions) -> Self {
+        Self {
+            prev,
+            key_fn,
+            lookup,
+            options,
+            cache: None,
+            pending: Vec::new(),
+            pending_items: 0,
+            buffer: Default::default(),
+            rt: None,
+            _fut: PhantomData,
+        }
+    }
+
+    /// Get the value of a key from the cache, if it is still valid.
+    fn cached(&self, key: &K) -> Option<Option<V>> {
+        let (value, time) = self.cache.as_ref()?.get(key)?;
+        match self.options.ttl {
+            Some(ttl) if time.elapsed() > ttl => None,
+            _ => Some(value.clone()),
+        }
+    }
+
+    /// Look up the keys of the pending elements that are not cached, and move the elements to the
+    /// output buffer.
+    fn resolve(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        self.pending_items = 0;
+
+        let mut resolved: HashMap<K, Option<V>> = HashMap::new();
+        let mut missing = HashSet::new();
+        let keyed = pending
+            .into_iter()
+            .map(|el| {
+                el.map(|item| {
+                    let key = (self.key_fn)(&item);
+                    if !resolved.contains_key(&key) && !missing.contains(&key) {
+                        match self.cached(&key) {
+                            Some(value) => {
+                                resolved.insert(key.clone(), value);
+                            }
+                            None => {
+                                missing.insert(key.clone());
+                            }
+                        }
+                    }
+                    (key, item)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if !missing.is_empty() {
+            let keys = missing.into_iter().collect::<Vec<_>>();
+            let rt = self.rt.as_ref().expect("LookupJoin was not set up");
+            let mut found = rt.block_on((self.lookup)(keys.clone()));
+            let now = Instant::now();
+            for key in keys {
+                let value = found.remove(&key);
+                if let Some(cache) = self.cache.as_mut() {
+                    if value.is_some() || self.options.cache_misses {
+                        cache.insert(key.clone(), (value.clone(), now));
+                    }
+                }
+                resolved.insert(key, value);
+            }
+        }
+
+        self.buffer.extend(keyed.into_iter().map(|el| {
+            el.map(|(key, item)| {
+                let value = resolved.get(&key).cloned().flatten();
+                (item, value)
+            })
+        }));
+    }
+}
+
+impl<K, V, Fk, F, Fut, Op> Clone for LookupJoin<K, V, Fk, F, Fut, Op>
+where
+    K: DataKey,
+    V: Data,
+    Op: Operator,
+    Fk: Fn(&Op::Out) -> K + Clone + Send,
+    F: Fn(Vec<K>) -> Fut + Clone + Send,
+    Fut: Future<Output = HashMap<K, V>> + Send,
+{
+    fn clone(&self) -> Self {
+        // each replica has its own cache
+        Self::new(
+            self.prev.clone(),
+            self.key_fn.clone(),
+            self.lookup.clone(),
+            self.options.clone(),
+        )
+    }
+}
+
+impl<K, V, Fk, F, Fut, Op> Display for LookupJoin<K, V, Fk, F, Fut, Op>
+where
+    Op: Operator,
+    Fk: Fn(&Op::Out) -> K + Clone + Send,
+    F: Fn(Vec<K>) -> Fut + Clone + Send,
+    Fut: Future<Output = HashMap<K, V>> + Send,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> LookupJoin<{}, {}>",
+            self.prev,
+            std::any::type_name::<K>(),
+            std::any::type_name::<V>()
+        )
+    }
+}
+
+impl<K, V, Fk, F, Fut, Op> Operator for LookupJoin<K, V, Fk, F, Fut, Op>
+where
+    K: DataKey,
+    V: Data,
+    Op: Operator,
+    Fk: Fn(&Op::Out) -> K + Clone + Send,
+    F: Fn(Vec<K>) -> Fut + Clone + Send,
+    Fut: Future<Output = HashMap<K, V>> + Send,
+{
+    type Out = (Op::Out, Option<V>);
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        self.rt = Some(tokio::runtime::Handle::current());
+        if self.options.capacity > 0 {
+            self.cache = Some(Cache::new(self.options.capacity));
+        }
+    }
+
+    fn next(&mut self) -> StreamElement<Self::Out> {
+        loop {
+            if let Some(el) = self.buffer.pop_front() {
+                return el;
+            }
+            let el = self.prev.next();
+            let is_item = matches!(el, StreamElement::Item(_) | StreamElement::Timestamped(..));
+            self.pending.push(el);
+            if is_item {
+                self.pending_items += 1;
+                if self.pending_items < self.options.batch_size {
+                    continue;
+                }
+            }
+            // the batch is full, or a control element must be forwarded without delay
+            self.resolve();
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        self.prev
+            .structure()
+            .add_operator(OperatorStructure::new::<Self::Out, _>("LookupJoin"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+    use crate::operator::LookupJoinOptions;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn lookup_join_cached() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let keys = Arc::new(AtomicUsize::new(0));
+        let env = StreamContext::new(RuntimeConfig::local(1).unwrap());
+        let res = env
+            .stream_iter((0..100u32).map(|n| n % 10))
+            .lookup_left_join(
+                |n| *n,
+                {
+                    let lookups = lookups.clone();
+                    let keys = keys.clone();
+                    move |batch: Vec<u32>| {
+                        lookups.fetch_add(1, Ordering::SeqCst);
+                        keys.fetch_add(batch.len(), Ordering::SeqCst);
+                        async move {
+                            batch
+                                .into_iter()
+                                .filter(|k| k % 2 == 0)
+                                .map(|k| (k, k * 100))
+                                .collect::<HashMap<_, _>>()
+                        }
+                    }
+                },
+                LookupJoinOptions::default()
+                    .batch_size(20)
+                    .cache_misses(true),
+            )
+            .collect_vec();
+        env.execute().await;
+
+        let res = res.get().unwrap();
+        let expected = (0..100u32)
+            .map(|n| n % 10)
+            .map(|n| (n, (n % 2 == 0).then_some(n * 100)))
+            .collect::<Vec<_>>();
+        assert_eq!(res, expected);
+        // every key is looked up only once, including the missing ones
+        assert_eq!(keys.load(Ordering::SeqCst), 10);
+        assert!(lookups.load(Ordering::SeqCst) <= 10);
+    }
+}
@@ -11,6 +11,9 @@ use crate::operator::{Data, Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
 use crate::BatchMode;
 
+/// Default number of futures evaluated at the same time by each replica of `map_async`.
+pub(super) const MAP_ASYNC_CONCURRENCY: usize = 4;
+
 #[derive(Debug, Clone)]
 pub(super) struct Batcher<T> {
     mode: BatchMode,
@@ -36,6 +39,10 @@ impl<T> Batcher<T> {
             | BatchMode::Timed {
                 max_size: n,
                 interval: max_delay,
+            }
+            | BatchMode::Controlled {
+                max_size: n,
+                latency_target: max_delay,
             } => {
                 self.buffer.push(message);
                 let timeout_elapsed = self.last_send.elapsed() > max_delay.into();
@@ -88,6 +95,8 @@ where
     flushing: bool,
     pending: usize,
     f: F,
+    concurrency: usize,
+    ordered: bool,
     i_tx: Sender<Vec<StreamElement<Op::Out>>>,
     o_rx: Receiver<Vec<StreamElement<O>>>,
 }
@@ -97,11 +106,17 @@ where
     F: Fn(Op::Out) -> Fut + Send + Sync + 'static,
     Fut: Future<Output = O> + Send,
     Op: Operator,
+    Fut: 'static,
     Op::Out: 'static,
     F: Clone,
 {
     fn clone(&self) -> Self {
-        Self::new(self.prev.clone(), self.f.clone(), 4)
+        Self::new(
+            self.prev.clone(),
+            self.f.clone(),
+            self.concurrency,
+            self.ordered,
+        )
     }
 }
 
@@ -122,14 +137,70 @@ where
     }
 }
 
+/// Evaluate the futures of the items of a batch, running up to `concurrency` of them at the same
+/// time.
+///
+/// If `ordered` is false the items are emitted as soon as they are ready, but never across the
+/// other elements of the batch (watermarks and flushes), which keep their position.
+async fn map_batch<I, O, F, Fut>(
+    batch: Vec<StreamElement<I>>,
+    f: &Arc<F>,
+    concurrency: usize,
+    ordered: bool,
+) -> Vec<StreamElement<O>>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+    F: Fn(I) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = O> + Send + 'static,
+{
+    let spawn = |el: StreamElement<I>| {
+        let f = f.clone();
+        tokio::spawn(async move { el.map_async(f.as_ref()).await })
+    };
+    if ordered {
+        return futures::stream::iter(batch)
+            .map(spawn)
+            .buffered(concurrency)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+    }
+
+    let mut out = Vec::with_capacity(batch.len());
+    let mut batch = batch.into_iter().peekable();
+    while batch.peek().is_some() {
+        let items: Vec<_> = std::iter::from_fn(|| {
+            batch
+                .next_if(|el| matches!(el, StreamElement::Item(_) | StreamElement::Timestamped(..)))
+        })
+        .collect();
+        let mut mapped = futures::stream::iter(items)
+            .map(spawn)
+            .buffer_unordered(concurrency)
+            .map(Result::unwrap);
+        while let Some(el) = mapped.next().await {
+            out.push(el);
+        }
+        if let Some(el) = batch.next() {
+            out.push(el.map(|_| unreachable!("items are mapped above")));
+        }
+    }
+    out
+}
+
 impl<O: Send + 'static, F, Fut, Op> MapAsync<O, F, Fut, Op>
 where
     F: Fn(Op::Out) -> Fut + Send + Sync + 'static + Clone,
-    Fut: Future<Output = O> + Send,
+    Fut: Future<Output = O> + Send + 'static,
     Op: Operator,
     Op::Out: 'static,
 {
-    pub(super) fn new(prev: Op, f: F, buffer: usize) -> Self {
+    pub(super) fn new(prev: Op, f: F, concurrency: usize, ordered: bool) -> Self {
+        assert!(
+            concurrency > 0,
+            "the concurrency of map_async must be positive"
+        );
         const CH: usize = 2;
         let (i_tx, i_rx) = flume::bounded::<Vec<StreamElement<Op::Out>>>(CH);
         let (o_tx, o_rx) = flume::bounded::<Vec<StreamElement<O>>>(CH);
@@ -137,16 +208,7 @@ where
         let ff = Arc::new(f.clone());
         tokio::spawn(async move {
             while let Ok(b) = i_rx.recv_async().await {
-                let v: Vec<_> = futures::stream::iter(b.into_iter())
-                    .map(|el| {
-                        let ff = ff.clone();
-                        tokio::spawn(async move { el.map_async(ff.as_ref()).await })
-                    })
-                    .buffered(buffer)
-                    .map(Result::unwrap)
-                    .collect()
-                    .await;
-
+                let v = map_batch(b, &ff, concurrency, ordered).await;
                 o_tx.send_async(v).await.unwrap();
             }
         });
@@ -155,6 +217,8 @@ where
             prev,
             batcher: Default::default(),
             f,
+            concurrency,
+            ordered,
             flushing: false,
             pending: 0,
             buffer: Default::default(),
@@ -188,7 +252,7 @@ where
 impl<O: Data, F, Fut, Op> Operator for MapAsync<O, F, Fut, Op>
 where
     F: Fn(Op::Out) -> Fut + Send + Sync + 'static + Clone,
-    Fut: Future<Output = O> + Send,
+    Fut: Future<Output = O> + Send + 'static,
     Op: Operator,
     Op::Out: 'static,
 {
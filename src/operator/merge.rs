@@ -72,3 +72,79 @@ where
         left.merge(right)
     }
 }
+
+/// Merge streams of different types into a single stream of an enum, with a variant for each
+/// stream.
+///
+/// The macro has two forms. The first one defines the enum: each variant wraps the type of the
+/// items of a stream. The enum derives `Clone`, `Serialize` and `Deserialize` (so the crate
+/// using the macro must depend on `serde`), together with the attributes given to it.
+///
+/// The second form takes the name of the enum and the stream of each variant, and merges them
+/// into a stream of the enum, wrapping each item in its variant. Not every variant needs a
+/// stream.
+///
+/// **Note**: the order of the resulting items is not specified.
+///
+/// **Note**: this operator will split the current block.
+///
+/// ## Example
+///
+/// ```
+/// # use renoir::{StreamContext, RuntimeConfig};
+/// # let mut env = StreamContext::new_local();
+/// use renoir::merge_variants;
+///
+/// merge_variants! {
+///     #[derive(Debug, PartialEq)]
+///     enum Event {
+///         Click(u32),
+///         View(String),
+///     }
+/// }
+///
+/// let clicks = env.stream_iter(0..2u32);
+/// let views = env.stream_iter(vec!["home".to_string()].into_iter());
+/// let res = merge_variants!(Event { Click: clicks, View: views }).collect_vec();
+///
+/// env.execute_blocking();
+///
+/// let res = res.get().unwrap();
+/// assert_eq!(res.len(), 3);
+/// assert!(res.contains(&Event::View("home".to_string())));
+/// ```
+#[macro_export]
+macro_rules! merge_variants {
+    (@merge [$($name:ident)::+] $acc:expr ;) => {
+        $acc
+    };
+    (@merge [$($name:ident)::+] $acc:expr ; $variant:ident: $stream:expr $(, $rest:ident: $rest_stream:expr)*) => {
+        $crate::merge_variants!(
+            @merge [$($name)::+] $acc.merge($stream.map($($name)::+::$variant)) ;
+            $($rest: $rest_stream),*
+        )
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($variant:ident($ty:ty)),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, ::serde::Serialize, ::serde::Deserialize)]
+        $vis enum $name {
+            $($variant($ty)),+
+        }
+    };
+    (
+        $($name:ident)::+ {
+            $first_variant:ident: $first:expr
+            $(, $variant:ident: $stream:expr)* $(,)?
+        }
+    ) => {
+        $crate::merge_variants!(
+            @merge [$($name)::+] $first.map($($name)::+::$first_variant) ;
+            $($variant: $stream),*
+        )
+    };
+}
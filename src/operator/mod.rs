@@ -4,30 +4,52 @@
 //! [`KeyedStream`], [`crate::WindowedStream`]
 
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::Display;
 use std::hash::Hash;
 use std::ops::{AddAssign, Div};
+use std::time::Duration;
 
 use cache::{CacheRegistry, CacheSink, CachedStream, Cacher, VecCacher};
-use flume::{unbounded, Receiver};
+use flume::{bounded, unbounded, Receiver};
 #[cfg(feature = "tokio")]
 use futures::Future;
 use limit_sorted::LimitSorted;
+use nanorand::{tls_rng, Rng};
 use serde::{Deserialize, Serialize};
 
 pub(crate) use start::*;
 
+pub use aggregate::AggregateFn;
+pub use anomaly::{AnomalyDetector, SeasonalZScore, ZScore};
+pub use distinct::DistinctOptions;
+pub use hyperloglog::HyperLogLog;
+pub use keyed_state::{ListState, MapState, ValueState};
+#[cfg(feature = "tokio")]
+pub use lookup_join::LookupJoinOptions;
+#[cfg(feature = "timestamp")]
+pub use pattern::Pattern;
+pub use process::{ProcessContext, ProcessFunction, Timer};
+pub use retry::RetryPolicy;
+pub use rich_function::{RichFilterFunction, RichMapFunction};
 pub use rich_map_custom::ElementGenerator;
+pub use tdigest::TDigest;
+#[cfg(feature = "timestamp")]
+pub use watermark_generator::{BoundedOutOfOrderness, WatermarkGenerator};
+#[cfg(feature = "timestamp")]
+pub use watermark_strategy::WatermarkStrategy;
 
 use crate::block::{group_by_hash, BlockStructure, GroupHasherBuilder, NextStrategy, Replication};
+use crate::config::ChainingStrategy;
 use crate::scheduler::ExecutionMetadata;
 
 use crate::stream::KeyedItem;
 use crate::{BatchMode, KeyedStream, Stream};
 
 #[cfg(feature = "tokio")]
-use self::map_async::MapAsync;
+use self::lookup_join::LookupJoin;
+#[cfg(feature = "tokio")]
+use self::map_async::{MapAsync, MAP_ASYNC_CONCURRENCY};
 use self::map_memo::MapMemo;
 use self::sink::collect::Collect;
 use self::sink::collect_channel::CollectChannelSink;
@@ -39,8 +61,14 @@ use self::sink::{StreamOutput, StreamOutputRef};
 use self::{
     add_timestamps::{AddTimestamp, DropTimestamp},
     interval_join::IntervalJoin,
+    watermark_generator::ApplyWatermarkGenerator,
+    watermark_strategy::ApplyWatermarkStrategy,
 };
 use self::{
+    chaining::Chain,
+    debounce::Debounce,
+    dedup_approx::DedupApprox,
+    distinct::Distinct,
     end::End,
     filter::Filter,
     filter_map::FilterMap,
@@ -48,49 +76,84 @@ use self::{
     flatten::{Flatten, KeyedFlatten},
     fold::Fold,
     inspect::Inspect,
+    instrumented::Instrumented,
     key_by::KeyBy,
     keyed_fold::KeyedFold,
+    keyed_state::RichMapState,
     map::Map,
     merge::MergeElement,
+    process::Process,
     reorder::Reorder,
+    rich_function::{FilterAsMap, RichMapInit},
     rich_map::RichMap,
     rich_map_custom::RichMapCustom,
     route::RouterBuilder,
-    zip::Zip,
+    sample::Reservoir,
+    sort::{MergePartitions, RangePartition, SortPartitions},
+    top_k::TopK,
+    zip::{Zip, ZipMismatch},
 };
 
 #[cfg(feature = "timestamp")]
 mod add_timestamps;
+mod aggregate;
+mod anomaly;
+#[cfg(feature = "timestamp")]
+mod asof_join;
 mod batch_mode;
 pub mod boxed;
 pub mod cache;
+mod chaining;
+mod composite_key;
+mod debounce;
+mod dedup_approx;
+mod distinct;
 pub(crate) mod end;
+mod enumerate;
 mod filter;
 mod filter_map;
 mod flat_map;
 mod flatten;
 mod fold;
+mod hyperloglog;
 mod inspect;
+pub(crate) mod instrumented;
 #[cfg(feature = "timestamp")]
 mod interval_join;
 pub mod iteration;
 pub mod join;
 mod key_by;
 mod keyed_fold;
+mod keyed_state;
 mod limit_sorted;
+#[cfg(feature = "tokio")]
+mod lookup_join;
 mod map;
 #[cfg(feature = "tokio")]
 mod map_async;
 mod map_memo;
 mod merge;
+#[cfg(feature = "timestamp")]
+mod pattern;
+mod process;
 mod reorder;
 mod replication;
+mod retry;
+mod rich_function;
 mod rich_map;
 mod rich_map_custom;
 mod route;
+mod sample;
 pub mod sink;
+mod sort;
 pub mod source;
 mod start;
+mod tdigest;
+mod top_k;
+#[cfg(feature = "timestamp")]
+mod watermark_generator;
+#[cfg(feature = "timestamp")]
+mod watermark_strategy;
 pub mod window;
 mod zip;
 
@@ -329,7 +392,7 @@ where
         self,
         timestamp_gen: F,
         watermark_gen: G,
-    ) -> Stream<AddTimestamp<F, G, Op>>
+    ) -> Stream<Instrumented<AddTimestamp<F, G, Op>>>
     where
         F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
         G: FnMut(&Op::Out, &Timestamp) -> Option<Timestamp> + Clone + Send + 'static,
@@ -338,9 +401,114 @@ where
     }
 
     #[cfg(feature = "timestamp")]
-    pub fn drop_timestamps(self) -> Stream<DropTimestamp<Op>> {
+    pub fn drop_timestamps(self) -> Stream<Instrumented<DropTimestamp<Op>>> {
         self.add_operator(|prev| DropTimestamp::new(prev))
     }
+
+    /// Tag each item with a timestamp and generate the watermarks following a
+    /// [`WatermarkStrategy`].
+    ///
+    /// This is a higher level alternative to [`Stream::add_timestamps`] that covers the common
+    /// cases of streams with bounded out-of-orderness and replicas that may become idle.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::WatermarkStrategy;
+    /// # use std::time::Duration;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..10i64);
+    /// s.watermark_strategy(
+    ///     WatermarkStrategy::bounded_out_of_orderness(|n: &i64| *n, 5)
+    ///         .with_idleness(Duration::from_secs(10)),
+    /// );
+    /// ```
+    #[cfg(feature = "timestamp")]
+    pub fn watermark_strategy<F>(
+        self,
+        strategy: WatermarkStrategy<F>,
+    ) -> Stream<Instrumented<ApplyWatermarkStrategy<F, Op>>>
+    where
+        F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+    {
+        self.add_operator(|prev| ApplyWatermarkStrategy::new(prev, strategy))
+    }
+
+    /// Tag each item with a timestamp and generate the watermarks with a
+    /// [`WatermarkGenerator`].
+    ///
+    /// Unlike [`Stream::watermark_strategy`], the watermarks can depend on the content of the
+    /// items: for example they can be extracted from special marker records in the data. The
+    /// markers are kept in the stream, and can be filtered out afterwards.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::window::EventTimeWindow;
+    /// # let mut env = StreamContext::new_local();
+    /// // (timestamp, value), the value `None` marks the end of a batch
+    /// let s = env.stream_iter(vec![(1, Some(1)), (3, Some(2)), (3, None), (6, Some(3))].into_iter());
+    /// let res = s
+    ///     .watermark_generator(
+    ///         |&(ts, _)| ts,
+    ///         |&(_, value): &(i64, Option<i32>), ts| value.is_none().then_some(ts + 1),
+    ///     )
+    ///     .filter_map(|(_, value)| value)
+    ///     .window_all(EventTimeWindow::tumbling(4))
+    ///     .sum::<i32>()
+    ///     .drop_key()
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![1 + 2, 3]);
+    /// ```
+    #[cfg(feature = "timestamp")]
+    pub fn watermark_generator<F, W>(
+        self,
+        timestamp_fn: F,
+        generator: W,
+    ) -> Stream<Instrumented<ApplyWatermarkGenerator<F, W, Op>>>
+    where
+        F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+        W: WatermarkGenerator<Op::Out>,
+    {
+        self.add_operator(|prev| ApplyWatermarkGenerator::new(prev, timestamp_fn, generator))
+    }
+
+    /// Ignore in the watermarks of this block the previous replicas that are idle for `timeout`.
+    ///
+    /// The watermark of a block is the minimum of the watermarks of all the replicas that send
+    /// data to it, so a single replica that stops receiving data stalls the event time of all
+    /// the operators downstream. With this option, a replica that has not sent anything for
+    /// `timeout` is excluded from the minimum until it sends something again. The watermark never
+    /// moves backwards: the elements of a replica that resumes behind it may be late.
+    ///
+    /// This applies to the current block, that is from the last operator that split the block
+    /// (e.g. [`Stream::shuffle`] or [`Stream::group_by`]) to the next one.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use std::time::Duration;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env
+    ///     .stream_par_iter(0..100i64)
+    ///     .add_timestamps(|&n| n, |_, &ts| Some(ts))
+    ///     .shuffle()
+    ///     .watermark_idleness(Duration::from_secs(10));
+    /// ```
+    #[cfg(feature = "timestamp")]
+    pub fn watermark_idleness(mut self, timeout: Duration) -> Self {
+        self.block.watermark_idle_timeout = Some(timeout);
+        self
+    }
+
     /// Change the batch mode for this stream.
     ///
     /// This change will be propagated to all the operators following, even of the next blocks,
@@ -412,6 +580,36 @@ where
         self.add_operator(|prev| Filter::new(prev, predicate))
     }
 
+    /// Keep each element of the stream with probability `p`, independently from the others.
+    ///
+    /// This is a cheap way to get an approximate view of a large stream, for example for
+    /// estimating statistics or for debugging. See [`Stream::reservoir_sample`] for a sample
+    /// with a fixed size.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..1000);
+    /// let res = s.sample(0.1).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// // about 100 elements
+    /// let res = res.get().unwrap();
+    /// assert!(res.len() < 1000);
+    /// ```
+    pub fn sample(self, p: f64) -> Stream<impl Operator<Out = Op::Out>> {
+        assert!(
+            (0.0..=1.0).contains(&p),
+            "the probability of sampling must be between 0 and 1"
+        );
+        let threshold = (p * u64::MAX as f64) as u64;
+        self.filter(move |_| p >= 1.0 || tls_rng().generate::<u64>() < threshold)
+    }
+
     /// Reorder timestamped items
     ///
     /// # Example
@@ -530,6 +728,178 @@ where
             .drop_key()
     }
 
+    /// Map the elements of the stream using a [`RichMapFunction`] built separately for each
+    /// replica.
+    ///
+    /// The `factory` is called once per replica when the job starts, with the
+    /// [`ExecutionMetadata`] of the replica; then [`RichMapFunction::open`] is called before the
+    /// first element and [`RichMapFunction::close`] after the last one. This is the place to
+    /// initialize per-replica resources, like connections or file handles, that cannot be
+    /// cloned or sent between threads.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::RichMapFunction;
+    /// # let mut env = StreamContext::new_local();
+    /// struct Tagger {
+    ///     replica: u64,
+    ///     seen: usize,
+    /// }
+    ///
+    /// impl RichMapFunction<i32> for Tagger {
+    ///     type Out = (u64, i32);
+    ///
+    ///     fn map(&mut self, item: i32) -> (u64, i32) {
+    ///         self.seen += 1;
+    ///         (self.replica, item)
+    ///     }
+    ///
+    ///     fn close(&mut self) {
+    ///         println!("replica {} mapped {} items", self.replica, self.seen);
+    ///     }
+    /// }
+    ///
+    /// let res = env
+    ///     .stream_iter(0..5)
+    ///     .rich_map_init(|metadata| Tagger { replica: metadata.global_id as u64, seen: 0 })
+    ///     .collect_vec();
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![(0, 0), (0, 1), (0, 2), (0, 3), (0, 4)]);
+    /// ```
+    pub fn rich_map_init<M, Factory>(self, factory: Factory) -> Stream<impl Operator<Out = M::Out>>
+    where
+        M: RichMapFunction<Op::Out> + 'static,
+        M::Out: 'static,
+        Factory: Fn(&ExecutionMetadata) -> M + Clone + Send + 'static,
+    {
+        self.add_operator(|prev| RichMapInit::new(prev, factory))
+    }
+
+    /// Remove from the stream the elements rejected by a [`RichFilterFunction`] built separately
+    /// for each replica.
+    ///
+    /// This is like [`Stream::rich_map_init`], see it for when the `factory` and the hooks of the
+    /// function are called.
+    pub fn rich_filter_init<M, Factory>(
+        self,
+        factory: Factory,
+    ) -> Stream<impl Operator<Out = Op::Out>>
+    where
+        Op::Out: Data,
+        M: RichFilterFunction<Op::Out> + 'static,
+        Factory: Fn(&ExecutionMetadata) -> M + Clone + Send + 'static,
+    {
+        self.add_operator(|prev| {
+            RichMapInit::new(prev, move |metadata: &ExecutionMetadata| {
+                FilterAsMap(factory(metadata))
+            })
+        })
+        .filter_map(|x| x)
+    }
+
+    /// Split the stream in two streams of different types with a single pass over the data.
+    ///
+    /// Each element is mapped with `f`: the `Ok` values go to the first stream (the main output)
+    /// and the `Err` values go to the second stream (the side output). This is useful for keeping
+    /// the elements that failed a step, like parse errors, without running the upstream pipeline
+    /// twice. More outputs can be obtained by splitting one of the resulting streams again.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # let mut env = StreamContext::new_local();
+    /// let (numbers, errors) = env
+    ///     .stream_iter(vec!["1", "x", "3", "y"].into_iter())
+    ///     .split_by(|s| s.parse::<i32>().map_err(|_| s.to_string()));
+    /// let numbers = numbers.collect_vec();
+    /// let errors = errors.collect_vec();
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(numbers.get().unwrap(), vec![1, 3]);
+    /// assert_eq!(errors.get().unwrap(), vec!["x".to_string(), "y".to_string()]);
+    /// ```
+    pub fn split_by<O, E, F>(
+        self,
+        f: F,
+    ) -> (
+        Stream<impl Operator<Out = O>>,
+        Stream<impl Operator<Out = E>>,
+    )
+    where
+        F: Fn(Op::Out) -> Result<O, E> + Send + Clone + 'static,
+        O: ExchangeData,
+        E: ExchangeData,
+    {
+        let mut routes = self
+            .map(f)
+            .route()
+            .add_route(|r| r.is_ok())
+            .add_route(|r| r.is_err())
+            .build_inner();
+        let side = routes.pop().unwrap().map(|r| match r {
+            Err(e) => e,
+            Ok(_) => unreachable!("only the errors are routed to the side output"),
+        });
+        let main = routes.pop().unwrap().map(|r| match r {
+            Ok(o) => o,
+            Err(_) => unreachable!("only the values are routed to the main output"),
+        });
+        (main, side)
+    }
+
+    /// Map the elements of the stream with a fallible function, retrying the failed elements
+    /// according to `policy` and sending the elements that still fail to a dead-letter stream.
+    ///
+    /// The first stream contains the results of the successful calls, the second one contains
+    /// the elements for which all the attempts failed, together with the last error, so that a
+    /// failure does not stop the job. The worker sleeps between the attempts, so long backoffs
+    /// delay the following elements.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::RetryPolicy;
+    /// # let mut env = StreamContext::new_local();
+    /// let policy = RetryPolicy::exponential(2, Duration::from_millis(1));
+    /// let (ok, failed) = env
+    ///     .stream_iter(["1", "x", "3"].into_iter().map(String::from))
+    ///     .map_fallible(|s| s.parse::<i32>().map_err(|e| e.to_string()), policy);
+    /// let ok = ok.collect_vec();
+    /// let failed = failed.collect_vec();
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(ok.get().unwrap(), vec![1, 3]);
+    /// let failed = failed.get().unwrap();
+    /// assert_eq!(failed.len(), 1);
+    /// assert_eq!(failed[0].0, "x");
+    /// ```
+    pub fn map_fallible<O, E, F>(
+        self,
+        f: F,
+        policy: RetryPolicy,
+    ) -> (
+        Stream<impl Operator<Out = O>>,
+        Stream<impl Operator<Out = (Op::Out, E)>>,
+    )
+    where
+        F: Fn(&Op::Out) -> Result<O, E> + Send + Clone + 'static,
+        Op::Out: ExchangeData,
+        O: ExchangeData,
+        E: ExchangeData,
+    {
+        self.split_by(move |item| policy.retry(|| f(&item)).map_err(|e| (item, e)))
+    }
+
     /// Map the elements of the stream into new elements.
     ///
     /// **Note**: this is very similar to [`Iteartor::map`](std::iter::Iterator::map).
@@ -554,6 +924,93 @@ where
         self.add_operator(|prev| Map::new(prev, f))
     }
 
+    /// Enrich each element of the stream with a value looked up in an external store, like a
+    /// database or a web service.
+    ///
+    /// The keys of the elements are obtained with `key_fn`, and the keys that are not cached are
+    /// passed in batches to `lookup`, whose future returns the values that were found. Each
+    /// replica keeps its own cache of the looked up values, configured with
+    /// [`LookupJoinOptions`].
+    ///
+    /// The elements whose key is not found are paired with `None`; to drop them use
+    /// [`Stream::lookup_join`] instead.
+    ///
+    /// **Note**: the elements are kept in order, but the batch is not sent downstream until all
+    /// its keys have been looked up.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::LookupJoinOptions;
+    /// # use std::collections::HashMap;
+    /// # tokio::runtime::Runtime::new()
+    /// #    .unwrap()
+    /// #    .block_on(base());
+    /// # async fn base() {
+    /// #    let mut env = StreamContext::new_local();
+    /// let res = env
+    ///     .stream_iter(0..5u32)
+    ///     .lookup_left_join(
+    ///         |n| *n,
+    ///         |keys: Vec<u32>| async move {
+    ///             // query the store
+    ///             keys.into_iter()
+    ///                 .filter(|k| k % 2 == 0)
+    ///                 .map(|k| (k, k * 10))
+    ///                 .collect::<HashMap<_, _>>()
+    ///         },
+    ///         LookupJoinOptions::default(),
+    ///     )
+    ///     .collect_vec();
+    /// env.execute().await;
+    /// assert_eq!(
+    ///     res.get().unwrap(),
+    ///     vec![(0, Some(0)), (1, None), (2, Some(20)), (3, None), (4, Some(40))]
+    /// );
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn lookup_left_join<K, V, Fk, F, Fut>(
+        self,
+        key_fn: Fk,
+        lookup: F,
+        options: LookupJoinOptions,
+    ) -> Stream<impl Operator<Out = (Op::Out, Option<V>)>>
+    where
+        Fk: Fn(&Op::Out) -> K + Clone + Send + 'static,
+        F: Fn(Vec<K>) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = HashMap<K, V>> + Send + 'static,
+        K: DataKey,
+        V: Data,
+    {
+        self.add_operator(|prev| LookupJoin::new(prev, key_fn, lookup, options))
+    }
+
+    /// Enrich each element of the stream with a value looked up in an external store, dropping
+    /// the elements whose key is not found.
+    ///
+    /// This is exactly like [`Stream::lookup_left_join`], but only the elements that found a
+    /// value are kept.
+    #[cfg(feature = "tokio")]
+    pub fn lookup_join<K, V, Fk, F, Fut>(
+        self,
+        key_fn: Fk,
+        lookup: F,
+        options: LookupJoinOptions,
+    ) -> Stream<impl Operator<Out = (Op::Out, V)>>
+    where
+        Op::Out: Data,
+        Fk: Fn(&Op::Out) -> K + Clone + Send + 'static,
+        F: Fn(Vec<K>) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = HashMap<K, V>> + Send + 'static,
+        K: DataKey,
+        V: Data,
+    {
+        self.lookup_left_join(key_fn, lookup, options)
+            .filter_map(|(item, value)| value.map(|value| (item, value)))
+    }
+
     /// Map the elements of the stream into new elements by evaluating a future for each one.
     /// Use memoization to cache outputs for previously seen inputs.
     ///
@@ -620,7 +1077,8 @@ where
                             .unwrap()
                     }
                 },
-                4,
+                MAP_ASYNC_CONCURRENCY,
+                true,
             )
         })
     }
@@ -649,7 +1107,82 @@ where
         F: Fn(Op::Out) -> Fut + Send + Sync + 'static + Clone,
         Fut: futures::Future<Output = O> + Send + 'static,
     {
-        self.add_operator(|prev| MapAsync::new(prev, f, 4))
+        self.add_operator(|prev| MapAsync::new(prev, f, MAP_ASYNC_CONCURRENCY, true))
+    }
+
+    /// Map the elements of the stream into new elements by evaluating a future for each one,
+    /// running up to `concurrency` futures at the same time in each replica.
+    ///
+    /// The output keeps the order of the input. See [`Stream::map_async_unordered`] to emit the
+    /// items as soon as they are ready.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # tokio::runtime::Runtime::new()
+    /// #    .unwrap()
+    /// #    .block_on(base());
+    /// # async fn base() {
+    /// #    let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..10);
+    /// // e.g. enrich the items with an http call
+    /// let res = s
+    ///     .map_async_concurrent(|n| async move { n * 10 }, 64)
+    ///     .collect_vec();
+    /// env.execute().await;
+    /// assert_eq!(res.get().unwrap(), (0..10).map(|n| n * 10).collect::<Vec<_>>());
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn map_async_concurrent<O: Data, F, Fut>(
+        self,
+        f: F,
+        concurrency: usize,
+    ) -> Stream<impl Operator<Out = O>>
+    where
+        F: Fn(Op::Out) -> Fut + Send + Sync + 'static + Clone,
+        Fut: futures::Future<Output = O> + Send + 'static,
+    {
+        self.add_operator(|prev| MapAsync::new(prev, f, concurrency, true))
+    }
+
+    /// Map the elements of the stream into new elements by evaluating a future for each one,
+    /// running up to `concurrency` futures at the same time in each replica.
+    ///
+    /// The items are emitted as soon as their future completes, so a slow item does not delay
+    /// the following ones. Items are never reordered across watermarks.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # tokio::runtime::Runtime::new()
+    /// #    .unwrap()
+    /// #    .block_on(base());
+    /// # async fn base() {
+    /// #    let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..10);
+    /// let res = s
+    ///     .map_async_unordered(|n| async move { n * 10 }, 64)
+    ///     .collect_vec();
+    /// env.execute().await;
+    /// let mut res = res.get().unwrap();
+    /// res.sort();
+    /// assert_eq!(res, (0..10).map(|n| n * 10).collect::<Vec<_>>());
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn map_async_unordered<O: Data, F, Fut>(
+        self,
+        f: F,
+        concurrency: usize,
+    ) -> Stream<impl Operator<Out = O>>
+    where
+        F: Fn(Op::Out) -> Fut + Send + Sync + 'static + Clone,
+        Fut: futures::Future<Output = O> + Send + 'static,
+    {
+        self.add_operator(|prev| MapAsync::new(prev, f, concurrency, false))
     }
 
     /// Map the elements of the stream into new elements. Use memoization
@@ -773,9 +1306,9 @@ where
         G: Fn(&mut O, O) + Send + Clone + 'static,
         O: ExchangeData,
     {
-        self.add_operator(|prev| Fold::new(prev, init.clone(), local))
+        self.add_operator(|prev| Fold::new(prev, init.clone(), local).partial())
             .replication(Replication::One)
-            .add_operator(|prev| Fold::new(prev, init, global))
+            .add_operator(|prev| Fold::new(prev, init, global).checkpointed())
     }
 
     /// Perform the folding operation separately for each key.
@@ -843,11 +1376,11 @@ where
             // key_by with given keyer
             .add_operator(|prev| KeyBy::new(prev, keyer.clone()))
             // local fold
-            .add_operator(|prev| KeyedFold::new(prev, init.clone(), local))
+            .add_operator(|prev| KeyedFold::new(prev, init.clone(), local).partial())
             // group by key
             .split_block(End::new, next_strategy)
             // global fold
-            .add_operator(|prev| KeyedFold::new(prev, init.clone(), global));
+            .add_operator(|prev| KeyedFold::new(prev, init.clone(), global).checkpointed());
 
         KeyedStream(new_stream)
     }
@@ -1217,6 +1750,9 @@ where
 
     /// Sort the items in the stream using the provided comparison function.
     ///
+    /// Each replica sorts its own items, use [`Stream::sorted_global_by`] for a total order
+    /// across all the replicas.
+    ///
     /// **Note**: This is a blocking operator an will retain items until the end
     /// of the stream or a restart.
     ///
@@ -1239,7 +1775,7 @@ where
     ///
     /// assert_eq!(res.get().unwrap(), (0..20).collect::<Vec<_>>());
     /// ```
-    pub fn sorted_by<F>(self, compare: F) -> Stream<LimitSorted<F, Op>>
+    pub fn sorted_by<F>(self, compare: F) -> Stream<Instrumented<LimitSorted<F, Op>>>
     where
         F: Fn(&Op::Out, &Op::Out) -> std::cmp::Ordering + Clone + Send,
     {
@@ -1276,7 +1812,8 @@ where
         self,
         limit: usize,
         offset: Option<usize>,
-    ) -> Stream<LimitSorted<impl Fn(&Op::Out, &Op::Out) -> Ordering + Clone + Send, Op>> {
+    ) -> Stream<Instrumented<LimitSorted<impl Fn(&Op::Out, &Op::Out) -> Ordering + Clone + Send, Op>>>
+    {
         use std::cmp::Ordering;
 
         self.add_operator(|prev| {
@@ -1318,7 +1855,7 @@ where
         compare: F,
         limit: usize,
         offset: Option<usize>,
-    ) -> Stream<LimitSorted<F, Op>>
+    ) -> Stream<Instrumented<LimitSorted<F, Op>>>
     where
         F: Fn(&Op::Out, &Op::Out) -> std::cmp::Ordering + Clone + Send,
     {
@@ -1331,10 +1868,19 @@ where
     I: ExchangeData,
     Op: Operator<Out = I> + 'static,
 {
-    /// Duplicate each element of the stream and forward it to all the replicas of the next block.
+    /// Sort all the items in the stream using the provided comparison function.
     ///
-    /// **Note**: this will duplicate the elements of the stream, this is potentially a very
-    /// expensive operation.
+    /// Unlike [`Stream::sorted_by`], the output is totally ordered even when the stream is
+    /// processed by many replicas. The items are partitioned by range, using a random sample of
+    /// the stream to choose the ranges, each partition is sorted by a different replica and the
+    /// sorted partitions are concatenated in order.
+    ///
+    /// The SQL `ORDER BY` clause is compiled to this operator.
+    ///
+    /// **Note**: This is a blocking operator an will retain items until the end
+    /// of the stream or a restart.
+    ///
+    /// **Note**: the timestamps of the items are discarded.
     ///
     /// **Note**: this operator will split the current block.
     ///
@@ -1343,13 +1889,378 @@ where
     /// ```
     /// # use renoir::{StreamContext, RuntimeConfig};
     /// # use renoir::operator::source::IteratorSource;
-    /// # let mut env = StreamContext::new_local();
-    /// let s = env.stream_iter(0..10);
-    /// s.broadcast();
-    /// ```
-    pub fn broadcast(self) -> Stream<impl Operator<Out = Op::Out>> {
-        self.split_block(End::new, NextStrategy::all())
-    }
+    /// # use rand::{rng, Rng};
+    /// # use rand::seq::SliceRandom;
+    /// # let mut env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+    ///
+    /// let mut vec: Vec<_> = (0..100).collect::<Vec<_>>();
+    /// vec.shuffle(&mut rng());
+    ///
+    /// let s = env.stream_iter(vec.into_iter()).shuffle();
+    /// let res = s.sorted_global_by(|a,b| a.cmp(b)).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), (0..100).collect::<Vec<_>>());
+    /// ```
+    pub fn sorted_global_by<F>(self, compare: F) -> Stream<impl Operator<Out = I>>
+    where
+        F: Fn(&I, &I) -> Ordering + Clone + Send + 'static,
+    {
+        let mut splits = self.split(2);
+        let data = splits.pop().unwrap();
+        let samples = splits.pop().unwrap().fold_assoc(
+            (0, Vec::new()),
+            sort::sample_item,
+            sort::merge_samples,
+        );
+
+        let compare2 = compare.clone();
+        data.binary_connection(
+            samples.map(|(_, sample)| sample),
+            Start::multiple,
+            NextStrategy::only_one(),
+            NextStrategy::all(),
+        )
+        .add_operator(|prev| RangePartition::new(prev, compare))
+        .split_block(
+            End::new,
+            NextStrategy::Partition(|(partition, _): &(u64, I)| *partition, Default::default()),
+        )
+        .add_operator(|prev| SortPartitions::new(prev, compare2))
+        .repartition(Replication::One, NextStrategy::only_one())
+        .add_operator(|prev| MergePartitions::new(prev))
+    }
+
+    /// Keep only the first `k` items in the order given by the comparison function, and emit
+    /// them in that order.
+    ///
+    /// Each replica keeps its first `k` items in a bounded heap, then the partial results are
+    /// merged by a single replica. This is equivalent to `sorted_global_by(compare)` followed by taking
+    /// the first `k` items, but it does not retain the whole stream. To get the greatest items,
+    /// reverse the comparison function.
+    ///
+    /// **Note**: this operator will retain at most `k` items per replica and emit the values only
+    /// when the stream ends. Therefore this is not properly _streaming_.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..100);
+    /// let res = s.top_k(3, |a, b| b.cmp(a)).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![99, 98, 97]);
+    /// ```
+    pub fn top_k<F>(self, k: usize, compare: F) -> Stream<impl Operator<Out = I>>
+    where
+        F: Fn(&I, &I) -> Ordering + Clone + Send + 'static,
+    {
+        let compare2 = compare.clone();
+        let compare3 = compare.clone();
+        self.fold_assoc(
+            TopK::new(k),
+            move |acc, item| acc.push(item, &compare),
+            move |acc, other| acc.merge(other, &compare2),
+        )
+        .flat_map(move |acc| acc.into_sorted_vec(&compare3))
+    }
+
+    /// Draw a uniform random sample of `n` elements of the stream, emitting them when the stream
+    /// ends. If the stream has at most `n` elements, all of them are emitted.
+    ///
+    /// Each replica draws a sample of its elements with reservoir sampling, then the samples are
+    /// merged by a single replica taking into account how many elements each of them was drawn
+    /// from. The order of the emitted elements is unspecified.
+    ///
+    /// **Note**: this operator will retain at most `n` elements per replica and emit the values
+    /// only when the stream ends. Therefore this is not properly _streaming_.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..1000);
+    /// let res = s.reservoir_sample(10).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let res = res.get().unwrap();
+    /// assert_eq!(res.len(), 10);
+    /// assert!(res.iter().all(|n| *n < 1000));
+    /// ```
+    pub fn reservoir_sample(self, n: usize) -> Stream<impl Operator<Out = I>> {
+        self.fold_assoc(
+            Reservoir::new(n),
+            |reservoir, item| reservoir.push(item),
+            |reservoir, other| reservoir.merge(other),
+        )
+        .flat_map(|reservoir| reservoir.into_items())
+    }
+
+    /// Estimate the number of distinct elements of the stream, emitting the estimate when the
+    /// stream ends.
+    ///
+    /// The estimate is computed with a [`HyperLogLog`] sketch, using a fixed amount of memory
+    /// (4 KiB per replica) regardless of the number of distinct elements, with a standard error
+    /// of about 1.6%. Each replica builds the sketch of its elements, then the sketches are
+    /// merged by a single replica. For an exact count use [`Stream::distinct`] instead.
+    ///
+    /// The `APPROX_COUNT_DISTINCT` aggregate of the SQL DSL compiles to this sketch.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter((0..1000).map(|n| n % 100));
+    /// let res = s.count_distinct_approx().collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let res = res.get().unwrap();
+    /// assert!((95..=105).contains(&res[0]));
+    /// ```
+    pub fn count_distinct_approx(self) -> Stream<impl Operator<Out = usize>>
+    where
+        I: Hash,
+    {
+        self.fold_assoc(
+            HyperLogLog::default(),
+            |hll, item| hll.insert(&item),
+            |hll, other| hll.merge(&other),
+        )
+        .map(|hll| hll.estimate())
+    }
+
+    /// Estimate the quantiles `qs` (each between 0 and 1) of the stream, emitting them when the
+    /// stream ends, in the same order as `qs`.
+    ///
+    /// The estimate is computed with a [`TDigest`] sketch, using a bounded amount of memory
+    /// regardless of the length of the stream, and it is more accurate for the extreme quantiles
+    /// than for the median. Each replica builds the digest of its elements, then the digests are
+    /// merged by a single replica. Nothing is emitted if the stream is empty.
+    ///
+    /// The `APPROX_PERCENTILE` aggregate of the SQL DSL compiles to this sketch.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..1000u32);
+    /// let res = s.quantiles(&[0.0, 0.5, 1.0]).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let res = res.get().unwrap();
+    /// assert_eq!(res[0][0], 0.0);
+    /// assert!((490.0..=510.0).contains(&res[0][1]));
+    /// assert_eq!(res[0][2], 999.0);
+    /// ```
+    pub fn quantiles(self, qs: &[f64]) -> Stream<impl Operator<Out = Vec<f64>>>
+    where
+        I: Into<f64>,
+    {
+        assert!(
+            qs.iter().all(|q| (0.0..=1.0).contains(q)),
+            "quantiles must be between 0 and 1"
+        );
+        let qs = qs.to_vec();
+        self.fold_assoc(
+            TDigest::default(),
+            |digest, item| digest.insert(item.into()),
+            |digest, other| digest.merge(&other),
+        )
+        .filter_map(move |digest| digest.quantiles(&qs))
+    }
+
+    /// Deduplicate the elements of the stream, forwarding only the first occurrence of each
+    /// element.
+    ///
+    /// The stream is partitioned by the hash of the elements, and each replica remembers the
+    /// elements it has seen. On unbounded streams consider
+    /// [`Stream::distinct_by_with_options`] for limiting the memory used.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(vec![1, 2, 1, 3, 2].into_iter());
+    /// let res = s.distinct().collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![1, 2, 3]);
+    /// ```
+    pub fn distinct(self) -> Stream<impl Operator<Out = I>>
+    where
+        I: Hash + Eq,
+    {
+        self.distinct_by(|item| item.clone())
+    }
+
+    /// Deduplicate the elements of the stream, forwarding only the first element for each key
+    /// computed by `key_fn`.
+    ///
+    /// The stream is partitioned by key, and each replica remembers the keys it has seen. On
+    /// unbounded streams consider [`Stream::distinct_by_with_options`] for limiting the memory
+    /// used.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(vec![(1, 'a'), (2, 'b'), (1, 'c')].into_iter());
+    /// let res = s.distinct_by(|(id, _)| *id).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![(1, 'a'), (2, 'b')]);
+    /// ```
+    pub fn distinct_by<K, Fk>(self, key_fn: Fk) -> Stream<impl Operator<Out = I>>
+    where
+        K: DataKey,
+        Fk: Fn(&I) -> K + Send + Clone + 'static,
+    {
+        self.distinct_by_with_options(key_fn, DistinctOptions::default())
+    }
+
+    /// Like [`Stream::distinct_by`], but the keys can be forgotten after some time or when there
+    /// are too many of them, see [`DistinctOptions`]. After a key is forgotten, its next
+    /// occurrence is forwarded again.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::DistinctOptions;
+    /// # use std::time::Duration;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(vec![1, 2, 1, 3, 2].into_iter());
+    /// let options = DistinctOptions::default()
+    ///     .ttl(Duration::from_secs(60))
+    ///     .max_keys(100_000);
+    /// let res = s.distinct_by_with_options(|n| *n, options).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![1, 2, 3]);
+    /// ```
+    pub fn distinct_by_with_options<K, Fk>(
+        self,
+        key_fn: Fk,
+        options: DistinctOptions,
+    ) -> Stream<impl Operator<Out = I>>
+    where
+        K: DataKey,
+        Fk: Fn(&I) -> K + Send + Clone + 'static,
+    {
+        let key_fn2 = key_fn.clone();
+        self.split_block(
+            End::new,
+            NextStrategy::group_by(move |item: &I| group_by_hash(&key_fn2(item))),
+        )
+        .add_operator(|prev| Distinct::new(prev, key_fn, options))
+    }
+
+    /// Deduplicate the elements of the stream on a best-effort basis, using a bounded amount of
+    /// memory.
+    ///
+    /// The elements seen are remembered in partitioned Bloom filters, sized for `expected_items`
+    /// elements with a false positive rate of `fp_rate`. Unlike [`Stream::distinct`] the memory
+    /// does not grow with the number of distinct elements, so this is suitable for unbounded
+    /// streams, with two caveats:
+    ///
+    /// - a false positive drops an element that was never seen before, with probability
+    ///   `fp_rate`;
+    /// - when a filter is full a new one is started, and the full one is forgotten once the new
+    ///   one is full too: a duplicate arriving after more than `expected_items` other distinct
+    ///   elements may be forwarded again.
+    ///
+    /// The stream is partitioned by the hash of the elements, and each replica keeps filters for
+    /// its share of `expected_items`.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(vec![1, 2, 1, 3, 2, 1].into_iter());
+    /// let res = s.dedup_approx(1000, 0.001).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![1, 2, 3]);
+    /// ```
+    pub fn dedup_approx(self, expected_items: usize, fp_rate: f64) -> Stream<impl Operator<Out = I>>
+    where
+        I: Hash,
+    {
+        self.split_block(
+            End::new,
+            NextStrategy::group_by(|item: &I| group_by_hash(item)),
+        )
+        .add_operator(|prev| DedupApprox::new(prev, expected_items, fp_rate))
+    }
+
+    /// Duplicate each element of the stream and forward it to all the replicas of the next block.
+    ///
+    /// **Note**: this will duplicate the elements of the stream, this is potentially a very
+    /// expensive operation.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..10);
+    /// s.broadcast();
+    /// ```
+    pub fn broadcast(self) -> Stream<impl Operator<Out = Op::Out>> {
+        self.split_block(End::new, NextStrategy::all())
+    }
 
     /// Given a stream, make a [`KeyedStream`] partitioning the values according to a key generated
     /// by the `keyer` function provided.
@@ -1943,6 +2854,91 @@ where
         self.split_block(End::new, NextStrategy::random())
     }
 
+    /// Redistribute the messages among the replicas of the next block in turn (round-robin).
+    ///
+    /// Each replica receives the same number of messages (±1) from each sender, so the load is
+    /// balanced even when the stream is short, where [`Stream::shuffle`] may still be skewed by
+    /// chance. The order of the messages is not preserved.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..5);
+    /// let res = s.rebalance();
+    /// ```
+    pub fn rebalance(self) -> Stream<impl Operator<Out = Op::Out>> {
+        self.split_block(End::new, NextStrategy::round_robin())
+    }
+
+    /// Hint that the following operators should run in a new block, without moving the items
+    /// between the replicas.
+    ///
+    /// Each replica of the new block receives the items of the same replica of the current one, so
+    /// the operators of the two blocks run in different threads. This can isolate an expensive
+    /// operator, or increase the parallelism of a long chain of operators. The hint is ignored if
+    /// the stream is [`Stream::chained`], or if the chaining strategy of the
+    /// [`RuntimeConfig`](crate::RuntimeConfig) is [`ChainingStrategy::Always`].
+    ///
+    /// [`ChainingStrategy::Always`]: crate::config::ChainingStrategy::Always
+    ///
+    /// **Note**: this operator may split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..5);
+    /// let res = s.map(|x| x * 2).start_new_block().map(|x| x + 1);
+    /// ```
+    pub fn start_new_block(self) -> Stream<impl Operator<Out = Op::Out>> {
+        let split = match self.ctx.lock().config.chaining() {
+            ChainingStrategy::Hints => !self.block.chained,
+            ChainingStrategy::Always => false,
+            ChainingStrategy::Never => true,
+        };
+        if split {
+            let scheduling = self.block.scheduling.clone();
+            let mut new_stream = self
+                .split_block(End::new, NextStrategy::only_one())
+                .add_operator(Chain::Split);
+            new_stream.block.scheduling = scheduling;
+            new_stream
+        } else {
+            self.add_operator(Chain::Fused)
+        }
+    }
+
+    /// Keep the following operators in the current block, ignoring the
+    /// [`Stream::start_new_block`] hints until the block is split by an operator that moves the
+    /// items between the replicas (e.g. [`Stream::group_by`]).
+    ///
+    /// The hints are still followed if the chaining strategy of the
+    /// [`RuntimeConfig`](crate::RuntimeConfig) is [`ChainingStrategy::Never`].
+    ///
+    /// [`ChainingStrategy::Never`]: crate::config::ChainingStrategy::Never
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..5);
+    /// // the two maps are fused in the same block
+    /// let res = s.chained().map(|x| x * 2).start_new_block().map(|x| x + 1);
+    /// ```
+    pub fn chained(mut self) -> Self {
+        self.block.chained = true;
+        self
+    }
+
     /// Split the stream into `splits` streams, each with all the elements of the first one.
     ///
     /// This will effectively duplicate every item in the stream into the newly created streams.
@@ -1979,9 +2975,16 @@ where
     /// Given two [`Stream`]s, zip their elements together: the resulting stream will be a stream of
     /// pairs, each of which is an element from both streams respectively.
     ///
+    /// The elements are paired by position: the `i`-th element sent by each replica of the first
+    /// stream is paired with the `i`-th element sent by the replica with the same index of the
+    /// second stream, even if they arrive in a different order. When the two streams are
+    /// partitioned differently (e.g. they have a different number of replicas) the elements are
+    /// paired in order of arrival.
+    ///
     /// **Note**: all the elements after the end of one of the streams are discarded (i.e. the
     /// resulting stream will have a number of elements that is the minimum between the lengths of
-    /// the two input streams).
+    /// the two input streams). See [`Stream::zip_exact`] and [`Stream::zip_longest`] for a
+    /// different behavior.
     ///
     /// **Note**: this operator will split the current block.
     ///
@@ -2000,13 +3003,87 @@ where
     /// assert_eq!(res.get().unwrap(), vec![('A', 1), ('B', 2), ('C', 3)]);
     /// ```
     pub fn zip<I2, Op2>(self, oth: Stream<Op2>) -> Stream<impl Operator<Out = (I, I2)>>
+    where
+        Op2: Operator<Out = I2> + 'static,
+        I2: ExchangeData,
+    {
+        self.zip_inner(oth, ZipMismatch::Truncate)
+    }
+
+    /// Like [`Stream::zip`], but panics if the two streams have different lengths.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s1 = env.stream_iter((vec!['A', 'B', 'C'].into_iter()));
+    /// let s2 = env.stream_iter((vec![1, 2, 3].into_iter()));
+    /// let res = s1.zip_exact(s2).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![('A', 1), ('B', 2), ('C', 3)]);
+    /// ```
+    pub fn zip_exact<I2, Op2>(self, oth: Stream<Op2>) -> Stream<impl Operator<Out = (I, I2)>>
+    where
+        Op2: Operator<Out = I2> + 'static,
+        I2: ExchangeData,
+    {
+        self.zip_inner(oth, ZipMismatch::Panic)
+    }
+
+    /// Like [`Stream::zip`], but the elements of the longer stream are not discarded: they are
+    /// paired with `None`.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s1 = env.stream_iter((vec!['A', 'B', 'C'].into_iter()));
+    /// let s2 = env.stream_iter((vec![1, 2].into_iter()));
+    /// let res = s1.zip_longest(s2).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(
+    ///     res.get().unwrap(),
+    ///     vec![(Some('A'), Some(1)), (Some('B'), Some(2)), (Some('C'), None)]
+    /// );
+    /// ```
+    pub fn zip_longest<I2, Op2>(
+        self,
+        oth: Stream<Op2>,
+    ) -> Stream<impl Operator<Out = (Option<I>, Option<I2>)>>
+    where
+        Op2: Operator<Out = I2> + 'static,
+        I2: ExchangeData,
+    {
+        self.map(Some)
+            .zip_inner(oth.map(Some), ZipMismatch::Pad(|| None, || None))
+    }
+
+    fn zip_inner<I2, Op2>(
+        self,
+        oth: Stream<Op2>,
+        mismatch: ZipMismatch<I, I2>,
+    ) -> Stream<Zip<I, I2>>
     where
         Op2: Operator<Out = I2> + 'static,
         I2: ExchangeData,
     {
         let mut new_stream = self.binary_connection(
             oth,
-            Zip::new,
+            |id1, id2, cache1, cache2, state_lock| {
+                Zip::new(id1, id2, cache1, cache2, state_lock).with_mismatch(mismatch)
+            },
             NextStrategy::only_one(),
             NextStrategy::only_one(),
         );
@@ -2047,6 +3124,38 @@ where
             .finalize_block();
         rx
     }
+
+    /// Close the stream and send resulting items to a bounded channel on a single host.
+    ///
+    /// The items are sent as soon as they reach the sink, so they can be consumed while the job
+    /// is still running (e.g. running `execute_blocking` in another thread, or awaiting
+    /// [`Receiver::recv_async`] alongside `execute`). When the channel is full the sink waits for
+    /// the receiver, slowing down the job. If the receiver is dropped, the remaining items are
+    /// discarded.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # let mut env = StreamContext::new_local();
+    /// let rx = env.stream_iter(0..100u32).collect_channel_bounded(8);
+    ///
+    /// let job = std::thread::spawn(move || env.execute_blocking());
+    /// // stop after the first 10 items
+    /// let first: Vec<_> = rx.iter().take(10).collect();
+    /// drop(rx);
+    /// job.join().unwrap();
+    /// assert_eq!(first, (0..10u32).collect::<Vec<_>>());
+    /// ```
+    pub fn collect_channel_bounded(self, capacity: usize) -> Receiver<I> {
+        let (tx, rx) = bounded(capacity);
+        self.replication(Replication::One)
+            .add_operator(|prev| CollectChannelSink::new(prev, tx))
+            .finalize_block();
+        rx
+    }
     /// Close the stream and send resulting items to a channel on each single host.
     ///
     /// Each host sends its outputs to the channel without repartitioning.
@@ -2228,6 +3337,32 @@ where
         StreamOutput::from(output)
     }
 
+    /// Close the stream and store all the distinct items into a [`HashSet`] on a single host.
+    ///
+    /// If the stream is distributed among multiple replicas, a bottleneck is placed where all the
+    /// replicas sends the items to.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter([1, 2, 1, 3, 2].into_iter());
+    /// let res = s.collect_set();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), [1, 2, 3].into_iter().collect());
+    /// ```
+    pub fn collect_set(self) -> StreamOutput<HashSet<I>>
+    where
+        I: Hash + Eq,
+    {
+        self.collect()
+    }
+
     /// Collect the output of the stream to a [StreamCache] that can later be resumed to
     /// create a [Stream] with its content. Returns the cache and consumes the stream.
     ///
@@ -2446,30 +3581,71 @@ where
     /// use renoir::operator::Timestamp;
     /// # let mut env = StreamContext::new_local();
     ///
-    /// let s = env.stream_iter(0..10);
-    /// s
-    ///     .group_by(|i| i % 2)
-    ///     .add_timestamps(
-    ///     |&(_k, n)| n,
-    ///     |&(_k, n), &ts| if n % 2 == 0 { Some(ts) } else { None }
-    /// );
-    /// ```
+    /// let s = env.stream_iter(0..10);
+    /// s
+    ///     .group_by(|i| i % 2)
+    ///     .add_timestamps(
+    ///     |&(_k, n)| n,
+    ///     |&(_k, n), &ts| if n % 2 == 0 { Some(ts) } else { None }
+    /// );
+    /// ```
+    #[cfg(feature = "timestamp")]
+    pub fn add_timestamps<F, G>(
+        self,
+        timestamp_gen: F,
+        watermark_gen: G,
+    ) -> KeyedStream<impl Operator<Out = Op::Out>>
+    where
+        F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+        G: FnMut(&Op::Out, &Timestamp) -> Option<Timestamp> + Clone + Send + 'static,
+    {
+        self.add_operator(|prev| AddTimestamp::new(prev, timestamp_gen, watermark_gen))
+    }
+
+    #[cfg(feature = "timestamp")]
+    pub fn drop_timestamps(self) -> KeyedStream<impl Operator<Out = Op::Out>> {
+        self.add_operator(|prev| DropTimestamp::new(prev))
+    }
+
+    /// Tag each item with a timestamp and generate the watermarks following a
+    /// [`WatermarkStrategy`].
+    ///
+    /// See [`Stream::watermark_strategy`] for more details.
+    #[cfg(feature = "timestamp")]
+    pub fn watermark_strategy<F>(
+        self,
+        strategy: WatermarkStrategy<F>,
+    ) -> KeyedStream<impl Operator<Out = Op::Out>>
+    where
+        F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+    {
+        self.add_operator(|prev| ApplyWatermarkStrategy::new(prev, strategy))
+    }
+
+    /// Tag each item with a timestamp and generate the watermarks with a
+    /// [`WatermarkGenerator`].
+    ///
+    /// See [`Stream::watermark_generator`] for more details.
     #[cfg(feature = "timestamp")]
-    pub fn add_timestamps<F, G>(
+    pub fn watermark_generator<F, W>(
         self,
-        timestamp_gen: F,
-        watermark_gen: G,
+        timestamp_fn: F,
+        generator: W,
     ) -> KeyedStream<impl Operator<Out = Op::Out>>
     where
         F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
-        G: FnMut(&Op::Out, &Timestamp) -> Option<Timestamp> + Clone + Send + 'static,
+        W: WatermarkGenerator<Op::Out>,
     {
-        self.add_operator(|prev| AddTimestamp::new(prev, timestamp_gen, watermark_gen))
+        self.add_operator(|prev| ApplyWatermarkGenerator::new(prev, timestamp_fn, generator))
     }
 
+    /// Ignore in the watermarks of this block the previous replicas that are idle for `timeout`.
+    ///
+    /// See [`Stream::watermark_idleness`] for more details.
     #[cfg(feature = "timestamp")]
-    pub fn drop_timestamps(self) -> KeyedStream<impl Operator<Out = Op::Out>> {
-        self.add_operator(|prev| DropTimestamp::new(prev))
+    pub fn watermark_idleness(mut self, timeout: Duration) -> Self {
+        self.0.block.watermark_idle_timeout = Some(timeout);
+        self
     }
 
     /// Change the batch mode for this stream.
@@ -2600,6 +3776,94 @@ where
         self.add_operator(|prev| Inspect::new(prev, f))
     }
 
+    /// Process the elements of each key with a low level [`ProcessFunction`], which has access to
+    /// a state for each key and can register timers.
+    ///
+    /// Event time timers fire when a watermark reaches their timestamp, and processing time
+    /// timers fire after their deadline, allowing custom windowing, timeouts and state expiration
+    /// logic. The processing time is checked as the elements of the stream arrive. When the
+    /// stream ends all the pending event time timers fire, while the pending processing time
+    /// timers are discarded.
+    ///
+    /// The elements emitted while processing an element have its timestamp, the ones emitted by
+    /// an event time timer have the timestamp of the timer.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::{ProcessContext, ProcessFunction, Timer};
+    /// # let mut env = StreamContext::new_local();
+    /// /// Emit the number of elements of each key at the end of the stream.
+    /// #[derive(Clone)]
+    /// struct CountAtEnd;
+    ///
+    /// impl ProcessFunction<i32, i32> for CountAtEnd {
+    ///     type State = usize;
+    ///     type Out = usize;
+    ///
+    ///     fn process(&mut self, _key: &i32, _item: i32, count: &mut usize, ctx: &mut ProcessContext<usize>) {
+    ///         *count += 1;
+    ///         ctx.register_event_time_timer(i64::MAX);
+    ///     }
+    ///
+    ///     fn on_timer(&mut self, _key: &i32, _timer: Timer, count: &mut usize, ctx: &mut ProcessContext<usize>) {
+    ///         ctx.emit(*count);
+    ///     }
+    /// }
+    ///
+    /// let s = env.stream_iter(0..5).group_by(|&n| n % 2);
+    /// let res = s.process(CountAtEnd).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![(0, 3), (1, 2)]);
+    /// ```
+    pub fn process<P>(self, function: P) -> KeyedStream<impl Operator<Out = (K, P::Out)>>
+    where
+        P: ProcessFunction<K, I> + 'static,
+    {
+        self.add_operator(|prev| Process::new(prev, function))
+    }
+
+    /// Emit only the latest element of each key, after the key has not been updated for the
+    /// `quiet` period.
+    ///
+    /// This collapses bursts of updates of the same key into a single element, for example before
+    /// some expensive processing. The quiet periods are measured in processing time, and the
+    /// elements are emitted when their quiet period ends even if no other element arrives: when
+    /// the stream ends the latest element of every key is emitted immediately. The timestamps of the emitted elements are kept, but
+    /// they may be late with respect to the watermarks that have already been forwarded.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use std::time::Duration;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env
+    ///     .stream_iter(vec![('a', 1), ('b', 1), ('a', 2), ('a', 3)].into_iter())
+    ///     .group_by(|(sensor, _)| *sensor)
+    ///     .map(|(_, (_, value))| value);
+    /// let res = s.debounce(Duration::from_secs(1)).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![('a', 3), ('b', 1)]);
+    /// ```
+    pub fn debounce(self, quiet: Duration) -> KeyedStream<impl Operator<Out = (K, I)>>
+    where
+        I: Clone,
+    {
+        self.add_operator(|prev| Debounce::new(prev, quiet))
+    }
+
     /// Perform the folding operation separately for each key.
     ///
     /// Note that there is a difference between `stream.group_by(keyer).fold(...)` and
@@ -2694,6 +3958,87 @@ where
         .map(|(_, value)| value.unwrap())
     }
 
+    /// Perform the reduction operation separately for each key, starting from an accumulator
+    /// built from the key.
+    ///
+    /// The accumulator of each key is created by `init` when the first item of the key arrives,
+    /// then each item is added to it with `f`. This is useful when the identity element depends
+    /// on the key (e.g. a per-key default or configuration), and avoids using a sentinel value.
+    ///
+    /// The resulting stream will still be keyed and will contain only a single message per key (the
+    /// final result).
+    ///
+    /// **Note**: this operator will retain all the messages of the stream and emit the values only
+    /// when the stream ends. Therefore this is not properly _streaming_.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..5).group_by(|&n| n % 2);
+    /// let res = s
+    ///     .reduce_init(|&key| 100 * key, |acc, value| *acc += value)
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![(0, 0 + 2 + 4), (1, 100 + 1 + 3)]);
+    /// ```
+    pub fn reduce_init<O, Init, F>(
+        self,
+        init: Init,
+        f: F,
+    ) -> KeyedStream<impl Operator<Out = (K, O)>>
+    where
+        O: Send + Clone + 'static,
+        Init: Fn(&K) -> O + Send + Clone + 'static,
+        F: Fn(&mut O, I) + Send + Clone + 'static,
+    {
+        self.map(|(key, value)| (key.clone(), value))
+            .fold(None, move |acc, (key, value)| {
+                f(acc.get_or_insert_with(|| init(&key)), value)
+            })
+            .map(|(_, acc)| acc.unwrap())
+    }
+
+    /// Keep only the first `k` items of each key in the order given by the comparison function,
+    /// and emit them in that order.
+    ///
+    /// The items of each key are kept in a bounded heap, therefore at most `k` items per key are
+    /// retained. To get the greatest items, reverse the comparison function.
+    ///
+    /// **Note**: this operator will emit the values only when the stream ends. Therefore this is
+    /// not properly _streaming_.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..10).group_by(|&n| n % 2);
+    /// let res = s.top_k(2, |a, b| b.cmp(a)).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![(0, 6), (0, 8), (1, 7), (1, 9)]);
+    /// ```
+    pub fn top_k<F>(self, k: usize, compare: F) -> KeyedStream<impl Operator<Out = (K, I)>>
+    where
+        I: Data,
+        F: Fn(&I, &I) -> Ordering + Send + Clone + 'static,
+    {
+        let compare2 = compare.clone();
+        self.fold(TopK::new(k), move |acc, item| acc.push(item, &compare))
+            .flat_map(move |(_, acc)| acc.into_sorted_vec(&compare2))
+    }
+
     /// Map the elements of the stream into new elements.
     ///
     /// **Note**: this is very similar to [`Iteartor::map`](std::iter::Iterator::map).
@@ -2775,6 +4120,174 @@ where
             .map(|(_, x)| x.unwrap())
     }
 
+    /// Map the elements of the stream into new elements, using a state that is kept separately
+    /// for each key.
+    ///
+    /// The function receives the element together with a mutable reference to the state of its
+    /// key, which is created with [`Default`] when the key is first seen. The state is usually
+    /// built from [`ValueState`], [`ListState`] and [`MapState`] (or a tuple or struct of them),
+    /// but any serializable `Default + Clone` type can be used.
+    ///
+    /// Unlike [`KeyedStream::rich_map`], the function itself is shared by all the keys of a
+    /// replica, and only the state is per key.
+    ///
+    /// **Note**: the state is stored in the configured [state backend](crate::state) and lives
+    /// until the end of the stream.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::ValueState;
+    /// # let mut env = StreamContext::new_local();
+    /// let res = env
+    ///     .stream_iter(vec![1, 2, 3, 4, 5, 6].into_iter())
+    ///     .group_by(|&n| n % 2)
+    ///     .rich_map_with_state(|(_k, n), sum: &mut ValueState<i32>| {
+    ///         let sum = sum.get_or_insert_with(|| 0);
+    ///         *sum += n;
+    ///         *sum
+    ///     })
+    ///     .collect_vec();
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![(0, 2), (0, 6), (0, 12), (1, 1), (1, 4), (1, 9)]);
+    /// ```
+    pub fn rich_map_with_state<O, S, F>(self, f: F) -> KeyedStream<impl Operator<Out = (K, O)>>
+    where
+        F: FnMut((&K, I), &mut S) -> O + Clone + Send + 'static,
+        S: ExchangeData + Default,
+        O: Data,
+    {
+        self.add_operator(|prev| RichMapState::new(prev, f))
+    }
+
+    /// Remove from the stream all the elements for which the provided function returns `None`,
+    /// using a state that is kept separately for each key.
+    ///
+    /// This is exactly like [`KeyedStream::rich_map_with_state`], but only the elements that
+    /// returned `Some(_)` are kept.
+    ///
+    /// ## Example
+    ///
+    /// Deduplicate the events of each user:
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::MapState;
+    /// # let mut env = StreamContext::new_local();
+    /// let res = env
+    ///     .stream_iter(vec![('a', 1), ('b', 1), ('a', 1), ('a', 2), ('b', 1)].into_iter())
+    ///     .group_by(|&(user, _)| user)
+    ///     .rich_filter_map_with_state(|(_user, (_, event)), seen: &mut MapState<i32, ()>| {
+    ///         seen.put(event, ()).is_none().then_some(event)
+    ///     })
+    ///     .collect_vec();
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![('a', 1), ('a', 2), ('b', 1)]);
+    /// ```
+    pub fn rich_filter_map_with_state<O, S, F>(
+        self,
+        f: F,
+    ) -> KeyedStream<impl Operator<Out = (K, O)>>
+    where
+        F: FnMut((&K, I), &mut S) -> Option<O> + Clone + Send + 'static,
+        S: ExchangeData + Default,
+        O: Data,
+    {
+        self.rich_map_with_state(f)
+            .filter(|(_, x)| x.is_some())
+            .map(|(_, x)| x.unwrap())
+    }
+
+    /// Compute the moving average of the last `window_size` elements of each key, emitting the
+    /// updated average for every element.
+    ///
+    /// Until a key has `window_size` elements, the average is over the elements seen so far.
+    /// Each key keeps only its last `window_size` elements.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(vec![('a', 1), ('b', 10), ('a', 3), ('a', 8)].into_iter());
+    /// let res = s
+    ///     .group_by(|&(k, _)| k)
+    ///     .map(|(_, (_, v))| v)
+    ///     .moving_avg(2)
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    /// assert_eq!(res, vec![('a', 1.0), ('a', 2.0), ('a', 5.5), ('b', 10.0)]);
+    /// ```
+    pub fn moving_avg(self, window_size: usize) -> KeyedStream<impl Operator<Out = (K, f64)>>
+    where
+        I: Into<f64>,
+    {
+        assert!(window_size > 0, "moving_avg window must not be empty");
+        let mut window = VecDeque::with_capacity(window_size);
+        let mut sum = 0.0;
+        self.rich_map(move |(_, x)| {
+            let x = x.into();
+            window.push_back(x);
+            sum += x;
+            if window.len() > window_size {
+                sum -= window.pop_front().unwrap();
+            }
+            sum / window.len() as f64
+        })
+    }
+
+    /// Compute the exponentially weighted moving average of each key, emitting the updated
+    /// average for every element.
+    ///
+    /// The average of a key starts from its first element, and each following element `x`
+    /// updates it to `alpha * x + (1 - alpha) * avg`: a higher `alpha` (between 0 and 1) gives
+    /// more weight to the recent elements. Each key keeps only its current average.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(vec![4.0, 8.0, 0.0].into_iter());
+    /// let res = s.group_by(|_| ()).ewma(0.5).drop_key().collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![4.0, 6.0, 3.0]);
+    /// ```
+    pub fn ewma(self, alpha: f64) -> KeyedStream<impl Operator<Out = (K, f64)>>
+    where
+        I: Into<f64>,
+    {
+        assert!(
+            alpha > 0.0 && alpha <= 1.0,
+            "ewma alpha must be in the interval (0, 1]"
+        );
+        let mut avg: Option<f64> = None;
+        self.rich_map(move |(_, x)| {
+            let x = x.into();
+            let next = match avg {
+                Some(avg) => alpha * x + (1.0 - alpha) * avg,
+                None => x,
+            };
+            avg = Some(next);
+            next
+        })
+    }
+
     /// Map the elements of the stream into new elements. The mapping function can be stateful.
     ///
     /// This is exactly like [`Stream::rich_map`], but the function is cloned for each key. This
@@ -2859,6 +4372,47 @@ where
     K: ExchangeDataKey,
     I: ExchangeData,
 {
+    /// Change the key of the stream, partitioning the elements by the new key.
+    ///
+    /// The new key of each element is computed from its current key and its value. This is
+    /// equivalent to `stream.unkey().group_by(...)` followed by dropping the old key, but the
+    /// elements are sent through the network without the old key, and the new key is computed
+    /// only once.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let stream = env.stream_iter(0..6).group_by(|&n| n % 2);
+    /// let res = stream
+    ///     .rekey(|&parity, &n| (parity, n < 3))
+    ///     .reduce(|a, b| *a += b)
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(
+    ///     res,
+    ///     vec![((0, false), 4), ((0, true), 0 + 2), ((1, false), 3 + 5), ((1, true), 1)]
+    /// );
+    /// ```
+    pub fn rekey<K2, Fk>(self, keyer: Fk) -> KeyedStream<impl Operator<Out = (K2, I)>>
+    where
+        Fk: Fn(&K, &I) -> K2 + Send + Clone + 'static,
+        K2: ExchangeDataKey,
+    {
+        self.0
+            .map(move |(key, value)| (keyer(&key, &value), value))
+            .group_by(|(key, _)| key.clone())
+            .map(|(_, (_, value))| value)
+    }
+
     /// Given two streams **with timestamps** join them according to an interval centered around the
     /// timestamp of the left side.
     ///
@@ -2866,10 +4420,36 @@ where
     /// elements on the right with timestamp Q such that `T - lower_bound <= Q <= T + upper_bound`.
     /// Only items with the same key can be joined together.
     ///
+    /// The items of the right side are kept only until the watermark guarantees that no item of
+    /// the left side can match them anymore, so the state does not grow with the length of the
+    /// stream.
+    ///
     /// **Note**: this operator will split the current block.
     ///
     /// ## Example
-    /// TODO: example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # let mut env = StreamContext::new_local();
+    /// let clicks = env
+    ///     .stream_iter(vec![('a', 10), ('b', 12), ('a', 30)].into_iter())
+    ///     .add_timestamps(|&(_, ts)| ts, |_, &ts| Some(ts))
+    ///     .group_by(|&(user, _)| user);
+    /// let views = env
+    ///     .stream_iter(vec![('b', 2), ('a', 8), ('a', 25)].into_iter())
+    ///     .add_timestamps(|&(_, ts)| ts, |_, &ts| Some(ts))
+    ///     .group_by(|&(user, _)| user);
+    /// // join each click with the views of the same user in the 5 units of time before it
+    /// let res = clicks
+    ///     .interval_join(views, 5, 0)
+    ///     .map(|(_, ((_, click), (_, view)))| (click, view))
+    ///     .collect_vec();
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![('a', (10, 8)), ('a', (30, 25))]);
+    /// ```
     #[cfg(feature = "timestamp")]
     pub fn interval_join<I2, Op2>(
         self,
@@ -3064,6 +4644,12 @@ where
     pub fn collect_channel(self) -> Receiver<(K, I)> {
         self.unkey().collect_channel()
     }
+
+    /// Close the stream and send resulting items to a bounded channel on a single host, as soon
+    /// as they reach the sink. See [`Stream::collect_channel_bounded`].
+    pub fn collect_channel_bounded(self, capacity: usize) -> Receiver<(K, I)> {
+        self.unkey().collect_channel_bounded(capacity)
+    }
     /// Close the stream and send resulting items to a channel on each single host.
     ///
     /// Each host sends its outputs to the channel without repartitioning.
@@ -3208,6 +4794,34 @@ where
     pub fn collect_all<C: FromIterator<(K, I)> + Send + 'static>(self) -> StreamOutput<C> {
         self.unkey().collect_all()
     }
+
+    /// Close the stream and store all the resulting items into a [`HashMap`] on a single host.
+    ///
+    /// If the stream is distributed among multiple replicas, a bottleneck is placed where all the
+    /// replicas sends the items to.
+    ///
+    /// **Note**: if a key has more than one value, only one of them is kept and which one is
+    /// unspecified.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..5).group_by_count(|&n| n % 2);
+    /// let res = s.collect_map();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let res = res.get().unwrap();
+    /// assert_eq!(res[&0], 3);
+    /// assert_eq!(res[&1], 2);
+    /// ```
+    pub fn collect_map(self) -> StreamOutput<HashMap<K, I>> {
+        self.collect()
+    }
 }
 
 impl<K, I, O, It, Op> KeyedStream<Op>
@@ -0,0 +1,378 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::sync::Arc;
+
+use crate::block::{BlockStructure, GroupHasherBuilder, OperatorStructure};
+use crate::operator::{Data, DataKey, Operator, StreamElement, Timestamp};
+use crate::scheduler::ExecutionMetadata;
+use crate::KeyedStream;
+
+type Predicate<T> = Arc<dyn Fn(&T) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+struct Stage<T> {
+    predicate: Predicate<T>,
+    /// A negated stage forbids the events matching it between the previous and the next stage.
+    negated: bool,
+}
+
+/// A sequence of conditions to be matched by the events of a key, used by
+/// [`KeyedStream::pattern`].
+///
+/// A pattern starts with [`Pattern::begin`], and each [`Pattern::followed_by`] adds a condition
+/// that must be matched by a later event: the events in between that do not match it are
+/// skipped. [`Pattern::not_followed_by`] forbids the events matching a condition between the
+/// previous and the next stage, and [`Pattern::within`] bounds the time between the first and
+/// the last event of a match.
+///
+/// ## Example
+///
+/// A login failure followed by a success within 10 units of time, without a logout in between:
+///
+/// ```
+/// # use renoir::operator::Pattern;
+/// let pattern = Pattern::begin(|e: &&str| *e == "fail")
+///     .not_followed_by(|e| *e == "logout")
+///     .followed_by(|e| *e == "success")
+///     .within(10);
+/// ```
+#[derive(Clone)]
+pub struct Pattern<T> {
+    stages: Vec<Stage<T>>,
+    within: Option<Timestamp>,
+}
+
+impl<T> Pattern<T> {
+    /// Start a pattern whose first event matches `predicate`.
+    pub fn begin<F>(predicate: F) -> Self
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            stages: vec![Stage {
+                predicate: Arc::new(predicate),
+                negated: false,
+            }],
+            within: None,
+        }
+    }
+
+    /// Require a later event matching `predicate`, skipping the events that do not match it.
+    pub fn followed_by<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.stages.push(Stage {
+            predicate: Arc::new(predicate),
+            negated: false,
+        });
+        self
+    }
+
+    /// Discard the partial matches where an event matching `predicate` comes before the event of
+    /// the next stage. A pattern cannot end with a negation.
+    pub fn not_followed_by<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.stages.push(Stage {
+            predicate: Arc::new(predicate),
+            negated: true,
+        });
+        self
+    }
+
+    /// Require the whole match to happen within `within` units of time from its first event.
+    ///
+    /// The time constraint uses the timestamps of the events, so the stream must be timestamped.
+    pub fn within(mut self, within: Timestamp) -> Self {
+        self.within = Some(within);
+        self
+    }
+}
+
+/// A match in progress.
+#[derive(Clone)]
+struct PartialMatch<T> {
+    events: Vec<T>,
+    start: Option<Timestamp>,
+    /// Index of the next positive stage to match.
+    next: usize,
+}
+
+#[derive(Clone)]
+pub(crate) struct PatternMatch<K, T, Op>
+where
+    K: DataKey,
+    T: Data,
+    Op: Operator<Out = (K, T)>,
+{
+    prev: Op,
+    pattern: Pattern<T>,
+    /// Index of the positive stages of the pattern.
+    positive: Vec<usize>,
+    partial: HashMap<K, Vec<PartialMatch<T>>, GroupHasherBuilder>,
+    buffer: VecDeque<StreamElement<(K, Vec<T>)>>,
+}
+
+impl<K, T, Op> Display for PatternMatch<K, T, Op>
+where
+    K: DataKey,
+    T: Data,
+    Op: Operator<Out = (K, T)>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> Pattern<{}>",
+            self.prev,
+            std::any::type_name::<T>()
+        )
+    }
+}
+
+impl<K, T, Op> PatternMatch<K, T, Op>
+where
+    K: DataKey,
+    T: Data,
+    Op: Operator<Out = (K, T)>,
+{
+    pub(super) fn new(prev: Op, pattern: Pattern<T>) -> Self {
+        assert!(
+            !pattern.stages.last().unwrap().negated,
+            "a pattern cannot end with not_followed_by"
+        );
+        let positive = pattern
+            .stages
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| !s.negated)
+            .map(|(i, _)| i)
+            .collect();
+        Self {
+            prev,
+            pattern,
+            positive,
+            partial: Default::default(),
+            buffer: Default::default(),
+        }
+    }
+
+    fn expired(&self, start: Option<Timestamp>, ts: Timestamp) -> bool {
+        match (self.pattern.within, start) {
+            (Some(within), Some(start)) => ts - start > within,
+            _ => false,
+        }
+    }
+
+    fn emit(&mut self, key: K, events: Vec<T>, ts: Option<Timestamp>) {
+        self.buffer.push_back(match ts {
+            Some(ts) => StreamElement::Timestamped((key, events), ts),
+            None => StreamElement::Item((key, events)),
+        });
+    }
+
+    fn process(&mut self, key: K, event: T, ts: Option<Timestamp>) {
+        if self.pattern.within.is_some() && ts.is_none() {
+            panic!("Patterns with a time constraint can only handle timestamped items!");
+        }
+        let mut partial = self.partial.remove(&key).unwrap_or_default();
+        let mut completed = Vec::new();
+
+        partial.retain_mut(|m| {
+            if ts.is_some_and(|ts| self.expired(m.start, ts)) {
+                return false;
+            }
+            // the negated stages between the last matched stage and the next one
+            let stage = self.positive[m.next];
+            let from = self.positive[m.next - 1] + 1;
+            if self.pattern.stages[from..stage]
+                .iter()
+                .any(|s| (s.predicate)(&event))
+            {
+                return false;
+            }
+            if (self.pattern.stages[stage].predicate)(&event) {
+                m.events.push(event.clone());
+                m.next += 1;
+                if m.next == self.positive.len() {
+                    completed.push(std::mem::take(&mut m.events));
+                    return false;
+                }
+            }
+            true
+        });
+
+        // every event matching the first stage starts a new match
+        if (self.pattern.stages[0].predicate)(&event) {
+            if self.positive.len() == 1 {
+                completed.push(vec![event]);
+            } else {
+                partial.push(PartialMatch {
+                    events: vec![event],
+                    start: ts,
+                    next: 1,
+                });
+            }
+        }
+
+        for events in completed {
+            self.emit(key.clone(), events, ts);
+        }
+        if !partial.is_empty() {
+            self.partial.insert(key, partial);
+        }
+    }
+}
+
+impl<K, T, Op> Operator for PatternMatch<K, T, Op>
+where
+    K: DataKey,
+    T: Data,
+    Op: Operator<Out = (K, T)>,
+{
+    type Out = (K, Vec<T>);
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+    }
+
+    fn next(&mut self) -> StreamElement<Self::Out> {
+        loop {
+            if let Some(el) = self.buffer.pop_front() {
+                return el;
+            }
+            match self.prev.next() {
+                StreamElement::Item((key, event)) => self.process(key, event, None),
+                StreamElement::Timestamped((key, event), ts) => self.process(key, event, Some(ts)),
+                StreamElement::Watermark(ts) => {
+                    // the partial matches that cannot complete anymore are dropped
+                    if let Some(within) = self.pattern.within {
+                        self.partial.retain(|_, matches| {
+                            matches.retain(|m| m.start.is_none_or(|start| start + within >= ts));
+                            !matches.is_empty()
+                        });
+                    }
+                    return StreamElement::Watermark(ts);
+                }
+                StreamElement::FlushAndRestart => {
+                    self.partial.clear();
+                    return StreamElement::FlushAndRestart;
+                }
+                StreamElement::FlushBatch => return StreamElement::FlushBatch,
+                StreamElement::Terminate => return StreamElement::Terminate,
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<Self::Out, _>("Pattern");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+impl<K, I, Op> KeyedStream<Op>
+where
+    K: DataKey,
+    I: Data,
+    Op: Operator<Out = (K, I)> + 'static,
+{
+    /// Match a [`Pattern`] against the events of each key, emitting the events of each complete
+    /// match.
+    ///
+    /// Every event matching the first stage of the pattern starts a new match, and each match
+    /// advances with the first following event that matches its next stage (skipping the
+    /// others), so the matches of a key can overlap. A match is discarded when an event matching
+    /// a negated stage comes before its next stage, or when the time constraint of the pattern
+    /// expires. The partial matches are kept in memory until they complete or expire.
+    ///
+    /// The events of each key are matched in the order they arrive: use
+    /// [`KeyedStream::reorder`] before this operator if the stream is out of order.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::Pattern;
+    /// # let mut env = StreamContext::new_local();
+    /// let events = vec![
+    ///     ('a', "fail", 0),
+    ///     ('b', "fail", 1),
+    ///     ('a', "success", 3),
+    ///     ('b', "logout", 4),
+    ///     ('b', "success", 5),
+    ///     ('a', "fail", 10),
+    ///     ('a', "success", 30),
+    /// ];
+    /// let pattern = Pattern::begin(|e: &(char, String, i64)| e.1 == "fail")
+    ///     .not_followed_by(|e| e.1 == "logout")
+    ///     .followed_by(|e| e.1 == "success")
+    ///     .within(10);
+    /// let res = env
+    ///     .stream_iter(events.into_iter().map(|(user, action, ts)| (user, action.to_string(), ts)))
+    ///     .add_timestamps(|e| e.2, |_, &ts| Some(ts))
+    ///     .group_by(|e| e.0)
+    ///     .pattern(pattern)
+    ///     .map(|(_, events)| events.iter().map(|e| e.2).collect::<Vec<_>>())
+    ///     .collect_vec();
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![('a', vec![0, 3])]);
+    /// ```
+    pub fn pattern(self, pattern: Pattern<I>) -> KeyedStream<impl Operator<Out = (K, Vec<I>)>> {
+        self.add_operator(|prev| PatternMatch::new(prev, pattern))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pattern, PatternMatch};
+    use crate::operator::{Operator, StreamElement};
+    use crate::test::FakeOperator;
+
+    fn run(pattern: Pattern<(u8, i64)>, events: Vec<(u8, i64)>) -> Vec<Vec<(u8, i64)>> {
+        let mut fake = FakeOperator::empty();
+        for e in events {
+            fake.push(StreamElement::Timestamped(((), e), e.1));
+        }
+        let mut op = PatternMatch::new(fake, pattern);
+        let mut res = Vec::new();
+        loop {
+            match op.next() {
+                StreamElement::Timestamped(((), events), _) => res.push(events),
+                StreamElement::Terminate => return res,
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn pattern_followed_by() {
+        let pattern = Pattern::begin(|e: &(u8, i64)| e.0 == 1)
+            .followed_by(|e| e.0 == 2)
+            .followed_by(|e| e.0 == 3);
+        let res = run(
+            pattern,
+            vec![(1, 0), (2, 1), (9, 2), (1, 3), (3, 4), (2, 5), (3, 6)],
+        );
+        assert_eq!(
+            res,
+            vec![vec![(1, 0), (2, 1), (3, 4)], vec![(1, 3), (2, 5), (3, 6)]]
+        );
+    }
+
+    #[test]
+    fn pattern_negation_and_within() {
+        let pattern = Pattern::begin(|e: &(u8, i64)| e.0 == 1)
+            .not_followed_by(|e| e.0 == 0)
+            .followed_by(|e| e.0 == 2)
+            .within(5);
+        // the first match is broken by the negation, the second one expires
+        let res = run(
+            pattern,
+            vec![(1, 0), (0, 1), (2, 2), (1, 10), (2, 20), (1, 21), (2, 23)],
+        );
+        assert_eq!(res, vec![vec![(1, 21), (2, 23)]]);
+    }
+}
@@ -0,0 +1,522 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::time::Instant;
+
+use crate::block::{BlockStructure, OperatorStructure};
+use crate::operator::{DataKey, ExchangeData, Operator, StreamElement, Timestamp};
+use crate::scheduler::{ExecutionMetadata, WakeUp};
+use crate::state::StateMap;
+
+/// A low level function processing the elements of a [`KeyedStream`](crate::KeyedStream) one at
+/// a time, with access to a state for each key and to timers.
+///
+/// See [`KeyedStream::process`](crate::KeyedStream::process).
+pub trait ProcessFunction<K, I>: Clone + Send {
+    /// The state kept for each key, stored in the configured [state backend](crate::state).
+    type State: ExchangeData + Default;
+    /// The type of the emitted elements.
+    type Out: Send;
+
+    /// Process an element of the stream.
+    fn process(
+        &mut self,
+        key: &K,
+        item: I,
+        state: &mut Self::State,
+        ctx: &mut ProcessContext<Self::Out>,
+    );
+
+    /// Called when a timer registered for `key` fires.
+    ///
+    /// An event time timer fires when a watermark reaches its timestamp, or when the stream ends.
+    /// A processing time timer fires when its deadline passes, even if no element arrives; the
+    /// processing time timers that have not fired when the stream ends are discarded.
+    fn on_timer(
+        &mut self,
+        _key: &K,
+        _timer: Timer,
+        _state: &mut Self::State,
+        _ctx: &mut ProcessContext<Self::Out>,
+    ) {
+    }
+}
+
+/// A timer that fired, see [`ProcessFunction::on_timer`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timer {
+    /// An event time timer, with its timestamp.
+    EventTime(Timestamp),
+    /// A processing time timer, with its deadline.
+    ProcessingTime(Instant),
+}
+
+/// The interface of a [`ProcessFunction`] with the operator: it allows emitting elements and
+/// registering timers for the current key.
+#[derive(Clone, Debug)]
+pub struct ProcessContext<O> {
+    timestamp: Option<Timestamp>,
+    watermark: Option<Timestamp>,
+    output: Vec<O>,
+    event_timers: Vec<(Timestamp, bool)>,
+    processing_timers: Vec<(Instant, bool)>,
+    clear_state: bool,
+}
+
+impl<O> Default for ProcessContext<O> {
+    fn default() -> Self {
+        Self {
+            timestamp: None,
+            watermark: None,
+            output: Vec::new(),
+            event_timers: Vec::new(),
+            processing_timers: Vec::new(),
+            clear_state: false,
+        }
+    }
+}
+
+impl<O> ProcessContext<O> {
+    /// The timestamp of the element being processed, or of the event time timer that fired.
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+
+    /// The last watermark received.
+    pub fn watermark(&self) -> Option<Timestamp> {
+        self.watermark
+    }
+
+    /// Emit an element, with the timestamp of the element being processed.
+    pub fn emit(&mut self, item: O) {
+        self.output.push(item);
+    }
+
+    /// Register a timer firing when the watermark reaches `ts`. Registering the same timer twice
+    /// for a key has no effect.
+    pub fn register_event_time_timer(&mut self, ts: Timestamp) {
+        self.event_timers.push((ts, true));
+    }
+
+    /// Remove a timer registered with [`ProcessContext::register_event_time_timer`].
+    pub fn delete_event_time_timer(&mut self, ts: Timestamp) {
+        self.event_timers.push((ts, false));
+    }
+
+    /// Register a timer firing after the `deadline`. Registering the same timer twice for a key
+    /// has no effect.
+    pub fn register_processing_time_timer(&mut self, deadline: Instant) {
+        self.processing_timers.push((deadline, true));
+    }
+
+    /// Remove a timer registered with [`ProcessContext::register_processing_time_timer`].
+    pub fn delete_processing_time_timer(&mut self, deadline: Instant) {
+        self.processing_timers.push((deadline, false));
+    }
+
+    /// Remove the state of the current key once the function returns: the next element of the
+    /// key starts from the default state. The timers of the key are kept.
+    pub fn clear_state(&mut self) {
+        self.clear_state = true;
+    }
+}
+
+pub(crate) struct Process<K, I, P, Op>
+where
+    K: DataKey,
+    P: ProcessFunction<K, I>,
+    Op: Operator<Out = (K, I)>,
+{
+    prev: Op,
+    function: P,
+    states: StateMap<K, P::State>,
+    ctx: ProcessContext<P::Out>,
+    event_timers: BTreeMap<Timestamp, HashSet<K>>,
+    processing_timers: BTreeMap<Instant, HashSet<K>>,
+    /// Wakes up the replica when the first processing time timer fires.
+    wake_up: WakeUp,
+    /// The deadline of the last wake up requested.
+    requested: Option<Instant>,
+    buffer: VecDeque<StreamElement<(K, P::Out)>>,
+    _i: PhantomData<fn(I)>,
+}
+
+impl<K, I, P, Op> Process<K, I, P, Op>
+where
+    K: DataKey,
+    P: ProcessFunction<K, I>,
+    Op: Operator<Out = (K, I)>,
+{
+    pub(super) fn new(prev: Op, function: P) -> Self {
+        Self {
+            prev,
+            function,
+            states: StateMap::default().persistent(),
+            ctx: Default::default(),
+            event_timers: Default::default(),
+            processing_timers: Default::default(),
+            wake_up: Default::default(),
+            requested: None,
+            buffer: Default::default(),
+            _i: PhantomData,
+        }
+    }
+
+    /// Apply the changes requested by the function through the context.
+    fn apply_context(&mut self, key: &K) {
+        if std::mem::take(&mut self.ctx.clear_state) {
+            self.states.remove(key);
+        }
+        for (ts, register) in self.ctx.event_timers.drain(..) {
+            if register {
+                self.event_timers.entry(ts).or_default().insert(key.clone());
+            } else if let Some(keys) = self.event_timers.get_mut(&ts) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.event_timers.remove(&ts);
+                }
+            }
+        }
+        for (deadline, register) in self.ctx.processing_timers.drain(..) {
+            if register {
+                self.processing_timers
+                    .entry(deadline)
+                    .or_default()
+                    .insert(key.clone());
+            } else if let Some(keys) = self.processing_timers.get_mut(&deadline) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.processing_timers.remove(&deadline);
+                }
+            }
+        }
+        let ts = self.ctx.timestamp;
+        for item in self.ctx.output.drain(..) {
+            let item = (key.clone(), item);
+            self.buffer.push_back(match ts {
+                Some(ts) => StreamElement::Timestamped(item, ts),
+                None => StreamElement::Item(item),
+            });
+        }
+    }
+
+    fn process(&mut self, key: K, item: I, ts: Option<Timestamp>) {
+        self.ctx.timestamp = ts;
+        let (function, ctx) = (&mut self.function, &mut self.ctx);
+        self.states.update(key.clone(), Default::default, |state| {
+            function.process(&key, item, state, ctx)
+        });
+        self.apply_context(&key);
+    }
+
+    fn fire(&mut self, key: K, timer: Timer) {
+        self.ctx.timestamp = match timer {
+            Timer::EventTime(ts) => Some(ts),
+            Timer::ProcessingTime(_) => self.ctx.watermark,
+        };
+        let (function, ctx) = (&mut self.function, &mut self.ctx);
+        self.states.update(key.clone(), Default::default, |state| {
+            function.on_timer(&key, timer, state, ctx)
+        });
+        self.apply_context(&key);
+    }
+
+    /// Fire the event time timers up to `watermark`, or all of them.
+    fn fire_event_timers(&mut self, watermark: Option<Timestamp>) {
+        while let Some(entry) = self.event_timers.first_entry() {
+            if watermark.is_some_and(|w| *entry.key() > w) {
+                break;
+            }
+            let (ts, keys) = entry.remove_entry();
+            for key in keys {
+                self.fire(key, Timer::EventTime(ts));
+            }
+        }
+    }
+
+    fn fire_processing_timers(&mut self, now: Instant) {
+        while let Some(entry) = self.processing_timers.first_entry() {
+            if *entry.key() > now {
+                break;
+            }
+            let (deadline, keys) = entry.remove_entry();
+            for key in keys {
+                self.fire(key, Timer::ProcessingTime(deadline));
+            }
+        }
+    }
+
+    /// Ask to be woken up when the first processing time timer fires, if it changed.
+    fn request_wake_up(&mut self) {
+        let first = self.processing_timers.first_key_value().map(|(&at, _)| at);
+        if first != self.requested {
+            if let Some(at) = first {
+                self.wake_up.request(at);
+            }
+            self.requested = first;
+        }
+    }
+}
+
+impl<K, I, P, Op> Clone for Process<K, I, P, Op>
+where
+    K: DataKey,
+    P: ProcessFunction<K, I>,
+    Op: Operator<Out = (K, I)>,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.prev.clone(), self.function.clone())
+    }
+}
+
+impl<K, I, P, Op> Display for Process<K, I, P, Op>
+where
+    K: DataKey,
+    P: ProcessFunction<K, I>,
+    Op: Operator<Out = (K, I)>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> Process<{}>",
+            self.prev,
+            std::any::type_name::<P>()
+        )
+    }
+}
+
+impl<K, I, P, Op> Operator for Process<K, I, P, Op>
+where
+    K: DataKey,
+    P: ProcessFunction<K, I>,
+    Op: Operator<Out = (K, I)>,
+{
+    type Out = (K, P::Out);
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        self.states.setup(metadata);
+        self.wake_up = metadata.wake_up.clone();
+    }
+
+    fn next(&mut self) -> StreamElement<(K, P::Out)> {
+        loop {
+            if let Some(el) = self.buffer.pop_front() {
+                return el;
+            }
+            let el = self.prev.next();
+            // the start of the block emits a `FlushBatch` when the first timer fires
+            if !self.processing_timers.is_empty() {
+                self.fire_processing_timers(Instant::now());
+            }
+            match el {
+                StreamElement::Item((key, item)) => self.process(key, item, None),
+                StreamElement::Timestamped((key, item), ts) => self.process(key, item, Some(ts)),
+                StreamElement::Watermark(ts) => {
+                    self.ctx.watermark = Some(ts);
+                    self.fire_event_timers(Some(ts));
+                    self.buffer.push_back(StreamElement::Watermark(ts));
+                }
+                StreamElement::FlushBatch => {
+                    // the wake up may have cleared the deadline, request it again
+                    self.requested = None;
+                    self.buffer.push_back(StreamElement::FlushBatch);
+                }
+                StreamElement::FlushAndRestart | StreamElement::Terminate => {
+                    self.fire_event_timers(None);
+                    self.processing_timers.clear();
+                    if matches!(el, StreamElement::FlushAndRestart) {
+                        self.states.clear();
+                        self.ctx.watermark = None;
+                    }
+                    self.buffer.push_back(el.map(|_| unreachable!()));
+                }
+            }
+            self.request_wake_up();
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<(K, P::Out), _>("Process");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::block::BatchMode;
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+    use crate::operator::{ProcessContext, ProcessFunction, Timer};
+
+    /// Emit the number of elements of a key after a period without elements of that key.
+    #[derive(Clone)]
+    struct CountUntilIdle {
+        idle: Duration,
+        /// The deadlines are saved in the state as offsets from this instant.
+        start: Instant,
+    }
+
+    impl ProcessFunction<u32, u32> for CountUntilIdle {
+        type State = (usize, Option<Duration>);
+        type Out = usize;
+
+        fn process(
+            &mut self,
+            _key: &u32,
+            _item: u32,
+            (count, deadline): &mut Self::State,
+            ctx: &mut ProcessContext<usize>,
+        ) {
+            *count += 1;
+            if let Some(deadline) = deadline.take() {
+                ctx.delete_processing_time_timer(self.start + deadline);
+            }
+            let new_deadline = Instant::now() + self.idle;
+            ctx.register_processing_time_timer(new_deadline);
+            *deadline = Some(new_deadline - self.start);
+        }
+
+        fn on_timer(
+            &mut self,
+            _key: &u32,
+            _timer: Timer,
+            (count, deadline): &mut Self::State,
+            ctx: &mut ProcessContext<usize>,
+        ) {
+            ctx.emit(std::mem::take(count));
+            *deadline = None;
+        }
+    }
+
+    #[test]
+    fn process_processing_time_timers() {
+        let env = StreamContext::new(RuntimeConfig::local(1).unwrap());
+        let res = env
+            .stream_iter([0, 0, 0, 1, 0].into_iter())
+            .group_by(|&n| n)
+            .inspect(|(key, _)| {
+                // key 0 is idle before the element of key 1
+                if *key == 1 {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+            })
+            .process(CountUntilIdle {
+                idle: Duration::from_millis(20),
+                start: Instant::now(),
+            })
+            .collect_vec();
+        env.execute_blocking();
+
+        // the timers pending at the end of the stream are discarded
+        assert_eq!(res.get().unwrap(), vec![(0, 3)]);
+    }
+
+    /// Emit the number of elements of a key in a session that lasts `length` from its first element.
+    #[derive(Clone)]
+    struct CountPerSession {
+        length: Duration,
+    }
+
+    impl ProcessFunction<u32, u32> for CountPerSession {
+        type State = usize;
+        type Out = usize;
+
+        fn process(
+            &mut self,
+            _key: &u32,
+            _item: u32,
+            count: &mut usize,
+            ctx: &mut ProcessContext<usize>,
+        ) {
+            *count += 1;
+            if *count == 1 {
+                ctx.register_processing_time_timer(Instant::now() + self.length);
+            }
+        }
+
+        fn on_timer(
+            &mut self,
+            _key: &u32,
+            _timer: Timer,
+            count: &mut usize,
+            ctx: &mut ProcessContext<usize>,
+        ) {
+            ctx.emit(*count);
+            ctx.clear_state();
+        }
+    }
+
+    #[test]
+    fn process_processing_time_timers_while_idle() {
+        let env = StreamContext::new(RuntimeConfig::local(1).unwrap());
+        // `None` pauses the source long enough for the session to end
+        let items = [Some(0), Some(0), None, Some(0), None];
+        let res = env
+            .stream_iter(items.into_iter().filter_map(|n| {
+                if n.is_none() {
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                n
+            }))
+            .batch_mode(BatchMode::single())
+            .group_by(|&n| n)
+            .process(CountPerSession {
+                length: Duration::from_millis(20),
+            })
+            .collect_vec();
+        env.execute_blocking();
+
+        // the timers fire while no element arrives, and the state restarts after being cleared
+        assert_eq!(res.get().unwrap(), vec![(0, 2), (0, 1)]);
+    }
+
+    /// Emit the sum of the elements of a key at the end of each period of 10 time units.
+    #[derive(Clone)]
+    struct SumPerPeriod;
+
+    impl ProcessFunction<u32, i64> for SumPerPeriod {
+        type State = i64;
+        type Out = i64;
+
+        fn process(&mut self, _key: &u32, item: i64, sum: &mut i64, ctx: &mut ProcessContext<i64>) {
+            *sum += item;
+            let ts = ctx.timestamp().unwrap();
+            ctx.register_event_time_timer(ts - ts % 10 + 9);
+        }
+
+        fn on_timer(
+            &mut self,
+            _key: &u32,
+            _timer: Timer,
+            sum: &mut i64,
+            ctx: &mut ProcessContext<i64>,
+        ) {
+            ctx.emit(std::mem::take(sum));
+        }
+    }
+
+    #[cfg(feature = "timestamp")]
+    #[test]
+    fn process_event_time_timers() {
+        let env = StreamContext::new(RuntimeConfig::local(1).unwrap());
+        let res = env
+            .stream_iter(0..25i64)
+            .add_timestamps(|&n| n, |&n, &ts| (n % 5 == 4).then_some(ts))
+            .group_by(|&n| (n % 2) as u32)
+            .process(SumPerPeriod)
+            .drop_key()
+            .collect_vec();
+        env.execute_blocking();
+
+        let mut res = res.get().unwrap();
+        res.sort_unstable();
+        // even and odd numbers of [0, 10), [10, 20) and [20, 25)
+        let mut expected = vec![20, 25, 70, 75, 66, 44];
+        expected.sort_unstable();
+        assert_eq!(res, expected);
+    }
+}
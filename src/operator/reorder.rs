@@ -146,9 +146,9 @@ where
     }
 
     fn structure(&self) -> BlockStructure {
-        self.prev
-            .structure()
-            .add_operator(OperatorStructure::new::<Op::Out, _>("Reorder"))
+        let mut operator = OperatorStructure::new::<Op::Out, _>("Reorder");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
     }
 }
 
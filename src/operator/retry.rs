@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+/// How many times, and how often, a failed operation is retried.
+///
+/// The waiting time between two attempts starts from the initial backoff and is multiplied at
+/// every attempt (by 2 by default), up to the maximum backoff.
+///
+/// ## Example
+///
+/// ```
+/// # use std::time::Duration;
+/// # use renoir::operator::RetryPolicy;
+/// // 5 retries, waiting 10ms, 20ms, 40ms, 50ms and 50ms
+/// let policy = RetryPolicy::exponential(5, Duration::from_millis(10))
+///     .max_backoff(Duration::from_millis(50));
+/// ```
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_retries: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: u32,
+}
+
+impl Default for RetryPolicy {
+    /// 3 retries, waiting 100ms, 200ms and 400ms.
+    fn default() -> Self {
+        Self::exponential(3, Duration::from_millis(100))
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry: the operation is attempted once.
+    pub fn none() -> Self {
+        Self::exponential(0, Duration::ZERO)
+    }
+
+    /// Retry up to `max_retries` times, waiting `initial_backoff` before the first retry and
+    /// doubling the wait at every following one.
+    pub fn exponential(max_retries: usize, initial_backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_backoff,
+            max_backoff: Duration::MAX,
+            multiplier: 2,
+        }
+    }
+
+    /// Retry up to `max_retries` times, always waiting `backoff` between the attempts.
+    pub fn fixed(max_retries: usize, backoff: Duration) -> Self {
+        Self {
+            multiplier: 1,
+            ..Self::exponential(max_retries, backoff)
+        }
+    }
+
+    /// Bound the wait between two attempts.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Call `f` until it succeeds or the retries are exhausted, returning the last error in the
+    /// latter case. The current thread sleeps between the attempts.
+    pub(crate) fn retry<O, E>(&self, mut f: impl FnMut() -> Result<O, E>) -> Result<O, E> {
+        let mut backoff = self.initial_backoff.min(self.max_backoff);
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Ok(o) => return Ok(o),
+                Err(e) if attempt >= self.max_retries => return Err(e),
+                Err(_) => {
+                    attempt += 1;
+                    log::debug!("attempt {attempt} failed, retrying in {backoff:?}");
+                    std::thread::sleep(backoff);
+                    backoff = backoff
+                        .saturating_mul(self.multiplier)
+                        .min(self.max_backoff);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RetryPolicy;
+
+    #[test]
+    fn retry_policy() {
+        let policy = RetryPolicy::exponential(3, Duration::from_millis(1));
+
+        let mut attempts = 0;
+        let res: Result<i32, i32> = policy.retry(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(attempts)
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(res, Ok(3));
+
+        let mut attempts = 0;
+        let res: Result<i32, i32> = policy.retry(|| {
+            attempts += 1;
+            Err(attempts)
+        });
+        assert_eq!(res, Err(4));
+
+        let mut attempts = 0;
+        let res: Result<i32, i32> = RetryPolicy::none().retry(|| {
+            attempts += 1;
+            Err(attempts)
+        });
+        assert_eq!(res, Err(1));
+    }
+}
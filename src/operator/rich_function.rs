@@ -0,0 +1,231 @@
+use std::fmt::Display;
+use std::marker::PhantomData;
+
+use crate::block::{BlockStructure, OperatorStructure};
+use crate::operator::{Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+
+/// A mapping function with per-replica setup and teardown, see [`Stream::rich_map_init`].
+///
+/// [`Stream::rich_map_init`]: crate::Stream::rich_map_init
+pub trait RichMapFunction<I>: Send {
+    type Out: Send;
+
+    /// Called once per replica, before the first item.
+    fn open(&mut self) {}
+
+    /// Map an item of the stream.
+    fn map(&mut self, item: I) -> Self::Out;
+
+    /// Called once per replica, after the last item. It is also called if the replica is dropped
+    /// without reaching the end of the stream.
+    fn close(&mut self) {}
+}
+
+/// A filtering function with per-replica setup and teardown, see [`Stream::rich_filter_init`].
+///
+/// [`Stream::rich_filter_init`]: crate::Stream::rich_filter_init
+pub trait RichFilterFunction<I>: Send {
+    /// Called once per replica, before the first item.
+    fn open(&mut self) {}
+
+    /// Whether to keep an item of the stream.
+    fn filter(&mut self, item: &I) -> bool;
+
+    /// Called once per replica, after the last item. It is also called if the replica is dropped
+    /// without reaching the end of the stream.
+    fn close(&mut self) {}
+}
+
+/// Adapter for using a [`RichFilterFunction`] as a [`RichMapFunction`].
+pub(super) struct FilterAsMap<F>(pub(super) F);
+
+impl<I: Send, F: RichFilterFunction<I>> RichMapFunction<I> for FilterAsMap<F> {
+    type Out = Option<I>;
+
+    fn open(&mut self) {
+        self.0.open()
+    }
+
+    fn map(&mut self, item: I) -> Option<I> {
+        self.0.filter(&item).then_some(item)
+    }
+
+    fn close(&mut self) {
+        self.0.close()
+    }
+}
+
+pub struct RichMapInit<Op, M, Factory>
+where
+    Op: Operator,
+    M: RichMapFunction<Op::Out>,
+    Factory: Fn(&ExecutionMetadata) -> M + Clone + Send,
+{
+    prev: Op,
+    factory: Factory,
+    function: Option<M>,
+    _m: PhantomData<fn() -> M>,
+}
+
+impl<Op, M, Factory> RichMapInit<Op, M, Factory>
+where
+    Op: Operator,
+    M: RichMapFunction<Op::Out>,
+    Factory: Fn(&ExecutionMetadata) -> M + Clone + Send,
+{
+    pub(super) fn new(prev: Op, factory: Factory) -> Self {
+        Self {
+            prev,
+            factory,
+            function: None,
+            _m: PhantomData,
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(mut function) = self.function.take() {
+            function.close();
+        }
+    }
+}
+
+impl<Op, M, Factory> Clone for RichMapInit<Op, M, Factory>
+where
+    Op: Operator,
+    M: RichMapFunction<Op::Out>,
+    Factory: Fn(&ExecutionMetadata) -> M + Clone + Send,
+{
+    fn clone(&self) -> Self {
+        // every replica builds its own function during setup
+        Self::new(self.prev.clone(), self.factory.clone())
+    }
+}
+
+impl<Op, M, Factory> Display for RichMapInit<Op, M, Factory>
+where
+    Op: Operator,
+    M: RichMapFunction<Op::Out>,
+    Factory: Fn(&ExecutionMetadata) -> M + Clone + Send,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> RichMapInit<{} -> {}>",
+            self.prev,
+            std::any::type_name::<Op::Out>(),
+            std::any::type_name::<M::Out>()
+        )
+    }
+}
+
+impl<Op, M, Factory> Drop for RichMapInit<Op, M, Factory>
+where
+    Op: Operator,
+    M: RichMapFunction<Op::Out>,
+    Factory: Fn(&ExecutionMetadata) -> M + Clone + Send,
+{
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<Op, M, Factory> Operator for RichMapInit<Op, M, Factory>
+where
+    Op: Operator,
+    M: RichMapFunction<Op::Out>,
+    Factory: Fn(&ExecutionMetadata) -> M + Clone + Send,
+{
+    type Out = M::Out;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        let mut function = (self.factory)(metadata);
+        function.open();
+        self.function = Some(function);
+    }
+
+    #[inline]
+    fn next(&mut self) -> StreamElement<M::Out> {
+        let el = self.prev.next();
+        if matches!(el, StreamElement::Terminate) {
+            self.close();
+            return StreamElement::Terminate;
+        }
+        let function = self.function.as_mut().expect("RichMapInit was not set up");
+        el.map(|item| function.map(item))
+    }
+
+    fn structure(&self) -> BlockStructure {
+        self.prev
+            .structure()
+            .add_operator(OperatorStructure::new::<M::Out, _>("RichMapInit"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+    use crate::operator::{RichFilterFunction, RichMapFunction};
+
+    struct Offset {
+        offset: u64,
+        opened: bool,
+        closed: Arc<AtomicUsize>,
+    }
+
+    impl RichMapFunction<u64> for Offset {
+        type Out = u64;
+
+        fn open(&mut self) {
+            self.opened = true;
+        }
+
+        fn map(&mut self, item: u64) -> u64 {
+            assert!(self.opened);
+            item + self.offset
+        }
+
+        fn close(&mut self) {
+            self.closed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    struct Even;
+
+    impl RichFilterFunction<u64> for Even {
+        fn filter(&mut self, item: &u64) -> bool {
+            item % 2 == 0
+        }
+    }
+
+    #[test]
+    fn rich_map_init() {
+        let closed = Arc::new(AtomicUsize::new(0));
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_par_iter(0..100u64)
+            .rich_filter_init(|_| Even)
+            .rich_map_init({
+                let closed = closed.clone();
+                move |metadata| Offset {
+                    offset: 1000 * metadata.global_id,
+                    opened: false,
+                    closed: closed.clone(),
+                }
+            })
+            .collect_vec();
+        env.execute_blocking();
+
+        let res = res.get().unwrap();
+        assert_eq!(res.len(), 50);
+        let mut values = res.into_iter().map(|x| x % 1000).collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(values, (0..100).step_by(2).collect::<Vec<_>>());
+        assert_eq!(closed.load(Ordering::SeqCst), 4);
+    }
+}
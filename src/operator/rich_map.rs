@@ -102,8 +102,8 @@ where
     }
 
     fn structure(&self) -> BlockStructure {
-        self.prev
-            .structure()
-            .add_operator(OperatorStructure::new::<O, _>("RichMap"))
+        let mut operator = OperatorStructure::new::<O, _>("RichMap");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
     }
 }
@@ -92,8 +92,8 @@ where
     }
 
     fn structure(&self) -> BlockStructure {
-        self.prev
-            .structure()
-            .add_operator(OperatorStructure::new::<O, _>("RichMapCustom"))
+        let mut operator = OperatorStructure::new::<O, _>("RichMapCustom");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
     }
 }
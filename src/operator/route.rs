@@ -3,8 +3,10 @@ use crate::operator::{ExchangeData, Operator};
 use crate::stream::Stream;
 use std::collections::HashMap;
 use std::fmt::{Debug, Display};
+use std::sync::Arc;
 
 use crate::block::{BatchMode, Batcher, BlockStructure, Connection, OperatorStructure};
+use crate::checkpoint::ReplicaCheckpoint;
 use crate::network::{Coord, ReceiverEndpoint};
 use crate::operator::{KeyerFn, StreamElement};
 use crate::scheduler::{BlockId, ExecutionMetadata};
@@ -109,6 +111,8 @@ where
 
     endpoints: Vec<Endpoint<Out>>,
     routes: Vec<(BlockId, FilterFn<Out>)>,
+    #[derivative(Clone(clone_with = "clone_default"))]
+    checkpoint: Option<Arc<ReplicaCheckpoint>>,
 }
 
 impl<Out: ExchangeData, OperatorChain, IndexFn> Display for RoutingEnd<Out, OperatorChain, IndexFn>
@@ -144,6 +148,7 @@ where
             endpoints: Default::default(),
             routes,
             senders: Default::default(),
+            checkpoint: None,
         }
     }
 
@@ -211,6 +216,7 @@ where
 
         self.setup_endpoints();
 
+        self.checkpoint = metadata.checkpoint.clone();
         self.coord = Some(metadata.coord);
     }
 
@@ -256,11 +262,18 @@ where
 
         // Flushing messages
         match to_return {
-            StreamElement::FlushAndRestart | StreamElement::FlushBatch => {
+            StreamElement::FlushAndRestart => {
                 for (_, batcher) in self.senders.iter_mut() {
                     batcher.flush();
                 }
             }
+            StreamElement::FlushBatch => {
+                // forward the barrier of the checkpoint in progress after the flushed messages
+                match self.checkpoint.as_ref().and_then(|c| c.in_progress()) {
+                    Some(id) => self.senders.iter_mut().for_each(|(_, b)| b.barrier(id)),
+                    None => self.senders.iter_mut().for_each(|(_, b)| b.flush()),
+                }
+            }
             StreamElement::Terminate => {
                 log::trace!(
                     "routing_end terminate {}, closing {} channels",
@@ -0,0 +1,127 @@
+use nanorand::{tls_rng, Rng};
+use serde::{Deserialize, Serialize};
+
+/// A uniform random sample of at most `capacity` items of a stream, built with reservoir
+/// sampling.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Reservoir<T> {
+    capacity: usize,
+    /// Number of items the sample has been drawn from.
+    count: u64,
+    items: Vec<T>,
+}
+
+impl<T> Reservoir<T> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            count: 0,
+            items: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, item: T) {
+        self.count += 1;
+        if self.items.len() < self.capacity {
+            self.items.push(item);
+        } else {
+            let i = tls_rng().generate_range(0..self.count);
+            if i < self.capacity as u64 {
+                self.items[i as usize] = item;
+            }
+        }
+    }
+
+    /// Merge with the sample of another part of the stream, with the same capacity.
+    ///
+    /// Each item of the merged sample is drawn from one of the two samples with a probability
+    /// proportional to the number of items the sample was drawn from, so that the result is a
+    /// uniform sample of the union of the two parts.
+    pub(crate) fn merge(&mut self, mut other: Reservoir<T>) {
+        let mut mine = std::mem::take(&mut self.items);
+        let (mut count_mine, mut count_other) = (self.count, other.count);
+        let size = self.capacity.min((count_mine + count_other) as usize);
+        let mut rng = tls_rng();
+        while self.items.len() < size {
+            // a sample drawn from at most `capacity` items contains all of them, so it cannot be
+            // emptied before its count reaches zero
+            let from = if rng.generate_range(0..count_mine + count_other) < count_mine {
+                count_mine -= 1;
+                &mut mine
+            } else {
+                count_other -= 1;
+                &mut other.items
+            };
+            let i = rng.generate_range(0..from.len());
+            self.items.push(from.swap_remove(i));
+        }
+        self.count += other.count;
+    }
+
+    pub(crate) fn into_items(self) -> Vec<T> {
+        self.items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Reservoir;
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    #[test]
+    fn reservoir_merge() {
+        let mut a = Reservoir::new(10);
+        let mut b = Reservoir::new(10);
+        (0..5).for_each(|n| a.push(n));
+        (100..1000).for_each(|n| b.push(n));
+        a.merge(b);
+
+        let items = a.into_items();
+        assert_eq!(items.len(), 10);
+        assert!(items
+            .iter()
+            .all(|n| (0..5).contains(n) || (100..1000).contains(n)));
+
+        let mut small = Reservoir::new(10);
+        (0..3).for_each(|n| small.push(n));
+        small.merge(Reservoir::new(10));
+        let mut items = small.into_items();
+        items.sort_unstable();
+        assert_eq!(items, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn reservoir_sample() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_par_iter(0..10_000u32)
+            .reservoir_sample(100)
+            .collect_vec();
+        env.execute_blocking();
+
+        let mut res = res.get().unwrap();
+        assert_eq!(res.len(), 100);
+        res.sort_unstable();
+        res.dedup();
+        assert_eq!(res.len(), 100);
+        assert!(res.iter().all(|n| *n < 10_000));
+    }
+
+    #[test]
+    fn sample() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let all = env.stream_par_iter(0..1000u32).sample(1.0).collect_count();
+        let none = env.stream_par_iter(0..1000u32).sample(0.0).collect_count();
+        let some = env
+            .stream_par_iter(0..10_000u32)
+            .sample(0.5)
+            .collect_count();
+        env.execute_blocking();
+
+        assert_eq!(all.get().unwrap(), 1000);
+        assert_eq!(none.get().unwrap(), 0);
+        let some = some.get().unwrap();
+        assert!((4000..6000).contains(&some), "sampled {some} items");
+    }
+}
@@ -65,7 +65,7 @@ where
             }
         });
         let c = C::from_iter(iter);
-        *self.output.lock().unwrap() = Some(c);
+        self.output.set(c);
 
         StreamElement::Terminate
     }
@@ -89,7 +89,7 @@ where
 
 #[cfg(test)]
 mod qtests {
-    use std::collections::HashSet;
+    use std::collections::{HashMap, HashSet};
 
     use crate::config::RuntimeConfig;
     use crate::environment::StreamContext;
@@ -112,4 +112,17 @@ mod qtests {
         env.execute_blocking();
         assert_eq!(res.get().unwrap(), (0..10).collect::<HashSet<_>>());
     }
+
+    #[test]
+    fn collect_map() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let source = source::IteratorSource::new(0..10u8);
+        let res = env
+            .stream(source)
+            .group_by_fold(|&n| n % 3, 0u8, |acc, n| *acc += n, |acc, n| *acc += n)
+            .collect_map();
+        env.execute_blocking();
+        let expected: HashMap<_, _> = [(0, 18), (1, 12), (2, 15)].into_iter().collect();
+        assert_eq!(res.get().unwrap(), expected);
+    }
 }
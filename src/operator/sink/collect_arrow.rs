@@ -0,0 +1,216 @@
+use std::fmt::Display;
+use std::sync::Arc;
+
+use arrow::array::RecordBatch;
+use arrow::datatypes::{Schema, SchemaRef};
+use arrow::json::reader::Decoder;
+use arrow::json::ReaderBuilder;
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure};
+use crate::operator::sink::{StreamOutput, StreamOutputRef};
+use crate::operator::{ExchangeData, Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::{Replication, Stream};
+
+/// Maximum number of rows of each collected batch.
+const BATCH_SIZE: usize = 8 * 1024;
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct CollectArrowSink<Op: Operator> {
+    prev: Op,
+    schema: SchemaRef,
+    #[derivative(Debug = "ignore")]
+    buffer: Vec<Op::Out>,
+    batches: Vec<RecordBatch>,
+    #[derivative(Debug = "ignore")]
+    decoder: Option<Decoder>,
+    output: StreamOutputRef<Vec<RecordBatch>>,
+}
+
+impl<Op: Operator> CollectArrowSink<Op>
+where
+    Op::Out: ExchangeData,
+{
+    pub(crate) fn new(
+        prev: Op,
+        schema: SchemaRef,
+        output: StreamOutputRef<Vec<RecordBatch>>,
+    ) -> Self {
+        Self {
+            prev,
+            schema,
+            buffer: Vec::with_capacity(BATCH_SIZE),
+            batches: Vec::new(),
+            decoder: None,
+            output,
+        }
+    }
+
+    /// Convert the buffered items into a new batch.
+    fn flush_batch(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let decoder = self.decoder.as_mut().unwrap();
+        decoder
+            .serialize(&self.buffer)
+            .expect("failed to serialize struct to arrow RecordBatch");
+        self.buffer.clear();
+        if let Some(batch) = decoder
+            .flush()
+            .expect("failed to decode struct to arrow RecordBatch")
+        {
+            self.batches.push(batch);
+        }
+    }
+}
+
+impl<Op: Operator> Display for CollectArrowSink<Op> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> CollectArrowSink", self.prev)
+    }
+}
+
+impl<Op: Operator> Operator for CollectArrowSink<Op>
+where
+    Op::Out: ExchangeData,
+{
+    type Out = ();
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        let decoder = ReaderBuilder::new(self.schema.clone())
+            .with_batch_size(BATCH_SIZE)
+            .build_decoder()
+            .expect("failed to build the arrow decoder");
+        self.decoder = Some(decoder);
+    }
+
+    fn next(&mut self) -> StreamElement<()> {
+        match self.prev.next() {
+            StreamElement::Item(t) | StreamElement::Timestamped(t, _) => {
+                self.buffer.push(t);
+                if self.buffer.len() >= BATCH_SIZE {
+                    self.flush_batch();
+                }
+                StreamElement::Item(())
+            }
+            StreamElement::Watermark(w) => StreamElement::Watermark(w),
+            StreamElement::Terminate => {
+                self.flush_batch();
+                self.output.set(std::mem::take(&mut self.batches));
+                StreamElement::Terminate
+            }
+            StreamElement::FlushBatch => StreamElement::FlushBatch,
+            StreamElement::FlushAndRestart => StreamElement::FlushAndRestart,
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<Op::Out, _>("CollectArrowSink");
+        operator.kind = OperatorKind::Sink;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+impl<Op: Operator> Clone for CollectArrowSink<Op> {
+    fn clone(&self) -> Self {
+        panic!("CollectArrowSink cannot be cloned, replication should be 1");
+    }
+}
+
+impl<Op> Stream<Op>
+where
+    Op: Operator + 'static,
+    Op::Out: ExchangeData,
+{
+    /// Close the stream and store all the resulting items into arrow [`RecordBatch`]es with the
+    /// given schema, on a single host.
+    ///
+    /// The items are converted to rows by matching the names of their fields with the names of
+    /// the columns of the schema.
+    ///
+    /// **Note**: the order of items is unspecified.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use arrow::datatypes::{DataType, Field, Schema};
+    /// # let mut env = StreamContext::new_local();
+    /// #[derive(serde::Serialize, serde::Deserialize, Clone)]
+    /// struct Row {
+    ///     id: u32,
+    ///     name: String,
+    /// }
+    ///
+    /// let schema = Schema::new(vec![
+    ///     Field::new("id", DataType::UInt32, false),
+    ///     Field::new("name", DataType::Utf8, false),
+    /// ]);
+    /// let res = env
+    ///     .stream_iter(0..10)
+    ///     .map(|id| Row { id, name: format!("n{id}") })
+    ///     .collect_arrow(schema);
+    /// env.execute_blocking();
+    ///
+    /// let batches = res.get().unwrap();
+    /// assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+    /// ```
+    pub fn collect_arrow(self, schema: Schema) -> StreamOutput<Vec<RecordBatch>> {
+        let output = StreamOutputRef::default();
+        let schema = Arc::new(schema);
+        self.replication(Replication::One)
+            .add_operator(|prev| CollectArrowSink::new(prev, schema, output.clone()))
+            .finalize_block();
+        StreamOutput::from(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{AsArray, RecordBatch};
+    use arrow::datatypes::{DataType, Field, Schema, UInt64Type};
+    use serde::{Deserialize, Serialize};
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    #[derive(Serialize, Deserialize, Clone)]
+    struct Row {
+        a: u64,
+        b: Option<String>,
+    }
+
+    #[test]
+    fn collect_arrow() {
+        let schema = Schema::new(vec![
+            Field::new("a", DataType::UInt64, false),
+            Field::new("b", DataType::Utf8, true),
+        ]);
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_iter(0..20_000u64)
+            .map(|a| Row {
+                a,
+                b: (a % 2 == 0).then(|| a.to_string()),
+            })
+            .collect_arrow(schema);
+        env.execute_blocking();
+
+        let batches: Vec<RecordBatch> = res.get().unwrap();
+        assert!(batches.len() > 1);
+        let mut values = batches
+            .iter()
+            .flat_map(|b| {
+                assert_eq!(b.column(1).null_count(), b.num_rows() / 2);
+                b.column(0).as_primitive::<UInt64Type>().values().to_vec()
+            })
+            .collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(values, (0..20_000).collect::<Vec<_>>());
+    }
+}
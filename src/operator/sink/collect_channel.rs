@@ -46,7 +46,11 @@ where
     fn next(&mut self) -> StreamElement<()> {
         match self.prev.next() {
             StreamElement::Item(t) | StreamElement::Timestamped(t, _) => {
-                let _ = self.tx.as_ref().map(|tx| tx.send(t));
+                // once the receiver is dropped the remaining items are discarded
+                if matches!(self.tx.as_ref().map(|tx| tx.send(t)), Some(Err(_))) {
+                    tracing::debug!("collect channel receiver dropped, discarding the items");
+                    self.tx = None;
+                }
                 StreamElement::Item(())
             }
             StreamElement::Watermark(w) => StreamElement::Watermark(w),
@@ -86,4 +90,28 @@ mod tests {
         }
         assert_eq!(v, (0..10).collect_vec());
     }
+
+    #[test]
+    fn collect_channel_bounded_while_running() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let source = source::IteratorSource::new(0..1000u32);
+        let rx = env.stream(source).collect_channel_bounded(4);
+        // the channel is much smaller than the output, so it must be consumed while the job runs
+        let job = std::thread::spawn(move || env.execute_blocking());
+        let v = rx.iter().collect_vec();
+        job.join().unwrap();
+        assert_eq!(v, (0..1000).collect_vec());
+    }
+
+    #[test]
+    fn collect_channel_early_drop() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let source = source::IteratorSource::new(0..1000u32);
+        let rx = env.stream(source).collect_channel_bounded(4);
+        let job = std::thread::spawn(move || env.execute_blocking());
+        let v = rx.iter().take(10).collect_vec();
+        drop(rx);
+        job.join().unwrap();
+        assert_eq!(v, (0..10).collect_vec());
+    }
 }
@@ -56,7 +56,7 @@ where
             }
             StreamElement::Watermark(w) => StreamElement::Watermark(w),
             StreamElement::Terminate => {
-                *self.output.lock().unwrap() = Some(self.result);
+                self.output.set(self.result);
                 StreamElement::Terminate
             }
             StreamElement::FlushBatch => StreamElement::FlushBatch,
@@ -59,7 +59,7 @@ where
             StreamElement::Watermark(w) => StreamElement::Watermark(w),
             StreamElement::Terminate => {
                 if let Some(result) = self.result.take() {
-                    *self.output.lock().unwrap() = Some(result);
+                    self.output.set(result);
                 }
                 StreamElement::Terminate
             }
@@ -1,8 +1,10 @@
 use serde::Serialize;
+use std::ffi::OsString;
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{BufWriter, Write};
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use crate::block::NextStrategy;
 use crate::operator::{ExchangeData, Operator};
@@ -11,70 +13,209 @@ use crate::{CoordUInt, Replication, Stream};
 
 use super::writer::{sequential_path, WriteOperator, WriterOperator};
 
+/// Options for the CSV sinks.
+#[derive(Clone, Debug)]
+pub struct CsvWriteOptions {
+    append: bool,
+    delimiter: u8,
+    header: bool,
+    rotate_size: Option<u64>,
+    rotate_interval: Option<Duration>,
+}
+
+impl Default for CsvWriteOptions {
+    fn default() -> Self {
+        Self {
+            append: false,
+            delimiter: b',',
+            header: true,
+            rotate_size: None,
+            rotate_interval: None,
+        }
+    }
+}
+
+impl CsvWriteOptions {
+    /// Append to the existing files instead of truncating them.
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+
+    /// Set the field delimiter, by default `,`.
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Whether to write the header row at the beginning of each file, by default `true`.
+    ///
+    /// The header is never written when appending to a non-empty file.
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Start a new file when the current one grows over `bytes`.
+    ///
+    /// The size is checked after each item, so the files can be slightly larger than this.
+    pub fn rotate_size(mut self, bytes: u64) -> Self {
+        self.rotate_size = Some(bytes);
+        self
+    }
+
+    /// Start a new file when the current one has been open for longer than `interval`.
+    pub fn rotate_interval(mut self, interval: Duration) -> Self {
+        self.rotate_interval = Some(interval);
+        self
+    }
+}
+
+/// Default size of the buffer of the csv writer.
+const CSV_BUFFER_CAPACITY: usize = 8 * 1024;
+
+/// Writer counting the number of bytes written to the file.
+struct CountingWriter {
+    inner: BufWriter<File>,
+    written: u64,
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Path of the `part`-th file of a rotated output, the first part is `path` itself while the
+/// following ones have the part number before the extension (`out.csv`, `out-00001.csv`, ...).
+fn part_path(path: &Path, part: usize) -> PathBuf {
+    if part == 0 {
+        return path.to_path_buf();
+    }
+    let mut name = OsString::new();
+    name.push(path.file_stem().unwrap_or_default());
+    name.push(format!("-{part:05}"));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
+
 // #[derive(Debug)]
 pub struct CsvWriteOp<T> {
     _t: PhantomData<T>,
-    append: bool,
+    options: CsvWriteOptions,
     path: Option<PathBuf>,
+    /// Index of the current file when rotating the output.
+    part: usize,
+    opened_at: Instant,
     /// Reader used to parse the CSV file.
-    writer: Option<csv::Writer<BufWriter<File>>>,
+    writer: Option<csv::Writer<CountingWriter>>,
 }
 
 impl<T> CsvWriteOp<T>
 where
     T: Serialize + Send,
 {
-    pub fn new(append: bool) -> Self {
+    pub fn with_options(options: CsvWriteOptions) -> Self {
         Self {
             _t: PhantomData,
-            append,
+            options,
             path: None,
+            part: 0,
+            opened_at: Instant::now(),
             writer: None,
         }
     }
-}
 
-impl<T> WriteOperator<T> for CsvWriteOp<T>
-where
-    T: Serialize + Send,
-{
-    type Destination = PathBuf;
-
-    fn setup(&mut self, destination: PathBuf) {
-        self.path = Some(destination);
-
-        tracing::debug!("Write csv to path {:?}", self.path.as_ref().unwrap());
+    fn open(&mut self) {
+        let path = part_path(self.path.as_ref().unwrap(), self.part);
+        tracing::debug!("Write csv to path {:?}", path);
+        let append = self.options.append;
         let file = File::options()
             .read(true)
             .write(true)
             .create(true)
-            .truncate(!self.append)
-            .append(self.append)
-            .open(self.path.as_ref().unwrap())
+            .truncate(!append)
+            .append(append)
+            .open(&path)
             .unwrap_or_else(|err| {
-                panic!(
-                    "CsvSource: error while opening file {:?}: {:?}",
-                    self.path, err
-                )
+                panic!("CsvSource: error while opening file {:?}: {:?}", path, err)
             });
         let file_len = file.metadata().unwrap().len();
 
-        let buf_writer = BufWriter::new(file);
+        let counting = CountingWriter {
+            inner: BufWriter::new(file),
+            written: file_len,
+        };
+        // keep the internal buffer smaller than the rotation size, so that the bytes written
+        // to the file are counted with a reasonable precision
+        let capacity = self
+            .options
+            .rotate_size
+            .map(|s| (s as usize).clamp(1, CSV_BUFFER_CAPACITY))
+            .unwrap_or(CSV_BUFFER_CAPACITY);
         let csv_writer = csv::WriterBuilder::default()
-            .has_headers(file_len == 0)
-            .from_writer(buf_writer);
+            .buffer_capacity(capacity)
+            .delimiter(self.options.delimiter)
+            .has_headers(self.options.header && file_len == 0)
+            .from_writer(counting);
 
         self.writer = Some(csv_writer);
+        self.opened_at = Instant::now();
+    }
+
+    /// Whether the current file should be closed and a new one started.
+    fn should_rotate(&self) -> bool {
+        let written = self.writer.as_ref().unwrap().get_ref().written;
+        if matches!(self.options.rotate_size, Some(max) if written >= max) {
+            return true;
+        }
+        matches!(self.options.rotate_interval, Some(i) if self.opened_at.elapsed() >= i)
+    }
+
+    fn rotate(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush().unwrap();
+        }
+        self.part += 1;
+        self.open();
+    }
+}
+
+impl<T> WriteOperator<T> for CsvWriteOp<T>
+where
+    T: Serialize + Send,
+{
+    type Destination = PathBuf;
+
+    fn setup(&mut self, destination: PathBuf) {
+        self.path = Some(destination);
+        self.open();
     }
 
     fn write(&mut self, items: &mut impl Iterator<Item = T>) {
+        let rotating = self.options.rotate_size.is_some() || self.options.rotate_interval.is_some();
         for item in items {
             self.writer.as_mut().unwrap().serialize(item).unwrap();
+            if rotating && self.should_rotate() {
+                self.rotate();
+            }
         }
     }
 
     fn flush(&mut self) {
         self.writer.as_mut().unwrap().flush().ok();
+        if matches!(self.options.rotate_interval, Some(i) if self.opened_at.elapsed() >= i) {
+            self.rotate();
+        }
     }
 
     fn finalize(&mut self) {
@@ -86,8 +227,10 @@ impl<T> Clone for CsvWriteOp<T> {
     fn clone(&self) -> Self {
         Self {
             _t: PhantomData,
-            append: self.append,
+            options: self.options.clone(),
             path: None,
+            part: 0,
+            opened_at: Instant::now(),
             writer: None,
         }
     }
@@ -102,11 +245,39 @@ where
         self,
         make_path: F,
         append: bool,
+    ) {
+        self.write_csv_with(make_path, CsvWriteOptions::default().append(append))
+    }
+
+    /// Write output to CSV files, one for each replica, using the given [`CsvWriteOptions`].
+    ///
+    /// `make_path` is called with the id of the replica and returns the path of its file.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::prelude::*;
+    /// # use renoir::operator::sink::CsvWriteOptions;
+    /// # use std::time::Duration;
+    /// # let mut env = StreamContext::new_local();
+    /// env.stream_par_iter(0..100).map(|i| (i, i * i)).write_csv_with(
+    ///     |id| format!("/data/out-{id}.tsv").into(),
+    ///     CsvWriteOptions::default()
+    ///         .delimiter(b'\t')
+    ///         .header(false)
+    ///         .rotate_size(64 * 1024 * 1024)
+    ///         .rotate_interval(Duration::from_secs(3600)),
+    /// );
+    /// ```
+    pub fn write_csv_with<F: FnOnce(CoordUInt) -> PathBuf + Clone + Send + 'static>(
+        self,
+        make_path: F,
+        options: CsvWriteOptions,
     ) {
         let make_destination = |metadata: &ExecutionMetadata| (make_path)(metadata.global_id);
 
         self.add_operator(|prev| {
-            let writer = CsvWriteOp::new(append);
+            let writer = CsvWriteOp::with_options(options);
             WriterOperator::new(prev, writer, make_destination)
         })
         .finalize_block();
@@ -124,8 +295,16 @@ where
     /// + `template_path`: `/data/renoir/output.csv` -> `/data/renoir/output0000.csv`, /data/renoir/output0001.csv` ...
     /// + `template_path`: `/data/renoir/` -> `/data/renoir/0000.csv`, /data/renoir/0001.csv` ...
     pub fn write_csv_seq(self, template_path: PathBuf, append: bool) {
+        self.write_csv_seq_with(template_path, CsvWriteOptions::default().append(append))
+    }
+
+    /// Like [`Stream::write_csv_seq`], using the given [`CsvWriteOptions`].
+    ///
+    /// When rotating, the following files of each replica have the part number appended to the
+    /// name (`output0000.csv`, `output0000-00001.csv`, ...).
+    pub fn write_csv_seq_with(self, template_path: PathBuf, options: CsvWriteOptions) {
         self.add_operator(|prev| {
-            let writer = CsvWriteOp::new(append);
+            let writer = CsvWriteOp::with_options(options);
             WriterOperator::new(prev, writer, |m| sequential_path(template_path, m))
         })
         .finalize_block();
@@ -138,12 +317,79 @@ where
     Op::Out: ExchangeData,
 {
     pub fn write_csv_one<P: Into<PathBuf>>(self, path: P, append: bool) {
+        self.write_csv_one_with(path, CsvWriteOptions::default().append(append))
+    }
+
+    /// Write all the items to a single CSV file, using the given [`CsvWriteOptions`].
+    pub fn write_csv_one_with<P: Into<PathBuf>>(self, path: P, options: CsvWriteOptions) {
         let path = path.into();
         self.repartition(Replication::One, NextStrategy::only_one())
             .add_operator(|prev| {
-                let writer = CsvWriteOp::new(append);
+                let writer = CsvWriteOp::with_options(options);
                 WriterOperator::new(prev, writer, move |_| path)
             })
             .finalize_block();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use itertools::Itertools;
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+    use crate::operator::sink::CsvWriteOptions;
+
+    #[test]
+    fn csv_delimiter_no_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.csv");
+
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        env.stream_iter(0..3)
+            .map(|i| (i, i * 10))
+            .write_csv_one_with(
+                &path,
+                CsvWriteOptions::default().delimiter(b';').header(false),
+            );
+        env.execute_blocking();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content, "0;0\n1;10\n2;20\n");
+    }
+
+    #[test]
+    fn csv_append_and_rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.csv");
+        std::fs::write(&path, "0,0\n").unwrap();
+
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        env.stream_iter(1..10).map(|i| (i, i)).write_csv_one_with(
+            &path,
+            CsvWriteOptions::default()
+                .append(true)
+                .rotate_size(16)
+                .rotate_interval(Duration::from_secs(3600)),
+        );
+        env.execute_blocking();
+
+        let lines = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .flat_map(|p| {
+                std::fs::read_to_string(p)
+                    .unwrap()
+                    .lines()
+                    .map(str::to_owned)
+                    .collect_vec()
+            })
+            .sorted()
+            .collect_vec();
+        let expected = (0..10).map(|i| format!("{i},{i}")).collect_vec();
+        assert_eq!(lines, expected);
+        assert!(dir.path().join("out-00001.csv").exists());
+    }
+}
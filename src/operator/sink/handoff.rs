@@ -0,0 +1,256 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use flume::{Receiver, Sender};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::block::{BlockStructure, NextStrategy, OperatorKind, OperatorStructure, Replication};
+use crate::operator::{ExchangeData, Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::Stream;
+
+/// How long the sink keeps trying to connect to the consumer of a [`Handoff::Tcp`].
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+const CONNECT_RETRY: Duration = Duration::from_millis(100);
+
+/// The in-memory hand-off channels of this process, by name.
+static CHANNELS: Lazy<Mutex<HashMap<String, Box<dyn Any + Send>>>> = Lazy::new(Default::default);
+
+/// A channel for passing the output of a job to another job, see [`Stream::write_handoff`] and
+/// [`StreamContext::stream_handoff`](crate::StreamContext::stream_handoff).
+///
+/// The items are sent as they are produced, followed by an end marker, so the consumer can tell a
+/// complete stream from an interrupted one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Handoff {
+    /// An in-memory channel with the given name, for jobs running in the same process. The items
+    /// are buffered until they are consumed, so the jobs can run concurrently or one after the
+    /// other.
+    Memory(String),
+    /// A file, for jobs running one after the other. The file is written under a temporary name
+    /// and renamed when complete; the consumer waits for it to appear.
+    File(PathBuf),
+    /// A TCP connection, for jobs running concurrently in different processes. The consumer
+    /// listens on the address and the producer connects to it.
+    Tcp(SocketAddr),
+}
+
+/// Get the in-memory channel with the given name, creating it if needed.
+pub(crate) fn memory_channel<T: Send + 'static>(
+    name: &str,
+) -> (Sender<Option<T>>, Receiver<Option<T>>) {
+    let mut channels = CHANNELS.lock().unwrap();
+    let channel = channels
+        .entry(name.to_string())
+        .or_insert_with(|| Box::new(flume::unbounded::<Option<T>>()));
+    let (tx, rx) = channel
+        .downcast_ref::<(Sender<Option<T>>, Receiver<Option<T>>)>()
+        .unwrap_or_else(|| panic!("handoff channel {name:?} has a different item type"));
+    (tx.clone(), rx.clone())
+}
+
+/// Remove the in-memory channel with the given name, after it has been fully consumed.
+pub(crate) fn remove_memory_channel(name: &str) {
+    CHANNELS.lock().unwrap().remove(name);
+}
+
+/// Path of the file while it is being written.
+pub(crate) fn partial_path(path: &std::path::Path) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".partial");
+    name.into()
+}
+
+enum HandoffWriter<T> {
+    Memory(Sender<Option<T>>),
+    File(BufWriter<File>, PathBuf),
+    Tcp(BufWriter<TcpStream>),
+}
+
+impl<T: Serialize> HandoffWriter<T> {
+    fn open(handoff: &Handoff) -> Self
+    where
+        T: Send + 'static,
+    {
+        match handoff {
+            Handoff::Memory(name) => HandoffWriter::Memory(memory_channel(name).0),
+            Handoff::File(path) => {
+                let partial = partial_path(path);
+                let file = File::create(&partial).unwrap_or_else(|err| {
+                    panic!("HandoffSink: error while creating {partial:?}: {err:?}")
+                });
+                HandoffWriter::File(BufWriter::new(file), path.clone())
+            }
+            Handoff::Tcp(addr) => {
+                let start = Instant::now();
+                let stream = loop {
+                    match TcpStream::connect(addr) {
+                        Ok(stream) => break stream,
+                        Err(e) if start.elapsed() < CONNECT_TIMEOUT => {
+                            tracing::debug!("waiting for the handoff consumer at {addr}: {e}");
+                            std::thread::sleep(CONNECT_RETRY);
+                        }
+                        Err(e) => panic!("HandoffSink: cannot connect to {addr}: {e}"),
+                    }
+                };
+                HandoffWriter::Tcp(BufWriter::new(stream))
+            }
+        }
+    }
+
+    fn send(&mut self, item: Option<T>) {
+        let mut w: &mut dyn Write = match self {
+            HandoffWriter::Memory(tx) => {
+                // the consumer may have stopped early
+                let _ = tx.send(item);
+                return;
+            }
+            HandoffWriter::File(w, _) => w,
+            HandoffWriter::Tcp(w) => w,
+        };
+        bincode::serde::encode_into_std_write(&item, &mut w, bincode::config::standard())
+            .expect("HandoffSink: failed to write item");
+    }
+
+    fn flush(&mut self) {
+        match self {
+            HandoffWriter::Memory(_) => {}
+            HandoffWriter::File(w, _) => w.flush().unwrap(),
+            HandoffWriter::Tcp(w) => w.flush().unwrap(),
+        }
+    }
+
+    /// Send the end marker and close the channel.
+    fn close(mut self) {
+        self.send(None);
+        self.flush();
+        if let HandoffWriter::File(w, path) = self {
+            drop(w);
+            std::fs::rename(partial_path(&path), &path).unwrap_or_else(|err| {
+                panic!("HandoffSink: error while renaming {path:?}: {err:?}")
+            });
+        }
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct HandoffSink<Op: Operator> {
+    prev: Op,
+    handoff: Handoff,
+    #[derivative(Debug = "ignore")]
+    writer: Option<HandoffWriter<Op::Out>>,
+}
+
+impl<Op: Operator> HandoffSink<Op> {
+    pub(crate) fn new(prev: Op, handoff: Handoff) -> Self {
+        Self {
+            prev,
+            handoff,
+            writer: None,
+        }
+    }
+}
+
+impl<Op: Operator> Clone for HandoffSink<Op> {
+    fn clone(&self) -> Self {
+        panic!("HandoffSink cannot be cloned, replication should be 1");
+    }
+}
+
+impl<Op: Operator> Display for HandoffSink<Op> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> HandoffSink", self.prev)
+    }
+}
+
+impl<Op> Operator for HandoffSink<Op>
+where
+    Op: Operator,
+    Op::Out: ExchangeData,
+{
+    type Out = ();
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        self.writer = Some(HandoffWriter::open(&self.handoff));
+    }
+
+    fn next(&mut self) -> StreamElement<()> {
+        loop {
+            match self.prev.next() {
+                StreamElement::Item(t) | StreamElement::Timestamped(t, _) => {
+                    self.writer.as_mut().unwrap().send(Some(t))
+                }
+                StreamElement::Watermark(w) => return StreamElement::Watermark(w),
+                StreamElement::FlushBatch => {
+                    self.writer.as_mut().unwrap().flush();
+                    return StreamElement::FlushBatch;
+                }
+                StreamElement::FlushAndRestart => {
+                    self.writer.as_mut().unwrap().flush();
+                    return StreamElement::FlushAndRestart;
+                }
+                StreamElement::Terminate => {
+                    if let Some(writer) = self.writer.take() {
+                        writer.close();
+                    }
+                    return StreamElement::Terminate;
+                }
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<Op::Out, _>("HandoffSink");
+        operator.kind = OperatorKind::Sink;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+impl<Op> Stream<Op>
+where
+    Op: Operator + 'static,
+    Op::Out: ExchangeData,
+{
+    /// Close the stream and pass all its items to another job, that reads them with
+    /// [`StreamContext::stream_handoff`](crate::StreamContext::stream_handoff) using the same
+    /// [`Handoff`].
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::sink::Handoff;
+    /// let first = StreamContext::new_local();
+    /// first
+    ///     .stream_iter(0..10)
+    ///     .map(|n| n * n)
+    ///     .write_handoff(Handoff::Memory("squares".into()));
+    /// first.execute_blocking();
+    ///
+    /// let second = StreamContext::new_local();
+    /// let res = second
+    ///     .stream_handoff::<i32>(Handoff::Memory("squares".into()))
+    ///     .filter(|n| n % 2 == 0)
+    ///     .collect_vec();
+    /// second.execute_blocking();
+    /// assert_eq!(res.get().unwrap(), vec![0, 4, 16, 36, 64]);
+    /// ```
+    pub fn write_handoff(self, handoff: Handoff) {
+        self.repartition(Replication::One, NextStrategy::only_one())
+            .add_operator(|prev| HandoffSink::new(prev, handoff))
+            .finalize_block();
+    }
+}
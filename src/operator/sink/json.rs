@@ -0,0 +1,201 @@
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+use crate::block::NextStrategy;
+use crate::operator::{ExchangeData, Operator};
+use crate::{Replication, Stream};
+
+use super::writer::{sequential_path, WriteOperator, WriterOperator};
+
+/// Layout of the JSON output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonFormat {
+    /// One JSON object per line (newline delimited JSON).
+    Lines,
+    /// A single indented JSON array containing all the items.
+    PrettyArray,
+}
+
+// #[derive(Debug)]
+pub struct JsonWriteOp<T> {
+    _t: PhantomData<T>,
+    format: JsonFormat,
+    /// Whether no item has been written yet.
+    empty: bool,
+    writer: Option<BufWriter<File>>,
+}
+
+impl<T> JsonWriteOp<T>
+where
+    T: Serialize + Send,
+{
+    pub fn new(format: JsonFormat) -> Self {
+        Self {
+            _t: PhantomData,
+            format,
+            empty: true,
+            writer: None,
+        }
+    }
+}
+
+impl<T> WriteOperator<T> for JsonWriteOp<T>
+where
+    T: Serialize + Send,
+{
+    type Destination = PathBuf;
+
+    fn setup(&mut self, destination: PathBuf) {
+        tracing::debug!("Write json to path {:?}", destination);
+        let file = File::create(&destination).unwrap_or_else(|err| {
+            panic!(
+                "JsonSink: error while opening file {:?}: {:?}",
+                destination, err
+            )
+        });
+        let mut writer = BufWriter::new(file);
+        if self.format == JsonFormat::PrettyArray {
+            writer.write_all(b"[").unwrap();
+        }
+        self.writer = Some(writer);
+    }
+
+    fn write(&mut self, items: &mut impl Iterator<Item = T>) {
+        let w = self.writer.as_mut().unwrap();
+        for item in items {
+            match self.format {
+                JsonFormat::Lines => {
+                    serde_json::to_writer(&mut *w, &item).expect("failed to serialize to json");
+                    w.write_all(b"\n").unwrap();
+                }
+                JsonFormat::PrettyArray => {
+                    let sep: &[u8] = if self.empty { b"\n" } else { b",\n" };
+                    w.write_all(sep).unwrap();
+                    // indent the items inside the array
+                    let json =
+                        serde_json::to_string_pretty(&item).expect("failed to serialize to json");
+                    for (i, line) in json.lines().enumerate() {
+                        if i > 0 {
+                            w.write_all(b"\n").unwrap();
+                        }
+                        w.write_all(b"  ").unwrap();
+                        w.write_all(line.as_bytes()).unwrap();
+                    }
+                }
+            }
+            self.empty = false;
+        }
+    }
+
+    fn flush(&mut self) {
+        self.writer.as_mut().unwrap().flush().ok();
+    }
+
+    fn finalize(&mut self) {
+        if let Some(mut w) = self.writer.take() {
+            if self.format == JsonFormat::PrettyArray {
+                let end: &[u8] = if self.empty { b"]\n" } else { b"\n]\n" };
+                w.write_all(end).unwrap();
+            }
+            w.flush().unwrap();
+        }
+    }
+}
+
+impl<T> Clone for JsonWriteOp<T> {
+    fn clone(&self) -> Self {
+        Self {
+            _t: PhantomData,
+            format: self.format,
+            empty: true,
+            writer: None,
+        }
+    }
+}
+
+impl<Op: Operator> Stream<Op>
+where
+    Op: 'static,
+    Op::Out: Serialize,
+{
+    /// Write output to newline delimited JSON files. A file is created for each replica of the
+    /// current block, named after `template_path` as in [`Stream::write_csv_seq`].
+    pub fn write_json_seq(self, template_path: PathBuf) {
+        self.add_operator(|prev| {
+            let writer = JsonWriteOp::new(JsonFormat::Lines);
+            WriterOperator::new(prev, writer, |m| sequential_path(template_path, m))
+        })
+        .finalize_block();
+    }
+}
+
+impl<Op: Operator> Stream<Op>
+where
+    Op: 'static,
+    Op::Out: ExchangeData,
+{
+    /// Write all the items to a single file with one JSON object per line (NDJSON).
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # let mut env = StreamContext::new_local();
+    /// env.stream_iter(0..5).map(|i| (i, i * i)).write_json("/data/squares.ndjson");
+    /// ```
+    pub fn write_json<P: Into<PathBuf>>(self, path: P) {
+        self.write_json_format(path, JsonFormat::Lines)
+    }
+
+    /// Write all the items to a single file as an indented JSON array.
+    ///
+    /// The whole array is built incrementally, but it is valid JSON only after the job has
+    /// completed. This is meant for small results that are read by humans or other tools.
+    pub fn write_json_pretty<P: Into<PathBuf>>(self, path: P) {
+        self.write_json_format(path, JsonFormat::PrettyArray)
+    }
+
+    /// Write all the items to a single JSON file with the given format.
+    pub fn write_json_format<P: Into<PathBuf>>(self, path: P, format: JsonFormat) {
+        let path = path.into();
+        self.repartition(Replication::One, NextStrategy::only_one())
+            .add_operator(|prev| {
+                let writer = JsonWriteOp::new(format);
+                WriterOperator::new(prev, writer, move |_| path)
+            })
+            .finalize_block();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    #[test]
+    fn json_lines_and_pretty() {
+        let dir = tempfile::tempdir().unwrap();
+        let lines = dir.path().join("out.ndjson");
+        let pretty = dir.path().join("out.json");
+
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let s = env.stream_iter(0..3).map(|i| (i, format!("n{i}")));
+        let mut split = s.split(2);
+        split.pop().unwrap().write_json(&lines);
+        split.pop().unwrap().write_json_pretty(&pretty);
+        env.execute_blocking();
+
+        let content = std::fs::read_to_string(&lines).unwrap();
+        assert_eq!(content, "[0,\"n0\"]\n[1,\"n1\"]\n[2,\"n2\"]\n");
+
+        let content = std::fs::read_to_string(&pretty).unwrap();
+        let parsed: Vec<(i32, String)> = serde_json::from_str(&content).unwrap();
+        assert_eq!(
+            parsed,
+            vec![(0, "n0".into()), (1, "n1".into()), (2, "n2".into())]
+        );
+    }
+}
@@ -2,104 +2,392 @@ use std::fmt::Display;
 use std::sync::Arc;
 use std::time::Duration;
 
-use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord, Producer};
 use rdkafka::util::Timeout;
 use rdkafka::ClientConfig;
 
 use crate::block::{BlockStructure, OperatorKind, OperatorStructure};
 
-use crate::operator::{Operator, StreamElement};
+use crate::operator::{Data, DataKey, Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
-use crate::Stream;
+use crate::{KeyedStream, Stream};
 
-#[derive(Clone, Derivative)]
+/// Options of the kafka sink.
+///
+/// Batching of the messages sent to the brokers is handled by the producer and can be tuned with
+/// the usual producer configuration (e.g. `linger.ms` and `batch.num.messages`).
+#[derive(Clone, Debug)]
+pub struct KafkaSinkOptions {
+    /// Maximum number of messages waiting for a delivery acknowledgement, when the limit is
+    /// reached the sink waits for the pending ones before sending more.
+    pub max_in_flight: usize,
+    /// Maximum time waited for the acknowledgements and for the transactions to complete.
+    pub timeout: Duration,
+    /// Write the messages inside transactions using this transactional id (suffixed with the id
+    /// of the replica). A transaction is committed every time the stream is flushed.
+    pub transactional_id: Option<String>,
+}
+
+impl Default for KafkaSinkOptions {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 16 * 1024,
+            timeout: Duration::from_secs(10),
+            transactional_id: None,
+        }
+    }
+}
+
+impl KafkaSinkOptions {
+    /// Write the messages using transactions with the given transactional id.
+    pub fn transactional(mut self, transactional_id: impl Into<String>) -> Self {
+        self.transactional_id = Some(transactional_id.into());
+        self
+    }
+
+    /// Set the maximum number of messages waiting for an acknowledgement.
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        assert!(max_in_flight > 0, "max_in_flight must be positive");
+        self.max_in_flight = max_in_flight;
+        self
+    }
+
+    /// Set the timeout for the acknowledgements and the transactions.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[derive(Derivative)]
 #[derivative(Debug)]
-pub struct KafkaSink<Op>
+pub struct KafkaSink<Op, F, K>
 where
     Op: Operator,
+    F: Fn(&Op::Out) -> K + Clone + Send + 'static,
 {
     prev: Op,
     #[derivative(Debug = "ignore")]
-    producer: FutureProducer,
+    config: ClientConfig,
+    #[derivative(Debug = "ignore")]
+    key_fn: F,
+    #[derivative(Debug = "ignore")]
+    payload_fn: for<'a> fn(&'a Op::Out) -> &'a [u8],
     topic: Arc<String>,
-    rt: tokio::runtime::Handle,
+    options: KafkaSinkOptions,
+    #[derivative(Debug = "ignore")]
+    producer: Option<FutureProducer>,
+    #[derivative(Debug = "ignore")]
+    pending: Vec<DeliveryFuture>,
+    in_transaction: bool,
+    rt: Option<tokio::runtime::Handle>,
+}
+
+impl<Op, F, K> Clone for KafkaSink<Op, F, K>
+where
+    Op: Operator,
+    F: Fn(&Op::Out) -> K + Clone + Send + 'static,
+{
+    fn clone(&self) -> Self {
+        assert!(
+            self.producer.is_none(),
+            "KafkaSink must be cloned before calling setup"
+        );
+        Self::new(
+            self.prev.clone(),
+            self.config.clone(),
+            (*self.topic).clone(),
+            self.key_fn.clone(),
+            self.payload_fn,
+            self.options.clone(),
+        )
+    }
 }
 
-impl<Op> KafkaSink<Op>
+impl<Op, F, K> KafkaSink<Op, F, K>
 where
     Op: Operator,
+    F: Fn(&Op::Out) -> K + Clone + Send + 'static,
 {
-    pub(crate) fn new(prev: Op, producer: FutureProducer, topic: String) -> Self {
+    pub(crate) fn new(
+        prev: Op,
+        config: ClientConfig,
+        topic: String,
+        key_fn: F,
+        payload_fn: for<'a> fn(&'a Op::Out) -> &'a [u8],
+        options: KafkaSinkOptions,
+    ) -> Self {
         Self {
             prev,
-            producer,
+            config,
+            key_fn,
+            payload_fn,
             topic: Arc::new(topic),
-            rt: tokio::runtime::Handle::current(),
+            options,
+            producer: None,
+            pending: Vec::new(),
+            in_transaction: false,
+            rt: None,
+        }
+    }
+}
+
+impl<Op, F, K> KafkaSink<Op, F, K>
+where
+    Op: Operator,
+    F: Fn(&Op::Out) -> K + Clone + Send + 'static,
+    K: AsRef<[u8]>,
+{
+    /// Wait for the acknowledgement of all the messages sent so far.
+    fn wait_pending(&mut self) {
+        let rt = self.rt.as_ref().unwrap();
+        for delivery in self.pending.drain(..) {
+            match rt.block_on(delivery) {
+                Ok(Ok(_)) => {}
+                Ok(Err((e, _))) => panic!("kafka delivery failed: {e}"),
+                Err(_) => panic!("kafka delivery cancelled"),
+            }
+        }
+    }
+
+    fn send(&mut self, item: &Op::Out) {
+        let producer = self.producer.clone().unwrap();
+        let topic = self.topic.clone();
+        if self.options.transactional_id.is_some() && !self.in_transaction {
+            producer
+                .begin_transaction()
+                .expect("failed to begin kafka transaction");
+            self.in_transaction = true;
+        }
+        let key = (self.key_fn)(item);
+        let mut record = FutureRecord::to(&topic)
+            .key(key.as_ref())
+            .payload((self.payload_fn)(item));
+        loop {
+            match producer.send_result(record) {
+                Ok(delivery) => {
+                    self.pending.push(delivery);
+                    break;
+                }
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), r)) => {
+                    // the local queue is full: wait for the acknowledgements and retry
+                    record = r;
+                    self.wait_pending();
+                }
+                Err((e, _)) => panic!("kafka producer fail: {e}"),
+            }
+        }
+        if self.pending.len() >= self.options.max_in_flight {
+            self.wait_pending();
+        }
+    }
+
+    /// Wait for the pending messages and commit the current transaction, if any.
+    fn flush(&mut self) {
+        self.wait_pending();
+        if self.in_transaction {
+            self.producer
+                .as_ref()
+                .unwrap()
+                .commit_transaction(Timeout::After(self.options.timeout))
+                .expect("failed to commit kafka transaction");
+            self.in_transaction = false;
         }
     }
 }
 
-impl<Op> Display for KafkaSink<Op>
+impl<Op, F, K> Display for KafkaSink<Op, F, K>
 where
     Op: Operator,
+    F: Fn(&Op::Out) -> K + Clone + Send + 'static,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{} -> KafkaSink", self.prev)
     }
 }
 
-impl<Op> Operator for KafkaSink<Op>
+impl<Op, F, K> Operator for KafkaSink<Op, F, K>
 where
     Op: Operator,
-    Op::Out: AsRef<[u8]> + 'static,
+    F: Fn(&Op::Out) -> K + Clone + Send + 'static,
+    K: AsRef<[u8]> + 'static,
 {
     type Out = ();
 
     fn setup(&mut self, metadata: &mut ExecutionMetadata) {
         self.prev.setup(metadata);
+
+        let mut config = self.config.clone();
+        if let Some(id) = &self.options.transactional_id {
+            // each replica needs its own transactional id
+            config.set("transactional.id", format!("{id}-{}", metadata.global_id));
+        }
+        let producer = config
+            .create::<FutureProducer>()
+            .expect("failed to create kafka producer");
+        if self.options.transactional_id.is_some() {
+            producer
+                .init_transactions(Timeout::After(self.options.timeout))
+                .expect("failed to initialize kafka transactions");
+        }
+        self.producer = Some(producer);
+        self.rt = Some(tokio::runtime::Handle::current());
     }
 
     fn next(&mut self) -> StreamElement<()> {
         loop {
             match self.prev.next() {
-                StreamElement::Item(t) | StreamElement::Timestamped(t, _) => {
-                    let producer = self.producer.clone();
-                    let topic = self.topic.clone();
-
-                    self.rt.spawn(async move {
-                        let record = FutureRecord::to(&topic).key(&[]).payload(t.as_ref());
-
-                        producer
-                            .send(record, Timeout::After(Duration::from_secs(10)))
-                            .await
-                            .expect("kafka producer fail");
-                    });
-                }
+                StreamElement::Item(t) | StreamElement::Timestamped(t, _) => self.send(&t),
                 StreamElement::Watermark(w) => return StreamElement::Watermark(w),
                 StreamElement::Terminate => return StreamElement::Terminate,
-                StreamElement::FlushBatch => return StreamElement::FlushBatch,
-                StreamElement::FlushAndRestart => return StreamElement::FlushAndRestart,
+                StreamElement::FlushBatch => {
+                    self.flush();
+                    return StreamElement::FlushBatch;
+                }
+                StreamElement::FlushAndRestart => {
+                    self.flush();
+                    return StreamElement::FlushAndRestart;
+                }
             }
         }
     }
 
     fn structure(&self) -> BlockStructure {
-        let mut operator = OperatorStructure::new::<Op::Out, _>("KafkaSinkSink");
+        let mut operator = OperatorStructure::new::<Op::Out, _>("KafkaSink");
         operator.kind = OperatorKind::Sink;
         self.prev.structure().add_operator(operator)
     }
 }
 
+impl<Op, F, K> Drop for KafkaSink<Op, F, K>
+where
+    Op: Operator,
+    F: Fn(&Op::Out) -> K + Clone + Send + 'static,
+{
+    fn drop(&mut self) {
+        if self.in_transaction {
+            if let Some(producer) = &self.producer {
+                tracing::warn!("aborting uncommitted kafka transaction");
+                let _ = producer.abort_transaction(Timeout::After(self.options.timeout));
+            }
+        }
+    }
+}
+
+fn value_payload<K, V: AsRef<[u8]>>((_, v): &(K, V)) -> &[u8] {
+    v.as_ref()
+}
+
 impl<Op> Stream<Op>
 where
     Op: Operator<Out: AsRef<[u8]>> + 'static,
 {
+    /// Write the items of the stream as messages of a kafka topic, using `key_fn` to compute the
+    /// key of each message.
+    ///
+    /// The sink waits for the acknowledgement of the messages every time the stream is flushed,
+    /// panicking if a message could not be delivered.
+    ///
     /// # WARNING: KAFKA API IS EXPERIMENTAL
-    pub fn write_kafka(self, producer_config: ClientConfig, topic: &str) {
-        let producer = producer_config
-            .create::<FutureProducer>()
-            .expect("failed to create kafka producer");
-        self.add_operator(|prev| KafkaSink::new(prev, producer, topic.to_owned()))
+    pub fn write_kafka<F, K>(self, producer_config: ClientConfig, topic: &str, key_fn: F)
+    where
+        F: Fn(&Op::Out) -> K + Clone + Send + 'static,
+        K: AsRef<[u8]> + 'static,
+    {
+        self.write_kafka_with(producer_config, topic, key_fn, KafkaSinkOptions::default())
+    }
+
+    /// Like [`Stream::write_kafka`], with custom [`KafkaSinkOptions`].
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::prelude::*;
+    /// # use renoir::operator::sink::KafkaSinkOptions;
+    /// # use rdkafka::ClientConfig;
+    /// # let mut env = StreamContext::new_local();
+    /// let mut producer = ClientConfig::new();
+    /// producer.set("bootstrap.servers", "localhost:9092");
+    /// env.stream_iter(0..100)
+    ///     .map(|x: u64| x.to_string())
+    ///     .write_kafka_with(
+    ///         producer,
+    ///         "numbers",
+    ///         |s| s.len().to_string(),
+    ///         KafkaSinkOptions::default().transactional("numbers-writer"),
+    ///     );
+    /// ```
+    ///
+    /// # WARNING: KAFKA API IS EXPERIMENTAL
+    pub fn write_kafka_with<F, K>(
+        self,
+        producer_config: ClientConfig,
+        topic: &str,
+        key_fn: F,
+        options: KafkaSinkOptions,
+    ) where
+        F: Fn(&Op::Out) -> K + Clone + Send + 'static,
+        K: AsRef<[u8]> + 'static,
+    {
+        self.add_operator(|prev| {
+            KafkaSink::new(
+                prev,
+                producer_config,
+                topic.to_owned(),
+                key_fn,
+                <Op::Out as AsRef<[u8]>>::as_ref,
+                options,
+            )
+        })
+        .finalize_block();
+    }
+}
+
+impl<Op, K, V> KeyedStream<Op>
+where
+    K: DataKey,
+    V: Data + AsRef<[u8]>,
+    Op: Operator<Out = (K, V)> + 'static,
+{
+    /// Write the values of the stream as messages of a kafka topic, using `key_fn` to compute the
+    /// key of each message from the key of the stream.
+    ///
+    /// See [`Stream::write_kafka`] for more details.
+    ///
+    /// # WARNING: KAFKA API IS EXPERIMENTAL
+    pub fn write_kafka<F, KB>(self, producer_config: ClientConfig, topic: &str, key_fn: F)
+    where
+        F: Fn(&K) -> KB + Clone + Send + 'static,
+        KB: AsRef<[u8]> + 'static,
+    {
+        self.write_kafka_with(producer_config, topic, key_fn, KafkaSinkOptions::default())
+    }
+
+    /// Like [`KeyedStream::write_kafka`], with custom [`KafkaSinkOptions`].
+    ///
+    /// # WARNING: KAFKA API IS EXPERIMENTAL
+    pub fn write_kafka_with<F, KB>(
+        self,
+        producer_config: ClientConfig,
+        topic: &str,
+        key_fn: F,
+        options: KafkaSinkOptions,
+    ) where
+        F: Fn(&K) -> KB + Clone + Send + 'static,
+        KB: AsRef<[u8]> + 'static,
+    {
+        self.0
+            .add_operator(|prev| {
+                KafkaSink::new(
+                    prev,
+                    producer_config,
+                    topic.to_owned(),
+                    move |(k, _): &(K, V)| key_fn(k),
+                    value_payload::<K, V>,
+                    options,
+                )
+            })
             .finalize_block();
     }
 }
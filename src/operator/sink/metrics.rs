@@ -0,0 +1,320 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::block::{BlockStructure, OperatorStructure};
+use crate::network::Coord;
+use crate::operator::{Operator, StreamElement};
+use crate::profiler::metrics;
+use crate::scheduler::ExecutionMetadata;
+use crate::Stream;
+
+/// Number of items counted locally before publishing them to the shared counters.
+const PUBLISH_EVERY: u64 = 1024;
+
+/// The metrics registered in an environment, by name.
+#[derive(Debug, Default)]
+pub(crate) struct MetricsRegistry(Mutex<BTreeMap<String, Arc<StreamMetrics>>>);
+
+impl MetricsRegistry {
+    /// Get the metrics registered with the given name, creating them if needed.
+    pub(crate) fn register(&self, name: &str) -> Arc<StreamMetrics> {
+        self.0
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(StreamMetrics::new(name)))
+            .clone()
+    }
+
+    /// Get the metrics registered with the given name, if any.
+    pub(crate) fn get(&self, name: &str) -> Option<Arc<StreamMetrics>> {
+        self.0.lock().unwrap().get(name).cloned()
+    }
+
+    /// Get a snapshot of all the registered metrics, sorted by name.
+    pub(crate) fn all(&self) -> Vec<MetricsSnapshot> {
+        self.0
+            .lock()
+            .unwrap()
+            .values()
+            .map(|m| m.snapshot())
+            .collect()
+    }
+}
+
+/// Counters of the items flowing through a point of a stream.
+///
+/// The counters belong to the environment, and are shared by all the replicas on this host and by
+/// all the operators registered with the same name. They are updated in batches, so they may lag
+/// slightly behind while the job is running.
+#[derive(Debug)]
+pub struct StreamMetrics {
+    name: String,
+    items: AtomicU64,
+    created: Instant,
+    /// Time of the first and of the last item, in nanoseconds since `created`.
+    first: AtomicU64,
+    last: AtomicU64,
+    /// The replicas of the operators, with the bytes they had sent to the network when they
+    /// started.
+    replicas: Mutex<Vec<(Coord, u64)>>,
+}
+
+/// A point-in-time copy of a [`StreamMetrics`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricsSnapshot {
+    pub name: String,
+    /// Number of items.
+    pub items: u64,
+    /// Bytes sent to the network by the replicas of the block of the operator: the size of its
+    /// output once serialized, or 0 if the output of the block stays on this host.
+    pub bytes: u64,
+    /// Time between the first and the last item.
+    pub elapsed: Duration,
+    /// Average number of items per second.
+    pub items_per_sec: f64,
+    /// Average number of bytes per second.
+    pub bytes_per_sec: f64,
+}
+
+impl StreamMetrics {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            items: AtomicU64::new(0),
+            created: Instant::now(),
+            first: AtomicU64::new(u64::MAX),
+            last: AtomicU64::new(0),
+            replicas: Default::default(),
+        }
+    }
+
+    /// Count the bytes sent to the network by the replica `coord` from now on.
+    fn add_replica(&self, coord: Coord) {
+        // the sizes are measured by the network layer once the metrics are enabled
+        metrics::enable();
+        let sent = metrics::bytes_sent(coord);
+        self.replicas.lock().unwrap().push((coord, sent));
+    }
+
+    fn add(&self, items: u64, first: Instant, last: Instant) {
+        self.items.fetch_add(items, Ordering::Relaxed);
+        let nanos = |t: Instant| t.duration_since(self.created).as_nanos() as u64;
+        self.first.fetch_min(nanos(first), Ordering::Relaxed);
+        self.last.fetch_max(nanos(last), Ordering::Relaxed);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the current value of the counters.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let items = self.items.load(Ordering::Relaxed);
+        let bytes = self
+            .replicas
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|&(coord, start)| metrics::bytes_sent(coord).saturating_sub(start))
+            .sum();
+        let first = self.first.load(Ordering::Relaxed);
+        let last = self.last.load(Ordering::Relaxed);
+        let elapsed = Duration::from_nanos(last.saturating_sub(first));
+        let secs = elapsed.as_secs_f64();
+        let rate = |v: u64| if secs > 0.0 { v as f64 / secs } else { 0.0 };
+        MetricsSnapshot {
+            name: self.name.clone(),
+            items,
+            bytes,
+            elapsed,
+            items_per_sec: rate(items),
+            bytes_per_sec: rate(bytes),
+        }
+    }
+}
+
+/// Render the metrics in the Prometheus text exposition format.
+pub(crate) fn prometheus(metrics: &[MetricsSnapshot]) -> String {
+    let mut out = String::new();
+    for (metric, help, value) in [
+        (
+            "renoir_stream_items_total",
+            "Number of items that reached the metrics operator.",
+            (|m: &MetricsSnapshot| m.items as f64) as fn(&MetricsSnapshot) -> f64,
+        ),
+        (
+            "renoir_stream_bytes_total",
+            "Bytes sent to the network by the block of the metrics operator.",
+            |m| m.bytes as f64,
+        ),
+        (
+            "renoir_stream_items_per_second",
+            "Average number of items per second.",
+            |m| m.items_per_sec,
+        ),
+    ] {
+        let kind = if metric.ends_with("_total") {
+            "counter"
+        } else {
+            "gauge"
+        };
+        writeln!(out, "# HELP {metric} {help}").unwrap();
+        writeln!(out, "# TYPE {metric} {kind}").unwrap();
+        for m in metrics {
+            let name = m.name.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(out, "{metric}{{name=\"{name}\"}} {}", value(m)).unwrap();
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsOperator<Op> {
+    prev: Op,
+    metrics: Arc<StreamMetrics>,
+    items: u64,
+    first: Option<Instant>,
+    last: Option<Instant>,
+}
+
+impl<Op: Operator> MetricsOperator<Op> {
+    pub(crate) fn new(prev: Op, metrics: Arc<StreamMetrics>) -> Self {
+        Self {
+            prev,
+            metrics,
+            items: 0,
+            first: None,
+            last: None,
+        }
+    }
+
+    fn count(&mut self) {
+        let now = Instant::now();
+        self.first.get_or_insert(now);
+        self.last = Some(now);
+        self.items += 1;
+        if self.items >= PUBLISH_EVERY {
+            self.publish();
+        }
+    }
+
+    /// Add the local counters to the shared ones.
+    fn publish(&mut self) {
+        if let (Some(first), Some(last)) = (self.first.take(), self.last.take()) {
+            self.metrics.add(self.items, first, last);
+        }
+        self.items = 0;
+    }
+}
+
+impl<Op: Operator> Display for MetricsOperator<Op> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> Metrics[{}]", self.prev, self.metrics.name)
+    }
+}
+
+impl<Op: Operator> Operator for MetricsOperator<Op> {
+    type Out = Op::Out;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        self.metrics.add_replica(metadata.coord);
+    }
+
+    fn next(&mut self) -> StreamElement<Op::Out> {
+        let el = self.prev.next();
+        match &el {
+            StreamElement::Item(_) | StreamElement::Timestamped(_, _) => self.count(),
+            StreamElement::FlushBatch
+            | StreamElement::FlushAndRestart
+            | StreamElement::Terminate => self.publish(),
+            StreamElement::Watermark(_) => {}
+        }
+        el
+    }
+
+    fn structure(&self) -> BlockStructure {
+        self.prev
+            .structure()
+            .add_operator(OperatorStructure::new::<Op::Out, _>("Metrics"))
+    }
+}
+
+impl<Op> Stream<Op>
+where
+    Op: Operator + 'static,
+{
+    /// Count the items flowing through this point of the stream, forwarding them unchanged.
+    ///
+    /// The number of items and their average rate are accumulated in the [`StreamMetrics`]
+    /// registered in the environment with the given name, that can be read while the job is
+    /// running from [`StreamContext::metrics`](crate::StreamContext::metrics). The metrics also
+    /// report the bytes that the block of the operator sends to the network, as measured by the
+    /// network layer: the items are not serialized just for counting them.
+    ///
+    /// The metrics are exported by the endpoint started with
+    /// [`RuntimeConfig::with_metrics_port`](crate::RuntimeConfig::with_metrics_port).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # let mut env = StreamContext::new_local();
+    /// let res = env
+    ///     .stream_iter(0..100)
+    ///     .inspect_metrics("numbers")
+    ///     .filter(|n| n % 2 == 0)
+    ///     .collect_vec();
+    /// let numbers = env.metrics("numbers").unwrap();
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(numbers.snapshot().items, 100);
+    /// ```
+    pub fn inspect_metrics(self, name: &str) -> Stream<impl Operator<Out = Op::Out>> {
+        let metrics = self.ctx.lock().metrics.register(name);
+        self.add_operator(|prev| MetricsOperator::new(prev, metrics))
+    }
+
+    /// Close the stream, discarding the items after having counted them like
+    /// [`Stream::inspect_metrics`].
+    ///
+    /// Returns the [`StreamMetrics`] with the counters.
+    pub fn write_metrics(self, name: &str) -> Arc<StreamMetrics> {
+        let metrics = self.ctx.lock().metrics.register(name);
+        self.inspect_metrics(name).for_each(|_| {});
+        metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    use super::prometheus;
+
+    #[test]
+    fn metrics_sink() {
+        // the counters of each environment start from zero
+        for _ in 0..2 {
+            let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+            let metrics = env
+                .stream_par_iter(0..10_000u32)
+                .write_metrics("test_metrics_sink");
+            env.execute_blocking();
+
+            let snapshot = metrics.snapshot();
+            assert_eq!(snapshot.items, 10_000);
+            // the output of the block doesn't leave the host
+            assert_eq!(snapshot.bytes, 0);
+            assert!(prometheus(&[snapshot])
+                .contains("renoir_stream_items_total{name=\"test_metrics_sink\"} 10000"));
+        }
+    }
+}
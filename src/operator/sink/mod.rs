@@ -3,33 +3,134 @@
 //! The actual operators can be found in [`Stream`](crate::Stream) and
 //! [`KeyedStream`](crate::KeyedStream).
 
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+pub use self::csv::CsvWriteOptions;
+pub use self::handoff::Handoff;
+pub use self::json::JsonFormat;
+pub(crate) use self::metrics::{prometheus, MetricsRegistry};
+pub use self::metrics::{MetricsSnapshot, StreamMetrics};
+#[cfg(feature = "parquet")]
+pub use self::parquet::ParquetWriteOptions;
+#[cfg(feature = "redis")]
+pub use self::redis::RedisWriteMode;
+pub use self::transactional::{FileTransactionalSink, TransactionalSink};
+#[cfg(feature = "rdkafka")]
+pub use kafka::KafkaSinkOptions;
+#[cfg(feature = "postgres")]
+pub use postgres::{PostgresSinkConfig, PostgresValue};
 
 #[cfg(feature = "avro")]
 pub(super) mod avro;
 pub(super) mod collect;
+#[cfg(feature = "arrow")]
+pub(super) mod collect_arrow;
 pub(super) mod collect_channel;
 pub(super) mod collect_count;
 pub(super) mod collect_vec;
 pub(super) mod csv;
 pub(super) mod for_each;
+pub(super) mod handoff;
+pub(super) mod json;
 #[cfg(feature = "rdkafka")]
 pub(super) mod kafka;
+pub(super) mod metrics;
 #[cfg(feature = "parquet")]
 pub(super) mod parquet;
+pub(super) mod partitioned;
+#[cfg(feature = "postgres")]
+pub(super) mod postgres;
+pub(super) mod print_table;
+#[cfg(feature = "redis")]
+pub(super) mod redis;
+pub(super) mod transactional;
 pub(super) mod writer;
 
-pub(crate) type StreamOutputRef<Out> = Arc<Mutex<Option<Out>>>;
+/// State shared between a [`StreamOutput`] and the sinks writing into it.
+struct OutputState<Out> {
+    value: Option<Out>,
+    /// Whether the sinks have completed, after which the value will not change anymore.
+    done: bool,
+    wakers: Vec<Waker>,
+}
+
+struct OutputInner<Out> {
+    state: Mutex<OutputState<Out>>,
+    /// Number of live [`StreamOutputRef`] handles.
+    writers: AtomicUsize,
+}
+
+impl<Out> OutputInner<Out> {
+    fn complete(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.done = true;
+        for waker in state.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// Handle used by the sinks to write the result of a [`StreamOutput`].
+///
+/// The output is considered complete when the value is set, or when all the handles have been
+/// dropped (e.g. because the sink is not executed on this host).
+pub(crate) struct StreamOutputRef<Out>(Arc<OutputInner<Out>>);
+
+impl<Out> StreamOutputRef<Out> {
+    /// Store the result of the sink.
+    pub(crate) fn set(&self, value: Out) {
+        self.0.state.lock().unwrap().value = Some(value);
+        self.0.complete();
+    }
+}
+
+impl<Out> Default for StreamOutputRef<Out> {
+    fn default() -> Self {
+        Self(Arc::new(OutputInner {
+            state: Mutex::new(OutputState {
+                value: None,
+                done: false,
+                wakers: Vec::new(),
+            }),
+            writers: AtomicUsize::new(1),
+        }))
+    }
+}
+
+impl<Out> Clone for StreamOutputRef<Out> {
+    fn clone(&self) -> Self {
+        self.0.writers.fetch_add(1, Ordering::AcqRel);
+        Self(self.0.clone())
+    }
+}
+
+impl<Out> Drop for StreamOutputRef<Out> {
+    fn drop(&mut self) {
+        if self.0.writers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.complete();
+        }
+    }
+}
+
+impl<Out> std::fmt::Debug for StreamOutputRef<Out> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamOutputRef").finish_non_exhaustive()
+    }
+}
 
 /// The result of a stream after the execution.
 ///
 /// This will eventually hold the value _after_ the environment has been fully executed. To access
-/// the content of the output you have to call [`StreamOutput::get`].
-pub struct StreamOutput<Out>(StreamOutputRef<Out>);
+/// the content of the output you have to call [`StreamOutput::get`], or
+/// [`StreamOutput::get_async`] to wait for it from async code.
+pub struct StreamOutput<Out>(Arc<OutputInner<Out>>);
 
 impl<Out> From<StreamOutputRef<Out>> for StreamOutput<Out> {
     fn from(value: StreamOutputRef<Out>) -> Self {
-        Self(value)
+        // the handle is dropped here, only the ones owned by the sinks are left
+        Self(value.0.clone())
     }
 }
 
@@ -39,6 +140,93 @@ impl<Out> StreamOutput<Out> {
     /// This will consume the result and return the owned content. If the content has already been
     /// extracted, or if the content is not ready yet, this will return `None`.
     pub fn get(self) -> Option<Out> {
-        self.0.try_lock().expect("Cannot lock output result").take()
+        self.0
+            .state
+            .try_lock()
+            .expect("Cannot lock output result")
+            .value
+            .take()
+    }
+
+    /// Whether the sink producing this output has completed.
+    pub fn is_ready(&self) -> bool {
+        self.0.state.lock().unwrap().done
+    }
+
+    /// Obtain the content of the output without blocking.
+    ///
+    /// Returns [`Poll::Pending`] if the sink producing the output has not completed yet,
+    /// otherwise the content, as in [`StreamOutput::get`]. The output is not consumed, so it can
+    /// be polled again until it is ready.
+    pub fn try_get(&self) -> Poll<Option<Out>> {
+        let mut state = self.0.state.lock().unwrap();
+        if state.done {
+            Poll::Ready(state.value.take())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Wait for the sink producing this output to complete and obtain its content.
+    ///
+    /// The job must be running for the output to complete, for example by awaiting this
+    /// together with `StreamContext::execute` (available with the `tokio` feature).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # #[cfg(feature = "tokio")]
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let env = StreamContext::new_local();
+    /// let res = env.stream_iter(0..10).collect_vec();
+    ///
+    /// let (_, res) = tokio::join!(env.execute(), res.get_async());
+    /// assert_eq!(res.unwrap(), (0..10).collect::<Vec<_>>());
+    /// # });
+    /// ```
+    pub async fn get_async(self) -> Option<Out> {
+        std::future::poll_fn(|cx| {
+            let mut state = self.0.state.lock().unwrap();
+            if state.done {
+                Poll::Ready(state.value.take())
+            } else {
+                if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                    state.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Poll;
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    #[test]
+    fn stream_output_try_get() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env.stream_iter(0..10).collect_vec();
+        assert!(!res.is_ready());
+        assert_eq!(res.try_get(), Poll::Pending);
+        env.execute_blocking();
+        assert!(res.is_ready());
+        assert_eq!(res.try_get(), Poll::Ready(Some((0..10).collect())));
+        // the content has already been taken
+        assert_eq!(res.try_get(), Poll::Ready(None));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stream_output_get_async() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env.stream_iter(0..10).collect_count();
+        let (_, res) = tokio::join!(env.execute(), res.get_async());
+        assert_eq!(res, Some(10));
     }
 }
@@ -18,28 +18,74 @@ use crate::{Replication, Stream};
 
 use super::writer::{sequential_path, WriterOperator};
 
+const DEFAULT_MAX_ROW_GROUP_SIZE: usize = 1024 * 1024;
+
+/// Options for the parquet sinks.
+#[derive(Clone, Debug)]
+pub struct ParquetWriteOptions {
+    schema: Arc<Schema>,
+    compression: Compression,
+    max_row_group_size: usize,
+    single_file: bool,
+}
+
+impl ParquetWriteOptions {
+    /// Write the items with the given arrow schema, one file per replica, with snappy compression
+    /// and the default row group size.
+    pub fn new(schema: Schema) -> Self {
+        Self {
+            schema: Arc::new(schema),
+            compression: Compression::SNAPPY,
+            max_row_group_size: DEFAULT_MAX_ROW_GROUP_SIZE,
+            single_file: false,
+        }
+    }
+
+    /// Set the compression codec of the column chunks.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set the maximum number of rows in a row group.
+    pub fn row_group_size(mut self, rows: usize) -> Self {
+        assert!(rows > 0, "the row group size must be positive");
+        self.max_row_group_size = rows;
+        self
+    }
+
+    /// Merge the output of all the replicas into a single file.
+    pub fn single_file(mut self, single_file: bool) -> Self {
+        self.single_file = single_file;
+        self
+    }
+}
+
 #[derive(Debug)]
 pub struct ParquetSink<T> {
-    // path: PathBuf,
-    /// Reader used to parse the CSV file.
     writer: Option<ArrowWriter<BufWriter<File>>>,
     decoder: Option<Decoder>,
-    schema: Arc<Schema>,
+    options: ParquetWriteOptions,
     _t: PhantomData<T>,
 }
 
-impl<T> Clone for ParquetSink<T> {
-    fn clone(&self) -> Self {
+impl<T> ParquetSink<T> {
+    pub(super) fn new(options: ParquetWriteOptions) -> Self {
         Self {
-            _t: PhantomData,
-            schema: self.schema.clone(),
             writer: None,
             decoder: None,
-            // schema: self.schema.clone(),
+            options,
+            _t: PhantomData,
         }
     }
 }
 
+impl<T> Clone for ParquetSink<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.options.clone())
+    }
+}
+
 impl<T> WriteOperator<T> for ParquetSink<T>
 where
     T: Serialize + Send,
@@ -49,16 +95,14 @@ where
     fn setup(&mut self, destination: Self::Destination) {
         let file = BufWriter::new(File::create(destination).unwrap());
         let props = WriterProperties::builder()
-            .set_compression(Compression::SNAPPY)
+            .set_compression(self.options.compression)
+            .set_max_row_group_size(self.options.max_row_group_size)
             .build();
 
-        let writer = ArrowWriter::try_new(file, self.schema.clone(), Some(props)).unwrap();
+        let schema = self.options.schema.clone();
+        let writer = ArrowWriter::try_new(file, schema.clone(), Some(props)).unwrap();
         self.writer = Some(writer);
-        self.decoder = Some(
-            ReaderBuilder::new(self.schema.clone())
-                .build_decoder()
-                .unwrap(),
-        );
+        self.decoder = Some(ReaderBuilder::new(schema).build_decoder().unwrap());
     }
 
     fn write(&mut self, items: &mut impl Iterator<Item = T>) {
@@ -77,8 +121,8 @@ where
             .flush()
             .expect("failed to decode struct to arrow RecordBatch")
         {
+            // the writer closes the row groups when they reach the configured size
             self.writer.as_mut().unwrap().write(&batch).unwrap();
-            self.writer.as_mut().unwrap().flush().unwrap();
         }
     }
 
@@ -92,13 +136,11 @@ where
     Op: 'static,
     Op::Out: Serialize,
 {
+    /// Write the items of the stream to parquet files, one per replica.
+    ///
+    /// Shorthand for [`Stream::write_parquet`] with the default options.
     pub fn write_parquet_seq<P: Into<PathBuf>>(self, path: P, schema: Schema) {
-        let writer = ParquetSink {
-            writer: None,
-            decoder: None,
-            schema: Arc::new(schema),
-            _t: PhantomData,
-        };
+        let writer = ParquetSink::new(ParquetWriteOptions::new(schema));
         let path = path.into();
         self.add_operator(|prev| {
             WriterOperator::new(prev, writer, |meta| sequential_path(path, meta))
@@ -111,16 +153,44 @@ impl<Op> Stream<Op>
 where
     Op: Operator<Out: ExchangeData> + 'static,
 {
-    pub fn write_parquet_one<P: Into<PathBuf>>(self, path: P, schema: Schema) {
-        let writer = ParquetSink {
-            writer: None,
-            decoder: None,
-            schema: Arc::new(schema),
-            _t: PhantomData,
-        };
+    /// Write the items of the stream as rows of parquet files.
+    ///
+    /// The items are converted to arrow records with the schema given in the options. By default
+    /// each replica writes its own file, named after `path` (see [`Stream::write_parquet_seq`]),
+    /// use [`ParquetWriteOptions::single_file`] to merge all the items into a single file at
+    /// `path`.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::prelude::*;
+    /// # use renoir::operator::sink::ParquetWriteOptions;
+    /// # use arrow::datatypes::{DataType, Field, Schema};
+    /// # use parquet::basic::{Compression, ZstdLevel};
+    /// # let mut env = StreamContext::new_local();
+    /// let schema = Schema::new(vec![Field::new("value", DataType::UInt32, false)]);
+    /// #[derive(serde::Serialize, serde::Deserialize, Clone)]
+    /// struct Row {
+    ///     value: u32,
+    /// }
+    /// env.stream_par_iter(0..100u32).map(|value| Row { value }).write_parquet(
+    ///     "/data/out.parquet",
+    ///     ParquetWriteOptions::new(schema)
+    ///         .compression(Compression::ZSTD(ZstdLevel::default()))
+    ///         .row_group_size(64 * 1024),
+    /// );
+    /// ```
+    pub fn write_parquet<P: Into<PathBuf>>(self, path: P, options: ParquetWriteOptions) {
         let path = path.into();
+        let single_file = options.single_file;
+        let writer = ParquetSink::new(options);
 
-        if matches!(self.block.scheduling.replication, Replication::One) {
+        if !single_file {
+            self.add_operator(|prev| {
+                WriterOperator::new(prev, writer, |meta| sequential_path(path, meta))
+            })
+            .finalize_block();
+        } else if matches!(self.block.scheduling.replication, Replication::One) {
             self.add_operator(|prev| WriterOperator::new(prev, writer, |_| path))
                 .finalize_block();
         } else {
@@ -129,4 +199,11 @@ where
                 .finalize_block();
         }
     }
+
+    /// Write all the items of the stream to a single parquet file.
+    ///
+    /// Shorthand for [`Stream::write_parquet`] with the default options.
+    pub fn write_parquet_one<P: Into<PathBuf>>(self, path: P, schema: Schema) {
+        self.write_parquet(path, ParquetWriteOptions::new(schema).single_file(true))
+    }
 }
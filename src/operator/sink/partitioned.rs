@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::operator::Operator;
+use crate::scheduler::ExecutionMetadata;
+use crate::{CoordUInt, Stream};
+
+use super::csv::{CsvWriteOp, CsvWriteOptions};
+use super::writer::{WriteOperator, WriterOperator};
+
+/// Escape the characters that cannot appear in the name of a partition directory, the same way
+/// Hive does.
+fn escape_partition_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' | '#' | '%' | '\'' | '*' | '/' | ':' | '=' | '?' | '\\' | '\x7f' | '{' | '['
+            | ']' | '^' => escaped.push_str(&format!("%{:02X}", c as u32)),
+            c if c.is_control() => escaped.push_str(&format!("%{:02X}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    if escaped.is_empty() {
+        escaped.push_str("__HIVE_DEFAULT_PARTITION__");
+    }
+    escaped
+}
+
+/// Writer that routes each item to a file inside a `key=value` partition directory.
+///
+/// Each replica writes a `part-<replica id>.<extension>` file inside each partition directory it
+/// receives items for, using a clone of the inner writer.
+pub struct PartitionedWriteOp<T, W, F> {
+    _t: PhantomData<T>,
+    writer: W,
+    partition_fn: F,
+    extension: String,
+    base: PathBuf,
+    replica: CoordUInt,
+    open: HashMap<PathBuf, W>,
+}
+
+impl<T, W, F> PartitionedWriteOp<T, W, F>
+where
+    T: Serialize + Send,
+    W: WriteOperator<T, Destination = PathBuf>,
+    F: Fn(&T) -> Vec<(String, String)> + Clone + Send,
+{
+    pub(crate) fn new(writer: W, partition_fn: F, extension: &str) -> Self {
+        Self {
+            _t: PhantomData,
+            writer,
+            partition_fn,
+            extension: extension.to_string(),
+            base: PathBuf::new(),
+            replica: 0,
+            open: HashMap::new(),
+        }
+    }
+
+    /// Directory of the partition of the item.
+    fn partition_dir(&self, item: &T) -> PathBuf {
+        let mut dir = self.base.clone();
+        for (key, value) in (self.partition_fn)(item) {
+            dir.push(format!("{key}={}", escape_partition_value(&value)));
+        }
+        dir
+    }
+
+    /// Get the writer of the partition, creating it if needed.
+    fn partition_writer(&mut self, dir: PathBuf) -> &mut W {
+        self.open.entry(dir).or_insert_with_key(|dir| {
+            std::fs::create_dir_all(dir).unwrap_or_else(|err| {
+                panic!("PartitionedSink: error while creating {dir:?}: {err:?}")
+            });
+            let mut writer = self.writer.clone();
+            writer.setup(dir.join(format!("part-{:04}.{}", self.replica, self.extension)));
+            writer
+        })
+    }
+}
+
+impl<T, W, F> WriteOperator<T> for PartitionedWriteOp<T, W, F>
+where
+    T: Serialize + Send,
+    W: WriteOperator<T, Destination = PathBuf>,
+    F: Fn(&T) -> Vec<(String, String)> + Clone + Send,
+{
+    type Destination = (PathBuf, CoordUInt);
+
+    fn setup(&mut self, (base, replica): Self::Destination) {
+        self.base = base;
+        self.replica = replica;
+    }
+
+    fn write(&mut self, items: &mut impl Iterator<Item = T>) {
+        // group the items by partition, to write them with a single call to each writer
+        let mut partitions: HashMap<PathBuf, Vec<T>> = HashMap::new();
+        for item in items {
+            partitions
+                .entry(self.partition_dir(&item))
+                .or_default()
+                .push(item);
+        }
+        for (dir, items) in partitions {
+            self.partition_writer(dir).write(&mut items.into_iter());
+        }
+    }
+
+    fn flush(&mut self) {
+        self.open.values_mut().for_each(|w| w.flush());
+    }
+
+    fn finalize(&mut self) {
+        self.open.values_mut().for_each(|w| w.finalize());
+        self.open.clear();
+    }
+}
+
+impl<T, W: Clone, F: Clone> Clone for PartitionedWriteOp<T, W, F> {
+    fn clone(&self) -> Self {
+        Self {
+            _t: PhantomData,
+            writer: self.writer.clone(),
+            partition_fn: self.partition_fn.clone(),
+            extension: self.extension.clone(),
+            base: PathBuf::new(),
+            replica: 0,
+            open: HashMap::new(),
+        }
+    }
+}
+
+impl<Op: Operator> Stream<Op>
+where
+    Op: 'static,
+    Op::Out: Serialize,
+{
+    /// Write the items into a Hive-style partitioned directory layout, in the file
+    /// `part-<replica id>.<extension>` written by `writer` inside the directory of each partition.
+    pub(crate) fn write_partitioned<W, F>(
+        self,
+        base: impl Into<PathBuf>,
+        partition_fn: F,
+        writer: W,
+        extension: &str,
+    ) where
+        W: WriteOperator<Op::Out, Destination = PathBuf> + 'static,
+        F: Fn(&Op::Out) -> Vec<(String, String)> + Clone + Send + 'static,
+    {
+        let base = base.into();
+        let writer = PartitionedWriteOp::new(writer, partition_fn, extension);
+        self.add_operator(|prev| {
+            WriterOperator::new(prev, writer, move |metadata: &ExecutionMetadata| {
+                (base, metadata.global_id)
+            })
+        })
+        .finalize_block();
+    }
+
+    /// Write the items into a Hive-style partitioned directory layout of CSV files.
+    ///
+    /// `partition_fn` returns the list of `(key, value)` pairs identifying the partition of an
+    /// item: the item is written into the `base/key1=value1/key2=value2/` directory, in the file
+    /// `part-<replica id>.csv`. The values are escaped like Hive does.
+    ///
+    /// **Note**: each replica keeps a file open for every partition it has written to, so the
+    /// number of distinct partitions should be limited.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::prelude::*;
+    /// # use renoir::operator::sink::CsvWriteOptions;
+    /// # let mut env = StreamContext::new_local();
+    /// #[derive(serde::Serialize, serde::Deserialize, Clone)]
+    /// struct Sale {
+    ///     date: String,
+    ///     country: String,
+    ///     amount: f64,
+    /// }
+    /// # let sales: Vec<Sale> = vec![];
+    /// // writes files like /data/sales/date=2024-01-01/country=IT/part-0000.csv
+    /// env.stream_iter(sales.into_iter()).write_partitioned_csv(
+    ///     "/data/sales",
+    ///     |s| vec![("date".into(), s.date.clone()), ("country".into(), s.country.clone())],
+    ///     CsvWriteOptions::default(),
+    /// );
+    /// ```
+    pub fn write_partitioned_csv<F>(
+        self,
+        base: impl Into<PathBuf>,
+        partition_fn: F,
+        options: CsvWriteOptions,
+    ) where
+        F: Fn(&Op::Out) -> Vec<(String, String)> + Clone + Send + 'static,
+    {
+        self.write_partitioned(base, partition_fn, CsvWriteOp::with_options(options), "csv")
+    }
+
+    /// Write the items into a Hive-style partitioned directory layout of parquet files, in the
+    /// file `part-<replica id>.parquet` of each partition.
+    ///
+    /// See [`Stream::write_partitioned_csv`] for the layout of the partitions.
+    #[cfg(feature = "parquet")]
+    pub fn write_partitioned_parquet<F>(
+        self,
+        base: impl Into<PathBuf>,
+        partition_fn: F,
+        options: super::ParquetWriteOptions,
+    ) where
+        F: Fn(&Op::Out) -> Vec<(String, String)> + Clone + Send + 'static,
+    {
+        let writer = super::parquet::ParquetSink::new(options);
+        self.write_partitioned(base, partition_fn, writer, "parquet")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+    use crate::operator::sink::CsvWriteOptions;
+
+    use super::escape_partition_value;
+
+    #[test]
+    fn escape_values() {
+        assert_eq!(escape_partition_value("2024-01-01"), "2024-01-01");
+        assert_eq!(escape_partition_value("a/b=c"), "a%2Fb%3Dc");
+        assert_eq!(escape_partition_value(""), "__HIVE_DEFAULT_PARTITION__");
+    }
+
+    #[test]
+    fn partitioned_csv() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        env.stream_par_iter(0..20u32).write_partitioned_csv(
+            dir.path(),
+            |i| {
+                vec![
+                    ("parity".into(), (i % 2).to_string()),
+                    ("tens".into(), (i / 10).to_string()),
+                ]
+            },
+            CsvWriteOptions::default().header(false),
+        );
+        env.execute_blocking();
+
+        for parity in 0..2 {
+            for tens in 0..2 {
+                let part = dir.path().join(format!("parity={parity}/tens={tens}"));
+                let values = std::fs::read_dir(&part)
+                    .unwrap()
+                    .flat_map(|f| {
+                        let content = std::fs::read_to_string(f.unwrap().path()).unwrap();
+                        content
+                            .lines()
+                            .map(|l| l.parse::<u32>().unwrap())
+                            .collect_vec()
+                    })
+                    .sorted()
+                    .collect_vec();
+                let expected = (0..20u32)
+                    .filter(|i| i % 2 == parity && i / 10 == tens)
+                    .collect_vec();
+                assert_eq!(values, expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::BytesMut;
+use tokio_postgres::types::{IsNull, ToSql, Type};
+use tokio_postgres::{Client, NoTls};
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure};
+use crate::operator::{Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::Stream;
+
+/// Maximum number of parameters of a single postgres statement.
+const MAX_PARAMS: usize = u16::MAX as usize;
+
+/// A value written to a column of the table.
+pub type PostgresValue = Box<dyn ToSql + Send + Sync>;
+
+/// Configuration of the PostgreSQL sink.
+///
+/// The items are written into `table` by the `row_fn` given to [`Stream::write_postgres`], which
+/// returns the values of the configured columns, in the same order. The names of the table and of
+/// the columns are quoted, so they are case sensitive; the table can be qualified with its schema
+/// (`schema.table`).
+#[derive(Clone, Debug)]
+pub struct PostgresSinkConfig {
+    connection: String,
+    table: String,
+    columns: Vec<String>,
+    conflict_columns: Vec<String>,
+    batch_size: usize,
+    max_retries: usize,
+    retry_backoff: Duration,
+}
+
+impl PostgresSinkConfig {
+    /// Write into `table` of the database at `connection` (e.g.
+    /// `host=localhost user=postgres dbname=test`), filling the given `columns`.
+    pub fn new<I, S>(connection: &str, table: &str, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let columns: Vec<String> = columns.into_iter().map(Into::into).collect();
+        assert!(!columns.is_empty(), "at least one column is required");
+        Self {
+            connection: connection.to_string(),
+            table: table.to_string(),
+            columns,
+            conflict_columns: Vec::new(),
+            batch_size: 1000,
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(500),
+        }
+    }
+
+    /// Update the existing rows that conflict on the given columns instead of failing
+    /// (`INSERT ... ON CONFLICT (..) DO UPDATE`). The columns must have a unique constraint.
+    ///
+    /// A statement cannot update the same row twice, so when a batch contains more rows with the
+    /// same values of these columns, only the last one is written.
+    pub fn upsert<I, S>(mut self, conflict_columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.conflict_columns = conflict_columns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Maximum number of rows written by a single statement.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "the batch size must be positive");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Retry a failed statement up to `max_retries` times, waiting `backoff` (doubled at every
+    /// attempt) between the attempts. The connection is reopened before retrying.
+    pub fn retry(mut self, max_retries: usize, backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Build the statement inserting `rows` rows.
+    fn statement(&self, rows: usize) -> String {
+        let cols = self.columns.len();
+        let table: Vec<_> = self.table.split('.').map(quote).collect();
+        let columns: Vec<_> = self.columns.iter().map(|c| quote(c)).collect();
+        let mut stmt = format!(
+            "INSERT INTO {} ({}) VALUES ",
+            table.join("."),
+            columns.join(", ")
+        );
+        for r in 0..rows {
+            if r > 0 {
+                stmt.push_str(", ");
+            }
+            stmt.push('(');
+            for c in 0..cols {
+                if c > 0 {
+                    stmt.push_str(", ");
+                }
+                stmt.push_str(&format!("${}", r * cols + c + 1));
+            }
+            stmt.push(')');
+        }
+        if !self.conflict_columns.is_empty() {
+            let updates: Vec<_> = self
+                .columns
+                .iter()
+                .filter(|c| !self.conflict_columns.contains(c))
+                .map(|c| format!("{0} = EXCLUDED.{0}", quote(c)))
+                .collect();
+            let conflict: Vec<_> = self.conflict_columns.iter().map(|c| quote(c)).collect();
+            stmt.push_str(&format!(" ON CONFLICT ({}) DO ", conflict.join(", ")));
+            if updates.is_empty() {
+                stmt.push_str("NOTHING");
+            } else {
+                stmt.push_str(&format!("UPDATE SET {}", updates.join(", ")));
+            }
+        }
+        stmt
+    }
+
+    /// Keep only the last of the rows with the same values of the conflict columns, whose types
+    /// are given. The values are compared by their binary encoding, and the rows with a `NULL`
+    /// value are all kept, since they never conflict.
+    fn dedup(&self, rows: Vec<Vec<PostgresValue>>, types: &[Type]) -> Vec<Vec<PostgresValue>> {
+        let key_columns: Vec<usize> = (0..self.columns.len())
+            .filter(|&c| self.conflict_columns.contains(&self.columns[c]))
+            .collect();
+        let keys: Vec<Option<Vec<BytesMut>>> =
+            rows.iter()
+                .map(|row| {
+                    key_columns
+                        .iter()
+                        .map(|&c| {
+                            let mut buf = BytesMut::new();
+                            let is_null = row[c]
+                                .to_sql_checked(&types[c], &mut buf)
+                                .unwrap_or_else(|e| {
+                                    panic!("invalid value of {}: {e}", self.columns[c])
+                                });
+                            match is_null {
+                                IsNull::Yes => None,
+                                IsNull::No => Some(buf),
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+        let mut last = HashMap::new();
+        for (i, key) in keys.iter().enumerate() {
+            if let Some(key) = key {
+                last.insert(key, i);
+            }
+        }
+        rows.into_iter()
+            .zip(&keys)
+            .enumerate()
+            .filter(|(i, (_, key))| key.as_ref().is_none_or(|key| last[key] == *i))
+            .map(|(_, (row, _))| row)
+            .collect()
+    }
+}
+
+/// Quote an identifier, so that it's not interpreted as a keyword or SQL code.
+fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct PostgresSink<Op, F>
+where
+    Op: Operator,
+    F: Fn(&Op::Out) -> Vec<PostgresValue> + Clone + Send + 'static,
+{
+    prev: Op,
+    config: Arc<PostgresSinkConfig>,
+    #[derivative(Debug = "ignore")]
+    row_fn: F,
+    #[derivative(Debug = "ignore")]
+    rows: Vec<Vec<PostgresValue>>,
+    #[derivative(Debug = "ignore")]
+    client: Option<Arc<Client>>,
+    /// The types of the columns, resolved from the statement the first time they are needed.
+    types: Option<Vec<Type>>,
+    rt: Option<tokio::runtime::Handle>,
+}
+
+impl<Op, F> PostgresSink<Op, F>
+where
+    Op: Operator,
+    F: Fn(&Op::Out) -> Vec<PostgresValue> + Clone + Send + 'static,
+{
+    pub(crate) fn new(prev: Op, config: PostgresSinkConfig, row_fn: F) -> Self {
+        Self {
+            prev,
+            config: Arc::new(config),
+            row_fn,
+            rows: Vec::new(),
+            client: None,
+            types: None,
+            rt: None,
+        }
+    }
+
+    /// Maximum number of rows per statement, respecting the limit on the number of parameters.
+    fn max_rows(&self) -> usize {
+        self.config
+            .batch_size
+            .min(MAX_PARAMS / self.config.columns.len())
+    }
+
+    async fn connect(config: &PostgresSinkConfig) -> Result<Client, tokio_postgres::Error> {
+        let (client, connection) = tokio_postgres::connect(&config.connection, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::error!("postgres connection error: {e}");
+            }
+        });
+        Ok(client)
+    }
+
+    /// Run `op` with the connection, reopening it and retrying in case of failure.
+    fn with_retries<T, Fut>(&mut self, op: impl Fn(Arc<Client>) -> Fut) -> T
+    where
+        Fut: Future<Output = Result<T, tokio_postgres::Error>>,
+    {
+        let rt = self.rt.clone().unwrap();
+        let config = self.config.clone();
+        let mut backoff = config.retry_backoff;
+        let mut attempt = 0;
+        loop {
+            let result = rt.block_on(async {
+                if self.client.as_ref().map(|c| c.is_closed()).unwrap_or(true) {
+                    self.client = Some(Arc::new(Self::connect(&config).await?));
+                }
+                op(self.client.clone().unwrap()).await
+            });
+            match result {
+                Ok(res) => return res,
+                Err(e) if attempt < config.max_retries => {
+                    attempt += 1;
+                    tracing::warn!(
+                        "postgres write failed (attempt {attempt}/{}): {e}",
+                        config.max_retries
+                    );
+                    self.client = None;
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                }
+                Err(e) => panic!("failed to write to postgres table {}: {e}", config.table),
+            }
+        }
+    }
+
+    /// Write the buffered rows, retrying in case of failure.
+    fn write_rows(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let config = self.config.clone();
+        let max_rows = self.max_rows();
+        let mut rows = std::mem::take(&mut self.rows);
+        if !config.conflict_columns.is_empty() {
+            if self.types.is_none() {
+                let stmt = config.statement(1);
+                let stmt = stmt.as_str();
+                let types = self.with_retries(|client| async move {
+                    let stmt = client.prepare(stmt).await?;
+                    Ok(stmt.params().to_vec())
+                });
+                self.types = Some(types);
+            }
+            rows = config.dedup(rows, self.types.as_ref().unwrap());
+        }
+        for chunk in rows.chunks(max_rows) {
+            let stmt = config.statement(chunk.len());
+            let stmt = stmt.as_str();
+            let params: Vec<&(dyn ToSql + Sync)> = chunk
+                .iter()
+                .flatten()
+                .map(|v| v.as_ref() as &(dyn ToSql + Sync))
+                .collect();
+            let params = params.as_slice();
+            self.with_retries(|client| async move { client.execute(stmt, params).await });
+        }
+    }
+}
+
+impl<Op, F> Clone for PostgresSink<Op, F>
+where
+    Op: Operator,
+    F: Fn(&Op::Out) -> Vec<PostgresValue> + Clone + Send + 'static,
+{
+    fn clone(&self) -> Self {
+        assert!(
+            self.client.is_none() && self.rows.is_empty(),
+            "PostgresSink must be cloned before calling setup"
+        );
+        Self {
+            prev: self.prev.clone(),
+            config: self.config.clone(),
+            row_fn: self.row_fn.clone(),
+            rows: Vec::new(),
+            client: None,
+            types: None,
+            rt: None,
+        }
+    }
+}
+
+impl<Op, F> Display for PostgresSink<Op, F>
+where
+    Op: Operator,
+    F: Fn(&Op::Out) -> Vec<PostgresValue> + Clone + Send + 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> PostgresSink", self.prev)
+    }
+}
+
+impl<Op, F> Operator for PostgresSink<Op, F>
+where
+    Op: Operator,
+    F: Fn(&Op::Out) -> Vec<PostgresValue> + Clone + Send + 'static,
+{
+    type Out = ();
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        // the connection is opened lazily, outside of the runtime context
+        self.rt = Some(tokio::runtime::Handle::current());
+    }
+
+    fn next(&mut self) -> StreamElement<()> {
+        loop {
+            match self.prev.next() {
+                StreamElement::Item(t) | StreamElement::Timestamped(t, _) => {
+                    let row = (self.row_fn)(&t);
+                    assert_eq!(
+                        row.len(),
+                        self.config.columns.len(),
+                        "the number of values does not match the number of columns"
+                    );
+                    self.rows.push(row);
+                    if self.rows.len() >= self.max_rows() {
+                        self.write_rows();
+                    }
+                }
+                StreamElement::Watermark(w) => return StreamElement::Watermark(w),
+                StreamElement::Terminate => return StreamElement::Terminate,
+                StreamElement::FlushBatch => {
+                    self.write_rows();
+                    return StreamElement::FlushBatch;
+                }
+                StreamElement::FlushAndRestart => {
+                    self.write_rows();
+                    return StreamElement::FlushAndRestart;
+                }
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<Op::Out, _>("PostgresSink");
+        operator.kind = OperatorKind::Sink;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+impl<Op> Stream<Op>
+where
+    Op: Operator + 'static,
+{
+    /// Write the items of the stream as rows of a PostgreSQL table.
+    ///
+    /// `row_fn` maps each item to the values of the columns listed in the configuration. The rows
+    /// are written with batched `INSERT` statements (or upserts, see
+    /// [`PostgresSinkConfig::upsert`]) every time the batch is full or the stream is flushed.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::prelude::*;
+    /// # use renoir::operator::sink::{PostgresSinkConfig, PostgresValue};
+    /// # let mut env = StreamContext::new_local();
+    /// let config = PostgresSinkConfig::new(
+    ///     "host=localhost user=postgres dbname=test",
+    ///     "word_count",
+    ///     ["word", "count"],
+    /// )
+    /// .upsert(["word"])
+    /// .batch_size(500);
+    ///
+    /// env.stream_iter(["a", "b", "a"].into_iter())
+    ///     .group_by_count(|w| w.to_string())
+    ///     .unkey()
+    ///     .write_postgres(config, |(w, c)| {
+    ///         vec![Box::new(w.clone()) as PostgresValue, Box::new(*c as i64)]
+    ///     });
+    /// ```
+    pub fn write_postgres<F>(self, config: PostgresSinkConfig, row_fn: F)
+    where
+        F: Fn(&Op::Out) -> Vec<PostgresValue> + Clone + Send + 'static,
+    {
+        self.add_operator(|prev| PostgresSink::new(prev, config, row_fn))
+            .finalize_block();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_postgres::types::Type;
+
+    use super::{PostgresSinkConfig, PostgresValue};
+
+    #[test]
+    fn postgres_statement() {
+        let config = PostgresSinkConfig::new("", "t", ["id", "a", "b"]);
+        assert_eq!(
+            config.statement(2),
+            r#"INSERT INTO "t" ("id", "a", "b") VALUES ($1, $2, $3), ($4, $5, $6)"#
+        );
+
+        let config = config.upsert(["id"]);
+        assert_eq!(
+            config.statement(1),
+            r#"INSERT INTO "t" ("id", "a", "b") VALUES ($1, $2, $3) "#.to_string()
+                + r#"ON CONFLICT ("id") DO UPDATE SET "a" = EXCLUDED."a", "b" = EXCLUDED."b""#
+        );
+    }
+
+    #[test]
+    fn postgres_quote_identifiers() {
+        let config = PostgresSinkConfig::new("", "public.Events", [r#"a"; DROP TABLE t; --"#]);
+        assert_eq!(
+            config.statement(1),
+            r#"INSERT INTO "public"."Events" ("a""; DROP TABLE t; --") VALUES ($1)"#
+        );
+    }
+
+    #[test]
+    fn postgres_upsert_dedup() {
+        let config = PostgresSinkConfig::new("", "t", ["id", "v"]).upsert(["id"]);
+        let rows: [(Option<i32>, i32); 5] = [
+            (Some(1), 1),
+            (Some(2), 2),
+            (Some(1), 3),
+            (None, 4),
+            (None, 5),
+        ];
+        let rows = rows
+            .into_iter()
+            .map(|(id, v)| vec![Box::new(id) as PostgresValue, Box::new(v)])
+            .collect();
+        let rows = config.dedup(rows, &[Type::INT4, Type::INT4]);
+        // the values are checked through their debug representation
+        let rows: Vec<_> = rows.iter().map(|r| format!("{:?}", r[1])).collect();
+        assert_eq!(rows, ["2", "3", "4", "5"]);
+    }
+}
@@ -0,0 +1,262 @@
+use std::fmt::{Display, Write};
+
+use serde::Serialize;
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure};
+use crate::operator::{ExchangeData, Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::{Replication, Stream};
+
+/// Split an item into its header (if it is a struct) and the values of its fields.
+///
+/// The item is serialized as a CSV record, which gives the field names in order of declaration.
+/// Values that cannot be represented as a record are rendered as a single JSON cell.
+fn to_row<T: Serialize>(item: &T) -> (Option<Vec<String>>, Vec<String>) {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(true)
+        .from_writer(Vec::new());
+    let records = writer
+        .serialize(item)
+        .ok()
+        .and_then(|_| writer.into_inner().ok())
+        .and_then(|data| {
+            csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(data.as_slice())
+                .records()
+                .collect::<Result<Vec<_>, _>>()
+                .ok()
+        });
+    let to_vec = |r: &csv::StringRecord| r.iter().map(str::to_string).collect::<Vec<_>>();
+    match records.as_deref() {
+        Some([row]) => (None, to_vec(row)),
+        Some([header, row]) => (Some(to_vec(header)), to_vec(row)),
+        _ => (
+            None,
+            vec![serde_json::to_string(item).unwrap_or_else(|e| format!("<{e}>"))],
+        ),
+    }
+}
+
+/// Render the rows as an aligned ASCII table.
+fn render_table(header: &[String], rows: &[Vec<String>]) -> String {
+    let columns = rows
+        .iter()
+        .map(|r| r.len())
+        .chain(std::iter::once(header.len()))
+        .max()
+        .unwrap_or(0);
+    let mut widths = vec![0; columns];
+    for row in rows.iter().chain(std::iter::once(&header.to_vec())) {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    let separator = |out: &mut String| {
+        for w in &widths {
+            out.push('+');
+            out.push_str(&"-".repeat(w + 2));
+        }
+        out.push_str("+\n");
+    };
+    let line = |out: &mut String, row: &[String]| {
+        for (i, &w) in widths.iter().enumerate() {
+            let cell = row.get(i).map(String::as_str).unwrap_or("");
+            write!(out, "| {cell:<w$} ").unwrap();
+        }
+        out.push_str("|\n");
+    };
+
+    separator(&mut out);
+    line(&mut out, header);
+    separator(&mut out);
+    for row in rows {
+        line(&mut out, row);
+    }
+    separator(&mut out);
+    out
+}
+
+#[derive(Debug, Clone)]
+pub struct PrintTableSink<Op> {
+    prev: Op,
+    limit: usize,
+    header: Option<Vec<String>>,
+    rows: Vec<Vec<String>>,
+    count: usize,
+}
+
+impl<Op: Operator> PrintTableSink<Op>
+where
+    Op::Out: Serialize,
+{
+    pub(crate) fn new(prev: Op, limit: usize) -> Self {
+        Self {
+            prev,
+            limit,
+            header: None,
+            rows: Vec::new(),
+            count: 0,
+        }
+    }
+
+    fn print(&mut self) {
+        let rows = std::mem::take(&mut self.rows);
+        let header = self.header.take().unwrap_or_else(|| {
+            let columns = rows.iter().map(|r| r.len()).max().unwrap_or(1);
+            if columns == 1 {
+                vec!["value".to_string()]
+            } else {
+                (0..columns).map(|i| i.to_string()).collect()
+            }
+        });
+        print!("{}", render_table(&header, &rows));
+        if self.count > rows.len() {
+            println!("({} of {} rows shown)", rows.len(), self.count);
+        } else {
+            println!("({} rows)", self.count);
+        }
+        self.count = 0;
+    }
+}
+
+impl<Op: Operator> Display for PrintTableSink<Op> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> PrintTable", self.prev)
+    }
+}
+
+impl<Op: Operator> Operator for PrintTableSink<Op>
+where
+    Op::Out: Serialize,
+{
+    type Out = ();
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+    }
+
+    fn next(&mut self) -> StreamElement<()> {
+        loop {
+            match self.prev.next() {
+                StreamElement::Item(t) | StreamElement::Timestamped(t, _) => {
+                    if self.rows.len() < self.limit {
+                        let (header, row) = to_row(&t);
+                        if self.header.is_none() {
+                            self.header = header;
+                        }
+                        self.rows.push(row);
+                    }
+                    self.count += 1;
+                }
+                StreamElement::Watermark(w) => return StreamElement::Watermark(w),
+                StreamElement::Terminate => return StreamElement::Terminate,
+                StreamElement::FlushBatch => return StreamElement::FlushBatch,
+                StreamElement::FlushAndRestart => {
+                    self.print();
+                    return StreamElement::FlushAndRestart;
+                }
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<Op::Out, _>("PrintTable");
+        operator.kind = OperatorKind::Sink;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+impl<Op> Stream<Op>
+where
+    Op: Operator + 'static,
+    Op::Out: ExchangeData,
+{
+    /// Print the first `limit` items of the stream to the standard output as an aligned table.
+    ///
+    /// The column headers are the names of the fields of the items, when they are structs.
+    /// Otherwise the columns are numbered. The table is printed when the stream ends, together
+    /// with the total number of items.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # let mut env = StreamContext::new_local();
+    /// #[derive(serde::Serialize, serde::Deserialize, Clone)]
+    /// struct Person {
+    ///     name: String,
+    ///     age: u32,
+    /// }
+    ///
+    /// env.stream_iter(["alice", "bob"].into_iter())
+    ///     .map(|name| Person { name: name.into(), age: 30 })
+    ///     .print_table(10);
+    /// env.execute_blocking();
+    /// // +-------+-----+
+    /// // | name  | age |
+    /// // +-------+-----+
+    /// // | alice | 30  |
+    /// // | bob   | 30  |
+    /// // +-------+-----+
+    /// // (2 rows)
+    /// ```
+    pub fn print_table(self, limit: usize) {
+        self.replication(Replication::One)
+            .add_operator(|prev| PrintTableSink::new(prev, limit))
+            .finalize_block();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::{render_table, to_row};
+
+    #[derive(Serialize)]
+    struct Person {
+        name: String,
+        age: u32,
+    }
+
+    #[test]
+    fn table_rows() {
+        let person = Person {
+            name: "alice".into(),
+            age: 30,
+        };
+        assert_eq!(
+            to_row(&person),
+            (
+                Some(vec!["name".into(), "age".into()]),
+                vec!["alice".into(), "30".into()]
+            )
+        );
+        assert_eq!(to_row(&(1, "a")), (None, vec!["1".into(), "a".into()]));
+        assert_eq!(to_row(&5), (None, vec!["5".into()]));
+    }
+
+    #[test]
+    fn table_render() {
+        let header = vec!["name".to_string(), "age".to_string()];
+        let rows = vec![
+            vec!["alice".to_string(), "30".to_string()],
+            vec!["bob".to_string(), "7".to_string()],
+        ];
+        let expected = "\
++-------+-----+
+| name  | age |
++-------+-----+
+| alice | 30  |
+| bob   | 7   |
++-------+-----+
+";
+        assert_eq!(render_table(&header, &rows), expected);
+    }
+}
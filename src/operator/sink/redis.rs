@@ -0,0 +1,299 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+use redis::{Client, Connection, Pipeline, ToRedisArgs};
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure};
+use crate::operator::{Data, DataKey, Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::KeyedStream;
+
+/// Maximum number of commands sent in a single pipeline.
+const PIPELINE_SIZE: usize = 1024;
+
+/// How the keyed items are written into Redis.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RedisWriteMode {
+    /// `SET key value`, optionally with an expiration time.
+    Set { ttl: Option<Duration> },
+    /// `HSET hash key value`: all the items are fields of the same hash.
+    HSet { hash: String },
+    /// `XADD stream * key value`: each item is appended to a stream, which is optionally capped
+    /// to approximately `max_len` entries.
+    XAdd {
+        stream: String,
+        max_len: Option<usize>,
+    },
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct RedisSink<Op> {
+    prev: Op,
+    url: String,
+    mode: RedisWriteMode,
+    #[derivative(Debug = "ignore")]
+    connection: Option<Connection>,
+    #[derivative(Debug = "ignore")]
+    pipeline: Pipeline,
+    pending: usize,
+}
+
+impl<Op: Clone> Clone for RedisSink<Op> {
+    fn clone(&self) -> Self {
+        assert!(
+            self.connection.is_none(),
+            "RedisSink must be cloned before calling setup"
+        );
+        Self::new(self.prev.clone(), self.url.clone(), self.mode.clone())
+    }
+}
+
+impl<Op> RedisSink<Op> {
+    pub(crate) fn new(prev: Op, url: String, mode: RedisWriteMode) -> Self {
+        Self {
+            prev,
+            url,
+            mode,
+            connection: None,
+            pipeline: redis::pipe(),
+            pending: 0,
+        }
+    }
+
+    fn add<K: ToRedisArgs, V: ToRedisArgs>(&mut self, key: &K, value: &V) {
+        match &self.mode {
+            RedisWriteMode::Set { ttl: None } => {
+                self.pipeline.cmd("SET").arg(key).arg(value).ignore();
+            }
+            RedisWriteMode::Set { ttl: Some(ttl) } => {
+                self.pipeline
+                    .cmd("SET")
+                    .arg(key)
+                    .arg(value)
+                    .arg("PX")
+                    .arg(ttl.as_millis() as u64)
+                    .ignore();
+            }
+            RedisWriteMode::HSet { hash } => {
+                self.pipeline
+                    .cmd("HSET")
+                    .arg(hash)
+                    .arg(key)
+                    .arg(value)
+                    .ignore();
+            }
+            RedisWriteMode::XAdd { stream, max_len } => {
+                let cmd = self.pipeline.cmd("XADD").arg(stream);
+                if let Some(max_len) = max_len {
+                    cmd.arg("MAXLEN").arg("~").arg(*max_len);
+                }
+                cmd.arg("*").arg(key).arg(value).ignore();
+            }
+        }
+        self.pending += 1;
+        if self.pending >= PIPELINE_SIZE {
+            self.flush();
+        }
+    }
+
+    /// Send the pending commands.
+    fn flush(&mut self) {
+        if self.pending == 0 {
+            return;
+        }
+        let connection = self.connection.as_mut().unwrap();
+        self.pipeline
+            .query::<()>(connection)
+            .unwrap_or_else(|e| panic!("failed to write to redis: {e}"));
+        self.pipeline.clear();
+        self.pending = 0;
+    }
+}
+
+impl<Op: Operator> Display for RedisSink<Op> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> RedisSink", self.prev)
+    }
+}
+
+impl<K, V, Op> Operator for RedisSink<Op>
+where
+    K: DataKey + ToRedisArgs,
+    V: Data + ToRedisArgs,
+    Op: Operator<Out = (K, V)>,
+{
+    type Out = ();
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        let connection = Client::open(self.url.as_str())
+            .and_then(|c| c.get_connection())
+            .unwrap_or_else(|e| panic!("failed to connect to redis at {}: {e}", self.url));
+        self.connection = Some(connection);
+    }
+
+    fn next(&mut self) -> StreamElement<()> {
+        loop {
+            match self.prev.next() {
+                StreamElement::Item((k, v)) | StreamElement::Timestamped((k, v), _) => {
+                    self.add(&k, &v)
+                }
+                StreamElement::Watermark(w) => return StreamElement::Watermark(w),
+                StreamElement::Terminate => return StreamElement::Terminate,
+                StreamElement::FlushBatch => {
+                    self.flush();
+                    return StreamElement::FlushBatch;
+                }
+                StreamElement::FlushAndRestart => {
+                    self.flush();
+                    return StreamElement::FlushAndRestart;
+                }
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<Op::Out, _>("RedisSink");
+        operator.kind = OperatorKind::Sink;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+impl<K, V, Op> KeyedStream<Op>
+where
+    K: DataKey + ToRedisArgs,
+    V: Data + ToRedisArgs,
+    Op: Operator<Out = (K, V)> + 'static,
+{
+    /// Write the items of the stream into Redis, using the key of the stream as key of the
+    /// records.
+    ///
+    /// The commands are pipelined and sent every time the stream is flushed.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::prelude::*;
+    /// # use renoir::operator::sink::RedisWriteMode;
+    /// # let mut env = StreamContext::new_local();
+    /// env.stream_iter(["a", "b", "a"].into_iter())
+    ///     .group_by_count(|w| w.to_string())
+    ///     .write_redis(
+    ///         "redis://127.0.0.1/",
+    ///         RedisWriteMode::HSet {
+    ///             hash: "word_count".into(),
+    ///         },
+    ///     );
+    /// ```
+    pub fn write_redis(self, url: &str, mode: RedisWriteMode) {
+        self.0
+            .add_operator(|prev| RedisSink::new(prev, url.to_string(), mode))
+            .finalize_block();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use itertools::Itertools;
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+    use crate::operator::sink::RedisWriteMode;
+
+    type Commands = Arc<Mutex<Vec<Vec<String>>>>;
+
+    /// Start a server that records the commands it receives and answers `+OK` to all of them.
+    fn fake_redis() -> (String, Commands) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("redis://{}/", listener.local_addr().unwrap());
+        let commands = Commands::default();
+        let recorded = commands.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let commands = commands.clone();
+                std::thread::spawn(move || serve(stream, commands));
+            }
+        });
+        (url, recorded)
+    }
+
+    /// Read the commands, each an array of bulk strings, until the connection is closed.
+    fn serve(mut stream: TcpStream, commands: Commands) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut line = String::new();
+        let mut read_len = |reader: &mut BufReader<TcpStream>| -> std::io::Result<Option<usize>> {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            Ok(Some(line.trim_end()[1..].parse().unwrap()))
+        };
+        while let Some(args) = read_len(&mut reader)? {
+            let mut command = Vec::new();
+            for _ in 0..args {
+                let len = read_len(&mut reader)?.unwrap();
+                let mut arg = vec![0; len + 2];
+                reader.read_exact(&mut arg)?;
+                arg.truncate(len);
+                command.push(String::from_utf8(arg).unwrap());
+            }
+            commands.lock().unwrap().push(command);
+            stream.write_all(b"+OK\r\n")?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn write_redis() {
+        let modes = [
+            (
+                RedisWriteMode::Set {
+                    ttl: Some(Duration::from_secs(5)),
+                },
+                vec!["SET", "{n}", "{n}", "PX", "5000"],
+            ),
+            (
+                RedisWriteMode::HSet { hash: "h".into() },
+                vec!["HSET", "h", "{n}", "{n}"],
+            ),
+            (
+                RedisWriteMode::XAdd {
+                    stream: "s".into(),
+                    max_len: Some(10),
+                },
+                vec!["XADD", "s", "MAXLEN", "~", "10", "*", "{n}", "{n}"],
+            ),
+        ];
+        for (mode, command) in modes {
+            let (url, commands) = fake_redis();
+            let env = StreamContext::new(RuntimeConfig::local(2).unwrap());
+            env.stream_iter(0..100u32)
+                .key_by(|&n| n)
+                .write_redis(&url, mode.clone());
+            env.execute_blocking();
+
+            let written = commands
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|c| c[0] == command[0])
+                .cloned()
+                .sorted()
+                .collect_vec();
+            let expected = (0..100u32)
+                .map(|n| {
+                    let n = n.to_string();
+                    command.iter().map(|a| a.replace("{n}", &n)).collect_vec()
+                })
+                .sorted()
+                .collect_vec();
+            assert_eq!(written, expected, "{mode:?}");
+        }
+    }
+}
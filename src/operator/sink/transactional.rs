@@ -0,0 +1,428 @@
+use std::collections::VecDeque;
+use std::fmt::{Debug, Display};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure};
+use crate::checkpoint::{CheckpointId, OperatorState};
+use crate::config::DeliveryGuarantee;
+use crate::operator::{Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::{CoordUInt, Stream};
+
+/// Name of the directory holding the files of the transactions not committed yet.
+const PENDING_DIR: &str = ".pending";
+
+/// A sink that writes to an external system using a two-phase commit protocol.
+///
+/// The items are written inside transactions, each one identified by an increasing epoch. At
+/// the end of an epoch the transaction is first prepared, making its data durable but still
+/// invisible, and then committed. A transaction that is prepared but not committed can be
+/// committed again after a failure, so `commit` must be idempotent.
+///
+/// Without checkpoints an epoch ends when the stream is flushed at the end of an iteration or of
+/// the stream. With the checkpoints an epoch ends at the barrier of each checkpoint: with
+/// at-least-once delivery it's committed immediately, while with exactly-once delivery it's
+/// prepared at the barrier and committed only when the checkpoint is completed by all the hosts,
+/// since a checkpoint completed only by some of them cannot be restored. When the job is
+/// restored from a checkpoint, the transactions it prepared are committed again.
+///
+/// The transactions that are still open when the sink is dropped must be discarded by the
+/// implementation.
+pub trait TransactionalSink<T>: Clone + Send {
+    type Error: Debug;
+
+    /// Called once per replica before any transaction is started.
+    fn setup(&mut self, _metadata: &ExecutionMetadata) {}
+    /// Start the transaction of `epoch`.
+    fn begin(&mut self, epoch: u64) -> Result<(), Self::Error>;
+    /// Write an item inside the current transaction.
+    fn write(&mut self, item: T) -> Result<(), Self::Error>;
+    /// Make the data of the current transaction durable, ready to be committed.
+    fn prepare(&mut self, epoch: u64) -> Result<(), Self::Error>;
+    /// Make the data of a prepared transaction visible.
+    fn commit(&mut self, epoch: u64) -> Result<(), Self::Error>;
+    /// Discard the data of the transaction of `epoch`.
+    fn abort(&mut self, epoch: u64);
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactionalSinkOperator<Op, S> {
+    prev: Op,
+    sink: S,
+    epoch: u64,
+    /// Whether a transaction for the current epoch has been started.
+    open: bool,
+    /// The prepared transactions, with the checkpoint that has to be completed to commit them.
+    prepared: VecDeque<(CheckpointId, u64)>,
+    /// Saves the next epoch and the prepared transactions in the checkpoints.
+    checkpoint: OperatorState<(u64, Vec<u64>)>,
+}
+
+impl<Op, S> TransactionalSinkOperator<Op, S>
+where
+    Op: Operator,
+    S: TransactionalSink<Op::Out>,
+{
+    pub(crate) fn new(prev: Op, sink: S) -> Self {
+        Self {
+            prev,
+            sink,
+            epoch: 0,
+            open: false,
+            prepared: Default::default(),
+            checkpoint: OperatorState::new(),
+        }
+    }
+
+    fn write(&mut self, item: Op::Out) {
+        if !self.open {
+            self.sink
+                .begin(self.epoch)
+                .unwrap_or_else(|e| panic!("failed to begin epoch {}: {e:?}", self.epoch));
+            self.open = true;
+        }
+        if let Err(e) = self.sink.write(item) {
+            self.sink.abort(self.epoch);
+            panic!("failed to write in epoch {}: {e:?}", self.epoch);
+        }
+    }
+
+    /// Prepare and commit the transaction of the current epoch, if any, and move to the next one.
+    fn end_epoch(&mut self) {
+        if self.prepare() {
+            self.commit(self.epoch - 1);
+        }
+    }
+
+    /// Prepare the transaction of the current epoch, if any, and move to the next one.
+    ///
+    /// Returns whether a transaction has been prepared.
+    fn prepare(&mut self) -> bool {
+        let open = std::mem::take(&mut self.open);
+        if open {
+            if let Err(e) = self.sink.prepare(self.epoch) {
+                self.sink.abort(self.epoch);
+                panic!("failed to prepare epoch {}: {e:?}", self.epoch);
+            }
+        }
+        self.epoch += 1;
+        open
+    }
+
+    fn commit(&mut self, epoch: u64) {
+        self.sink
+            .commit(epoch)
+            .unwrap_or_else(|e| panic!("failed to commit epoch {epoch}: {e:?}"));
+    }
+
+    /// Commit the prepared transactions whose checkpoint has been completed.
+    fn commit_completed(&mut self) {
+        let Some(completed) = self.checkpoint.replica().map(|c| c.last_completed()) else {
+            return;
+        };
+        while let Some(&(id, epoch)) = self.prepared.front() {
+            if id > completed {
+                break;
+            }
+            self.commit(epoch);
+            self.prepared.pop_front();
+        }
+    }
+
+    /// The barrier of the checkpoint `id` has been received: end the current epoch.
+    fn barrier(&mut self, id: CheckpointId) {
+        let guarantee = self.checkpoint.replica().map(|c| c.guarantee());
+        if guarantee != Some(DeliveryGuarantee::ExactlyOnce) {
+            self.end_epoch();
+            return;
+        }
+        if self.prepare() {
+            self.prepared.push_back((id, self.epoch - 1));
+        }
+        let prepared = self.prepared.iter().map(|&(_, epoch)| epoch).collect();
+        self.checkpoint.snapshot(&(self.epoch, prepared));
+    }
+}
+
+impl<Op: Operator, S> Display for TransactionalSinkOperator<Op, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> TransactionalSink<{}>",
+            self.prev,
+            std::any::type_name::<S>()
+        )
+    }
+}
+
+impl<Op, S> Operator for TransactionalSinkOperator<Op, S>
+where
+    Op: Operator,
+    S: TransactionalSink<Op::Out>,
+{
+    type Out = ();
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        self.sink.setup(metadata);
+        self.checkpoint.setup(metadata);
+        if let Some((epoch, prepared)) = self.checkpoint.restore() {
+            // these transactions are part of the restored checkpoint, which is completed
+            for epoch in prepared {
+                self.commit(epoch);
+            }
+            self.epoch = epoch;
+        }
+    }
+
+    fn next(&mut self) -> StreamElement<()> {
+        // with checkpoints the epochs end at the barriers
+        let checkpointed = self.checkpoint.replica().is_some();
+        loop {
+            if !self.prepared.is_empty() {
+                self.commit_completed();
+            }
+            match self.prev.next() {
+                StreamElement::Item(t) | StreamElement::Timestamped(t, _) => self.write(t),
+                StreamElement::Watermark(w) => return StreamElement::Watermark(w),
+                StreamElement::FlushBatch => {
+                    if let Some(id) = self.checkpoint.replica().and_then(|c| c.in_progress()) {
+                        self.barrier(id);
+                    }
+                    return StreamElement::FlushBatch;
+                }
+                StreamElement::FlushAndRestart => {
+                    if !checkpointed {
+                        self.end_epoch();
+                    }
+                    return StreamElement::FlushAndRestart;
+                }
+                StreamElement::Terminate => {
+                    // the stream has ended, there is nothing left to replay
+                    while let Some((_, epoch)) = self.prepared.pop_front() {
+                        self.commit(epoch);
+                    }
+                    if self.open {
+                        self.end_epoch();
+                    }
+                    return StreamElement::Terminate;
+                }
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<Op::Out, _>("TransactionalSink");
+        operator.kind = OperatorKind::Sink;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+/// Reference [`TransactionalSink`] writing newline delimited JSON files.
+///
+/// Each transaction is written to a file inside the `.pending` subdirectory, which is moved
+/// into the output directory when the transaction is committed, as
+/// `part-<replica id>-<epoch>.ndjson`. Since the rename is atomic, readers of the output
+/// directory only see the files of committed transactions.
+pub struct FileTransactionalSink<T> {
+    _t: PhantomData<T>,
+    dir: PathBuf,
+    replica: CoordUInt,
+    epoch: u64,
+    writer: Option<BufWriter<File>>,
+}
+
+impl<T> FileTransactionalSink<T> {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            _t: PhantomData,
+            dir: dir.into(),
+            replica: 0,
+            epoch: 0,
+            writer: None,
+        }
+    }
+
+    fn file_name(&self, epoch: u64) -> String {
+        format!("part-{:04}-{epoch:06}.ndjson", self.replica)
+    }
+
+    fn pending_path(&self, epoch: u64) -> PathBuf {
+        self.dir.join(PENDING_DIR).join(self.file_name(epoch))
+    }
+
+    fn committed_path(&self, epoch: u64) -> PathBuf {
+        self.dir.join(self.file_name(epoch))
+    }
+}
+
+impl<T> Clone for FileTransactionalSink<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.dir.clone())
+    }
+}
+
+impl<T: Serialize + Send> TransactionalSink<T> for FileTransactionalSink<T> {
+    type Error = std::io::Error;
+
+    fn setup(&mut self, metadata: &ExecutionMetadata) {
+        self.replica = metadata.global_id;
+        std::fs::create_dir_all(self.dir.join(PENDING_DIR)).unwrap_or_else(|err| {
+            panic!(
+                "FileTransactionalSink: error while creating {:?}: {err:?}",
+                self.dir
+            )
+        });
+    }
+
+    fn begin(&mut self, epoch: u64) -> Result<(), Self::Error> {
+        self.epoch = epoch;
+        let file = File::create(self.pending_path(epoch))?;
+        self.writer = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    fn write(&mut self, item: T) -> Result<(), Self::Error> {
+        let writer = self.writer.as_mut().expect("no open transaction");
+        serde_json::to_writer(&mut *writer, &item)?;
+        writer.write_all(b"\n")
+    }
+
+    fn prepare(&mut self, _epoch: u64) -> Result<(), Self::Error> {
+        if let Some(writer) = self.writer.take() {
+            let file = writer.into_inner().map_err(|e| e.into_error())?;
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self, epoch: u64) -> Result<(), Self::Error> {
+        let committed = self.committed_path(epoch);
+        match std::fs::rename(self.pending_path(epoch), &committed) {
+            // the transaction has already been committed
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && committed.exists() => Ok(()),
+            res => res,
+        }
+    }
+
+    fn abort(&mut self, epoch: u64) {
+        self.writer.take();
+        let _ = std::fs::remove_file(self.pending_path(epoch));
+    }
+}
+
+impl<T> Drop for FileTransactionalSink<T> {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            let _ = std::fs::remove_file(self.pending_path(self.epoch));
+        }
+    }
+}
+
+impl<Op> Stream<Op>
+where
+    Op: Operator + 'static,
+{
+    /// Write the items of the stream using a [`TransactionalSink`].
+    ///
+    /// Each replica writes its items in a transaction per epoch, that is committed with a
+    /// two-phase commit when the epoch ends. With the checkpoints enabled and exactly-once
+    /// delivery, see [`DeliveryGuarantee`], the output is committed only when the checkpoints
+    /// are completed, so a job restored from a checkpoint never writes an item twice.
+    pub fn write_transactional<S>(self, sink: S)
+    where
+        S: TransactionalSink<Op::Out> + 'static,
+    {
+        self.add_operator(|prev| TransactionalSinkOperator::new(prev, sink))
+            .finalize_block();
+    }
+
+    /// Write the items of the stream as newline delimited JSON files inside `dir`, making them
+    /// visible only when their transaction is committed. See [`FileTransactionalSink`].
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::prelude::*;
+    /// # let mut env = StreamContext::new_local();
+    /// env.stream_par_iter(0..100)
+    ///     .map(|i| (i, i * i))
+    ///     .write_json_transactional("/data/squares");
+    /// ```
+    pub fn write_json_transactional(self, dir: impl Into<PathBuf>)
+    where
+        Op::Out: Serialize,
+    {
+        self.write_transactional(FileTransactionalSink::new(dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    use super::{FileTransactionalSink, TransactionalSink, PENDING_DIR};
+
+    #[test]
+    fn file_transactional_commit() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        env.stream_par_iter(0..100u32)
+            .write_json_transactional(dir.path());
+        env.execute_blocking();
+
+        let pending = std::fs::read_dir(dir.path().join(PENDING_DIR)).unwrap();
+        assert_eq!(pending.count(), 0);
+
+        let values = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.is_file())
+            .flat_map(|p| {
+                std::fs::read_to_string(p)
+                    .unwrap()
+                    .lines()
+                    .map(|l| l.parse::<u32>().unwrap())
+                    .collect_vec()
+            })
+            .sorted()
+            .collect_vec();
+        assert_eq!(values, (0..100).collect_vec());
+    }
+
+    #[test]
+    fn file_transactional_abort() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(PENDING_DIR)).unwrap();
+
+        let mut sink = FileTransactionalSink::new(dir.path());
+        sink.begin(0).unwrap();
+        sink.write(1).unwrap();
+        sink.prepare(0).unwrap();
+        sink.commit(0).unwrap();
+        // committing again is a no-op
+        sink.commit(0).unwrap();
+
+        sink.begin(1).unwrap();
+        sink.write(2).unwrap();
+        sink.abort(1);
+
+        let files = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .sorted()
+            .collect_vec();
+        assert_eq!(files, vec![PENDING_DIR, "part-0000-000000.ndjson"]);
+        let pending = std::fs::read_dir(dir.path().join(PENDING_DIR)).unwrap();
+        assert_eq!(pending.count(), 0);
+    }
+}
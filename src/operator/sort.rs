@@ -0,0 +1,428 @@
+//! Operators for the distributed sort, see [`Stream::sorted_global_by`](crate::Stream::sorted_global_by).
+//!
+//! The sort is a sample sort in three steps:
+//! 1. a random sample of the items is collected on a single replica and sorted, then it is sent to
+//!    every replica, where it is used to split the range of the items into one partition per
+//!    replica;
+//! 2. each item is sent to the replica of its partition, that sorts it with the other items of the
+//!    same partition;
+//! 3. the sorted partitions are concatenated in order on a single replica.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Display;
+
+use nanorand::{tls_rng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::block::{BlockStructure, OperatorStructure};
+use crate::operator::start::BinaryElement;
+use crate::operator::{ExchangeData, Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+
+/// Number of items sampled by each replica for choosing the ranges of the partitions.
+const SAMPLES_PER_REPLICA: usize = 128;
+
+/// Add an item to the reservoir sample of a replica.
+pub(super) fn sample_item<T>((count, sample): &mut (u64, Vec<T>), item: T) {
+    *count += 1;
+    if sample.len() < SAMPLES_PER_REPLICA {
+        sample.push(item);
+    } else {
+        let i = tls_rng().generate_range(0..*count) as usize;
+        if i < SAMPLES_PER_REPLICA {
+            sample[i] = item;
+        }
+    }
+}
+
+/// Merge the samples of two replicas.
+pub(super) fn merge_samples<T>(acc: &mut (u64, Vec<T>), (count, sample): (u64, Vec<T>)) {
+    acc.0 += count;
+    acc.1.extend(sample);
+}
+
+/// An item of a sorted partition, or the end of a partition.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum SortedRun<T> {
+    Item(u64, T),
+    End(u64),
+}
+
+/// Assign each item to a partition, using the sorted sample to choose the ranges.
+///
+/// The items are kept until the sample is received.
+#[derive(Clone)]
+pub(crate) struct RangePartition<T, F, Op>
+where
+    Op: Operator<Out = BinaryElement<T, Vec<T>>>,
+    T: ExchangeData,
+    F: Fn(&T, &T) -> Ordering + Clone + Send,
+{
+    prev: Op,
+    compare: F,
+    partitions: usize,
+    sample: Vec<T>,
+    /// The first item of each partition but the first, available when the sample has been
+    /// received.
+    splitters: Option<Vec<T>>,
+    buffer: Vec<T>,
+}
+
+impl<T, F, Op> RangePartition<T, F, Op>
+where
+    Op: Operator<Out = BinaryElement<T, Vec<T>>>,
+    T: ExchangeData,
+    F: Fn(&T, &T) -> Ordering + Clone + Send,
+{
+    pub(super) fn new(prev: Op, compare: F) -> Self {
+        Self {
+            prev,
+            compare,
+            partitions: 1,
+            sample: Vec::new(),
+            splitters: None,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn partition(&self, item: &T) -> u64 {
+        let splitters = self.splitters.as_ref().unwrap();
+        splitters.partition_point(|s| (self.compare)(s, item) == Ordering::Less) as u64
+    }
+}
+
+impl<T, F, Op> Display for RangePartition<T, F, Op>
+where
+    Op: Operator<Out = BinaryElement<T, Vec<T>>>,
+    T: ExchangeData,
+    F: Fn(&T, &T) -> Ordering + Clone + Send,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> RangePartition<{}>",
+            self.prev,
+            std::any::type_name::<T>()
+        )
+    }
+}
+
+impl<T, F, Op> Operator for RangePartition<T, F, Op>
+where
+    Op: Operator<Out = BinaryElement<T, Vec<T>>>,
+    T: ExchangeData,
+    F: Fn(&T, &T) -> Ordering + Clone + Send,
+{
+    type Out = (u64, T);
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+        self.partitions = metadata.replicas.len();
+    }
+
+    fn next(&mut self) -> StreamElement<(u64, T)> {
+        loop {
+            if self.splitters.is_some() {
+                if let Some(item) = self.buffer.pop() {
+                    return StreamElement::Item((self.partition(&item), item));
+                }
+            }
+            match self.prev.next() {
+                StreamElement::Item(BinaryElement::Left(item))
+                | StreamElement::Timestamped(BinaryElement::Left(item), _) => {
+                    if self.splitters.is_some() {
+                        return StreamElement::Item((self.partition(&item), item));
+                    }
+                    self.buffer.push(item);
+                }
+                StreamElement::Item(BinaryElement::Right(sample))
+                | StreamElement::Timestamped(BinaryElement::Right(sample), _) => {
+                    self.sample.extend(sample)
+                }
+                StreamElement::Item(BinaryElement::RightEnd)
+                | StreamElement::Timestamped(BinaryElement::RightEnd, _) => {
+                    let mut sample = std::mem::take(&mut self.sample);
+                    sample.sort_by(&self.compare);
+                    let n = sample.len();
+                    let splitters = if n == 0 {
+                        Vec::new()
+                    } else {
+                        (1..self.partitions)
+                            .map(|i| sample[i * n / self.partitions].clone())
+                            .collect()
+                    };
+                    self.splitters = Some(splitters);
+                }
+                StreamElement::Item(BinaryElement::LeftEnd)
+                | StreamElement::Timestamped(BinaryElement::LeftEnd, _)
+                | StreamElement::Watermark(_) => {}
+                StreamElement::FlushBatch => return StreamElement::FlushBatch,
+                StreamElement::FlushAndRestart => {
+                    debug_assert!(self.buffer.is_empty());
+                    self.splitters = None;
+                    return StreamElement::FlushAndRestart;
+                }
+                StreamElement::Terminate => return StreamElement::Terminate,
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        self.prev
+            .structure()
+            .add_operator(OperatorStructure::new::<(u64, T), _>("RangePartition"))
+    }
+}
+
+/// Sort the items of each partition, sending them followed by the end of the partition.
+#[derive(Clone)]
+pub(crate) struct SortPartitions<T, F, Op>
+where
+    Op: Operator<Out = (u64, T)>,
+    T: ExchangeData,
+    F: Fn(&T, &T) -> Ordering + Clone + Send,
+{
+    prev: Op,
+    compare: F,
+    partitions: BTreeMap<u64, Vec<T>>,
+    /// The sorted partitions being sent.
+    flushing: VecDeque<(u64, std::vec::IntoIter<T>)>,
+    /// The element to return after all the partitions have been sent.
+    end: Option<StreamElement<SortedRun<T>>>,
+}
+
+impl<T, F, Op> SortPartitions<T, F, Op>
+where
+    Op: Operator<Out = (u64, T)>,
+    T: ExchangeData,
+    F: Fn(&T, &T) -> Ordering + Clone + Send,
+{
+    pub(super) fn new(prev: Op, compare: F) -> Self {
+        Self {
+            prev,
+            compare,
+            partitions: Default::default(),
+            flushing: Default::default(),
+            end: None,
+        }
+    }
+}
+
+impl<T, F, Op> Display for SortPartitions<T, F, Op>
+where
+    Op: Operator<Out = (u64, T)>,
+    T: ExchangeData,
+    F: Fn(&T, &T) -> Ordering + Clone + Send,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> SortPartitions<{}>",
+            self.prev,
+            std::any::type_name::<T>()
+        )
+    }
+}
+
+impl<T, F, Op> Operator for SortPartitions<T, F, Op>
+where
+    Op: Operator<Out = (u64, T)>,
+    T: ExchangeData,
+    F: Fn(&T, &T) -> Ordering + Clone + Send,
+{
+    type Out = SortedRun<T>;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+    }
+
+    fn next(&mut self) -> StreamElement<SortedRun<T>> {
+        loop {
+            if let Some((partition, items)) = self.flushing.front_mut() {
+                let partition = *partition;
+                return match items.next() {
+                    Some(item) => StreamElement::Item(SortedRun::Item(partition, item)),
+                    None => {
+                        self.flushing.pop_front();
+                        StreamElement::Item(SortedRun::End(partition))
+                    }
+                };
+            }
+            if let Some(end) = self.end.take() {
+                return end;
+            }
+            match self.prev.next() {
+                StreamElement::Item((partition, item))
+                | StreamElement::Timestamped((partition, item), _) => {
+                    self.partitions.entry(partition).or_default().push(item)
+                }
+                StreamElement::Watermark(_) => {}
+                StreamElement::FlushBatch => return StreamElement::FlushBatch,
+                el @ (StreamElement::FlushAndRestart | StreamElement::Terminate) => {
+                    for (partition, mut items) in std::mem::take(&mut self.partitions) {
+                        items.sort_by(&self.compare);
+                        self.flushing.push_back((partition, items.into_iter()));
+                    }
+                    self.end = Some(el.map(|_| unreachable!()));
+                }
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<SortedRun<T>, _>("SortPartitions");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+/// Concatenate the sorted partitions in order.
+///
+/// The items of the partition being sent are forwarded as soon as they arrive, the ones of the
+/// following partitions are kept until all the previous partitions have ended.
+#[derive(Clone)]
+pub(crate) struct MergePartitions<T, Op>
+where
+    Op: Operator<Out = SortedRun<T>>,
+{
+    prev: Op,
+    current: u64,
+    runs: BTreeMap<u64, VecDeque<T>>,
+    ended: BTreeSet<u64>,
+    /// The element to return after all the partitions have been sent.
+    closing: Option<StreamElement<T>>,
+}
+
+impl<T, Op> MergePartitions<T, Op>
+where
+    Op: Operator<Out = SortedRun<T>>,
+{
+    pub(super) fn new(prev: Op) -> Self {
+        Self {
+            prev,
+            current: 0,
+            runs: Default::default(),
+            ended: Default::default(),
+            closing: None,
+        }
+    }
+}
+
+impl<T, Op> Display for MergePartitions<T, Op>
+where
+    Op: Operator<Out = SortedRun<T>>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} -> MergePartitions<{}>",
+            self.prev,
+            std::any::type_name::<T>()
+        )
+    }
+}
+
+impl<T, Op> Operator for MergePartitions<T, Op>
+where
+    T: Clone + Send,
+    Op: Operator<Out = SortedRun<T>>,
+{
+    type Out = T;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+    }
+
+    fn next(&mut self) -> StreamElement<T> {
+        loop {
+            if let Some(item) = self
+                .runs
+                .get_mut(&self.current)
+                .and_then(VecDeque::pop_front)
+            {
+                return StreamElement::Item(item);
+            }
+            if self.ended.remove(&self.current) {
+                self.runs.remove(&self.current);
+                self.current += 1;
+                continue;
+            }
+            if self.closing.is_some() {
+                // every partition has been received, the missing ones were empty
+                self.runs.remove(&self.current);
+                match self.runs.keys().next() {
+                    Some(&next) => self.current = next,
+                    None => {
+                        self.current = 0;
+                        self.ended.clear();
+                        return self.closing.take().unwrap();
+                    }
+                }
+                continue;
+            }
+            match self.prev.next() {
+                StreamElement::Item(SortedRun::Item(partition, item))
+                | StreamElement::Timestamped(SortedRun::Item(partition, item), _) => {
+                    if partition == self.current {
+                        return StreamElement::Item(item);
+                    }
+                    self.runs.entry(partition).or_default().push_back(item);
+                }
+                StreamElement::Item(SortedRun::End(partition))
+                | StreamElement::Timestamped(SortedRun::End(partition), _) => {
+                    self.ended.insert(partition);
+                }
+                StreamElement::Watermark(_) => {}
+                StreamElement::FlushBatch => return StreamElement::FlushBatch,
+                el @ (StreamElement::FlushAndRestart | StreamElement::Terminate) => {
+                    self.closing = Some(el.map(|_| unreachable!()));
+                }
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<T, _>("MergePartitions");
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nanorand::{tls_rng, Rng};
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    #[test]
+    fn sorted_global_by() {
+        let mut data = (0..10_000u32).map(|n| n % 3_000).collect::<Vec<_>>();
+        tls_rng().shuffle(&mut data);
+
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_iter(data.clone().into_iter())
+            .shuffle()
+            .sorted_global_by(|a, b| b.cmp(a))
+            .collect_vec();
+        env.execute_blocking();
+
+        data.sort_unstable_by(|a, b| b.cmp(a));
+        assert_eq!(res.get().unwrap(), data);
+    }
+
+    #[test]
+    fn sorted_global_by_empty() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_iter(Vec::<u32>::new().into_iter())
+            .shuffle()
+            .sorted_global_by(|a, b| a.cmp(b))
+            .collect_vec();
+        env.execute_blocking();
+
+        assert!(res.get().unwrap().is_empty());
+    }
+}
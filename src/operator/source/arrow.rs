@@ -0,0 +1,385 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use arrow::{
+    array::{cast::AsArray, types::ArrowPrimitiveType, Array, RecordBatch},
+    datatypes::*,
+    ipc::reader::FileReader,
+};
+use flume::Sender;
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
+use crate::operator::source::{ChannelSource, Source};
+use crate::operator::{Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::Stream;
+
+/// Source that reads the record batches stored in an Arrow IPC file.
+///
+/// The batches are distributed among the replicas: replica `r` out of `n` emits the batches
+/// `r, r + n, r + 2n, ...`. The batches are emitted as they are, use [`Stream::to_rows`] to convert
+/// them into rows.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct ArrowIpcSource {
+    path: PathBuf,
+    #[derivative(Debug = "ignore")]
+    reader: Option<FileReader<BufReader<File>>>,
+    next_batch: usize,
+    step: usize,
+    terminated: bool,
+    cancellation: CancellationToken,
+}
+
+impl Display for ArrowIpcSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ArrowIpcSource<{}>",
+            std::any::type_name::<RecordBatch>()
+        )
+    }
+}
+
+impl ArrowIpcSource {
+    /// Create a new source that reads the record batches from the Arrow IPC file at `path`.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::ArrowIpcSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let source = ArrowIpcSource::new("/data/input.arrow");
+    /// let rows = env
+    ///     .stream(source)
+    ///     .to_rows::<(arrow::datatypes::Int64Type, arrow::datatypes::Utf8Type)>();
+    /// ```
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            reader: None,
+            next_batch: 0,
+            step: 1,
+            terminated: false,
+            cancellation: Default::default(),
+        }
+    }
+}
+
+impl Source for ArrowIpcSource {
+    fn replication(&self) -> Replication {
+        Replication::Unlimited
+    }
+}
+
+impl Operator for ArrowIpcSource {
+    type Out = RecordBatch;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
+        let file = File::open(&self.path).unwrap_or_else(|err| {
+            panic!(
+                "ArrowIpcSource: error while opening file {:?}: {:?}",
+                self.path, err
+            )
+        });
+        let reader = FileReader::try_new(BufReader::new(file), None)
+            .expect("failed to create arrow ipc reader");
+        self.reader = Some(reader);
+        self.next_batch = metadata.global_id as usize;
+        self.step = metadata.replicas.len();
+    }
+
+    fn next(&mut self) -> StreamElement<RecordBatch> {
+        if self.terminated {
+            return StreamElement::Terminate;
+        }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
+        let reader = self.reader.as_mut().unwrap();
+        if self.next_batch < reader.num_batches() {
+            reader
+                .set_index(self.next_batch)
+                .expect("failed to seek arrow ipc file");
+            self.next_batch += self.step;
+            let batch = reader
+                .next()
+                .expect("missing arrow record batch")
+                .expect("failed to read arrow record batch");
+            return StreamElement::Item(batch);
+        }
+        self.terminated = true;
+        StreamElement::FlushAndRestart
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<RecordBatch, _>("ArrowIpcSource");
+        operator.kind = OperatorKind::Source;
+        BlockStructure::default().add_operator(operator)
+    }
+}
+
+impl Clone for ArrowIpcSource {
+    fn clone(&self) -> Self {
+        assert!(
+            self.reader.is_none(),
+            "ArrowIpcSource must be cloned before calling setup"
+        );
+        Self::new(self.path.clone())
+    }
+}
+
+impl crate::StreamContext {
+    /// Convenience method, creates an `ArrowIpcSource` and makes a stream using `StreamContext::stream`
+    pub fn stream_arrow_ipc(&self, path: impl Into<PathBuf>) -> Stream<ArrowIpcSource> {
+        self.stream(ArrowIpcSource::new(path))
+    }
+
+    /// Create a stream of record batches fed through an in-memory channel.
+    ///
+    /// The batches sent with the returned sender are moved into the stream without being copied.
+    /// The stream ends when the sender is dropped.
+    pub fn stream_arrow_channel(
+        &self,
+        channel_size: usize,
+    ) -> (Sender<RecordBatch>, Stream<ChannelSource<RecordBatch>>) {
+        let (tx, source) = ChannelSource::new(channel_size, Replication::One);
+        (tx, self.stream(source))
+    }
+}
+
+impl<Op> Stream<Op>
+where
+    Op: Operator<Out = RecordBatch> + 'static,
+{
+    pub fn to_rows<T>(self) -> Stream<impl Operator<Out = Result<T::Native, FromRecordBatchError>>>
+    where
+        T: FromRecordBatchRow + Send + 'static,
+        T::Native: Send,
+    {
+        self.flat_map(|batch| {
+            let mut i = 0;
+            let num_rows = batch.num_rows();
+            std::iter::from_fn(move || {
+                if i < num_rows {
+                    let r = T::from_record_batch_row(&batch, i);
+                    i += 1;
+                    Some(r)
+                } else {
+                    None
+                }
+            })
+        })
+    }
+}
+
+pub trait FromRecordBatchRow {
+    type Native;
+    fn from_record_batch_row(
+        batch: &RecordBatch,
+        row: usize,
+    ) -> Result<Self::Native, FromRecordBatchError>;
+    fn is_compatible(batch: &RecordBatch) -> bool;
+}
+
+pub trait ArrowTypeCast {
+    type Native;
+    const DATA_TYPE: DataType;
+
+    fn value_at(
+        batch: &RecordBatch,
+        col: usize,
+        row: usize,
+    ) -> Result<Self::Native, FromRecordBatchError>;
+}
+
+macro_rules! impl_arrow_cast_primitive {
+    ($t:ty) => {
+        impl ArrowTypeCast for $t {
+            type Native = <$t as ArrowPrimitiveType>::Native;
+            const DATA_TYPE: DataType = <$t as ArrowPrimitiveType>::DATA_TYPE;
+
+            #[inline]
+            fn value_at(
+                batch: &RecordBatch,
+                col: usize,
+                row: usize,
+            ) -> Result<Self::Native, FromRecordBatchError> {
+                batch
+                    .column(col)
+                    .as_primitive_opt::<$t>()
+                    .ok_or_else(|| FromRecordBatchError::IncompatibleTypes(col))
+                    .map(|array| array.value(row).clone())
+            }
+        }
+    };
+}
+
+impl_arrow_cast_primitive!(Date32Type);
+impl_arrow_cast_primitive!(Date64Type);
+impl_arrow_cast_primitive!(Decimal128Type);
+impl_arrow_cast_primitive!(Decimal256Type);
+impl_arrow_cast_primitive!(DurationMicrosecondType);
+impl_arrow_cast_primitive!(DurationMillisecondType);
+impl_arrow_cast_primitive!(DurationNanosecondType);
+impl_arrow_cast_primitive!(DurationSecondType);
+impl_arrow_cast_primitive!(Float16Type);
+impl_arrow_cast_primitive!(Float32Type);
+impl_arrow_cast_primitive!(Float64Type);
+impl_arrow_cast_primitive!(Int8Type);
+impl_arrow_cast_primitive!(Int16Type);
+impl_arrow_cast_primitive!(Int32Type);
+impl_arrow_cast_primitive!(Int64Type);
+impl_arrow_cast_primitive!(IntervalDayTimeType);
+impl_arrow_cast_primitive!(IntervalMonthDayNanoType);
+impl_arrow_cast_primitive!(IntervalYearMonthType);
+impl_arrow_cast_primitive!(Time32MillisecondType);
+impl_arrow_cast_primitive!(Time32SecondType);
+impl_arrow_cast_primitive!(Time64MicrosecondType);
+impl_arrow_cast_primitive!(Time64NanosecondType);
+impl_arrow_cast_primitive!(TimestampMicrosecondType);
+impl_arrow_cast_primitive!(TimestampMillisecondType);
+impl_arrow_cast_primitive!(TimestampNanosecondType);
+impl_arrow_cast_primitive!(TimestampSecondType);
+impl_arrow_cast_primitive!(UInt8Type);
+impl_arrow_cast_primitive!(UInt16Type);
+impl_arrow_cast_primitive!(UInt32Type);
+impl_arrow_cast_primitive!(UInt64Type);
+
+macro_rules! impl_arrow_cast_bytes {
+    ($t:ty) => {
+        impl ArrowTypeCast for $t {
+            type Native = <<$t as ByteArrayType>::Native as ToOwned>::Owned;
+            const DATA_TYPE: DataType = <$t as ByteArrayType>::DATA_TYPE;
+
+            #[inline]
+            fn value_at(
+                batch: &RecordBatch,
+                col: usize,
+                row: usize,
+            ) -> Result<Self::Native, FromRecordBatchError> {
+                batch
+                    .column(col)
+                    .as_bytes_opt::<$t>()
+                    .ok_or_else(|| FromRecordBatchError::IncompatibleTypes(col))
+                    .map(|array| array.value(row).to_owned())
+            }
+        }
+    };
+}
+
+impl_arrow_cast_bytes!(Utf8Type);
+impl_arrow_cast_bytes!(LargeUtf8Type);
+impl_arrow_cast_bytes!(BinaryType);
+
+macro_rules! impl_from_record_batch_tuple {
+    ($($id:ident, )+) => {
+
+    // Implementations for tuples
+    impl<$($id: ArrowTypeCast, )+> FromRecordBatchRow for ($($id, )+)
+    {
+        type Native = ($($id::Native, )+);
+
+        #[inline]
+        fn from_record_batch_row(batch: &RecordBatch, row: usize) -> Result<Self::Native, FromRecordBatchError> {
+            let mut idx = 0;
+
+            Ok((
+                $($id::value_at(batch, { idx += 1; idx - 1}, row)?,)+
+            ))
+        }
+
+        #[inline]
+        fn is_compatible(batch: &RecordBatch) -> bool {
+            let count = const {
+                let mut cnt = 0;
+                $(
+                    let _ : $id;
+                    cnt += 1;
+                )+
+                cnt
+            };
+            let mut idx = 0;
+            batch.num_columns() == count
+                $(&& batch.column({ idx += 1; idx - 1}).data_type() == &$id::DATA_TYPE)+
+        }
+    }
+
+    };
+}
+
+impl_from_record_batch_tuple!(A0,);
+impl_from_record_batch_tuple!(A0, A1,);
+impl_from_record_batch_tuple!(A0, A1, A2,);
+impl_from_record_batch_tuple!(A0, A1, A2, A3,);
+impl_from_record_batch_tuple!(A0, A1, A2, A3, A4,);
+impl_from_record_batch_tuple!(A0, A1, A2, A3, A4, A5,);
+impl_from_record_batch_tuple!(A0, A1, A2, A3, A4, A5, A6,);
+impl_from_record_batch_tuple!(A0, A1, A2, A3, A4, A5, A6, A7,);
+impl_from_record_batch_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8,);
+impl_from_record_batch_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9,);
+impl_from_record_batch_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10,);
+impl_from_record_batch_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11,);
+impl_from_record_batch_tuple!(A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11, A12,);
+
+#[derive(Debug, thiserror::Error)]
+pub enum FromRecordBatchError {
+    #[error("type cannot be converted to primitive")]
+    InvalidType,
+    #[error("type does not match at column {0}")]
+    IncompatibleTypes(usize),
+    #[error("index out of bounds")]
+    OutOfBounds,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Int64Array, RecordBatch, StringArray};
+    use arrow::datatypes::{DataType, Field, Int64Type, Schema, Utf8Type};
+    use arrow::ipc::writer::FileWriter;
+    use itertools::Itertools;
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    #[test]
+    fn arrow_ipc_rows() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = FileWriter::try_new(file.reopen().unwrap(), &schema).unwrap();
+        for b in 0..5i64 {
+            let ids = Int64Array::from_iter_values(b * 10..b * 10 + 10);
+            let names = StringArray::from_iter_values((b * 10..b * 10 + 10).map(|i| i.to_string()));
+            let batch =
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(ids), Arc::new(names)]).unwrap();
+            writer.write(&batch).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_arrow_ipc(file.path())
+            .to_rows::<(Int64Type, Utf8Type)>()
+            .map(|r| r.unwrap())
+            .collect_vec();
+        env.execute_blocking();
+
+        let res = res.get().unwrap().into_iter().sorted().collect_vec();
+        let expected = (0..50i64).map(|i| (i, i.to_string())).collect_vec();
+        assert_eq!(res, expected);
+    }
+}
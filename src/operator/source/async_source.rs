@@ -0,0 +1,217 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use flume::{Receiver, RecvTimeoutError};
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
+use crate::operator::source::Source;
+use crate::operator::{Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::Stream;
+
+/// Maximum time waited for a new item before flushing the output batches.
+const FLUSH_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A source whose items are produced asynchronously.
+///
+/// Implement this trait for sources backed by asynchronous clients (network services, message
+/// queues, databases...). Each replica of the source runs on the tokio runtime of the execution
+/// and hands its items over to the worker thread, so the worker never blocks on I/O nor busy-polls
+/// the client.
+///
+/// Use [`StreamContext::stream_async`](crate::StreamContext::stream_async) to create a stream out
+/// of an `AsyncSource`.
+///
+/// ## Example
+///
+/// ```
+/// # use renoir::prelude::*;
+/// #[derive(Clone)]
+/// struct Ticker {
+///     count: u64,
+/// }
+///
+/// impl AsyncSource for Ticker {
+///     type Out = u64;
+///
+///     async fn next(&mut self) -> Option<u64> {
+///         tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+///         self.count += 1;
+///         (self.count <= 5).then_some(self.count)
+///     }
+/// }
+///
+/// let env = StreamContext::new_local();
+/// let res = env.stream_async(Ticker { count: 0 }).collect_vec();
+/// env.execute_blocking();
+/// assert_eq!(res.get().unwrap(), vec![1, 2, 3, 4, 5]);
+/// ```
+pub trait AsyncSource: Clone + Send + 'static {
+    type Out: Send + 'static;
+
+    /// Initialize the replica of the source, called before the first call to `next`.
+    fn setup(&mut self, _metadata: &ExecutionMetadata) {}
+
+    /// The maximum parallelism offered by this source. Defaults to a single replica.
+    fn replication(&self) -> Replication {
+        Replication::One
+    }
+
+    /// Produce the next item, `None` ends the stream.
+    fn next(&mut self) -> impl Future<Output = Option<Self::Out>> + Send;
+}
+
+enum AsyncSourceInner<S: AsyncSource> {
+    Init(S),
+    Running {
+        rx: Receiver<S::Out>,
+        cancel_token: Arc<AtomicBool>,
+        cooldown: bool,
+        cancellation: CancellationToken,
+    },
+    Terminated,
+}
+
+/// Operator that drives an [`AsyncSource`] on the tokio runtime.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct AsyncSourceOperator<S: AsyncSource> {
+    #[derivative(Debug = "ignore")]
+    inner: AsyncSourceInner<S>,
+    replication: Replication,
+    channel_size: usize,
+}
+
+impl<S: AsyncSource> AsyncSourceOperator<S> {
+    /// Wrap an `AsyncSource`, buffering at most `channel_size` items produced in advance.
+    pub fn new(source: S, channel_size: usize) -> Self {
+        Self {
+            replication: source.replication(),
+            inner: AsyncSourceInner::Init(source),
+            channel_size,
+        }
+    }
+}
+
+impl<S: AsyncSource> Display for AsyncSourceOperator<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AsyncSource<{}>", std::any::type_name::<S::Out>())
+    }
+}
+
+impl<S: AsyncSource> Source for AsyncSourceOperator<S> {
+    fn replication(&self) -> Replication {
+        self.replication
+    }
+}
+
+impl<S: AsyncSource> Operator for AsyncSourceOperator<S> {
+    type Out = S::Out;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        let AsyncSourceInner::Init(source) = &self.inner else {
+            panic!("AsyncSource in invalid state")
+        };
+        let mut source = source.clone();
+        source.setup(metadata);
+
+        let (tx, rx) = flume::bounded(self.channel_size);
+        let cancel_token = Arc::new(AtomicBool::new(false));
+        let cancel = cancel_token.clone();
+        tokio::spawn(async move {
+            while let Some(item) = source.next().await {
+                if cancel.load(Ordering::Acquire) || tx.send_async(item).await.is_err() {
+                    break;
+                }
+            }
+        });
+        self.inner = AsyncSourceInner::Running {
+            rx,
+            cancel_token,
+            cooldown: false,
+            cancellation: metadata.cancellation.clone(),
+        };
+    }
+
+    fn next(&mut self) -> StreamElement<S::Out> {
+        let AsyncSourceInner::Running {
+            rx,
+            cancel_token,
+            cooldown,
+            cancellation,
+        } = &mut self.inner
+        else {
+            return StreamElement::Terminate;
+        };
+        if cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        let result = if cancellation.is_cancelled() {
+            None
+        } else if *cooldown {
+            // batches have already been flushed, wait for the next item
+            cancellation
+                .recv(rx)
+                .map(|r| r.map_err(|_| RecvTimeoutError::Disconnected))
+        } else {
+            Some(rx.recv_timeout(FLUSH_TIMEOUT))
+        };
+        match result {
+            Some(Ok(item)) => {
+                *cooldown = false;
+                StreamElement::Item(item)
+            }
+            Some(Err(RecvTimeoutError::Timeout)) => {
+                *cooldown = true;
+                StreamElement::FlushBatch
+            }
+            Some(Err(RecvTimeoutError::Disconnected)) => {
+                self.inner = AsyncSourceInner::Terminated;
+                StreamElement::FlushAndRestart
+            }
+            None => {
+                cancel_token.store(true, Ordering::Release);
+                self.inner = AsyncSourceInner::Terminated;
+                StreamElement::FlushAndRestart
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<S::Out, _>("AsyncSource");
+        operator.kind = OperatorKind::Source;
+        BlockStructure::default().add_operator(operator)
+    }
+}
+
+impl<S: AsyncSource> Clone for AsyncSourceOperator<S> {
+    fn clone(&self) -> Self {
+        match &self.inner {
+            AsyncSourceInner::Init(source) => Self {
+                inner: AsyncSourceInner::Init(source.clone()),
+                replication: self.replication,
+                channel_size: self.channel_size,
+            },
+            _ => panic!("can only clone AsyncSource in initialization state"),
+        }
+    }
+}
+
+impl<S: AsyncSource> Drop for AsyncSourceOperator<S> {
+    fn drop(&mut self) {
+        if let AsyncSourceInner::Running { cancel_token, .. } = &self.inner {
+            cancel_token.store(true, Ordering::Release);
+        }
+    }
+}
+
+impl crate::StreamContext {
+    /// Convenience method, creates a `AsyncSourceOperator` and makes a stream using `StreamContext::stream`
+    pub fn stream_async<S: AsyncSource>(&self, source: S) -> Stream<AsyncSourceOperator<S>> {
+        self.stream(AsyncSourceOperator::new(source, 1024))
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt::Display;
 use futures::{Stream, StreamExt};
 
 use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
 use crate::operator::source::Source;
 use crate::operator::{Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
@@ -21,6 +22,7 @@ where
     #[derivative(Debug = "ignore")]
     inner: S,
     terminated: bool,
+    cancellation: CancellationToken,
 }
 
 impl<S> Display for AsyncStreamSource<S>
@@ -59,6 +61,7 @@ where
         Self {
             inner,
             terminated: false,
+            cancellation: Default::default(),
         }
     }
 }
@@ -80,12 +83,21 @@ where
 {
     type Out = <S as Stream>::Item;
 
-    fn setup(&mut self, _metadata: &mut ExecutionMetadata) {}
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
+    }
 
     fn next(&mut self) -> StreamElement<Self::Out> {
         if self.terminated {
             return StreamElement::Terminate;
         }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
         // TODO: with adaptive batching this does not work since S never emits FlushBatch messages
         let rt = tokio::runtime::Handle::current();
         match rt.block_on(self.inner.next()) {
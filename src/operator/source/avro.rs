@@ -8,6 +8,7 @@ use apache_avro::{from_value, Reader};
 use serde::Deserialize;
 
 use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
 use crate::operator::source::Source;
 use crate::operator::{Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
@@ -53,6 +54,7 @@ pub struct AvroSource<R: MakeReader> {
     reader: Option<Reader<'static, R::Reader>>,
 
     terminated: bool,
+    cancellation: CancellationToken,
 }
 
 impl<R: MakeReader> Display for AvroSource<R> {
@@ -68,6 +70,7 @@ impl AvroSource<MakeFileReader> {
             make_reader: MakeFileReader { path: path.into() },
             reader: None,
             terminated: false,
+            cancellation: Default::default(),
         }
     }
 }
@@ -79,6 +82,7 @@ impl<F: MakeReader> AvroSource<F> {
             make_reader: f,
             reader: None,
             terminated: false,
+            cancellation: Default::default(),
         }
     }
 }
@@ -93,6 +97,7 @@ impl<R: MakeReader> Operator for AvroSource<R> {
     type Out = Value;
 
     fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
         let global_id = metadata.global_id;
         let instances = metadata.replicas.len() as CoordUInt;
 
@@ -106,6 +111,13 @@ impl<R: MakeReader> Operator for AvroSource<R> {
         if self.terminated {
             return StreamElement::Terminate;
         }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
         let reader = self
             .reader
             .as_mut()
@@ -140,6 +152,7 @@ impl<R: MakeReader + Clone> Clone for AvroSource<R> {
         Self {
             reader: None,
             terminated: false,
+            cancellation: Default::default(),
             replication: self.replication,
             make_reader: self.make_reader.clone(),
         }
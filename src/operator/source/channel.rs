@@ -1,8 +1,11 @@
 use std::fmt::Display;
+use std::sync::Arc;
 
 use flume::{bounded, Receiver, RecvError, Sender, TryRecvError};
 
 use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
+use crate::checkpoint::ReplicaCheckpoint;
 use crate::operator::source::Source;
 use crate::operator::{Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
@@ -19,8 +22,11 @@ pub struct ChannelSource<Out: Send + 'static> {
     #[derivative(Debug = "ignore")]
     rx: Receiver<Out>,
     terminated: bool,
+    cancellation: CancellationToken,
     retry_count: u8,
     replication: Replication,
+    #[derivative(Debug = "ignore")]
+    checkpoint: Option<Arc<ReplicaCheckpoint>>,
 }
 
 impl<Out: Send> Display for ChannelSource<Out> {
@@ -55,8 +61,10 @@ impl<Out: Send + 'static> ChannelSource<Out> {
         let s = Self {
             rx,
             terminated: false,
+            cancellation: Default::default(),
             retry_count: 0,
             replication,
+            checkpoint: None,
         };
 
         (tx, s)
@@ -72,13 +80,27 @@ impl<Out: Send + core::fmt::Debug> Source for ChannelSource<Out> {
 impl<Out: Send + core::fmt::Debug> Operator for ChannelSource<Out> {
     type Out = Out;
 
-    fn setup(&mut self, _metadata: &mut ExecutionMetadata) {}
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
+        self.checkpoint = metadata.checkpoint.clone();
+    }
 
     fn next(&mut self) -> StreamElement<Out> {
         loop {
             if self.terminated {
                 return StreamElement::Terminate;
             }
+            if self.cancellation.hold() {
+                return StreamElement::FlushBatch;
+            }
+            if self.cancellation.is_cancelled() {
+                self.terminated = true;
+                return StreamElement::FlushAndRestart;
+            }
+            if self.checkpoint.as_ref().is_some_and(|c| c.inject_barrier()) {
+                // the barrier of the checkpoint requested by the coordinator
+                return StreamElement::FlushBatch;
+            }
             let result = self.rx.try_recv();
 
             log::debug!("Channel received stuff");
@@ -100,9 +122,11 @@ impl<Out: Send + core::fmt::Debug> Operator for ChannelSource<Out> {
                 Err(TryRecvError::Empty) => {
                     log::debug!("flushed and no values ready, blocking");
                     self.retry_count = 0;
-                    match self.rx.recv() {
-                        Ok(t) => return StreamElement::Item(t),
-                        Err(RecvError::Disconnected) => {
+                    match self.cancellation.recv(&self.rx) {
+                        Some(Ok(t)) => return StreamElement::Item(t),
+                        // checked at the start of the loop
+                        None => continue,
+                        Some(Err(RecvError::Disconnected)) => {
                             self.terminated = true;
                             log::info!("Stream disconnected");
                             return StreamElement::FlushAndRestart;
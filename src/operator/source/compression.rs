@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Compression format of an input file, detected from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// Detect the compression of a file from its extension, `None` if the file is not compressed.
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "bz2" => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+
+    /// Wrap the file in a reader that decompresses it on the fly.
+    ///
+    /// Compressed files cannot be split by byte ranges, the whole file must be read by the same
+    /// replica from the beginning.
+    #[cfg(feature = "compression")]
+    pub(crate) fn reader(self, file: File) -> Box<dyn BufRead + Send> {
+        let file = BufReader::new(file);
+        match self {
+            Compression::Gzip => {
+                Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(file)))
+            }
+            Compression::Zstd => Box::new(BufReader::new(
+                zstd::stream::read::Decoder::with_buffer(file)
+                    .expect("failed to initialize zstd decoder"),
+            )),
+            Compression::Bzip2 => {
+                Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(file)))
+            }
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    pub(crate) fn reader(self, _file: File) -> Box<dyn BufRead + Send> {
+        panic!("reading {self:?} compressed files requires the `compression` feature")
+    }
+}
+
+/// Open a file for reading, decompressing it if needed.
+pub(crate) fn open_maybe_compressed(path: &Path) -> std::io::Result<Box<dyn BufRead + Send>> {
+    let file = File::open(path)?;
+    Ok(match Compression::from_path(path) {
+        Some(compression) => compression.reader(file),
+        None => Box::new(BufReader::new(file)),
+    })
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use std::io::Write;
+
+    use itertools::Itertools;
+
+    use crate::block::Replication;
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+    use crate::operator::source::{FileSource, Source};
+
+    #[test]
+    fn file_source_compressed() {
+        let lines = (0..1000).map(|i| format!("line {i}\n")).collect_vec();
+        for suffix in [".gz", ".zst", ".bz2"] {
+            let file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+            let mut writer: Box<dyn Write> = match suffix {
+                ".gz" => Box::new(flate2::write::GzEncoder::new(
+                    file.as_file(),
+                    flate2::Compression::default(),
+                )),
+                ".zst" => Box::new(
+                    zstd::stream::write::Encoder::new(file.as_file(), 0)
+                        .unwrap()
+                        .auto_finish(),
+                ),
+                _ => Box::new(bzip2::write::BzEncoder::new(
+                    file.as_file(),
+                    bzip2::Compression::default(),
+                )),
+            };
+            for line in &lines {
+                writer.write_all(line.as_bytes()).unwrap();
+            }
+            // the encoders write the end of the stream when dropped
+            drop(writer);
+
+            let source = FileSource::new(file.path());
+            // the compressed file is not split among the replicas
+            assert_eq!(source.replication(), Replication::One);
+            let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+            let res = env.stream(source).shuffle().collect_vec();
+            env.execute_blocking();
+
+            let res = res.get().unwrap().into_iter().sorted().collect_vec();
+            assert_eq!(
+                res,
+                lines.iter().cloned().sorted().collect_vec(),
+                "{suffix}"
+            );
+        }
+    }
+}
@@ -9,6 +9,8 @@ use csv::{ByteRecord, Reader, ReaderBuilder, Terminator, Trim};
 use serde::Deserialize;
 
 use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
+use crate::operator::source::compression::Compression;
 use crate::operator::source::Source;
 use crate::operator::{Data, Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
@@ -86,15 +88,21 @@ impl Default for CsvOptions {
 /// Source that reads and parses a CSV file.
 ///
 /// The file is divided in chunks and is read concurrently by multiple replicas.
+///
+/// Files ending with `.gz`, `.zst` or `.bz2` are decompressed on the fly (requires the
+/// `compression` feature). Since compressed files cannot be split in byte ranges, the source of a
+/// compressed file has a single replica that reads it entirely: use [`Stream::shuffle`] after it
+/// to spread the records among the replicas of the following operators.
 pub struct CsvSource<Out: Data + for<'a> Deserialize<'a>> {
     /// Path of the file.
     path: PathBuf,
     /// Reader used to parse the CSV file.
-    csv_reader: Option<Reader<LimitedReader<BufReader<File>>>>,
+    csv_reader: Option<Reader<Box<dyn Read + Send>>>,
     /// Options to customize the CSV parser.
     options: CsvOptions,
     /// Whether the reader has terminated its job.
     terminated: bool,
+    cancellation: CancellationToken,
     _out: PhantomData<Out>,
     buf: ByteRecord,
 }
@@ -144,6 +152,7 @@ impl<Out: Data + for<'a> Deserialize<'a>> CsvSource<Out> {
             csv_reader: None,
             options: Default::default(),
             terminated: false,
+            cancellation: Default::default(),
             _out: PhantomData,
             buf: ByteRecord::new(),
         }
@@ -260,9 +269,31 @@ impl<Out: Data + for<'a> Deserialize<'a>> CsvSource<Out> {
     }
 }
 
+impl<Out: Data + for<'a> Deserialize<'a>> CsvSource<Out> {
+    /// Build a `csv::ReaderBuilder` configured with the options of this source.
+    fn reader_builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
+            .comment(self.options.comment)
+            .delimiter(self.options.delimiter)
+            .double_quote(self.options.double_quote)
+            .escape(self.options.escape)
+            .flexible(self.options.flexible)
+            .quote(self.options.quote)
+            .quoting(self.options.quoting)
+            .terminator(self.options.terminator)
+            .trim(self.options.trim)
+            .has_headers(self.options.has_headers);
+        builder
+    }
+}
+
 impl<Out: Data + for<'a> Deserialize<'a>> Source for CsvSource<Out> {
     fn replication(&self) -> Replication {
-        Replication::Unlimited
+        match Compression::from_path(&self.path) {
+            Some(_) => Replication::One,
+            None => Replication::Unlimited,
+        }
     }
 }
 
@@ -270,6 +301,7 @@ impl<Out: Data + for<'a> Deserialize<'a>> Operator for CsvSource<Out> {
     type Out = Out;
 
     fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
         let global_id = metadata.global_id;
         let instances = metadata.replicas.len();
 
@@ -284,6 +316,13 @@ impl<Out: Data + for<'a> Deserialize<'a>> Operator for CsvSource<Out> {
                 )
             });
 
+        if let Some(compression) = Compression::from_path(&self.path) {
+            // compressed files cannot be split, the only replica reads the whole file
+            let reader: Box<dyn Read + Send> = Box::new(compression.reader(file));
+            self.csv_reader = Some(self.reader_builder().from_reader(reader));
+            return;
+        }
+
         let file_size = file.metadata().unwrap().len();
 
         let mut buf_reader = BufReader::new(file);
@@ -349,18 +388,8 @@ impl<Out: Data + for<'a> Deserialize<'a>> Operator for CsvSource<Out> {
         let limited_reader = LimitedReader::new(buf_reader, (end - start) as usize);
 
         // Create csv::Reader
-        let mut csv_reader = ReaderBuilder::new()
-            .comment(self.options.comment)
-            .delimiter(self.options.delimiter)
-            .double_quote(self.options.double_quote)
-            .escape(self.options.escape)
-            .flexible(self.options.flexible)
-            .quote(self.options.quote)
-            .quoting(self.options.quoting)
-            .terminator(self.options.terminator)
-            .trim(self.options.trim)
-            .has_headers(self.options.has_headers)
-            .from_reader(limited_reader);
+        let reader: Box<dyn Read + Send> = Box::new(limited_reader);
+        let mut csv_reader = self.reader_builder().from_reader(reader);
 
         if self.options.has_headers {
             // set the headers of the CSV file
@@ -379,6 +408,13 @@ impl<Out: Data + for<'a> Deserialize<'a>> Operator for CsvSource<Out> {
         if self.terminated {
             return StreamElement::Terminate;
         }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
         let csv_reader = self
             .csv_reader
             .as_mut()
@@ -418,6 +454,7 @@ impl<Out: Data + for<'a> Deserialize<'a>> Clone for CsvSource<Out> {
             csv_reader: None,
             options: self.options.clone(),
             terminated: false,
+            cancellation: Default::default(),
             _out: PhantomData,
             buf: ByteRecord::new(),
         }
@@ -426,6 +463,8 @@ impl<Out: Data + for<'a> Deserialize<'a>> Clone for CsvSource<Out> {
 
 impl crate::StreamContext {
     /// Convenience method, creates a `CsvSource` and makes a stream using `StreamContext::stream`
+    ///
+    /// Compressed files are read by a single replica, see [`CsvSource`].
     pub fn stream_csv<T: Data + for<'a> Deserialize<'a>>(
         &self,
         path: impl Into<PathBuf>,
@@ -439,13 +478,19 @@ impl crate::StreamContext {
 mod tests {
     use std::io::Write;
 
+    #[cfg(feature = "compression")]
+    use flate2::{write::GzEncoder, Compression};
     use itertools::Itertools;
     use serde::{Deserialize, Serialize};
     use tempfile::NamedTempFile;
 
+    #[cfg(feature = "compression")]
+    use crate::block::Replication;
     use crate::config::RuntimeConfig;
     use crate::environment::StreamContext;
     use crate::operator::source::CsvSource;
+    #[cfg(feature = "compression")]
+    use crate::operator::source::Source;
 
     #[test]
     fn csv_without_headers() {
@@ -500,4 +545,28 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn csv_gzip() {
+        let file = tempfile::Builder::new()
+            .suffix(".csv.gz")
+            .tempfile()
+            .unwrap();
+        let mut encoder = GzEncoder::new(file.as_file(), Compression::default());
+        for i in 0..1000 {
+            writeln!(encoder, "{},{}", i, i + 1).unwrap();
+        }
+        encoder.finish().unwrap();
+
+        let source = CsvSource::<(i32, i32)>::new(file.path()).has_headers(false);
+        // the compressed file is not split among the replicas
+        assert_eq!(source.replication(), Replication::One);
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env.stream(source).shuffle().collect_vec();
+        env.execute_blocking();
+
+        let res = res.get().unwrap().into_iter().sorted().collect_vec();
+        assert_eq!(res, (0..1000).map(|x| (x, x + 1)).collect_vec());
+    }
 }
@@ -0,0 +1,320 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Display;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::block::{group_by_hash, BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
+use crate::operator::source::compression::open_maybe_compressed;
+use crate::operator::source::Source;
+use crate::operator::{Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::Stream;
+
+/// Source that monitors a directory and emits line-by-line the content of the files inside of it.
+///
+/// The files already present when the job starts are read first, then the directory is polled
+/// for new files which are read as soon as they appear.
+///
+/// Files are expected to be **moved** (or renamed) into the directory once they are complete:
+/// files whose name starts with `.` or `_`, or ends with one of the in-progress suffixes (by
+/// default `.tmp`, `.part` and `.inprogress`) are ignored until they are renamed. Each file is
+/// read by exactly one replica, chosen by hashing its name.
+///
+/// Compressed files (`.gz`, `.zst`, `.bz2`) are decompressed on the fly when the `compression`
+/// feature is enabled.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct DirectoryWatchSource {
+    path: PathBuf,
+    poll_interval: Duration,
+    idle_timeout: Option<Duration>,
+    in_progress_suffixes: Vec<String>,
+    global_id: u64,
+    instances: u64,
+    /// Files already read or queued.
+    seen: HashSet<PathBuf>,
+    /// Files ready to be read, in order of modification time.
+    queue: VecDeque<PathBuf>,
+    #[derivative(Debug = "ignore")]
+    reader: Option<Box<dyn BufRead + Send>>,
+    last_poll: Option<Instant>,
+    last_activity: Instant,
+    flushed: bool,
+    terminated: bool,
+    cancellation: CancellationToken,
+}
+
+impl Display for DirectoryWatchSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "DirectoryWatchSource<{}>",
+            std::any::type_name::<String>()
+        )
+    }
+}
+
+impl DirectoryWatchSource {
+    /// Create a new source that watches the directory at `path`.
+    ///
+    /// By default the directory is polled every second and the source never terminates, use
+    /// [`DirectoryWatchSource::idle_timeout`] to end the stream when no new file is found for a
+    /// while.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::DirectoryWatchSource;
+    /// # use std::time::Duration;
+    /// # let mut env = StreamContext::new_local();
+    /// let source = DirectoryWatchSource::new("/data/incoming")
+    ///     .poll_interval(Duration::from_millis(200));
+    /// let s = env.stream(source);
+    /// ```
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            poll_interval: Duration::from_secs(1),
+            idle_timeout: None,
+            in_progress_suffixes: vec![".tmp".into(), ".part".into(), ".inprogress".into()],
+            global_id: 0,
+            instances: 1,
+            seen: Default::default(),
+            queue: Default::default(),
+            reader: None,
+            last_poll: None,
+            last_activity: Instant::now(),
+            flushed: false,
+            terminated: false,
+            cancellation: Default::default(),
+        }
+    }
+
+    /// How often the directory is listed looking for new files.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Terminate the stream if no new file appears for the given duration.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the suffixes that mark a file as still being written.
+    pub fn in_progress_suffixes<I, S>(mut self, suffixes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.in_progress_suffixes = suffixes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Whether the file is complete and should be read by this replica.
+    fn is_ready(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return false;
+        };
+        if name.starts_with('.') || name.starts_with('_') {
+            return false;
+        }
+        if self.in_progress_suffixes.iter().any(|s| name.ends_with(s)) {
+            return false;
+        }
+        group_by_hash(&name) % self.instances == self.global_id
+    }
+
+    /// List the directory and enqueue the new complete files.
+    fn poll(&mut self) {
+        self.last_poll = Some(Instant::now());
+        let entries = match std::fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!("DirectoryWatchSource: cannot list {:?}: {e}", self.path);
+                return;
+            }
+        };
+        let mut new_files: Vec<(SystemTime, PathBuf)> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+            .map(|e| e.path())
+            .filter(|p| !self.seen.contains(p) && self.is_ready(p))
+            .map(|p| {
+                let mtime = p
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                (mtime, p)
+            })
+            .collect();
+        new_files.sort();
+        for (_, path) in new_files {
+            tracing::debug!("DirectoryWatchSource: new file {path:?}");
+            self.seen.insert(path.clone());
+            self.queue.push_back(path);
+        }
+    }
+
+    /// Read the next line from the current file, opening the next one if needed.
+    fn next_line(&mut self) -> Option<String> {
+        loop {
+            if self.reader.is_none() {
+                let path = self.queue.pop_front()?;
+                match open_maybe_compressed(&path) {
+                    Ok(reader) => self.reader = Some(reader),
+                    Err(e) => {
+                        tracing::warn!("DirectoryWatchSource: cannot open {path:?}: {e}");
+                        continue;
+                    }
+                }
+            }
+            let mut line = String::new();
+            match self.reader.as_mut().unwrap().read_line(&mut line) {
+                Ok(len) if len > 0 => return Some(line),
+                Ok(_) => self.reader = None,
+                Err(e) => panic!("Error while reading file: {e:?}"),
+            }
+        }
+    }
+}
+
+impl Source for DirectoryWatchSource {
+    fn replication(&self) -> Replication {
+        Replication::Unlimited
+    }
+}
+
+impl Operator for DirectoryWatchSource {
+    type Out = String;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
+        self.global_id = metadata.global_id;
+        self.instances = metadata.replicas.len() as u64;
+        self.last_activity = Instant::now();
+        self.poll();
+    }
+
+    fn next(&mut self) -> StreamElement<String> {
+        loop {
+            if self.terminated {
+                return StreamElement::Terminate;
+            }
+            if self.cancellation.hold() {
+                return StreamElement::FlushBatch;
+            }
+            if self.cancellation.is_cancelled() {
+                self.terminated = true;
+                return StreamElement::FlushAndRestart;
+            }
+            if let Some(line) = self.next_line() {
+                self.flushed = false;
+                self.last_activity = Instant::now();
+                return StreamElement::Item(line);
+            }
+            // no more data available: flush what has been produced so far
+            if !self.flushed {
+                self.flushed = true;
+                return StreamElement::FlushBatch;
+            }
+            if let Some(timeout) = self.idle_timeout {
+                if self.last_activity.elapsed() >= timeout {
+                    self.terminated = true;
+                    return StreamElement::FlushAndRestart;
+                }
+            }
+            let elapsed = self.last_poll.map(|t| t.elapsed()).unwrap_or_default();
+            if elapsed < self.poll_interval {
+                std::thread::sleep(self.poll_interval - elapsed);
+            }
+            self.poll();
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<String, _>("DirectoryWatchSource");
+        operator.kind = OperatorKind::Source;
+        BlockStructure::default().add_operator(operator)
+    }
+}
+
+impl Clone for DirectoryWatchSource {
+    fn clone(&self) -> Self {
+        assert!(
+            self.reader.is_none() && self.seen.is_empty(),
+            "DirectoryWatchSource must be cloned before calling setup"
+        );
+        Self {
+            path: self.path.clone(),
+            poll_interval: self.poll_interval,
+            idle_timeout: self.idle_timeout,
+            in_progress_suffixes: self.in_progress_suffixes.clone(),
+            global_id: 0,
+            instances: 1,
+            seen: Default::default(),
+            queue: Default::default(),
+            reader: None,
+            last_poll: None,
+            last_activity: Instant::now(),
+            flushed: false,
+            terminated: false,
+            cancellation: Default::default(),
+        }
+    }
+}
+
+impl crate::StreamContext {
+    /// Convenience method, creates a `DirectoryWatchSource` and makes a stream using `StreamContext::stream`
+    pub fn stream_directory<P: Into<PathBuf>>(&self, path: P) -> Stream<DirectoryWatchSource> {
+        let source = DirectoryWatchSource::new(path);
+        self.stream(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use itertools::Itertools;
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+    use crate::operator::source::DirectoryWatchSource;
+
+    #[test]
+    fn directory_existing_and_new_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "1\n2\n").unwrap();
+        std::fs::write(dir.path().join("ignored.tmp"), "100\n").unwrap();
+
+        let path = dir.path().to_path_buf();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            let tmp = path.join("b.txt.tmp");
+            let mut f = std::fs::File::create(&tmp).unwrap();
+            writeln!(f, "3").unwrap();
+            drop(f);
+            std::fs::rename(&tmp, path.join("b.txt")).unwrap();
+        });
+
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let source = DirectoryWatchSource::new(dir.path())
+            .poll_interval(Duration::from_millis(20))
+            .idle_timeout(Duration::from_millis(500));
+        let res = env
+            .stream(source)
+            .map(|l| l.trim().parse::<u32>().unwrap())
+            .collect_vec();
+        env.execute_blocking();
+        writer.join().unwrap();
+
+        let res = res.get().unwrap().into_iter().sorted().collect_vec();
+        assert_eq!(res, vec![1, 2, 3]);
+    }
+}
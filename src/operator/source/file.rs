@@ -7,7 +7,9 @@ use std::path::PathBuf;
 
 use crate::block::Replication;
 use crate::block::{BlockStructure, OperatorKind, OperatorStructure};
+use crate::cancellation::CancellationToken;
 use crate::network::Coord;
+use crate::operator::source::compression::Compression;
 use crate::operator::source::Source;
 use crate::operator::{Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
@@ -16,14 +18,22 @@ use crate::Stream;
 /// Source that reads a text file line-by-line.
 ///
 /// The file is divided in chunks and is read concurrently by multiple replicas.
-#[derive(Debug)]
+///
+/// Files ending with `.gz`, `.zst` or `.bz2` are decompressed on the fly (requires the
+/// `compression` feature). Since compressed files cannot be split in byte ranges, the source of a
+/// compressed file has a single replica that reads it entirely: use [`Stream::shuffle`] after it
+/// to spread the lines among the replicas of the following operators.
+#[derive(Derivative)]
+#[derivative(Debug)]
 pub struct FileSource {
     path: PathBuf,
     // reader is initialized in `setup`, before it is None
-    reader: Option<BufReader<File>>,
+    #[derivative(Debug = "ignore")]
+    reader: Option<Box<dyn BufRead + Send>>,
     current: usize,
     end: usize,
     terminated: bool,
+    cancellation: CancellationToken,
     coord: Option<Coord>,
 }
 
@@ -62,6 +72,7 @@ impl FileSource {
             current: 0,
             end: 0,
             terminated: false,
+            cancellation: Default::default(),
             coord: None,
         }
     }
@@ -69,7 +80,10 @@ impl FileSource {
 
 impl Source for FileSource {
     fn replication(&self) -> Replication {
-        Replication::Unlimited
+        match Compression::from_path(&self.path) {
+            Some(_) => Replication::One,
+            None => Replication::Unlimited,
+        }
     }
 }
 
@@ -77,6 +91,7 @@ impl Operator for FileSource {
     type Out = String;
 
     fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
         let global_id = metadata.global_id;
         let instances = metadata.replicas.len();
 
@@ -86,6 +101,16 @@ impl Operator for FileSource {
                 self.path, err
             )
         });
+
+        if let Some(compression) = Compression::from_path(&self.path) {
+            // compressed files cannot be split, the only replica reads the whole file
+            self.coord = Some(metadata.coord);
+            self.current = 0;
+            self.end = usize::MAX;
+            self.reader = Some(compression.reader(file));
+            return;
+        }
+
         let file_size = file.metadata().unwrap().len() as usize;
 
         let range_size = file_size / instances;
@@ -111,7 +136,7 @@ impl Operator for FileSource {
                 .expect("Cannot read line from file");
         }
         self.coord = Some(metadata.coord);
-        self.reader = Some(reader);
+        self.reader = Some(Box::new(reader));
     }
 
     fn next(&mut self) -> StreamElement<String> {
@@ -119,6 +144,13 @@ impl Operator for FileSource {
             log::trace!("terminate {}", self.coord.unwrap());
             return StreamElement::Terminate;
         }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
         let element = if self.current <= self.end {
             let mut line = String::new();
             match self
@@ -164,6 +196,7 @@ impl Clone for FileSource {
             current: 0,
             end: 0,
             terminated: false,
+            cancellation: Default::default(),
             coord: None,
         }
     }
@@ -171,6 +204,8 @@ impl Clone for FileSource {
 
 impl crate::StreamContext {
     /// Convenience method, creates a `FileSource` and makes a stream using `StreamContext::stream`
+    ///
+    /// Compressed files are read by a single replica, see [`FileSource`].
     pub fn stream_file<P: Into<PathBuf>>(&self, path: P) -> Stream<FileSource> {
         let source = FileSource::new(path);
         self.stream(source)
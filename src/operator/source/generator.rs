@@ -0,0 +1,225 @@
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
+use crate::checkpoint::OperatorState;
+use crate::operator::source::Source;
+use crate::operator::{Data, Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::Stream;
+
+/// Source that generates synthetic items at a controlled rate.
+///
+/// Each replica calls the generator function with a sequence of indices and emits the generated
+/// items respecting the configured rate. The indices are unique across the replicas: replica `r`
+/// out of `n` generates the indices `r, r + n, r + 2n, ...`.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct GeneratorSource<F, Out>
+where
+    F: FnMut(u64) -> Out + Clone + Send + 'static,
+    Out: Data,
+{
+    #[derivative(Debug = "ignore")]
+    generator: F,
+    /// Number of items generated per second by each replica.
+    rate_per_sec: f64,
+    /// Generate only the indices lower than this.
+    limit: Option<u64>,
+    replication: Replication,
+    next_index: u64,
+    step: u64,
+    emitted: u64,
+    start: Option<Instant>,
+    flushed: bool,
+    terminated: bool,
+    cancellation: CancellationToken,
+    /// Saves `next_index` in the checkpoints.
+    state: OperatorState<u64>,
+}
+
+impl<F, Out> Display for GeneratorSource<F, Out>
+where
+    F: FnMut(u64) -> Out + Clone + Send + 'static,
+    Out: Data,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GeneratorSource<{}>", std::any::type_name::<Out>())
+    }
+}
+
+impl<F, Out> GeneratorSource<F, Out>
+where
+    F: FnMut(u64) -> Out + Clone + Send + 'static,
+    Out: Data,
+{
+    /// Create a new source that emits `generator(i)` at a rate of `rate_per_sec` items per second
+    /// on each replica.
+    ///
+    /// The source never ends unless a limit is set with [`GeneratorSource::limit`].
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::GeneratorSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let source = GeneratorSource::new(|i| i * 2, 10_000.0).limit(100);
+    /// let res = env.stream(source).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort();
+    /// assert_eq!(res, (0..100).map(|i| i * 2).collect::<Vec<_>>());
+    /// ```
+    pub fn new(generator: F, rate_per_sec: f64) -> Self {
+        assert!(rate_per_sec > 0.0, "the rate must be positive");
+        Self {
+            generator,
+            rate_per_sec,
+            limit: None,
+            replication: Replication::Unlimited,
+            next_index: 0,
+            step: 1,
+            emitted: 0,
+            start: None,
+            flushed: true,
+            terminated: false,
+            cancellation: Default::default(),
+            state: OperatorState::new(),
+        }
+    }
+
+    /// Stop generating after the indices from `0` to `limit - 1` have been generated (across all
+    /// the replicas).
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Limit the number of replicas generating data.
+    pub fn replication(mut self, replication: Replication) -> Self {
+        self.replication = replication;
+        self
+    }
+}
+
+impl<F, Out> Source for GeneratorSource<F, Out>
+where
+    F: FnMut(u64) -> Out + Clone + Send + 'static,
+    Out: Data,
+{
+    fn replication(&self) -> Replication {
+        self.replication
+    }
+}
+
+impl<F, Out> Operator for GeneratorSource<F, Out>
+where
+    F: FnMut(u64) -> Out + Clone + Send + 'static,
+    Out: Data,
+{
+    type Out = Out;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
+        self.next_index = metadata.global_id;
+        self.step = metadata.replicas.len() as u64;
+        self.state.setup(metadata);
+        if let Some(next_index) = self.state.restore() {
+            // resume from where the source was when the checkpoint was taken
+            self.next_index = next_index;
+        }
+        // the replica had already ended in the restored checkpoint
+        if let Some(limit) = self.limit.filter(|_| self.state.ended()) {
+            self.next_index = limit;
+        }
+    }
+
+    fn next(&mut self) -> StreamElement<Out> {
+        if self.terminated {
+            return StreamElement::Terminate;
+        }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
+        if self.state.inject_barrier() {
+            // the barrier of the checkpoint requested by the coordinator
+            self.state.snapshot(&self.next_index);
+            return StreamElement::FlushBatch;
+        }
+        if matches!(self.limit, Some(limit) if self.next_index >= limit) {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
+
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let deadline = start + Duration::from_secs_f64(self.emitted as f64 / self.rate_per_sec);
+        let now = Instant::now();
+        if deadline > now {
+            // flush the pending items before waiting for the next one
+            if !self.flushed {
+                self.flushed = true;
+                return StreamElement::FlushBatch;
+            }
+            std::thread::sleep(deadline - now);
+        }
+
+        let item = (self.generator)(self.next_index);
+        self.next_index += self.step;
+        self.emitted += 1;
+        self.flushed = false;
+        StreamElement::Item(item)
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<Out, _>("GeneratorSource");
+        operator.kind = OperatorKind::Source;
+        BlockStructure::default().add_operator(operator)
+    }
+}
+
+impl<F, Out> Clone for GeneratorSource<F, Out>
+where
+    F: FnMut(u64) -> Out + Clone + Send + 'static,
+    Out: Data,
+{
+    fn clone(&self) -> Self {
+        Self {
+            generator: self.generator.clone(),
+            rate_per_sec: self.rate_per_sec,
+            limit: self.limit,
+            replication: self.replication,
+            next_index: 0,
+            step: 1,
+            emitted: 0,
+            start: None,
+            flushed: true,
+            terminated: false,
+            cancellation: Default::default(),
+            state: OperatorState::new(),
+        }
+    }
+}
+
+impl crate::StreamContext {
+    /// Convenience method, creates a `GeneratorSource` and makes a stream using `StreamContext::stream`
+    pub fn stream_generator<F, Out>(
+        &self,
+        generator: F,
+        rate_per_sec: f64,
+    ) -> Stream<GeneratorSource<F, Out>>
+    where
+        F: FnMut(u64) -> Out + Clone + Send + 'static,
+        Out: Data,
+    {
+        let source = GeneratorSource::new(generator, rate_per_sec);
+        self.stream(source)
+    }
+}
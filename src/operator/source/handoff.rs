@@ -0,0 +1,242 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::net::TcpListener;
+use std::time::Duration;
+
+use flume::{Receiver, RecvError, TryRecvError};
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
+use crate::operator::sink::handoff::{memory_channel, remove_memory_channel};
+use crate::operator::sink::Handoff;
+use crate::operator::source::Source;
+use crate::operator::{ExchangeData, Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::Stream;
+
+/// Number of items buffered between the thread reading the hand-off and the source.
+const CHANNEL_SIZE: usize = 1024;
+/// How often to check whether the file of a [`Handoff::File`] has been written.
+const FILE_POLL: Duration = Duration::from_millis(100);
+
+/// Read the items written by [`Stream::write_handoff`](crate::Stream::write_handoff) until the end
+/// marker, sending them to the channel.
+fn read_items<T: ExchangeData>(mut reader: impl Read, tx: flume::Sender<Option<T>>) {
+    loop {
+        let item: Option<T> =
+            bincode::serde::decode_from_std_read(&mut reader, bincode::config::standard())
+                .unwrap_or_else(|e| panic!("HandoffSource: the stream was interrupted: {e}"));
+        let end = item.is_none();
+        if tx.send(item).is_err() || end {
+            break;
+        }
+    }
+}
+
+/// Source that reads the output of another job, written with
+/// [`Stream::write_handoff`](crate::Stream::write_handoff).
+///
+/// The items are read **only from one replica**, located on the first host, therefore this
+/// source is not parallel.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct HandoffSource<T> {
+    handoff: Handoff,
+    #[derivative(Debug = "ignore")]
+    rx: Option<Receiver<Option<T>>>,
+    flushed: bool,
+    terminated: bool,
+    cancellation: CancellationToken,
+}
+
+impl<T> Display for HandoffSource<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HandoffSource<{}>", std::any::type_name::<T>())
+    }
+}
+
+impl<T: ExchangeData> HandoffSource<T> {
+    /// Create a new source reading the items passed through `handoff`.
+    ///
+    /// See [`StreamContext::stream_handoff`](crate::StreamContext::stream_handoff).
+    pub fn new(handoff: Handoff) -> Self {
+        Self {
+            handoff,
+            rx: None,
+            flushed: true,
+            terminated: false,
+            cancellation: Default::default(),
+        }
+    }
+
+    fn end(&mut self) -> StreamElement<T> {
+        if let Handoff::Memory(name) = &self.handoff {
+            remove_memory_channel(name);
+        }
+        self.terminated = true;
+        StreamElement::FlushAndRestart
+    }
+}
+
+impl<T: ExchangeData> Source for HandoffSource<T> {
+    fn replication(&self) -> Replication {
+        Replication::One
+    }
+}
+
+impl<T: ExchangeData> Operator for HandoffSource<T> {
+    type Out = T;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
+        let rx = match self.handoff.clone() {
+            Handoff::Memory(name) => memory_channel(&name).1,
+            Handoff::File(path) => {
+                let (tx, rx) = flume::bounded(CHANNEL_SIZE);
+                std::thread::Builder::new()
+                    .name("renoir-handoff".into())
+                    .spawn(move || {
+                        // the file is renamed into place when complete
+                        while !path.exists() {
+                            std::thread::sleep(FILE_POLL);
+                        }
+                        let file = File::open(&path).unwrap_or_else(|err| {
+                            panic!("HandoffSource: error while opening {path:?}: {err:?}")
+                        });
+                        read_items(BufReader::new(file), tx);
+                    })
+                    .expect("failed to spawn handoff reader thread");
+                rx
+            }
+            Handoff::Tcp(addr) => {
+                let listener = TcpListener::bind(addr)
+                    .unwrap_or_else(|e| panic!("HandoffSource: cannot listen on {addr}: {e}"));
+                let (tx, rx) = flume::bounded(CHANNEL_SIZE);
+                std::thread::Builder::new()
+                    .name("renoir-handoff".into())
+                    .spawn(move || {
+                        let (stream, peer) = listener
+                            .accept()
+                            .unwrap_or_else(|e| panic!("HandoffSource: accept failed: {e}"));
+                        tracing::debug!("handoff producer connected from {peer}");
+                        read_items(BufReader::new(stream), tx);
+                    })
+                    .expect("failed to spawn handoff reader thread");
+                rx
+            }
+        };
+        self.rx = Some(rx);
+    }
+
+    fn next(&mut self) -> StreamElement<T> {
+        if self.terminated {
+            return StreamElement::Terminate;
+        }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
+        let rx = self.rx.as_ref().expect("HandoffSource was not set up");
+        let item = match rx.try_recv() {
+            Ok(item) => Ok(item),
+            Err(TryRecvError::Empty) if !self.flushed => {
+                // nothing ready: flush what has been read so far before blocking
+                self.flushed = true;
+                return StreamElement::FlushBatch;
+            }
+            Err(TryRecvError::Empty) => match self.cancellation.recv(rx) {
+                Some(result) => result,
+                None => {
+                    self.terminated = true;
+                    return StreamElement::FlushAndRestart;
+                }
+            },
+            Err(TryRecvError::Disconnected) => Err(RecvError::Disconnected),
+        };
+        match item {
+            Ok(Some(item)) => {
+                self.flushed = false;
+                StreamElement::Item(item)
+            }
+            Ok(None) => self.end(),
+            Err(RecvError::Disconnected) => {
+                panic!("HandoffSource: the producer stopped before the end of the stream")
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<T, _>("HandoffSource");
+        operator.kind = OperatorKind::Source;
+        BlockStructure::default().add_operator(operator)
+    }
+}
+
+impl<T> Clone for HandoffSource<T> {
+    fn clone(&self) -> Self {
+        // Since this is a non-parallel source, we don't want the other replicas to emit any value
+        panic!("HandoffSource cannot be cloned, replication should be 1");
+    }
+}
+
+impl crate::StreamContext {
+    /// Read the items written by another job with [`Stream::write_handoff`] using the same
+    /// [`Handoff`].
+    ///
+    /// The stream ends when the producer has written all its items. See
+    /// [`Stream::write_handoff`] for an example.
+    pub fn stream_handoff<T: ExchangeData>(&self, handoff: Handoff) -> Stream<HandoffSource<T>> {
+        self.stream(HandoffSource::new(handoff))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+    use crate::operator::sink::Handoff;
+
+    #[test]
+    fn handoff_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let handoff = Handoff::File(dir.path().join("out.bin"));
+
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        env.stream_par_iter(0..100u32)
+            .write_handoff(handoff.clone());
+        env.execute_blocking();
+
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env.stream_handoff::<u32>(handoff).collect_vec();
+        env.execute_blocking();
+        let mut res = res.get().unwrap();
+        res.sort_unstable();
+        assert_eq!(res, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn handoff_memory_concurrent() {
+        let handoff = Handoff::Memory("handoff_memory_concurrent".into());
+
+        let consumer = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = consumer
+            .stream_handoff::<u32>(handoff.clone())
+            .map(|x| x * 2)
+            .collect_vec();
+        let consumer = std::thread::spawn(move || consumer.execute_blocking());
+
+        let producer = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        producer.stream_iter(0..100u32).write_handoff(handoff);
+        producer.execute_blocking();
+
+        consumer.join().unwrap();
+        assert_eq!(
+            res.get().unwrap(),
+            (0..100).map(|x| x * 2).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use std::fmt::Display;
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
+use crate::operator::source::Source;
+use crate::operator::{Operator, StreamElement, Timestamp};
+use crate::scheduler::ExecutionMetadata;
+use crate::Stream;
+
+/// The source a replica of a [`HybridSource`] is reading from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Backfill,
+    Live,
+}
+
+/// Source that first reads a bounded backfill of historical data, e.g. files, then switches to a
+/// live source, e.g. a kafka topic, without ending the stream.
+///
+/// Each replica switches when its part of the backfill ends: the end of the backfill is not
+/// forwarded, so the following operators see a single stream, and no element of the backfill is
+/// emitted after the first one of the live source. The live source is set up together with the
+/// backfill, but it's polled only after the switch; it should start from the position recorded
+/// with the historical data, e.g. with `KafkaSource::start` (with the `rdkafka` feature).
+///
+/// The watermarks never go back across the switch: the live source can only advance the last
+/// watermark of the backfill. Its watermarks and its timestamped elements not after that watermark
+/// are dropped, since the backfill already covered them, so the live source may start a bit before
+/// the end of the historical data without duplicates.
+///
+/// The elements of the live source are converted to the type of the backfill with `convert`.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct HybridSource<B, L, F>
+where
+    B: Source,
+    L: Source,
+    F: Fn(L::Out) -> B::Out + Clone + Send,
+{
+    backfill: B,
+    live: L,
+    #[derivative(Debug = "ignore")]
+    convert: F,
+    phase: Phase,
+    /// The last watermark emitted, `None` if there was none.
+    last_watermark: Option<Timestamp>,
+    cancellation: CancellationToken,
+}
+
+impl<B, L, F> Display for HybridSource<B, L, F>
+where
+    B: Source,
+    L: Source,
+    F: Fn(L::Out) -> B::Out + Clone + Send,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Hybrid({} then {})", self.backfill, self.live)
+    }
+}
+
+impl<B, L, F> HybridSource<B, L, F>
+where
+    B: Source,
+    L: Source,
+    F: Fn(L::Out) -> B::Out + Clone + Send,
+{
+    /// Read `backfill` until it ends, then `live`, converting its elements with `convert`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::{HybridSource, IteratorSource};
+    /// # let mut env = StreamContext::new_local();
+    /// let history = IteratorSource::new(0..5u64);
+    /// let live = IteratorSource::new(5..10u32);
+    /// let source = HybridSource::new(history, live, |x| x as u64);
+    /// let res = env.stream(source).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), (0..10).collect::<Vec<_>>());
+    /// ```
+    pub fn new(backfill: B, live: L, convert: F) -> Self {
+        Self {
+            backfill,
+            live,
+            convert,
+            phase: Phase::Backfill,
+            last_watermark: None,
+            cancellation: Default::default(),
+        }
+    }
+
+    /// Whether an element of the live source at `ts` was already covered by the backfill.
+    fn covered(&self, ts: Timestamp) -> bool {
+        self.last_watermark.is_some_and(|w| ts <= w)
+    }
+}
+
+impl<B, L, F> Source for HybridSource<B, L, F>
+where
+    B: Source,
+    L: Source,
+    F: Fn(L::Out) -> B::Out + Clone + Send,
+{
+    fn replication(&self) -> Replication {
+        self.backfill
+            .replication()
+            .intersect(self.live.replication())
+    }
+}
+
+impl<B, L, F> Operator for HybridSource<B, L, F>
+where
+    B: Source,
+    L: Source,
+    F: Fn(L::Out) -> B::Out + Clone + Send,
+{
+    type Out = B::Out;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
+        self.backfill.setup(metadata);
+        self.live.setup(metadata);
+    }
+
+    fn next(&mut self) -> StreamElement<B::Out> {
+        loop {
+            if self.phase == Phase::Backfill {
+                match self.backfill.next() {
+                    StreamElement::Watermark(ts) => {
+                        self.last_watermark = self.last_watermark.max(Some(ts));
+                        return StreamElement::Watermark(ts);
+                    }
+                    // the job was stopped: end the stream without going live
+                    end @ (StreamElement::FlushAndRestart | StreamElement::Terminate)
+                        if self.cancellation.is_cancelled() =>
+                    {
+                        return end
+                    }
+                    StreamElement::FlushAndRestart | StreamElement::Terminate => {
+                        tracing::debug!("{self}: backfill completed, switching to the live source");
+                        self.phase = Phase::Live;
+                        // send downstream what is left of the backfill before waiting
+                        return StreamElement::FlushBatch;
+                    }
+                    element => return element,
+                }
+            }
+
+            return match self.live.next() {
+                StreamElement::Timestamped(_, ts) | StreamElement::Watermark(ts)
+                    if self.covered(ts) =>
+                {
+                    continue
+                }
+                StreamElement::Watermark(ts) => {
+                    self.last_watermark = Some(ts);
+                    StreamElement::Watermark(ts)
+                }
+                element => element.map(&self.convert),
+            };
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut structure = self.backfill.structure();
+        structure.operators.extend(self.live.structure().operators);
+        let mut operator = OperatorStructure::new::<B::Out, _>("HybridSource");
+        operator.kind = OperatorKind::Source;
+        structure.add_operator(operator)
+    }
+}
+
+impl<B, L, F> Clone for HybridSource<B, L, F>
+where
+    B: Source,
+    L: Source,
+    F: Fn(L::Out) -> B::Out + Clone + Send,
+{
+    fn clone(&self) -> Self {
+        Self {
+            backfill: self.backfill.clone(),
+            live: self.live.clone(),
+            convert: self.convert.clone(),
+            phase: Phase::Backfill,
+            last_watermark: None,
+            cancellation: Default::default(),
+        }
+    }
+}
+
+impl crate::StreamContext {
+    /// Convenience method, creates a `HybridSource` reading `backfill` and then `live` and makes a
+    /// stream using `StreamContext::stream`
+    pub fn stream_hybrid<B, L, F>(
+        &self,
+        backfill: B,
+        live: L,
+        convert: F,
+    ) -> Stream<HybridSource<B, L, F>>
+    where
+        B: Source + Send + 'static,
+        L: Source + Send + 'static,
+        F: Fn(L::Out) -> B::Out + Clone + Send + 'static,
+    {
+        self.stream(HybridSource::new(backfill, live, convert))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+    use crate::operator::source::IteratorSource;
+
+    #[test]
+    fn hybrid_single_stream() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let history = IteratorSource::new(0..100u32);
+        let live = IteratorSource::new(100..150u32);
+        // a single fold: the end of the backfill doesn't end the stream
+        let res = env
+            .stream_hybrid(history, live, |x| x)
+            .fold(Vec::new(), |v, x| v.push(x))
+            .collect_vec();
+        env.execute_blocking();
+        assert_eq!(res.get().unwrap(), vec![(0..150).collect::<Vec<_>>()]);
+    }
+
+    #[cfg(feature = "timestamp")]
+    #[test]
+    fn hybrid_watermarks() {
+        use crate::operator::source::ReplaySource;
+        use crate::operator::window::EventTimeWindow;
+
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let history =
+            ReplaySource::new(IteratorSource::new(vec![(0, 'a'), (100, 'b')].into_iter()))
+                .speedup(1e9);
+        // the live source starts before the last watermark of the backfill
+        let live = ReplaySource::new(IteratorSource::new(vec![(50, 'a'), (150, 'c')].into_iter()))
+            .speedup(1e9);
+        let res = env
+            .stream_hybrid(history, live, |x| x)
+            .window_all(EventTimeWindow::tumbling(1000))
+            .fold(Vec::new(), |v, x| v.push(x))
+            .drop_key()
+            .collect_vec();
+        env.execute_blocking();
+        assert_eq!(res.get().unwrap(), vec![vec!['a', 'b', 'c']]);
+    }
+}
@@ -1,6 +1,8 @@
 use std::fmt::Display;
 
 use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
+use crate::checkpoint::OperatorState;
 use crate::operator::source::Source;
 use crate::operator::{Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
@@ -19,6 +21,10 @@ where
     #[derivative(Debug = "ignore")]
     inner: It,
     terminated: bool,
+    cancellation: CancellationToken,
+    /// The number of items emitted, saved in the checkpoints.
+    emitted: u64,
+    state: OperatorState<u64>,
 }
 
 impl<It> Display for IteratorSource<It>
@@ -56,6 +62,9 @@ where
         Self {
             inner,
             terminated: false,
+            cancellation: Default::default(),
+            emitted: 0,
+            state: OperatorState::new(),
         }
     }
 }
@@ -77,15 +86,45 @@ where
 {
     type Out = It::Item;
 
-    fn setup(&mut self, _metadata: &mut ExecutionMetadata) {}
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
+        self.state.setup(metadata);
+        if let Some(emitted) = self.state.restore() {
+            // resume from where the source was when the checkpoint was taken
+            for _ in 0..emitted {
+                self.inner.next();
+            }
+            self.emitted = emitted;
+        }
+    }
 
     fn next(&mut self) -> StreamElement<Self::Out> {
         if self.terminated {
             return StreamElement::Terminate;
         }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
+        if self.state.inject_barrier() {
+            // the barrier of the checkpoint requested by the coordinator
+            self.state.snapshot(&self.emitted);
+            return StreamElement::FlushBatch;
+        }
+        // the replica had already ended in the restored checkpoint
+        if self.state.ended() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
         // TODO: with adaptive batching this does not work since it never emits FlushBatch messages
         match self.inner.next() {
-            Some(t) => StreamElement::Item(t),
+            Some(t) => {
+                self.emitted += 1;
+                StreamElement::Item(t)
+            }
             None => {
                 self.terminated = true;
                 StreamElement::FlushAndRestart
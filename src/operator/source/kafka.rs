@@ -1,14 +1,17 @@
 use std::fmt::Display;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use flume::Receiver;
 use futures::StreamExt;
-use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::consumer::{BaseConsumer, CommitMode, Consumer, StreamConsumer};
+use rdkafka::error::KafkaError;
 use rdkafka::message::OwnedMessage;
-use rdkafka::ClientConfig;
+use rdkafka::{ClientConfig, Message, Offset, TopicPartitionList};
 
 use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
 use crate::operator::source::Source;
 use crate::operator::{Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
@@ -18,6 +21,10 @@ enum KafkaSourceInner {
     Init {
         config: ClientConfig,
         topics: Vec<String>,
+        /// The position to start from, `None` for the committed offsets of the consumer group.
+        start: Option<KafkaOffset>,
+        /// The explicit start positions of some partitions.
+        partition_starts: Vec<(String, i32, KafkaOffset)>,
     },
     Running {
         rx: Receiver<OwnedMessage>,
@@ -29,9 +36,16 @@ enum KafkaSourceInner {
 impl Clone for KafkaSourceInner {
     fn clone(&self) -> Self {
         match self {
-            Self::Init { config, topics } => Self::Init {
+            Self::Init {
+                config,
+                topics,
+                start,
+                partition_starts,
+            } => Self::Init {
                 config: config.clone(),
                 topics: topics.clone(),
+                start: *start,
+                partition_starts: partition_starts.clone(),
             },
             _ => panic!("can only clone KafkaSource in itialization state"),
         }
@@ -53,6 +67,7 @@ pub struct KafkaSource {
     inner: KafkaSourceInner,
     replication: Replication,
     terminated: bool,
+    cancellation: CancellationToken,
 }
 
 impl Display for KafkaSource {
@@ -61,6 +76,75 @@ impl Display for KafkaSource {
     }
 }
 
+impl KafkaSource {
+    /// Consume `topics` as part of the consumer group of `config`, from its committed offsets.
+    ///
+    /// See [`StreamContext::stream_kafka`](crate::StreamContext::stream_kafka).
+    pub fn new(config: ClientConfig, topics: &[&str], replication: Replication) -> Self {
+        Self {
+            inner: KafkaSourceInner::Init {
+                config,
+                topics: topics.iter().map(|s| s.to_string()).collect(),
+                start: None,
+                partition_starts: Vec::new(),
+            },
+            replication,
+            terminated: false,
+            cancellation: Default::default(),
+        }
+    }
+
+    /// Start every partition of the topics from this position, instead of the committed offsets
+    /// of the consumer group, e.g. where the historical data read by a
+    /// [`HybridSource`](crate::operator::source::HybridSource) ends.
+    ///
+    /// With an explicit start the partitions are assigned to the replicas of the source, instead of
+    /// being balanced by the consumer group.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::prelude::*;
+    /// # use renoir::operator::source::{FileSource, KafkaOffset, KafkaSource};
+    /// # use rdkafka::ClientConfig;
+    /// # let mut env = StreamContext::new_local();
+    /// let mut config = ClientConfig::new();
+    /// config
+    ///     .set("bootstrap.servers", "localhost:9092")
+    ///     .set("group.id", "events");
+    /// // the file holds the events before the recorded offset
+    /// let history = FileSource::new("events.log");
+    /// let live = KafkaSource::new(config, &["events"], Replication::One)
+    ///     .partition_start("events", 0, KafkaOffset::Offset(1_000_000));
+    /// env.stream_hybrid(history, live, |msg| {
+    ///     use rdkafka::Message;
+    ///     String::from_utf8_lossy(msg.payload().unwrap_or_default()).into_owned()
+    /// })
+    /// .for_each(|event| println!("{event}"));
+    /// env.execute_blocking();
+    /// ```
+    pub fn start(mut self, position: KafkaOffset) -> Self {
+        if let KafkaSourceInner::Init { start, .. } = &mut self.inner {
+            *start = Some(position);
+        }
+        self
+    }
+
+    /// Start a specific partition from this position, overriding [`start`](Self::start) for it.
+    ///
+    /// If the partition does not belong to one of the topics given in the constructor it is read
+    /// as well.
+    pub fn partition_start(mut self, topic: &str, partition: i32, position: KafkaOffset) -> Self {
+        if let KafkaSourceInner::Init {
+            partition_starts, ..
+        } = &mut self.inner
+        {
+            partition_starts.push((topic.to_string(), partition, position));
+        }
+        self
+    }
+}
+
 impl Source for KafkaSource {
     fn replication(&self) -> Replication {
         self.replication
@@ -70,19 +154,67 @@ impl Source for KafkaSource {
 impl Operator for KafkaSource {
     type Out = rdkafka::message::OwnedMessage;
 
-    fn setup(&mut self, _metadata: &mut ExecutionMetadata) {
-        let KafkaSourceInner::Init { config, topics } = &self.inner else {
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
+        let KafkaSourceInner::Init {
+            config,
+            topics,
+            start,
+            partition_starts,
+        } = &self.inner
+        else {
             panic!("KafkaSource in invalid state")
         };
 
         let consumer = config
             .create::<StreamConsumer>()
             .expect("failed to create kafka consumer");
-        let t = topics.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
-        consumer
-            .subscribe(t.as_slice())
-            .expect("failed to subscribe to kafka topics");
-        tracing::debug!("kafka source subscribed to {topics:?}");
+        if start.is_none() && partition_starts.is_empty() {
+            let t = topics.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+            consumer
+                .subscribe(t.as_slice())
+                .expect("failed to subscribe to kafka topics");
+            tracing::debug!("kafka source subscribed to {topics:?}");
+        } else {
+            // the partitions are assigned to the replicas, like in the bounded source
+            let resolver = config
+                .create::<BaseConsumer>()
+                .expect("failed to create kafka consumer");
+            let mut starts: Vec<_> = partition_starts
+                .iter()
+                .map(|(topic, partition, start)| (topic.clone(), *partition, Some(*start)))
+                .collect();
+            for (topic, partition) in topic_partitions(&resolver, topics) {
+                if !starts
+                    .iter()
+                    .any(|(t, p, _)| *t == topic && *p == partition)
+                {
+                    starts.push((topic, partition, *start));
+                }
+            }
+            starts.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+
+            let instances = metadata.replicas.len();
+            let global_id = metadata.global_id as usize;
+            let mut tpl = TopicPartitionList::new();
+            for (i, (topic, partition, start)) in starts.into_iter().enumerate() {
+                if i % instances != global_id {
+                    continue;
+                }
+                let offset = match start {
+                    Some(start) => {
+                        Offset::Offset(resolve_offset(&resolver, &topic, partition, start))
+                    }
+                    None => Offset::Stored,
+                };
+                tracing::debug!("kafka source reading {topic}[{partition}] from {offset:?}");
+                tpl.add_partition_offset(&topic, partition, offset)
+                    .expect("invalid kafka partition");
+            }
+            consumer
+                .assign(&tpl)
+                .expect("failed to assign kafka partitions");
+        }
 
         let (tx, rx) = flume::bounded(8);
         let cancel_token = Arc::new(AtomicBool::new(false));
@@ -116,6 +248,16 @@ impl Operator for KafkaSource {
     }
 
     fn next(&mut self) -> StreamElement<Self::Out> {
+        if self.terminated {
+            return StreamElement::Terminate;
+        }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
         match &mut self.inner {
             KafkaSourceInner::Init { .. } => {
                 unreachable!("KafkaSource executing before setup!")
@@ -123,15 +265,19 @@ impl Operator for KafkaSource {
             // KafkaSourceInner::Terminated => return StreamElement::Terminate,
             KafkaSourceInner::Running { rx, cooldown, .. } => {
                 if *cooldown {
-                    match rx.recv() {
-                        Ok(msg) => {
+                    match self.cancellation.recv(rx) {
+                        Some(Ok(msg)) => {
                             *cooldown = false;
                             return StreamElement::Item(msg);
                         }
-                        Err(flume::RecvError::Disconnected) => {
+                        Some(Err(flume::RecvError::Disconnected)) => {
                             tracing::warn!("kafka background task disconnected.");
                             return StreamElement::Terminate;
                         }
+                        None => {
+                            self.terminated = true;
+                            return StreamElement::FlushAndRestart;
+                        }
                     }
                 }
 
@@ -171,6 +317,7 @@ impl Clone for KafkaSource {
             inner: self.inner.clone(),
             replication: self.replication,
             terminated: false,
+            cancellation: Default::default(),
         }
     }
 }
@@ -205,14 +352,400 @@ impl crate::StreamContext {
         topics: &[&str],
         replication: Replication,
     ) -> Stream<KafkaSource> {
-        let source = KafkaSource {
-            inner: KafkaSourceInner::Init {
-                config: client_config,
-                topics: topics.iter().map(|s| s.to_string()).collect(),
-            },
-            replication,
+        self.stream(KafkaSource::new(client_config, topics, replication))
+    }
+}
+
+/// Position in a kafka partition, used to delimit a bounded read or to start a live one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KafkaOffset {
+    /// The first offset available in the partition.
+    Beginning,
+    /// The end of the partition at the time the job starts.
+    End,
+    /// An explicit offset.
+    Offset(i64),
+    /// The first offset whose message timestamp (in milliseconds) is greater or equal to this.
+    Timestamp(i64),
+}
+
+/// Bounded read of a single kafka partition, from `start` (inclusive) to `end` (exclusive).
+#[derive(Clone, Debug)]
+struct KafkaPartitionRange {
+    topic: String,
+    partition: i32,
+    start: KafkaOffset,
+    end: KafkaOffset,
+}
+
+/// Source that reads a fixed range of offsets from each partition of some kafka topics and then
+/// terminates.
+///
+/// The end of each range is resolved when the job starts, so messages produced during the
+/// execution after the end of the range are not read, making the result of the job reproducible.
+/// The partitions are distributed among the replicas of the source. No offset is committed to
+/// the consumer group.
+///
+/// # WARNING: KAFKA API IS EXPERIMENTAL
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct KafkaBoundedSource {
+    #[derivative(Debug = "ignore")]
+    config: ClientConfig,
+    topics: Vec<String>,
+    start: KafkaOffset,
+    end: KafkaOffset,
+    ranges: Vec<KafkaPartitionRange>,
+    replication: Replication,
+    #[derivative(Debug = "ignore")]
+    rx: Option<Receiver<OwnedMessage>>,
+    cancel_token: Arc<AtomicBool>,
+    cooldown: bool,
+    terminated: bool,
+    cancellation: CancellationToken,
+}
+
+impl KafkaBoundedSource {
+    /// Read all the partitions of `topics` from the beginning to their current end.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::prelude::*;
+    /// # use renoir::operator::source::{KafkaBoundedSource, KafkaOffset};
+    /// # use rdkafka::ClientConfig;
+    /// # let mut env = StreamContext::new_local();
+    /// let mut config = ClientConfig::new();
+    /// config
+    ///     .set("bootstrap.servers", "localhost:9092")
+    ///     .set("group.id", "snapshot");
+    /// let source = KafkaBoundedSource::new(config, &["events"])
+    ///     .start(KafkaOffset::Timestamp(1_700_000_000_000))
+    ///     .partition_range("events", 0, KafkaOffset::Offset(100), KafkaOffset::Offset(200));
+    /// let res = env.stream(source).collect_count();
+    /// env.execute_blocking();
+    /// ```
+    pub fn new(config: ClientConfig, topics: &[&str]) -> Self {
+        Self {
+            config,
+            topics: topics.iter().map(|s| s.to_string()).collect(),
+            start: KafkaOffset::Beginning,
+            end: KafkaOffset::End,
+            ranges: Vec::new(),
+            replication: Replication::Unlimited,
+            rx: None,
+            cancel_token: Default::default(),
+            cooldown: false,
             terminated: false,
+            cancellation: Default::default(),
+        }
+    }
+
+    /// Position from which every partition of the topics is read, by default the beginning.
+    pub fn start(mut self, start: KafkaOffset) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// Position at which the read of every partition of the topics stops, by default the end of
+    /// the partition when the job starts.
+    pub fn end(mut self, end: KafkaOffset) -> Self {
+        self.end = end;
+        self
+    }
+
+    /// Read a specific range of a partition, overriding the default range for it.
+    ///
+    /// If the partition does not belong to one of the topics given in the constructor it is read
+    /// as well.
+    pub fn partition_range(
+        mut self,
+        topic: &str,
+        partition: i32,
+        start: KafkaOffset,
+        end: KafkaOffset,
+    ) -> Self {
+        self.ranges.push(KafkaPartitionRange {
+            topic: topic.to_string(),
+            partition,
+            start,
+            end,
+        });
+        self
+    }
+
+    /// Limit the number of replicas reading the partitions.
+    pub fn replication(mut self, replication: Replication) -> Self {
+        self.replication = replication;
+        self
+    }
+
+    /// List the ranges to read, the explicit ones override the default range of the topics.
+    fn resolve_ranges(&self, consumer: &BaseConsumer) -> Vec<KafkaPartitionRange> {
+        let mut ranges = self.ranges.clone();
+        for (topic, partition) in topic_partitions(consumer, &self.topics) {
+            if !ranges
+                .iter()
+                .any(|r| r.topic == topic && r.partition == partition)
+            {
+                ranges.push(KafkaPartitionRange {
+                    topic,
+                    partition,
+                    start: self.start,
+                    end: self.end,
+                });
+            }
+        }
+        ranges.sort_by(|a, b| (&a.topic, a.partition).cmp(&(&b.topic, b.partition)));
+        ranges
+    }
+}
+
+const KAFKA_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// List the partitions of `topics`.
+fn topic_partitions(consumer: &BaseConsumer, topics: &[String]) -> Vec<(String, i32)> {
+    let mut partitions = Vec::new();
+    for topic in topics {
+        let metadata = consumer
+            .fetch_metadata(Some(topic), KAFKA_TIMEOUT)
+            .expect("failed to fetch kafka topic metadata");
+        for t in metadata.topics() {
+            for p in t.partitions() {
+                partitions.push((t.name().to_string(), p.id()));
+            }
+        }
+    }
+    partitions
+}
+
+/// Resolve a position in a partition to a concrete offset.
+fn resolve_offset(consumer: &BaseConsumer, topic: &str, partition: i32, pos: KafkaOffset) -> i64 {
+    let (low, high) = consumer
+        .fetch_watermarks(topic, partition, KAFKA_TIMEOUT)
+        .expect("failed to fetch kafka partition watermarks");
+    match pos {
+        KafkaOffset::Beginning => low,
+        KafkaOffset::End => high,
+        KafkaOffset::Offset(offset) => offset.clamp(low, high),
+        KafkaOffset::Timestamp(ts) => {
+            let mut tpl = TopicPartitionList::new();
+            tpl.add_partition_offset(topic, partition, Offset::Offset(ts))
+                .expect("invalid kafka partition");
+            let offsets = consumer
+                .offsets_for_times(tpl, KAFKA_TIMEOUT)
+                .expect("failed to look up kafka offsets for timestamp");
+            match offsets.elements().first().map(|e| e.offset()) {
+                Some(Offset::Offset(offset)) => offset,
+                // no message after the timestamp
+                _ => high,
+            }
+        }
+    }
+}
+
+impl Display for KafkaBoundedSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "KafkaBoundedSource")
+    }
+}
+
+impl Source for KafkaBoundedSource {
+    fn replication(&self) -> Replication {
+        self.replication
+    }
+}
+
+impl Operator for KafkaBoundedSource {
+    type Out = OwnedMessage;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
+        // the end of the log is notified, in case the messages before the end of a range are
+        // missing (e.g. compacted or transaction markers)
+        let consumer = self
+            .config
+            .clone()
+            .set("enable.partition.eof", "true")
+            .create::<BaseConsumer>()
+            .expect("failed to create kafka consumer");
+        let consumer = Arc::new(consumer);
+
+        let instances = metadata.replicas.len();
+        let global_id = metadata.global_id as usize;
+        let mut end_offsets = Vec::new();
+        let mut tpl = TopicPartitionList::new();
+        for (i, range) in self.resolve_ranges(&consumer).into_iter().enumerate() {
+            if i % instances != global_id {
+                continue;
+            }
+            let start = resolve_offset(&consumer, &range.topic, range.partition, range.start);
+            let end = resolve_offset(&consumer, &range.topic, range.partition, range.end);
+            tracing::debug!(
+                "kafka bounded source reading {}[{}] from {start} to {end}",
+                range.topic,
+                range.partition
+            );
+            if start < end {
+                tpl.add_partition_offset(&range.topic, range.partition, Offset::Offset(start))
+                    .expect("invalid kafka partition");
+                end_offsets.push((range.topic, range.partition, end));
+            }
+        }
+
+        let (tx, rx) = flume::bounded(8);
+        let cancel = self.cancel_token.clone();
+        // the consumer is blocking, poll it from a dedicated thread
+        std::thread::Builder::new()
+            .name("renoir-kafka-bounded".into())
+            .spawn(move || {
+                if end_offsets.is_empty() {
+                    return;
+                }
+                consumer
+                    .assign(&tpl)
+                    .expect("failed to assign kafka partitions");
+                // each partition is read from its own queue, to know which one reached the end of
+                // the log when the end is notified
+                let mut partitions: Vec<_> = end_offsets
+                    .into_iter()
+                    .map(|(topic, partition, end)| {
+                        let queue = consumer
+                            .split_partition_queue(&topic, partition)
+                            .expect("failed to split kafka partition queue");
+                        (queue, end)
+                    })
+                    .collect();
+                let mut idle = false;
+                while !partitions.is_empty() && !cancel.load(Ordering::SeqCst) {
+                    // serve the events of the consumer, the messages are in the partition queues
+                    let timeout = if idle { 100 } else { 0 };
+                    if let Some(Err(e)) = consumer.poll(Duration::from_millis(timeout)) {
+                        panic!("failed receiving from kafka: {e}");
+                    }
+                    idle = true;
+                    let mut i = 0;
+                    while i < partitions.len() {
+                        let (queue, end) = &partitions[i];
+                        let ended = match queue.poll(Duration::ZERO) {
+                            None => false,
+                            // the end of the range is not after the end of the log when the job
+                            // started, so all its messages have been read
+                            Some(Err(KafkaError::PartitionEOF(_))) => true,
+                            Some(Err(e)) => panic!("failed receiving from kafka: {e}"),
+                            Some(Ok(msg)) => {
+                                idle = false;
+                                if msg.offset() < *end && tx.send(msg.detach()).is_err() {
+                                    return;
+                                }
+                                msg.offset() + 1 >= *end
+                            }
+                        };
+                        if ended {
+                            partitions.swap_remove(i);
+                        } else {
+                            i += 1;
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn kafka consumer thread");
+        self.rx = Some(rx);
+    }
+
+    fn next(&mut self) -> StreamElement<Self::Out> {
+        if self.terminated {
+            return StreamElement::Terminate;
+        }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
+        let rx = self
+            .rx
+            .as_ref()
+            .expect("KafkaBoundedSource executing before setup!");
+        let msg = if self.cooldown {
+            match self.cancellation.recv(rx) {
+                Some(msg) => msg.map_err(|_| flume::RecvTimeoutError::Disconnected),
+                None => {
+                    self.terminated = true;
+                    return StreamElement::FlushAndRestart;
+                }
+            }
+        } else {
+            rx.recv_timeout(Duration::from_millis(100))
         };
+        match msg {
+            Ok(msg) => {
+                self.cooldown = false;
+                StreamElement::Item(msg)
+            }
+            Err(flume::RecvTimeoutError::Timeout) => {
+                self.cooldown = true;
+                StreamElement::FlushBatch
+            }
+            Err(flume::RecvTimeoutError::Disconnected) => {
+                // all the ranges have been read
+                self.terminated = true;
+                StreamElement::FlushAndRestart
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<Self::Out, _>("KafkaBoundedSource");
+        operator.kind = OperatorKind::Source;
+        BlockStructure::default().add_operator(operator)
+    }
+}
+
+impl Clone for KafkaBoundedSource {
+    fn clone(&self) -> Self {
+        assert!(
+            self.rx.is_none(),
+            "KafkaBoundedSource must be cloned before calling setup"
+        );
+        Self {
+            config: self.config.clone(),
+            topics: self.topics.clone(),
+            start: self.start,
+            end: self.end,
+            ranges: self.ranges.clone(),
+            replication: self.replication,
+            rx: None,
+            cancel_token: Default::default(),
+            cooldown: false,
+            terminated: false,
+            cancellation: Default::default(),
+        }
+    }
+}
+
+impl Drop for KafkaBoundedSource {
+    fn drop(&mut self) {
+        self.cancel_token.store(true, Ordering::SeqCst);
+    }
+}
+
+impl crate::StreamContext {
+    /// Convenience method, creates a `KafkaBoundedSource` reading `topics` between `start` and
+    /// `end` and makes a stream using `StreamContext::stream`
+    ///
+    /// # WARNING: KAFKA API IS EXPERIMENTAL
+    pub fn stream_kafka_bounded(
+        &self,
+        client_config: ClientConfig,
+        topics: &[&str],
+        start: KafkaOffset,
+        end: KafkaOffset,
+    ) -> Stream<KafkaBoundedSource> {
+        let source = KafkaBoundedSource::new(client_config, topics)
+            .start(start)
+            .end(end);
         self.stream(source)
     }
 }
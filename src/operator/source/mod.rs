@@ -1,34 +1,61 @@
 //! Utility traits and structures related to the source operators.
 
+#[cfg(feature = "arrow")]
+pub use self::arrow::*;
 pub use self::csv::*;
+#[cfg(feature = "object_store")]
+pub use self::object_store::*;
+#[cfg(feature = "tokio")]
+pub use async_source::*;
 #[cfg(feature = "tokio")]
 pub use async_stream::*;
 #[cfg(feature = "avro")]
 pub use avro::*;
 pub use channel::*;
+pub use directory::*;
 pub use file::*;
+pub use generator::*;
+pub use handoff::*;
+pub use hybrid::*;
 pub use iterator::*;
 #[cfg(feature = "rdkafka")]
 pub use kafka::*;
 pub use parallel_iterator::*;
 #[cfg(feature = "parquet")]
 pub use parquet::*;
+#[cfg(feature = "timestamp")]
+pub use replay::*;
+pub use stdin::*;
 
 use crate::{block::Replication, operator::Operator};
 
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(feature = "tokio")]
+mod async_source;
 #[cfg(feature = "tokio")]
 mod async_stream;
 #[cfg(feature = "avro")]
 mod avro;
 mod channel;
+mod compression;
 mod csv;
+mod directory;
 mod file;
+mod generator;
+mod handoff;
+mod hybrid;
 mod iterator;
 #[cfg(feature = "rdkafka")]
 mod kafka;
+#[cfg(feature = "object_store")]
+mod object_store;
 mod parallel_iterator;
 #[cfg(feature = "parquet")]
 mod parquet;
+#[cfg(feature = "timestamp")]
+mod replay;
+mod stdin;
 
 /// This trait marks all the operators that can be used as source.
 pub trait Source: Operator {
@@ -0,0 +1,359 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::ops::Range;
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use object_store::path::Path;
+use object_store::{ObjectMeta, ObjectStore};
+use url::Url;
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
+use crate::operator::source::Source;
+use crate::operator::{Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::Stream;
+
+/// Size of the ranges requested to the object store while reading an object.
+const CHUNK_SIZE: usize = 8 << 20;
+
+/// Byte range of a remote object assigned to a replica.
+#[derive(Debug, Clone)]
+struct ObjectRange {
+    location: Path,
+    /// Total size of the object.
+    size: usize,
+    /// Bytes assigned to this replica, following the same convention of
+    /// [`FileSource`](super::FileSource): a line belongs to the replica whose range contains its
+    /// first byte.
+    range: Range<usize>,
+}
+
+/// Buffered line reader over a byte range of a remote object.
+struct ObjectReader {
+    object: ObjectRange,
+    /// Offset inside the object of the first byte of `buffer`.
+    pos: usize,
+    buffer: Vec<u8>,
+}
+
+impl ObjectReader {
+    fn new(object: ObjectRange) -> Self {
+        Self {
+            pos: object.range.start,
+            object,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Fetch the next chunk of the object, returns false if the object has been fully read.
+    fn fetch(&mut self, store: &dyn ObjectStore, rt: &tokio::runtime::Handle) -> bool {
+        let start = self.pos + self.buffer.len();
+        if start >= self.object.size {
+            return false;
+        }
+        let end = (start + CHUNK_SIZE).min(self.object.size);
+        let bytes = rt
+            .block_on(store.get_range(&self.object.location, start..end))
+            .unwrap_or_else(|e| {
+                panic!(
+                    "ObjectStoreSource: error while reading {}: {e}",
+                    self.object.location
+                )
+            });
+        self.buffer.extend_from_slice(&bytes);
+        true
+    }
+
+    /// Read a line, including the terminator. Returns the line and the offset of its first byte.
+    fn read_line(
+        &mut self,
+        store: &dyn ObjectStore,
+        rt: &tokio::runtime::Handle,
+    ) -> Option<(usize, Vec<u8>)> {
+        let mut searched = 0;
+        loop {
+            if let Some(i) = self.buffer[searched..].iter().position(|&b| b == b'\n') {
+                let line_end = searched + i + 1;
+                let rest = self.buffer.split_off(line_end);
+                let line = std::mem::replace(&mut self.buffer, rest);
+                let start = self.pos;
+                self.pos += line_end;
+                return Some((start, line));
+            }
+            searched = self.buffer.len();
+            if !self.fetch(store, rt) {
+                if self.buffer.is_empty() {
+                    return None;
+                }
+                let line = std::mem::take(&mut self.buffer);
+                let start = self.pos;
+                self.pos += line.len();
+                return Some((start, line));
+            }
+        }
+    }
+}
+
+/// Source that reads text objects line-by-line from an object store (S3, GCS, Azure or any other
+/// backend supported by the [`object_store`](https://crates.io/crates/object_store) crate).
+///
+/// All the objects matching the prefix of the URL are listed and split into byte ranges across the
+/// replicas, in the same way [`FileSource`](super::FileSource) splits a local file.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct ObjectStoreSource {
+    url: String,
+    options: Vec<(String, String)>,
+    #[derivative(Debug = "ignore")]
+    store: Option<Arc<dyn ObjectStore>>,
+    prefix: Option<Path>,
+    #[derivative(Debug = "ignore")]
+    rt: Option<tokio::runtime::Handle>,
+    global_id: usize,
+    instances: usize,
+    /// Ranges still to be read, `None` until the objects have been listed.
+    pending: Option<VecDeque<ObjectRange>>,
+    #[derivative(Debug = "ignore")]
+    reader: Option<ObjectReader>,
+    terminated: bool,
+    cancellation: CancellationToken,
+}
+
+impl Display for ObjectStoreSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ObjectStoreSource<{}>", self.url)
+    }
+}
+
+impl ObjectStoreSource {
+    /// Create a new source that reads the lines of all the objects whose path starts with the
+    /// prefix of the given URL.
+    ///
+    /// The scheme of the URL selects the backend: `s3://bucket/prefix`, `gs://bucket/prefix`,
+    /// `az://container/prefix`, `file:///path`... Credentials are read from the environment, as
+    /// documented by the `object_store` crate, unless they are passed with
+    /// [`ObjectStoreSource::option`].
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::ObjectStoreSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let source = ObjectStoreSource::new("s3://datasets/logs/2024-")
+    ///     .option("aws_region", "eu-south-1");
+    /// let s = env.stream(source);
+    /// ```
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            options: Vec::new(),
+            store: None,
+            prefix: None,
+            rt: None,
+            global_id: 0,
+            instances: 1,
+            pending: None,
+            reader: None,
+            terminated: false,
+            cancellation: Default::default(),
+        }
+    }
+
+    /// Set a configuration option for the object store backend (e.g. `aws_access_key_id`).
+    pub fn option(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.push((key.into(), value.into()));
+        self
+    }
+
+    /// List the objects and compute the byte ranges assigned to this replica.
+    fn assign_ranges(&self, mut objects: Vec<ObjectMeta>) -> VecDeque<ObjectRange> {
+        objects.sort_by(|a, b| a.location.cmp(&b.location));
+        let total: usize = objects.iter().map(|o| o.size).sum();
+        let range_size = total / self.instances;
+        let start = range_size * self.global_id;
+        let end = if self.global_id == self.instances - 1 {
+            total
+        } else {
+            start + range_size
+        };
+
+        // map the global range of this replica to the ranges of the objects
+        let mut offset = 0;
+        let mut ranges = VecDeque::new();
+        for object in objects {
+            let obj_start = offset;
+            let obj_end = offset + object.size;
+            offset = obj_end;
+            if obj_end <= start || obj_start > end || (obj_start == end && end != total) {
+                continue;
+            }
+            let range = start.saturating_sub(obj_start)..(end - obj_start).min(object.size);
+            ranges.push_back(ObjectRange {
+                location: object.location,
+                size: object.size,
+                range,
+            });
+        }
+        ranges
+    }
+
+    fn list(&self) -> VecDeque<ObjectRange> {
+        let store = self.store.as_ref().unwrap();
+        let rt = self.rt.as_ref().unwrap();
+        let objects: Vec<ObjectMeta> = rt
+            .block_on(store.list(self.prefix.as_ref()).try_collect())
+            .unwrap_or_else(|e| panic!("ObjectStoreSource: error listing {}: {e}", self.url));
+        tracing::debug!(
+            "object store source {} found {} objects",
+            self.url,
+            objects.len()
+        );
+        self.assign_ranges(objects)
+    }
+}
+
+impl Source for ObjectStoreSource {
+    fn replication(&self) -> Replication {
+        Replication::Unlimited
+    }
+}
+
+impl Operator for ObjectStoreSource {
+    type Out = String;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
+        let url = Url::parse(&self.url)
+            .unwrap_or_else(|e| panic!("ObjectStoreSource: invalid url {}: {e}", self.url));
+        let (store, prefix) = object_store::parse_url_opts(&url, self.options.clone())
+            .unwrap_or_else(|e| panic!("ObjectStoreSource: unsupported url {}: {e}", self.url));
+        self.store = Some(Arc::from(store));
+        self.prefix = Some(prefix);
+        self.rt = Some(tokio::runtime::Handle::current());
+        self.global_id = metadata.global_id as usize;
+        self.instances = metadata.replicas.len();
+    }
+
+    fn next(&mut self) -> StreamElement<String> {
+        if self.terminated {
+            return StreamElement::Terminate;
+        }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
+        if self.pending.is_none() {
+            self.pending = Some(self.list());
+        }
+        let store = self.store.clone().unwrap();
+        let rt = self.rt.clone().unwrap();
+        loop {
+            if self.reader.is_none() {
+                match self.pending.as_mut().unwrap().pop_front() {
+                    Some(object) => {
+                        let mut reader = ObjectReader::new(object);
+                        if reader.object.range.start != 0 {
+                            // discard first line, it belongs to the previous replica
+                            reader.read_line(store.as_ref(), &rt);
+                        }
+                        self.reader = Some(reader);
+                    }
+                    None => {
+                        self.terminated = true;
+                        return StreamElement::FlushAndRestart;
+                    }
+                }
+            }
+            let reader = self.reader.as_mut().unwrap();
+            if reader.pos <= reader.object.range.end {
+                if let Some((start, line)) = reader.read_line(store.as_ref(), &rt) {
+                    if start <= reader.object.range.end {
+                        let line = String::from_utf8(line).unwrap_or_else(|e| {
+                            panic!("ObjectStoreSource: invalid utf-8 in {}: {e}", self.url)
+                        });
+                        return StreamElement::Item(line);
+                    }
+                }
+            }
+            self.reader = None;
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<String, _>("ObjectStoreSource");
+        operator.kind = OperatorKind::Source;
+        BlockStructure::default().add_operator(operator)
+    }
+}
+
+impl Clone for ObjectStoreSource {
+    fn clone(&self) -> Self {
+        assert!(
+            self.store.is_none(),
+            "ObjectStoreSource must be cloned before calling setup"
+        );
+        Self {
+            url: self.url.clone(),
+            options: self.options.clone(),
+            store: None,
+            prefix: None,
+            rt: None,
+            global_id: 0,
+            instances: 1,
+            pending: None,
+            reader: None,
+            terminated: false,
+            cancellation: Default::default(),
+        }
+    }
+}
+
+impl crate::StreamContext {
+    /// Convenience method, creates a `ObjectStoreSource` and makes a stream using `StreamContext::stream`
+    pub fn stream_object_store(&self, url: impl Into<String>) -> Stream<ObjectStoreSource> {
+        let source = ObjectStoreSource::new(url);
+        self.stream(source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Write;
+
+    use itertools::Itertools;
+    use url::Url;
+
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn object_store_split() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut expected = Vec::new();
+        // objects of different sizes, including an empty one
+        for (i, lines) in [0, 1, 57, 300].into_iter().enumerate() {
+            let mut file = File::create(dir.path().join(format!("part-{i}.txt"))).unwrap();
+            for j in 0..lines {
+                let line = format!("{i} {j}\n");
+                file.write_all(line.as_bytes()).unwrap();
+                expected.push(line);
+            }
+        }
+
+        let url = Url::from_directory_path(dir.path()).unwrap();
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env.stream_object_store(url.as_str()).collect_vec();
+        env.execute().await;
+
+        // each line is emitted by exactly one replica
+        let res = res.get().unwrap().into_iter().sorted().collect_vec();
+        assert_eq!(res, expected.into_iter().sorted().collect_vec());
+    }
+}
@@ -2,6 +2,8 @@ use std::fmt::Display;
 use std::ops::Range;
 
 use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
+use crate::checkpoint::OperatorState;
 use crate::operator::source::Source;
 use crate::operator::{Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
@@ -133,6 +135,10 @@ where
     #[derivative(Debug = "ignore")]
     inner: IteratorGenerator<Source>,
     terminated: bool,
+    cancellation: CancellationToken,
+    /// The number of items emitted, saved in the checkpoints.
+    emitted: u64,
+    state: OperatorState<u64>,
 }
 
 impl<Source> Display for ParallelIteratorSource<Source>
@@ -157,6 +163,7 @@ where
     type Out = <S::Iter as Iterator>::Item;
 
     fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
         self.inner.generate(
             metadata.global_id,
             metadata
@@ -165,15 +172,43 @@ where
                 .try_into()
                 .expect("Num replicas > max id"),
         );
+        self.state.setup(metadata);
+        if let Some(emitted) = self.state.restore() {
+            // resume from where the source was when the checkpoint was taken
+            for _ in 0..emitted {
+                self.inner.next();
+            }
+            self.emitted = emitted;
+        }
     }
 
     fn next(&mut self) -> StreamElement<Self::Out> {
         if self.terminated {
             return StreamElement::Terminate;
         }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
+        if self.state.inject_barrier() {
+            // the barrier of the checkpoint requested by the coordinator
+            self.state.snapshot(&self.emitted);
+            return StreamElement::FlushBatch;
+        }
+        // the replica had already ended in the restored checkpoint
+        if self.state.ended() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
         // TODO: with adaptive batching this does not work since it never emits FlushBatch messages
         match self.inner.next() {
-            Some(t) => StreamElement::Item(t),
+            Some(t) => {
+                self.emitted += 1;
+                StreamElement::Item(t)
+            }
             None => {
                 self.terminated = true;
                 StreamElement::FlushAndRestart
@@ -197,6 +232,9 @@ where
         Self {
             inner: self.inner.clone(),
             terminated: false,
+            cancellation: Default::default(),
+            emitted: 0,
+            state: OperatorState::new(),
         }
     }
 }
@@ -275,6 +313,9 @@ where
         Self {
             inner: IteratorGenerator::Generator(generator),
             terminated: false,
+            cancellation: Default::default(),
+            emitted: 0,
+            state: OperatorState::new(),
         }
     }
 }
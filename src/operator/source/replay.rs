@@ -0,0 +1,203 @@
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::operator::source::Source;
+use crate::operator::{Operator, StreamElement, Timestamp};
+use crate::scheduler::ExecutionMetadata;
+use crate::Stream;
+
+/// Source that replays a recorded trace of `(timestamp, payload)` records, emitting each record
+/// when its time comes.
+///
+/// The timestamps are interpreted as milliseconds: the gap between two consecutive records is
+/// reproduced (divided by the speedup factor) before emitting the second one. Each record is
+/// emitted as a timestamped element and watermarks are generated as the timestamps advance, so
+/// the replayed stream can be used directly with event-time windows.
+///
+/// The records produced by each replica of the inner source should be sorted by timestamp,
+/// records older than `max_delay` with respect to the latest timestamp may be considered late
+/// by the following operators.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct ReplaySource<S, T>
+where
+    S: Source<Out = (Timestamp, T)>,
+    T: Send,
+{
+    inner: S,
+    speedup: f64,
+    max_delay: Timestamp,
+    /// Wall clock and timestamp of the first record.
+    origin: Option<(Instant, Timestamp)>,
+    /// Record read from the inner source but not emitted yet.
+    #[derivative(Debug = "ignore")]
+    pending: Option<(Timestamp, T)>,
+    last_watermark: Option<Timestamp>,
+    flushed: bool,
+}
+
+impl<S, T> Display for ReplaySource<S, T>
+where
+    S: Source<Out = (Timestamp, T)>,
+    T: Send,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> Replay", self.inner)
+    }
+}
+
+impl<S, T> ReplaySource<S, T>
+where
+    S: Source<Out = (Timestamp, T)>,
+    T: Send,
+{
+    /// Wrap a source producing `(timestamp, payload)` records, replaying them in real time.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::{IteratorSource, ReplaySource};
+    /// # let mut env = StreamContext::new_local();
+    /// let trace = vec![(0, 'a'), (100, 'b'), (250, 'c')];
+    /// // replay the trace 10 times faster than it was recorded
+    /// let source = ReplaySource::new(IteratorSource::new(trace.into_iter())).speedup(10.0);
+    /// let res = env.stream(source).collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec!['a', 'b', 'c']);
+    /// ```
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            speedup: 1.0,
+            max_delay: 0,
+            origin: None,
+            pending: None,
+            last_watermark: None,
+            flushed: true,
+        }
+    }
+
+    /// Replay the trace `speedup` times faster than it was recorded.
+    pub fn speedup(mut self, speedup: f64) -> Self {
+        assert!(speedup > 0.0, "the speedup factor must be positive");
+        self.speedup = speedup;
+        self
+    }
+
+    /// Lag of the generated watermarks behind the latest timestamp, useful when the trace is not
+    /// perfectly sorted.
+    pub fn max_delay(mut self, max_delay: Timestamp) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Instant at which the record with the given timestamp should be emitted.
+    fn deadline(&mut self, ts: Timestamp) -> Instant {
+        let (wall, first) = *self.origin.get_or_insert_with(|| (Instant::now(), ts));
+        let offset = (ts - first).max(0) as f64 / self.speedup;
+        wall + Duration::from_secs_f64(offset / 1000.0)
+    }
+}
+
+impl<S, T> Source for ReplaySource<S, T>
+where
+    S: Source<Out = (Timestamp, T)>,
+    T: Send,
+{
+    fn replication(&self) -> Replication {
+        self.inner.replication()
+    }
+}
+
+impl<S, T> Operator for ReplaySource<S, T>
+where
+    S: Source<Out = (Timestamp, T)>,
+    T: Send,
+{
+    type Out = T;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.inner.setup(metadata);
+    }
+
+    fn next(&mut self) -> StreamElement<T> {
+        let (ts, item) = match self.pending.take() {
+            Some(record) => record,
+            None => match self.inner.next() {
+                StreamElement::Item((ts, item)) | StreamElement::Timestamped((ts, item), _) => {
+                    (ts, item)
+                }
+                StreamElement::Watermark(_) | StreamElement::FlushBatch => {
+                    return StreamElement::FlushBatch
+                }
+                StreamElement::FlushAndRestart => {
+                    self.origin = None;
+                    self.last_watermark = None;
+                    return StreamElement::FlushAndRestart;
+                }
+                StreamElement::Terminate => return StreamElement::Terminate,
+            },
+        };
+
+        let deadline = self.deadline(ts);
+        let now = Instant::now();
+        if deadline > now {
+            if !self.flushed {
+                self.flushed = true;
+                self.pending = Some((ts, item));
+                return StreamElement::FlushBatch;
+            }
+            std::thread::sleep(deadline - now);
+        }
+
+        // the watermark is advanced right before emitting a record with a newer timestamp
+        let watermark = ts - self.max_delay - 1;
+        if self.last_watermark.map(|w| watermark > w).unwrap_or(true) {
+            self.last_watermark = Some(watermark);
+            self.pending = Some((ts, item));
+            return StreamElement::Watermark(watermark);
+        }
+        self.flushed = false;
+        StreamElement::Timestamped(item, ts)
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<T, _>("ReplaySource");
+        operator.kind = OperatorKind::Source;
+        self.inner.structure().add_operator(operator)
+    }
+}
+
+impl<S, T> Clone for ReplaySource<S, T>
+where
+    S: Source<Out = (Timestamp, T)>,
+    T: Send,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            speedup: self.speedup,
+            max_delay: self.max_delay,
+            origin: None,
+            pending: None,
+            last_watermark: None,
+            flushed: true,
+        }
+    }
+}
+
+impl crate::StreamContext {
+    /// Convenience method, creates a `ReplaySource` wrapping `source` and makes a stream using
+    /// `StreamContext::stream`
+    pub fn stream_replay<S, T>(&self, source: S, speedup: f64) -> Stream<ReplaySource<S, T>>
+    where
+        S: Source<Out = (Timestamp, T)> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.stream(ReplaySource::new(source).speedup(speedup))
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt::Display;
+use std::io::BufRead;
+
+use flume::{Receiver, RecvError, TryRecvError};
+
+use crate::block::{BlockStructure, OperatorKind, OperatorStructure, Replication};
+use crate::cancellation::CancellationToken;
+use crate::operator::source::Source;
+use crate::operator::{Operator, StreamElement};
+use crate::scheduler::ExecutionMetadata;
+use crate::Stream;
+
+/// Number of lines buffered between the thread reading the standard input and the source.
+const CHANNEL_SIZE: usize = 1024;
+
+/// Source that reads the standard input line by line.
+///
+/// The standard input is read **only from one replica**, located on the first host, therefore
+/// this source is not parallel. Each line is emitted without the trailing newline. When no input
+/// is available the current batch is flushed, so the source can be used to process interactive
+/// input or the output of long-running commands.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct StdinSource {
+    #[derivative(Debug = "ignore")]
+    rx: Option<Receiver<String>>,
+    flushed: bool,
+    terminated: bool,
+    cancellation: CancellationToken,
+}
+
+impl Display for StdinSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "StdinSource<{}>", std::any::type_name::<String>())
+    }
+}
+
+impl StdinSource {
+    /// Create a new source that reads the lines from the standard input.
+    ///
+    /// **Note**: this source is **not parallel**, use [`StreamContext::stream_stdin`] to
+    /// distribute the lines among all the replicas of the following operators.
+    ///
+    /// [`StreamContext::stream_stdin`]: crate::StreamContext::stream_stdin
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::StdinSource;
+    /// # let mut env = StreamContext::new_local();
+    /// let source = StdinSource::new();
+    /// let s = env.stream(source);
+    /// ```
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            rx: None,
+            flushed: true,
+            terminated: false,
+            cancellation: Default::default(),
+        }
+    }
+}
+
+impl Source for StdinSource {
+    fn replication(&self) -> Replication {
+        Replication::One
+    }
+}
+
+impl Operator for StdinSource {
+    type Out = String;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.cancellation = metadata.cancellation.clone();
+        let (tx, rx) = flume::bounded(CHANNEL_SIZE);
+        // reading from stdin blocks, use a dedicated thread so the source can flush when idle
+        std::thread::Builder::new()
+            .name("renoir-stdin".into())
+            .spawn(move || {
+                for line in std::io::stdin().lock().lines() {
+                    let line = line.expect("failed to read from stdin");
+                    if tx.send(line).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn stdin reader thread");
+        self.rx = Some(rx);
+    }
+
+    fn next(&mut self) -> StreamElement<String> {
+        if self.terminated {
+            return StreamElement::Terminate;
+        }
+        if self.cancellation.hold() {
+            return StreamElement::FlushBatch;
+        }
+        if self.cancellation.is_cancelled() {
+            self.terminated = true;
+            return StreamElement::FlushAndRestart;
+        }
+        let rx = self.rx.as_ref().expect("StdinSource was not set up");
+        let line = match rx.try_recv() {
+            Ok(line) => Ok(line),
+            Err(TryRecvError::Empty) if !self.flushed => {
+                // no input ready: flush what has been read so far before blocking
+                self.flushed = true;
+                return StreamElement::FlushBatch;
+            }
+            Err(TryRecvError::Empty) => match self.cancellation.recv(rx) {
+                Some(result) => result,
+                None => {
+                    self.terminated = true;
+                    return StreamElement::FlushAndRestart;
+                }
+            },
+            Err(TryRecvError::Disconnected) => Err(RecvError::Disconnected),
+        };
+        match line {
+            Ok(line) => {
+                self.flushed = false;
+                StreamElement::Item(line)
+            }
+            Err(RecvError::Disconnected) => {
+                self.terminated = true;
+                StreamElement::FlushAndRestart
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        let mut operator = OperatorStructure::new::<String, _>("StdinSource");
+        operator.kind = OperatorKind::Source;
+        BlockStructure::default().add_operator(operator)
+    }
+}
+
+impl Clone for StdinSource {
+    fn clone(&self) -> Self {
+        // Since this is a non-parallel source, we don't want the other replicas to emit any value
+        panic!("StdinSource cannot be cloned, replication should be 1");
+    }
+}
+
+impl crate::StreamContext {
+    /// Read the standard input of the first host line by line and distribute the lines among the
+    /// replicas of the following operators.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # let mut env = StreamContext::new_local();
+    /// // count the words read from stdin
+    /// let res = env
+    ///     .stream_stdin()
+    ///     .flat_map(|line| line.split_whitespace().map(str::to_owned).collect::<Vec<_>>())
+    ///     .group_by_count(|w| w.clone())
+    ///     .collect_vec();
+    /// env.execute_blocking();
+    /// ```
+    pub fn stream_stdin(&self) -> Stream<impl Operator<Out = String>> {
+        self.stream(StdinSource::new()).shuffle()
+    }
+}
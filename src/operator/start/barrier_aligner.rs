@@ -0,0 +1,183 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::checkpoint::CheckpointId;
+use crate::network::{Coord, NetworkMessage};
+
+/// Align the checkpoint barriers coming from multiple replicas.
+///
+/// When a replica sends the barrier of a checkpoint, the messages it sends after it belong to the
+/// next checkpoint: they are held back until the barrier has been received from all the previous
+/// replicas, i.e. the barriers are aligned. Then the snapshot can be taken and the held messages
+/// are released, in the order they were received.
+///
+/// The replicas that have already ended count as aligned. A barrier of a newer checkpoint received
+/// while aligning abandons the current one.
+///
+/// Without alignment (at-least-once delivery) the messages are never held back: the checkpoint
+/// still starts when all the barriers are received, but its state may include some messages that
+/// follow them.
+#[derive(Debug)]
+pub(super) struct BarrierAligner<T> {
+    prev_replicas: HashSet<Coord>,
+    ended: HashSet<Coord>,
+    /// The checkpoint being aligned, with the replicas that already sent its barrier.
+    aligning: Option<(CheckpointId, HashSet<Coord>)>,
+    /// The last checkpoint aligned.
+    last: CheckpointId,
+    held: VecDeque<NetworkMessage<T>>,
+    released: VecDeque<NetworkMessage<T>>,
+    aligned: bool,
+}
+
+/// Like the batch being iterated by `Start`, the messages in flight are not cloned.
+impl<T> Clone for BarrierAligner<T> {
+    fn clone(&self) -> Self {
+        Self {
+            prev_replicas: self.prev_replicas.clone(),
+            ended: self.ended.clone(),
+            aligning: self.aligning.clone(),
+            last: self.last,
+            held: Default::default(),
+            released: Default::default(),
+            aligned: self.aligned,
+        }
+    }
+}
+
+impl<T> Default for BarrierAligner<T> {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl<T> BarrierAligner<T> {
+    pub fn new(prev_replicas: impl IntoIterator<Item = Coord>) -> Self {
+        Self {
+            prev_replicas: prev_replicas.into_iter().collect(),
+            ended: Default::default(),
+            aligning: None,
+            last: 0,
+            held: Default::default(),
+            released: Default::default(),
+            aligned: true,
+        }
+    }
+
+    /// Whether the messages that follow a barrier are held back until the barriers are aligned.
+    pub fn with_alignment(mut self, aligned: bool) -> Self {
+        self.aligned = aligned;
+        self
+    }
+
+    /// Whether the messages from `sender` have to be held back.
+    pub fn is_held(&self, sender: Coord) -> bool {
+        self.aligned
+            && self
+                .aligning
+                .as_ref()
+                .is_some_and(|(_, received)| received.contains(&sender))
+    }
+
+    /// Hold back a message until the barriers are aligned.
+    pub fn hold(&mut self, message: NetworkMessage<T>) {
+        self.held.push_back(message);
+    }
+
+    /// The next message released after an alignment.
+    pub fn next_released(&mut self) -> Option<NetworkMessage<T>> {
+        self.released.pop_front()
+    }
+
+    /// Record the barrier of the checkpoint `id` from `sender`.
+    ///
+    /// Returns the id of the checkpoint if the barriers are now aligned.
+    pub fn barrier(&mut self, sender: Coord, id: CheckpointId) -> Option<CheckpointId> {
+        if id <= self.last || !self.prev_replicas.contains(&sender) {
+            return None;
+        }
+        match &mut self.aligning {
+            Some((current, received)) if *current == id => {
+                received.insert(sender);
+            }
+            Some((current, _)) if *current > id => return None,
+            _ => {
+                if let Some((current, _)) = self.aligning.take() {
+                    log::warn!("abandoning the alignment of checkpoint {current} for {id}");
+                    self.released.append(&mut self.held);
+                }
+                self.aligning = Some((id, HashSet::from([sender])));
+            }
+        }
+        self.check_aligned()
+    }
+
+    /// Record that `sender` has ended, so it won't send any more barrier.
+    ///
+    /// Returns the id of the checkpoint if the barriers are now aligned.
+    pub fn end(&mut self, sender: Coord) -> Option<CheckpointId> {
+        self.ended.insert(sender);
+        self.check_aligned()
+    }
+
+    fn check_aligned(&mut self) -> Option<CheckpointId> {
+        let (id, received) = self.aligning.as_ref()?;
+        let aligned = self
+            .prev_replicas
+            .iter()
+            .all(|c| received.contains(c) || self.ended.contains(c));
+        if !aligned {
+            return None;
+        }
+        let id = *id;
+        self.aligning = None;
+        self.last = id;
+        self.released.append(&mut self.held);
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator::StreamElement;
+
+    fn coord(replica_id: u64) -> Coord {
+        Coord::new(0, 0, replica_id)
+    }
+
+    #[test]
+    fn barrier_alignment() {
+        let mut aligner = BarrierAligner::new([coord(0), coord(1), coord(2)]);
+
+        assert_eq!(aligner.barrier(coord(0), 1), None);
+        assert!(aligner.is_held(coord(0)));
+        assert!(!aligner.is_held(coord(1)));
+        aligner.hold(NetworkMessage::new_single(StreamElement::Item(1), coord(0)));
+
+        // a newer checkpoint abandons the current alignment
+        assert_eq!(aligner.barrier(coord(1), 2), None);
+        assert_eq!(aligner.next_released().unwrap().sender(), coord(0));
+        assert!(aligner.next_released().is_none());
+        assert!(!aligner.is_held(coord(0)));
+
+        assert_eq!(aligner.barrier(coord(0), 2), None);
+        aligner.hold(NetworkMessage::new_single(StreamElement::Item(2), coord(0)));
+        assert_eq!(aligner.end(coord(2)), Some(2));
+        assert_eq!(aligner.next_released().unwrap().sender(), coord(0));
+        assert!(!aligner.is_held(coord(0)));
+
+        // stale barriers are ignored
+        assert_eq!(aligner.barrier(coord(1), 1), None);
+        assert!(!aligner.is_held(coord(1)));
+    }
+
+    #[test]
+    fn barrier_unaligned() {
+        let mut aligner = BarrierAligner::<i32>::new([coord(0), coord(1)]).with_alignment(false);
+
+        assert_eq!(aligner.barrier(coord(0), 1), None);
+        assert!(!aligner.is_held(coord(0)));
+        assert_eq!(aligner.barrier(coord(1), 1), Some(1));
+        assert!(aligner.next_released().is_none());
+    }
+}
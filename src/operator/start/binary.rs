@@ -144,6 +144,16 @@ impl<OutL: ExchangeData, OutR: ExchangeData> BinaryStartReceiver<OutL, OutR> {
         }
     }
 
+    /// The replicas of the left side, or `None` if the side is cached.
+    pub(crate) fn left_replicas(&self) -> Option<Vec<Coord>> {
+        (!self.left.cached).then(|| self.left.receiver.prev_replicas())
+    }
+
+    /// The replicas of the right side, or `None` if the side is cached.
+    pub(crate) fn right_replicas(&self) -> Option<Vec<Coord>> {
+        (!self.right.cached).then(|| self.right.receiver.prev_replicas())
+    }
+
     /// Process the incoming batch from one of the two sides.
     ///
     /// This will map all the elements of the batch into a new batch whose elements are wrapped in
@@ -157,6 +167,10 @@ impl<OutL: ExchangeData, OutR: ExchangeData> BinaryStartReceiver<OutL, OutR> {
         end: BinaryElement<OutL, OutR>,
     ) -> NetworkMessage<BinaryElement<OutL, OutR>> {
         let sender = message.sender();
+        // the barriers are aligned by the `Start`, they are not put in the cache
+        if let Some(id) = message.barrier() {
+            return NetworkMessage::new_barrier(id, sender);
+        }
         let data = message
             .into_iter()
             .flat_map(|item| {
@@ -324,6 +338,17 @@ impl<OutL: ExchangeData, OutR: ExchangeData> StartReceiver for BinaryStartReceiv
         previous
     }
 
+    fn barrier_replicas(&self) -> Vec<Coord> {
+        let mut previous = Vec::new();
+        if !self.left.cached {
+            previous.append(&mut self.left.receiver.prev_replicas());
+        }
+        if !self.right.cached {
+            previous.append(&mut self.right.receiver.prev_replicas());
+        }
+        previous
+    }
+
     // fn cached_replicas(&self) -> usize {
     //     let mut cached = 0;
     //     if self.left.cached {
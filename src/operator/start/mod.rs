@@ -1,6 +1,6 @@
 use std::fmt::{Debug, Display};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 pub(crate) use binary::*;
 pub(crate) use simple::*;
@@ -9,13 +9,19 @@ pub(crate) use simple::*;
 use super::Timestamp;
 use crate::block::{BlockStructure, Replication};
 use crate::channel::RecvTimeoutError;
+use crate::checkpoint::{CheckpointId, ReplicaCheckpoint};
+use crate::config::DeliveryGuarantee;
 use crate::network::{Coord, NetworkDataIterator, NetworkMessage};
 use crate::operator::iteration::IterationStateLock;
 use crate::operator::source::Source;
+use crate::operator::start::barrier_aligner::BarrierAligner;
 use crate::operator::start::watermark_frontier::WatermarkFrontier;
 use crate::operator::{ExchangeData, Operator, StreamElement};
-use crate::scheduler::{BlockId, ExecutionMetadata};
+#[cfg(feature = "timestamp")]
+use crate::profiler::metrics;
+use crate::scheduler::{BlockId, ExecutionMetadata, WakeUp};
 
+mod barrier_aligner;
 mod binary;
 mod simple;
 mod watermark_frontier;
@@ -31,6 +37,13 @@ pub(crate) trait StartReceiver: Clone {
     /// This list should contain all the replicas this receiver will receive data from.
     fn prev_replicas(&self) -> Vec<Coord>;
 
+    /// Obtain the list of the previous replicas that send the checkpoint barriers.
+    ///
+    /// By default all the previous replicas, the cached ones don't take part in the checkpoints.
+    fn barrier_replicas(&self) -> Vec<Coord> {
+        self.prev_replicas()
+    }
+
     // /// The number of those replicas which are behind a cache, and therefore never will emit a
     // /// `StreamElement::Terminate` message.
     // fn cached_replicas(&self) -> usize;
@@ -90,10 +103,16 @@ pub(crate) struct Start<Receiver: StartReceiver + Send> {
     ///
     /// The next time `next()` is called it will not wait the timeout asked by the batch mode.
     already_timed_out: bool,
+    /// When the following operators have to be woken up, to fire their timers.
+    wake_up: WakeUp,
 
     /// The current frontier of the watermarks from the previous replicas.
     watermark_frontier: WatermarkFrontier,
 
+    /// The alignment of the checkpoint barriers from the previous replicas.
+    barriers: BarrierAligner<Receiver::Out>,
+    checkpoint: Option<Arc<ReplicaCheckpoint>>,
+
     /// Whether the iteration has ended and the current block has to wait for the local iteration
     /// leader to update the iteration state before letting the messages pass.
     wait_for_state: bool,
@@ -112,7 +131,10 @@ impl<Receiver: StartReceiver + Send> Clone for Start<Receiver> {
             missing_flush_and_restart: self.missing_flush_and_restart,
             num_previous_replicas: self.num_previous_replicas,
             already_timed_out: self.already_timed_out,
+            wake_up: Default::default(),
             watermark_frontier: self.watermark_frontier.clone(),
+            barriers: self.barriers.clone(),
+            checkpoint: self.checkpoint.clone(),
             wait_for_state: self.wait_for_state,
             state_lock: self.state_lock.clone(),
             state_generation: self.state_generation,
@@ -171,9 +193,13 @@ impl<Receiver: StartReceiver + Send> Start<Receiver> {
             num_previous_replicas: 0,
 
             already_timed_out: Default::default(),
+            wake_up: Default::default(),
 
             watermark_frontier: Default::default(),
 
+            barriers: Default::default(),
+            checkpoint: None,
+
             wait_for_state: Default::default(),
             state_lock,
             state_generation: Default::default(),
@@ -183,6 +209,18 @@ impl<Receiver: StartReceiver + Send> Start<Receiver> {
     pub(crate) fn receiver(&self) -> &Receiver {
         &self.receiver
     }
+
+    /// The replica that sent the last element returned by `next`, if it came from a batch.
+    pub(crate) fn last_sender(&self) -> Option<Coord> {
+        self.batch_iter.as_ref().map(|(sender, _)| *sender)
+    }
+
+    /// Start the snapshot of the checkpoint `id`, whose barriers are aligned.
+    fn begin_checkpoint(&self, id: CheckpointId) -> bool {
+        self.checkpoint
+            .as_ref()
+            .is_some_and(|checkpoint| checkpoint.begin(id))
+    }
 }
 
 impl<Receiver> Operator for Start<Receiver>
@@ -199,7 +237,16 @@ where
         self.num_previous_replicas = prev_replicas.len();
         self.missing_terminate = self.num_previous_replicas;
         self.missing_flush_and_restart = self.num_previous_replicas;
-        self.watermark_frontier = WatermarkFrontier::new(prev_replicas);
+        self.watermark_frontier = WatermarkFrontier::new(prev_replicas)
+            .with_idle_timeout(metadata.watermark_idle_timeout);
+        self.checkpoint = metadata.checkpoint.clone();
+        self.wake_up = metadata.wake_up.clone();
+        let aligned = self
+            .checkpoint
+            .as_ref()
+            .is_none_or(|c| c.guarantee() == DeliveryGuarantee::ExactlyOnce);
+        self.barriers =
+            BarrierAligner::new(self.receiver.barrier_replicas()).with_alignment(aligned);
 
         log::trace!(
             "{} initialized <{}>",
@@ -242,7 +289,11 @@ where
                             StreamElement::Watermark(ts) => {
                                 // update the frontier and return a watermark if necessary
                                 match self.watermark_frontier.update(sender, ts) {
-                                    Some(ts) => StreamElement::Watermark(ts), // ts is safe
+                                    Some(ts) => {
+                                        #[cfg(feature = "timestamp")]
+                                        metrics::watermark(coord, ts);
+                                        StreamElement::Watermark(ts) // ts is safe
+                                    }
                                     None => continue,
                                 }
                             }
@@ -262,6 +313,11 @@ where
                                     coord,
                                     self.missing_terminate
                                 );
+                                if let Some(id) = self.barriers.end(sender) {
+                                    if self.begin_checkpoint(id) {
+                                        return StreamElement::FlushBatch;
+                                    }
+                                }
                                 continue;
                             }
                             _ => item,
@@ -280,33 +336,77 @@ where
                 return msg;
             }
 
+            // the messages held back during the last alignment of the barriers
+            if let Some(net_msg) = self.barriers.next_released() {
+                self.batch_iter = Some((net_msg.sender(), net_msg.into_iter()));
+                continue;
+            }
+
             // Receive next batch
-            let net_msg = match (self.already_timed_out, self.max_delay) {
-                // check the timeout only if there is one and the last time we didn't timed out
-                (false, Some(max_delay)) => {
-                    match self.receiver.recv_timeout(max_delay) {
-                        Ok(net_msg) => net_msg,
+            // check the timeout of the batch only if there is one and the last time we didn't timed
+            // out, and wake up the operators that asked so
+            let batch_timeout = self.max_delay.filter(|_| !self.already_timed_out);
+            let wake_up = self
+                .wake_up
+                .deadline()
+                .map(|at| at.saturating_duration_since(Instant::now()));
+            let net_msg = match batch_timeout.into_iter().chain(wake_up).min() {
+                Some(timeout) => {
+                    match self.receiver.recv_timeout(timeout) {
+                        Ok(net_msg) => {
+                            self.already_timed_out = false;
+                            self.watermark_frontier.mark_active(net_msg.sender());
+                            net_msg
+                        }
                         Err(_) => {
                             // timed out: tell the block to flush the current batch
                             // next time we wait indefinitely without the timeout since the batch is
                             // currently empty
                             self.already_timed_out = true;
+                            self.wake_up.expire(Instant::now());
                             // this is a fake batch, and its sender is meaningless and will be
                             // forget immediately
-                            NetworkMessage::new_single(
+                            let net_msg = NetworkMessage::new_single(
                                 StreamElement::FlushBatch,
                                 Default::default(),
-                            )
+                            );
+                            // some previous replicas may have become idle
+                            if !self.wait_for_state {
+                                if let Some(ts) = self.watermark_frontier.refresh() {
+                                    #[cfg(feature = "timestamp")]
+                                    metrics::watermark(coord, ts);
+                                    self.batch_iter = Some((net_msg.sender(), net_msg.into_iter()));
+                                    return StreamElement::Watermark(ts);
+                                }
+                            }
+                            self.batch_iter = Some((net_msg.sender(), net_msg.into_iter()));
+                            continue;
                         }
                     }
                 }
-                _ => {
+                None => {
                     self.already_timed_out = false;
-                    self.receiver.recv()
+                    let net_msg = self.receiver.recv();
+                    self.watermark_frontier.mark_active(net_msg.sender());
+                    net_msg
                 }
             };
 
-            self.batch_iter = Some((net_msg.sender(), net_msg.into_iter()));
+            let sender = net_msg.sender();
+            if self.barriers.is_held(sender) {
+                self.barriers.hold(net_msg);
+                continue;
+            }
+            if let Some(id) = net_msg.barrier() {
+                // the barrier is emitted as a `FlushBatch` once aligned
+                if let Some(id) = self.barriers.barrier(sender, id) {
+                    if self.begin_checkpoint(id) {
+                        return StreamElement::FlushBatch;
+                    }
+                }
+                continue;
+            }
+            self.batch_iter = Some((sender, net_msg.into_iter()));
         }
     }
 
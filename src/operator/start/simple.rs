@@ -3,7 +3,7 @@ use std::time::Duration;
 
 use crate::block::{BlockStructure, OperatorReceiver, OperatorStructure};
 use crate::channel::RecvTimeoutError;
-use crate::network::{Coord, NetworkMessage, NetworkReceiver, ReceiverEndpoint};
+use crate::network::{Coord, NetworkMessage, NetworkReceiver, ReceiverEndpoint, Stealer};
 use crate::operator::start::StartReceiver;
 use crate::operator::ExchangeData;
 use crate::scheduler::{BlockId, ExecutionMetadata};
@@ -12,6 +12,8 @@ use crate::scheduler::{BlockId, ExecutionMetadata};
 #[derive(Debug)]
 pub(crate) struct SimpleStartReceiver<Out: ExchangeData> {
     pub(super) receiver: Option<NetworkReceiver<Out>>,
+    /// Present if the replica steals the batches of its siblings.
+    stealer: Option<Stealer<Out>>,
     previous_replicas: Vec<Coord>,
     pub(super) previous_block_id: BlockId,
 }
@@ -20,6 +22,7 @@ impl<Out: ExchangeData> SimpleStartReceiver<Out> {
     pub(super) fn new(previous_block_id: BlockId) -> Self {
         Self {
             receiver: None,
+            stealer: None,
             previous_replicas: Default::default(),
             previous_block_id,
         }
@@ -34,6 +37,7 @@ impl<Out: ExchangeData> StartReceiver for SimpleStartReceiver<Out> {
 
         let endpoint = ReceiverEndpoint::new(metadata.coord, self.previous_block_id);
         self.receiver = Some(metadata.network.get_receiver(endpoint));
+        self.stealer = metadata.network.get_stealer(endpoint);
 
         for &(prev, typ) in metadata.prev.iter() {
             // ignore this connection because it refers to a different type, another Start
@@ -53,12 +57,19 @@ impl<Out: ExchangeData> StartReceiver for SimpleStartReceiver<Out> {
 
     fn recv_timeout(&mut self, timeout: Duration) -> Result<NetworkMessage<Out>, RecvTimeoutError> {
         let receiver = self.receiver.as_mut().unwrap();
-        receiver.recv_timeout(timeout)
+        match self.stealer.as_mut() {
+            Some(stealer) => stealer.recv_timeout(receiver, Some(timeout)),
+            None => receiver.recv_timeout(timeout),
+        }
     }
 
     fn recv(&mut self) -> NetworkMessage<Out> {
         let receiver = self.receiver.as_mut().unwrap();
-        receiver.recv().expect("Network receiver failed")
+        match self.stealer.as_mut() {
+            Some(stealer) => stealer.recv_timeout(receiver, None).ok(),
+            None => receiver.recv().ok(),
+        }
+        .expect("Network receiver failed")
     }
 
     fn structure(&self) -> BlockStructure {
@@ -74,6 +85,7 @@ impl<Out: ExchangeData> Clone for SimpleStartReceiver<Out> {
     fn clone(&self) -> Self {
         Self {
             receiver: None,
+            stealer: None,
             previous_block_id: self.previous_block_id,
             previous_replicas: self.previous_replicas.clone(),
         }
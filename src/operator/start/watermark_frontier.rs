@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use indexmap::IndexMap;
 
 use crate::block::CoordHasherBuilder;
@@ -8,10 +10,17 @@ use crate::operator::Timestamp;
 ///
 /// A watermark with timestamp `ts` is safe to be passed downstream if and only if, for every
 /// previous replica, a watermark with timestamp greater or equal to `ts` has already been received.
+///
+/// If an idle timeout is set, the replicas that have not sent anything for longer than the
+/// timeout are ignored, until they send something again. The frontier never moves backwards:
+/// when an idle replica resumes with a lower watermark, the frontier waits for it to catch up.
 #[derive(Clone, Debug, Default)]
 pub(super) struct WatermarkFrontier {
     map: IndexMap<Coord, Option<Timestamp>, CoordHasherBuilder>,
     front: Option<Timestamp>,
+    idle_timeout: Option<Duration>,
+    /// The last time each replica sent something, tracked only with an idle timeout.
+    last_active: IndexMap<Coord, Instant, CoordHasherBuilder>,
 }
 
 fn opt_join<T: std::cmp::Ord>(a: Option<T>, b: Option<T>, f: fn(T, T) -> T) -> Option<T> {
@@ -26,13 +35,43 @@ impl WatermarkFrontier {
         Self {
             map: prev_replicas.into_iter().map(|c| (c, None)).collect(),
             front: None,
+            idle_timeout: None,
+            last_active: Default::default(),
+        }
+    }
+
+    /// Ignore the replicas that have not sent anything for `timeout`.
+    pub fn with_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        if timeout.is_some() {
+            let now = Instant::now();
+            self.last_active = self.map.keys().map(|&c| (c, now)).collect();
+        }
+        self
+    }
+
+    /// Record that a replica has sent something.
+    pub fn mark_active(&mut self, coord: Coord) {
+        if let Some(last_active) = self.last_active.get_mut(&coord) {
+            *last_active = Instant::now();
+        }
+    }
+
+    fn is_idle(&self, coord: &Coord) -> bool {
+        match (self.idle_timeout, self.last_active.get(coord)) {
+            (Some(timeout), Some(last_active)) => last_active.elapsed() >= timeout,
+            _ => false,
         }
     }
 
     fn compute_frontier(&self) -> Option<Timestamp> {
-        let (complete, min) = self.map.values().fold((true, None), |(all, min), x| {
-            (all & x.is_some(), opt_join(min, *x, std::cmp::min))
-        });
+        let (complete, min) = self
+            .map
+            .iter()
+            .filter(|(coord, _)| !self.is_idle(coord))
+            .fold((true, None), |(all, min), (_, x)| {
+                (all & x.is_some(), opt_join(min, *x, std::cmp::min))
+            });
 
         if complete {
             min
@@ -41,6 +80,24 @@ impl WatermarkFrontier {
         }
     }
 
+    /// Compute the frontier again, return `Some(ts)` if it advanced to `ts`.
+    pub fn refresh(&mut self) -> Option<Timestamp> {
+        let prev_frontier = self.front;
+        let new = self.compute_frontier();
+
+        match (prev_frontier, new) {
+            (None, Some(new)) => {
+                self.front = Some(new);
+                Some(new)
+            }
+            (Some(old), Some(new)) if new > old => {
+                self.front = Some(new);
+                Some(new)
+            }
+            _ => None,
+        }
+    }
+
     /// Update the frontier, return `Some(ts)` if timestamp `ts` is now safe
     pub fn update(&mut self, coord: Coord, ts: Timestamp) -> Option<Timestamp> {
         let t0 = &mut self.map[&coord];
@@ -50,20 +107,45 @@ impl WatermarkFrontier {
         }
         *t0 = Some(ts);
 
-        // get new frontier
-        let prev_frontier = self.front;
-        self.front = self.compute_frontier();
-
-        match (prev_frontier, self.front) {
-            (None, Some(new)) => Some(new),
-            (Some(old), Some(new)) if old != new => Some(new),
-            _ => None,
-        }
+        self.refresh()
     }
 
     /// Reset all the watermarks.
     pub fn reset(&mut self) {
         self.map.values_mut().for_each(|v| *v = None);
         self.front = None;
+        let now = Instant::now();
+        self.last_active.values_mut().for_each(|t| *t = now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::WatermarkFrontier;
+    use crate::network::Coord;
+
+    #[test]
+    #[cfg(feature = "timestamp")]
+    fn idle_replica_is_ignored() {
+        let (a, b) = (Coord::new(0, 0, 0), Coord::new(0, 0, 1));
+        let mut frontier =
+            WatermarkFrontier::new([a, b]).with_idle_timeout(Some(Duration::from_millis(10)));
+
+        frontier.mark_active(a);
+        assert_eq!(frontier.update(a, 10), None);
+        std::thread::sleep(Duration::from_millis(20));
+
+        // b is idle: the frontier follows a
+        frontier.mark_active(a);
+        assert_eq!(frontier.update(a, 20), Some(20));
+
+        // b resumes behind the frontier, which does not move backwards
+        frontier.mark_active(b);
+        assert_eq!(frontier.update(b, 5), None);
+        frontier.mark_active(a);
+        assert_eq!(frontier.update(a, 30), None);
+        assert_eq!(frontier.update(b, 25), Some(25));
     }
 }
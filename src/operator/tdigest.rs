@@ -0,0 +1,230 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+/// Default compression of the digests, keeping at most a few hundred centroids.
+pub(crate) const DEFAULT_COMPRESSION: f64 = 100.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// A t-digest sketch, estimating the quantiles of the values inserted using a bounded amount of
+/// memory.
+///
+/// The values are summarized by a sorted list of centroids, which are smaller near the extremes
+/// of the distribution, so the estimate of the tail quantiles (like the 99th percentile) is more
+/// accurate than the one of the median. The number of centroids is bounded by the `compression`.
+/// The digests are mergeable: merging the digests of two parts of a stream gives a digest of the
+/// whole stream, so each replica can keep its own digest.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    /// Values and centroids not yet merged into `centroids`.
+    buffer: Vec<Centroid>,
+    count: u64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for TDigest {
+    fn default() -> Self {
+        Self::new(DEFAULT_COMPRESSION)
+    }
+}
+
+impl TDigest {
+    /// Create an empty digest. A higher `compression` gives more accurate estimates using more
+    /// memory.
+    pub fn new(compression: f64) -> Self {
+        assert!(compression >= 10.0, "t-digest compression must be >= 10");
+        Self {
+            compression,
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Number of values inserted in the digest.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Add a value to the digest. `NaN` values are ignored.
+    pub fn insert(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.buffer.push(Centroid {
+            mean: value,
+            weight: 1.0,
+        });
+        if self.buffer.len() >= 5 * self.compression as usize {
+            self.compress();
+        }
+    }
+
+    /// Merge the digest of another part of the stream.
+    pub fn merge(&mut self, other: &TDigest) {
+        if other.is_empty() {
+            return;
+        }
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.buffer.extend_from_slice(&other.centroids);
+        self.buffer.extend_from_slice(&other.buffer);
+        self.compress();
+    }
+
+    /// Estimate the `q`-quantile of the values inserted, with `q` between 0 and 1. Returns `None`
+    /// if the digest is empty.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        assert!((0.0..=1.0).contains(&q), "quantile must be between 0 and 1");
+        if !self.buffer.is_empty() {
+            let mut digest = self.clone();
+            digest.compress();
+            return digest.quantile(q);
+        }
+        let (first, last) = (self.centroids.first()?, self.centroids.last()?);
+        let index = q * self.count as f64;
+        // the extreme centroids are interpolated with the exact minimum and maximum
+        if index < first.weight / 2.0 {
+            let t = index / (first.weight / 2.0);
+            return Some(self.min + t * (first.mean - self.min));
+        }
+        let from_end = self.count as f64 - index;
+        if from_end < last.weight / 2.0 {
+            let t = from_end / (last.weight / 2.0);
+            return Some(self.max - t * (self.max - last.mean));
+        }
+        let mut weight_so_far = first.weight / 2.0;
+        for pair in self.centroids.windows(2) {
+            let (left, right) = (&pair[0], &pair[1]);
+            let step = (left.weight + right.weight) / 2.0;
+            if weight_so_far + step > index {
+                let t = (index - weight_so_far) / step;
+                return Some(left.mean + t * (right.mean - left.mean));
+            }
+            weight_so_far += step;
+        }
+        Some(last.mean)
+    }
+
+    /// Estimate the quantiles of the values inserted, see [`TDigest::quantile`].
+    pub fn quantiles(&self, qs: &[f64]) -> Option<Vec<f64>> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut digest = self.clone();
+        digest.compress();
+        qs.iter().map(|&q| digest.quantile(q)).collect()
+    }
+
+    /// The scale function bounding the size of the centroids at quantile `q`.
+    #[inline]
+    fn scale(&self, q: f64) -> f64 {
+        self.compression / (2.0 * PI) * (2.0 * q - 1.0).asin()
+    }
+
+    /// Merge the buffered values into the centroids.
+    fn compress(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let mut points = std::mem::take(&mut self.centroids);
+        points.append(&mut self.buffer);
+        points.sort_unstable_by(|a, b| a.mean.total_cmp(&b.mean));
+
+        let total = self.count as f64;
+        let mut merged = Vec::with_capacity(self.compression as usize);
+        let mut points = points.into_iter();
+        let mut current = points.next().unwrap();
+        let mut weight_so_far = 0.0;
+        let mut k_lower = self.scale(0.0);
+        for point in points {
+            let q = (weight_so_far + current.weight + point.weight) / total;
+            if self.scale(q) - k_lower <= 1.0 {
+                current.weight += point.weight;
+                current.mean += (point.mean - current.mean) * point.weight / current.weight;
+            } else {
+                weight_so_far += current.weight;
+                k_lower = self.scale(weight_so_far / total);
+                merged.push(current);
+                current = point;
+            }
+        }
+        merged.push(current);
+        self.centroids = merged;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TDigest;
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    fn assert_close(estimate: f64, exact: f64, tolerance: f64) {
+        assert!(
+            (estimate - exact).abs() <= tolerance,
+            "estimate {estimate}, exact {exact}"
+        );
+    }
+
+    #[test]
+    fn tdigest_quantiles() {
+        let mut digest = TDigest::default();
+        assert_eq!(digest.quantile(0.5), None);
+        // insert the values out of order
+        for i in 0..100_000u64 {
+            digest.insert(((i * 7919) % 100_000) as f64);
+        }
+        assert_eq!(digest.quantile(0.0), Some(0.0));
+        assert_eq!(digest.quantile(1.0), Some(99_999.0));
+        assert_close(digest.quantile(0.5).unwrap(), 50_000.0, 1000.0);
+        assert_close(digest.quantile(0.99).unwrap(), 99_000.0, 200.0);
+        assert_close(digest.quantile(0.999).unwrap(), 99_900.0, 50.0);
+        assert!(digest.centroids.len() < 500);
+    }
+
+    #[test]
+    fn tdigest_merge() {
+        let mut a = TDigest::default();
+        let mut b = TDigest::default();
+        (0..50_000).for_each(|i| a.insert(i as f64));
+        (50_000..100_000).for_each(|i| b.insert(i as f64));
+        a.merge(&b);
+        assert_eq!(a.count(), 100_000);
+        assert_close(a.quantile(0.25).unwrap(), 25_000.0, 1000.0);
+        assert_close(a.quantile(0.95).unwrap(), 95_000.0, 500.0);
+    }
+
+    #[test]
+    fn quantiles() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_par_iter(0..10_000u32)
+            .quantiles(&[0.5, 0.9])
+            .collect_vec();
+        env.execute_blocking();
+
+        let res = res.get().unwrap();
+        assert_eq!(res.len(), 1);
+        assert_close(res[0][0], 5_000.0, 200.0);
+        assert_close(res[0][1], 9_000.0, 100.0);
+    }
+}
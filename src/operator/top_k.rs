@@ -0,0 +1,127 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+/// The first `k` items seen according to a comparison function.
+///
+/// The items are kept in a binary heap with the last of them on top, so that a new item is
+/// compared only with the last one kept when the heap is full.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct TopK<T> {
+    k: usize,
+    heap: Vec<T>,
+}
+
+impl<T> TopK<T> {
+    pub(crate) fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, item: T, compare: impl Fn(&T, &T) -> Ordering) {
+        if self.heap.len() < self.k {
+            self.heap.push(item);
+            self.sift_up(self.heap.len() - 1, &compare);
+        } else if self.k > 0 && compare(&item, &self.heap[0]) == Ordering::Less {
+            self.heap[0] = item;
+            self.sift_down(0, &compare);
+        }
+    }
+
+    /// Add the items kept by another instance, with the same `k`.
+    pub(crate) fn merge(&mut self, other: TopK<T>, compare: impl Fn(&T, &T) -> Ordering) {
+        for item in other.heap {
+            self.push(item, &compare);
+        }
+    }
+
+    /// The items kept, in the order given by `compare`.
+    pub(crate) fn into_sorted_vec(self, compare: impl Fn(&T, &T) -> Ordering) -> Vec<T> {
+        let mut items = self.heap;
+        items.sort_by(compare);
+        items
+    }
+
+    fn sift_up(&mut self, mut pos: usize, compare: impl Fn(&T, &T) -> Ordering) {
+        while pos > 0 {
+            let parent = (pos - 1) / 2;
+            if compare(&self.heap[pos], &self.heap[parent]) != Ordering::Greater {
+                break;
+            }
+            self.heap.swap(pos, parent);
+            pos = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut pos: usize, compare: impl Fn(&T, &T) -> Ordering) {
+        loop {
+            let mut largest = pos;
+            for child in [2 * pos + 1, 2 * pos + 2] {
+                if child < self.heap.len()
+                    && compare(&self.heap[child], &self.heap[largest]) == Ordering::Greater
+                {
+                    largest = child;
+                }
+            }
+            if largest == pos {
+                break;
+            }
+            self.heap.swap(pos, largest);
+            pos = largest;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TopK;
+    use crate::config::RuntimeConfig;
+    use crate::environment::StreamContext;
+
+    #[test]
+    fn top_k_heap() {
+        let mut a = TopK::new(3);
+        let mut b = TopK::new(3);
+        for n in [5, 1, 9, 7, 3] {
+            a.push(n, i32::cmp);
+        }
+        for n in [8, 0, 2] {
+            b.push(n, i32::cmp);
+        }
+        a.merge(b, i32::cmp);
+        assert_eq!(a.into_sorted_vec(i32::cmp), vec![0, 1, 2]);
+
+        let mut empty = TopK::new(0);
+        empty.push(1, i32::cmp);
+        assert!(empty.into_sorted_vec(i32::cmp).is_empty());
+    }
+
+    #[test]
+    fn top_k_stream() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_par_iter(0..1000u32)
+            .top_k(5, |a, b| b.cmp(a))
+            .collect_vec();
+        env.execute_blocking();
+
+        assert_eq!(res.get().unwrap(), vec![999, 998, 997, 996, 995]);
+    }
+
+    #[test]
+    fn top_k_keyed() {
+        let env = StreamContext::new(RuntimeConfig::local(4).unwrap());
+        let res = env
+            .stream_par_iter(0..100u32)
+            .group_by(|n| n % 3)
+            .top_k(2, |a, b| a.cmp(b))
+            .collect_vec();
+        env.execute_blocking();
+
+        let mut res = res.get().unwrap();
+        res.sort_unstable();
+        assert_eq!(res, vec![(0, 0), (0, 3), (1, 1), (1, 4), (2, 2), (2, 5)]);
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt::Display;
+
+use crate::block::{BlockStructure, OperatorStructure};
+use crate::operator::{Operator, StreamElement, Timestamp};
+use crate::scheduler::ExecutionMetadata;
+
+/// Generate the watermarks of a stream from its items.
+///
+/// The generator is called for each item with its timestamp, and can return a new watermark.
+/// This allows extracting the watermarks from the data itself, for example from the marker
+/// records that an upstream system sends at the end of each batch (_punctuated_ watermarks).
+/// The generator is also called each time the stream is flushed, for the watermarks that depend
+/// only on the timestamps seen so far, like [`BoundedOutOfOrderness`].
+///
+/// It is implemented by the closures `FnMut(&T, Timestamp) -> Option<Timestamp>`, which generate
+/// punctuated watermarks.
+///
+/// See [`Stream::watermark_generator`](crate::Stream::watermark_generator).
+pub trait WatermarkGenerator<T>: Clone + Send + 'static {
+    /// Called for each item with its timestamp, returns the new watermark if any.
+    fn on_event(&mut self, item: &T, timestamp: Timestamp) -> Option<Timestamp>;
+
+    /// Called before the stream is flushed, returns the new watermark if any.
+    fn on_periodic_emit(&mut self) -> Option<Timestamp> {
+        None
+    }
+}
+
+impl<T, F> WatermarkGenerator<T> for F
+where
+    F: FnMut(&T, Timestamp) -> Option<Timestamp> + Clone + Send + 'static,
+{
+    #[inline]
+    fn on_event(&mut self, item: &T, timestamp: Timestamp) -> Option<Timestamp> {
+        self(item, timestamp)
+    }
+}
+
+/// Watermarks for streams whose items can arrive at most `max_out_of_orderness` behind the
+/// largest timestamp seen so far.
+///
+/// The watermark is emitted each time the stream is flushed. The same watermarks are generated by
+/// [`bounded_out_of_orderness`](super::WatermarkStrategy::bounded_out_of_orderness) strategies,
+/// which also emit them while the items are flowing and handle the idle replicas.
+#[derive(Clone, Debug)]
+pub struct BoundedOutOfOrderness {
+    max_out_of_orderness: Timestamp,
+    max_ts: Option<Timestamp>,
+}
+
+impl BoundedOutOfOrderness {
+    /// Watermarks that trail the largest timestamp seen by the replica by
+    /// `max_out_of_orderness`: the items later than that are late for the following operators.
+    ///
+    /// Panics if `max_out_of_orderness` is negative.
+    pub fn new(max_out_of_orderness: Timestamp) -> Self {
+        assert!(
+            max_out_of_orderness >= 0,
+            "the out-of-orderness bound cannot be negative"
+        );
+        Self {
+            max_out_of_orderness,
+            max_ts: None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn observe(&mut self, timestamp: Timestamp) {
+        self.max_ts = Some(self.max_ts.map_or(timestamp, |m| m.max(timestamp)));
+    }
+
+    /// The watermark implied by the timestamps observed so far, if any.
+    #[inline]
+    pub(crate) fn watermark(&self) -> Option<Timestamp> {
+        self.max_ts.map(|m| m - self.max_out_of_orderness)
+    }
+}
+
+impl<T> WatermarkGenerator<T> for BoundedOutOfOrderness {
+    #[inline]
+    fn on_event(&mut self, _item: &T, timestamp: Timestamp) -> Option<Timestamp> {
+        self.observe(timestamp);
+        None
+    }
+
+    #[inline]
+    fn on_periodic_emit(&mut self) -> Option<Timestamp> {
+        self.watermark()
+    }
+}
+
+#[derive(Clone)]
+pub struct ApplyWatermarkGenerator<F, W, Op>
+where
+    Op: Operator,
+    F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+    W: WatermarkGenerator<Op::Out>,
+{
+    prev: Op,
+    timestamp_fn: F,
+    init: W,
+    generator: W,
+    last_watermark: Option<Timestamp>,
+    /// A watermark or a flush to emit before pulling the next element, it never holds an item.
+    pending: Option<StreamElement<()>>,
+}
+
+impl<F, W, Op> Display for ApplyWatermarkGenerator<F, W, Op>
+where
+    Op: Operator,
+    F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+    W: WatermarkGenerator<Op::Out>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> WatermarkGenerator", self.prev)
+    }
+}
+
+impl<F, W, Op> ApplyWatermarkGenerator<F, W, Op>
+where
+    Op: Operator,
+    F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+    W: WatermarkGenerator<Op::Out>,
+{
+    pub(super) fn new(prev: Op, timestamp_fn: F, generator: W) -> Self {
+        Self {
+            prev,
+            timestamp_fn,
+            init: generator.clone(),
+            generator,
+            last_watermark: None,
+            pending: None,
+        }
+    }
+
+    /// Record the watermark if it advances the previous one.
+    fn advance(&mut self, watermark: Option<Timestamp>) -> Option<Timestamp> {
+        let watermark = watermark?;
+        if self.last_watermark.map(|w| watermark > w).unwrap_or(true) {
+            self.last_watermark = Some(watermark);
+            Some(watermark)
+        } else {
+            None
+        }
+    }
+}
+
+impl<F, W, Op> Operator for ApplyWatermarkGenerator<F, W, Op>
+where
+    Op: Operator,
+    F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+    W: WatermarkGenerator<Op::Out>,
+{
+    type Out = Op::Out;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+    }
+
+    #[inline]
+    fn next(&mut self) -> StreamElement<Self::Out> {
+        if let Some(el) = self.pending.take() {
+            return el.map(|_| unreachable!("an item is never pending"));
+        }
+
+        loop {
+            match self.prev.next() {
+                StreamElement::Item(item) | StreamElement::Timestamped(item, _) => {
+                    let ts = (self.timestamp_fn)(&item);
+                    let watermark = self.generator.on_event(&item, ts);
+                    if let Some(w) = self.advance(watermark) {
+                        self.pending = Some(StreamElement::Watermark(w));
+                    }
+                    return StreamElement::Timestamped(item, ts);
+                }
+                // the watermarks are generated by this operator
+                StreamElement::Watermark(_) => continue,
+                StreamElement::FlushBatch => {
+                    let watermark = self.generator.on_periodic_emit();
+                    if let Some(w) = self.advance(watermark) {
+                        self.pending = Some(StreamElement::FlushBatch);
+                        return StreamElement::Watermark(w);
+                    }
+                    return StreamElement::FlushBatch;
+                }
+                StreamElement::FlushAndRestart => {
+                    self.generator = self.init.clone();
+                    self.last_watermark = None;
+                    return StreamElement::FlushAndRestart;
+                }
+                StreamElement::Terminate => return StreamElement::Terminate,
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        self.prev
+            .structure()
+            .add_operator(OperatorStructure::new::<Self::Out, _>("WatermarkGenerator"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::operator::watermark_generator::{ApplyWatermarkGenerator, BoundedOutOfOrderness};
+    use crate::operator::{Operator, StreamElement};
+    use crate::test::FakeOperator;
+
+    #[test]
+    fn punctuated_watermarks() {
+        // the negative items mark the end of a batch
+        let fake_operator = FakeOperator::new([1i64, 3, -3, 2, 5, -5].into_iter());
+        let mut oper = ApplyWatermarkGenerator::new(
+            fake_operator,
+            |n: &i64| n.abs(),
+            |&n: &i64, ts| (n < 0).then_some(ts),
+        );
+
+        assert_eq!(oper.next(), StreamElement::Timestamped(1, 1));
+        assert_eq!(oper.next(), StreamElement::Timestamped(3, 3));
+        assert_eq!(oper.next(), StreamElement::Timestamped(-3, 3));
+        assert_eq!(oper.next(), StreamElement::Watermark(3));
+        assert_eq!(oper.next(), StreamElement::Timestamped(2, 2));
+        assert_eq!(oper.next(), StreamElement::Timestamped(5, 5));
+        assert_eq!(oper.next(), StreamElement::Timestamped(-5, 5));
+        assert_eq!(oper.next(), StreamElement::Watermark(5));
+        assert_eq!(oper.next(), StreamElement::Terminate);
+    }
+
+    #[test]
+    fn bounded_out_of_orderness_on_flush() {
+        let mut fake_operator = FakeOperator::new([10i64, 8, 15].into_iter());
+        fake_operator.push(StreamElement::FlushBatch);
+        let mut oper = ApplyWatermarkGenerator::new(
+            fake_operator,
+            |n: &i64| *n,
+            BoundedOutOfOrderness::new(5),
+        );
+
+        assert_eq!(oper.next(), StreamElement::Timestamped(10, 10));
+        assert_eq!(oper.next(), StreamElement::Timestamped(8, 8));
+        assert_eq!(oper.next(), StreamElement::Timestamped(15, 15));
+        assert_eq!(oper.next(), StreamElement::Watermark(10));
+        assert_eq!(oper.next(), StreamElement::FlushBatch);
+        assert_eq!(oper.next(), StreamElement::Terminate);
+    }
+}
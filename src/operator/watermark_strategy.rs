@@ -0,0 +1,274 @@
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use crate::block::{BlockStructure, OperatorStructure};
+use crate::operator::{BoundedOutOfOrderness, Operator, StreamElement, Timestamp};
+use crate::scheduler::ExecutionMetadata;
+
+/// Describe how timestamps are extracted from the items of a stream and how watermarks are
+/// generated from them.
+///
+/// See [`Stream::watermark_strategy`](crate::Stream::watermark_strategy).
+#[derive(Clone, Derivative)]
+#[derivative(Debug)]
+pub struct WatermarkStrategy<F> {
+    #[derivative(Debug = "ignore")]
+    timestamp_fn: F,
+    bound: BoundedOutOfOrderness,
+    idle_timeout: Option<Duration>,
+    emit_interval: Duration,
+}
+
+impl<F> WatermarkStrategy<F> {
+    /// Watermarks for streams whose items can arrive out of order, but at most
+    /// `max_out_of_orderness` behind the largest timestamp seen so far.
+    ///
+    /// The watermark follows the largest timestamp seen by the replica, minus
+    /// `max_out_of_orderness`. Items that are later than that are considered late by the
+    /// following operators, see [`BoundedOutOfOrderness`].
+    pub fn bounded_out_of_orderness(timestamp_fn: F, max_out_of_orderness: Timestamp) -> Self {
+        Self {
+            timestamp_fn,
+            bound: BoundedOutOfOrderness::new(max_out_of_orderness),
+            idle_timeout: None,
+            emit_interval: Duration::from_millis(200),
+        }
+    }
+
+    /// Watermarks for streams whose timestamps are non decreasing in each replica.
+    pub fn monotonous(timestamp_fn: F) -> Self {
+        Self::bounded_out_of_orderness(timestamp_fn, 0)
+    }
+
+    /// Consider the replica idle when no item is received for `timeout`.
+    ///
+    /// While a replica is idle its watermark keeps advancing from the last one by the time elapsed
+    /// since the last item, so that it does not hold back the event time of the operators
+    /// downstream. This mixes processing time into the event time on purpose: it assumes that the
+    /// timestamps are milliseconds and that the event time of the stream progresses like the wall
+    /// clock, which is the case for the streams that are timestamped when the events happen. Items
+    /// that arrive after the idle period with older timestamps may therefore be late.
+    ///
+    /// If this assumption does not hold, use
+    /// [`Stream::watermark_idleness`](crate::Stream::watermark_idleness) on the following block
+    /// instead: the idle replicas are ignored by the watermarks, without moving their event time.
+    ///
+    /// Idleness is detected when the previous operators have no data to emit (i.e. they flush the
+    /// current batch), a replica that never received any item does not emit watermarks.
+    pub fn with_idleness(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Minimum interval between two watermarks emitted while items are flowing, by default 200ms.
+    ///
+    /// A watermark is always emitted, if it advanced, before flushing the current batch.
+    pub fn emit_interval(mut self, interval: Duration) -> Self {
+        self.emit_interval = interval;
+        self
+    }
+}
+
+pub struct ApplyWatermarkStrategy<F, Op>
+where
+    Op: Operator,
+    F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+{
+    prev: Op,
+    strategy: WatermarkStrategy<F>,
+    bound: BoundedOutOfOrderness,
+    last_watermark: Option<Timestamp>,
+    last_emit: Option<Instant>,
+    last_item: Option<Instant>,
+    pending: Option<StreamElement<Op::Out>>,
+}
+
+impl<F, Op> Clone for ApplyWatermarkStrategy<F, Op>
+where
+    Op: Operator,
+    F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+{
+    fn clone(&self) -> Self {
+        Self::new(self.prev.clone(), self.strategy.clone())
+    }
+}
+
+impl<F, Op> Display for ApplyWatermarkStrategy<F, Op>
+where
+    Op: Operator,
+    F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> WatermarkStrategy", self.prev)
+    }
+}
+
+impl<F, Op> ApplyWatermarkStrategy<F, Op>
+where
+    Op: Operator,
+    F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+{
+    pub(super) fn new(prev: Op, strategy: WatermarkStrategy<F>) -> Self {
+        Self {
+            prev,
+            bound: strategy.bound.clone(),
+            strategy,
+            last_watermark: None,
+            last_emit: None,
+            last_item: None,
+            pending: None,
+        }
+    }
+
+    /// Watermark implied by the timestamps seen so far.
+    fn watermark(&self) -> Option<Timestamp> {
+        let watermark = self.bound.watermark()?;
+        match (self.strategy.idle_timeout, self.last_item) {
+            (Some(timeout), Some(last_item)) if last_item.elapsed() >= timeout => {
+                // idle: assume the event time advances with the wall clock, see `with_idleness`
+                let elapsed = last_item.elapsed().as_millis() as Timestamp;
+                Some(watermark.saturating_add(elapsed))
+            }
+            _ => Some(watermark),
+        }
+    }
+
+    /// Record the watermark if it advances the previous one.
+    fn advance(&mut self, watermark: Option<Timestamp>) -> Option<Timestamp> {
+        let watermark = watermark?;
+        if self.last_watermark.map(|w| watermark > w).unwrap_or(true) {
+            self.last_watermark = Some(watermark);
+            self.last_emit = Some(Instant::now());
+            Some(watermark)
+        } else {
+            None
+        }
+    }
+}
+
+impl<F, Op> Operator for ApplyWatermarkStrategy<F, Op>
+where
+    Op: Operator,
+    F: FnMut(&Op::Out) -> Timestamp + Clone + Send + 'static,
+{
+    type Out = Op::Out;
+
+    fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.prev.setup(metadata);
+    }
+
+    #[inline]
+    fn next(&mut self) -> StreamElement<Self::Out> {
+        if let Some(el) = self.pending.take() {
+            return el;
+        }
+
+        loop {
+            match self.prev.next() {
+                StreamElement::Item(item) | StreamElement::Timestamped(item, _) => {
+                    let ts = (self.strategy.timestamp_fn)(&item);
+                    self.bound.observe(ts);
+                    let now = Instant::now();
+                    self.last_item = Some(now);
+                    let due = self
+                        .last_emit
+                        .map(|t| now.duration_since(t) >= self.strategy.emit_interval)
+                        .unwrap_or(true);
+                    if due {
+                        let watermark = self.watermark();
+                        if let Some(w) = self.advance(watermark) {
+                            self.pending = Some(StreamElement::Watermark(w));
+                        }
+                    }
+                    return StreamElement::Timestamped(item, ts);
+                }
+                // the watermarks are generated by this operator
+                StreamElement::Watermark(_) => continue,
+                StreamElement::FlushBatch => {
+                    let watermark = self.watermark();
+                    if let Some(w) = self.advance(watermark) {
+                        self.pending = Some(StreamElement::FlushBatch);
+                        return StreamElement::Watermark(w);
+                    }
+                    return StreamElement::FlushBatch;
+                }
+                StreamElement::FlushAndRestart => {
+                    self.bound = self.strategy.bound.clone();
+                    self.last_watermark = None;
+                    self.last_emit = None;
+                    self.last_item = None;
+                    return StreamElement::FlushAndRestart;
+                }
+                StreamElement::Terminate => return StreamElement::Terminate,
+            }
+        }
+    }
+
+    fn structure(&self) -> BlockStructure {
+        self.prev
+            .structure()
+            .add_operator(OperatorStructure::new::<Self::Out, _>("WatermarkStrategy"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::operator::watermark_strategy::{ApplyWatermarkStrategy, WatermarkStrategy};
+    use crate::operator::{Operator, StreamElement};
+    use crate::test::FakeOperator;
+
+    #[test]
+    fn bounded_out_of_orderness() {
+        let fake_operator = FakeOperator::new([10i64, 8, 15, 12, 20].into_iter());
+        let strategy = WatermarkStrategy::bounded_out_of_orderness(|n: &i64| *n, 5)
+            .emit_interval(Duration::ZERO);
+        let mut oper = ApplyWatermarkStrategy::new(fake_operator, strategy);
+
+        assert_eq!(oper.next(), StreamElement::Timestamped(10, 10));
+        assert_eq!(oper.next(), StreamElement::Watermark(5));
+        assert_eq!(oper.next(), StreamElement::Timestamped(8, 8));
+        assert_eq!(oper.next(), StreamElement::Timestamped(15, 15));
+        assert_eq!(oper.next(), StreamElement::Watermark(10));
+        assert_eq!(oper.next(), StreamElement::Timestamped(12, 12));
+        assert_eq!(oper.next(), StreamElement::Timestamped(20, 20));
+        assert_eq!(oper.next(), StreamElement::Watermark(15));
+        assert_eq!(oper.next(), StreamElement::Terminate);
+    }
+
+    #[test]
+    fn watermark_before_flush() {
+        let mut fake_operator = FakeOperator::new([1i64, 2, 3].into_iter());
+        fake_operator.push(StreamElement::FlushBatch);
+        let strategy = WatermarkStrategy::monotonous(|n: &i64| *n);
+        let mut oper = ApplyWatermarkStrategy::new(fake_operator, strategy);
+
+        assert_eq!(oper.next(), StreamElement::Timestamped(1, 1));
+        assert_eq!(oper.next(), StreamElement::Watermark(1));
+        assert_eq!(oper.next(), StreamElement::Timestamped(2, 2));
+        assert_eq!(oper.next(), StreamElement::Timestamped(3, 3));
+        assert_eq!(oper.next(), StreamElement::Watermark(3));
+        assert_eq!(oper.next(), StreamElement::FlushBatch);
+        assert_eq!(oper.next(), StreamElement::Terminate);
+    }
+
+    #[test]
+    fn idle_replica_advances() {
+        let mut fake_operator = FakeOperator::new([100i64].into_iter());
+        fake_operator.push(StreamElement::FlushBatch);
+        let strategy = WatermarkStrategy::monotonous(|n: &i64| *n)
+            .with_idleness(Duration::from_millis(10))
+            .emit_interval(Duration::ZERO);
+        let mut oper = ApplyWatermarkStrategy::new(fake_operator, strategy);
+
+        assert_eq!(oper.next(), StreamElement::Timestamped(100, 100));
+        assert_eq!(oper.next(), StreamElement::Watermark(100));
+        std::thread::sleep(Duration::from_millis(20));
+        match oper.next() {
+            StreamElement::Watermark(w) => assert!(w >= 120),
+            el => panic!("expected a watermark, got {el:?}"),
+        }
+        assert_eq!(oper.next(), StreamElement::FlushBatch);
+    }
+}
@@ -0,0 +1,107 @@
+use super::super::*;
+use crate::operator::merge::MergeElement;
+use crate::operator::{Data, DataKey, Operator};
+use crate::stream::KeyedStream;
+
+#[derive(Clone)]
+struct CoGroup<L, R> {
+    left: Vec<L>,
+    right: Vec<R>,
+}
+
+impl<L: Data, R: Data> WindowAccumulator for CoGroup<L, R> {
+    type In = MergeElement<L, R>;
+    type Out = (Vec<L>, Vec<R>);
+
+    #[inline]
+    fn process(&mut self, el: Self::In) {
+        match el {
+            MergeElement::Left(l) => self.left.push(l),
+            MergeElement::Right(r) => self.right.push(r),
+        }
+    }
+
+    #[inline]
+    fn output(self) -> Self::Out {
+        (self.left, self.right)
+    }
+
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.left.capacity() * std::mem::size_of::<L>()
+            + self.right.capacity() * std::mem::size_of::<R>()
+    }
+}
+
+impl<Key, Out, OperatorChain> KeyedStream<OperatorChain>
+where
+    OperatorChain: Operator<Out = (Key, Out)> + 'static,
+    Key: ExchangeData + DataKey,
+    Out: ExchangeData,
+{
+    /// Group the elements of two streams by key and window, and apply `f` to each pair of groups.
+    ///
+    /// For each key and window `f` receives the elements of the left and of the right stream in
+    /// the window, and its result is emitted with the key. Unlike
+    /// [`window_join`](KeyedStream::window_join), `f` is called also when one of the two groups
+    /// is empty, so it can compute set differences, ratios or reconciliations between the two
+    /// streams.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::window::EventTimeWindow;
+    /// # let mut env = StreamContext::new_local();
+    /// let sent = env
+    ///     .stream_iter(vec![('a', 1), ('a', 2), ('b', 3), ('a', 6)].into_iter())
+    ///     .add_timestamps(|&(_, ts)| ts, |_, &ts| Some(ts))
+    ///     .group_by(|&(account, _)| account);
+    /// let received = env
+    ///     .stream_iter(vec![('a', 1), ('b', 3)].into_iter())
+    ///     .add_timestamps(|&(_, ts)| ts, |_, &ts| Some(ts))
+    ///     .group_by(|&(account, _)| account);
+    /// // the messages sent and not received in each window
+    /// let res = sent
+    ///     .window_co_group(EventTimeWindow::tumbling(5), received, |sent, received| {
+    ///         sent.iter()
+    ///             .filter(|m| !received.contains(m))
+    ///             .map(|&(_, ts)| ts)
+    ///             .collect::<Vec<_>>()
+    ///     })
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![('a', vec![2]), ('a', vec![6]), ('b', vec![])]);
+    /// ```
+    pub fn window_co_group<Out2, NewOut, OperatorChain2, WindowDescr, F>(
+        self,
+        descr: WindowDescr,
+        right: KeyedStream<OperatorChain2>,
+        f: F,
+    ) -> KeyedStream<impl Operator<Out = (Key, NewOut)>>
+    where
+        OperatorChain2: Operator<Out = (Key, Out2)> + 'static,
+        Out2: ExchangeData,
+        NewOut: Data,
+        F: Fn(&[Out], &[Out2]) -> NewOut + Send + Clone + 'static,
+        WindowDescr: WindowDescription<MergeElement<Out, Out2>> + 'static,
+    {
+        let acc = CoGroup::<Out, Out2> {
+            left: Default::default(),
+            right: Default::default(),
+        };
+
+        self.merge_distinct(right)
+            .window(descr)
+            .add_window_operator("WindowCoGroup", acc)
+            .map(move |(_, (left, right))| f(&left, &right))
+    }
+}
@@ -31,6 +31,11 @@ where
     fn output(self) -> Self::Out {
         (self.f)(self.vec)
     }
+
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.vec.capacity() * std::mem::size_of::<I>()
+    }
 }
 
 impl<Key, Out, WindowDescr, OperatorChain> WindowedStream<OperatorChain, Out, WindowDescr>
@@ -0,0 +1,179 @@
+use std::marker::PhantomData;
+
+use crate::operator::{Data, DataKey, Operator};
+use crate::stream::{KeyedStream, WindowedStream};
+
+use super::super::*;
+
+const NO_WINDOW: &str = "the window manager did not set the context of the window";
+
+#[derive(Clone)]
+struct FoldWithContext<I, S, F>
+where
+    F: FnMut(&WindowContext, &mut S, I),
+{
+    state: S,
+    f: F,
+    window: Option<WindowContext>,
+    _in: PhantomData<I>,
+}
+
+impl<I, S, F> WindowAccumulator for FoldWithContext<I, S, F>
+where
+    I: Clone + Send + 'static,
+    S: Clone + Send + 'static,
+    F: FnMut(&WindowContext, &mut S, I) + Clone + Send + 'static,
+{
+    type In = I;
+
+    type Out = S;
+
+    #[inline]
+    fn process(&mut self, el: Self::In) {
+        let window = self.window.as_ref().expect(NO_WINDOW);
+        (self.f)(window, &mut self.state, el);
+    }
+
+    #[inline]
+    fn output(self) -> Self::Out {
+        self.state
+    }
+
+    #[inline]
+    fn set_window(&mut self, window: WindowContext) {
+        self.window = Some(window);
+    }
+}
+
+#[derive(Clone)]
+struct CollectWithContext<I, O, F>
+where
+    F: Fn(WindowContext, Vec<I>) -> O,
+{
+    vec: Vec<I>,
+    f: F,
+    window: Option<WindowContext>,
+    _o: PhantomData<O>,
+}
+
+impl<I, O, F> WindowAccumulator for CollectWithContext<I, O, F>
+where
+    F: Fn(WindowContext, Vec<I>) -> O + Send + Clone + 'static,
+    I: Clone + Send + 'static,
+    O: Clone + Send + 'static,
+{
+    type In = I;
+
+    type Out = O;
+
+    #[inline]
+    fn process(&mut self, el: Self::In) {
+        self.vec.push(el);
+    }
+
+    #[inline]
+    fn output(self) -> Self::Out {
+        (self.f)(self.window.expect(NO_WINDOW), self.vec)
+    }
+
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.vec.capacity() * std::mem::size_of::<I>()
+    }
+
+    #[inline]
+    fn set_window(&mut self, window: WindowContext) {
+        self.window = Some(window);
+    }
+}
+
+impl<Key, Out, WindowDescr, OperatorChain> WindowedStream<OperatorChain, Out, WindowDescr>
+where
+    WindowDescr: ContextWindowDescription<Out>,
+    OperatorChain: Operator<Out = (Key, Out)> + 'static,
+    Key: DataKey,
+    Out: Data,
+{
+    /// Like [`fold`](WindowedStream::fold), but the closure also receives the
+    /// [`WindowContext`] with the boundaries of the window the element belongs to.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::window::EventTimeWindow;
+    /// # let mut env = StreamContext::new_local();
+    /// let res = env
+    ///     .stream_iter(vec![0, 3, 6, 9].into_iter())
+    ///     .add_timestamps(|&n| n, |_, &ts| Some(ts))
+    ///     .group_by(|_| ())
+    ///     .window(EventTimeWindow::tumbling(5))
+    ///     // the distance of each element from the start of its window
+    ///     .fold_with_context(Vec::new(), |w, v, n| v.push(n - w.start))
+    ///     .drop_key()
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![vec![0, 3], vec![1, 4]]);
+    /// ```
+    pub fn fold_with_context<NewOut: Data, F>(
+        self,
+        init: NewOut,
+        fold: F,
+    ) -> KeyedStream<impl Operator<Out = (Key, NewOut)>>
+    where
+        F: FnMut(&WindowContext, &mut NewOut, Out) + Clone + Send + 'static,
+    {
+        let acc = FoldWithContext {
+            state: init,
+            f: fold,
+            window: None,
+            _in: PhantomData,
+        };
+        self.add_window_operator("WindowFoldWithContext", acc)
+    }
+
+    /// Like [`map`](WindowedStream::map), but the closure also receives the [`WindowContext`]
+    /// with the boundaries of the window, so the result can carry them.
+    ///
+    /// Prefer [`fold_with_context`](WindowedStream::fold_with_context) if possible as it
+    /// doesn't save all elements.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::window::EventTimeWindow;
+    /// # let mut env = StreamContext::new_local();
+    /// let res = env
+    ///     .stream_iter(vec![0, 3, 6, 9, 12].into_iter())
+    ///     .add_timestamps(|&n| n, |_, &ts| Some(ts))
+    ///     .group_by(|_| ())
+    ///     .window(EventTimeWindow::tumbling(5))
+    ///     .map_with_context(|w, v| (w.start, w.end, v.len()))
+    ///     .drop_key()
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![(0, 5, 2), (5, 10, 2), (10, 15, 1)]);
+    /// ```
+    pub fn map_with_context<NewOut: Data, F>(
+        self,
+        f: F,
+    ) -> KeyedStream<impl Operator<Out = (Key, NewOut)>>
+    where
+        F: Fn(WindowContext, Vec<Out>) -> NewOut + Send + Clone + 'static,
+    {
+        let acc = CollectWithContext {
+            vec: Default::default(),
+            f,
+            window: None,
+            _o: PhantomData,
+        };
+        self.add_window_operator("WindowMapWithContext", acc)
+    }
+}
@@ -104,6 +104,9 @@ where
     /// reference to the accumulator and the element of the window. The closure should modify
     /// the accumulator, without returning anything.
     ///
+    /// The closure is called as soon as the element arrives, so each open window keeps only its
+    /// accumulator instead of buffering its elements until it is closed.
+    ///
     /// ## Example
     /// ```
     /// # use renoir::{StreamContext, RuntimeConfig};
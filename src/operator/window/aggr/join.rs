@@ -1,7 +1,7 @@
 use super::super::*;
 use crate::operator::merge::MergeElement;
 use crate::operator::{Data, DataKey, Operator};
-use crate::stream::KeyedStream;
+use crate::stream::{KeyedStream, Stream};
 
 #[derive(Clone)]
 struct Join<L, R> {
@@ -28,6 +28,13 @@ impl<L: Data, R: Data> WindowAccumulator for Join<L, R> {
             std::mem::take(&mut self.right),
         )
     }
+
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.left.capacity() * std::mem::size_of::<L>()
+            + self.right.capacity() * std::mem::size_of::<R>()
+    }
 }
 
 #[derive(Clone)]
@@ -98,6 +105,75 @@ where
     }
 }
 
+impl<Out, OperatorChain> Stream<OperatorChain>
+where
+    OperatorChain: Operator<Out = Out> + 'static,
+    Out: ExchangeData,
+{
+    /// Join the elements of two streams that fall in the same window and have the same key.
+    ///
+    /// The key of the elements of the left stream is computed with `keyer1`, the one of the right
+    /// stream with `keyer2`. Both streams are partitioned by key and windowed with `descr`, and for
+    /// each key and window all the pairs of a left and a right element of the window are emitted.
+    ///
+    /// This is a shortcut for grouping both streams and calling [`KeyedStream::window_join`].
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::window::EventTimeWindow;
+    /// # let mut env = StreamContext::new_local();
+    /// let orders = env
+    ///     .stream_iter(vec![("ann", 0), ("bob", 5), ("ann", 6)].into_iter())
+    ///     .map(|(user, ts)| (user.to_string(), ts))
+    ///     .add_timestamps(|&(_, ts)| ts, |_, &ts| Some(ts));
+    /// let payments = env
+    ///     .stream_iter(vec![(0, "ann"), (5, "bob"), (7, "bob")].into_iter())
+    ///     .map(|(ts, user)| (ts, user.to_string()))
+    ///     .add_timestamps(|&(ts, _)| ts, |_, &ts| Some(ts));
+    /// let res = orders
+    ///     .window_join(
+    ///         payments,
+    ///         EventTimeWindow::tumbling(5),
+    ///         |(user, _)| user.clone(),
+    ///         |(_, user)| user.clone(),
+    ///     )
+    ///     .map(|(_, ((_, order), (payment, _)))| (order, payment))
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// // the windows of each key start from its first element: the order of ann at 6 is in the
+    /// // second window of ann, without payments
+    /// let (ann, bob) = ("ann".to_string(), "bob".to_string());
+    /// assert_eq!(res, vec![(ann, (0, 0)), (bob.clone(), (5, 5)), (bob, (5, 7))]);
+    /// ```
+    pub fn window_join<Out2, OperatorChain2, Key, Keyer1, Keyer2, WindowDescr>(
+        self,
+        right: Stream<OperatorChain2>,
+        descr: WindowDescr,
+        keyer1: Keyer1,
+        keyer2: Keyer2,
+    ) -> KeyedStream<impl Operator<Out = (Key, (Out, Out2))>>
+    where
+        OperatorChain2: Operator<Out = Out2> + 'static,
+        Out2: ExchangeData,
+        Key: ExchangeData + DataKey,
+        Keyer1: Fn(&Out) -> Key + Send + Clone + 'static,
+        Keyer2: Fn(&Out2) -> Key + Send + Clone + 'static,
+        WindowDescr: WindowDescription<MergeElement<Out, Out2>> + 'static,
+    {
+        self.group_by(keyer1)
+            .window_join(descr, right.group_by(keyer2))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,4 +200,19 @@ mod tests {
 
         assert_eq!(expected, t);
     }
+
+    #[test]
+    fn join_memory() {
+        let mut manager = CountWindow::tumbling(1000).build(Join::<u64, u64> {
+            left: Vec::new(),
+            right: Vec::new(),
+        });
+        let empty = manager.memory();
+        for i in 0..100 {
+            manager.process(StreamElement::Item(MergeElement::Left(i)));
+            manager.process(StreamElement::Item(MergeElement::Right(i)));
+        }
+        // the elements buffered in the open window are counted
+        assert!(manager.memory() >= empty + 200 * std::mem::size_of::<u64>());
+    }
 }
@@ -2,12 +2,16 @@ mod fold;
 // mod columnar;
 pub(super) use fold::{Fold, FoldFirst};
 
+mod co_group;
 mod collect_vec;
+#[cfg(feature = "timestamp")]
+mod context;
 mod count;
 mod join;
 mod max;
 mod min;
 mod nth;
+mod quantiles;
 mod sum;
-#[cfg(feature = "parquet")]
+#[cfg(feature = "arrow")]
 mod to_arrow;
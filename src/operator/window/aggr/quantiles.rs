@@ -0,0 +1,72 @@
+use super::super::*;
+use crate::operator::{Data, DataKey, Operator, TDigest};
+use crate::stream::{KeyedStream, WindowedStream};
+
+#[derive(Clone)]
+pub(crate) struct Quantiles<T> {
+    digest: TDigest,
+    qs: Vec<f64>,
+    _t: PhantomData<T>,
+}
+
+impl<T: Data + Into<f64>> WindowAccumulator for Quantiles<T> {
+    type In = T;
+    type Out = Vec<f64>;
+
+    #[inline]
+    fn process(&mut self, el: Self::In) {
+        self.digest.insert(el.into());
+    }
+
+    #[inline]
+    fn output(self) -> Self::Out {
+        // windows are never empty, unless all the values were NaN
+        self.digest
+            .quantiles(&self.qs)
+            .unwrap_or_else(|| vec![f64::NAN; self.qs.len()])
+    }
+}
+
+impl<Key, Out, WindowDescr, OperatorChain> WindowedStream<OperatorChain, Out, WindowDescr>
+where
+    WindowDescr: WindowDescription<Out>,
+    OperatorChain: Operator<Out = (Key, Out)> + 'static,
+    Key: DataKey,
+    Out: Data + Into<f64>,
+{
+    /// Estimate the quantiles `qs` (each between 0 and 1) of each window, in the same order as
+    /// `qs`.
+    ///
+    /// Each open window keeps a [`TDigest`] sketch instead of its elements.
+    ///
+    /// ## Example
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::window::CountWindow;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(vec![1, 5, 3, 10, 30, 20].into_iter());
+    /// let res = s
+    ///     .group_by(|_| ())
+    ///     .window(CountWindow::tumbling(3))
+    ///     .quantiles(&[0.0, 1.0])
+    ///     .drop_key()
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![vec![1.0, 5.0], vec![10.0, 30.0]]);
+    /// ```
+    pub fn quantiles(self, qs: &[f64]) -> KeyedStream<impl Operator<Out = (Key, Vec<f64>)>> {
+        assert!(
+            qs.iter().all(|q| (0.0..=1.0).contains(q)),
+            "quantiles must be between 0 and 1"
+        );
+        let acc = Quantiles {
+            digest: TDigest::default(),
+            qs: qs.to_vec(),
+            _t: PhantomData,
+        };
+        self.add_window_operator("WindowQuantiles", acc)
+    }
+}
@@ -11,6 +11,30 @@ where
     Key: DataKey,
     Out: Data,
 {
+    /// Sum the elements of each window.
+    ///
+    /// Each open window keeps only its running sum, which is updated as the elements arrive,
+    /// so the memory used does not depend on the number of elements in the window.
+    ///
+    /// ## Example
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::window::CountWindow;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(0..5);
+    /// let res = s
+    ///     .group_by(|&n| n % 2)
+    ///     .window(CountWindow::tumbling(2))
+    ///     .sum::<i32>()
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![(0, 0 + 2), (1, 1 + 3)]);
+    /// ```
     pub fn sum<NewOut: Data + Default + AddAssign<Out>>(
         self,
     ) -> KeyedStream<impl Operator<Out = (Key, NewOut)>> {
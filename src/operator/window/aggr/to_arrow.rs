@@ -47,6 +47,11 @@ impl<T: Clone + Serialize + Send + 'static> WindowAccumulator for ToArrow<T> {
                 .expect("arrow error")
         }
     }
+
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.vec.capacity() * std::mem::size_of::<T>()
+    }
 }
 
 impl<Key, Out, WindowDescr, OperatorChain> WindowedStream<OperatorChain, Out, WindowDescr>
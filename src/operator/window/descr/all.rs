@@ -20,6 +20,11 @@ where
     type Out = A::Out;
     type Output = Option<WindowResult<A::Out>>;
 
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.accumulator.as_ref().map_or(0, |acc| acc.memory())
+    }
+
     #[inline]
     fn process(&mut self, el: StreamElement<A::In>) -> Self::Output {
         let ts = el.timestamp().cloned();
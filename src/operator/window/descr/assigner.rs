@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use super::super::*;
+use crate::operator::{Data, StreamElement, Timestamp};
+
+/// The logic that assigns each element to one or more event time windows.
+///
+/// Given an element and its timestamp, the assigner returns the ranges `start..end` of the
+/// windows the element belongs to. The windows with the same range are the same window, so
+/// the assigner can build windows of any shape: aligned to the calendar, with a size that
+/// depends on the element (and so on its key), or following irregular boundaries defined by
+/// the business logic. Use it with [`AssignedWindow`].
+///
+/// It is implemented by the closures `Fn(&T, Timestamp) -> impl IntoIterator<Item = Range>`.
+pub trait WindowAssigner<T>: Clone + Send + 'static {
+    /// The ranges of the windows of an element.
+    type Windows: IntoIterator<Item = Range<Timestamp>>;
+
+    /// The windows the element `item`, with timestamp `timestamp`, belongs to.
+    fn assign(&self, item: &T, timestamp: Timestamp) -> Self::Windows;
+}
+
+impl<T, F, I> WindowAssigner<T> for F
+where
+    F: Fn(&T, Timestamp) -> I + Clone + Send + 'static,
+    I: IntoIterator<Item = Range<Timestamp>>,
+{
+    type Windows = I;
+
+    #[inline]
+    fn assign(&self, item: &T, timestamp: Timestamp) -> I {
+        self(item, timestamp)
+    }
+}
+
+#[derive(Clone)]
+pub struct AssignedWindowManager<A, W>
+where
+    A: WindowAccumulator,
+{
+    init: A,
+    assigner: W,
+    last_watermark: Option<Timestamp>,
+    /// The open windows, sorted by end and start.
+    ws: BTreeMap<(Timestamp, Timestamp), A>,
+}
+
+impl<A: WindowAccumulator, W> WindowManager for AssignedWindowManager<A, W>
+where
+    A::In: Data,
+    A::Out: Data,
+    W: WindowAssigner<A::In>,
+{
+    type In = A::In;
+    type Out = A::Out;
+    type Output = Vec<WindowResult<A::Out>>;
+
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.ws.values().map(|acc| acc.memory()).sum::<usize>()
+    }
+
+    #[inline]
+    fn process(&mut self, el: StreamElement<A::In>) -> Self::Output {
+        match el {
+            StreamElement::Timestamped(item, ts) => {
+                for range in self.assigner.assign(&item, ts) {
+                    // Same condition of the event time windows for a closed window
+                    if self.last_watermark.map(|w| range.end < w).unwrap_or(false) {
+                        log::debug!("Discarding late element with timestamp {ts}");
+                        continue;
+                    }
+                    self.ws
+                        .entry((range.end, range.start))
+                        .or_insert_with(|| {
+                            let mut acc = self.init.clone();
+                            acc.set_window(WindowContext {
+                                start: range.start,
+                                end: range.end,
+                            });
+                            acc
+                        })
+                        .process(item.clone());
+                }
+                Vec::new()
+            }
+            StreamElement::Watermark(ts) => {
+                self.last_watermark = Some(ts);
+                let mut ret = Vec::new();
+                while let Some(entry) = self.ws.first_entry() {
+                    let end = entry.key().0;
+                    if end >= ts {
+                        break;
+                    }
+                    ret.push(WindowResult::Timestamped(entry.remove().output(), end));
+                }
+                ret
+            }
+            StreamElement::FlushAndRestart | StreamElement::Terminate => {
+                std::mem::take(&mut self.ws)
+                    .into_iter()
+                    .map(|((end, _), acc)| WindowResult::Timestamped(acc.output(), end))
+                    .collect()
+            }
+            StreamElement::Item(_) => {
+                panic!("Assigned windows can only handle timestamped items!")
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn recycle(&self) -> bool {
+        self.ws.is_empty()
+    }
+}
+
+/// Event time windows built by a custom [`WindowAssigner`].
+///
+/// Each element is added to the windows returned by the assigner, and a window emits its
+/// result, with its end as timestamp, when the watermark passes its end. The elements of
+/// windows that have already been closed are discarded.
+///
+/// ## Example
+///
+/// ```
+/// # use renoir::{StreamContext, RuntimeConfig};
+/// # use renoir::operator::source::IteratorSource;
+/// # use renoir::operator::window::AssignedWindow;
+/// # let mut env = StreamContext::new_local();
+/// // the premium accounts are billed every 10, the others every 5
+/// let events = vec![("basic", 1), ("premium", 2), ("basic", 6), ("premium", 8), ("premium", 12)];
+/// let res = env
+///     .stream_iter(events.into_iter().map(|(account, ts)| (account.to_string(), ts)))
+///     .add_timestamps(|&(_, ts)| ts, |_, &ts| Some(ts))
+///     .group_by(|(account, _)| account.clone())
+///     .window(AssignedWindow::new(|(account, _): &(String, i64), ts: i64| {
+///         let size = if account == "premium" { 10 } else { 5 };
+///         let start = ts - ts.rem_euclid(size);
+///         std::iter::once(start..start + size)
+///     }))
+///     .count()
+///     .collect_vec();
+///
+/// env.execute_blocking();
+///
+/// let mut res = res.get().unwrap();
+/// res.sort_unstable();
+/// let (basic, premium) = ("basic".to_string(), "premium".to_string());
+/// assert_eq!(res, vec![(basic.clone(), 1), (basic, 1), (premium.clone(), 1), (premium, 2)]);
+/// ```
+#[derive(Clone)]
+pub struct AssignedWindow<W> {
+    assigner: W,
+}
+
+impl<W> AssignedWindow<W> {
+    #[inline]
+    pub fn new(assigner: W) -> Self {
+        Self { assigner }
+    }
+}
+
+impl<T: Data, W: WindowAssigner<T>> WindowDescription<T> for AssignedWindow<W> {
+    type Manager<A: WindowAccumulator<In = T>> = AssignedWindowManager<A, W>;
+
+    #[inline]
+    fn build<A: WindowAccumulator<In = T>>(&self, accumulator: A) -> Self::Manager<A> {
+        AssignedWindowManager {
+            init: accumulator,
+            assigner: self.assigner.clone(),
+            last_watermark: None,
+            ws: Default::default(),
+        }
+    }
+}
+
+impl<T: Data, W: WindowAssigner<T>> ContextWindowDescription<T> for AssignedWindow<W> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator::window::aggr::Fold;
+
+    #[test]
+    fn assigned_window_irregular() {
+        // windows with irregular boundaries, an element may belong to more windows
+        let boundaries = [(0, 3), (2, 10), (10, 11)];
+        let window = AssignedWindow::new(move |_: &i64, ts: Timestamp| {
+            boundaries
+                .into_iter()
+                .filter(move |&(start, end)| start <= ts && ts < end)
+                .map(|(start, end)| start..end)
+        });
+
+        let fold = Fold::new(Vec::new(), |v, el| v.push(el));
+        let mut manager = window.build(fold);
+
+        let mut received = Vec::new();
+        for i in 0..5 {
+            received.extend(manager.process(StreamElement::Timestamped(i, i)));
+        }
+        received.extend(manager.process(StreamElement::Watermark(4)));
+        assert_eq!(received, vec![WindowResult::Timestamped(vec![0, 1, 2], 3)]);
+
+        // the first window is closed
+        received.extend(manager.process(StreamElement::Timestamped(1, 1)));
+        received.extend(manager.process(StreamElement::Timestamped(10, 10)));
+        received.extend(manager.process(StreamElement::FlushAndRestart));
+        assert_eq!(
+            received,
+            vec![
+                WindowResult::Timestamped(vec![0, 1, 2], 3),
+                WindowResult::Timestamped(vec![2, 3, 4], 10),
+                WindowResult::Timestamped(vec![10], 11),
+            ]
+        );
+        assert!(manager.recycle());
+    }
+}
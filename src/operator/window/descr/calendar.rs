@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{
+    DateTime, Datelike, Days, Months, NaiveDate, NaiveTime, TimeDelta, TimeZone, Weekday,
+};
+
+use super::super::*;
+use crate::operator::{Data, StreamElement, Timestamp};
+
+/// The calendar period covered by each [`CalendarWindow`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CalendarUnit {
+    /// From midnight to the next midnight.
+    Day,
+    /// From midnight of the given day of the week, for 7 days.
+    Week(Weekday),
+    /// From midnight of the first day of the month to the first day of the next month.
+    Month,
+}
+
+/// Split the time in the periods of a calendar, in a time zone.
+#[derive(Clone)]
+struct Calendar<Tz> {
+    unit: CalendarUnit,
+    tz: Tz,
+}
+
+impl<Tz: TimeZone> Calendar<Tz> {
+    /// The period that contains the instant `ts`, in milliseconds since the Unix epoch.
+    fn window(&self, ts: Timestamp) -> Range<Timestamp> {
+        let date = DateTime::from_timestamp_millis(ts)
+            .expect("timestamp out of range")
+            .with_timezone(&self.tz)
+            .date_naive();
+        let (start, end) = match self.unit {
+            CalendarUnit::Day => (date, date.checked_add_days(Days::new(1))),
+            CalendarUnit::Week(first) => {
+                let start = date.week(first).first_day();
+                (start, start.checked_add_days(Days::new(7)))
+            }
+            CalendarUnit::Month => {
+                let start = date.with_day(1).unwrap();
+                (start, start.checked_add_months(Months::new(1)))
+            }
+        };
+        let end = end.expect("date out of range");
+        self.midnight(start)..self.midnight(end)
+    }
+
+    /// The first instant of the day `date` in the time zone, in milliseconds since the Unix
+    /// epoch.
+    fn midnight(&self, date: NaiveDate) -> Timestamp {
+        let midnight = date.and_time(NaiveTime::MIN);
+        // When midnight is skipped by a daylight saving time change the day starts at the end
+        // of the gap, and when it is repeated at its first occurrence
+        (0..=24 * 4)
+            .find_map(|q| {
+                self.tz
+                    .from_local_datetime(&(midnight + TimeDelta::minutes(15 * q)))
+                    .earliest()
+            })
+            .expect("invalid local time")
+            .timestamp_millis()
+    }
+}
+
+#[inline]
+fn now_millis() -> Timestamp {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time before the Unix epoch")
+        .as_millis() as Timestamp
+}
+
+#[derive(Clone)]
+pub struct CalendarWindowManager<A, Tz>
+where
+    A: WindowAccumulator,
+{
+    init: A,
+    calendar: Calendar<Tz>,
+    processing_time: bool,
+    last_watermark: Option<Timestamp>,
+    /// The open windows, sorted by end and start.
+    ws: BTreeMap<(Timestamp, Timestamp), A>,
+}
+
+impl<A: WindowAccumulator, Tz: TimeZone> CalendarWindowManager<A, Tz> {
+    fn add(&mut self, item: A::In, ts: Timestamp) {
+        let range = self.calendar.window(ts);
+        // Same condition of the event time windows for a closed window
+        if self.last_watermark.map(|w| range.end < w).unwrap_or(false) {
+            log::debug!("Discarding late element with timestamp {ts}");
+            return;
+        }
+        self.ws
+            .entry((range.end, range.start))
+            .or_insert_with(|| {
+                let mut acc = self.init.clone();
+                acc.set_window(WindowContext {
+                    start: range.start,
+                    end: range.end,
+                });
+                acc
+            })
+            .process(item);
+    }
+
+    /// Close the windows that end before `limit`.
+    fn close(&mut self, limit: Timestamp) -> Vec<WindowResult<A::Out>> {
+        let mut ret = Vec::new();
+        while let Some(entry) = self.ws.first_entry() {
+            let end = entry.key().0;
+            if end >= limit {
+                break;
+            }
+            ret.push(Self::result(self.processing_time, entry.remove(), end));
+        }
+        ret
+    }
+
+    fn result(processing_time: bool, acc: A, end: Timestamp) -> WindowResult<A::Out> {
+        if processing_time {
+            WindowResult::Item(acc.output())
+        } else {
+            WindowResult::Timestamped(acc.output(), end)
+        }
+    }
+}
+
+impl<A: WindowAccumulator, Tz> WindowManager for CalendarWindowManager<A, Tz>
+where
+    A::In: Data,
+    A::Out: Data,
+    Tz: TimeZone + Send + 'static,
+{
+    type In = A::In;
+    type Out = A::Out;
+    type Output = Vec<WindowResult<A::Out>>;
+
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.ws.values().map(|acc| acc.memory()).sum::<usize>()
+    }
+
+    #[inline]
+    fn process(&mut self, el: StreamElement<A::In>) -> Self::Output {
+        if self.processing_time {
+            let now = now_millis();
+            return match el {
+                StreamElement::Item(item) | StreamElement::Timestamped(item, _) => {
+                    let ret = self.close(now + 1);
+                    self.add(item, now);
+                    ret
+                }
+                StreamElement::FlushAndRestart | StreamElement::Terminate => {
+                    self.close(Timestamp::MAX)
+                }
+                // The windows that ended before now can't receive other elements
+                _ => self.close(now + 1),
+            };
+        }
+
+        match el {
+            StreamElement::Timestamped(item, ts) => {
+                self.add(item, ts);
+                Vec::new()
+            }
+            StreamElement::Watermark(ts) => {
+                self.last_watermark = Some(ts);
+                self.close(ts)
+            }
+            StreamElement::FlushAndRestart | StreamElement::Terminate => {
+                self.last_watermark = None;
+                self.close(Timestamp::MAX)
+            }
+            StreamElement::Item(_) => {
+                panic!("Event time calendar windows can only handle timestamped items!")
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn recycle(&self) -> bool {
+        self.ws.is_empty()
+    }
+}
+
+/// Tumbling windows aligned to the days, weeks or months of the calendar in a time zone.
+///
+/// Unlike the tumbling windows of [`EventTimeWindow`] and [`ProcessingTimeWindow`], that have a
+/// fixed size and start from the first element, each window covers a period of the local
+/// calendar, from midnight to midnight: the aggregates match the business reporting periods,
+/// and the daylight saving time changes are taken into account (a day can last 23 or 25 hours).
+/// The time zone is any [`chrono::TimeZone`], like [`chrono::Utc`], [`chrono::FixedOffset`],
+/// [`chrono::Local`] or the zones of the IANA database from the `chrono-tz` crate.
+///
+/// The timestamps are milliseconds since the Unix epoch. With event time, a window emits its
+/// result, with its end as timestamp, when the watermark passes its end, and the elements of
+/// the windows that have already been closed are discarded. With processing time, each element
+/// goes in the window of the wall clock time at which it is processed.
+///
+/// ## Example
+///
+/// ```
+/// # use renoir::{StreamContext, RuntimeConfig};
+/// # use renoir::operator::source::IteratorSource;
+/// # use renoir::operator::window::{CalendarUnit, CalendarWindow};
+/// # let mut env = StreamContext::new_local();
+/// const HOUR: i64 = 60 * 60 * 1000;
+/// let tz = chrono::FixedOffset::east_opt(2 * 60 * 60).unwrap();
+/// // at UTC+2 the last two events are in the second day, while in UTC only the last one is
+/// let events = vec![10 * HOUR, 21 * HOUR, 23 * HOUR, 30 * HOUR];
+/// let res = env
+///     .stream_iter(events.into_iter())
+///     .add_timestamps(|&ts| ts, |_, &ts| Some(ts))
+///     .window_all(CalendarWindow::event_time(CalendarUnit::Day, tz))
+///     .count()
+///     .drop_key()
+///     .collect_vec();
+///
+/// env.execute_blocking();
+///
+/// assert_eq!(res.get().unwrap(), vec![2, 2]);
+/// ```
+#[derive(Clone)]
+pub struct CalendarWindow<Tz> {
+    calendar: Calendar<Tz>,
+    processing_time: bool,
+}
+
+impl<Tz: TimeZone> CalendarWindow<Tz> {
+    /// Calendar windows based on the timestamps of the elements.
+    #[inline]
+    pub fn event_time(unit: CalendarUnit, tz: Tz) -> Self {
+        Self {
+            calendar: Calendar { unit, tz },
+            processing_time: false,
+        }
+    }
+
+    /// Calendar windows based on the wall clock at the time of processing.
+    #[inline]
+    pub fn processing_time(unit: CalendarUnit, tz: Tz) -> Self {
+        Self {
+            calendar: Calendar { unit, tz },
+            processing_time: true,
+        }
+    }
+}
+
+impl<T: Data, Tz: TimeZone + Send + 'static> WindowDescription<T> for CalendarWindow<Tz> {
+    type Manager<A: WindowAccumulator<In = T>> = CalendarWindowManager<A, Tz>;
+
+    #[inline]
+    fn build<A: WindowAccumulator<In = T>>(&self, accumulator: A) -> Self::Manager<A> {
+        CalendarWindowManager {
+            init: accumulator,
+            calendar: self.calendar.clone(),
+            processing_time: self.processing_time,
+            last_watermark: None,
+            ws: Default::default(),
+        }
+    }
+}
+
+impl<T: Data, Tz: TimeZone + Send + 'static> ContextWindowDescription<T> for CalendarWindow<Tz> {}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, Utc};
+
+    use super::*;
+    use crate::operator::window::aggr::Fold;
+
+    const HOUR: Timestamp = 60 * 60 * 1000;
+    const DAY: Timestamp = 24 * HOUR;
+
+    fn utc(y: i32, m: u32, d: u32) -> Timestamp {
+        Utc.with_ymd_and_hms(y, m, d, 0, 0, 0)
+            .unwrap()
+            .timestamp_millis()
+    }
+
+    #[test]
+    fn calendar_boundaries() {
+        let tz = FixedOffset::west_opt(5 * 60 * 60).unwrap();
+        let day = Calendar {
+            unit: CalendarUnit::Day,
+            tz,
+        };
+        // 2024-03-01 03:00 UTC is still 2024-02-29 at UTC-5
+        let ts = utc(2024, 3, 1) + 3 * HOUR;
+        let start = utc(2024, 2, 29) + 5 * HOUR;
+        assert_eq!(day.window(ts), start..start + DAY);
+
+        // 2024-02-29 is a Thursday
+        let week = Calendar {
+            unit: CalendarUnit::Week(Weekday::Mon),
+            tz: Utc,
+        };
+        assert_eq!(
+            week.window(utc(2024, 2, 29) + HOUR),
+            utc(2024, 2, 26)..utc(2024, 3, 4)
+        );
+
+        let month = Calendar {
+            unit: CalendarUnit::Month,
+            tz: Utc,
+        };
+        assert_eq!(
+            month.window(utc(2024, 2, 29) + HOUR),
+            utc(2024, 2, 1)..utc(2024, 3, 1)
+        );
+    }
+
+    #[test]
+    fn calendar_window_event_time() {
+        let window = CalendarWindow::event_time(CalendarUnit::Month, Utc);
+
+        let fold = Fold::new(Vec::new(), |v, el| v.push(el));
+        let mut manager = window.build(fold);
+
+        let mut received = Vec::new();
+        for ts in [utc(2024, 1, 5), utc(2024, 1, 31), utc(2024, 2, 1)] {
+            received.extend(manager.process(StreamElement::Timestamped(ts, ts)));
+        }
+        received.extend(manager.process(StreamElement::Watermark(utc(2024, 2, 2))));
+        assert_eq!(
+            received,
+            vec![WindowResult::Timestamped(
+                vec![utc(2024, 1, 5), utc(2024, 1, 31)],
+                utc(2024, 2, 1)
+            )]
+        );
+
+        // january is closed
+        received.clear();
+        received.extend(manager.process(StreamElement::Timestamped(0, utc(2024, 1, 20))));
+        received.extend(manager.process(StreamElement::Terminate));
+        assert_eq!(
+            received,
+            vec![WindowResult::Timestamped(
+                vec![utc(2024, 2, 1)],
+                utc(2024, 3, 1)
+            )]
+        );
+        assert!(manager.recycle());
+    }
+}
@@ -58,6 +58,11 @@ where
     type Out = A::Out;
     type Output = Option<WindowResult<A::Out>>;
 
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.ws.iter().map(|w| w.acc.memory()).sum::<usize>()
+    }
+
     #[inline]
     fn process(&mut self, el: StreamElement<A::In>) -> Self::Output {
         let ts = el.timestamp().cloned();
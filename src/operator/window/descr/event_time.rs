@@ -11,36 +11,140 @@ where
     init: A,
     size: Timestamp,
     slide: Timestamp,
+    lateness: Timestamp,
+    early_elements: Option<usize>,
+    early_bytes: Option<usize>,
     last_watermark: Option<Timestamp>,
     ws: VecDeque<Slot<A>>,
 }
 impl<A: WindowAccumulator> EventTimeWindowManager<A> {
-    fn alloc_windows(&mut self, ts: Timestamp) {
-        assert!(self.last_watermark.map(|w| ts >= w).unwrap_or(true));
+    fn new_slot(&self, start: Timestamp) -> Slot<A> {
+        log::trace!("New window {}..{}", start, start + self.size);
+        let mut acc = self.init.clone();
+        acc.set_window(WindowContext {
+            start,
+            end: start + self.size,
+        });
+        let mut slot = Slot::new(acc, start, start + self.size);
+        slot.fired_memory = slot.acc.memory();
+        slot
+    }
 
+    fn alloc_windows(&mut self, ts: Timestamp) {
+        // The windows start from the first element: an element that is not late may precede
+        // them, allocate the windows up to its own instead of dropping it
+        while let Some(front) = self.ws.front().filter(|front| front.start > ts) {
+            let mut slot = self.new_slot(front.start - self.slide);
+            // The watermark has already passed its end: fire it for each element, like a window
+            // within the allowed lateness
+            slot.fired = self.last_watermark.is_some_and(|w| slot.end < w);
+            self.ws.push_front(slot);
+        }
         while self.ws.back().map(|b| b.start < ts).unwrap_or(true) {
             let mut next_start = self.ws.back().map(|b| b.start + self.slide).unwrap_or(ts);
             // Skip empty windows
             if let Some(w) = self.last_watermark {
+                let w = w - self.lateness;
                 next_start += (w - next_start).max(0) / self.slide * self.slide
             }
 
-            log::trace!("New window {}..{}", next_start, next_start + self.size);
-            self.ws.push_back(Slot::new(
-                self.init.clone(),
-                next_start,
-                next_start + self.size,
-            ));
+            let slot = self.new_slot(next_start);
+            self.ws.push_back(slot);
         }
     }
 }
 
+/// An element is late if its timestamp is lower than the last watermark minus the allowed
+/// lateness: the windows it belongs to may have already been closed.
+#[inline]
+fn is_late(ts: Timestamp, watermark: Option<Timestamp>, lateness: Timestamp) -> bool {
+    watermark.map(|w| ts < w - lateness).unwrap_or(false)
+}
+
+/// Tag each element with whether it is late (`Err`) or not (`Ok`), following the watermarks
+/// of the stream in the same way as [`EventTimeWindowManager`].
+#[derive(Clone)]
+pub(crate) struct TagLate<Key, Out, Op>
+where
+    Op: Operator<Out = (Key, Out)>,
+{
+    prev: Op,
+    lateness: Timestamp,
+    last_watermark: Option<Timestamp>,
+}
+
+impl<Key, Out, Op> TagLate<Key, Out, Op>
+where
+    Op: Operator<Out = (Key, Out)>,
+{
+    fn new(prev: Op, lateness: Timestamp) -> Self {
+        Self {
+            prev,
+            lateness,
+            last_watermark: None,
+        }
+    }
+}
+
+impl<Key, Out, Op> Display for TagLate<Key, Out, Op>
+where
+    Op: Operator<Out = (Key, Out)>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> TagLate", self.prev)
+    }
+}
+
+impl<Key, Out, Op> Operator for TagLate<Key, Out, Op>
+where
+    Key: DataKey,
+    Out: Data,
+    Op: Operator<Out = (Key, Out)>,
+{
+    type Out = (Key, Result<Out, Out>);
+
+    fn setup(&mut self, metadata: &mut crate::ExecutionMetadata) {
+        self.prev.setup(metadata);
+    }
+
+    fn next(&mut self) -> StreamElement<Self::Out> {
+        let el = self.prev.next();
+        if let StreamElement::Watermark(w) = el {
+            self.last_watermark = Some(w);
+        }
+        let late = match el {
+            StreamElement::Timestamped(_, ts) => is_late(ts, self.last_watermark, self.lateness),
+            _ => false,
+        };
+        el.map(|(key, item)| {
+            if late {
+                (key, Err(item))
+            } else {
+                (key, Ok(item))
+            }
+        })
+    }
+
+    fn structure(&self) -> crate::block::BlockStructure {
+        self.prev
+            .structure()
+            .add_operator(OperatorStructure::new::<Self::Out, _>("TagLate"))
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Slot<A> {
     acc: A,
     start: Timestamp,
     end: Timestamp,
     active: bool,
+    /// Whether the result of the window has already been emitted. A fired window is kept until
+    /// the allowed lateness has passed, and fires again for each late element it receives.
+    fired: bool,
+    /// The number of elements added since the last early firing.
+    pending: usize,
+    /// The memory used by the accumulator when it was created, or at the last early firing.
+    fired_memory: usize,
 }
 
 impl<A> Slot<A> {
@@ -51,6 +155,9 @@ impl<A> Slot<A> {
             start,
             end,
             active: false,
+            fired: false,
+            pending: 0,
+            fired_memory: 0,
         }
     }
 }
@@ -64,35 +171,71 @@ where
     type Out = A::Out;
     type Output = Vec<WindowResult<A::Out>>;
 
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.ws.iter().map(|w| w.acc.memory()).sum::<usize>()
+    }
+
     #[inline]
     fn process(&mut self, el: StreamElement<A::In>) -> Self::Output {
         match el {
+            StreamElement::Timestamped(_, ts)
+                if is_late(ts, self.last_watermark, self.lateness) =>
+            {
+                log::debug!("Discarding late element with timestamp {ts}");
+                Vec::new()
+            }
             StreamElement::Timestamped(item, ts) => {
                 self.alloc_windows(ts);
+                let (early_elements, early_bytes) = (self.early_elements, self.early_bytes);
                 self.ws
                     .iter_mut()
                     .skip_while(|w| w.end <= ts)
                     .take_while(|w| w.start <= ts)
-                    .for_each(|w| {
+                    .filter_map(|w| {
                         w.acc.process(item.clone());
                         w.active = true;
-                    });
-
-                Vec::new()
+                        w.pending += 1;
+                        let early = early_elements.is_some_and(|n| w.pending >= n)
+                            || early_bytes.is_some_and(|b| {
+                                w.acc.memory().saturating_sub(w.fired_memory) >= b
+                            });
+                        // Late element of a window that has already fired, or early firing:
+                        // emit the updated result
+                        (w.fired || early).then(|| {
+                            w.pending = 0;
+                            w.fired_memory = w.acc.memory();
+                            WindowResult::Timestamped(w.acc.clone().output(), w.end)
+                        })
+                    })
+                    .collect()
             }
             StreamElement::Watermark(ts) => {
                 self.last_watermark = Some(ts);
-                let split = self.ws.partition_point(|w| w.end < ts);
-                self.ws
+                // Windows past the allowed lateness are dropped, the others are kept after firing
+                let split = self.ws.partition_point(|w| w.end + self.lateness < ts);
+                let mut ret: Vec<_> = self
+                    .ws
                     .drain(..split)
-                    .filter(|w| w.active)
+                    .filter(|w| w.active && !w.fired)
                     .map(|w| WindowResult::Timestamped(w.acc.output(), w.end))
-                    .collect()
+                    .collect();
+                ret.extend(
+                    self.ws
+                        .iter_mut()
+                        .take_while(|w| w.end < ts)
+                        .filter(|w| w.active && !w.fired)
+                        .map(|w| {
+                            w.fired = true;
+                            WindowResult::Timestamped(w.acc.clone().output(), w.end)
+                        }),
+                );
+                ret
             }
             StreamElement::FlushAndRestart | StreamElement::Terminate => self
                 .ws
                 .drain(..)
-                .filter(|w| w.active)
+                .filter(|w| w.active && !w.fired)
                 .map(|w| WindowResult::Timestamped(w.acc.output(), w.end))
                 .collect(),
             StreamElement::Item(_) => {
@@ -112,6 +255,9 @@ where
 pub struct EventTimeWindow {
     size: Timestamp,
     slide: Timestamp,
+    lateness: Timestamp,
+    early_elements: Option<usize>,
+    early_bytes: Option<usize>,
 }
 
 impl EventTimeWindow {
@@ -119,13 +265,110 @@ impl EventTimeWindow {
     pub fn sliding(size: Timestamp, slide: Timestamp) -> Self {
         assert!(size > 0, "window size must be > 0");
         assert!(slide > 0, "window slide must be > 0");
-        Self { size, slide }
+        Self {
+            size,
+            slide,
+            lateness: 0,
+            early_elements: None,
+            early_bytes: None,
+        }
     }
 
     #[inline]
     pub fn tumbling(size: Timestamp) -> Self {
         assert!(size > 0, "window size must be > 0");
-        Self { size, slide: size }
+        Self {
+            size,
+            slide: size,
+            lateness: 0,
+            early_elements: None,
+            early_bytes: None,
+        }
+    }
+
+    /// Keep the windows for a grace period of `lateness` after the watermark has passed their
+    /// end.
+    ///
+    /// A window emits its result when the watermark passes its end, as usual, but its state is
+    /// kept until the watermark passes its end plus `lateness`. Each late element that arrives in
+    /// the meantime is added to the window, which fires again emitting the updated result: the
+    /// new result accumulates all the elements of the window and replaces the previous one.
+    /// Only the elements later than `lateness` are discarded (or routed to the side output of
+    /// [`split_late`](crate::WindowedStream::split_late)).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::window::EventTimeWindow;
+    /// # let mut env = StreamContext::new_local();
+    /// let res = env
+    ///     .stream_iter(vec![0, 1, 2, 10, 3, 11].into_iter())
+    ///     .add_timestamps(|&n| n, |_, &ts| Some(ts))
+    ///     .group_by(|_| ())
+    ///     .window(EventTimeWindow::tumbling(5).allowed_lateness(10))
+    ///     .sum::<i64>()
+    ///     .drop_key()
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// // the first window fires again when the late element 3 arrives
+    /// assert_eq!(res.get().unwrap(), vec![0 + 1 + 2, 0 + 1 + 2 + 3, 10 + 11]);
+    /// ```
+    #[inline]
+    pub fn allowed_lateness(mut self, lateness: Timestamp) -> Self {
+        assert!(lateness >= 0, "allowed lateness must be >= 0");
+        self.lateness = lateness;
+        self
+    }
+
+    /// Fire early each time a window accumulates `n` more elements.
+    ///
+    /// The window emits its partial result and keeps accumulating the elements: its final result,
+    /// emitted when the watermark passes its end, contains all of them as usual. This bounds the
+    /// delay of the results of the windows of the hot keys, and, with the aggregations that
+    /// collect the elements, it allows to process them before the window closes.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::window::EventTimeWindow;
+    /// # let mut env = StreamContext::new_local();
+    /// let res = env
+    ///     .stream_iter(0..5)
+    ///     .add_timestamps(|&n| n, |_, &ts| Some(ts))
+    ///     .group_by(|_| ())
+    ///     .window(EventTimeWindow::tumbling(10).early_fire_every(2))
+    ///     .sum::<i64>()
+    ///     .drop_key()
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(res.get().unwrap(), vec![0 + 1, 0 + 1 + 2 + 3, 0 + 1 + 2 + 3 + 4]);
+    /// ```
+    #[inline]
+    pub fn early_fire_every(mut self, n: usize) -> Self {
+        assert!(n > 0, "early firing count must be > 0");
+        self.early_elements = Some(n);
+        self
+    }
+
+    /// Fire early each time the memory used by a window grows by `bytes`, like
+    /// [`early_fire_every`](EventTimeWindow::early_fire_every).
+    ///
+    /// The memory is the estimate of [`WindowAccumulator::memory`]: it grows with the aggregations
+    /// that buffer the elements, while the ones that only keep a partial result (like `sum` or
+    /// `fold`) never fire early with this trigger.
+    #[inline]
+    pub fn early_fire_bytes(mut self, bytes: usize) -> Self {
+        assert!(bytes > 0, "early firing size must be > 0");
+        self.early_bytes = Some(bytes);
+        self
     }
 }
 
@@ -138,12 +381,87 @@ impl<T: Data> WindowDescription<T> for EventTimeWindow {
             init: accumulator,
             size: self.size,
             slide: self.slide,
+            lateness: self.lateness,
+            early_elements: self.early_elements,
+            early_bytes: self.early_bytes,
             last_watermark: Default::default(),
             ws: Default::default(),
         }
     }
 }
 
+impl<T: Data> ContextWindowDescription<T> for EventTimeWindow {}
+
+impl<Key, Out, WinOut, Op> WindowedStream<Op, WinOut, EventTimeWindow>
+where
+    Key: DataKey + ExchangeData,
+    Out: ExchangeData,
+    WinOut: Data,
+    Op: Operator<Out = (Key, Out)> + 'static,
+{
+    /// Separate the late elements from the windowed stream, instead of discarding them.
+    ///
+    /// An element is late when its timestamp is lower than the last watermark received (minus the
+    /// [allowed lateness](EventTimeWindow::allowed_lateness) of the windows), so the windows it
+    /// belongs to may have already been closed and emitted. By default these elements
+    /// are discarded by the windows; with this method they are routed to a second stream, so
+    /// that they can be logged or reprocessed. The first returned stream is the windowed stream
+    /// without the late elements, the second one contains the late elements with their key and
+    /// timestamp.
+    ///
+    /// **Note**: this operator will split the current block.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::window::EventTimeWindow;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env
+    ///     .stream_iter(vec![0, 1, 2, 10, 3, 11].into_iter())
+    ///     .add_timestamps(|&n| n, |_, &ts| Some(ts));
+    /// let (windows, late) = s
+    ///     .group_by(|_| ())
+    ///     .window(EventTimeWindow::tumbling(5))
+    ///     .split_late();
+    /// let windows = windows.sum::<i64>().drop_key().collect_vec();
+    /// let late = late.drop_key().collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// assert_eq!(windows.get().unwrap(), vec![0 + 1 + 2, 10 + 11]);
+    /// assert_eq!(late.get().unwrap(), vec![3]);
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn split_late(
+        self,
+    ) -> (
+        WindowedStream<impl Operator<Out = (Key, Out)>, WinOut, EventTimeWindow>,
+        KeyedStream<impl Operator<Out = (Key, Out)>>,
+    ) {
+        let descr = self.descr;
+        let lateness = descr.lateness;
+        let mut routes = self
+            .inner
+            .0
+            .add_operator(|prev| TagLate::new(prev, lateness))
+            .route()
+            .add_route(|(_, r)| r.is_ok())
+            .add_route(|(_, r)| r.is_err())
+            .build_inner();
+        let late = routes.pop().unwrap().map(|(key, r)| match r {
+            Err(item) => (key, item),
+            Ok(_) => unreachable!("only the late elements are routed to the side output"),
+        });
+        let main = routes.pop().unwrap().map(|(key, r)| match r {
+            Ok(item) => (key, item),
+            Err(_) => unreachable!("the late elements are routed to the side output"),
+        });
+        (KeyedStream(main).window(descr), KeyedStream(late))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -206,4 +524,108 @@ mod tests {
         let expected: Vec<Vec<_>> = vec![vec![1], vec![15, 16], vec![30, 31]];
         assert_eq!(received, expected)
     }
+
+    #[test]
+    fn event_time_window_allowed_lateness() {
+        let window = EventTimeWindow::tumbling(10).allowed_lateness(10);
+
+        let fold = Fold::new(Vec::new(), |v, el| v.push(el));
+        let mut manager = window.build(fold);
+
+        let mut received = Vec::new();
+        for i in [1, 2, 11] {
+            save_result!(manager.process(StreamElement::Timestamped(i, i)), received);
+        }
+        save_result!(manager.process(StreamElement::Watermark(12)), received);
+        assert_eq!(received, vec![vec![1, 2]]);
+
+        // Within the allowed lateness: the window fires again
+        save_result!(manager.process(StreamElement::Timestamped(3, 3)), received);
+        assert_eq!(received, vec![vec![1, 2], vec![1, 2, 3]]);
+
+        // Past the allowed lateness: the window is closed and the element is discarded
+        save_result!(manager.process(StreamElement::Watermark(22)), received);
+        save_result!(manager.process(StreamElement::Timestamped(4, 4)), received);
+        save_result!(manager.process(StreamElement::FlushAndRestart), received);
+        assert_eq!(received, vec![vec![1, 2], vec![1, 2, 3], vec![11]]);
+    }
+
+    #[test]
+    fn event_time_window_before_first() {
+        let window = EventTimeWindow::tumbling(10).allowed_lateness(10);
+
+        let fold = Fold::new(Vec::new(), |v, el| v.push(el));
+        let mut manager = window.build(fold);
+
+        // The windows are aligned to 12, the element 3 belongs to a window before the first one
+        let mut received = Vec::new();
+        for i in [12, 3] {
+            save_result!(manager.process(StreamElement::Timestamped(i, i)), received);
+        }
+        save_result!(manager.process(StreamElement::Watermark(13)), received);
+        assert_eq!(received, vec![vec![3]]);
+
+        // Within the allowed lateness: the window fires again
+        save_result!(manager.process(StreamElement::Timestamped(5, 5)), received);
+        save_result!(manager.process(StreamElement::FlushAndRestart), received);
+        assert_eq!(received, vec![vec![3], vec![3, 5], vec![12]]);
+
+        // The window of 5 is allocated after the watermark has passed its end, and fires immediately
+        let mut received = Vec::new();
+        save_result!(
+            manager.process(StreamElement::Timestamped(12, 12)),
+            received
+        );
+        save_result!(manager.process(StreamElement::Watermark(13)), received);
+        save_result!(manager.process(StreamElement::Timestamped(5, 5)), received);
+        assert_eq!(received, vec![vec![5]]);
+    }
+
+    #[test]
+    fn event_time_window_early_firing() {
+        let window = EventTimeWindow::tumbling(10).early_fire_every(3);
+
+        let fold = Fold::new(Vec::new(), |v, el| v.push(el));
+        let mut manager = window.build(fold);
+
+        let mut received = Vec::new();
+        for i in 0..7 {
+            save_result!(manager.process(StreamElement::Timestamped(i, i)), received);
+        }
+        assert_eq!(received, vec![vec![0, 1, 2], vec![0, 1, 2, 3, 4, 5]]);
+
+        // The final result contains all the elements
+        save_result!(manager.process(StreamElement::Watermark(11)), received);
+        assert_eq!(received.len(), 3);
+        assert_eq!(received[2], vec![0, 1, 2, 3, 4, 5, 6]);
+
+        let window = EventTimeWindow::tumbling(10).early_fire_bytes(2 * 8);
+        let mut manager = window.build(Buffer(Vec::new()));
+        let mut received = Vec::new();
+        for i in 0..5 {
+            save_result!(manager.process(StreamElement::Timestamped(i, i)), received);
+        }
+        assert_eq!(received, vec![vec![0, 1], vec![0, 1, 2, 3]]);
+    }
+
+    /// Buffers the elements, using 8 bytes for each of them.
+    #[derive(Clone)]
+    struct Buffer(Vec<i64>);
+
+    impl WindowAccumulator for Buffer {
+        type In = i64;
+        type Out = Vec<i64>;
+
+        fn process(&mut self, el: i64) {
+            self.0.push(el);
+        }
+
+        fn output(self) -> Vec<i64> {
+            self.0
+        }
+
+        fn memory(&self) -> usize {
+            self.0.len() * 8
+        }
+    }
 }
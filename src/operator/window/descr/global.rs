@@ -0,0 +1,337 @@
+use std::collections::VecDeque;
+
+use super::super::*;
+use crate::operator::{Data, StreamElement, Timestamp};
+
+/// What a [`Trigger`] does with the global window after an element is added.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerAction {
+    /// Keep collecting the elements.
+    Continue,
+    /// Emit the result of the window, keeping its elements.
+    Fire,
+    /// Emit the result of the window and remove all its elements.
+    FireAndPurge,
+}
+
+/// Decide when a [`GlobalWindow`] emits its result.
+///
+/// The trigger is called for each element added to the window, and it can keep its own state,
+/// like the number of elements since the last firing. It is implemented by [`CountTrigger`]
+/// and by the closures `FnMut(&T) -> TriggerAction`, which can be used to fire when a
+/// punctuation element is received.
+pub trait Trigger<T>: Clone + Send + 'static {
+    /// Called for each `item` added to the window.
+    fn on_element(&mut self, item: &T) -> TriggerAction;
+}
+
+impl<T, F> Trigger<T> for F
+where
+    F: FnMut(&T) -> TriggerAction + Clone + Send + 'static,
+{
+    #[inline]
+    fn on_element(&mut self, item: &T) -> TriggerAction {
+        self(item)
+    }
+}
+
+/// Fire every `n` elements.
+#[derive(Clone, Debug)]
+pub struct CountTrigger {
+    n: usize,
+    count: usize,
+    purge: bool,
+}
+
+impl CountTrigger {
+    /// Fire every `n` elements, keeping the elements in the window.
+    #[inline]
+    pub fn new(n: usize) -> Self {
+        assert!(n > 0, "trigger count must be > 0");
+        Self {
+            n,
+            count: 0,
+            purge: false,
+        }
+    }
+
+    /// Fire every `n` elements, removing them from the window.
+    #[inline]
+    pub fn purging(n: usize) -> Self {
+        Self {
+            purge: true,
+            ..Self::new(n)
+        }
+    }
+}
+
+impl<T> Trigger<T> for CountTrigger {
+    #[inline]
+    fn on_element(&mut self, _item: &T) -> TriggerAction {
+        self.count += 1;
+        if self.count < self.n {
+            TriggerAction::Continue
+        } else {
+            self.count = 0;
+            if self.purge {
+                TriggerAction::FireAndPurge
+            } else {
+                TriggerAction::Fire
+            }
+        }
+    }
+}
+
+/// Remove some elements from a [`GlobalWindow`].
+///
+/// The evictor is called each time an element is added to the window, before it fires. It is
+/// implemented by [`CountEvictor`], and by `()` which keeps all the elements.
+pub trait Evictor<T>: Clone + Send + 'static {
+    /// Remove the unwanted elements from the window, sorted from the oldest to the newest.
+    fn evict(&mut self, elements: &mut VecDeque<T>);
+}
+
+impl<T> Evictor<T> for () {
+    #[inline]
+    fn evict(&mut self, _elements: &mut VecDeque<T>) {}
+}
+
+/// Keep only the last `n` elements of the window.
+#[derive(Clone, Debug)]
+pub struct CountEvictor {
+    n: usize,
+}
+
+impl CountEvictor {
+    #[inline]
+    pub fn new(n: usize) -> Self {
+        Self { n }
+    }
+}
+
+impl<T> Evictor<T> for CountEvictor {
+    #[inline]
+    fn evict(&mut self, elements: &mut VecDeque<T>) {
+        let excess = elements.len().saturating_sub(self.n);
+        elements.drain(..excess);
+    }
+}
+
+#[derive(Clone)]
+pub struct GlobalWindowManager<A, Tr, Ev>
+where
+    A: WindowAccumulator,
+{
+    init: A,
+    trigger: Tr,
+    evictor: Ev,
+    elements: VecDeque<A::In>,
+    ts: Option<Timestamp>,
+    /// Whether some elements were added after the last firing.
+    pending: bool,
+}
+
+impl<A: WindowAccumulator, Tr, Ev> GlobalWindowManager<A, Tr, Ev> {
+    fn output(&mut self) -> WindowResult<A::Out> {
+        self.pending = false;
+        let mut acc = self.init.clone();
+        for el in self.elements.iter() {
+            acc.process(el.clone());
+        }
+        WindowResult::new(acc.output(), self.ts)
+    }
+}
+
+impl<A: WindowAccumulator, Tr, Ev> WindowManager for GlobalWindowManager<A, Tr, Ev>
+where
+    A::In: Data,
+    A::Out: Data,
+    Tr: Trigger<A::In>,
+    Ev: Evictor<A::In>,
+{
+    type In = A::In;
+    type Out = A::Out;
+    type Output = Option<WindowResult<A::Out>>;
+
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.elements.capacity() * std::mem::size_of::<A::In>()
+    }
+
+    #[inline]
+    fn process(&mut self, el: StreamElement<A::In>) -> Self::Output {
+        let ts = el.timestamp().cloned();
+        match el {
+            StreamElement::Item(item) | StreamElement::Timestamped(item, _) => {
+                self.ts = self.ts.max(ts);
+                let action = self.trigger.on_element(&item);
+                self.elements.push_back(item);
+                self.evictor.evict(&mut self.elements);
+                self.pending = true;
+                match action {
+                    TriggerAction::Continue => None,
+                    TriggerAction::Fire => Some(self.output()),
+                    TriggerAction::FireAndPurge => {
+                        let ret = self.output();
+                        self.elements.clear();
+                        self.ts = None;
+                        Some(ret)
+                    }
+                }
+            }
+            StreamElement::FlushAndRestart | StreamElement::Terminate => {
+                let ret = if self.pending {
+                    Some(self.output())
+                } else {
+                    None
+                };
+                self.elements.clear();
+                self.ts = None;
+                ret
+            }
+            _ => None,
+        }
+    }
+
+    fn recycle(&self) -> bool {
+        self.elements.is_empty()
+    }
+}
+
+/// A single window with all the elements of the stream, that emits its result when its
+/// [`Trigger`] fires.
+///
+/// The elements are kept in the window, and the result is computed from all of them each time
+/// the window fires, so an [`Evictor`] should limit their number: for example with a
+/// [`CountTrigger`] and a [`CountEvictor`] the window slides over the last elements. The
+/// elements added after the last firing are emitted when the stream ends.
+///
+/// ## Example
+///
+/// ```
+/// # use renoir::{StreamContext, RuntimeConfig};
+/// # use renoir::operator::source::IteratorSource;
+/// # use renoir::operator::window::{CountEvictor, CountTrigger, GlobalWindow, TriggerAction};
+/// # let mut env = StreamContext::new_local();
+/// let s = env.stream_iter(1..=6);
+/// // the sum of the last 3 elements, every 2 elements
+/// let sliding = s
+///     .window_all(GlobalWindow::new(CountTrigger::new(2)).evictor(CountEvictor::new(3)))
+///     .sum::<i32>()
+///     .drop_key()
+///     .collect_vec();
+///
+/// let s = env.stream_iter(vec![1, 2, 0, 3, 0, 4].into_iter());
+/// // the sum of the elements up to each zero, a punctuation
+/// let punctuated = s
+///     .window_all(GlobalWindow::new(|&x: &i32| {
+///         if x == 0 {
+///             TriggerAction::FireAndPurge
+///         } else {
+///             TriggerAction::Continue
+///         }
+///     }))
+///     .sum::<i32>()
+///     .drop_key()
+///     .collect_vec();
+///
+/// env.execute_blocking();
+///
+/// assert_eq!(sliding.get().unwrap(), vec![1 + 2, 2 + 3 + 4, 4 + 5 + 6]);
+/// assert_eq!(punctuated.get().unwrap(), vec![1 + 2, 3, 4]);
+/// ```
+#[derive(Clone)]
+pub struct GlobalWindow<Tr, Ev = ()> {
+    trigger: Tr,
+    evictor: Ev,
+}
+
+impl<Tr> GlobalWindow<Tr> {
+    /// A global window that fires following `trigger`.
+    #[inline]
+    pub fn new(trigger: Tr) -> Self {
+        Self {
+            trigger,
+            evictor: (),
+        }
+    }
+}
+
+impl<Tr, Ev> GlobalWindow<Tr, Ev> {
+    /// Remove the elements from the window with `evictor`.
+    #[inline]
+    pub fn evictor<Ev2>(self, evictor: Ev2) -> GlobalWindow<Tr, Ev2> {
+        GlobalWindow {
+            trigger: self.trigger,
+            evictor,
+        }
+    }
+}
+
+impl<T: Data, Tr: Trigger<T>, Ev: Evictor<T>> WindowDescription<T> for GlobalWindow<Tr, Ev> {
+    type Manager<A: WindowAccumulator<In = T>> = GlobalWindowManager<A, Tr, Ev>;
+
+    #[inline]
+    fn build<A: WindowAccumulator<In = T>>(&self, accumulator: A) -> Self::Manager<A> {
+        GlobalWindowManager {
+            init: accumulator,
+            trigger: self.trigger.clone(),
+            evictor: self.evictor.clone(),
+            elements: Default::default(),
+            ts: None,
+            pending: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator::window::aggr::Fold;
+
+    #[test]
+    fn global_window_sliding_count() {
+        let window = GlobalWindow::new(CountTrigger::new(2)).evictor(CountEvictor::new(3));
+
+        let fold = Fold::new(Vec::new(), |v, el| v.push(el));
+        let mut manager = window.build(fold);
+
+        let mut received = Vec::new();
+        for i in 1..=7 {
+            received.extend(manager.process(StreamElement::Timestamped(i, i)));
+        }
+        received.extend(manager.process(StreamElement::Terminate));
+        assert_eq!(
+            received,
+            vec![
+                WindowResult::Timestamped(vec![1, 2], 2),
+                WindowResult::Timestamped(vec![2, 3, 4], 4),
+                WindowResult::Timestamped(vec![4, 5, 6], 6),
+                WindowResult::Timestamped(vec![5, 6, 7], 7),
+            ]
+        );
+        assert!(manager.recycle());
+    }
+
+    #[test]
+    fn global_window_purging() {
+        let window = GlobalWindow::new(CountTrigger::purging(2));
+
+        let fold = Fold::new(Vec::new(), |v, el| v.push(el));
+        let mut manager = window.build(fold);
+
+        let mut received = Vec::new();
+        for i in 1..=4 {
+            received.extend(manager.process(StreamElement::Item(i)));
+        }
+        // nothing was added after the last firing
+        received.extend(manager.process(StreamElement::FlushAndRestart));
+        assert_eq!(
+            received,
+            vec![
+                WindowResult::Item(vec![1, 2]),
+                WindowResult::Item(vec![3, 4])
+            ]
+        );
+    }
+}
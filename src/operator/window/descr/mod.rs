@@ -4,18 +4,31 @@ pub use count::CountWindow;
 mod all;
 pub use all::AllWindow;
 
+#[cfg(feature = "timestamp")]
+mod assigner;
+#[cfg(feature = "timestamp")]
+pub use assigner::{AssignedWindow, WindowAssigner};
+
+#[cfg(feature = "calendar")]
+mod calendar;
+#[cfg(feature = "calendar")]
+pub use calendar::{CalendarUnit, CalendarWindow};
+
 #[cfg(feature = "timestamp")]
 mod event_time;
 #[cfg(feature = "timestamp")]
 pub use event_time::EventTimeWindow;
 
+mod global;
+pub use global::{CountEvictor, CountTrigger, Evictor, GlobalWindow, Trigger, TriggerAction};
+
 mod processing_time;
 pub use processing_time::ProcessingTimeWindow;
 
 mod session;
-pub use session::SessionWindow;
+pub use session::{SessionGap, SessionWindow};
 
 #[cfg(feature = "timestamp")]
 mod transaction;
 #[cfg(feature = "timestamp")]
-pub use transaction::{TransactionOp, TransactionWindow};
+pub use transaction::{EpochWindow, TransactionOp, TransactionWindow};
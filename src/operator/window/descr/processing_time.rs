@@ -44,6 +44,11 @@ where
     type Out = A::Out;
     type Output = Vec<WindowResult<A::Out>>;
 
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.ws.iter().map(|w| w.acc.memory()).sum::<usize>()
+    }
+
     #[inline]
     fn process(&mut self, el: StreamElement<A::In>) -> Self::Output {
         let now = Instant::now();
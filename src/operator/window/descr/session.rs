@@ -3,44 +3,77 @@ use std::time::{Duration, Instant};
 use super::super::*;
 use crate::operator::{Data, StreamElement};
 
+/// The inactivity gap that closes a session, computed from the elements of the session.
+///
+/// It is implemented by [`Duration`], for a static gap, and by the closures `Fn(&T) -> Duration`,
+/// for a gap that depends on the element.
+pub trait SessionGap<T>: Clone + Send + 'static {
+    /// The inactivity gap after the element `item`.
+    fn gap(&self, item: &T) -> Duration;
+}
+
+impl<T> SessionGap<T> for Duration {
+    #[inline]
+    fn gap(&self, _item: &T) -> Duration {
+        *self
+    }
+}
+
+impl<T, F> SessionGap<T> for F
+where
+    F: Fn(&T) -> Duration + Clone + Send + 'static,
+{
+    #[inline]
+    fn gap(&self, item: &T) -> Duration {
+        self(item)
+    }
+}
+
 #[derive(Clone)]
-pub struct SessionWindowManager<A>
+pub struct SessionWindowManager<A, G = Duration>
 where
     A: WindowAccumulator,
 {
     init: A,
-    gap: Duration,
+    gap: G,
     w: Option<Slot<A>>,
 }
 
 #[derive(Clone)]
 struct Slot<A> {
     acc: A,
-    last: Instant,
+    /// The session closes if no element is received until this instant.
+    end: Instant,
 }
 
 impl<A> Slot<A> {
     #[inline]
-    fn new(acc: A, last: Instant) -> Self {
-        Self { acc, last }
+    fn new(acc: A, end: Instant) -> Self {
+        Self { acc, end }
     }
 }
 
-impl<A: WindowAccumulator> WindowManager for SessionWindowManager<A>
+impl<A: WindowAccumulator, G> WindowManager for SessionWindowManager<A, G>
 where
     A::In: Data,
     A::Out: Data,
+    G: SessionGap<A::In>,
 {
     type In = A::In;
     type Out = A::Out;
     type Output = Option<WindowResult<A::Out>>;
 
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.w.as_ref().map_or(0, |w| w.acc.memory())
+    }
+
     #[inline]
     fn process(&mut self, el: StreamElement<A::In>) -> Self::Output {
         let ts = Instant::now();
 
         let ret = match &self.w {
-            Some(slot) if ts - slot.last > self.gap => {
+            Some(slot) if ts > slot.end => {
                 let output = self.w.take().unwrap().acc.output();
                 Some(WindowResult::Item(output))
             }
@@ -49,11 +82,13 @@ where
 
         match el {
             StreamElement::Item(item) | StreamElement::Timestamped(item, _) => {
+                let end = ts + self.gap.gap(&item);
                 let slot = self
                     .w
-                    .get_or_insert_with(|| Slot::new(self.init.clone(), ts));
+                    .get_or_insert_with(|| Slot::new(self.init.clone(), end));
                 slot.acc.process(item);
-                slot.last = ts;
+                // an element with a shorter gap does not shorten the session
+                slot.end = slot.end.max(end);
                 ret
             }
             StreamElement::Terminate | StreamElement::FlushAndRestart => {
@@ -66,8 +101,8 @@ where
 
 /// Window that splits after if no element is received for a fixed wall clock duration
 #[derive(Clone)]
-pub struct SessionWindow {
-    gap: Duration,
+pub struct SessionWindow<G = Duration> {
+    gap: G,
 }
 
 impl SessionWindow {
@@ -78,14 +113,51 @@ impl SessionWindow {
     }
 }
 
-impl<T: Data> WindowDescription<T> for SessionWindow {
-    type Manager<A: WindowAccumulator<In = T>> = SessionWindowManager<A>;
+impl<F> SessionWindow<F> {
+    /// Session window whose inactivity gap is computed for each element by `gap_fn`.
+    ///
+    /// The session is closed if no element is received for the gap of the last element, or of
+    /// any previous element of the session if longer. This allows different gaps for different
+    /// kinds of elements, for example a longer gap for the users of a premium tier.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use renoir::{StreamContext, RuntimeConfig};
+    /// # use renoir::operator::source::IteratorSource;
+    /// # use renoir::operator::window::SessionWindow;
+    /// # use std::time::Duration;
+    /// # let mut env = StreamContext::new_local();
+    /// let s = env.stream_iter(vec![('a', 1), ('b', 2), ('a', 3)].into_iter());
+    /// let res = s
+    ///     .group_by(|&(user, _)| user)
+    ///     .map(|(_, (_, n))| n)
+    ///     .window(SessionWindow::with_dynamic_gap(|&n: &i32| {
+    ///         Duration::from_secs(if n > 2 { 60 } else { 10 })
+    ///     }))
+    ///     .sum::<i32>()
+    ///     .collect_vec();
+    ///
+    /// env.execute_blocking();
+    ///
+    /// let mut res = res.get().unwrap();
+    /// res.sort_unstable();
+    /// assert_eq!(res, vec![('a', 1 + 3), ('b', 2)]);
+    /// ```
+    #[inline]
+    pub fn with_dynamic_gap(gap_fn: F) -> Self {
+        Self { gap: gap_fn }
+    }
+}
+
+impl<T: Data, G: SessionGap<T>> WindowDescription<T> for SessionWindow<G> {
+    type Manager<A: WindowAccumulator<In = T>> = SessionWindowManager<A, G>;
 
     #[inline]
     fn build<A: WindowAccumulator<In = T>>(&self, accumulator: A) -> Self::Manager<A> {
         SessionWindowManager {
             init: accumulator,
-            gap: self.gap,
+            gap: self.gap.clone(),
             w: Default::default(),
         }
     }
@@ -130,4 +202,28 @@ mod tests {
             vec![(0..33).collect(), (33..80).collect(), (80..100).collect()];
         assert_eq!(received, expected)
     }
+
+    #[test]
+    fn session_window_dynamic_gap() {
+        // odd elements have a longer gap
+        let window = SessionWindow::with_dynamic_gap(|n: &i64| {
+            Duration::from_millis(if n % 2 == 1 { 200 } else { 10 })
+        });
+
+        let fold = Fold::new(Vec::new(), |v, el| v.push(el));
+        let mut manager = window.build(fold);
+
+        let mut received = Vec::new();
+        for i in [0, 2, 4, 1, 6i64] {
+            if i == 4 || i == 6 {
+                std::thread::sleep(Duration::from_millis(20))
+            }
+            save_result!(manager.process(StreamElement::Item(i)), received);
+        }
+        save_result!(manager.process(StreamElement::FlushAndRestart), received);
+
+        // the gap after 2 is short, the gap after 1 is long
+        let expected: Vec<Vec<_>> = vec![vec![0, 2], vec![4, 1, 6]];
+        assert_eq!(received, expected)
+    }
 }
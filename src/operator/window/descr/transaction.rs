@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use super::super::*;
 use crate::operator::{Data, StreamElement};
 
@@ -49,6 +51,11 @@ where
     type Out = A::Out;
     type Output = Option<WindowResult<A::Out>>;
 
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.w.as_ref().map_or(0, |w| w.acc.memory())
+    }
+
     #[inline]
     fn process(&mut self, el: StreamElement<A::In>) -> Self::Output {
         macro_rules! return_current {
@@ -139,6 +146,239 @@ impl<T: Data, F: Fn(&T) -> TransactionOp + Data> WindowDescription<T> for Transa
     }
 }
 
+#[derive(Clone)]
+pub struct EpochWindowManager<A, K, E, F>
+where
+    A: WindowAccumulator,
+{
+    init: A,
+    epoch: E,
+    logic: F,
+    commit_on_new_epoch: bool,
+    /// The open windows, sorted by epoch.
+    ws: BTreeMap<K, Slot<A>>,
+}
+
+impl<A, K, E, F> WindowManager for EpochWindowManager<A, K, E, F>
+where
+    A: WindowAccumulator,
+    A::In: Data,
+    A::Out: Data,
+    K: Ord + Clone + Send + 'static,
+    E: Fn(&A::In) -> K + Clone + Send + 'static,
+    F: Fn(&A::In) -> TransactionOp + Clone + Send + 'static,
+{
+    type In = A::In;
+    type Out = A::Out;
+    type Output = Vec<WindowResult<A::Out>>;
+
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self) + self.ws.values().map(|w| w.acc.memory()).sum::<usize>()
+    }
+
+    #[inline]
+    fn process(&mut self, el: StreamElement<A::In>) -> Self::Output {
+        match el {
+            StreamElement::Item(item) | StreamElement::Timestamped(item, _) => {
+                let epoch = (self.epoch)(&item);
+                let mut ret = Vec::new();
+                if self.commit_on_new_epoch {
+                    // The windows of the previous epochs are complete
+                    while let Some(entry) = self.ws.first_entry() {
+                        if *entry.key() >= epoch {
+                            break;
+                        }
+                        ret.push(WindowResult::Item(entry.remove().acc.output()));
+                    }
+                }
+
+                let command = (self.logic)(&item);
+                let slot = self
+                    .ws
+                    .entry(epoch.clone())
+                    .or_insert_with(|| Slot::new(self.init.clone()));
+                slot.acc.process(item);
+
+                match command {
+                    TransactionOp::Commit => {
+                        let slot = self.ws.remove(&epoch).unwrap();
+                        ret.push(WindowResult::Item(slot.acc.output()));
+                    }
+                    TransactionOp::CommitAfter(t) => slot.close = Some(t),
+                    TransactionOp::Discard => {
+                        self.ws.remove(&epoch);
+                    }
+                    TransactionOp::Continue => {}
+                }
+                ret
+            }
+            StreamElement::Watermark(ts) => {
+                let (closed, open) = std::mem::take(&mut self.ws)
+                    .into_iter()
+                    .partition::<BTreeMap<_, _>, _>(|(_, w)| w.close.is_some_and(|c| c < ts));
+                self.ws = open;
+                closed
+                    .into_values()
+                    .map(|w| WindowResult::Item(w.acc.output()))
+                    .collect()
+            }
+            // The windows that were never committed are discarded
+            StreamElement::Terminate | StreamElement::FlushAndRestart => {
+                std::mem::take(&mut self.ws)
+                    .into_values()
+                    .filter(|w| w.close.is_some())
+                    .map(|w| WindowResult::Item(w.acc.output()))
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn recycle(&self) -> bool {
+        self.ws.is_empty()
+    }
+}
+
+/// Transaction windows where each element belongs to the transaction, or epoch, returned by a
+/// user supplied closure.
+///
+/// This generalizes [`TransactionWindow`] to streams where the elements of more transactions are
+/// interleaved, like the change data capture (CDC) streams of a database, where each change is
+/// tagged with the id of its transaction.
+///
+/// + The `epoch` function returns the epoch of each element. A window is implicitly created
+///   for each epoch, and more windows per partition can be active at the same time.
+/// + The `logic` function is called on each element, before it is passed to the accumulator of
+///   its epoch, and it controls that window like in [`TransactionWindow`]: returning
+///   [`TransactionOp::Commit`] on the end-of-transaction record closes the window of the epoch.
+/// + With [`commit_on_new_epoch`](EpochWindow::commit_on_new_epoch), the epochs are sequential
+///   and the windows of the previous epochs are committed when an element of a greater epoch
+///   is received.
+/// + At the end of the stream, the windows that were not committed are discarded, like the
+///   uncommitted transactions, except for the ones waiting for [`TransactionOp::CommitAfter`].
+///
+/// Unlike [`TransactionWindow`], the stream doesn't need to be timestamped, unless
+/// [`TransactionOp::CommitAfter`] is used.
+///
+/// ## Example
+///
+/// ```
+/// # use renoir::{StreamContext, RuntimeConfig};
+/// # use renoir::operator::source::IteratorSource;
+/// # use renoir::operator::window::{EpochWindow, TransactionOp};
+/// # let mut env = StreamContext::new_local();
+/// // (transaction id, change), a change of 0 marks the end of the transaction
+/// let changes = vec![(1, 10), (2, 5), (1, 20), (2, 0), (1, 0), (3, 7)];
+/// let res = env
+///     .stream_iter(changes.into_iter())
+///     .window_all(EpochWindow::new(
+///         |&(tx, _): &(i32, i32)| tx,
+///         |&(_, change): &(i32, i32)| {
+///             if change == 0 {
+///                 TransactionOp::Commit
+///             } else {
+///                 TransactionOp::Continue
+///             }
+///         },
+///     ))
+///     .fold(0, |sum, (_, change)| *sum += change)
+///     .drop_key()
+///     .collect_vec();
+///
+/// env.execute_blocking();
+///
+/// // the transaction 3 is never committed
+/// assert_eq!(res.get().unwrap(), vec![5, 30]);
+/// ```
+#[derive(Clone)]
+pub struct EpochWindow<T, E, F> {
+    epoch: E,
+    logic: F,
+    commit_on_new_epoch: bool,
+    _t: PhantomData<T>,
+}
+
+impl<T, K, E, F> EpochWindow<T, E, F>
+where
+    E: Fn(&T) -> K,
+    F: Fn(&T) -> TransactionOp,
+{
+    #[inline]
+    pub fn new(epoch: E, logic: F) -> Self {
+        Self {
+            epoch,
+            logic,
+            commit_on_new_epoch: false,
+            _t: PhantomData,
+        }
+    }
+
+    /// Commit the windows of the previous epochs when an element of a greater epoch is
+    /// received, as if the `logic` returned [`TransactionOp::Commit`] for them.
+    #[inline]
+    pub fn commit_on_new_epoch(mut self) -> Self {
+        self.commit_on_new_epoch = true;
+        self
+    }
+}
+
+impl<T: Data, K, E, F> WindowDescription<T> for EpochWindow<T, E, F>
+where
+    K: Ord + Clone + Send + 'static,
+    E: Fn(&T) -> K + Clone + Send + 'static,
+    F: Fn(&T) -> TransactionOp + Clone + Send + 'static,
+{
+    type Manager<A: WindowAccumulator<In = T>> = EpochWindowManager<A, K, E, F>;
+
+    #[inline]
+    fn build<A: WindowAccumulator<In = T>>(&self, accumulator: A) -> Self::Manager<A> {
+        EpochWindowManager {
+            init: accumulator,
+            epoch: self.epoch.clone(),
+            logic: self.logic.clone(),
+            commit_on_new_epoch: self.commit_on_new_epoch,
+            ws: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator::window::aggr::Fold;
+
+    #[test]
+    fn epoch_window_commit_on_new_epoch() {
+        // (epoch, value), the epoch 2 is discarded by the logic
+        let window = EpochWindow::new(
+            |&(epoch, _): &(u64, i32)| epoch,
+            |&(_, v): &(u64, i32)| {
+                if v < 0 {
+                    TransactionOp::Discard
+                } else {
+                    TransactionOp::Continue
+                }
+            },
+        )
+        .commit_on_new_epoch();
+
+        let fold = Fold::new(Vec::new(), |v, (_, el)| v.push(el));
+        let mut manager = window.build(fold);
+
+        let mut received = Vec::new();
+        for el in [(1, 1), (1, 2), (2, 3), (2, -1), (3, 4), (3, 5)] {
+            received.extend(manager.process(StreamElement::Item(el)));
+        }
+        assert_eq!(received, vec![WindowResult::Item(vec![1, 2])]);
+
+        // the last epoch is never committed
+        received.extend(manager.process(StreamElement::Terminate));
+        assert_eq!(received.len(), 1);
+        assert!(manager.recycle());
+    }
+}
+
 // #[cfg(test)]
 // mod tests {
 //     use std::time::Duration;
@@ -5,11 +5,14 @@ use std::fmt::Display;
 use std::marker::PhantomData;
 
 pub use descr::*;
+#[cfg(feature = "timestamp")]
+use serde::{Deserialize, Serialize};
 // pub use aggregator::*;
 // pub use description::*;
 
 use crate::block::{GroupHasherBuilder, OperatorStructure, Replication};
 use crate::operator::{Data, DataKey, ExchangeData, Operator, StreamElement, Timestamp};
+use crate::spill::MemoryTracker;
 use crate::stream::{KeyedStream, Stream, WindowedStream};
 
 mod aggr;
@@ -32,6 +35,10 @@ pub trait WindowDescription<T> {
 /// processing on element at a time. If the operation requires accessing elements in random
 /// order, they should first be collected then the operation can be finizalized on the collection
 ///
+/// The window managers keep one accumulator for each open window and feed it each element as
+/// soon as it is assigned to the window, so the memory used by a window is the size of its
+/// accumulator: the aggregations like `sum`, `count` and `fold` never buffer the elements.
+///
 /// Convention: output will always be called after at least one element has been processed
 pub trait WindowAccumulator: Clone + Send + 'static {
     type In: Data;
@@ -41,8 +48,35 @@ pub trait WindowAccumulator: Clone + Send + 'static {
     fn process(&mut self, el: Self::In);
     /// Finalize the accumulator and produce a result
     fn output(self) -> Self::Out;
+    /// An estimate of the memory used by the accumulator, including the elements it buffers on
+    /// the heap, used to track the windows with the memory budget of the replica
+    #[inline]
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+    /// Called once on a new accumulator, before any element is processed, by the window
+    /// managers whose windows have known boundaries (see [`ContextWindowDescription`])
+    #[cfg(feature = "timestamp")]
+    #[inline]
+    fn set_window(&mut self, _window: WindowContext) {}
+}
+
+/// The boundaries of an event time window, passed to the closures of the aggregations like
+/// [`WindowedStream::fold_with_context`].
+#[cfg(feature = "timestamp")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct WindowContext {
+    /// The first timestamp of the window (included)
+    pub start: Timestamp,
+    /// The end of the window (excluded), also the timestamp of its result
+    pub end: Timestamp,
 }
 
+/// Marker trait for the window descriptions whose managers pass the [`WindowContext`] of each
+/// window to its accumulator, so they can be used with the `*_with_context` aggregations.
+#[cfg(feature = "timestamp")]
+pub trait ContextWindowDescription<T>: WindowDescription<T> {}
+
 #[derive(Clone)]
 pub(crate) struct KeyedWindowManager<Key, In, Out, W: WindowManager> {
     windows: HashMap<Key, W, GroupHasherBuilder>,
@@ -75,6 +109,11 @@ pub trait WindowManager: Clone + Send {
     fn recycle(&self) -> bool {
         false
     }
+    /// An estimate of the memory used by the manager and by its open windows (see
+    /// [`WindowAccumulator::memory`])
+    fn memory(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -137,6 +176,11 @@ where
     manager: KeyedWindowManager<Key, In, Out, W>,
     /// A buffer for storing ready items.
     output_buffer: VecDeque<StreamElement<(Key, Out)>>,
+    /// Tracks the memory of the window managers, if there is a memory budget. They contain the
+    /// user functions, so they cannot be spilled.
+    budget: Option<MemoryTracker>,
+    /// The memory of the window managers and of their keys, updated if there is a budget.
+    memory: usize,
 }
 
 impl<Key, In, Out, Prev, W> Display for WindowOperator<Key, In, Out, Prev, W>
@@ -168,6 +212,7 @@ where
 
     fn setup(&mut self, metadata: &mut crate::ExecutionMetadata) {
         self.prev.setup(metadata);
+        self.budget = metadata.state_backend.memory_tracker(false);
     }
 
     fn next(&mut self) -> StreamElement<(Key, Out)> {
@@ -175,12 +220,20 @@ where
             if let Some(item) = self.output_buffer.pop_front() {
                 return item;
             }
+            if let Some(budget) = &mut self.budget {
+                budget.update(self.memory as u64);
+            }
 
             let el = self.prev.next();
             match el {
                 el @ (StreamElement::Item(_) | StreamElement::Timestamped(_, _)) => {
                     let (key, el) = el.take_key();
                     let key = key.unwrap();
+                    let tracked = self.budget.is_some();
+                    let before = match self.manager.windows.get(&key) {
+                        Some(mgr) if tracked => std::mem::size_of::<Key>() + mgr.memory(),
+                        _ => 0,
+                    };
 
                     let mgr = self
                         .manager
@@ -189,6 +242,10 @@ where
                         .or_insert_with(|| self.manager.init.clone());
 
                     let ret = mgr.process(el);
+                    if tracked {
+                        self.memory =
+                            self.memory + std::mem::size_of::<Key>() + mgr.memory() - before;
+                    }
                     self.output_buffer.extend(
                         ret.into_iter()
                             .map(|e| StreamElement::from(e).add_key(key.clone())),
@@ -198,14 +255,21 @@ where
                 el => {
                     let (_, el) = el.take_key();
 
+                    let tracked = self.budget.is_some();
+                    let mut memory = 0;
                     self.manager.windows.retain(|key, mgr| {
                         let ret = mgr.process(el.clone());
                         self.output_buffer.extend(
                             ret.into_iter()
                                 .map(|e| StreamElement::from(e).add_key(key.clone())),
                         );
-                        !mgr.recycle()
+                        let keep = !mgr.recycle();
+                        if keep && tracked {
+                            memory += std::mem::size_of::<Key>() + mgr.memory();
+                        }
+                        keep
                     });
+                    self.memory = memory;
 
                     // Forward system messages and watermarks
                     let msg = match el {
@@ -221,9 +285,9 @@ where
     }
 
     fn structure(&self) -> crate::block::BlockStructure {
-        self.prev
-            .structure()
-            .add_operator(OperatorStructure::new::<(Key, Out), _>(&self.name))
+        let mut operator = OperatorStructure::new::<(Key, Out), _>(&self.name);
+        operator.unsaved_state = true;
+        self.prev.structure().add_operator(operator)
     }
 }
 
@@ -241,6 +305,8 @@ where
             name,
             manager,
             output_buffer: Default::default(),
+            budget: None,
+            memory: 0,
         }
     }
 }
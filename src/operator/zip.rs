@@ -1,8 +1,9 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Display;
 use std::sync::Arc;
 
 use crate::block::{BlockStructure, OperatorReceiver, OperatorStructure, Replication};
+use crate::network::Coord;
 use crate::operator::iteration::IterationStateLock;
 use crate::operator::start::{BinaryElement, BinaryStartOperator, Start};
 use crate::operator::{ExchangeData, Operator, StreamElement};
@@ -10,11 +11,88 @@ use crate::scheduler::{BlockId, ExecutionMetadata};
 
 use super::source::Source;
 
+/// The number of elements of a side waiting for a counterpart from the same replica, above which
+/// they are paired in order of arrival with the elements waiting on the other side.
+const MAX_STASHED: usize = 1 << 16;
+
+/// What to do with the elements of the longer stream when the two zipped streams have different
+/// lengths.
+#[derive(Clone)]
+pub(crate) enum ZipMismatch<Out1, Out2> {
+    /// Discard them.
+    Truncate,
+    /// Pair them with the values returned by the two functions.
+    Pad(fn() -> Out1, fn() -> Out2),
+    /// Panic.
+    Panic,
+}
+
+/// An element waiting for its counterpart, with its arrival order.
+#[derive(Clone)]
+struct Stashed<T> {
+    arrival: u64,
+    element: StreamElement<T>,
+}
+
+/// The elements of one side waiting for their counterpart, one queue for each replica of the side.
+#[derive(Clone)]
+struct Stash<T> {
+    queues: Vec<VecDeque<Stashed<T>>>,
+    len: usize,
+}
+
+impl<T> Default for Stash<T> {
+    fn default() -> Self {
+        Self {
+            queues: vec![Default::default()],
+            len: 0,
+        }
+    }
+}
+
+impl<T> Stash<T> {
+    fn push(&mut self, queue: usize, stashed: Stashed<T>) {
+        self.queues[queue].push_back(stashed);
+        self.len += 1;
+    }
+
+    fn pop(&mut self, queue: usize) -> Option<StreamElement<T>> {
+        let stashed = self.queues[queue].pop_front()?;
+        self.len -= 1;
+        Some(stashed.element)
+    }
+
+    /// Remove the element that arrived first.
+    fn pop_oldest(&mut self) -> Option<StreamElement<T>> {
+        let queue = (0..self.queues.len())
+            .filter_map(|i| self.queues[i].front().map(|s| (s.arrival, i)))
+            .min()?
+            .1;
+        self.pop(queue)
+    }
+}
+
+/// Pair the elements of two streams by position.
+///
+/// The `i`-th element sent by a replica of the first stream is paired with the `i`-th element sent
+/// by the replica with the same index of the second stream, even if they arrive in a different
+/// order. This keeps the pairing when both the streams are repartitioned in the same way.
+///
+/// If the two sides have a different number of replicas (or one of them is cached) the elements
+/// are paired in order of arrival. The same happens when too many elements wait for a counterpart
+/// from the same replica, and to the ones still unmatched when the streams end: then the remaining
+/// ones are handled according to the [`ZipMismatch`].
 #[derive(Clone)]
 pub struct Zip<Out1: ExchangeData, Out2: ExchangeData> {
     prev: BinaryStartOperator<Out1, Out2>,
-    stash1: VecDeque<StreamElement<Out1>>,
-    stash2: VecDeque<StreamElement<Out2>>,
+    mismatch: ZipMismatch<Out1, Out2>,
+    /// The index of the stash queue of each replica of the two sides.
+    queues: HashMap<Coord, usize>,
+    stash1: Stash<Out1>,
+    stash2: Stash<Out2>,
+    arrivals: u64,
+    /// The pairs of the unmatched elements, emitted when the streams end.
+    buffer: VecDeque<StreamElement<(Out1, Out2)>>,
     prev_block_id1: BlockId,
     prev_block_id2: BlockId,
 }
@@ -46,12 +124,111 @@ impl<Out1: ExchangeData, Out2: ExchangeData> Zip<Out1, Out2> {
                 right_cache,
                 state_lock,
             ),
+            mismatch: ZipMismatch::Truncate,
+            queues: Default::default(),
             stash1: Default::default(),
             stash2: Default::default(),
+            arrivals: 0,
+            buffer: Default::default(),
             prev_block_id1,
             prev_block_id2,
         }
     }
+
+    /// Set what to do with the elements of the longer stream, by default they are discarded.
+    pub(super) fn with_mismatch(mut self, mismatch: ZipMismatch<Out1, Out2>) -> Self {
+        self.mismatch = mismatch;
+        self
+    }
+
+    fn pair(left: StreamElement<Out1>, right: StreamElement<Out2>) -> StreamElement<(Out1, Out2)> {
+        match (left, right) {
+            (StreamElement::Item(item1), StreamElement::Item(item2)) => {
+                StreamElement::Item((item1, item2))
+            }
+            (StreamElement::Timestamped(item1, ts1), StreamElement::Timestamped(item2, ts2)) => {
+                StreamElement::Timestamped((item1, item2), ts1.max(ts2))
+            }
+            _ => panic!("Unsupported mixing of timestamped and non-timestamped items"),
+        }
+    }
+
+    /// The stash queue of the elements sent by `sender`.
+    fn queue(&self, sender: Option<Coord>) -> usize {
+        sender
+            .and_then(|sender| self.queues.get(&sender).copied())
+            .unwrap_or(0)
+    }
+
+    /// Stash an element of the left side, returning its pair if the counterpart has already
+    /// arrived.
+    fn left(&mut self, element: StreamElement<Out1>) -> Option<StreamElement<(Out1, Out2)>> {
+        let queue = self.queue(self.prev.last_sender());
+        if let Some(right) = self.stash2.pop(queue) {
+            return Some(Self::pair(element, right));
+        }
+        let arrival = self.next_arrival();
+        self.stash1.push(queue, Stashed { arrival, element });
+        self.pair_overflow()
+    }
+
+    /// Stash an element of the right side, returning its pair if the counterpart has already
+    /// arrived.
+    fn right(&mut self, element: StreamElement<Out2>) -> Option<StreamElement<(Out1, Out2)>> {
+        let queue = self.queue(self.prev.last_sender());
+        if let Some(left) = self.stash1.pop(queue) {
+            return Some(Self::pair(left, element));
+        }
+        let arrival = self.next_arrival();
+        self.stash2.push(queue, Stashed { arrival, element });
+        self.pair_overflow()
+    }
+
+    fn next_arrival(&mut self) -> u64 {
+        self.arrivals += 1;
+        self.arrivals
+    }
+
+    /// Pair the oldest elements of the two sides if too many are waiting for a counterpart from
+    /// the same replica, i.e. the two sides are partitioned differently.
+    fn pair_overflow(&mut self) -> Option<StreamElement<(Out1, Out2)>> {
+        if self.stash1.len.max(self.stash2.len) <= MAX_STASHED
+            || self.stash1.len == 0
+            || self.stash2.len == 0
+        {
+            return None;
+        }
+        let left = self.stash1.pop_oldest().unwrap();
+        let right = self.stash2.pop_oldest().unwrap();
+        Some(Self::pair(left, right))
+    }
+
+    /// Both the streams have ended: pair the elements still waiting in order of arrival.
+    fn flush_stashed(&mut self) {
+        if self.stash1.len != self.stash2.len && matches!(self.mismatch, ZipMismatch::Panic) {
+            panic!(
+                "The zipped streams have different lengths: {} elements on the left are \
+                unmatched, {} on the right",
+                self.stash1.len, self.stash2.len
+            );
+        }
+        loop {
+            let pair = match (self.stash1.pop_oldest(), self.stash2.pop_oldest()) {
+                (None, None) => break,
+                (Some(left), Some(right)) => Self::pair(left, right),
+                (Some(left), None) => match &self.mismatch {
+                    ZipMismatch::Pad(_, pad) => left.map(|item| (item, pad())),
+                    _ => continue,
+                },
+                (None, Some(right)) => match &self.mismatch {
+                    ZipMismatch::Pad(pad, _) => right.map(|item| (pad(), item)),
+                    _ => continue,
+                },
+            };
+            self.buffer.push_back(pair);
+        }
+        self.arrivals = 0;
+    }
 }
 
 impl<Out1: ExchangeData, Out2: ExchangeData> Operator for Zip<Out1, Out2> {
@@ -59,24 +236,45 @@ impl<Out1: ExchangeData, Out2: ExchangeData> Operator for Zip<Out1, Out2> {
 
     fn setup(&mut self, metadata: &mut ExecutionMetadata) {
         self.prev.setup(metadata);
+
+        // the replicas with the same index of the two sides are paired
+        let receiver = self.prev.receiver();
+        if let (Some(mut left), Some(mut right)) =
+            (receiver.left_replicas(), receiver.right_replicas())
+        {
+            if left.len() == right.len() {
+                left.sort_unstable();
+                right.sort_unstable();
+                for (i, (l, r)) in left.into_iter().zip(right).enumerate() {
+                    self.queues.insert(l, i);
+                    self.queues.insert(r, i);
+                }
+            }
+        }
+        let queues = self.queues.len().div_ceil(2).max(1);
+        self.stash1.queues.resize_with(queues, Default::default);
+        self.stash2.queues.resize_with(queues, Default::default);
     }
 
     #[inline]
     fn next(&mut self) -> StreamElement<(Out1, Out2)> {
-        while self.stash1.is_empty() || self.stash2.is_empty() {
+        loop {
+            if let Some(el) = self.buffer.pop_front() {
+                return el;
+            }
             let item = self.prev.next();
-            match item {
+            let pair = match item {
                 StreamElement::Item(BinaryElement::Left(left)) => {
-                    self.stash1.push_back(StreamElement::Item(left))
+                    self.left(StreamElement::Item(left))
                 }
                 StreamElement::Timestamped(BinaryElement::Left(left), ts) => {
-                    self.stash1.push_back(StreamElement::Timestamped(left, ts))
+                    self.left(StreamElement::Timestamped(left, ts))
                 }
                 StreamElement::Item(BinaryElement::Right(right)) => {
-                    self.stash2.push_back(StreamElement::Item(right))
+                    self.right(StreamElement::Item(right))
                 }
                 StreamElement::Timestamped(BinaryElement::Right(right), ts) => {
-                    self.stash2.push_back(StreamElement::Timestamped(right, ts))
+                    self.right(StreamElement::Timestamped(right, ts))
                 }
                 // ignore LeftEnd | RightEnd
                 StreamElement::Item(_) | StreamElement::Timestamped(_, _) => continue,
@@ -88,33 +286,25 @@ impl<Out1: ExchangeData, Out2: ExchangeData> Operator for Zip<Out1, Out2> {
                 StreamElement::Watermark(_) => return item.map(|_| unreachable!()),
 
                 // Both sides are done, we may still have unmatched items in one of the two side.
-                // Forget them since the stream ended.
                 StreamElement::FlushAndRestart => {
-                    self.stash1.clear();
-                    self.stash2.clear();
-                    return item.map(|_| unreachable!());
+                    self.flush_stashed();
+                    self.buffer.push_back(StreamElement::FlushAndRestart);
+                    continue;
                 }
 
                 StreamElement::FlushBatch | StreamElement::Terminate => {
                     return item.map(|_| unreachable!())
                 }
+            };
+            if let Some(pair) = pair {
+                return pair;
             }
         }
-        let item1 = self.stash1.pop_front().unwrap();
-        let item2 = self.stash2.pop_front().unwrap();
-        match (item1, item2) {
-            (StreamElement::Item(item1), StreamElement::Item(item2)) => {
-                StreamElement::Item((item1, item2))
-            }
-            (StreamElement::Timestamped(item1, ts1), StreamElement::Timestamped(item2, ts2)) => {
-                StreamElement::Timestamped((item1, item2), ts1.max(ts2))
-            }
-            _ => panic!("Unsupported mixing of timestamped and non-timestamped items"),
-        }
     }
 
     fn structure(&self) -> BlockStructure {
         let mut operator = OperatorStructure::new::<(Out1, Out2), _>("Zip");
+        operator.unsaved_state = true;
         operator
             .receivers
             .push(OperatorReceiver::new::<Out1>(self.prev_block_id1));
@@ -134,7 +324,7 @@ impl<Out1: ExchangeData, Out2: ExchangeData> Source for Zip<Out1, Out2> {
 #[cfg(test)]
 mod tests {
     use crate::network::{Coord, NetworkMessage, NetworkSender};
-    use crate::operator::zip::Zip;
+    use crate::operator::zip::{Zip, ZipMismatch};
     use crate::operator::{Operator, StreamElement};
     use crate::test::FakeNetworkTopology;
 
@@ -178,4 +368,54 @@ mod tests {
 
         assert_eq!(zip.next(), StreamElement::Item((3, 300)));
     }
+
+    #[test]
+    fn zip_replicas_and_padding() {
+        let mut t = FakeNetworkTopology::new(2, 2);
+
+        let (coord_l1, sender_l1) = t.senders_mut()[0].pop().unwrap();
+        let (coord_l0, sender_l0) = t.senders_mut()[0].pop().unwrap();
+        let (coord_r1, sender_r1) = t.senders_mut()[1].pop().unwrap();
+        let (coord_r0, sender_r0) = t.senders_mut()[1].pop().unwrap();
+
+        let mut zip =
+            Zip::<i32, i32>::new(coord_l0.block_id, coord_r0.block_id, false, false, None)
+                .with_mismatch(ZipMismatch::Pad(|| -1, || -1));
+        zip.setup(&mut t.metadata());
+
+        let send = |sender: &NetworkSender<i32>, from: Coord, data: Vec<i32>| {
+            let data = data.into_iter().map(StreamElement::Item).collect();
+            sender.send(NetworkMessage::new_batch(data, from)).unwrap();
+        };
+        let flush = |sender: &NetworkSender<i32>, from: Coord| {
+            let data = vec![StreamElement::FlushAndRestart];
+            sender.send(NetworkMessage::new_batch(data, from)).unwrap();
+        };
+
+        // the elements are paired with the ones of the replica with the same index, not by arrival
+        send(&sender_l1, coord_l1, vec![10, 11]);
+        send(&sender_l0, coord_l0, vec![0]);
+        send(&sender_r0, coord_r0, vec![100]);
+
+        assert_eq!(zip.next(), StreamElement::Item((0, 100)));
+
+        send(&sender_r1, coord_r1, vec![110]);
+
+        assert_eq!(zip.next(), StreamElement::Item((10, 110)));
+
+        send(&sender_r0, coord_r0, vec![101, 102]);
+        for (sender, coord) in [
+            (&sender_l0, coord_l0),
+            (&sender_l1, coord_l1),
+            (&sender_r0, coord_r0),
+            (&sender_r1, coord_r1),
+        ] {
+            flush(sender, coord);
+        }
+
+        // the unmatched elements are paired in order of arrival, then padded
+        assert_eq!(zip.next(), StreamElement::Item((11, 101)));
+        assert_eq!(zip.next(), StreamElement::Item((-1, 102)));
+        assert_eq!(zip.next(), StreamElement::FlushAndRestart);
+    }
 }
@@ -0,0 +1,84 @@
+//! The web dashboard served by the metrics endpoint, with the `dashboard` feature.
+//!
+//! The page polls `/graph.json` with the structure of the blocks and the metrics of the replicas,
+//! and draws the job graph: the blocks with their operators, and the connections between them
+//! colored by their throughput and by the backpressure of the receiving replicas.
+
+use serde::Serialize;
+
+use crate::block::BlockStructure;
+use crate::network::CHANNEL_CAPACITY;
+use crate::scheduler::{BlockId, HostId};
+
+use super::metrics::{self, ReplicaSnapshot};
+
+/// The page of the dashboard.
+pub(super) const PAGE: &str = include_str!("dashboard.html");
+
+#[derive(Serialize)]
+struct Graph {
+    host_id: HostId,
+    /// The capacity of the input channels, the maximum of the queue depth.
+    channel_capacity: usize,
+    blocks: Vec<Block>,
+    replicas: Vec<ReplicaSnapshot>,
+}
+
+#[derive(Serialize)]
+struct Block {
+    id: BlockId,
+    structure: BlockStructure,
+}
+
+/// The job graph with the current metrics, in JSON.
+pub(super) fn graph_json() -> String {
+    let graph = Graph {
+        host_id: metrics::host_id(),
+        channel_capacity: CHANNEL_CAPACITY,
+        blocks: metrics::blocks()
+            .into_iter()
+            .map(|(id, structure)| Block { id, structure })
+            .collect(),
+        replicas: metrics::replicas(),
+    };
+    serde_json::to_string(&graph).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::block::{BlockStructure, OperatorStructure};
+    use crate::network::Coord;
+
+    use super::*;
+
+    #[test]
+    fn dashboard_graph() {
+        metrics::serve((Ipv4Addr::LOCALHOST, 0).into(), 0).unwrap();
+        let coord = Coord::new(4343, 0, 0);
+        let structure =
+            BlockStructure::default().add_operator(OperatorStructure::new::<u8, _>("Map"));
+        metrics::register_block(coord, &structure);
+        metrics::items_in(coord, 5);
+        metrics::replica_ended(coord, false);
+
+        let graph: serde_json::Value = serde_json::from_str(&graph_json()).unwrap();
+        let block = graph["blocks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|b| b["id"] == 4343)
+            .unwrap();
+        assert_eq!(block["structure"]["operators"][0]["title"], "Map");
+        let replica = graph["replicas"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|r| r["coord"]["block_id"] == 4343)
+            .unwrap();
+        assert_eq!(replica["items_in"], 5);
+        assert_eq!(replica["status"], "finished");
+        assert!(PAGE.contains("/graph.json"));
+    }
+}
@@ -0,0 +1,499 @@
+//! The metrics of the replicas of this host, exported by an HTTP endpoint in the Prometheus text
+//! exposition format.
+//!
+//! Unlike the profiler, the metrics are always available: they are collected only after the
+//! endpoint is started with [`serve`], and they can be scraped while the job is running. The
+//! names of the metrics are stable:
+//!
+//! - `renoir_items_in_total`: the items received by a replica from the previous blocks;
+//! - `renoir_items_out_total`: the items sent by a replica to the next blocks;
+//! - `renoir_bytes_received_total`, `renoir_bytes_sent_total`: the bytes received and sent by a
+//!   replica through the network;
+//! - `renoir_queue_depth`: the number of messages waiting in the input channel of a replica, the
+//!   last time a message was received;
+//! - `renoir_watermark_lag_seconds`: how far the last watermark of a replica is behind the wall
+//!   clock, assuming the timestamps are milliseconds since the Unix epoch;
+//! - `renoir_operator_items_out_total`: the items emitted by each operator of a replica, also
+//!   labeled with the `operator` position in the block and its `name`;
+//! - `renoir_block_info`: always 1, with the operators of the block as label.
+//!
+//! With the `dashboard` feature the endpoint also serves, at `/`, a page with the job graph and the
+//! live metrics of the replicas.
+//!
+//! All of them are labeled with the `host`, the `block` and the `replica`. The throughput of the
+//! blocks and of the operators is the `rate` of the counters; the metrics registered by
+//! [`Stream::inspect_metrics`](crate::Stream::inspect_metrics) in the environments that are alive
+//! are exported too.
+
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write as _};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::block::{BlockStructure, CoordHasherBuilder};
+use crate::network::Coord;
+use crate::operator::sink::{prometheus, MetricsRegistry};
+use crate::scheduler::{BlockId, HostId};
+
+/// Whether the metrics are collected, i.e. the endpoint has been started.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// The address of the endpoint, once started, with the id of this host.
+static SERVER: OnceLock<Option<(SocketAddr, HostId)>> = OnceLock::new();
+/// The metrics of each replica of this host.
+static REPLICAS: Lazy<DashMap<Coord, ReplicaMetrics, CoordHasherBuilder>> =
+    Lazy::new(Default::default);
+/// The metrics registered by the operators of each environment.
+static ENVIRONMENTS: Lazy<Mutex<Vec<Weak<MetricsRegistry>>>> = Lazy::new(Default::default);
+/// The items emitted by the operators of the replicas of this host, by position in the block.
+static OPERATORS: Lazy<DashMap<(Coord, usize), OperatorMetrics>> = Lazy::new(Default::default);
+/// The structure of each block.
+static BLOCKS: Lazy<DashMap<BlockId, BlockStructure, CoordHasherBuilder>> =
+    Lazy::new(Default::default);
+
+#[derive(Debug)]
+struct ReplicaMetrics {
+    items_in: AtomicU64,
+    items_out: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    queue_depth: AtomicU64,
+    /// The last watermark, `i64::MIN` if there was none.
+    watermark: AtomicI64,
+    /// The `ReplicaStatus` of the replica.
+    status: AtomicU8,
+}
+
+#[derive(Debug)]
+struct OperatorMetrics {
+    name: String,
+    items_out: Arc<AtomicU64>,
+}
+
+/// Whether a replica is still running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ReplicaStatus {
+    Running,
+    Finished,
+    Failed,
+}
+
+/// A point-in-time copy of the metrics of a replica.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ReplicaSnapshot {
+    pub coord: Coord,
+    pub items_in: u64,
+    pub items_out: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub queue_depth: u64,
+    /// How far the last watermark is behind the wall clock, in seconds.
+    pub watermark_lag: Option<f64>,
+    pub status: ReplicaStatus,
+}
+
+impl Default for ReplicaMetrics {
+    fn default() -> Self {
+        Self {
+            items_in: Default::default(),
+            items_out: Default::default(),
+            bytes_in: Default::default(),
+            bytes_out: Default::default(),
+            queue_depth: Default::default(),
+            watermark: AtomicI64::new(i64::MIN),
+            status: AtomicU8::new(ReplicaStatus::Running as u8),
+        }
+    }
+}
+
+#[inline]
+fn update(coord: Coord, f: impl FnOnce(&ReplicaMetrics)) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    match REPLICAS.get(&coord) {
+        Some(metrics) => f(&metrics),
+        None => f(&REPLICAS.entry(coord).or_default()),
+    }
+}
+
+/// Increase the number of items received by the replica `to`.
+#[inline]
+pub(crate) fn items_in(to: Coord, amount: usize) {
+    update(to, |m| {
+        m.items_in.fetch_add(amount as u64, Ordering::Relaxed);
+    });
+}
+
+/// Increase the number of items sent by the replica `from`.
+#[inline]
+pub(crate) fn items_out(from: Coord, amount: usize) {
+    update(from, |m| {
+        m.items_out.fetch_add(amount as u64, Ordering::Relaxed);
+    });
+}
+
+/// Increase the number of bytes received from the network by the replica `to`.
+#[inline]
+pub(crate) fn bytes_in(to: Coord, amount: usize) {
+    update(to, |m| {
+        m.bytes_in.fetch_add(amount as u64, Ordering::Relaxed);
+    });
+}
+
+/// Increase the number of bytes sent to the network by the replica `from`.
+#[inline]
+pub(crate) fn bytes_out(from: Coord, amount: usize) {
+    update(from, |m| {
+        m.bytes_out.fetch_add(amount as u64, Ordering::Relaxed);
+    });
+}
+
+/// The number of bytes sent to the network by the replica `coord` so far.
+pub(crate) fn bytes_sent(coord: Coord) -> u64 {
+    REPLICAS
+        .get(&coord)
+        .map_or(0, |m| m.bytes_out.load(Ordering::Relaxed))
+}
+
+/// Export the metrics registered in an environment, as long as it's alive.
+pub(crate) fn register_environment(registry: &Arc<MetricsRegistry>) {
+    let mut environments = ENVIRONMENTS.lock().unwrap();
+    environments.retain(|e| e.strong_count() > 0);
+    environments.push(Arc::downgrade(registry));
+}
+
+/// Set the number of messages waiting in the input channel of the replica `coord`.
+#[inline]
+pub(crate) fn queue_depth(coord: Coord, depth: usize) {
+    update(coord, |m| {
+        m.queue_depth.store(depth as u64, Ordering::Relaxed)
+    });
+}
+
+/// Set the last watermark of the replica `coord`.
+#[inline]
+pub(crate) fn watermark(coord: Coord, ts: i64) {
+    update(coord, |m| m.watermark.store(ts, Ordering::Relaxed));
+}
+
+/// Set the status of the replica `coord` when it ends.
+pub(crate) fn replica_ended(coord: Coord, failed: bool) {
+    let status = if failed {
+        ReplicaStatus::Failed
+    } else {
+        ReplicaStatus::Finished
+    };
+    update(coord, |m| m.status.store(status as u8, Ordering::Relaxed));
+}
+
+/// Whether the metrics are collected.
+#[inline]
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Register the operator `name` at position `index` in the chain of the replica `coord`, returning
+/// the counter of the items it emits.
+pub(crate) fn register_operator(coord: Coord, index: usize, name: &str) -> Arc<AtomicU64> {
+    OPERATORS
+        .entry((coord, index))
+        .or_insert_with(|| OperatorMetrics {
+            name: name.to_string(),
+            items_out: Default::default(),
+        })
+        .items_out
+        .clone()
+}
+
+/// Remember the structure of the block of `coord`, which has started.
+pub(crate) fn register_block(coord: Coord, structure: &BlockStructure) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    BLOCKS.insert(coord.block_id, structure.clone());
+    REPLICAS
+        .entry(coord)
+        .or_default()
+        .status
+        .store(ReplicaStatus::Running as u8, Ordering::Relaxed);
+}
+
+/// The id of this host, as given to [`serve`].
+pub(crate) fn host_id() -> HostId {
+    SERVER.get().copied().flatten().map_or(0, |(_, host)| host)
+}
+
+/// The structure of the blocks registered, sorted by id.
+pub(crate) fn blocks() -> Vec<(BlockId, BlockStructure)> {
+    let mut blocks: Vec<_> = BLOCKS
+        .iter()
+        .map(|e| (*e.key(), e.value().clone()))
+        .collect();
+    blocks.sort_by_key(|(block_id, _)| *block_id);
+    blocks
+}
+
+/// The metrics of the replicas of this host, sorted by coordinate.
+pub(crate) fn replicas() -> Vec<ReplicaSnapshot> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64);
+    let mut replicas: Vec<_> = REPLICAS
+        .iter()
+        .map(|entry| {
+            let m = entry.value();
+            let watermark = m.watermark.load(Ordering::Relaxed);
+            let status = match m.status.load(Ordering::Relaxed) {
+                s if s == ReplicaStatus::Finished as u8 => ReplicaStatus::Finished,
+                s if s == ReplicaStatus::Failed as u8 => ReplicaStatus::Failed,
+                _ => ReplicaStatus::Running,
+            };
+            ReplicaSnapshot {
+                coord: *entry.key(),
+                items_in: m.items_in.load(Ordering::Relaxed),
+                items_out: m.items_out.load(Ordering::Relaxed),
+                bytes_in: m.bytes_in.load(Ordering::Relaxed),
+                bytes_out: m.bytes_out.load(Ordering::Relaxed),
+                queue_depth: m.queue_depth.load(Ordering::Relaxed),
+                watermark_lag: (watermark != i64::MIN)
+                    .then(|| (now - watermark).max(0) as f64 / 1000.0),
+                status,
+            }
+        })
+        .collect();
+    replicas.sort_by_key(|r| r.coord);
+    replicas
+}
+
+/// Start the collection of the metrics without the endpoint, e.g. for the progress reports of the
+/// workers started by a daemon.
+pub(crate) fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Start the endpoint on `address`, and the collection of the metrics.
+///
+/// The endpoint is started once per process: the following calls return the address of the first
+/// one, or `None` if it could not be started.
+pub(crate) fn serve(address: SocketAddr, host_id: HostId) -> Option<SocketAddr> {
+    let server = SERVER.get_or_init(|| {
+        let listener = match TcpListener::bind(address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::error!("cannot start the metrics endpoint on {address}: {e}");
+                return None;
+            }
+        };
+        let address = listener.local_addr().ok()?;
+        log::info!("serving the metrics at http://{address}/metrics");
+        std::thread::Builder::new()
+            .name("metrics".into())
+            .spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    if let Err(e) = respond(stream) {
+                        log::warn!("failed to respond to a metrics request: {e}");
+                    }
+                }
+            })
+            .unwrap();
+        ENABLED.store(true, Ordering::Relaxed);
+        Some((address, host_id))
+    });
+    server.map(|(address, _)| address)
+}
+
+/// Answer to an HTTP request: `GET /metrics` gets the metrics, `GET /` the dashboard (with the
+/// `dashboard` feature), everything else is not found.
+fn respond(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // skip the headers
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    let mut parts = request.split_whitespace();
+    let text = "text/plain; version=0.0.4";
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", text, render()),
+        #[cfg(feature = "dashboard")]
+        (Some("GET"), Some("/")) => ("200 OK", "text/html", super::dashboard::PAGE.to_string()),
+        #[cfg(feature = "dashboard")]
+        (Some("GET"), Some("/graph.json")) => {
+            ("200 OK", "application/json", super::dashboard::graph_json())
+        }
+        _ => ("404 Not Found", text, String::new()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Render the metrics in the Prometheus text exposition format.
+pub(crate) fn render() -> String {
+    let host_id = host_id();
+    let replicas = replicas();
+
+    let mut out = String::new();
+    let header = |out: &mut String, metric: &str, kind: &str, help: &str| {
+        writeln!(out, "# HELP {metric} {help}").unwrap();
+        writeln!(out, "# TYPE {metric} {kind}").unwrap();
+    };
+    let labels = |c: &Coord| {
+        format!(
+            "host=\"{host_id}\",block=\"{}\",replica=\"{}\"",
+            c.block_id, c.replica_id
+        )
+    };
+    let counters: [fn(&ReplicaSnapshot) -> u64; 5] = [
+        |r| r.items_in,
+        |r| r.items_out,
+        |r| r.bytes_in,
+        |r| r.bytes_out,
+        |r| r.queue_depth,
+    ];
+    for (value, (metric, kind, help)) in counters.into_iter().zip([
+        (
+            "renoir_items_in_total",
+            "counter",
+            "Number of items received by a replica.",
+        ),
+        (
+            "renoir_items_out_total",
+            "counter",
+            "Number of items sent by a replica.",
+        ),
+        (
+            "renoir_bytes_received_total",
+            "counter",
+            "Number of bytes received from the network.",
+        ),
+        (
+            "renoir_bytes_sent_total",
+            "counter",
+            "Number of bytes sent to the network.",
+        ),
+        (
+            "renoir_queue_depth",
+            "gauge",
+            "Number of messages waiting in the input channel.",
+        ),
+    ]) {
+        header(&mut out, metric, kind, help);
+        for replica in &replicas {
+            let labels = labels(&replica.coord);
+            writeln!(out, "{metric}{{{labels}}} {}", value(replica)).unwrap();
+        }
+    }
+    let metric = "renoir_watermark_lag_seconds";
+    header(
+        &mut out,
+        metric,
+        "gauge",
+        "Delay of the last watermark from the wall clock.",
+    );
+    for replica in &replicas {
+        if let Some(lag) = replica.watermark_lag {
+            writeln!(out, "{metric}{{{}}} {lag}", labels(&replica.coord)).unwrap();
+        }
+    }
+    let metric = "renoir_operator_items_out_total";
+    header(
+        &mut out,
+        metric,
+        "counter",
+        "Number of items emitted by an operator.",
+    );
+    let mut operators: Vec<_> = OPERATORS
+        .iter()
+        .map(|e| {
+            (
+                *e.key(),
+                e.name.clone(),
+                e.items_out.load(Ordering::Relaxed),
+            )
+        })
+        .collect();
+    operators.sort_by_key(|(key, _, _)| *key);
+    for ((coord, index), name, items) in operators {
+        let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(
+            out,
+            "{metric}{{{},operator=\"{index}\",name=\"{name}\"}} {items}",
+            labels(&coord)
+        )
+        .unwrap();
+    }
+    let metric = "renoir_block_info";
+    header(&mut out, metric, "gauge", "The operators of a block.");
+    for (block_id, structure) in blocks() {
+        let operators = structure
+            .operators
+            .iter()
+            .map(|op| op.title.as_str())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        let operators = operators.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(
+            out,
+            "{metric}{{host=\"{host_id}\",block=\"{block_id}\",operators=\"{operators}\"}} 1"
+        )
+        .unwrap();
+    }
+    let stream_metrics: Vec<_> = ENVIRONMENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .flat_map(|registry| registry.all())
+        .collect();
+    out.push_str(&prometheus(&stream_metrics));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn metrics_endpoint() {
+        let address = serve((Ipv4Addr::LOCALHOST, 0).into(), 0).unwrap();
+        let coord = Coord::new(4242, 0, 1);
+        items_in(coord, 10);
+        items_out(coord, 7);
+        bytes_out(coord, 128);
+        queue_depth(coord, 3);
+        watermark(coord, 0);
+        register_operator(coord, 2, "Map").fetch_add(5, Ordering::Relaxed);
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        let labels = "{host=\"0\",block=\"4242\",replica=\"1\"}";
+        assert!(response.contains(&format!("renoir_items_in_total{labels} 10\n")));
+        assert!(response.contains(&format!("renoir_items_out_total{labels} 7\n")));
+        assert!(response.contains(&format!("renoir_bytes_sent_total{labels} 128\n")));
+        assert!(response.contains(&format!("renoir_queue_depth{labels} 3\n")));
+        assert!(response.contains(&format!("renoir_watermark_lag_seconds{labels} ")));
+        let labels = "{host=\"0\",block=\"4242\",replica=\"1\",operator=\"2\",name=\"Map\"}";
+        assert!(response.contains(&format!("renoir_operator_items_out_total{labels} 5\n")));
+    }
+}
@@ -8,6 +8,9 @@ use crate::{block::BlockStructure, network::Coord, scheduler::BlockId};
 
 #[cfg(feature = "profiler")]
 mod bucket_profiler;
+#[cfg(feature = "dashboard")]
+mod dashboard;
+pub(crate) mod metrics;
 
 #[cfg(feature = "ssh")]
 pub const TRACING_PREFIX: &str = "__renoir_TRACING_DATA__";
@@ -3,26 +3,28 @@ use std::collections::HashMap;
 use std::fmt::Write as FmtWrite;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::BufReader;
+use std::io::{BufReader, ErrorKind};
 use std::net::TcpStream;
+use std::panic::AssertUnwindSafe;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
-use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64, Engine};
-
-use sha2::Digest;
 #[cfg(feature = "ssh")]
 use ssh2::Session;
 
+use crate::checkpoint::completed_checkpoints;
+use crate::cluster::{self, JobDescription, Response};
 use crate::config::CONFIG_ENV_VAR;
 use crate::config::HOST_ID_ENV_VAR;
-use crate::config::{HostConfig, RemoteConfig};
+use crate::config::{HeartbeatConfig, HostConfig, RemoteConfig};
 use crate::profiler::try_parse_trace;
 use crate::profiler::TracingData;
 use crate::scheduler::HostId;
 
 /// Size of the buffer usedahash to send the executable file via SCP.
 pub(crate) const SCP_BUFFER_SIZE: usize = 512 * 1024;
+/// The line a remote worker prints on its standard output to tell the spawner it's alive.
+const HEARTBEAT_LINE: &str = "__renoir_HEARTBEAT__";
 
 /// Execution results returned by a remote worker.
 struct HostExecutionResult {
@@ -34,34 +36,34 @@ struct HostExecutionResult {
     execution_time: Duration,
     /// Worker process exit code.
     exit_code: i32,
+    /// Whether the job was cancelled on the daemon of the host.
+    cancelled: bool,
 }
 
 /// Compute a cryptographic hash digest of the current executable and return it as a string.
 /// Intended as a discrimintaor for file changes
 fn executable_hash() -> String {
-    let mut hasher = sha2::Sha256::new();
-    let mut buf = vec![0u8; 1 << 20];
-    let mut f = File::open(std::env::current_exe().unwrap()).unwrap();
-
-    loop {
-        match f.read(&mut buf) {
-            Ok(0) => break,
-            Ok(n) => hasher.update(&buf[..n]),
-            Err(e) => panic!("Error reading the current executable! {e}"),
-        }
-    }
-
-    let digest = hasher.finalize();
-    B64.encode(digest)
+    let f = File::open(std::env::current_exe().unwrap()).unwrap();
+    let f = BufReader::with_capacity(1 << 20, f);
+    cluster::executable_hash(f)
+        .unwrap_or_else(|e| panic!("Error reading the current executable! {e}"))
 }
 
 /// Spawn all the remote workers via ssh and wait until all of them complete, after that exit from
 /// the process,
 ///
-/// If this was already a spawned process to nothing.
-pub(crate) fn spawn_remote_workers(config: RemoteConfig) {
+/// If a remote worker fails and the configuration has a restart policy, the whole job is deployed
+/// again, restoring the last completed checkpoint if the checkpoints are enabled. A job cancelled
+/// on a worker daemon is not restarted.
+///
+/// If this was already a spawned process to nothing, except sending the heartbeats to the
+/// spawner if they are enabled.
+pub(crate) fn spawn_remote_workers(mut config: RemoteConfig) {
     // if this process already comes from a the spawner do not spawn again!
     if is_spawned_process() {
+        if let Some(heartbeat) = config.heartbeat {
+            start_heartbeats(heartbeat);
+        }
         return;
     }
 
@@ -69,31 +71,65 @@ pub(crate) fn spawn_remote_workers(config: RemoteConfig) {
     info!("starting {} remote workers", config.hosts.len());
 
     let start = Instant::now();
-    let exe_hash = executable_hash();
-    let mut join_handles = Vec::new();
-    let mut host_dup: HashMap<String, usize> = HashMap::new(); // Used to detect deployments with replicated host
-    for (host_id, host) in config.hosts.iter().enumerate() {
-        let mut exe_uid = exe_hash.clone();
-        let ctr = host_dup.entry(host.address.clone()).or_default();
-        if *ctr > 0 {
-            write!(&mut exe_uid, "-{:02}", *ctr).unwrap();
+    let executable_uids = executable_uids(&config);
+    let mut attempt = 0;
+    let results = loop {
+        let results = deploy(&config, &executable_uids);
+        let failed = results
+            .iter()
+            .enumerate()
+            .filter(|(_, result)| result.as_ref().is_none_or(|r| r.exit_code != 0))
+            .map(|(host_id, _)| host_id)
+            .collect::<Vec<_>>();
+        if failed.is_empty() {
+            break results;
+        }
+        if results.iter().flatten().any(|result| result.cancelled) {
+            warn!("the job was cancelled");
+            break results;
+        }
+        error!("the remote workers of the hosts {failed:?} failed");
+
+        attempt += 1;
+        let Some(delay) = config
+            .restart
+            .as_ref()
+            .and_then(|policy| policy.delay(attempt))
+        else {
+            break results;
+        };
+        // resume from the last checkpoint completed by all the hosts, if any, otherwise from the
+        // same state of the failed execution
+        if let Some(checkpoint) = config.checkpoint.as_mut() {
+            match completed_checkpoints(&checkpoint.path, config.hosts.len()) {
+                Ok(completed) => {
+                    checkpoint.restore = completed.last().copied().or(checkpoint.restore)
+                }
+                Err(e) => warn!("cannot read the completed checkpoints: {e}"),
+            }
+        }
+        match config
+            .checkpoint
+            .as_ref()
+            .and_then(|checkpoint| checkpoint.restore)
+        {
+            Some(id) => {
+                warn!("restarting the job from checkpoint {id} in {delay:?} (attempt {attempt})")
+            }
+            None => warn!("restarting the job from the start in {delay:?} (attempt {attempt})"),
         }
-        *ctr += 1;
+        std::thread::sleep(delay);
+    };
 
-        let config = config.clone();
-        let host = host.clone();
-        let join_handle = std::thread::Builder::new()
-            .name(format!("remote-{host_id:02}",))
-            .spawn(move || remote_worker(host_id as _, host, config, exe_uid))
-            .unwrap();
-        join_handles.push(join_handle);
-    }
     let mut tracing_data = TracingData::default();
     let mut max_execution_time = Duration::default();
     let mut max_sync_time = Duration::default();
     let mut exit_code_or = 0;
-    for join_handle in join_handles {
-        let result = join_handle.join().unwrap();
+    for result in results {
+        let Some(result) = result else {
+            exit_code_or |= 1;
+            continue;
+        };
         max_execution_time = max_execution_time.max(result.execution_time);
         max_sync_time = max_sync_time.max(result.sync_time);
         exit_code_or |= result.exit_code;
@@ -123,6 +159,129 @@ pub(crate) fn spawn_remote_workers(config: RemoteConfig) {
     std::process::exit(exit_code_or);
 }
 
+/// The unique identifier of the executable on each host.
+///
+/// The hosts with the same address get a different identifier, so that their workers don't share
+/// the executable.
+fn executable_uids(config: &RemoteConfig) -> Vec<String> {
+    let exe_hash = executable_hash();
+    let mut host_dup: HashMap<String, usize> = HashMap::new(); // Used to detect deployments with replicated host
+    config
+        .hosts
+        .iter()
+        .map(|host| {
+            let mut exe_uid = exe_hash.clone();
+            let ctr = host_dup.entry(host.address.clone()).or_default();
+            if *ctr > 0 {
+                write!(&mut exe_uid, "-{:02}", *ctr).unwrap();
+            }
+            *ctr += 1;
+            exe_uid
+        })
+        .collect()
+}
+
+/// Deploy the job on all the hosts, with a new job id, and wait until all the remote workers
+/// complete.
+///
+/// When a remote worker fails the workers of the other hosts are stopped, since they would wait
+/// forever for the failed one. The result of a host is `None` if its worker could not be run.
+fn deploy(config: &RemoteConfig, executable_uids: &[String]) -> Vec<Option<HostExecutionResult>> {
+    let job_id = cluster::new_job_id();
+    info!("deploying job {job_id}");
+    let (sender, receiver) = std::sync::mpsc::channel();
+    for (host_id, (host, exe_uid)) in config.hosts.iter().zip(executable_uids).enumerate() {
+        let config = config.clone();
+        let host = host.clone();
+        let exe_uid = exe_uid.clone();
+        let job_id = job_id.clone();
+        let sender = sender.clone();
+        std::thread::Builder::new()
+            .name(format!("remote-{host_id:02}",))
+            .spawn(move || {
+                let result =
+                    std::panic::catch_unwind(AssertUnwindSafe(|| match host.daemon_port {
+                        Some(port) => {
+                            daemon_worker(host_id as _, host, port, config, job_id, exe_uid)
+                        }
+                        None => remote_worker(host_id as _, host, config, exe_uid),
+                    }));
+                let _ = sender.send((host_id, result.ok()));
+            })
+            .unwrap();
+    }
+    drop(sender);
+
+    let mut results = config.hosts.iter().map(|_| None).collect::<Vec<_>>();
+    let mut stopping = false;
+    for (host_id, result) in receiver {
+        let failed = result
+            .as_ref()
+            .is_none_or(|r: &HostExecutionResult| r.exit_code != 0);
+        if failed && !stopping {
+            stopping = true;
+            stop_remote_workers(config, executable_uids, &job_id, host_id);
+        }
+        results[host_id] = result;
+    }
+    results
+}
+
+/// Kill the remote workers of all the hosts except `failed`.
+fn stop_remote_workers(
+    config: &RemoteConfig,
+    executable_uids: &[String],
+    job_id: &str,
+    failed: usize,
+) {
+    for (host_id, (host, exe_uid)) in config.hosts.iter().zip(executable_uids).enumerate() {
+        if host_id == failed {
+            continue;
+        }
+        let host = host.clone();
+        let remote_path = remote_executable_path(exe_uid);
+        let job_id = job_id.to_string();
+        std::thread::Builder::new()
+            .name(format!("stop-{host_id:02}",))
+            .spawn(move || {
+                info!("stopping remote worker for host {}", host_id);
+                if let Some(port) = host.daemon_port {
+                    if let Err(e) = cluster::kill(
+                        (host.address.as_str(), port),
+                        host.daemon_secret.as_deref(),
+                        &job_id,
+                    ) {
+                        warn!("failed to stop the worker of host {host_id} on its daemon: {e}");
+                    }
+                    return;
+                }
+                let mut session = connect(host_id as _, &host);
+                // the pattern doesn't match the command line of the shell running pkill
+                let remote_path = remote_path.to_str().expect("non UTF-8 executable path");
+                let pattern = format!("[/]{}", remote_path.trim_start_matches('/'));
+                let kill = format!("pkill -f {}", shell_escape::escape(pattern.into()));
+                let (_, exit_code) = run_remote_command(&mut session, &kill);
+                log::debug!("pkill on host {} exited with {}", host_id, exit_code);
+            })
+            .unwrap();
+    }
+}
+
+/// Print the heartbeats of this worker on its standard output, which the spawner reads through
+/// the SSH channel, until the process exits.
+fn start_heartbeats(heartbeat: HeartbeatConfig) {
+    std::thread::Builder::new()
+        .name("heartbeat".into())
+        .spawn(move || loop {
+            // the standard output is locked, so the heartbeat is not mixed with other lines
+            if writeln!(std::io::stdout().lock(), "{HEARTBEAT_LINE}").is_err() {
+                break;
+            }
+            std::thread::sleep(heartbeat.interval);
+        })
+        .unwrap();
+}
+
 /// Check if this is a spawned process.
 fn is_spawned_process() -> bool {
     std::env::var_os(HOST_ID_ENV_VAR).is_some()
@@ -151,49 +310,7 @@ fn remote_worker(
     }
     info!("starting remote worker for host {}: {:?}", host_id, host);
 
-    // connect to the ssh server
-    let address = (host.address.as_str(), host.ssh.ssh_port);
-    let stream = TcpStream::connect(address).unwrap_or_else(|e| {
-        panic!(
-            "Failed to connect to remote SSH for host {} at {} port {}: {:?}",
-            host_id, host.address, host.ssh.ssh_port, e
-        )
-    });
-    let mut session = Session::new().unwrap();
-    session.set_tcp_stream(stream);
-    session.handshake().unwrap();
-    log::debug!(
-        "connected to ssh server for host {}: {:?}",
-        host_id,
-        address
-    );
-
-    // try to authenticate
-    let username = host.ssh.username.as_ref().unwrap().as_str();
-    match (host.ssh.password.as_ref(), host.ssh.key_file.as_ref()) {
-        (None, None) => {
-            session.userauth_agent(username).unwrap();
-        }
-        (Some(password), None) => {
-            session
-                .userauth_password(username, password.as_str())
-                .unwrap();
-        }
-        (None, Some(key_file)) => session
-            .userauth_pubkey_file(
-                username,
-                None,
-                key_file.as_path(),
-                host.ssh.key_passphrase.as_deref(),
-            )
-            .unwrap(),
-        (Some(_), Some(_)) => unreachable!("Cannot use both password and key"),
-    }
-    assert!(
-        session.authenticated(),
-        "Failed to authenticate to remote host {host_id} at {address:?}"
-    );
-    log::debug!("authentication succeeded to host {}", host_id);
+    let mut session = connect(host_id, &host);
 
     let sync_start = Instant::now();
 
@@ -201,11 +318,7 @@ fn remote_worker(
     log::debug!("executable located at {}", current_exe.display());
 
     // generate a temporary file on remote host
-    let remote_path = Path::new("/tmp/renoir/").join(format!(
-        "{}-{}",
-        current_exe.file_name().unwrap().to_string_lossy(),
-        executable_uid
-    ));
+    let remote_path = remote_executable_path(&executable_uid);
     log::debug!(
         "executable destination for host {}: {}",
         host_id,
@@ -229,14 +342,33 @@ fn remote_worker(
     let mut channel = session.channel_session().unwrap();
     channel.exec(&command).unwrap();
 
+    // without heartbeats for the timeout the reads fail, the worker is considered dead
+    if let Some(heartbeat) = config.heartbeat {
+        session.set_timeout(heartbeat.timeout.as_millis().try_into().unwrap_or(u32::MAX));
+    }
     let stderr_reader = BufReader::new(channel.stderr());
     let stdout_reader = BufReader::new(&mut channel);
 
     let mut tracing_data = None;
 
-    for line in stdout_reader.lines().map_while(Result::ok) {
-        println!("{host_id}|{line}");
+    for line in stdout_reader.lines() {
+        match line {
+            Ok(line) if line == HEARTBEAT_LINE => {}
+            Ok(line) => println!("{host_id}|{line}"),
+            Err(e) if e.kind() == ErrorKind::TimedOut => {
+                error!("{host_id}|No heartbeat received in time, the host is considered dead");
+                return HostExecutionResult {
+                    tracing: None,
+                    execution_time: execution_start.elapsed(),
+                    sync_time,
+                    exit_code: 1,
+                    cancelled: false,
+                };
+            }
+            Err(_) => break,
+        }
     }
+    session.set_timeout(0);
 
     // copy to stderr the output of the remote process
     for line in stderr_reader.lines().map_while(Result::ok) {
@@ -248,7 +380,12 @@ fn remote_worker(
     }
 
     channel.wait_close().unwrap();
-    let exit_code = channel.exit_status().unwrap();
+    let mut exit_code = channel.exit_status().unwrap();
+    // a process killed by a signal has no exit status
+    if let Some(signal) = channel.exit_signal().unwrap().exit_signal {
+        error!("{}|Killed by signal {}", host_id, signal);
+        exit_code = 1;
+    }
     info!("{}|Exit status: {}", host_id, exit_code);
 
     let execution_time = execution_start.elapsed();
@@ -280,9 +417,151 @@ fn remote_worker(
         execution_time,
         sync_time,
         exit_code,
+        cancelled: false,
+    }
+}
+
+/// Submit the worker to the daemon of the host, in the standalone cluster mode.
+///
+/// Like `remote_worker`, but the daemon receives the executable only if it doesn't have it already,
+/// and the output of the worker comes back on the connection with the daemon. The time spent
+/// sending the executable is part of the execution time.
+fn daemon_worker(
+    host_id: HostId,
+    host: HostConfig,
+    port: u16,
+    mut config: RemoteConfig,
+    job_id: String,
+    executable_uid: String,
+) -> HostExecutionResult {
+    // the workers don't need the secrets of the daemons
+    for host in &mut config.hosts {
+        host.daemon_secret = None;
+    }
+    info!(
+        "submitting the worker of host {} to the daemon at {}:{}",
+        host_id, host.address, port
+    );
+    if host.perf_path.is_some() {
+        warn!("the workers submitted to a daemon don't run under perf (host {host_id})");
+    }
+    let job = JobDescription {
+        job_id,
+        executable_uid,
+        host_id,
+        config: toml::to_string(&config).unwrap(),
+        args: std::env::args().skip(1).collect(),
+        env: ["RUST_LOG", "RUST_BACKTRACE"]
+            .into_iter()
+            .map(|var| (var.to_string(), std::env::var(var).unwrap_or_default()))
+            .chain([("RUST_LOG_STYLE".to_string(), "always".to_string())])
+            .collect(),
+    };
+
+    let execution_start = Instant::now();
+    let mut tracing_data = None;
+    let result = cluster::submit(
+        (host.address.as_str(), port),
+        host.daemon_secret.as_deref(),
+        job,
+        &std::env::current_exe().unwrap(),
+        config.heartbeat.map(|heartbeat| heartbeat.timeout),
+        |line| match line {
+            Response::Stdout(line) if line == HEARTBEAT_LINE => {}
+            Response::Stdout(line) => println!("{host_id}|{line}"),
+            Response::Stderr(line) => match try_parse_trace(&line) {
+                Some(trace) => tracing_data = Some(trace),
+                None => eprintln!("{host_id}|{line}"),
+            },
+            _ => {}
+        },
+    );
+    let cancelled = matches!(result, Ok(None));
+    let exit_code = match result {
+        Ok(Some(exit_code)) => exit_code,
+        Ok(None) => {
+            warn!("{host_id}|The job was cancelled");
+            1
+        }
+        Err(e) if e.kind() == ErrorKind::TimedOut => {
+            error!("{host_id}|No heartbeat received in time, the host is considered dead");
+            1
+        }
+        Err(e) => {
+            error!("{host_id}|Failed to run the worker on the daemon: {e}");
+            1
+        }
+    };
+    info!("{}|Exit status: {}", host_id, exit_code);
+
+    HostExecutionResult {
+        tracing: tracing_data,
+        execution_time: execution_start.elapsed(),
+        sync_time: Duration::default(),
+        exit_code,
+        cancelled,
     }
 }
 
+/// The path of the executable on the remote host.
+fn remote_executable_path(executable_uid: &str) -> PathBuf {
+    let current_exe = std::env::current_exe().unwrap();
+    Path::new("/tmp/renoir/").join(format!(
+        "{}-{}",
+        current_exe.file_name().unwrap().to_string_lossy(),
+        executable_uid
+    ))
+}
+
+/// Connect via SSH to the remote host and authenticate.
+fn connect(host_id: HostId, host: &HostConfig) -> Session {
+    // connect to the ssh server
+    let address = (host.address.as_str(), host.ssh.ssh_port);
+    let stream = TcpStream::connect(address).unwrap_or_else(|e| {
+        panic!(
+            "Failed to connect to remote SSH for host {} at {} port {}: {:?}",
+            host_id, host.address, host.ssh.ssh_port, e
+        )
+    });
+    let mut session = Session::new().unwrap();
+    session.set_tcp_stream(stream);
+    session.handshake().unwrap();
+    log::debug!(
+        "connected to ssh server for host {}: {:?}",
+        host_id,
+        address
+    );
+
+    // try to authenticate
+    let username = host.ssh.username.clone().unwrap_or_else(whoami::username);
+    let username = username.as_str();
+    match (host.ssh.password.as_ref(), host.ssh.key_file.as_ref()) {
+        (None, None) => {
+            session.userauth_agent(username).unwrap();
+        }
+        (Some(password), None) => {
+            session
+                .userauth_password(username, password.as_str())
+                .unwrap();
+        }
+        (None, Some(key_file)) => session
+            .userauth_pubkey_file(
+                username,
+                None,
+                key_file.as_path(),
+                host.ssh.key_passphrase.as_deref(),
+            )
+            .unwrap(),
+        (Some(_), Some(_)) => unreachable!("Cannot use both password and key"),
+    }
+    assert!(
+        session.authenticated(),
+        "Failed to authenticate to remote host {host_id} at {address:?}"
+    );
+    log::debug!("authentication succeeded to host {}", host_id);
+    session
+}
+
 /// Execute a command remotely and return the standard output and the exit code.
 fn run_remote_command(session: &mut Session, command: &str) -> (String, i32) {
     log::debug!("remote command: {}", command);
@@ -3,13 +3,25 @@ use std::collections::HashMap;
 use std::fmt::Write;
 use std::sync::Arc;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
+use parking_lot::Mutex;
+
+use crate::affinity::{CorePlacement, Placement};
 use crate::block::{BatchMode, Block, BlockStructure, JobGraphGenerator, Replication};
-use crate::config::{LocalConfig, RemoteConfig, RuntimeConfig};
-use crate::network::{Coord, NetworkTopology};
+use crate::cancellation::{CancellationToken, ExecutionStatus, PauseHandle};
+use crate::channel::UnboundedReceiver;
+use crate::checkpoint::{
+    assert_saved_state, CheckpointCoordinator, ReplicaCheckpoint, SavepointRequest,
+    SavepointTrigger,
+};
+use crate::cluster;
+use crate::config::{DeliveryGuarantee, LocalConfig, RemoteConfig, RuntimeConfig};
+use crate::network::{Coord, NetworkTopology, ReceiverEndpoint};
 use crate::operator::Operator;
-use crate::profiler::{log_trace, wait_profiler};
-use crate::worker::spawn_worker;
+use crate::profiler::{log_trace, metrics, wait_profiler};
+use crate::state::StateBackendFactory;
+use crate::worker::{setup_worker, StartWorkerFn};
 use crate::CoordUInt;
 
 /// Identifier of a block in the job graph.
@@ -20,7 +32,7 @@ pub type HostId = CoordUInt;
 pub type ReplicaId = CoordUInt;
 
 type BlockInitFn =
-    Box<dyn FnOnce(&mut ExecutionMetadata) -> (JoinHandle<()>, BlockStructure) + Send>;
+    Box<dyn FnOnce(&mut ExecutionMetadata) -> (BlockStructure, StartWorkerFn) + Send>;
 
 /// Metadata used to initialize a block at the start of an execution
 #[derive(Debug)]
@@ -37,6 +49,48 @@ pub struct ExecutionMetadata<'a> {
     pub(crate) network: &'a mut NetworkTopology,
     /// The batching mode to use inside this block.
     pub batch_mode: BatchMode,
+    /// After how long a previous replica that sends nothing is ignored by the watermarks.
+    pub watermark_idle_timeout: Option<Duration>,
+    /// The checkpoints of this replica, if they are enabled.
+    pub(crate) checkpoint: Option<Arc<ReplicaCheckpoint>>,
+    /// Opens the backends of the keyed state of the operators.
+    pub(crate) state_backend: StateBackendFactory,
+    /// The core the thread of the replica is pinned to, if any.
+    pub(crate) placement: Option<Placement>,
+    /// Tells the sources that the job has been cancelled.
+    pub(crate) cancellation: CancellationToken,
+    /// When the operators of the replica have to be woken up while no message arrives.
+    pub(crate) wake_up: WakeUp,
+}
+
+/// The earliest instant an operator of a replica wants to be woken up at, to fire its timers.
+///
+/// While no message arrives the `Start` of the block emits a `FlushBatch` at that instant, then the
+/// operators that still have some timers ask again.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WakeUp(Arc<Mutex<Option<Instant>>>);
+
+impl WakeUp {
+    /// Wake up the replica at `at`, or earlier if another operator asked so.
+    pub(crate) fn request(&self, at: Instant) {
+        let mut deadline = self.0.lock();
+        if deadline.is_none_or(|deadline| at < deadline) {
+            *deadline = Some(at);
+        }
+    }
+
+    /// The instant the replica has to be woken up at, if any.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        *self.0.lock()
+    }
+
+    /// Forget the deadline, if it has passed.
+    pub(crate) fn expire(&self, now: Instant) {
+        let mut deadline = self.0.lock();
+        if deadline.is_some_and(|deadline| deadline <= now) {
+            *deadline = None;
+        }
+    }
 }
 
 /// Information about a block in the job graph.
@@ -52,6 +106,10 @@ struct SchedulerBlockInfo {
     batch_mode: BatchMode,
     /// Whether this block has `NextStrategy::OnlyOne`.
     is_only_one_strategy: bool,
+    /// After how long a previous replica that sends nothing is ignored by the watermarks.
+    watermark_idle_timeout: Option<Duration>,
+    /// Whether the replicas of this block may steal the batches received by each other.
+    work_stealing: bool,
 }
 
 /// The `Scheduler` is the entity that keeps track of all the blocks of the job graph and when the
@@ -69,6 +127,10 @@ pub(crate) struct Scheduler {
     block_init: Vec<(Coord, BlockInitFn)>,
     /// The network topology that keeps track of all the connections inside the execution graph.
     network: NetworkTopology,
+    /// The trigger of the savepoints, with the receiver of its requests, if one was asked.
+    savepoints: Option<(SavepointTrigger, UnboundedReceiver<SavepointRequest>)>,
+    /// The token that stops this execution.
+    cancellation: CancellationToken,
 }
 
 impl Scheduler {
@@ -80,9 +142,29 @@ impl Scheduler {
             block_init: Default::default(),
             network: NetworkTopology::new(config.clone()),
             config,
+            savepoints: None,
+            cancellation: Default::default(),
         }
     }
 
+    /// The trigger of the savepoints of this execution.
+    pub(crate) fn savepoint_trigger(&mut self) -> SavepointTrigger {
+        self.savepoints
+            .get_or_insert_with(SavepointTrigger::new)
+            .0
+            .clone()
+    }
+
+    /// The token that stops this execution.
+    pub(crate) fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// The handle that suspends this execution.
+    pub(crate) fn pause_handle(&self) -> PauseHandle {
+        self.cancellation.pause_handle()
+    }
+
     /// Register a new block inside the scheduler.
     ///
     /// This spawns a worker for each replica of the block in the execution graph and saves its
@@ -123,7 +205,7 @@ impl Scheduler {
             // spawn the actual worker
             self.block_init.push((
                 coord,
-                Box::new(move |metadata| spawn_worker(block, metadata)),
+                Box::new(move |metadata| setup_worker(block, metadata)),
             ));
         }
     }
@@ -153,14 +235,36 @@ impl Scheduler {
         self.network.build();
         self.network.log();
 
-        let mut join = vec![];
+        let mut workers = vec![];
         let mut block_structures = vec![];
         let mut job_graph_generator = JobGraphGenerator::new();
+        // without the checkpoints the requests are dropped, and the savepoints fail
+        let requests = self.savepoints.take().map(|(_, requests)| requests);
+        let checkpoint = self
+            .config
+            .checkpoint()
+            .filter(|checkpoint| checkpoint.guarantee != DeliveryGuarantee::AtMostOnce);
+        let mut checkpoint_coordinator = checkpoint.map(|checkpoint| {
+            CheckpointCoordinator::new(
+                checkpoint.clone(),
+                self.config.host_id().unwrap(),
+                self.config.num_hosts(),
+                requests,
+            )
+        });
+        let mut placement = self.config.pinning().map(CorePlacement::new);
+        if let Some(address) = self.config.metrics_address() {
+            metrics::serve(address, self.config.host_id().unwrap());
+        }
+        cluster::start_progress_reports();
 
         for (coord, init_fn) in self.block_init.drain(..) {
             let block_info = &self.block_info[&coord.block_id];
-            let replicas = block_info.replicas.values().flatten().cloned().collect();
+            let replicas: Vec<_> = block_info.replicas.values().flatten().cloned().collect();
             let global_id = block_info.global_ids[&coord];
+            let checkpoint = checkpoint_coordinator
+                .as_mut()
+                .map(|coordinator| coordinator.replica(coord, &replicas));
             let mut metadata = ExecutionMetadata {
                 coord,
                 replicas,
@@ -168,13 +272,35 @@ impl Scheduler {
                 prev: self.network.prev(coord),
                 network: &mut self.network,
                 batch_mode: block_info.batch_mode,
+                watermark_idle_timeout: block_info.watermark_idle_timeout,
+                checkpoint,
+                state_backend: StateBackendFactory::new(
+                    self.config.state_backend().clone(),
+                    self.config.memory_budget().cloned(),
+                    coord,
+                ),
+                placement: placement
+                    .as_mut()
+                    .and_then(|placement| placement.place(coord)),
+                cancellation: self.cancellation.clone(),
+                wake_up: Default::default(),
             };
-            let (handle, structure) = init_fn(&mut metadata);
-            join.push(handle);
+            let (structure, start) = init_fn(&mut metadata);
+            metrics::register_block(coord, &structure);
+            workers.push(start);
             block_structures.push((coord, structure.clone()));
             job_graph_generator.add_block(coord.block_id, structure);
         }
 
+        // refuse to take checkpoints that could not be restored, before any replica starts
+        if checkpoint.is_some() {
+            assert_saved_state(&block_structures);
+        }
+        let mut join: Vec<_> = workers.into_iter().map(|start| start()).collect();
+
+        // the coordinator ends with the last replica
+        join.extend(checkpoint_coordinator.map(CheckpointCoordinator::spawn));
+
         let job_graph = job_graph_generator.finalize();
         log::debug!("job graph:\n{}", job_graph);
 
@@ -185,7 +311,7 @@ impl Scheduler {
 
     #[cfg(feature = "tokio")]
     /// Start the computation returning the list of handles used to join the workers.
-    pub(crate) async fn start(mut self, block_count: CoordUInt) {
+    pub(crate) async fn start(mut self, block_count: CoordUInt) -> ExecutionStatus {
         debug!("start scheduler: {:?}", self.config);
         self.log_topology();
 
@@ -211,13 +337,46 @@ impl Scheduler {
         join_result.expect("Could not join worker threads");
 
         log_trace(block_structures, wait_profiler());
+        self.cancellation.status()
+    }
+
+    /// Start the computation on a tokio runtime shared with the caller, blocking until it
+    /// completes.
+    ///
+    /// The network tasks and the async operators are spawned on `runtime`, and the replicas run
+    /// inside its context. This must not be called from an async context.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn start_on(
+        self,
+        runtime: &tokio::runtime::Handle,
+        block_count: CoordUInt,
+    ) -> ExecutionStatus {
+        runtime.block_on(self.start(block_count))
     }
 
     /// Start the computation returning the list of handles used to join the workers.
     ///
     /// NOTE: If running with the `tokio` feature enable, this will create a new
     /// tokio runtime.
-    pub(crate) fn start_blocking(mut self, num_blocks: CoordUInt) {
+    pub(crate) fn start_blocking(self, num_blocks: CoordUInt) -> ExecutionStatus {
+        #[cfg(feature = "tokio")]
+        {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .enable_io()
+                .enable_time()
+                .build()
+                .unwrap();
+            self.start_on(runtime.handle(), num_blocks)
+        }
+        #[cfg(not(feature = "tokio"))]
+        {
+            self.start_threads(num_blocks)
+        }
+    }
+
+    /// Start the computation on the worker threads, blocking until it completes.
+    #[cfg(not(feature = "tokio"))]
+    fn start_threads(mut self, num_blocks: CoordUInt) -> ExecutionStatus {
         debug!("start scheduler: {:?}", self.config);
         self.log_topology();
 
@@ -229,40 +388,16 @@ impl Scheduler {
             self.block_info.len(),
         );
 
-        #[cfg(feature = "tokio")]
-        {
-            tokio::runtime::Builder::new_multi_thread()
-                .enable_io()
-                .enable_time()
-                .build()
-                .unwrap()
-                .block_on(async move {
-                    let (join, block_structures) = self.build_all();
-
-                    let (_, join_result) = tokio::join!(
-                        self.network.stop_and_wait(),
-                        tokio::task::spawn_blocking(move || {
-                            for handle in join {
-                                handle.join().unwrap();
-                            }
-                        })
-                    );
-                    join_result.expect("Could not join worker threads");
-                    log_trace(block_structures, wait_profiler());
-                });
-        }
-        #[cfg(not(feature = "tokio"))]
-        {
-            let (join, block_structures) = self.build_all();
-
-            for handle in join {
-                handle.join().unwrap();
-            }
+        let (join, block_structures) = self.build_all();
 
-            self.network.stop_and_wait();
-            let profiler_results = wait_profiler();
-            log_trace(block_structures, profiler_results);
+        for handle in join {
+            handle.join().unwrap();
         }
+
+        self.network.stop_and_wait();
+        let profiler_results = wait_profiler();
+        log_trace(block_structures, profiler_results);
+        self.cancellation.status()
     }
 
     /// Get the ids of the previous blocks of a given block in the job graph
@@ -279,6 +414,16 @@ impl Scheduler {
                 let to = &self.block_info[&to_block_id];
                 // for each pair (from -> to) inside the job graph, connect all the corresponding
                 // jobs of the execution graph
+                let work_stealing = to.work_stealing
+                    && self.config.work_stealing()
+                    && self.config.delivery_guarantee() == DeliveryGuarantee::AtMostOnce
+                    && !fragile;
+                if work_stealing {
+                    for to_coord in to.replicas(self.config.host_id().unwrap()) {
+                        self.network
+                            .set_stealable(ReceiverEndpoint::new(to_coord, *from_block_id));
+                    }
+                }
                 for &from_coord in from.replicas.values().flatten() {
                     let to: Vec<_> = to.replicas.values().flatten().collect();
                     for &to_coord in &to {
@@ -357,6 +502,8 @@ impl Scheduler {
             global_ids: global_ids.into_iter().collect(),
             batch_mode: block.batch_mode,
             is_only_one_strategy: block.is_only_one_strategy,
+            watermark_idle_timeout: block.watermark_idle_timeout,
+            work_stealing: block.work_stealing,
         }
     }
 
@@ -427,6 +574,8 @@ impl Scheduler {
             global_ids,
             batch_mode: block.batch_mode,
             is_only_one_strategy: block.is_only_one_strategy,
+            watermark_idle_timeout: block.watermark_idle_timeout,
+            work_stealing: block.work_stealing,
         }
     }
 }
@@ -0,0 +1,685 @@
+//! Memory budgets for the state of the operators, spilling it to disk when they are exceeded.
+//!
+//! With [`RuntimeConfig::with_memory_budget`](crate::RuntimeConfig::with_memory_budget) each
+//! replica of each block has a [`MemoryBudget`]. The structures holding the state of its operators
+//! report to the budget an estimate of their memory with a [`MemoryTracker`], and when the total
+//! exceeds the limit the largest structure that can be spilled is told to move its content to
+//! disk:
+//!
+//! - the keyed state stored in a [`StateMap`](crate::state::StateMap), i.e. the accumulators of the
+//!   keyed aggregations and the items of the joins with the local hash strategy, is moved to a
+//!   [`SpillBackend`], a file with an index of the values in memory. The items of the joins are
+//!   then appended to the file without reading the spilled lists, and the accumulators updated
+//!   after the spill are moved back to memory until the next one;
+//! - the items of the joins with the local sort-merge strategy are kept in [`SortedRuns`]: they are
+//!   written to disk in runs sorted by key, which are merged when both sides end.
+//!
+//! The windows and the accumulators of the folds without a key are tracked, but never spilled:
+//! the windows contain the user functions of the aggregations, and a single accumulator cannot be
+//! split. Their memory still counts towards the budget, so it can make the other structures of the
+//! replica spill. The windows report the size of the elements they buffer (see
+//! [`WindowAccumulator::memory`](crate::operator::window::WindowAccumulator::memory)).
+//!
+//! The memory of a structure is estimated from the size of its entries, plus the size of the
+//! serialization of a sample of them for what is stored on the heap.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::config::MemoryBudgetConfig;
+use crate::network::Coord;
+use crate::scheduler::ExecutionMetadata;
+use crate::state::StateBackend;
+
+/// Structures smaller than this are never spilled: writing them to disk would free too little
+/// memory. It's lowered for the budgets smaller than 4 times this.
+const MIN_SPILL: u64 = 1 << 20;
+/// A structure reports its memory to the budget only when it changed by at least this much. It's
+/// lowered for the budgets smaller than 16 times this.
+const REPORT_STEP: u64 = 64 << 10;
+/// The size of the serialization of one entry every this many is sampled.
+const SAMPLE_INTERVAL: u64 = 64;
+
+/// The memory budget of the state of the operators of a replica.
+#[derive(Debug)]
+pub(crate) struct MemoryBudget {
+    config: MemoryBudgetConfig,
+    coord: Coord,
+    /// The structures tracked, indexed by the id of their tracker.
+    structures: Mutex<Vec<TrackedStructure>>,
+    /// Whether the budget was exceeded without anything to spill, to warn only once.
+    exhausted: AtomicBool,
+    /// The number of spill files created so far, used to give each one its own name.
+    files: AtomicUsize,
+}
+
+/// The memory of a structure of an operator, as last reported by its tracker.
+#[derive(Debug, Clone, Copy, Default)]
+struct TrackedStructure {
+    bytes: u64,
+    /// Whether the structure can move its content to disk.
+    spillable: bool,
+    /// Whether the structure has been told to spill at its next report.
+    spill: bool,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(config: MemoryBudgetConfig, coord: Coord) -> Self {
+        Self {
+            config,
+            coord,
+            structures: Default::default(),
+            exhausted: AtomicBool::new(false),
+            files: AtomicUsize::new(0),
+        }
+    }
+
+    /// Start tracking a new structure, which can be spilled if `spillable`.
+    pub(crate) fn tracker(self: &Arc<Self>, spillable: bool) -> MemoryTracker {
+        let mut structures = self.structures.lock();
+        structures.push(TrackedStructure {
+            spillable,
+            ..Default::default()
+        });
+        MemoryTracker {
+            budget: self.clone(),
+            id: structures.len() - 1,
+            reported: 0,
+        }
+    }
+
+    fn min_spill(&self) -> u64 {
+        MIN_SPILL.min(self.config.limit / 4)
+    }
+
+    fn report_step(&self) -> u64 {
+        REPORT_STEP.min(self.config.limit / 16).max(1)
+    }
+
+    /// Record that the structure `id` uses `bytes`, returning whether it has to be spilled.
+    fn report(&self, id: usize, bytes: u64) -> bool {
+        let mut structures = self.structures.lock();
+        structures[id].bytes = bytes;
+        if std::mem::take(&mut structures[id].spill) {
+            return true;
+        }
+
+        let total: u64 = structures.iter().map(|s| s.bytes).sum();
+        if total <= self.config.limit {
+            return false;
+        }
+        // the structures already told to spill will free their memory soon
+        let largest = structures
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.spillable && !s.spill && s.bytes >= self.min_spill())
+            .max_by_key(|(_, s)| s.bytes)
+            .map(|(i, _)| i);
+        match largest {
+            Some(i) if i == id => true,
+            Some(i) => {
+                structures[i].spill = true;
+                false
+            }
+            None => {
+                if !self.exhausted.swap(true, Ordering::Relaxed) {
+                    log::warn!(
+                        "{}: the state uses about {total} bytes, more than the memory budget of {} bytes, but none of it can be spilled",
+                        self.coord,
+                        self.config.limit
+                    );
+                }
+                false
+            }
+        }
+    }
+}
+
+/// Reports to the [`MemoryBudget`] of its replica the memory used by a structure of an operator.
+///
+/// A clone of a tracker tracks a new structure.
+#[derive(Debug)]
+pub(crate) struct MemoryTracker {
+    budget: Arc<MemoryBudget>,
+    /// The index of the structure in the budget.
+    id: usize,
+    /// The memory last reported.
+    reported: u64,
+}
+
+impl MemoryTracker {
+    /// Report that the structure uses about `bytes`, returning whether it has to be spilled.
+    ///
+    /// The small changes are not sent to the budget, so this is cheap to call after every update.
+    pub(crate) fn update(&mut self, bytes: u64) -> bool {
+        if bytes != 0 && bytes.abs_diff(self.reported) < self.budget.report_step() {
+            return false;
+        }
+        self.reported = bytes;
+        self.budget.report(self.id, bytes)
+    }
+
+    /// Set whether the structure can be spilled, e.g. it cannot after its content is on disk.
+    pub(crate) fn set_spillable(&mut self, spillable: bool) {
+        let mut structures = self.budget.structures.lock();
+        structures[self.id].spillable = spillable;
+        structures[self.id].spill = false;
+    }
+
+    /// Create a new empty file for spilling the content of the structure.
+    pub(crate) fn spill_file(&self) -> SpillFile {
+        let budget = &self.budget;
+        let n = budget.files.fetch_add(1, Ordering::Relaxed);
+        let name = format!(
+            "{}-{}-{}-{n}.spill",
+            budget.coord.block_id, budget.coord.host_id, budget.coord.replica_id
+        );
+        log::info!(
+            "{}: the memory budget of {} bytes is exceeded, spilling about {} bytes of state to {name}",
+            budget.coord,
+            budget.config.limit,
+            self.reported
+        );
+        SpillFile::create(budget.config.spill_path.join(name))
+    }
+}
+
+impl Clone for MemoryTracker {
+    fn clone(&self) -> Self {
+        let spillable = self.budget.structures.lock()[self.id].spillable;
+        self.budget.tracker(spillable)
+    }
+}
+
+impl Drop for MemoryTracker {
+    fn drop(&mut self) {
+        self.budget.structures.lock()[self.id] = Default::default();
+    }
+}
+
+/// A temporary file with spilled state: it's created empty, and it's removed when dropped.
+#[derive(Debug)]
+pub(crate) struct SpillFile {
+    file: File,
+    path: PathBuf,
+}
+
+impl SpillFile {
+    fn create(path: PathBuf) -> Self {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).unwrap_or_else(|e| {
+                panic!("cannot create the spill directory {}: {e}", dir.display())
+            });
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap_or_else(|e| panic!("cannot create the spill file {}: {e}", path.display()));
+        Self { file, path }
+    }
+
+    /// A new reader of the file from the start, independent from the other ones.
+    fn reader(&self) -> BufReader<File> {
+        let file = File::open(&self.path)
+            .unwrap_or_else(|e| panic!("cannot open the spill file {}: {e}", self.path.display()));
+        BufReader::new(file)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!("cannot remove the spill file {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Estimates the memory used by the entries of a structure.
+///
+/// The size of an entry is the size of its type plus the average size of the serialization of the
+/// sampled entries, as an estimate of what they store on the heap.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EntrySize {
+    /// The number of entries seen so far.
+    seen: u64,
+    /// The number of entries sampled, and the total size of their serialization.
+    samples: u64,
+    sampled_bytes: u64,
+}
+
+impl EntrySize {
+    /// Whether the size of the next entry should be sampled with [`EntrySize::record`].
+    pub(crate) fn sample(&mut self) -> bool {
+        self.seen += 1;
+        self.seen % SAMPLE_INTERVAL == 1
+    }
+
+    /// Record the size of the serialization of a sampled entry.
+    pub(crate) fn record(&mut self, bytes: usize) {
+        self.samples += 1;
+        self.sampled_bytes += bytes as u64;
+    }
+
+    /// The estimated memory used by an entry of type `T`.
+    pub(crate) fn bytes<T>(&self) -> u64 {
+        let heap = self.sampled_bytes.checked_div(self.samples).unwrap_or(0);
+        std::mem::size_of::<T>() as u64 + heap
+    }
+}
+
+/// The size of the serialization of `value`.
+pub(crate) fn encoded_len<V: Serialize>(value: &V) -> usize {
+    bincode::serde::encode_into_std_write(value, &mut std::io::sink(), bincode::config::standard())
+        .expect("failed to serialize the state of the operator")
+}
+
+/// A [`StateBackend`] storing the values in a [`SpillFile`].
+///
+/// The values are appended to the file, and the positions of the value of each key are kept in
+/// memory. An appended value is written after the others too, as another extent of the value of
+/// its key, so appending doesn't read the value. The file is compacted when most of it is taken by
+/// values that were replaced.
+pub(crate) struct SpillBackend {
+    file: SpillFile,
+    /// The offset and the length of each extent of the value of each key, in order.
+    index: HashMap<Vec<u8>, Vec<(u64, usize)>>,
+    /// The length of the file, and the bytes of the values that are still in the index.
+    len: u64,
+    live: u64,
+}
+
+impl SpillBackend {
+    pub(crate) fn new(file: SpillFile) -> Self {
+        Self {
+            file,
+            index: Default::default(),
+            len: 0,
+            live: 0,
+        }
+    }
+
+    fn read(&self, offset: u64, len: usize) -> Vec<u8> {
+        let mut file = &self.file.file;
+        let mut bytes = vec![0; len];
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut bytes))
+            .expect("failed to read the spilled state");
+        bytes
+    }
+
+    /// Append `bytes` at the end of the file, returning their offset.
+    fn write(&mut self, bytes: &[u8]) -> u64 {
+        let mut file = &self.file.file;
+        file.seek(SeekFrom::Start(self.len))
+            .and_then(|_| file.write_all(bytes))
+            .expect("failed to write the spilled state");
+        self.len += bytes.len() as u64;
+        self.len - bytes.len() as u64
+    }
+
+    /// Read all the extents of a value.
+    fn read_extents(&self, extents: &[(u64, usize)]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(extents.iter().map(|(_, len)| len).sum());
+        for &(offset, len) in extents {
+            bytes.extend_from_slice(&self.read(offset, len));
+        }
+        bytes
+    }
+
+    /// Rewrite the file with only the values in the index, each in a single extent.
+    fn compact(&mut self) {
+        let values: Vec<_> = self
+            .index
+            .iter()
+            .map(|(key, extents)| (key.clone(), self.read_extents(extents)))
+            .collect();
+        self.clear();
+        for (key, value) in values {
+            self.put(&key, &value);
+        }
+    }
+}
+
+impl StateBackend for SpillBackend {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        Some(self.read_extents(self.index.get(key)?))
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) {
+        let offset = self.write(value);
+        self.live += value.len() as u64;
+        if let Some(extents) = self.index.insert(key.to_vec(), vec![(offset, value.len())]) {
+            self.live -= extents.iter().map(|&(_, len)| len as u64).sum::<u64>();
+        }
+        if self.len > MIN_SPILL && self.len > 2 * self.live {
+            self.compact();
+        }
+    }
+
+    fn delete(&mut self, key: &[u8]) {
+        if let Some(extents) = self.index.remove(key) {
+            self.live -= extents.iter().map(|&(_, len)| len as u64).sum::<u64>();
+        }
+    }
+
+    fn append(&mut self, key: &[u8], value: &[u8]) {
+        let offset = self.write(value);
+        self.live += value.len() as u64;
+        self.index
+            .entry(key.to_vec())
+            .or_default()
+            .push((offset, value.len()));
+    }
+
+    fn clear(&mut self) {
+        self.index.clear();
+        self.file
+            .file
+            .set_len(0)
+            .expect("failed to write the spilled state");
+        self.len = 0;
+        self.live = 0;
+    }
+}
+
+/// The entries of a structure that are sorted only after they are all inserted, like the sides of
+/// the joins with the local sort-merge strategy.
+///
+/// When the memory budget is exceeded the entries in memory are sorted and written to disk as a
+/// run. After [`SortedRuns::finish`] the entries are popped in ascending order of key, merging the
+/// runs with the entries that are still in memory. Only the values are written to disk: the key of
+/// a value read back is computed again with the keyer of the structure.
+pub(crate) struct SortedRuns<K, V> {
+    /// The entries in memory: after `finish` they are sorted in descending order of key.
+    memory: Vec<(K, V)>,
+    /// The runs on disk, each with the readers of the values that are left and the next entry.
+    runs: Vec<SortedRun<K, V>>,
+    tracker: Option<MemoryTracker>,
+    entry_size: EntrySize,
+}
+
+struct SortedRun<K, V> {
+    file: SpillFile,
+    /// The number of values not read yet.
+    remaining: usize,
+    /// While merging: the reader of the file and the next entry.
+    reader: Option<BufReader<File>>,
+    head: Option<(K, V)>,
+}
+
+impl<K, V> Default for SortedRuns<K, V> {
+    fn default() -> Self {
+        Self {
+            memory: Default::default(),
+            runs: Default::default(),
+            tracker: None,
+            entry_size: Default::default(),
+        }
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for SortedRuns<K, V> {
+    fn clone(&self) -> Self {
+        assert!(
+            self.runs.is_empty(),
+            "the state of an operator cannot be cloned after it has been spilled"
+        );
+        Self {
+            memory: self.memory.clone(),
+            runs: Default::default(),
+            tracker: self.tracker.clone(),
+            entry_size: self.entry_size.clone(),
+        }
+    }
+}
+
+impl<K, V> Debug for SortedRuns<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SortedRuns")
+            .field("memory", &self.memory.len())
+            .field("runs", &self.runs.len())
+            .finish()
+    }
+}
+
+impl<K: Ord, V: Serialize + DeserializeOwned> SortedRuns<K, V> {
+    /// Track the memory of the entries with the budget of the replica, if any.
+    pub(crate) fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        self.tracker = metadata.state_backend.memory_tracker(true);
+    }
+
+    /// Insert a new entry, spilling the entries in memory if the budget is exceeded.
+    pub(crate) fn push(&mut self, key: K, value: V) {
+        if self.entry_size.sample() {
+            self.entry_size.record(encoded_len(&value));
+        }
+        self.memory.push((key, value));
+        let bytes = self.memory.len() as u64 * self.entry_size.bytes::<(K, V)>();
+        if self.tracker.as_mut().is_some_and(|t| t.update(bytes)) {
+            self.spill();
+        }
+    }
+
+    /// Sort the entries in memory and write them to disk as a new run.
+    fn spill(&mut self) {
+        let tracker = self.tracker.as_mut().unwrap();
+        let file = tracker.spill_file();
+        glidesort::sort_by(&mut self.memory, |(k1, _), (k2, _)| k1.cmp(k2));
+        let mut writer = BufWriter::new(&file.file);
+        for (_, value) in &self.memory {
+            bincode::serde::encode_into_std_write(value, &mut writer, bincode::config::standard())
+                .expect("failed to write the spilled state");
+        }
+        writer.flush().expect("failed to write the spilled state");
+        drop(writer);
+
+        self.runs.push(SortedRun {
+            file,
+            remaining: self.memory.len(),
+            reader: None,
+            head: None,
+        });
+        self.memory.clear();
+        self.memory.shrink_to_fit();
+        tracker.update(0);
+    }
+
+    /// Stop inserting and start popping the entries, computing the keys of the spilled values with
+    /// `keyer`.
+    pub(crate) fn finish(&mut self, keyer: impl Fn(&V) -> K) {
+        if self.is_empty() {
+            return;
+        }
+        glidesort::sort_by(&mut self.memory, |(k1, _), (k2, _)| k2.cmp(k1));
+        for run in &mut self.runs {
+            run.reader = Some(run.file.reader());
+            run.advance(&keyer);
+        }
+        if let Some(tracker) = &mut self.tracker {
+            tracker.set_spillable(false);
+        }
+    }
+
+    /// Where the smallest entry is: `None` for the memory, or the index of its run.
+    fn smallest(&self) -> Option<Option<usize>> {
+        let mut smallest = self.memory.last().map(|(key, _)| (key, None));
+        for (i, run) in self.runs.iter().enumerate() {
+            let Some((key, _)) = &run.head else {
+                continue;
+            };
+            let smaller = match smallest {
+                Some((min, _)) => key < min,
+                None => true,
+            };
+            if smaller {
+                smallest = Some((key, Some(i)));
+            }
+        }
+        smallest.map(|(_, source)| source)
+    }
+
+    /// The entry with the smallest key, without removing it.
+    pub(crate) fn peek(&self) -> Option<&(K, V)> {
+        match self.smallest()? {
+            None => self.memory.last(),
+            Some(i) => self.runs[i].head.as_ref(),
+        }
+    }
+
+    /// Remove the entry with the smallest key, computing the keys of the spilled values with
+    /// `keyer`. When the structure is empty it's ready to be filled again.
+    pub(crate) fn pop(&mut self, keyer: impl Fn(&V) -> K) -> Option<(K, V)> {
+        let entry = match self.smallest() {
+            Some(None) => self.memory.pop(),
+            Some(Some(i)) => {
+                let run = &mut self.runs[i];
+                let entry = run.head.take();
+                run.advance(&keyer);
+                entry
+            }
+            None => None,
+        };
+        if self.is_empty() {
+            self.reset();
+        }
+        entry
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.memory.is_empty()
+            && self
+                .runs
+                .iter()
+                .all(|run| run.head.is_none() && run.remaining == 0)
+    }
+
+    /// Remove the runs, which are all popped, so that new entries can be inserted.
+    fn reset(&mut self) {
+        self.runs.clear();
+        if let Some(tracker) = &mut self.tracker {
+            tracker.set_spillable(true);
+            tracker.update(0);
+        }
+    }
+}
+
+impl<K, V: DeserializeOwned> SortedRun<K, V> {
+    /// Read the next entry of the run, if any.
+    fn advance(&mut self, keyer: impl Fn(&V) -> K) {
+        if self.remaining == 0 {
+            self.head = None;
+            self.reader = None;
+            return;
+        }
+        self.remaining -= 1;
+        let reader = self.reader.as_mut().unwrap();
+        let value: V = bincode::serde::decode_from_std_read(reader, bincode::config::standard())
+            .expect("failed to read the spilled state");
+        self.head = Some((keyer(&value), value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(limit: u64) -> (Arc<MemoryBudget>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let config = MemoryBudgetConfig::new(limit, dir.path());
+        (Arc::new(MemoryBudget::new(config, Coord::default())), dir)
+    }
+
+    #[test]
+    fn spill_largest_structure() {
+        let (budget, _dir) = budget(1000);
+        let mut small = budget.tracker(true);
+        let mut large = budget.tracker(true);
+        let mut windows = budget.tracker(false);
+
+        assert!(!small.update(300));
+        assert!(!windows.update(600));
+        // the budget is exceeded, and the largest structure that can be spilled is this one
+        assert!(large.update(800));
+        assert!(!large.update(0));
+
+        // the windows cannot be spilled: the largest structure that can is told to spill at its
+        // next report
+        assert!(!large.update(200));
+        assert!(small.update(400));
+        assert!(!small.update(0));
+    }
+
+    #[test]
+    fn spill_backend() {
+        let (budget, dir) = budget(1000);
+        let mut backend = SpillBackend::new(budget.tracker(true).spill_file());
+        for i in 0..1000u32 {
+            backend.put(&(i % 10).to_be_bytes(), &vec![i as u8; 2000]);
+        }
+        assert_eq!(
+            backend.get(&3u32.to_be_bytes()),
+            Some(vec![(993 % 256) as u8; 2000])
+        );
+        assert!(backend.len <= MIN_SPILL + 2000);
+        backend.delete(&3u32.to_be_bytes());
+        assert_eq!(backend.get(&3u32.to_be_bytes()), None);
+
+        // the appended values are written as extents, without rewriting the previous ones
+        let len = backend.len;
+        for i in 0..100u8 {
+            backend.append(&3u32.to_be_bytes(), &[i; 10]);
+        }
+        assert_eq!(backend.len, len + 1000);
+        let value = backend.get(&3u32.to_be_bytes()).unwrap();
+        assert_eq!(value.len(), 1000);
+        assert_eq!(&value[990..], &[99; 10]);
+        backend.put(&3u32.to_be_bytes(), &[42]);
+        assert_eq!(backend.get(&3u32.to_be_bytes()), Some(vec![42]));
+        backend.clear();
+        assert_eq!(backend.get(&4u32.to_be_bytes()), None);
+
+        drop(backend);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn sorted_runs() {
+        let (budget, dir) = budget(4096);
+        let mut runs = SortedRuns {
+            tracker: Some(budget.tracker(true)),
+            ..Default::default()
+        };
+        for i in 0..1000u64 {
+            let value = (i * 7919) % 1000;
+            runs.push(value / 3, value);
+        }
+        assert!(runs.runs.len() > 1);
+
+        runs.finish(|value| value / 3);
+        assert_eq!(runs.peek().map(|(key, _)| *key), Some(0));
+        let mut values = Vec::new();
+        while let Some((key, value)) = runs.pop(|value| value / 3) {
+            assert_eq!(key, value / 3);
+            values.push(value);
+        }
+        assert!(values.windows(2).all(|w| w[0] / 3 <= w[1] / 3));
+        values.sort_unstable();
+        assert_eq!(values, (0..1000).collect::<Vec<_>>());
+
+        // the runs are removed once popped
+        assert!(runs.is_empty());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,867 @@
+//! Backends storing the keyed state of the operators.
+//!
+//! By default the keyed state is kept in memory, inside the data structures of the operators. With
+//! [`StateBackendConfig::RocksDb`] the state that is serializable is stored instead in a RocksDB
+//! database for each operator, so that it can grow larger than the memory of the host. The
+//! backend is selected with [`RuntimeConfig::with_state_backend`](crate::RuntimeConfig::with_state_backend).
+//!
+//! The operators that use the backend are:
+//!
+//! - the associative keyed aggregations, like [`Stream::group_by_fold`](crate::Stream::group_by_fold)
+//!   and [`Stream::group_by_reduce`](crate::Stream::group_by_reduce), for the accumulators of the
+//!   keys;
+//! - the joins with the local hash strategy, for the items of each side waiting for a match. Each
+//!   item is appended to the list of its key without reading the list back, with a merge
+//!   operator in RocksDB;
+//! - [`KeyedStream::rich_map_with_state`](crate::KeyedStream::rich_map_with_state) and
+//!   [`KeyedStream::process`](crate::KeyedStream::process), for the state of the keys.
+//!
+//! Only the values are stored in the backend: the keys of the state are kept in memory, since
+//! they don't need to be serializable.
+//!
+//! **Note**: the windowed state is never stored in the backend, whatever its configuration, so
+//! the open windows must fit in memory. Their accumulators are not serializable, since they
+//! include the user functions of the aggregations. They still count towards the
+//! [memory budget](crate::RuntimeConfig::with_memory_budget), so they can make the other state of
+//! the replica spill.
+//!
+//! With a [memory budget](crate::RuntimeConfig::with_memory_budget) the state kept in memory is
+//! tracked, and the values of the largest state are moved to a spill file on disk when the budget
+//! of the replica is exceeded. They are kept there until the state is drained or cleared.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::block::{group_by_hash, key_group_replica, GroupHasherBuilder};
+use crate::config::{MemoryBudgetConfig, StateBackendConfig};
+use crate::network::Coord;
+use crate::operator::DataKey;
+use crate::scheduler::ExecutionMetadata;
+use crate::spill::{EntrySize, MemoryBudget, MemoryTracker, SpillBackend};
+
+/// A key-value store for the serialized state of an operator.
+///
+/// Each operator of each replica has its own store, so the keys don't need to be unique across
+/// the operators.
+pub trait StateBackend: Send {
+    /// The value stored with `key`, if any.
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>>;
+    /// Store `value` with `key`, replacing the previous value.
+    fn put(&mut self, key: &[u8], value: &[u8]);
+    /// Remove the value stored with `key`, if any.
+    fn delete(&mut self, key: &[u8]);
+    /// Remove all the values.
+    fn clear(&mut self);
+    /// Append `value` to the bytes stored with `key`, storing it if missing.
+    ///
+    /// By default the value is read and written again: the backends that can append without
+    /// reading it should override this.
+    fn append(&mut self, key: &[u8], value: &[u8]) {
+        let mut bytes = self.get(key).unwrap_or_default();
+        bytes.extend_from_slice(value);
+        self.put(key, &bytes);
+    }
+}
+
+/// A [`StateBackend`] storing the values in a RocksDB database.
+///
+/// The database is temporary: it's created empty, and it's removed when the backend is dropped.
+#[cfg(feature = "rocksdb")]
+pub struct RocksDbBackend {
+    /// The database, taken only when the backend is dropped.
+    db: Option<rocksdb::DB>,
+    path: std::path::PathBuf,
+}
+
+#[cfg(feature = "rocksdb")]
+impl RocksDbBackend {
+    /// Create a new database at `path`, replacing the one that is already there, if any.
+    pub fn open(path: impl Into<std::path::PathBuf>) -> Self {
+        let path = path.into();
+        let mut options = rocksdb::Options::default();
+        options.create_if_missing(true);
+        options.set_merge_operator_associative("renoir-append", concat_merge);
+        let _ = rocksdb::DB::destroy(&options, &path);
+        let db = rocksdb::DB::open(&options, &path)
+            .unwrap_or_else(|e| panic!("cannot open the RocksDB state at {}: {e}", path.display()));
+        Self { db: Some(db), path }
+    }
+
+    fn db(&self) -> &rocksdb::DB {
+        self.db.as_ref().unwrap()
+    }
+}
+
+/// The merge operator of [`RocksDbBackend::append`]: the operands are concatenated to the value.
+#[cfg(feature = "rocksdb")]
+fn concat_merge(
+    _key: &[u8],
+    value: Option<&[u8]>,
+    operands: &rocksdb::MergeOperands,
+) -> Option<Vec<u8>> {
+    let mut value = value.map(<[u8]>::to_vec).unwrap_or_default();
+    for operand in operands {
+        value.extend_from_slice(operand);
+    }
+    Some(value)
+}
+
+#[cfg(feature = "rocksdb")]
+impl StateBackend for RocksDbBackend {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.db()
+            .get(key)
+            .expect("failed to read from the RocksDB state")
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) {
+        self.db()
+            .put(key, value)
+            .expect("failed to write to the RocksDB state");
+    }
+
+    fn delete(&mut self, key: &[u8]) {
+        self.db()
+            .delete(key)
+            .expect("failed to write to the RocksDB state");
+    }
+
+    fn clear(&mut self) {
+        let mut batch = rocksdb::WriteBatch::default();
+        for entry in self.db().iterator(rocksdb::IteratorMode::Start) {
+            let (key, _) = entry.expect("failed to read from the RocksDB state");
+            batch.delete(key);
+        }
+        self.db()
+            .write(batch)
+            .expect("failed to write to the RocksDB state");
+    }
+
+    fn append(&mut self, key: &[u8], value: &[u8]) {
+        self.db()
+            .merge(key, value)
+            .expect("failed to write to the RocksDB state");
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+impl Drop for RocksDbBackend {
+    fn drop(&mut self) {
+        // the database must be closed before removing it
+        drop(self.db.take());
+        if let Err(e) = rocksdb::DB::destroy(&rocksdb::Options::default(), &self.path) {
+            log::warn!(
+                "cannot remove the RocksDB state at {}: {e}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+/// Opens the state backends of the operators of a replica, and tracks the memory of the state
+/// kept in memory.
+#[derive(Debug, Clone)]
+pub(crate) struct StateBackendFactory {
+    config: StateBackendConfig,
+    #[cfg(feature = "rocksdb")]
+    coord: Coord,
+    /// The number of backends opened so far, used to give each operator its own store.
+    #[cfg(feature = "rocksdb")]
+    opened: usize,
+    /// The memory budget of the replica, if any.
+    budget: Option<Arc<MemoryBudget>>,
+}
+
+impl StateBackendFactory {
+    pub(crate) fn new(
+        config: StateBackendConfig,
+        memory_budget: Option<MemoryBudgetConfig>,
+        coord: Coord,
+    ) -> Self {
+        Self {
+            config,
+            #[cfg(feature = "rocksdb")]
+            coord,
+            #[cfg(feature = "rocksdb")]
+            opened: 0,
+            budget: memory_budget.map(|budget| Arc::new(MemoryBudget::new(budget, coord))),
+        }
+    }
+
+    /// Track a structure kept in memory with the budget of the replica, if any. The structure can
+    /// be spilled to disk if `spillable`.
+    pub(crate) fn memory_tracker(&self, spillable: bool) -> Option<MemoryTracker> {
+        self.budget.as_ref().map(|budget| budget.tracker(spillable))
+    }
+
+    /// Open the backend of the next operator, or `None` if the state is kept in memory.
+    fn open(&mut self) -> Option<Box<dyn StateBackend>> {
+        match &self.config {
+            StateBackendConfig::Memory => None,
+            #[cfg(feature = "rocksdb")]
+            StateBackendConfig::RocksDb { path } => {
+                let name = format!(
+                    "{}-{}-{}-{}",
+                    self.coord.block_id, self.coord.host_id, self.coord.replica_id, self.opened
+                );
+                self.opened += 1;
+                Some(Box::new(RocksDbBackend::open(path.join(name))))
+            }
+            #[cfg(not(feature = "rocksdb"))]
+            StateBackendConfig::RocksDb { .. } => {
+                panic!("The RocksDB state backend requires the `rocksdb` feature.")
+            }
+        }
+    }
+}
+
+/// A backend with the key in the backend of the value of each key.
+type BackendSlots<K> = (Box<dyn StateBackend>, HashMap<K, u64, GroupHasherBuilder>);
+/// The functions to serialize and deserialize a value.
+type Codec<V> = (fn(&V) -> Vec<u8>, fn(&[u8]) -> V);
+
+/// The keyed state of an operator: a map that is stored in memory or in the configured
+/// [`StateBackend`].
+///
+/// The values are moved to the backend in `setup`, only if they are serializable (see
+/// [`StateMap::persistent`]). The map has to be cloned before `setup`. When the state is kept in
+/// memory, the serializable values are moved to a [`SpillBackend`] if the memory budget of the
+/// replica is exceeded. After that the spilled values that are updated are moved back to memory,
+/// and the items pushed to the spilled lists are appended to the file, so that the values are
+/// not read and written again at each update.
+pub(crate) struct StateMap<K, V> {
+    /// The entries kept in memory: all of them without a backend, the ones updated since the last
+    /// spill with a spill file.
+    memory: HashMap<K, V, GroupHasherBuilder>,
+    /// The backend with the values, if any, and the key in the backend of the value of each key.
+    backend: Option<BackendSlots<K>>,
+    /// The next key to use in the backend.
+    next_slot: u64,
+    /// How to (de)serialize the values, if they can be stored in a backend.
+    codec: Option<Codec<V>>,
+    /// Tracks the memory of the entries, if the state is kept in memory and there is a budget.
+    budget: Option<MemoryTracker>,
+    entry_size: EntrySize,
+    /// Whether the backend is a spill file, used until the map is emptied.
+    spilled: bool,
+    /// Whether the values are lists encoded so that an item can be appended to them, see
+    /// [`StateMap::list`].
+    list: bool,
+}
+
+fn encode<V: Serialize>(value: &V) -> Vec<u8> {
+    bincode::serde::encode_to_vec(value, bincode::config::standard())
+        .expect("failed to serialize the state of the operator")
+}
+
+fn decode<V: DeserializeOwned>(bytes: &[u8]) -> V {
+    bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+        .expect("failed to deserialize the state of the operator")
+        .0
+}
+
+/// Serialize an item of a list, prefixed by its length so that the items can be concatenated.
+fn encode_item<T: Serialize>(item: &T) -> Vec<u8> {
+    let bytes = encode(item);
+    let mut encoded = Vec::with_capacity(4 + bytes.len());
+    encoded.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    encoded.extend_from_slice(&bytes);
+    encoded
+}
+
+// it's the encoder of the values of a `StateMap<K, Vec<T>>`
+#[allow(clippy::ptr_arg)]
+fn encode_list<T: Serialize>(items: &Vec<T>) -> Vec<u8> {
+    items.iter().flat_map(encode_item).collect()
+}
+
+fn decode_list<T: DeserializeOwned>(mut bytes: &[u8]) -> Vec<T> {
+    let mut items = Vec::new();
+    while let Some((len, rest)) = bytes.split_first_chunk::<4>() {
+        let (item, rest) = rest.split_at(u32::from_le_bytes(*len) as usize);
+        items.push(decode(item));
+        bytes = rest;
+    }
+    items
+}
+
+impl<K, V> Default for StateMap<K, V> {
+    fn default() -> Self {
+        Self {
+            memory: Default::default(),
+            backend: None,
+            next_slot: 0,
+            codec: None,
+            budget: None,
+            entry_size: Default::default(),
+            spilled: false,
+            list: false,
+        }
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for StateMap<K, V> {
+    fn clone(&self) -> Self {
+        assert!(
+            self.backend.is_none(),
+            "the state of an operator cannot be cloned after the setup"
+        );
+        Self {
+            memory: self.memory.clone(),
+            backend: None,
+            next_slot: self.next_slot,
+            codec: self.codec,
+            budget: self.budget.clone(),
+            entry_size: self.entry_size.clone(),
+            spilled: false,
+            list: self.list,
+        }
+    }
+}
+
+impl<K, V> Debug for StateMap<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StateMap")
+            .field("len", &self.len())
+            .field("backend", &self.backend.is_some())
+            .field("spilled", &self.spilled)
+            .finish()
+    }
+}
+
+impl<K, V: Serialize + DeserializeOwned> StateMap<K, V> {
+    /// Allow storing the values in the configured backend.
+    pub(crate) fn persistent(mut self) -> Self {
+        self.codec = Some((encode::<V>, decode::<V>));
+        self
+    }
+}
+
+impl<K, T: Serialize + DeserializeOwned> StateMap<K, Vec<T>> {
+    /// Allow storing the lists in the configured backend, appending the items with
+    /// [`StateMap::push`] without reading the lists back.
+    pub(crate) fn list(mut self) -> Self {
+        self.codec = Some((encode_list::<T>, decode_list::<T>));
+        self.list = true;
+        self
+    }
+}
+
+impl<K, V> StateMap<K, V> {
+    pub(crate) fn len(&self) -> usize {
+        match &self.backend {
+            Some((_, slots)) => slots.len() + self.memory.len(),
+            None => self.memory.len(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: DataKey, V> StateMap<K, V> {
+    /// Open the configured backend, if the values can be stored in it, otherwise track the memory
+    /// of the entries with the budget of the replica.
+    pub(crate) fn setup(&mut self, metadata: &mut ExecutionMetadata) {
+        if self.codec.is_some() {
+            if let Some(backend) = metadata.state_backend.open() {
+                let entries = std::mem::take(&mut self.memory);
+                self.backend = Some((backend, Default::default()));
+                self.extend(entries);
+                return;
+            }
+        }
+        self.budget = metadata.state_backend.memory_tracker(self.codec.is_some());
+    }
+
+    /// Report the memory used by the entries to the budget, spilling them if needed.
+    fn track_memory(&mut self) {
+        // the keys of the spilled values are still in memory
+        let spilled = self.backend.as_ref().map_or(0, |(_, slots)| slots.len());
+        let bytes = self.memory.len() as u64 * self.entry_size.bytes::<(K, V)>()
+            + spilled as u64 * std::mem::size_of::<(K, u64)>() as u64;
+        if self
+            .budget
+            .as_mut()
+            .is_some_and(|budget| budget.update(bytes))
+        {
+            self.spill();
+        }
+    }
+
+    /// Move the values in memory to a spill file, since the memory budget of the replica is
+    /// exceeded.
+    fn spill(&mut self) {
+        let budget = self.budget.as_mut().unwrap();
+        // only the keys are left in memory, until a spilled value is updated
+        budget.set_spillable(false);
+        if self.backend.is_none() {
+            let backend = SpillBackend::new(budget.spill_file());
+            self.backend = Some((Box::new(backend), Default::default()));
+            self.spilled = true;
+        }
+        let (encode, _) = self.codec.unwrap();
+        let (backend, slots) = self.backend.as_mut().unwrap();
+        for (key, value) in self.memory.drain() {
+            let slot = Self::slot(slots, &mut self.next_slot, key);
+            backend.put(&slot.to_be_bytes(), &encode(&value));
+        }
+    }
+
+    /// Keep the entries in memory again, after the spilled ones are removed.
+    fn unspill(&mut self) {
+        if !self.spilled {
+            return;
+        }
+        self.backend = None;
+        self.spilled = false;
+        if let Some(budget) = &mut self.budget {
+            budget.set_spillable(true);
+            budget.update(0);
+        }
+    }
+
+    /// Update the value of `key` with `f`, inserting it with `init` if missing.
+    pub(crate) fn update<R>(
+        &mut self,
+        key: K,
+        init: impl FnOnce() -> V,
+        f: impl FnOnce(&mut V) -> R,
+    ) -> R {
+        let mut spilled = None;
+        let mut unspill = false;
+        match &mut self.backend {
+            Some((backend, slots)) if !self.spilled => {
+                let (encode, decode) = self.codec.unwrap();
+                let slot = Self::slot(slots, &mut self.next_slot, key).to_be_bytes();
+                let mut value = match backend.get(&slot) {
+                    Some(bytes) => decode(&bytes),
+                    None => init(),
+                };
+                let result = f(&mut value);
+                backend.put(&slot, &encode(&value));
+                return result;
+            }
+            // a spilled value is moved back to memory, so that the following updates don't read
+            // and write it again: it's spilled again with the others if the budget is exceeded
+            Some((backend, slots)) => {
+                if let Some(slot) = slots.remove(&key) {
+                    let (_, decode) = self.codec.unwrap();
+                    let slot = slot.to_be_bytes();
+                    spilled = backend.get(&slot).map(|bytes| decode(&bytes));
+                    backend.delete(&slot);
+                    unspill = slots.is_empty();
+                }
+                if self.memory.is_empty() {
+                    if let Some(budget) = &mut self.budget {
+                        budget.set_spillable(true);
+                    }
+                }
+            }
+            None => {}
+        }
+        if unspill {
+            self.unspill();
+        }
+        let value = self
+            .memory
+            .entry(key)
+            .or_insert_with(|| spilled.unwrap_or_else(init));
+        let result = f(value);
+        if self.budget.is_some() && self.entry_size.sample() {
+            let bytes = self.codec.map_or(0, |(encode, _)| encode(value).len());
+            self.entry_size.record(bytes);
+        }
+        if self.budget.is_some() {
+            self.track_memory();
+        }
+        result
+    }
+
+    /// The slot in the backend of the value of `key`, assigning a new one if missing.
+    fn slot(slots: &mut HashMap<K, u64, GroupHasherBuilder>, next_slot: &mut u64, key: K) -> u64 {
+        *slots.entry(key).or_insert_with(|| {
+            *next_slot += 1;
+            *next_slot - 1
+        })
+    }
+
+    /// Call `f` with the value of `key`, if any.
+    pub(crate) fn with<R>(&self, key: &K, f: impl FnOnce(&V) -> R) -> Option<R> {
+        if let Some(value) = self.memory.get(key) {
+            return Some(f(value));
+        }
+        let (backend, slots) = self.backend.as_ref()?;
+        let (_, decode) = self.codec.unwrap();
+        let bytes = backend.get(&slots.get(key)?.to_be_bytes())?;
+        Some(f(&decode(&bytes)))
+    }
+
+    /// Set the value of `key`, replacing the previous one.
+    pub(crate) fn insert(&mut self, key: K, value: V) {
+        let value = Cell::new(Some(value));
+        self.update(
+            key,
+            || value.take().unwrap(),
+            |v| {
+                if let Some(value) = value.take() {
+                    *v = value;
+                }
+            },
+        );
+    }
+
+    /// Remove the value of `key`, returning it if present.
+    pub(crate) fn remove(&mut self, key: &K) -> Option<V> {
+        if let Some(value) = self.memory.remove(key) {
+            if self.budget.is_some() {
+                self.track_memory();
+            }
+            return Some(value);
+        }
+        let (backend, slots) = self.backend.as_mut()?;
+        let slot = slots.remove(key)?.to_be_bytes();
+        let empty = slots.is_empty();
+        let (_, decode) = self.codec.unwrap();
+        let value = backend.get(&slot).map(|bytes| decode(&bytes));
+        backend.delete(&slot);
+        if self.spilled {
+            if empty {
+                self.unspill();
+            } else {
+                self.track_memory();
+            }
+        }
+        value
+    }
+
+    /// Insert all the entries of `entries`, replacing the values of the keys already present.
+    pub(crate) fn extend(&mut self, entries: impl IntoIterator<Item = (K, V)>) {
+        for (key, value) in entries {
+            self.insert(key, value);
+        }
+    }
+
+    /// Remove all the entries, returning them in arbitrary order.
+    ///
+    /// The values in a backend are read one at a time, while the entries are consumed.
+    pub(crate) fn drain(&mut self) -> Box<dyn Iterator<Item = (K, V)> + '_> {
+        match self.backend.take() {
+            Some((backend, slots)) => {
+                let (_, decode) = self.codec.unwrap();
+                // a spill file is dropped after reading it, the other backends are reused
+                let restore = if self.spilled {
+                    self.unspill();
+                    None
+                } else {
+                    Some(&mut self.backend)
+                };
+                Box::new(self.memory.drain().chain(BackendDrain {
+                    backend: Some(backend),
+                    slots: slots.into_iter(),
+                    decode,
+                    restore,
+                }))
+            }
+            None => {
+                if let Some(budget) = &mut self.budget {
+                    budget.update(0);
+                }
+                Box::new(self.memory.drain())
+            }
+        }
+    }
+
+    /// Remove all the entries.
+    pub(crate) fn clear(&mut self) {
+        if let Some((backend, slots)) = &mut self.backend {
+            slots.clear();
+            backend.clear();
+            self.unspill();
+        }
+        if let Some(budget) = &mut self.budget {
+            budget.update(0);
+        }
+        self.memory.clear();
+    }
+}
+
+impl<K: DataKey, T: Serialize> StateMap<K, Vec<T>> {
+    /// Append `item` to the list of `key`, inserting an empty list if missing.
+    ///
+    /// With a map created by [`StateMap::list`] the item is appended to the value in the backend,
+    /// instead of reading and writing again the whole list.
+    pub(crate) fn push(&mut self, key: K, item: T) {
+        match &mut self.backend {
+            // the spilled lists moved back to memory are updated there
+            Some((backend, slots)) if self.list && !self.memory.contains_key(&key) => {
+                let slot = Self::slot(slots, &mut self.next_slot, key);
+                backend.append(&slot.to_be_bytes(), &encode_item(&item));
+                if self.spilled {
+                    self.track_memory();
+                }
+            }
+            _ => self.update(key, Vec::new, |items| items.push(item)),
+        }
+    }
+}
+
+/// The entries drained from a backend, whose values are read while they are consumed.
+struct BackendDrain<'a, K, V> {
+    backend: Option<Box<dyn StateBackend>>,
+    slots: std::collections::hash_map::IntoIter<K, u64>,
+    decode: fn(&[u8]) -> V,
+    /// Where to put back the backend, emptied, when the entries are dropped.
+    restore: Option<&'a mut Option<BackendSlots<K>>>,
+}
+
+impl<K, V> Iterator for BackendDrain<'_, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let backend = self.backend.as_ref().unwrap();
+        for (key, slot) in self.slots.by_ref() {
+            if let Some(bytes) = backend.get(&slot.to_be_bytes()) {
+                return Some((key, (self.decode)(&bytes)));
+            }
+        }
+        None
+    }
+}
+
+impl<K, V> Drop for BackendDrain<'_, K, V> {
+    fn drop(&mut self) {
+        let mut backend = self.backend.take().unwrap();
+        backend.clear();
+        if let Some(restore) = self.restore.take() {
+            *restore = Some((backend, Default::default()));
+        }
+    }
+}
+
+impl<K: DataKey, V> StateMap<K, V> {
+    /// Merge the snapshots of a keyed state taken with a different number of replicas, keeping
+    /// only the keys processed by the replica `index` out of `replicas`.
+    pub(crate) fn rescale(
+        snapshots: impl IntoIterator<Item = Self>,
+        index: usize,
+        replicas: usize,
+    ) -> Self {
+        let mut memory = HashMap::default();
+        for snapshot in snapshots {
+            for (key, value) in snapshot.memory {
+                if key_group_replica(group_by_hash(&key), replicas) == index {
+                    let previous = memory.insert(key, value);
+                    assert!(
+                        previous.is_none(),
+                        "a key is in the state of multiple replicas: it cannot be redistributed"
+                    );
+                }
+            }
+        }
+        Self {
+            memory,
+            ..Default::default()
+        }
+    }
+}
+
+/// The state is serialized as a map, for saving it in the checkpoints.
+impl<K: Serialize, V: Serialize> Serialize for StateMap<K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in &self.memory {
+            map.serialize_entry(key, value)?;
+        }
+        if let Some((backend, slots)) = &self.backend {
+            let (_, decode) = self.codec.unwrap();
+            for (key, slot) in slots {
+                if let Some(bytes) = backend.get(&slot.to_be_bytes()) {
+                    map.serialize_entry(key, &decode(&bytes))?;
+                }
+            }
+        }
+        map.end()
+    }
+}
+
+/// A deserialized state is kept in memory: it's moved to the backend with [`StateMap::extend`].
+impl<'de, K: DataKey + Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de> for StateMap<K, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let memory = HashMap::deserialize(deserializer)?;
+        Ok(Self {
+            memory,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A backend keeping the serialized values in memory.
+    #[derive(Default)]
+    struct MemoryBackend(HashMap<Vec<u8>, Vec<u8>>);
+
+    impl StateBackend for MemoryBackend {
+        fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+            self.0.get(key).cloned()
+        }
+
+        fn put(&mut self, key: &[u8], value: &[u8]) {
+            self.0.insert(key.to_vec(), value.to_vec());
+        }
+
+        fn delete(&mut self, key: &[u8]) {
+            self.0.remove(key);
+        }
+
+        fn clear(&mut self) {
+            self.0.clear();
+        }
+    }
+
+    fn check_state_map(map: &mut StateMap<u32, Vec<u32>>) {
+        for i in 0..10 {
+            map.update(i % 3, Vec::new, |values| values.push(i));
+        }
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.with(&1, |values| values.clone()), Some(vec![1, 4, 7]));
+        assert_eq!(map.with(&5, |values| values.clone()), None);
+        assert_eq!(map.remove(&5), None);
+        map.update(5, Vec::new, |values| values.push(5));
+        assert_eq!(map.remove(&5), Some(vec![5]));
+        assert_eq!(map.with(&5, |values| values.clone()), None);
+        assert_eq!(map.len(), 3);
+
+        let restored: StateMap<u32, Vec<u32>> = decode(&encode(&*map));
+        let mut entries = map.drain().collect::<Vec<_>>();
+        entries.sort_unstable();
+        assert_eq!(entries[0], (0, vec![0, 3, 6, 9]));
+        assert_eq!(entries[2], (2, vec![2, 5, 8]));
+        assert!(map.is_empty());
+
+        map.extend(restored.memory);
+        assert_eq!(map.with(&2, |values| values.len()), Some(3));
+        // the values of the keys already present are replaced
+        map.extend([(2, vec![42])]);
+        assert_eq!(map.with(&2, |values| values.clone()), Some(vec![42]));
+        // the entries not consumed are removed too
+        assert!(map.drain().next().is_some());
+        assert!(map.is_empty());
+        map.extend([(0, vec![0])]);
+        assert_eq!(map.with(&0, |values| values.clone()), Some(vec![0]));
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    fn check_list_map(map: &mut StateMap<u32, Vec<u32>>) {
+        check_state_map(map);
+        for i in 0..10 {
+            map.push(i % 3, i);
+        }
+        assert_eq!(map.with(&1, |values| values.clone()), Some(vec![1, 4, 7]));
+        map.update(1, Vec::new, |values| values.push(10));
+        map.push(1, 11);
+        assert_eq!(map.remove(&1), Some(vec![1, 4, 7, 10, 11]));
+        let mut entries = map.drain().collect::<Vec<_>>();
+        entries.sort_unstable();
+        assert_eq!(entries, vec![(0, vec![0, 3, 6, 9]), (2, vec![2, 5, 8])]);
+    }
+
+    #[test]
+    fn state_map_memory() {
+        check_state_map(&mut StateMap::default().persistent());
+    }
+
+    #[test]
+    fn state_map_backend() {
+        let mut map = StateMap::default().persistent();
+        map.backend = Some((Box::new(MemoryBackend::default()), Default::default()));
+        check_state_map(&mut map);
+    }
+
+    #[test]
+    fn state_map_list() {
+        check_list_map(&mut StateMap::default().list());
+        let mut map = StateMap::default().list();
+        map.backend = Some((Box::new(MemoryBackend::default()), Default::default()));
+        check_list_map(&mut map);
+    }
+
+    #[test]
+    fn state_map_spill() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = MemoryBudgetConfig::new(4096, dir.path());
+        let budget = Arc::new(MemoryBudget::new(config, Coord::default()));
+        let mut map = StateMap::default().persistent();
+        map.budget = Some(budget.tracker(true));
+        for i in 0..1000 {
+            map.update(i, Vec::new, |values| values.push(i));
+        }
+        // the values exceeded the budget and they were moved to disk
+        assert!(map.spilled);
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.with(&42, |values| values.clone()), Some(vec![42]));
+        // an updated value is moved back to memory
+        map.update(42, Vec::new, |values| values.push(0));
+        assert!(map.memory.contains_key(&42));
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.with(&42, |values| values.clone()), Some(vec![42, 0]));
+        assert_eq!(map.drain().count(), 1000);
+        assert!(!map.spilled);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        check_state_map(&mut map);
+    }
+
+    #[test]
+    fn state_map_spill_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = MemoryBudgetConfig::new(4096, dir.path());
+        let budget = Arc::new(MemoryBudget::new(config, Coord::default()));
+        let mut map = StateMap::default().persistent().list();
+        map.budget = Some(budget.tracker(true));
+        for i in 0..1000u64 {
+            map.push(i, i);
+        }
+        assert!(map.spilled);
+        let file = std::fs::read_dir(dir.path()).unwrap().next().unwrap();
+        let file = file.unwrap().path();
+        let len = std::fs::metadata(&file).unwrap().len();
+        for i in 0..1000u64 {
+            map.push(42, i);
+        }
+        // the items are appended to the spilled list, without writing it again each time
+        assert!(std::fs::metadata(&file).unwrap().len() - len < 20 * 1000);
+        assert!(!map.memory.contains_key(&42));
+        assert_eq!(map.with(&42, |items| items.len()), Some(1001));
+        assert_eq!(
+            map.drain().map(|(_, items)| items.len()).sum::<usize>(),
+            2000
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "rocksdb")]
+    fn state_map_rocksdb() {
+        let path = std::env::temp_dir().join(format!("renoir-state-{}", std::process::id()));
+        let mut map = StateMap::default().persistent();
+        map.backend = Some((Box::new(RocksDbBackend::open(&path)), Default::default()));
+        check_state_map(&mut map);
+        drop(map);
+        assert!(!path.exists());
+
+        let mut map = StateMap::default().list();
+        map.backend = Some((Box::new(RocksDbBackend::open(&path)), Default::default()));
+        check_list_map(&mut map);
+    }
+}
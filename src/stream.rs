@@ -6,6 +6,7 @@ use std::sync::Arc;
 use crate::block::{BatchMode, Block, NextStrategy, Scheduling};
 use crate::environment::StreamContextInner;
 use crate::operator::end::End;
+use crate::operator::instrumented::Instrumented;
 use crate::operator::iteration::IterationStateLock;
 use crate::operator::source::Source;
 use crate::operator::window::WindowDescription;
@@ -115,9 +116,22 @@ where
     /// the new chain of operators. The new chain cannot be simply passed as argument since it is
     /// required to do a partial move of the `InnerBlock` structure.
     ///
+    /// The items emitted by the operator are counted for the metrics endpoint, see
+    /// [`RuntimeConfig::with_metrics_port`](crate::RuntimeConfig::with_metrics_port).
+    ///
     /// **Note**: this is an advanced function that manipulates the block structure. Probably it is
     /// not what you are looking for.
-    pub fn add_operator<Op2, GetOp>(self, get_operator: GetOp) -> Stream<Op2>
+    pub fn add_operator<Op2, GetOp>(self, get_operator: GetOp) -> Stream<Instrumented<Op2>>
+    where
+        Op2: Operator,
+        GetOp: FnOnce(Op) -> Op2,
+    {
+        self.add_uncounted_operator(|prev| Instrumented::new(get_operator(prev)))
+    }
+
+    /// Like [`Stream::add_operator`], but the items emitted by the operator are not counted. It's
+    /// used by the operators whose type is part of the API, e.g. [`Stream::into_boxed`].
+    pub(crate) fn add_uncounted_operator<Op2, GetOp>(self, get_operator: GetOp) -> Stream<Op2>
     where
         Op2: Operator,
         GetOp: FnOnce(Op) -> Op2,
@@ -157,8 +171,15 @@ where
         let mut env_lock = ctx.lock();
         let prev_id = env_lock.close_block(block);
         // Create new block
+        // any replica may process any item, but inside an iteration the items of the next round
+        // may follow the end of the stream
+        let work_stealing = matches!(
+            next_strategy,
+            NextStrategy::Random | NextStrategy::RoundRobin(_)
+        ) && iteration_ctx.is_empty();
         let source = Start::single(prev_id, iteration_ctx.last().cloned());
-        let new_block = env_lock.new_block(source, batch_mode, iteration_ctx);
+        let mut new_block = env_lock.new_block(source, batch_mode, iteration_ctx);
+        new_block.work_stealing = work_stealing;
         // Connect blocks
         env_lock.connect_blocks::<Op::Out>(prev_id, new_block.id);
 
@@ -292,7 +313,10 @@ where
     OperatorChain: Operator + 'static,
     OperatorChain::Out: KeyedItem,
 {
-    pub(crate) fn add_operator<Op2, GetOp>(self, get_operator: GetOp) -> KeyedStream<Op2>
+    pub(crate) fn add_operator<Op2, GetOp>(
+        self,
+        get_operator: GetOp,
+    ) -> KeyedStream<Instrumented<Op2>>
     where
         Op2: Operator,
         GetOp: FnOnce(OperatorChain) -> Op2,
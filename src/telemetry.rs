@@ -0,0 +1,95 @@
+//! Export of the spans of the execution to an OpenTelemetry collector.
+//!
+//! The workers record their spans with [`tracing`]: a span for each replica, with the coordinates
+//! of the replica as attributes, and inside it the spans of the batches it produces and of the
+//! messages it sends to the other hosts. Each operator has a span for each batch too, labeled with
+//! its position in the block and its `name`, that contains the spans of the operators before it.
+//!
+//! With an OTLP endpoint in the [`RuntimeConfig`](crate::RuntimeConfig) each host installs a
+//! global subscriber that exports them, so the spans of all the hosts of a distributed execution end up in the same collector,
+//! where they can be correlated by their `block_id`, `host_id` and `replica_id`.
+//!
+//! If the application needs its own subscriber, it can skip the endpoint in the config and add
+//! the layer returned by [`otlp_layer`] to it.
+
+use std::sync::OnceLock;
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::scheduler::HostId;
+
+/// The provider of the spans exported by this process, to flush them at the end of the execution.
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+#[derive(Debug, thiserror::Error)]
+pub enum TelemetryError {
+    #[error("cannot build the OTLP exporter: {0}")]
+    Exporter(#[from] opentelemetry_otlp::ExporterBuildError),
+
+    #[error("cannot install the tracing subscriber: {0}")]
+    Subscriber(#[from] tracing_subscriber::util::TryInitError),
+}
+
+/// Build a layer that exports the spans to the OTLP `endpoint`, as the host `host_id`.
+///
+/// The spans are exported in batches by a background thread, they are flushed at the end of each
+/// execution.
+pub fn otlp_layer<S>(
+    endpoint: &str,
+    host_id: HostId,
+) -> Result<impl tracing_subscriber::Layer<S>, TelemetryError>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+    let resource = Resource::builder()
+        .with_service_name("renoir")
+        .with_attribute(KeyValue::new("renoir.host_id", host_id as i64))
+        .build();
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build();
+    let tracer = provider.tracer("renoir");
+    let _ = PROVIDER.set(provider);
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Install a global subscriber that exports the spans to the OTLP `endpoint`.
+///
+/// This fails if the process has already a global subscriber.
+pub fn init_otlp(endpoint: &str, host_id: HostId) -> Result<(), TelemetryError> {
+    tracing_subscriber::registry()
+        .with(otlp_layer(endpoint, host_id)?)
+        .try_init()?;
+    Ok(())
+}
+
+/// Install the exporter of the config, once per process.
+pub(crate) fn init_from_config(endpoint: &str, host_id: HostId) {
+    static INIT: OnceLock<()> = OnceLock::new();
+    INIT.get_or_init(|| {
+        if let Err(e) = init_otlp(endpoint, host_id) {
+            log::error!("cannot export the spans to {endpoint}: {e}");
+        }
+    });
+}
+
+/// Export the spans that are still buffered.
+pub(crate) fn flush() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.force_flush() {
+            log::warn!("cannot flush the spans: {e}");
+        }
+    }
+}
@@ -9,6 +9,7 @@ use crate::network::{Coord, NetworkSender, NetworkTopology, ReceiverEndpoint};
 use crate::operator::source::Source;
 use crate::operator::{Data, ExchangeData, Operator, StreamElement};
 use crate::scheduler::ExecutionMetadata;
+use crate::state::StateBackendFactory;
 use crate::CoordUInt;
 use crate::{BatchMode, RuntimeConfig};
 
@@ -116,6 +117,12 @@ impl<T: ExchangeData> FakeNetworkTopology<T> {
             prev: self.prev.clone(),
             network: &mut self.topology,
             batch_mode: BatchMode::adaptive(100, Duration::from_millis(100)),
+            watermark_idle_timeout: None,
+            checkpoint: None,
+            state_backend: StateBackendFactory::new(Default::default(), None, dest),
+            placement: None,
+            cancellation: Default::default(),
+            wake_up: Default::default(),
         }
     }
 
@@ -1,11 +1,21 @@
 use std::cell::RefCell;
+use std::sync::Arc;
 use std::thread::JoinHandle;
 
+use tracing::span::EnteredSpan;
+
+use crate::affinity::pin_current_thread;
 use crate::block::{Block, BlockStructure};
+use crate::checkpoint::ReplicaCheckpoint;
+use crate::config::HOST_ID_ENV_VAR;
 use crate::network::Coord;
 use crate::operator::{Operator, StreamElement};
+use crate::profiler::metrics;
 use crate::scheduler::ExecutionMetadata;
 
+/// The exit code of a remote worker process when one of its workers crashes.
+const WORKER_CRASH_EXIT_CODE: i32 = 101;
+
 thread_local! {
     /// Coordinates of the replica the current worker thread is working on.
     ///
@@ -54,40 +64,142 @@ impl<F: FnOnce()> Drop for CatchPanic<F> {
     }
 }
 
-pub(crate) fn spawn_worker<OperatorChain>(
+/// The span of the elements produced by a replica between two flushes of its batches.
+struct BatchSpan {
+    coord: Coord,
+    span: Option<EnteredSpan>,
+    items: u64,
+}
+
+impl BatchSpan {
+    fn new(coord: Coord) -> Self {
+        Self {
+            coord,
+            span: None,
+            items: 0,
+        }
+    }
+
+    /// Enter the span before asking the next element, if the batch has just started.
+    fn enter(&mut self) {
+        if self.span.is_none() {
+            let span = tracing::debug_span!(
+                "batch",
+                block_id = self.coord.block_id,
+                host_id = self.coord.host_id,
+                replica_id = self.coord.replica_id,
+                items = tracing::field::Empty,
+            );
+            self.span = Some(span.entered());
+        }
+    }
+
+    /// Count the element produced, closing the span if it ends the batch.
+    fn exit<T>(&mut self, element: &StreamElement<T>) {
+        match element {
+            StreamElement::Item(_) | StreamElement::Timestamped(_, _) => self.items += 1,
+            StreamElement::FlushBatch
+            | StreamElement::FlushAndRestart
+            | StreamElement::Terminate => {
+                if let Some(span) = self.span.take() {
+                    span.record("items", self.items);
+                }
+                self.items = 0;
+            }
+            StreamElement::Watermark(_) => {}
+        }
+    }
+}
+
+/// Starts the worker thread of a replica, returning its handle.
+pub(crate) type StartWorkerFn = Box<dyn FnOnce() -> JoinHandle<()>>;
+
+/// Set up the operators of a replica, returning the structure of its block and the function that
+/// starts its worker thread.
+pub(crate) fn setup_worker<OperatorChain>(
     mut block: Block<OperatorChain>,
     metadata: &mut ExecutionMetadata,
-) -> (JoinHandle<()>, BlockStructure)
+) -> (BlockStructure, StartWorkerFn)
 where
     OperatorChain: Operator + 'static,
     OperatorChain::Out: Send,
 {
     let coord = metadata.coord;
 
-    debug!("starting worker {}: {}", coord, block.to_string(),);
+    debug!("setting up worker {}: {}", coord, block.to_string(),);
 
     block.operators.setup(metadata);
     let structure = block.operators.structure();
+    let checkpoint = metadata.checkpoint.clone();
+    let placement = metadata.placement;
+
+    let start = move || {
+        debug!("starting worker {}", coord);
+        // the replicas run inside the runtime of the job, so that the operators can spawn tasks
+        // on it
+        #[cfg(feature = "tokio")]
+        let runtime = tokio::runtime::Handle::try_current().ok();
 
-    let join_handle = std::thread::Builder::new()
-        .name(format!("block-{}", block.id))
-        .spawn(move || {
-            // remember in the thread-local the coordinate of this block
-            COORD.with(|x| *x.borrow_mut() = Some(coord));
-            do_work(block, coord)
-        })
-        .unwrap();
+        std::thread::Builder::new()
+            .name(format!("block-{}", block.id))
+            .spawn(move || {
+                // remember in the thread-local the coordinate of this block
+                COORD.with(|x| *x.borrow_mut() = Some(coord));
+                #[cfg(feature = "tokio")]
+                let _runtime = runtime.as_ref().map(|runtime| runtime.enter());
+                if let Some(placement) = placement {
+                    pin_current_thread(placement);
+                }
+                do_work(block, coord, checkpoint)
+            })
+            .unwrap()
+    };
 
-    (join_handle, structure)
+    (structure, Box::new(start))
 }
 
-fn do_work<Op: Operator>(mut block: Block<Op>, coord: Coord) {
+fn do_work<Op: Operator>(
+    mut block: Block<Op>,
+    coord: Coord,
+    checkpoint: Option<Arc<ReplicaCheckpoint>>,
+) {
     let mut catch_panic = CatchPanic::new(|| {
         error!("worker {} crashed!", coord);
+        metrics::replica_ended(coord, true);
+        // the other replicas would wait forever for this one: exit, so that the spawner notices
+        // the failure and stops (or restarts) the job
+        if std::env::var_os(HOST_ID_ENV_VAR).is_some() {
+            std::process::exit(WORKER_CRASH_EXIT_CODE);
+        }
     });
-    while !matches!(block.operators.next(), StreamElement::Terminate) {
-        // nothing to do
+    let _span = tracing::info_span!(
+        "replica",
+        block_id = coord.block_id,
+        host_id = coord.host_id,
+        replica_id = coord.replica_id,
+    )
+    .entered();
+    let mut batch = BatchSpan::new(coord);
+    loop {
+        batch.enter();
+        let element = block.operators.next();
+        batch.exit(&element);
+        match element {
+            StreamElement::Terminate => break,
+            // the barrier of the checkpoint in progress has gone through the whole block
+            StreamElement::FlushBatch => {
+                if let Some(checkpoint) = &checkpoint {
+                    checkpoint.complete();
+                }
+            }
+            _ => {}
+        }
+    }
+    // the block may have ended while taking a snapshot
+    if let Some(checkpoint) = &checkpoint {
+        checkpoint.finish();
     }
+    metrics::replica_ended(coord, false);
     catch_panic.defuse();
     info!("worker {} completed", coord);
 }
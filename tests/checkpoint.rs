@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use renoir::config::CheckpointConfig;
+use renoir::operator::window::CountWindow;
+use renoir::{RuntimeConfig, StreamContext};
+
+const ITEMS: u64 = 10_000;
+
+/// Sum the items by their remainder modulo 10 with `group_by_fold`.
+///
+/// If `savepoint` is set, a savepoint is taken when half of the items have been read: the source
+/// slows down until it's completed, and its id is returned.
+fn sum_by_key(
+    parallelism: u64,
+    config: CheckpointConfig,
+    savepoint: bool,
+) -> (HashMap<u64, u64>, Option<u64>) {
+    let config = RuntimeConfig::local(parallelism)
+        .unwrap()
+        .with_checkpoints(config);
+    let env = StreamContext::new(config);
+    let trigger = env.savepoint_trigger();
+    let taken = Arc::new(AtomicU64::new(0));
+    let done = Arc::new(AtomicBool::new(!savepoint));
+    let (taken2, done2) = (taken.clone(), done.clone());
+    let items = (0..ITEMS).inspect(move |&i| {
+        if i == ITEMS / 2 {
+            let (trigger, taken, done) = (trigger.clone(), taken2.clone(), done2.clone());
+            std::thread::spawn(move || {
+                taken.store(trigger.savepoint().unwrap_or(0), Ordering::Release);
+                done.store(true, Ordering::Release);
+            });
+        }
+        // leave the time to take the savepoint while the job is running
+        if i >= ITEMS / 2 && !done2.load(Ordering::Acquire) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    });
+    let res = env
+        .stream_iter(items)
+        .shuffle()
+        .group_by_fold(|n| n % 10, 0, |acc, n| *acc += n, |acc, n| *acc += n)
+        .collect_vec();
+    env.execute_blocking();
+
+    let res = res.get().unwrap().into_iter().collect();
+    let taken = taken.load(Ordering::Acquire);
+    (res, (taken != 0).then_some(taken))
+}
+
+#[test]
+fn restore_group_by_fold_with_different_parallelism() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = CheckpointConfig::new(dir.path()).interval(Duration::from_secs(3600));
+    let mut expected = HashMap::new();
+    for n in 0..ITEMS {
+        *expected.entry(n % 10).or_default() += n;
+    }
+
+    let (res, savepoint) = sum_by_key(2, config.clone(), true);
+    assert_eq!(res, expected);
+    let savepoint = savepoint.expect("the savepoint was not taken");
+
+    // the partial sums of the replicas of the shuffle are sent before the barriers, and the sums of
+    // the keys are redistributed to the new replicas
+    let (res, _) = sum_by_key(3, config.restore_from(savepoint), false);
+    assert_eq!(res, expected);
+}
+
+#[test]
+#[should_panic(expected = "The checkpoints cannot save the state of the operator WindowCount")]
+fn refuse_operators_without_saved_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = RuntimeConfig::local(2)
+        .unwrap()
+        .with_checkpoints(CheckpointConfig::new(dir.path()));
+    let env = StreamContext::new(config);
+    // the open windows would be lost when the job is restored
+    env.stream_iter(0..100u64)
+        .group_by(|n| n % 10)
+        .window(CountWindow::tumbling(3))
+        .count()
+        .for_each(std::mem::drop);
+    env.execute_blocking();
+}
@@ -0,0 +1,32 @@
+use utils::TestHelper;
+
+mod utils;
+
+#[test]
+fn co_group() {
+    TestHelper::local_remote_env(|env| {
+        let n = 100u32;
+        let left = env.stream_iter(0..n).group_by(|x| x % 7);
+        let right = env.stream_iter(0..n / 2).shuffle().group_by(|x| x % 11);
+        let res = left
+            .co_group(right, |_, mut l, mut r| {
+                l.sort_unstable();
+                r.sort_unstable();
+                (l, r)
+            })
+            .collect_vec();
+        env.execute_blocking();
+
+        if let Some(mut res) = res.get() {
+            res.sort_unstable();
+            let expected = (0..11)
+                .map(|k| {
+                    let l = (0..n).filter(|x| k < 7 && x % 7 == k).collect::<Vec<_>>();
+                    let r = (0..n / 2).filter(|x| x % 11 == k).collect::<Vec<_>>();
+                    (k, (l, r))
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(res, expected);
+        }
+    });
+}
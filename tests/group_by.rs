@@ -24,3 +24,56 @@ fn group_by_stream() {
         }
     });
 }
+
+renoir::composite_key! {
+    struct DigitsKey {
+        tens: u8,
+        units: u8,
+    }
+}
+
+renoir::composite_key! {
+    struct ParityKey(bool, bool);
+}
+
+#[test]
+fn group_by_composite_key() {
+    TestHelper::local_remote_env(|env| {
+        let source = IteratorSource::new(0..100u8);
+        let res = env
+            .stream(source)
+            .group_by(|&n| DigitsKey::from((n / 10 % 2, n % 2)))
+            .fold(0usize, |count, _| *count += 1)
+            .map(|(key, count)| (ParityKey(key.tens == 0, key.units == 0), count))
+            .drop_key()
+            .collect_vec();
+        env.execute_blocking();
+        if let Some(res) = res.get() {
+            let res = res.into_iter().sorted().collect_vec();
+            let expected = [(false, false), (false, true), (true, false), (true, true)]
+                .into_iter()
+                .map(|(a, b)| (ParityKey(a, b), 25))
+                .collect_vec();
+            assert_eq!(res, expected);
+        }
+    });
+}
+
+#[test]
+fn rekey_stream() {
+    TestHelper::local_remote_env(|env| {
+        let source = IteratorSource::new(0..100u8);
+        let res = env
+            .stream(source)
+            .group_by(|&n| n % 10)
+            .rekey(|&units, &n| (units % 2, n / 50))
+            .fold(0usize, |count, _| *count += 1)
+            .collect_vec();
+        env.execute_blocking();
+        if let Some(res) = res.get() {
+            let res = res.into_iter().sorted().collect_vec();
+            let expected = vec![((0, 0), 25), ((0, 1), 25), ((1, 0), 25), ((1, 1), 25)];
+            assert_eq!(res, expected);
+        }
+    });
+}
@@ -1,5 +1,6 @@
 use itertools::Itertools;
 
+use renoir::operator::iteration::IterationOptions;
 use renoir::operator::source::IteratorSource;
 
 use super::utils::TestHelper;
@@ -168,3 +169,32 @@ fn test_iterate_primes() {
         }
     });
 }
+
+#[test]
+fn test_iterate_until_converged() {
+    TestHelper::local_remote_env(|env| {
+        let n = 1000u64;
+
+        let source = IteratorSource::new(0..n);
+        let (state, res) = env.stream(source).shuffle().iterate_with_options(
+            0u64,
+            |s, _| s.map(|x| x / 2),
+            |delta: &mut u64, x| *delta += x,
+            |old_state, delta| *old_state += delta,
+            |_state| true,
+            // the items are halved until they are all zero and the state stops changing
+            IterationOptions::default().until_converged(|old, new| old == new),
+        );
+        let state = state.collect_vec();
+        let res = res.collect_vec();
+        env.execute_blocking();
+
+        if let Some(state) = state.get() {
+            let expected: u64 = (1..=10).map(|i| (0..n).map(|x| x >> i).sum::<u64>()).sum();
+            assert_eq!(state, vec![expected]);
+        }
+        if let Some(res) = res.get() {
+            assert_eq!(res, vec![0; n as usize]);
+        }
+    });
+}
@@ -2,3 +2,4 @@ use super::utils;
 
 mod iterate;
 mod replay;
+mod workset;
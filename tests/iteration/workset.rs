@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use renoir::operator::source::IteratorSource;
+
+use super::utils::TestHelper;
+
+#[test]
+fn test_workset_connected_components() {
+    TestHelper::local_remote_env(|env| {
+        // two chains, 0 - 1 - ... - 9 and 10 - 11 - ... - 19, and an isolated node 20
+        let edges = (0..9u64)
+            .chain(10..19)
+            .flat_map(|x| [(x, x + 1), (x + 1, x)])
+            .collect_vec();
+        let mut adjacency: HashMap<u64, Vec<u64>> = HashMap::new();
+        for (x, y) in edges {
+            adjacency.entry(x).or_default().push(y);
+        }
+        let adjacency = Arc::new(adjacency);
+
+        let source = IteratorSource::new(0..21u64);
+        let res = env
+            .stream(source)
+            .shuffle()
+            .group_by(|&x| x)
+            .workset_iterate(
+                100,
+                |_node, component, candidate| {
+                    let smaller = candidate < *component;
+                    *component = (*component).min(candidate);
+                    smaller
+                },
+                move |workset| {
+                    workset
+                        .unkey()
+                        .flat_map(move |(node, component)| {
+                            adjacency
+                                .get(&node)
+                                .into_iter()
+                                .flatten()
+                                .map(|&neighbour| (neighbour, component))
+                                .collect_vec()
+                        })
+                        .group_by_reduce(|&(node, _)| node, |a, b| a.1 = a.1.min(b.1))
+                        .map(|(_, (_, component))| component)
+                },
+            )
+            .collect_vec();
+        env.execute_blocking();
+
+        if let Some(mut res) = res.get() {
+            res.sort_unstable();
+            let expected = (0..21u64)
+                .map(|x| {
+                    (
+                        x,
+                        if x < 10 {
+                            0
+                        } else if x < 20 {
+                            10
+                        } else {
+                            20
+                        },
+                    )
+                })
+                .collect_vec();
+            assert_eq!(res, expected);
+        }
+    });
+}
+
+#[test]
+fn test_workset_max_iterations() {
+    TestHelper::local_remote_env(|env| {
+        let source = IteratorSource::new(0..1u64);
+        // a chain that grows by one node at each iteration
+        let res = env
+            .stream(source)
+            .shuffle()
+            .group_by(|&x| x)
+            .workset_iterate(
+                5,
+                |_, _, _| false,
+                |workset| {
+                    workset
+                        .unkey()
+                        .map(|(node, _)| (node + 1, node + 1))
+                        .group_by(|&(node, _)| node)
+                        .map(|(_, (_, value))| value)
+                },
+            )
+            .collect_vec();
+        env.execute_blocking();
+
+        if let Some(mut res) = res.get() {
+            res.sort_unstable();
+            assert_eq!(res, (0..=5).map(|x| (x, x)).collect_vec());
+        }
+    });
+}
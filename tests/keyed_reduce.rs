@@ -43,3 +43,22 @@ fn reduce_keyed_stream() {
         }
     });
 }
+
+#[test]
+fn reduce_init_keyed_stream() {
+    TestHelper::local_remote_env(|env| {
+        let source = IteratorSource::new(0..10u32);
+        let res = env
+            .stream(source)
+            .group_by(|n| n % 2)
+            .reduce_init(|k| format!("{k}:"), |acc, y| *acc += &y.to_string())
+            .collect_vec();
+        env.execute_blocking();
+        if let Some(res) = res.get() {
+            let res = res.into_iter().sorted().collect_vec();
+            assert_eq!(res.len(), 2);
+            assert_eq!(res[0], (0, "0:02468".into()));
+            assert_eq!(res[1], (1, "1:13579".into()));
+        }
+    });
+}
@@ -0,0 +1,68 @@
+use itertools::Itertools;
+
+use renoir::operator::source::IteratorSource;
+use renoir::operator::{ListState, MapState, ValueState};
+use utils::TestHelper;
+
+mod utils;
+
+#[test]
+fn rich_map_with_state_value() {
+    TestHelper::local_remote_env(|env| {
+        let source = IteratorSource::new(0..10u8);
+        let res = env
+            .stream(source)
+            .group_by(|n| n % 2)
+            .rich_map_with_state(|(_k, _v), count: &mut ValueState<u32>| {
+                let count = count.get_or_insert_with(|| 0);
+                *count += 1;
+                *count
+            })
+            .collect_vec();
+        env.execute_blocking();
+        if let Some(res) = res.get() {
+            let res = res.into_iter().sorted().collect_vec();
+            let expected = (0..=1)
+                .flat_map(|k| (1..=5).map(move |v| (k, v)))
+                .sorted()
+                .collect_vec();
+            assert_eq!(res, expected);
+        }
+    });
+}
+
+#[test]
+fn rich_map_with_state_list_and_map() {
+    TestHelper::local_remote_env(|env| {
+        let source = IteratorSource::new(0..20u32);
+        let res = env
+            .stream(source)
+            .group_by(|n| n % 2)
+            .rich_filter_map_with_state(
+                |(_k, v), (window, seen): &mut (ListState<u32>, MapState<u32, ()>)| {
+                    window.add(v);
+                    seen.put(v % 3, ());
+                    if window.len() == 3 {
+                        let sum = window.take().into_iter().sum::<u32>();
+                        Some((sum, seen.len()))
+                    } else {
+                        None
+                    }
+                },
+            )
+            .collect_vec();
+        env.execute_blocking();
+        if let Some(res) = res.get() {
+            let res = res.into_iter().sorted().collect_vec();
+            let expected = vec![
+                (0, (6, 3)),
+                (0, (24, 3)),
+                (0, (42, 3)),
+                (1, (9, 3)),
+                (1, (27, 3)),
+                (1, (45, 3)),
+            ];
+            assert_eq!(res, expected);
+        }
+    });
+}
@@ -142,3 +142,38 @@ fn merge_keyed_stream() {
         }
     });
 }
+
+renoir::merge_variants! {
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    enum Variant {
+        Number(u16),
+        Text(String),
+        Flag(bool),
+    }
+}
+
+#[test]
+fn merge_variants() {
+    TestHelper::local_remote_env(|env| {
+        let numbers = env.stream(IteratorSource::new(0..100u16));
+        let texts = env.stream(IteratorSource::new((0..10).map(|i| i.to_string())));
+        let flags = env.stream(IteratorSource::new([true, false].into_iter()));
+
+        let res = renoir::merge_variants!(Variant {
+            Number: numbers,
+            Text: texts,
+            Flag: flags,
+        })
+        .collect_vec();
+        env.execute_blocking();
+        if let Some(res) = res.get() {
+            let res_sorted = res.into_iter().sorted().collect_vec();
+            let expected = (0..100)
+                .map(Variant::Number)
+                .chain((0..10).map(|i| Variant::Text(i.to_string())))
+                .chain([Variant::Flag(false), Variant::Flag(true)])
+                .collect_vec();
+            assert_eq!(res_sorted, expected);
+        }
+    });
+}
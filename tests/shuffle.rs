@@ -1,6 +1,10 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use itertools::Itertools;
 
 use renoir::operator::source::IteratorSource;
+use renoir::{BatchMode, RuntimeConfig};
 use utils::TestHelper;
 
 mod utils;
@@ -32,3 +36,76 @@ fn shuffle_stream() {
         }
     });
 }
+
+#[test]
+fn rebalance_stream() {
+    TestHelper::local_remote_env(|env| {
+        let source = IteratorSource::new(0..1000u16);
+        let res = env
+            .stream(source)
+            .rebalance()
+            .map(|x| x * 2)
+            .rebalance()
+            .collect_vec();
+        env.execute_blocking();
+        if let Some(res) = res.get() {
+            let res_sorted = res.into_iter().sorted().collect_vec();
+            let expected = (0..1000u16).map(|x| x * 2).collect_vec();
+            assert_eq!(res_sorted, expected);
+        }
+    });
+}
+
+#[test]
+fn rebalance_balanced() {
+    TestHelper::local_remote_env(|env| {
+        let source = IteratorSource::new(0..1000u32);
+        let parallelism = env.parallelism() as usize;
+        let res = env
+            .stream(source)
+            .rebalance()
+            // each replica numbers the items it receives
+            .rich_map({
+                let mut received = 0;
+                move |_| {
+                    received += 1;
+                    received
+                }
+            })
+            .collect_vec();
+        env.execute_blocking();
+        if let Some(res) = res.get() {
+            let replicas = res.iter().filter(|&&n| n == 1).count();
+            assert_eq!(replicas, parallelism);
+            // the only sender gives the same number of items (±1) to all the replicas
+            let max = res.into_iter().max().unwrap();
+            assert_eq!(max, 1000usize.div_ceil(parallelism));
+        }
+    });
+}
+
+#[test]
+fn rebalance_work_stealing() {
+    let config = RuntimeConfig::local(4).unwrap().with_work_stealing(true);
+    TestHelper::env_with_config(
+        config,
+        Arc::new(|env| {
+            let source = IteratorSource::new(0..1000u32);
+            let res = env
+                .stream(source)
+                .batch_mode(BatchMode::fixed(10))
+                .rebalance()
+                .map(|x| {
+                    // the batches with a multiple of 100 are slow, the other replicas steal
+                    if x % 100 == 0 {
+                        std::thread::sleep(Duration::from_millis(20));
+                    }
+                    x
+                })
+                .collect_vec();
+            env.execute_blocking();
+            let res = res.get().unwrap().into_iter().sorted().collect_vec();
+            assert_eq!(res, (0..1000).collect_vec());
+        }),
+    );
+}
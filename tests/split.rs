@@ -53,3 +53,30 @@ fn double_split_stream() {
         }
     });
 }
+
+#[test]
+fn split_by_stream() {
+    TestHelper::local_remote_env(|env| {
+        let source = IteratorSource::new(0..10u8);
+        let (even, odd) = env.stream(source).shuffle().split_by(|n| {
+            if n % 2 == 0 {
+                Ok(n / 2)
+            } else {
+                Err(n.to_string())
+            }
+        });
+        let even = even.collect_vec();
+        let odd = odd.collect_vec();
+        env.execute_blocking();
+
+        if let Some(even) = even.get() {
+            assert_eq!(even.into_iter().sorted().collect_vec(), &[0, 1, 2, 3, 4]);
+        }
+        if let Some(odd) = odd.get() {
+            assert_eq!(
+                odd.into_iter().sorted().collect_vec(),
+                &["1", "3", "5", "7", "9"]
+            );
+        }
+    });
+}
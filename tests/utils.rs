@@ -155,13 +155,7 @@ impl TestHelper {
             let high_part = (test_id & 0xff00) >> 8;
             let low_part = test_id & 0xff;
             let address = format!("127.{high_part}.{low_part}.{host_id}");
-            hosts.push(HostConfig {
-                address,
-                base_port: TEST_BASE_PORT,
-                num_cores: cores_per_host,
-                ssh: Default::default(),
-                perf_path: None,
-            });
+            hosts.push(HostConfig::new(address, TEST_BASE_PORT, cores_per_host));
         }
 
         let mut join_handles = vec![];
@@ -59,3 +59,68 @@ fn tumbling_event_time() {
         }
     });
 }
+
+#[test]
+fn split_late_event_time() {
+    TestHelper::local_remote_env(|env| {
+        let source = IteratorSource::new(vec![0, 1, 2, 10, 3, 11, 4, 12, 20].into_iter());
+
+        let (windows, late) = env
+            .stream(source)
+            .add_timestamps(|&x| x, |_, &ts| Some(ts))
+            .group_by(|x| x % 2)
+            .window(EventTimeWindow::tumbling(5))
+            .split_late();
+        let windows = windows.first().drop_key().collect_vec();
+        let late = late.drop_key().collect_vec();
+        env.execute_blocking();
+
+        if let Some(mut res) = windows.get() {
+            // Windows and elements
+            // 0 -> 5    [0, 2] and [1]
+            // 10 -> 15  [10, 12] and [11]
+            // 20 -> 25  [20] and []
+            res.sort_unstable();
+            assert_eq!(res, vec![0, 1, 10, 11, 20]);
+        }
+        if let Some(mut res) = late.get() {
+            res.sort_unstable();
+            assert_eq!(res, vec![3, 4]);
+        }
+    });
+}
+
+#[test]
+fn window_context_event_time() {
+    TestHelper::local_remote_env(|env| {
+        let source = IteratorSource::new(
+            vec![("a", 0), ("b", 0), ("a", 4), ("b", 7), ("a", 11)].into_iter(),
+        );
+
+        let res = env
+            .stream(source)
+            .add_timestamps(|&(_, ts)| ts, |_, &(_, ts)| Some(ts))
+            .group_by(|&(key, _)| key)
+            .window(EventTimeWindow::tumbling(5))
+            .fold_with_context((0, 0, 0), |w, (start, end, count), _| {
+                *start = w.start;
+                *end = w.end;
+                *count += 1;
+            })
+            .collect_vec();
+        env.execute_blocking();
+
+        if let Some(mut res) = res.get() {
+            res.sort_unstable();
+            assert_eq!(
+                res,
+                vec![
+                    ("a", (0, 5, 2)),
+                    ("a", (10, 15, 1)),
+                    ("b", (0, 5, 1)),
+                    ("b", (5, 10, 1)),
+                ]
+            );
+        }
+    });
+}
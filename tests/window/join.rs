@@ -142,3 +142,102 @@ fn session_window_join() {
         }
     });
 }
+
+#[test]
+fn stream_window_join() {
+    TestHelper::local_remote_env(|env| {
+        let source1 = IteratorSource::new(0..10);
+        let source2 = IteratorSource::new(0..10);
+
+        let stream1 = env
+            .stream(source1)
+            .add_timestamps(|&x| x, |&x, &ts| if x % 2 == 1 { Some(ts) } else { None })
+            .shuffle();
+        let stream2 = env
+            .stream(source2)
+            .add_timestamps(|&x| x, |&x, &ts| if x % 2 == 0 { Some(ts) } else { None })
+            .shuffle()
+            .map(|x| ('a'..='z').nth(x as usize).unwrap());
+
+        let res = stream1
+            .window_join(
+                stream2,
+                EventTimeWindow::tumbling(3),
+                |x| x % 2,
+                |c| (*c as i64 - 'a' as i64) % 2,
+            )
+            .collect_vec();
+        env.execute_blocking();
+
+        if let Some(mut res) = res.get() {
+            res.sort_unstable();
+
+            let windows = vec![
+                vec![0, 2],
+                vec![4],
+                vec![6, 8],
+                vec![1],
+                vec![3, 5],
+                vec![7],
+                vec![9],
+            ];
+
+            let mut expected = Vec::new();
+            for window in windows.into_iter() {
+                for &x in window.iter() {
+                    for &y in window.iter() {
+                        expected.push((x % 2, (x, ('a'..='z').nth(y as usize).unwrap())));
+                    }
+                }
+            }
+            expected.sort_unstable();
+
+            assert_eq!(res, expected);
+        }
+    });
+}
+
+#[test]
+fn window_co_group() {
+    TestHelper::local_remote_env(|env| {
+        let source1 = IteratorSource::new(0..10);
+        let source2 = IteratorSource::new(0..4);
+
+        let stream1 = env
+            .stream(source1)
+            .add_timestamps(|&x| x, |&x, &ts| if x % 2 == 1 { Some(ts) } else { None })
+            .shuffle()
+            .group_by(|x| x % 2);
+        let stream2 = env
+            .stream(source2)
+            .add_timestamps(|&x| x, |&x, &ts| if x % 2 == 0 { Some(ts) } else { None })
+            .shuffle()
+            .group_by(|x| x % 2);
+
+        let res = stream1
+            .window_co_group(EventTimeWindow::tumbling(3), stream2, |left, right| {
+                (left.len(), right.len())
+            })
+            .collect_vec();
+        env.execute_blocking();
+
+        if let Some(mut res) = res.get() {
+            res.sort_unstable();
+
+            let mut expected = vec![
+                // key 0: [0, 2], [4], [6, 8]
+                (0, (2, 2)),
+                (0, (1, 0)),
+                (0, (2, 0)),
+                // key 1: [1], [3, 5], [7], [9]
+                (1, (1, 1)),
+                (1, (2, 1)),
+                (1, (1, 0)),
+                (1, (1, 0)),
+            ];
+            expected.sort_unstable();
+
+            assert_eq!(res, expected);
+        }
+    });
+}
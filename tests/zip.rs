@@ -67,3 +67,26 @@ fn test_zip_unbalanced() {
         }
     });
 }
+
+#[test]
+fn test_zip_longest() {
+    TestHelper::local_remote_env(|env| {
+        let items1 = 0..5u8;
+        let items2 = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+
+        let source1 = IteratorSource::new(items1.clone());
+        let source2 = IteratorSource::new(items2.clone().into_iter());
+        let stream1 = env.stream(source1);
+        let stream2 = env.stream(source2);
+        let res = stream1.zip_longest(stream2).collect_vec();
+        env.execute_blocking();
+        if let Some(res) = res.get() {
+            let expected = items1
+                .into_iter()
+                .zip_longest(items2)
+                .map(|pair| pair.left_and_right())
+                .collect_vec();
+            assert_eq!(res, expected);
+        }
+    });
+}